@@ -147,6 +147,7 @@ impl<B: Backend> RendererState<B> {
                     count: 1,
                     stage_flags: pso::ShaderStageFlags::FRAGMENT,
                     immutable_samplers: false,
+                    flags: pso::DescriptorBindingFlags::empty(),
                 },
                 pso::DescriptorSetLayoutBinding {
                     binding: 1,
@@ -154,6 +155,7 @@ impl<B: Backend> RendererState<B> {
                     count: 1,
                     stage_flags: pso::ShaderStageFlags::FRAGMENT,
                     immutable_samplers: false,
+                    flags: pso::DescriptorBindingFlags::empty(),
                 },
             ],
         );
@@ -171,6 +173,7 @@ impl<B: Backend> RendererState<B> {
                 count: 1,
                 stage_flags: pso::ShaderStageFlags::FRAGMENT,
                 immutable_samplers: false,
+                flags: pso::DescriptorBindingFlags::empty(),
             }],
         );
 
@@ -668,6 +671,8 @@ impl<B: Backend> RenderPassState<B> {
                 inputs: &[],
                 resolves: &[],
                 preserves: &[],
+                depth_stencil_resolve: None,
+                shading_rate: None,
             };
 
             device
@@ -234,6 +234,7 @@ where
                         count: 1,
                         stage_flags: ShaderStageFlags::MESH,
                         immutable_samplers: false,
+                        flags: pso::DescriptorBindingFlags::empty(),
                     }),
                     iter::empty(),
                 )
@@ -377,6 +378,8 @@ where
                 inputs: &[],
                 resolves: &[],
                 preserves: &[],
+                depth_stencil_resolve: None,
+                shading_rate: None,
             };
 
             ManuallyDrop::new(
@@ -360,6 +360,7 @@ where
                             count: 1,
                             stage_flags: ShaderStageFlags::FRAGMENT,
                             immutable_samplers: false,
+                            flags: pso::DescriptorBindingFlags::empty(),
                         },
                         pso::DescriptorSetLayoutBinding {
                             binding: 1,
@@ -367,6 +368,7 @@ where
                             count: 1,
                             stage_flags: ShaderStageFlags::FRAGMENT,
                             immutable_samplers: false,
+                            flags: pso::DescriptorBindingFlags::empty(),
                         },
                     ]
                     .into_iter(),
@@ -733,6 +735,8 @@ where
                 inputs: &[],
                 resolves: &[],
                 preserves: &[],
+                depth_stencil_resolve: None,
+                shading_rate: None,
             };
 
             ManuallyDrop::new(
@@ -28,7 +28,7 @@ use crate::{
     DescriptorSetLayout, Fence, Framebuffer, GraphicsPipeline, Image, ImageView, InternalBuffer,
     InternalImage, Memory, MultiStageData, PipelineLayout, QueryPool, RawFence,
     RegisterAccumulator, RegisterData, RenderPass, ResourceIndex, Sampler, Semaphore, ShaderModule,
-    SubpassDesc, ViewInfo,
+    SubpassDesc, TimelineSemaphore, ViewInfo,
 };
 
 //TODO: expose coherent type 0x2 when it's properly supported
@@ -1007,6 +1007,17 @@ impl device::Device<Backend> for Device {
         desc: &pso::GraphicsPipelineDesc<'a, Backend>,
         _cache: Option<&()>,
     ) -> Result<GraphicsPipeline, pso::CreationError> {
+        // Primitive restart can't be toggled with commands.
+        if desc
+            .dynamic_states
+            .contains(pso::DynamicStates::PRIMITIVE_RESTART)
+            && !self
+                .features
+                .contains(hal::Features::PRIMITIVE_RESTART_DYNAMIC_STATE)
+        {
+            return Err(pso::CreationError::UnsupportedPipeline);
+        }
+
         let features = &self.features;
         let build_shader =
             |stage: ShaderStage, source: Option<&pso::EntryPoint<'a, Backend>>| match source {
@@ -1140,6 +1151,23 @@ impl device::Device<Backend> for Device {
         Ok(ComputePipeline { cs })
     }
 
+    unsafe fn create_ray_tracing_pipeline<'a>(
+        &self,
+        _desc: &pso::RayTracingPipelineDesc<'a, Backend>,
+        _cache: Option<&()>,
+    ) -> Result<(), pso::CreationError> {
+        Err(pso::CreationError::UnsupportedPipeline)
+    }
+
+    unsafe fn get_ray_tracing_shader_group_handles(
+        &self,
+        _pipeline: &(),
+        _first_group: u32,
+        _group_count: u32,
+    ) -> Result<Vec<u8>, device::OutOfMemory> {
+        panic!("DX11 doesn't support RAY_TRACING_PIPELINE")
+    }
+
     unsafe fn create_framebuffer<I>(
         &self,
         _renderpass: &RenderPass,
@@ -1437,6 +1465,28 @@ impl device::Device<Backend> for Device {
         unimplemented!()
     }
 
+    unsafe fn get_acceleration_structure_build_requirements(
+        &self,
+        _desc: &hal::acceleration_structure::GeometryDesc<Backend>,
+        _max_primitive_counts: &[u32],
+    ) -> hal::acceleration_structure::SizeRequirements {
+        panic!("DX11 doesn't support ACCELERATION_STRUCTURE")
+    }
+
+    unsafe fn create_acceleration_structure(
+        &self,
+        _desc: &hal::acceleration_structure::CreateDesc<Backend>,
+    ) -> Result<(), device::OutOfMemory> {
+        panic!("DX11 doesn't support ACCELERATION_STRUCTURE")
+    }
+
+    unsafe fn get_acceleration_structure_address(
+        &self,
+        _accel_struct: &(),
+    ) -> hal::acceleration_structure::DeviceAddress {
+        panic!("DX11 doesn't support ACCELERATION_STRUCTURE")
+    }
+
     unsafe fn create_image(
         &self,
         kind: image::Kind,
@@ -2083,6 +2133,9 @@ impl device::Device<Backend> for Device {
                     s: sampler.sampler_handle.as_raw() as *mut _,
                 },
                 pso::Descriptor::TexelBuffer(_buffer_view) => unimplemented!(),
+                pso::Descriptor::AccelerationStructure(_) => unimplemented!(),
+                pso::Descriptor::Mutable(..) => unimplemented!(),
+                pso::Descriptor::Null(_) => unimplemented!(),
             };
 
             let content = DescriptorContent::from(binding.ty);
@@ -2226,6 +2279,42 @@ impl device::Device<Backend> for Device {
         Ok(Semaphore)
     }
 
+    fn create_timeline_semaphore(
+        &self,
+        initial_value: u64,
+    ) -> Result<TimelineSemaphore, device::OutOfMemory> {
+        Ok(TimelineSemaphore::new(initial_value))
+    }
+
+    unsafe fn get_timeline_semaphore_value(
+        &self,
+        semaphore: &TimelineSemaphore,
+    ) -> Result<u64, device::DeviceLost> {
+        Ok(semaphore.value())
+    }
+
+    unsafe fn signal_timeline_semaphore(
+        &self,
+        semaphore: &TimelineSemaphore,
+        value: u64,
+    ) -> Result<(), device::OutOfMemory> {
+        semaphore.signal(value);
+        Ok(())
+    }
+
+    unsafe fn wait_for_timeline_semaphore(
+        &self,
+        semaphore: &TimelineSemaphore,
+        value: u64,
+        timeout_ns: u64,
+    ) -> Result<bool, device::WaitError> {
+        debug!(
+            "wait_for_timeline_semaphore {:?} to reach {} for {} ns",
+            semaphore, value, timeout_ns
+        );
+        Ok(semaphore.wait(value, timeout_ns))
+    }
+
     fn create_fence(&self, signalled: bool) -> Result<Fence, device::OutOfMemory> {
         Ok(Arc::new(RawFence {
             mutex: Mutex::new(signalled),
@@ -2328,6 +2417,10 @@ impl device::Device<Backend> for Device {
         unimplemented!()
     }
 
+    unsafe fn reset_query_pool(&self, _pool: &QueryPool, _queries: Range<query::Id>) {
+        unimplemented!()
+    }
+
     unsafe fn destroy_shader_module(&self, _shader_lib: ShaderModule) {}
 
     unsafe fn destroy_render_pass(&self, _rp: RenderPass) {
@@ -2340,8 +2433,45 @@ impl device::Device<Backend> for Device {
 
     unsafe fn destroy_graphics_pipeline(&self, _pipeline: GraphicsPipeline) {}
 
+    unsafe fn create_graphics_pipeline_library<'a>(
+        &self,
+        _desc: &pso::GraphicsPipelineDesc<'a, Backend>,
+        _parts: pso::GraphicsPipelineLibraryParts,
+        _cache: Option<&()>,
+    ) -> Result<(), pso::CreationError> {
+        unimplemented!()
+    }
+
+    unsafe fn link_graphics_pipeline<'a>(
+        &self,
+        _desc: &pso::GraphicsPipelineLinkDesc<'a, Backend>,
+        _cache: Option<&()>,
+    ) -> Result<GraphicsPipeline, pso::CreationError> {
+        unimplemented!()
+    }
+
+    unsafe fn destroy_graphics_pipeline_library(&self, _library: ()) {
+        unimplemented!()
+    }
+
     unsafe fn destroy_compute_pipeline(&self, _pipeline: ComputePipeline) {}
 
+    unsafe fn get_graphics_pipeline_executables(
+        &self,
+        _pipeline: &GraphicsPipeline,
+    ) -> Vec<pso::PipelineExecutable> {
+        panic!("DX11 doesn't support PIPELINE_EXECUTABLE_INFO")
+    }
+
+    unsafe fn get_compute_pipeline_executables(
+        &self,
+        _pipeline: &ComputePipeline,
+    ) -> Vec<pso::PipelineExecutable> {
+        panic!("DX11 doesn't support PIPELINE_EXECUTABLE_INFO")
+    }
+
+    unsafe fn destroy_ray_tracing_pipeline(&self, _pipeline: ()) {}
+
     unsafe fn destroy_framebuffer(&self, _fb: Framebuffer) {}
 
     unsafe fn destroy_buffer(&self, buffer: Buffer) {
@@ -2370,6 +2500,8 @@ impl device::Device<Backend> for Device {
         //unimplemented!()
     }
 
+    unsafe fn destroy_acceleration_structure(&self, _accel_struct: ()) {}
+
     unsafe fn destroy_image(&self, mut image: Image) {
         image.internal.release_resources();
     }
@@ -2396,6 +2528,8 @@ impl device::Device<Backend> for Device {
         //unimplemented!()
     }
 
+    unsafe fn destroy_timeline_semaphore(&self, _semaphore: TimelineSemaphore) {}
+
     unsafe fn destroy_event(&self, _event: ()) {
         //unimplemented!()
     }
@@ -2461,6 +2595,31 @@ impl device::Device<Backend> for Device {
         // TODO
     }
 
+    unsafe fn create_private_data_slot(&self) -> Result<(), device::OutOfMemory> {
+        unimplemented!()
+    }
+
+    unsafe fn destroy_private_data_slot(&self, _slot: ()) {
+        unimplemented!()
+    }
+
+    unsafe fn set_private_data(
+        &self,
+        _object: device::PrivateDataObject<Backend>,
+        _slot: &(),
+        _data: u64,
+    ) -> Result<(), device::OutOfMemory> {
+        unimplemented!()
+    }
+
+    unsafe fn get_private_data(
+        &self,
+        _object: device::PrivateDataObject<Backend>,
+        _slot: &(),
+    ) -> u64 {
+        unimplemented!()
+    }
+
     unsafe fn set_display_power_state(
         &self,
         _display: &hal::display::Display<Backend>,
@@ -51,11 +51,12 @@ use parking_lot::{Condvar, Mutex, RwLock};
 use std::{
     borrow::Borrow,
     cell::RefCell,
-    fmt, mem,
+    fmt, iter, mem,
     ops::Range,
     os::raw::c_void,
     ptr,
     sync::{Arc, Weak},
+    time,
 };
 
 macro_rules! debug_scope {
@@ -178,6 +179,8 @@ fn get_features(
         | hal::Features::NDC_Y_UP;
 
     let mut downlevel = hal::DownlevelProperties::default();
+    // render target views are only bound at the beginning of a render pass
+    downlevel.imageless_framebuffers = true;
     let performance = hal::PerformanceCaveats::default();
 
     if d3dcommon::D3D_FEATURE_LEVEL_9_1 <= feature_level
@@ -362,6 +365,7 @@ fn get_limits(feature_level: d3dcommon::D3D_FEATURE_LEVEL) -> hal::Limits {
             max_compute_work_group_size_z,
         ], // TODO
         max_vertex_input_attribute_offset: 255, // TODO
+        max_clip_distances: 0,
         max_vertex_input_attributes: max_input_slots,
         max_vertex_input_binding_stride: d3d11::D3D11_REQ_MULTI_ELEMENT_STRUCTURE_SIZE_IN_BYTES
             as _,
@@ -1066,6 +1070,8 @@ impl window::Surface<Backend> for Surface {
             present_modes: window::PresentMode::IMMEDIATE | window::PresentMode::FIFO,
             composite_alpha_modes: window::CompositeAlphaMode::OPAQUE, //TODO
             image_count: 1..=16,                                       // TODO:
+            current_transform: window::SurfaceTransform::IDENTITY,
+            transforms: window::SurfaceTransform::IDENTITY,
             current_extent,
             extents: window::Extent2D {
                 width: 16,
@@ -1076,6 +1082,8 @@ impl window::Surface<Backend> for Surface {
             },
             max_image_layers: 1,
             usage: image::Usage::COLOR_ATTACHMENT,
+            swapchain_maintenance: false,
+            present_wait: false,
         }
     }
 
@@ -1328,6 +1336,25 @@ impl queue::Queue<Backend> for Queue {
         }
     }
 
+    unsafe fn submit_timeline<'a, Ic, Iw, Is>(
+        &mut self,
+        command_buffers: Ic,
+        wait_semaphores: Iw,
+        signal_semaphores: Is,
+    ) where
+        Ic: Iterator<Item = &'a CommandBuffer>,
+        Iw: Iterator<Item = (&'a TimelineSemaphore, u64, pso::PipelineStage)>,
+        Is: Iterator<Item = (&'a TimelineSemaphore, u64)>,
+    {
+        for (semaphore, value, _stage) in wait_semaphores {
+            semaphore.wait(value, !0);
+        }
+        self.submit(command_buffers, iter::empty(), iter::empty(), None);
+        for (semaphore, value) in signal_semaphores {
+            semaphore.signal(value);
+        }
+    }
+
     unsafe fn present(
         &mut self,
         surface: &mut Surface,
@@ -2314,6 +2341,14 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         self.render_pass_cache = None;
     }
 
+    unsafe fn begin_rendering(&mut self, _info: &command::RenderingInfo<Backend>) {
+        panic!("DX11 doesn't support DYNAMIC_RENDERING")
+    }
+
+    unsafe fn end_rendering(&mut self) {
+        panic!("DX11 doesn't support DYNAMIC_RENDERING")
+    }
+
     unsafe fn pipeline_barrier<'a, T>(
         &mut self,
         _stages: Range<pso::PipelineStage>,
@@ -2506,6 +2541,50 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         // unimplemented!()
     }
 
+    unsafe fn set_cull_face(&mut self, _faces: pso::Face) {
+        panic!("DX11 doesn't support EXTENDED_DYNAMIC_STATE")
+    }
+
+    unsafe fn set_front_face(&mut self, _front_face: pso::FrontFace) {
+        panic!("DX11 doesn't support EXTENDED_DYNAMIC_STATE")
+    }
+
+    unsafe fn set_primitive_topology(&mut self, _primitive: pso::Primitive) {
+        panic!("DX11 doesn't support EXTENDED_DYNAMIC_STATE")
+    }
+
+    unsafe fn set_depth_test(&mut self, _depth: Option<pso::DepthTest>) {
+        panic!("DX11 doesn't support EXTENDED_DYNAMIC_STATE")
+    }
+
+    unsafe fn set_stencil_faces(&mut self, _faces: pso::Face, _value: pso::StencilFace) {
+        panic!("DX11 doesn't support EXTENDED_DYNAMIC_STATE")
+    }
+
+    unsafe fn set_vertex_input(
+        &mut self,
+        _buffers: &[pso::VertexBufferDesc],
+        _attributes: &[pso::AttributeDesc],
+    ) {
+        panic!("DX11 doesn't support VERTEX_INPUT_DYNAMIC_STATE")
+    }
+
+    unsafe fn set_primitive_restart(&mut self, _enable: bool) {
+        panic!("DX11 doesn't support EXTENDED_DYNAMIC_STATE")
+    }
+
+    unsafe fn set_fragment_shading_rate(
+        &mut self,
+        _fragment_size: window::Extent2D,
+        _combiner_ops: [pso::ShadingRateCombinerOp; 2],
+    ) {
+        unimplemented!()
+    }
+
+    unsafe fn set_sample_locations(&mut self, _locations: &[pso::SampleLocation]) {
+        unimplemented!()
+    }
+
     unsafe fn bind_graphics_pipeline(&mut self, pipeline: &GraphicsPipeline) {
         self.cache.set_graphics_pipeline(pipeline.clone());
         self.cache.bind(&self.context);
@@ -2839,6 +2918,42 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         unimplemented!()
     }
 
+    unsafe fn build_acceleration_structure(
+        &mut self,
+        _desc: &hal::acceleration_structure::BuildDesc<Backend>,
+        _ranges: &[hal::acceleration_structure::BuildRangeDesc],
+    ) {
+        panic!("DX11 doesn't support ACCELERATION_STRUCTURE")
+    }
+
+    unsafe fn bind_ray_tracing_pipeline(&mut self, _pipeline: &()) {
+        panic!("DX11 doesn't support RAY_TRACING_PIPELINE")
+    }
+
+    unsafe fn bind_ray_tracing_descriptor_sets<'a, I, J>(
+        &mut self,
+        _layout: &PipelineLayout,
+        _first_set: usize,
+        _sets: I,
+        _offsets: J,
+    ) where
+        I: Iterator<Item = &'a DescriptorSet>,
+        J: Iterator<Item = command::DescriptorSetOffset>,
+    {
+        panic!("DX11 doesn't support RAY_TRACING_PIPELINE")
+    }
+
+    unsafe fn trace_rays(
+        &mut self,
+        _raygen: &pso::ShaderBindingTable<Backend>,
+        _miss: Option<&pso::ShaderBindingTable<Backend>>,
+        _hit_group: Option<&pso::ShaderBindingTable<Backend>>,
+        _callable: Option<&pso::ShaderBindingTable<Backend>>,
+        _dimensions: [u32; 3],
+    ) {
+        panic!("DX11 doesn't support RAY_TRACING_PIPELINE")
+    }
+
     unsafe fn fill_buffer(&mut self, buffer: &Buffer, sub: buffer::SubRange, data: u32) {
         let mut device: *mut d3d11::ID3D11Device = mem::zeroed();
         self.context.GetDevice(&mut device as *mut _);
@@ -3195,6 +3310,19 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         unimplemented!()
     }
 
+    unsafe fn begin_conditional_rendering(
+        &mut self,
+        _buffer: &Buffer,
+        _offset: buffer::Offset,
+        _flags: command::ConditionalRenderingFlags,
+    ) {
+        unimplemented!()
+    }
+
+    unsafe fn end_conditional_rendering(&mut self) {
+        unimplemented!()
+    }
+
     unsafe fn push_graphics_constants(
         &mut self,
         _layout: &PipelineLayout,
@@ -4308,6 +4436,8 @@ impl From<pso::DescriptorType> for DescriptorContent {
                 ty: Bdt::Storage { read_only: false },
                 ..
             } => DescriptorContent::UAV,
+            Dt::AccelerationStructure => DescriptorContent::empty(),
+            Dt::Mutable { .. } => unimplemented!(),
         }
     }
 }
@@ -4448,6 +4578,51 @@ pub type Fence = Arc<RawFence>;
 
 #[derive(Debug)]
 pub struct Semaphore;
+
+/// Timeline semaphore, advanced on the host since the submissions are executed
+/// right away.
+///
+/// Waiting for a value that is only signalled by a later submission blocks forever,
+/// so `Features::TIMELINE_SEMAPHORE` isn't reported.
+#[derive(Debug)]
+pub struct TimelineSemaphore {
+    value: Mutex<u64>,
+    condvar: Condvar,
+}
+
+impl TimelineSemaphore {
+    fn new(value: u64) -> Self {
+        TimelineSemaphore {
+            value: Mutex::new(value),
+            condvar: Condvar::new(),
+        }
+    }
+
+    fn value(&self) -> u64 {
+        *self.value.lock()
+    }
+
+    fn signal(&self, value: u64) {
+        *self.value.lock() = value;
+        self.condvar.notify_all();
+    }
+
+    fn wait(&self, value: u64, timeout_ns: u64) -> bool {
+        let mut guard = self.value.lock();
+        let deadline = time::Instant::now().checked_add(time::Duration::from_nanos(timeout_ns));
+        while *guard < value {
+            match deadline {
+                Some(deadline) => {
+                    if self.condvar.wait_until(&mut guard, deadline).timed_out() {
+                        return *guard >= value;
+                    }
+                }
+                None => self.condvar.wait(&mut guard),
+            }
+        }
+        true
+    }
+}
 #[derive(Debug)]
 pub struct QueryPool;
 
@@ -4476,9 +4651,12 @@ impl hal::Backend for Backend {
 
     type ImageView = ImageView;
     type Sampler = Sampler;
+    type AccelerationStructure = ();
 
     type ComputePipeline = ComputePipeline;
+    type RayTracingPipeline = ();
     type GraphicsPipeline = GraphicsPipeline;
+    type GraphicsPipelineLibrary = ();
     type PipelineLayout = PipelineLayout;
     type PipelineCache = ();
     type DescriptorSetLayout = DescriptorSetLayout;
@@ -4487,8 +4665,10 @@ impl hal::Backend for Backend {
 
     type Fence = Fence;
     type Semaphore = Semaphore;
+    type TimelineSemaphore = TimelineSemaphore;
     type Event = ();
     type QueryPool = QueryPool;
+    type PrivateDataSlot = ();
 
     type Display = ();
     type DisplayMode = ();
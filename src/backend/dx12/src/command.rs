@@ -1373,6 +1373,14 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         }
     }
 
+    unsafe fn begin_rendering(&mut self, _info: &com::RenderingInfo<Backend>) {
+        unimplemented!()
+    }
+
+    unsafe fn end_rendering(&mut self) {
+        unimplemented!()
+    }
+
     unsafe fn pipeline_barrier<'a, T>(
         &mut self,
         stages: Range<pso::PipelineStage>,
@@ -2052,6 +2060,52 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         // unimplemented!()
     }
 
+    unsafe fn set_cull_face(&mut self, _faces: pso::Face) {
+        unimplemented!()
+    }
+
+    unsafe fn set_front_face(&mut self, _front_face: pso::FrontFace) {
+        unimplemented!()
+    }
+
+    unsafe fn set_primitive_topology(&mut self, _primitive: pso::Primitive) {
+        unimplemented!()
+    }
+
+    unsafe fn set_depth_test(&mut self, _depth: Option<pso::DepthTest>) {
+        unimplemented!()
+    }
+
+    unsafe fn set_stencil_faces(&mut self, _faces: pso::Face, _value: pso::StencilFace) {
+        unimplemented!()
+    }
+
+    unsafe fn set_vertex_input(
+        &mut self,
+        _buffers: &[pso::VertexBufferDesc],
+        _attributes: &[pso::AttributeDesc],
+    ) {
+        unimplemented!()
+    }
+
+    unsafe fn set_primitive_restart(&mut self, _enable: bool) {
+        unimplemented!()
+    }
+
+    unsafe fn set_fragment_shading_rate(
+        &mut self,
+        _fragment_size: hal::window::Extent2D,
+        _combiner_ops: [pso::ShadingRateCombinerOp; 2],
+    ) {
+        // Needs `ID3D12GraphicsCommandList5::RSSetShadingRate`, which isn't exposed by winapi.
+        unimplemented!()
+    }
+
+    unsafe fn set_sample_locations(&mut self, _locations: &[pso::SampleLocation]) {
+        // Needs `ID3D12GraphicsCommandList1::SetSamplePositions`, which isn't exposed by winapi.
+        unimplemented!()
+    }
+
     unsafe fn bind_graphics_pipeline(&mut self, pipeline: &r::GraphicsPipeline) {
         match self.gr_pipeline.pipeline {
             Some((_, ref shared)) if Arc::ptr_eq(shared, &pipeline.shared) => {
@@ -2166,6 +2220,42 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         );
     }
 
+    unsafe fn build_acceleration_structure(
+        &mut self,
+        _desc: &hal::acceleration_structure::BuildDesc<Backend>,
+        _ranges: &[hal::acceleration_structure::BuildRangeDesc],
+    ) {
+        unimplemented!()
+    }
+
+    unsafe fn bind_ray_tracing_pipeline(&mut self, _pipeline: &()) {
+        unimplemented!()
+    }
+
+    unsafe fn bind_ray_tracing_descriptor_sets<'a, I, J>(
+        &mut self,
+        _layout: &r::PipelineLayout,
+        _first_set: usize,
+        _sets: I,
+        _offsets: J,
+    ) where
+        I: Iterator<Item = &'a r::DescriptorSet>,
+        J: Iterator<Item = com::DescriptorSetOffset>,
+    {
+        unimplemented!()
+    }
+
+    unsafe fn trace_rays(
+        &mut self,
+        _raygen: &pso::ShaderBindingTable<Backend>,
+        _miss: Option<&pso::ShaderBindingTable<Backend>>,
+        _hit_group: Option<&pso::ShaderBindingTable<Backend>>,
+        _callable: Option<&pso::ShaderBindingTable<Backend>>,
+        _dimensions: [u32; 3],
+    ) {
+        unimplemented!()
+    }
+
     unsafe fn fill_buffer(&mut self, buffer: &r::Buffer, range: buffer::SubRange, data: u32) {
         let buffer = buffer.expect_bound();
         let bytes_per_unit = 4;
@@ -2699,6 +2789,7 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                 self.pipeline_stats_query = Some(query.id);
                 d3d12::D3D12_QUERY_TYPE_PIPELINE_STATISTICS
             }
+            query::Type::PerformanceCounters(_) => unreachable!(),
         };
 
         self.raw
@@ -2755,6 +2846,28 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         );
     }
 
+    unsafe fn begin_conditional_rendering(
+        &mut self,
+        buffer: &r::Buffer,
+        offset: buffer::Offset,
+        flags: com::ConditionalRenderingFlags,
+    ) {
+        let buffer = buffer.expect_bound();
+        // The predicate is read as a 64-bit value, which is why its upper half has to be zero.
+        let op = if flags.contains(com::ConditionalRenderingFlags::INVERTED) {
+            d3d12::D3D12_PREDICATION_OP_NOT_EQUAL_ZERO
+        } else {
+            d3d12::D3D12_PREDICATION_OP_EQUAL_ZERO
+        };
+        self.raw
+            .SetPredication(buffer.resource.as_mut_ptr(), offset, op);
+    }
+
+    unsafe fn end_conditional_rendering(&mut self) {
+        self.raw
+            .SetPredication(ptr::null_mut(), 0, d3d12::D3D12_PREDICATION_OP_EQUAL_ZERO);
+    }
+
     unsafe fn push_graphics_constants(
         &mut self,
         _layout: &r::PipelineLayout,
@@ -265,6 +265,18 @@ pub fn map_rasterizer(rasterizer: &pso::Rasterizer, multisample: bool) -> D3D12_
     if let pso::State::Static(w) = rasterizer.line_width {
         validate_line_width(w);
     }
+    if rasterizer.line_stipple.is_some() {
+        error!("Line stipple is not supported");
+    }
+    let (multisample_enable, antialiased_line_enable) = match rasterizer.line_rasterization_mode {
+        pso::LineRasterizationMode::Default => (multisample, false),
+        pso::LineRasterizationMode::Bresenham => (false, false),
+        pso::LineRasterizationMode::RectangularSmooth => (false, true),
+        pso::LineRasterizationMode::Rectangular => {
+            error!("Rectangular line rasterization is not supported");
+            (multisample, false)
+        }
+    };
 
     D3D12_RASTERIZER_DESC {
         FillMode: match rasterizer.polygon_mode {
@@ -289,9 +301,9 @@ pub fn map_rasterizer(rasterizer: &pso::Rasterizer, multisample: bool) -> D3D12_
         DepthBiasClamp: bias.clamp,
         SlopeScaledDepthBias: bias.slope_factor,
         DepthClipEnable: !rasterizer.depth_clamping as _,
-        MultisampleEnable: if multisample { TRUE } else { FALSE },
-        ForcedSampleCount: 0,         // TODO: currently not supported
-        AntialiasedLineEnable: FALSE, // TODO: currently not supported
+        MultisampleEnable: if multisample_enable { TRUE } else { FALSE },
+        ForcedSampleCount: 0, // TODO: currently not supported
+        AntialiasedLineEnable: if antialiased_line_enable { TRUE } else { FALSE },
         ConservativeRaster: if rasterizer.conservative {
             D3D12_CONSERVATIVE_RASTERIZATION_MODE_ON
         } else {
@@ -534,6 +546,9 @@ pub fn map_buffer_resource_state(access: buffer::Access) -> D3D12_RESOURCE_STATE
     if access.contains(Access::INDIRECT_COMMAND_READ) {
         state |= D3D12_RESOURCE_STATE_INDIRECT_ARGUMENT;
     }
+    if access.contains(Access::CONDITIONAL_RENDERING_READ) {
+        state |= D3D12_RESOURCE_STATE_PREDICATION;
+    }
     if access.contains(Access::SHADER_READ) {
         // SHADER_READ only allows SRV access
         state |= D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE
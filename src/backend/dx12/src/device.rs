@@ -1168,6 +1168,69 @@ impl Device {
         }
     }
 
+    pub(crate) fn buffer_desc(buffer_unbound: &r::BufferUnbound) -> d3d12::D3D12_RESOURCE_DESC {
+        d3d12::D3D12_RESOURCE_DESC {
+            Dimension: d3d12::D3D12_RESOURCE_DIMENSION_BUFFER,
+            Alignment: 0,
+            Width: buffer_unbound.requirements.size,
+            Height: 1,
+            DepthOrArraySize: 1,
+            MipLevels: 1,
+            Format: dxgiformat::DXGI_FORMAT_UNKNOWN,
+            SampleDesc: dxgitype::DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            Layout: d3d12::D3D12_TEXTURE_LAYOUT_ROW_MAJOR,
+            Flags: conv::map_buffer_flags(buffer_unbound.usage),
+        }
+    }
+
+    pub(crate) unsafe fn bind_buffer_resource(
+        &self,
+        resource: native::Resource,
+        buffer: &mut r::Buffer,
+    ) {
+        let buffer_unbound = buffer.expect_unbound();
+
+        if let Some(ref name) = buffer_unbound.name {
+            resource.SetName(name.as_ptr());
+        }
+
+        let clear_uav = if buffer_unbound.usage.contains(buffer::Usage::TRANSFER_DST) {
+            let handle = self.srv_uav_pool.lock().alloc_handle();
+            let mut view_desc = d3d12::D3D12_UNORDERED_ACCESS_VIEW_DESC {
+                Format: dxgiformat::DXGI_FORMAT_R32_TYPELESS,
+                ViewDimension: d3d12::D3D12_UAV_DIMENSION_BUFFER,
+                u: mem::zeroed(),
+            };
+
+            *view_desc.u.Buffer_mut() = d3d12::D3D12_BUFFER_UAV {
+                FirstElement: 0,
+                NumElements: (buffer_unbound.requirements.size / 4) as _,
+                StructureByteStride: 0,
+                CounterOffsetInBytes: 0,
+                Flags: d3d12::D3D12_BUFFER_UAV_FLAG_RAW,
+            };
+
+            self.raw.CreateUnorderedAccessView(
+                resource.as_mut_ptr(),
+                ptr::null_mut(),
+                &view_desc,
+                handle.raw,
+            );
+            Some(handle)
+        } else {
+            None
+        };
+
+        *buffer = r::Buffer::Bound(r::BufferBound {
+            resource,
+            requirements: buffer_unbound.requirements,
+            clear_uav,
+        });
+    }
+
     pub(crate) fn bind_image_resource(
         &self,
         resource: native::WeakPtr<d3d12::ID3D12Resource>,
@@ -1924,6 +1987,17 @@ impl d::Device<B> for Device {
         desc: &pso::GraphicsPipelineDesc<'a, B>,
         _cache: Option<&()>,
     ) -> Result<r::GraphicsPipeline, pso::CreationError> {
+        // Primitive restart can't be toggled with commands.
+        if desc
+            .dynamic_states
+            .contains(pso::DynamicStates::PRIMITIVE_RESTART)
+            && !self
+                .features
+                .contains(hal::Features::PRIMITIVE_RESTART_DYNAMIC_STATE)
+        {
+            return Err(pso::CreationError::UnsupportedPipeline);
+        }
+
         enum ShaderBc {
             Owned(native::Blob),
             Borrowed(native::Blob),
@@ -2338,6 +2412,23 @@ impl d::Device<B> for Device {
         }
     }
 
+    unsafe fn create_ray_tracing_pipeline<'a>(
+        &self,
+        _desc: &pso::RayTracingPipelineDesc<'a, B>,
+        _cache: Option<&()>,
+    ) -> Result<(), pso::CreationError> {
+        Err(pso::CreationError::UnsupportedPipeline)
+    }
+
+    unsafe fn get_ray_tracing_shader_group_handles(
+        &self,
+        _pipeline: &(),
+        _first_group: u32,
+        _group_count: u32,
+    ) -> Result<Vec<u8>, d::OutOfMemory> {
+        unimplemented!()
+    }
+
     unsafe fn create_framebuffer<I>(
         &self,
         _renderpass: &r::RenderPass,
@@ -2419,21 +2510,7 @@ impl d::Device<B> for Device {
         }
 
         let mut resource = native::Resource::null();
-        let desc = d3d12::D3D12_RESOURCE_DESC {
-            Dimension: d3d12::D3D12_RESOURCE_DIMENSION_BUFFER,
-            Alignment: 0,
-            Width: buffer_unbound.requirements.size,
-            Height: 1,
-            DepthOrArraySize: 1,
-            MipLevels: 1,
-            Format: dxgiformat::DXGI_FORMAT_UNKNOWN,
-            SampleDesc: dxgitype::DXGI_SAMPLE_DESC {
-                Count: 1,
-                Quality: 0,
-            },
-            Layout: d3d12::D3D12_TEXTURE_LAYOUT_ROW_MAJOR,
-            Flags: conv::map_buffer_flags(buffer_unbound.usage),
-        };
+        let desc = Self::buffer_desc(buffer_unbound);
 
         assert_eq!(
             winerror::S_OK,
@@ -2448,42 +2525,7 @@ impl d::Device<B> for Device {
             )
         );
 
-        if let Some(ref name) = buffer_unbound.name {
-            resource.SetName(name.as_ptr());
-        }
-
-        let clear_uav = if buffer_unbound.usage.contains(buffer::Usage::TRANSFER_DST) {
-            let handle = self.srv_uav_pool.lock().alloc_handle();
-            let mut view_desc = d3d12::D3D12_UNORDERED_ACCESS_VIEW_DESC {
-                Format: dxgiformat::DXGI_FORMAT_R32_TYPELESS,
-                ViewDimension: d3d12::D3D12_UAV_DIMENSION_BUFFER,
-                u: mem::zeroed(),
-            };
-
-            *view_desc.u.Buffer_mut() = d3d12::D3D12_BUFFER_UAV {
-                FirstElement: 0,
-                NumElements: (buffer_unbound.requirements.size / 4) as _,
-                StructureByteStride: 0,
-                CounterOffsetInBytes: 0,
-                Flags: d3d12::D3D12_BUFFER_UAV_FLAG_RAW,
-            };
-
-            self.raw.CreateUnorderedAccessView(
-                resource.as_mut_ptr(),
-                ptr::null_mut(),
-                &view_desc,
-                handle.raw,
-            );
-            Some(handle)
-        } else {
-            None
-        };
-
-        *buffer = r::Buffer::Bound(r::BufferBound {
-            resource,
-            requirements: buffer_unbound.requirements,
-            clear_uav,
-        });
+        self.bind_buffer_resource(resource, buffer);
 
         Ok(())
     }
@@ -2577,6 +2619,28 @@ impl d::Device<B> for Device {
         });
     }
 
+    unsafe fn get_acceleration_structure_build_requirements(
+        &self,
+        _desc: &hal::acceleration_structure::GeometryDesc<B>,
+        _max_primitive_counts: &[u32],
+    ) -> hal::acceleration_structure::SizeRequirements {
+        unimplemented!()
+    }
+
+    unsafe fn create_acceleration_structure(
+        &self,
+        _desc: &hal::acceleration_structure::CreateDesc<B>,
+    ) -> Result<(), d::OutOfMemory> {
+        unimplemented!()
+    }
+
+    unsafe fn get_acceleration_structure_address(
+        &self,
+        _accel_struct: &(),
+    ) -> hal::acceleration_structure::DeviceAddress {
+        unimplemented!()
+    }
+
     unsafe fn create_image(
         &self,
         kind: image::Kind,
@@ -3148,6 +3212,9 @@ impl d::Device<B> for Device {
                         src_uav = Some(handle.raw);
                     }
                 }
+                pso::Descriptor::AccelerationStructure(_) => unimplemented!(),
+                pso::Descriptor::Mutable(..) => unimplemented!(),
+                pso::Descriptor::Null(_) => unimplemented!(),
             }
 
             if let Some(handle) = src_cbv {
@@ -3333,6 +3400,83 @@ impl d::Device<B> for Device {
         Ok(r::Semaphore { raw: fence.raw })
     }
 
+    fn create_timeline_semaphore(
+        &self,
+        initial_value: u64,
+    ) -> Result<r::TimelineSemaphore, d::OutOfMemory> {
+        let mut handle = native::Fence::null();
+        let hr = unsafe {
+            self.raw.CreateFence(
+                initial_value,
+                d3d12::D3D12_FENCE_FLAG_NONE,
+                &d3d12::ID3D12Fence::uuidof(),
+                handle.mut_void(),
+            )
+        };
+        if !winerror::SUCCEEDED(hr) {
+            error!("Error in CreateFence: 0x{:X}", hr);
+            return Err(d::OutOfMemory::Device);
+        }
+        Ok(r::TimelineSemaphore { raw: handle })
+    }
+
+    unsafe fn get_timeline_semaphore_value(
+        &self,
+        semaphore: &r::TimelineSemaphore,
+    ) -> Result<u64, d::DeviceLost> {
+        // The completed value of a removed device is `UINT64_MAX`.
+        match semaphore.raw.GetCompletedValue() {
+            u64::MAX => Err(d::DeviceLost),
+            value => Ok(value),
+        }
+    }
+
+    unsafe fn signal_timeline_semaphore(
+        &self,
+        semaphore: &r::TimelineSemaphore,
+        value: u64,
+    ) -> Result<(), d::OutOfMemory> {
+        assert_eq!(winerror::S_OK, semaphore.raw.signal(value));
+        Ok(())
+    }
+
+    unsafe fn wait_for_timeline_semaphore(
+        &self,
+        semaphore: &r::TimelineSemaphore,
+        value: u64,
+        timeout_ns: u64,
+    ) -> Result<bool, d::WaitError> {
+        match semaphore.raw.GetCompletedValue() {
+            u64::MAX => return Err(d::WaitError::DeviceLost(d::DeviceLost)),
+            completed if completed >= value => return Ok(true),
+            _ => {}
+        }
+
+        let mut events = self.events.lock();
+        if events.is_empty() {
+            events.push(native::Event::create(false, false));
+        }
+        let event = events[0];
+        synchapi::ResetEvent(event.0);
+        assert_eq!(
+            winerror::S_OK,
+            semaphore.raw.set_event_on_completion(event, value)
+        );
+
+        // Rounds up, the same way as for the fences.
+        let timeout_ms = if timeout_ns > (<u32>::MAX as u64) * 1_000_000 {
+            <u32>::MAX
+        } else {
+            ((timeout_ns + 999_999) / 1_000_000) as u32
+        };
+        match synchapi::WaitForSingleObject(event.0, timeout_ms) {
+            winbase::WAIT_OBJECT_0 => Ok(true),
+            winbase::WAIT_FAILED => Err(d::WaitError::DeviceLost(d::DeviceLost)),
+            winerror::WAIT_TIMEOUT => Ok(false),
+            hr => panic!("Unexpected wait status 0x{:X}", hr),
+        }
+    }
+
     fn create_fence(&self, signalled: bool) -> Result<r::Fence, d::OutOfMemory> {
         Ok(r::Fence {
             raw: self.create_raw_fence(signalled),
@@ -3441,6 +3585,9 @@ impl d::Device<B> for Device {
             query::Type::Occlusion => native::QueryHeapType::Occlusion,
             query::Type::PipelineStatistics(_) => native::QueryHeapType::PipelineStatistics,
             query::Type::Timestamp => native::QueryHeapType::Timestamp,
+            query::Type::PerformanceCounters(_) => {
+                return Err(query::CreationError::Unsupported(query_ty))
+            }
         };
 
         let (query_heap, hr) = self.raw.create_query_heap(heap_ty, count, 0);
@@ -3544,6 +3691,7 @@ impl d::Device<B> for Device {
             query::Type::Occlusion => d3d12::D3D12_QUERY_TYPE_OCCLUSION,
             query::Type::PipelineStatistics(_) => d3d12::D3D12_QUERY_TYPE_PIPELINE_STATISTICS,
             query::Type::Timestamp => d3d12::D3D12_QUERY_TYPE_TIMESTAMP,
+            query::Type::PerformanceCounters(_) => unreachable!(),
         };
         com_list.ResolveQueryData(
             pool.raw.as_mut_ptr(),
@@ -3592,6 +3740,10 @@ impl d::Device<B> for Device {
         }
     }
 
+    unsafe fn reset_query_pool(&self, _pool: &r::QueryPool, _queries: Range<query::Id>) {
+        unimplemented!()
+    }
+
     unsafe fn destroy_shader_module(&self, shader_lib: r::ShaderModule) {
         if let r::ShaderModule::Compiled(shaders) = shader_lib {
             for (_, blob) in shaders {
@@ -3612,10 +3764,49 @@ impl d::Device<B> for Device {
         pipeline.raw.destroy();
     }
 
+    unsafe fn create_graphics_pipeline_library<'a>(
+        &self,
+        _desc: &pso::GraphicsPipelineDesc<'a, B>,
+        _parts: pso::GraphicsPipelineLibraryParts,
+        _cache: Option<&()>,
+    ) -> Result<(), pso::CreationError> {
+        unimplemented!()
+    }
+
+    unsafe fn link_graphics_pipeline<'a>(
+        &self,
+        _desc: &pso::GraphicsPipelineLinkDesc<'a, B>,
+        _cache: Option<&()>,
+    ) -> Result<r::GraphicsPipeline, pso::CreationError> {
+        unimplemented!()
+    }
+
+    unsafe fn destroy_graphics_pipeline_library(&self, _library: ()) {
+        unimplemented!()
+    }
+
     unsafe fn destroy_compute_pipeline(&self, pipeline: r::ComputePipeline) {
         pipeline.raw.destroy();
     }
 
+    unsafe fn get_graphics_pipeline_executables(
+        &self,
+        _pipeline: &r::GraphicsPipeline,
+    ) -> Vec<pso::PipelineExecutable> {
+        unimplemented!()
+    }
+
+    unsafe fn get_compute_pipeline_executables(
+        &self,
+        _pipeline: &r::ComputePipeline,
+    ) -> Vec<pso::PipelineExecutable> {
+        unimplemented!()
+    }
+
+    unsafe fn destroy_ray_tracing_pipeline(&self, _pipeline: ()) {
+        unimplemented!()
+    }
+
     unsafe fn destroy_framebuffer(&self, _fb: r::Framebuffer) {
         // Just drop
     }
@@ -3642,6 +3833,10 @@ impl d::Device<B> for Device {
         }
     }
 
+    unsafe fn destroy_acceleration_structure(&self, _accel_struct: ()) {
+        unimplemented!()
+    }
+
     unsafe fn destroy_image(&self, image: r::Image) {
         match image {
             r::Image::Bound(image) => {
@@ -3702,6 +3897,10 @@ impl d::Device<B> for Device {
         semaphore.raw.destroy();
     }
 
+    unsafe fn destroy_timeline_semaphore(&self, semaphore: r::TimelineSemaphore) {
+        semaphore.raw.destroy();
+    }
+
     unsafe fn destroy_event(&self, _event: ()) {
         unimplemented!()
     }
@@ -3776,6 +3975,27 @@ impl d::Device<B> for Device {
         pipeline_layout.shared.signature.SetName(cwstr.as_ptr());
     }
 
+    unsafe fn create_private_data_slot(&self) -> Result<(), d::OutOfMemory> {
+        unimplemented!()
+    }
+
+    unsafe fn destroy_private_data_slot(&self, _slot: ()) {
+        unimplemented!()
+    }
+
+    unsafe fn set_private_data(
+        &self,
+        _object: d::PrivateDataObject<B>,
+        _slot: &(),
+        _data: u64,
+    ) -> Result<(), d::OutOfMemory> {
+        unimplemented!()
+    }
+
+    unsafe fn get_private_data(&self, _object: d::PrivateDataObject<B>, _slot: &()) -> u64 {
+        unimplemented!()
+    }
+
     unsafe fn set_display_power_state(
         &self,
         _display: &display::Display<B>,
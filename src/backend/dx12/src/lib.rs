@@ -548,11 +548,46 @@ impl q::Queue<Backend> for Queue {
         }
     }
 
+    unsafe fn submit_timeline<'a, Ic, Iw, Is>(
+        &mut self,
+        command_buffers: Ic,
+        wait_semaphores: Iw,
+        signal_semaphores: Is,
+    ) where
+        Ic: Iterator<Item = &'a command::CommandBuffer>,
+        Iw: Iterator<Item = (&'a resource::TimelineSemaphore, u64, PipelineStage)>,
+        Is: Iterator<Item = (&'a resource::TimelineSemaphore, u64)>,
+    {
+        // Reset idle fence and event
+        // That's safe here due to exclusive access to the queue
+        self.idle_fence.signal(0);
+        synchapi::ResetEvent(self.idle_event.0);
+
+        // D3D12 fences are timelines, which the queue can wait for and signal by value.
+        for (semaphore, value, _stage) in wait_semaphores {
+            assert_eq!(
+                winerror::S_OK,
+                self.raw.Wait(semaphore.raw.as_mut_ptr(), value)
+            );
+        }
+        let lists = command_buffers
+            .map(|cmd_buf| cmd_buf.as_raw_list())
+            .collect::<SmallVec<[_; 4]>>();
+        self.raw
+            .ExecuteCommandLists(lists.len() as _, lists.as_ptr());
+        for (semaphore, value) in signal_semaphores {
+            assert_eq!(
+                winerror::S_OK,
+                self.raw.Signal(semaphore.raw.as_mut_ptr(), value)
+            );
+        }
+    }
+
     unsafe fn bind_sparse<'a, Iw, Is, Ibi, Ib, Iii, Io, Ii>(
         &mut self,
         _wait_semaphores: Iw,
         _signal_semaphores: Is,
-        _buffer_memory_binds: Ib,
+        buffer_memory_binds: Ib,
         _image_opaque_memory_binds: Io,
         image_memory_binds: Ii,
         device: &Device,
@@ -573,6 +608,82 @@ impl q::Queue<Backend> for Queue {
 
         // TODO: semaphores
 
+        // Buffers are tiled in units of the default placement alignment
+        let tile_size = d3d12::D3D12_DEFAULT_RESOURCE_PLACEMENT_ALIGNMENT as u64;
+        for (buffer, binds) in buffer_memory_binds {
+            // TODO avoid allocations
+            let mut resource_coords = Vec::new();
+            let mut region_sizes = Vec::new();
+            let mut range_flags = Vec::new();
+            let mut heap_range_start_offsets = Vec::new();
+            let mut range_tile_counts = Vec::new();
+
+            let mut heap: *mut d3d12::ID3D12Heap = std::ptr::null_mut();
+            for bind in binds {
+                let number_tiles = ((bind.size + tile_size - 1) / tile_size) as u32;
+                resource_coords.push(d3d12::D3D12_TILED_RESOURCE_COORDINATE {
+                    X: (bind.resource_offset / tile_size) as u32,
+                    Y: 0,
+                    Z: 0,
+                    Subresource: 0,
+                });
+                region_sizes.push(d3d12::D3D12_TILE_REGION_SIZE {
+                    NumTiles: number_tiles,
+                    UseBox: 0,
+                    Width: 0,
+                    Height: 0,
+                    Depth: 0,
+                });
+
+                if let Some((memory, memory_offset)) = bind.memory {
+                    // TODO multiple heap support
+                    if heap.is_null() {
+                        heap = memory.borrow().heap.as_mut_ptr();
+                    } else if cfg!(debug_assertions) {
+                        debug_assert_eq!(heap, memory.borrow().heap.as_mut_ptr());
+                    }
+                    range_flags.push(d3d12::D3D12_TILE_RANGE_FLAG_NONE);
+                    heap_range_start_offsets.push((memory_offset / tile_size) as u32);
+                } else {
+                    range_flags.push(d3d12::D3D12_TILE_RANGE_FLAG_NULL);
+                    heap_range_start_offsets.push(0);
+                }
+                range_tile_counts.push(number_tiles);
+            }
+
+            if let resource::Buffer::Unbound(ref buffer_unbound) = *buffer {
+                let mut resource = native::Resource::null();
+                assert_eq!(
+                    winerror::S_OK,
+                    device.raw.clone().CreateReservedResource(
+                        &Device::buffer_desc(buffer_unbound),
+                        d3d12::D3D12_RESOURCE_STATE_COMMON,
+                        std::ptr::null(),
+                        &d3d12::ID3D12Resource::uuidof(),
+                        resource.mut_void(),
+                    )
+                );
+                device.bind_buffer_resource(resource, buffer);
+            }
+            let resource = match *buffer {
+                resource::Buffer::Bound(ref bound) => bound.resource,
+                resource::Buffer::Unbound(_) => unreachable!(),
+            };
+
+            self.raw.UpdateTileMappings(
+                resource.as_mut_ptr(),
+                resource_coords.len() as u32,
+                resource_coords.as_ptr(),
+                region_sizes.as_ptr(),
+                heap,
+                range_flags.len() as u32,
+                range_flags.as_ptr(),
+                heap_range_start_offsets.as_ptr(),
+                range_tile_counts.as_ptr(),
+                d3d12::D3D12_TILE_MAPPING_FLAG_NONE,
+            );
+        }
+
         for (image, binds) in image_memory_binds {
             let image = image.borrow_mut();
 
@@ -690,7 +801,7 @@ impl q::Queue<Backend> for Queue {
                 }
             }
         }
-        // TODO sparse buffers and opaque images iterated here
+        // TODO opaque images iterated here
 
         if let Some(fence) = fence {
             assert_eq!(winerror::S_OK, self.raw.Signal(fence.raw.as_mut_ptr(), 1));
@@ -1388,6 +1499,8 @@ impl hal::Instance<Backend> for Instance {
                     Features::INDEPENDENT_BLENDING |
                     Features::SAMPLE_RATE_SHADING |
                     Features::FRAGMENT_STORES_AND_ATOMICS |
+                    Features::TIMELINE_SEMAPHORE |
+                    Features::CONDITIONAL_RENDERING |
                     tiled_resource_features |
                     conservative_faster_features,
                 properties: PhysicalDeviceProperties {
@@ -1486,6 +1599,13 @@ impl hal::Instance<Backend> for Instance {
                         | hal::DynamicStates::BLEND_CONSTANTS
                         | hal::DynamicStates::STENCIL_REFERENCE,
                     downlevel: hal::DownlevelProperties::all_enabled(),
+                    // Aliased lines follow the diamond-exit rule, and the alpha
+                    // antialiased lines match the smooth ones.
+                    line_rasterization: hal::LineRasterizationProperties {
+                        bresenham_lines: true,
+                        smooth_lines: true,
+                        ..hal::LineRasterizationProperties::default()
+                    },
                     ..PhysicalDeviceProperties::default()
                 },
                 format_properties: Arc::new(FormatProperties::new(device)),
@@ -1565,9 +1685,12 @@ impl hal::Backend for Backend {
     type Image = resource::Image;
     type ImageView = resource::ImageView;
     type Sampler = resource::Sampler;
+    type AccelerationStructure = ();
 
     type ComputePipeline = resource::ComputePipeline;
+    type RayTracingPipeline = ();
     type GraphicsPipeline = resource::GraphicsPipeline;
+    type GraphicsPipelineLibrary = ();
     type PipelineLayout = resource::PipelineLayout;
     type PipelineCache = ();
     type DescriptorSetLayout = resource::DescriptorSetLayout;
@@ -1576,8 +1699,10 @@ impl hal::Backend for Backend {
 
     type Fence = resource::Fence;
     type Semaphore = resource::Semaphore;
+    type TimelineSemaphore = resource::TimelineSemaphore;
     type Event = ();
     type QueryPool = resource::QueryPool;
+    type PrivateDataSlot = ();
 
     type Display = ();
     type DisplayMode = ();
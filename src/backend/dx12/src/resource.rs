@@ -436,6 +436,14 @@ pub struct Semaphore {
 unsafe impl Send for Semaphore {}
 unsafe impl Sync for Semaphore {}
 
+#[derive(Debug)]
+pub struct TimelineSemaphore {
+    pub(crate) raw: native::Fence,
+}
+
+unsafe impl Send for TimelineSemaphore {}
+unsafe impl Sync for TimelineSemaphore {}
+
 #[derive(Debug)]
 pub struct Memory {
     pub(crate) heap: native::Heap,
@@ -521,6 +529,8 @@ impl From<pso::DescriptorType> for DescriptorContent {
                 },
             },
             Dt::InputAttachment => Dc::SRV,
+            Dt::AccelerationStructure => Dc::SRV,
+            Dt::Mutable { .. } => unimplemented!(),
         }
     }
 }
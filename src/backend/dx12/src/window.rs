@@ -119,6 +119,8 @@ impl w::Surface<Backend> for Surface {
         w::SurfaceCapabilities {
             present_modes,
             composite_alpha_modes: w::CompositeAlphaMode::OPAQUE, //TODO
+            current_transform: w::SurfaceTransform::IDENTITY,
+            transforms: w::SurfaceTransform::IDENTITY,
             image_count: 2..=16, // we currently use a flip effect which supports 2..=16 buffers
             current_extent,
             extents: w::Extent2D {
@@ -130,6 +132,8 @@ impl w::Surface<Backend> for Surface {
             },
             max_image_layers: 1,
             usage: i::Usage::COLOR_ATTACHMENT | i::Usage::TRANSFER_SRC | i::Usage::TRANSFER_DST,
+            swapchain_maintenance: false,
+            present_wait: false,
         }
     }
 
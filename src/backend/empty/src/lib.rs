@@ -13,7 +13,7 @@ use crate::{
 use hal::{adapter, command, device, display, format, pass, pool, pso, query, queue, window};
 use log::debug;
 
-use std::{borrow::Borrow, ops::Range};
+use std::{borrow::Borrow, collections::VecDeque, ops::Range};
 
 mod buffer;
 mod descriptor;
@@ -47,9 +47,12 @@ impl hal::Backend for Backend {
     type Image = Image;
     type ImageView = ();
     type Sampler = ();
+    type AccelerationStructure = ();
 
     type ComputePipeline = ();
+    type RayTracingPipeline = ();
     type GraphicsPipeline = ();
+    type GraphicsPipelineLibrary = ();
     type PipelineCache = ();
     type PipelineLayout = ();
     type DescriptorSetLayout = DescriptorSetLayout;
@@ -58,8 +61,10 @@ impl hal::Backend for Backend {
 
     type Fence = ();
     type Semaphore = ();
+    type TimelineSemaphore = ();
     type Event = ();
     type QueryPool = ();
+    type PrivateDataSlot = ();
 
     type Display = ();
     type DisplayMode = ();
@@ -200,13 +205,22 @@ impl queue::Queue<Backend> for Queue {
     {
     }
 
+    unsafe fn submit_timeline<'a, Ic, Iw, Is>(&mut self, _: Ic, _: Iw, _: Is)
+    where
+        Ic: Iterator<Item = &'a CommandBuffer>,
+    {
+    }
+
     unsafe fn present(
         &mut self,
-        _surface: &mut Surface,
+        surface: &mut Surface,
         _image: SwapchainImage,
         _wait_semaphore: Option<&mut ()>,
     ) -> Result<Option<window::Suboptimal>, window::PresentError> {
-        Ok(None)
+        match surface.present_errors.pop_front() {
+            Some(err) => Err(err),
+            None => Ok(None),
+        }
     }
 
     fn wait_idle(&mut self) -> Result<(), device::OutOfMemory> {
@@ -294,6 +308,45 @@ impl device::Device<Backend> for Device {
         unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
     }
 
+    unsafe fn create_ray_tracing_pipeline<'a>(
+        &self,
+        _: &pso::RayTracingPipelineDesc<'a, Backend>,
+        _: Option<&()>,
+    ) -> Result<(), pso::CreationError> {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
+    unsafe fn get_ray_tracing_shader_group_handles(
+        &self,
+        _: &(),
+        _: u32,
+        _: u32,
+    ) -> Result<Vec<u8>, device::OutOfMemory> {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
+    unsafe fn get_acceleration_structure_build_requirements(
+        &self,
+        _: &hal::acceleration_structure::GeometryDesc<Backend>,
+        _: &[u32],
+    ) -> hal::acceleration_structure::SizeRequirements {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
+    unsafe fn create_acceleration_structure(
+        &self,
+        _: &hal::acceleration_structure::CreateDesc<Backend>,
+    ) -> Result<(), device::OutOfMemory> {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
+    unsafe fn get_acceleration_structure_address(
+        &self,
+        _: &(),
+    ) -> hal::acceleration_structure::DeviceAddress {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
     unsafe fn merge_pipeline_caches<'a, I>(
         &self,
         _: &mut (),
@@ -444,6 +497,27 @@ impl device::Device<Backend> for Device {
         Ok(())
     }
 
+    fn create_timeline_semaphore(&self, _: u64) -> Result<(), device::OutOfMemory> {
+        Ok(())
+    }
+
+    unsafe fn get_timeline_semaphore_value(&self, _: &()) -> Result<u64, device::DeviceLost> {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
+    unsafe fn signal_timeline_semaphore(&self, _: &(), _: u64) -> Result<(), device::OutOfMemory> {
+        Ok(())
+    }
+
+    unsafe fn wait_for_timeline_semaphore(
+        &self,
+        _: &(),
+        _: u64,
+        _: u64,
+    ) -> Result<bool, device::WaitError> {
+        Ok(true)
+    }
+
     fn create_fence(&self, _: bool) -> Result<(), device::OutOfMemory> {
         Ok(())
     }
@@ -487,6 +561,10 @@ impl device::Device<Backend> for Device {
         unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
     }
 
+    unsafe fn reset_query_pool(&self, _: &(), _: Range<query::Id>) {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
     unsafe fn map_memory(
         &self,
         memory: &mut Memory,
@@ -523,9 +601,39 @@ impl device::Device<Backend> for Device {
 
     unsafe fn destroy_graphics_pipeline(&self, _: ()) {}
 
+    unsafe fn create_graphics_pipeline_library<'a>(
+        &self,
+        _: &pso::GraphicsPipelineDesc<'a, Backend>,
+        _: pso::GraphicsPipelineLibraryParts,
+        _: Option<&()>,
+    ) -> Result<(), pso::CreationError> {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
+    unsafe fn link_graphics_pipeline<'a>(
+        &self,
+        _: &pso::GraphicsPipelineLinkDesc<'a, Backend>,
+        _: Option<&()>,
+    ) -> Result<(), pso::CreationError> {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
+    unsafe fn destroy_graphics_pipeline_library(&self, _: ()) {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
     unsafe fn destroy_compute_pipeline(&self, _: ()) {
         unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
     }
+    unsafe fn get_graphics_pipeline_executables(&self, _: &()) -> Vec<pso::PipelineExecutable> {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+    unsafe fn get_compute_pipeline_executables(&self, _: &()) -> Vec<pso::PipelineExecutable> {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+    unsafe fn destroy_ray_tracing_pipeline(&self, _: ()) {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
     unsafe fn destroy_framebuffer(&self, _: ()) {}
 
     unsafe fn destroy_buffer(&self, _: Buffer) {}
@@ -534,6 +642,10 @@ impl device::Device<Backend> for Device {
         unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
     }
 
+    unsafe fn destroy_acceleration_structure(&self, _: ()) {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
     unsafe fn destroy_image(&self, _: Image) {}
 
     unsafe fn destroy_image_view(&self, _: ()) {}
@@ -548,6 +660,8 @@ impl device::Device<Backend> for Device {
 
     unsafe fn destroy_semaphore(&self, _: ()) {}
 
+    unsafe fn destroy_timeline_semaphore(&self, _: ()) {}
+
     unsafe fn destroy_event(&self, _: ()) {
         unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
     }
@@ -596,6 +710,27 @@ impl device::Device<Backend> for Device {
         unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
     }
 
+    unsafe fn create_private_data_slot(&self) -> Result<(), device::OutOfMemory> {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
+    unsafe fn destroy_private_data_slot(&self, _: ()) {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
+    unsafe fn set_private_data(
+        &self,
+        _: device::PrivateDataObject<Backend>,
+        _: &(),
+        _: u64,
+    ) -> Result<(), device::OutOfMemory> {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
+    unsafe fn get_private_data(&self, _: device::PrivateDataObject<Backend>, _: &()) -> u64 {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
     unsafe fn reset_fence(&self, _: &mut ()) -> Result<(), device::OutOfMemory> {
         Ok(())
     }
@@ -796,6 +931,46 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
     }
 
+    unsafe fn set_cull_face(&mut self, _: pso::Face) {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
+    unsafe fn set_front_face(&mut self, _: pso::FrontFace) {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
+    unsafe fn set_primitive_topology(&mut self, _: pso::Primitive) {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
+    unsafe fn set_depth_test(&mut self, _: Option<pso::DepthTest>) {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
+    unsafe fn set_stencil_faces(&mut self, _: pso::Face, _: pso::StencilFace) {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
+    unsafe fn set_vertex_input(&mut self, _: &[pso::VertexBufferDesc], _: &[pso::AttributeDesc]) {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
+    unsafe fn set_primitive_restart(&mut self, _enable: bool) {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
+    unsafe fn set_fragment_shading_rate(
+        &mut self,
+        _: window::Extent2D,
+        _: [pso::ShadingRateCombinerOp; 2],
+    ) {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
+    unsafe fn set_sample_locations(&mut self, _: &[pso::SampleLocation]) {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
     unsafe fn begin_render_pass<'a, T>(
         &mut self,
         _: &(),
@@ -814,6 +989,14 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
 
     unsafe fn end_render_pass(&mut self) {}
 
+    unsafe fn begin_rendering(&mut self, _: &command::RenderingInfo<Backend>) {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
+    unsafe fn end_rendering(&mut self) {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
     unsafe fn bind_graphics_pipeline(&mut self, _: &()) {}
 
     unsafe fn bind_graphics_descriptor_sets<'a, I, J>(&mut self, _: &(), _: usize, _: I, _: J)
@@ -842,6 +1025,36 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
     }
 
+    unsafe fn build_acceleration_structure(
+        &mut self,
+        _: &hal::acceleration_structure::BuildDesc<Backend>,
+        _: &[hal::acceleration_structure::BuildRangeDesc],
+    ) {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
+    unsafe fn bind_ray_tracing_pipeline(&mut self, _: &()) {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
+    unsafe fn bind_ray_tracing_descriptor_sets<'a, I, J>(&mut self, _: &(), _: usize, _: I, _: J)
+    where
+        I: Iterator<Item = &'a DescriptorSet>,
+    {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
+    unsafe fn trace_rays(
+        &mut self,
+        _: &pso::ShaderBindingTable<Backend>,
+        _: Option<&pso::ShaderBindingTable<Backend>>,
+        _: Option<&pso::ShaderBindingTable<Backend>>,
+        _: Option<&pso::ShaderBindingTable<Backend>>,
+        _: [u32; 3],
+    ) {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
     unsafe fn copy_buffer<T>(&mut self, _: &Buffer, _: &Buffer, _: T) {
         unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
     }
@@ -995,6 +1208,19 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
     }
 
+    unsafe fn begin_conditional_rendering(
+        &mut self,
+        _: &Buffer,
+        _: hal::buffer::Offset,
+        _: command::ConditionalRenderingFlags,
+    ) {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
+    unsafe fn end_conditional_rendering(&mut self) {
+        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    }
+
     unsafe fn push_graphics_constants(
         &mut self,
         _: &(),
@@ -1028,8 +1254,19 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
 }
 
 /// Dummy surface.
-#[derive(Debug)]
-pub struct Surface;
+///
+/// Its fields can be set to simulate the events of a window, such as a resize.
+#[derive(Debug, Default)]
+pub struct Surface {
+    /// Extent reported by the capabilities, `None` to let the swapchain decide.
+    pub current_extent: Option<window::Extent2D>,
+    /// Errors returned by the next image acquisitions, in order.
+    pub acquire_errors: VecDeque<window::AcquireError>,
+    /// Errors returned by the next presentations, in order.
+    pub present_errors: VecDeque<window::PresentError>,
+    /// Configurations of the swapchain, in order.
+    pub configurations: Vec<window::SwapchainConfig>,
+}
 impl window::Surface<Backend> for Surface {
     fn supports_queue_family(&self, _: &QueueFamily) -> bool {
         true
@@ -1052,12 +1289,16 @@ impl window::Surface<Backend> for Surface {
         let composite_alpha_modes = window::CompositeAlphaMode::OPAQUE;
         window::SurfaceCapabilities {
             image_count: 1..=1,
-            current_extent: None,
+            current_extent: self.current_extent,
             extents,
             max_image_layers: 1,
             usage,
             present_modes,
             composite_alpha_modes,
+            current_transform: window::SurfaceTransform::IDENTITY,
+            transforms: window::SurfaceTransform::IDENTITY,
+            swapchain_maintenance: false,
+            present_wait: false,
         }
     }
 
@@ -1085,8 +1326,9 @@ impl window::PresentationSurface<Backend> for Surface {
     unsafe fn configure_swapchain(
         &mut self,
         _: &Device,
-        _: window::SwapchainConfig,
+        config: window::SwapchainConfig,
     ) -> Result<(), window::SwapchainError> {
+        self.configurations.push(config);
         Ok(())
     }
 
@@ -1096,7 +1338,10 @@ impl window::PresentationSurface<Backend> for Surface {
         &mut self,
         _: u64,
     ) -> Result<(SwapchainImage, Option<window::Suboptimal>), window::AcquireError> {
-        Ok((SwapchainImage, None))
+        match self.acquire_errors.pop_front() {
+            Some(err) => Err(err),
+            None => Ok((SwapchainImage, None)),
+        }
     }
 }
 
@@ -1135,7 +1380,7 @@ impl hal::Instance<Backend> for Instance {
     ) -> Result<Surface, hal::window::InitError> {
         // TODO: maybe check somehow that the given handle is valid?
         let _handle = raw_window_handle.raw_window_handle();
-        Ok(Surface)
+        Ok(Surface::default())
     }
 
     unsafe fn destroy_surface(&self, _surface: Surface) {}
@@ -0,0 +1,101 @@
+//! Bindless textures (`GL_ARB_bindless_texture`).
+//!
+//! A texture, optionally combined with a sampler, is referenced by a 64-bit handle
+//! instead of a texture unit. Once resident, the handle can be written into uniform
+//! or storage buffers, and sampled by shaders declaring the extension.
+
+use crate::native::{Sampler, Texture};
+
+use std::fmt;
+
+/// Entry points of `GL_ARB_bindless_texture`, which aren't exposed by `glow`.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct BindlessTextureFns {
+    get_texture_handle: unsafe extern "system" fn(u32) -> u64,
+    get_texture_sampler_handle: unsafe extern "system" fn(u32, u32) -> u64,
+    make_texture_handle_resident: unsafe extern "system" fn(u64),
+    make_texture_handle_non_resident: unsafe extern "system" fn(u64),
+}
+
+/// Bindless textures are not available on WebGL.
+#[cfg(target_arch = "wasm32")]
+pub(crate) enum BindlessTextureFns {}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl BindlessTextureFns {
+    /// Load the entry points with `get_proc_address`.
+    pub(crate) unsafe fn load(
+        mut get_proc_address: impl FnMut(&str) -> *const std::ffi::c_void,
+    ) -> Option<Self> {
+        let get_texture_handle = get_proc_address("glGetTextureHandleARB");
+        let get_texture_sampler_handle = get_proc_address("glGetTextureSamplerHandleARB");
+        let make_texture_handle_resident = get_proc_address("glMakeTextureHandleResidentARB");
+        let make_texture_handle_non_resident =
+            get_proc_address("glMakeTextureHandleNonResidentARB");
+        if get_texture_handle.is_null()
+            || get_texture_sampler_handle.is_null()
+            || make_texture_handle_resident.is_null()
+            || make_texture_handle_non_resident.is_null()
+        {
+            return None;
+        }
+        Some(BindlessTextureFns {
+            get_texture_handle: std::mem::transmute(get_texture_handle),
+            get_texture_sampler_handle: std::mem::transmute(get_texture_sampler_handle),
+            make_texture_handle_resident: std::mem::transmute(make_texture_handle_resident),
+            make_texture_handle_non_resident: std::mem::transmute(make_texture_handle_non_resident),
+        })
+    }
+
+    /// Get the handle of `texture`, sampled with `sampler` or with its own parameters.
+    ///
+    /// The texture and the sampler can't be modified afterwards.
+    /// Returns `None` if the driver failed to create the handle, e.g. for an incomplete texture.
+    pub(crate) unsafe fn get_handle(
+        &self,
+        texture: Texture,
+        sampler: Option<Sampler>,
+    ) -> Option<u64> {
+        let handle = match sampler {
+            Some(sampler) => (self.get_texture_sampler_handle)(texture, sampler),
+            None => (self.get_texture_handle)(texture),
+        };
+        // Zero is never a valid handle, it's returned on errors.
+        if handle == 0 {
+            None
+        } else {
+            Some(handle)
+        }
+    }
+
+    /// Make the texture of `handle` accessible to shaders.
+    pub(crate) unsafe fn make_resident(&self, handle: u64) {
+        (self.make_texture_handle_resident)(handle);
+    }
+
+    /// Make the texture of `handle` inaccessible to shaders.
+    pub(crate) unsafe fn make_non_resident(&self, handle: u64) {
+        (self.make_texture_handle_non_resident)(handle);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl BindlessTextureFns {
+    pub(crate) unsafe fn get_handle(&self, _: Texture, _: Option<Sampler>) -> Option<u64> {
+        match *self {}
+    }
+
+    pub(crate) unsafe fn make_resident(&self, _: u64) {
+        match *self {}
+    }
+
+    pub(crate) unsafe fn make_non_resident(&self, _: u64) {
+        match *self {}
+    }
+}
+
+impl fmt::Debug for BindlessTextureFns {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("BindlessTextureFns")
+    }
+}
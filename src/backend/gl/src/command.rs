@@ -73,6 +73,21 @@ pub enum Command {
         base_vertex: hal::VertexOffset,
         instances: Range<hal::InstanceCount>,
     },
+    DrawIndirect {
+        primitive: u32,
+        buffer: n::RawBuffer,
+        offset: buffer::Offset,
+        draw_count: hal::DrawCount,
+        stride: buffer::Stride,
+    },
+    DrawIndexedIndirect {
+        primitive: u32,
+        index_type: u32,
+        buffer: n::RawBuffer,
+        offset: buffer::Offset,
+        draw_count: hal::DrawCount,
+        stride: buffer::Stride,
+    },
     BindIndexBuffer(n::RawBuffer),
     //BindVertexBuffers(BufferSlice),
     BindUniform {
@@ -83,6 +98,8 @@ pub enum Command {
         rasterizer: pso::Rasterizer,
     },
     BindDepth(Option<pso::Comparison>),
+    /// Bind a stencil test with resolved dynamic states, for the faces that aren't culled.
+    BindStencil(Option<pso::StencilTest>, pso::Face),
     SetViewports {
         first_viewport: u32,
         viewport_ptr: BufferSlice,
@@ -110,11 +127,14 @@ pub enum Command {
     },
     SetDrawColorBuffers(ArrayVec<[u8; MAX_COLOR_ATTACHMENTS]>),
     SetPatchSize(i32),
+    SetClipDistances(u32),
+    SetPrimitiveRestart(bool),
     BindProgram(<GlContext as glow::HasContext>::Program),
     SetBlend(Option<pso::BlendState>),
     SetBlendSlot(ColorSlot, Option<pso::BlendState>),
     BindAttribute(n::AttributeDesc, n::RawBuffer, i32, u32),
-    //UnbindAttribute(n::AttributeDesc),
+    /// Disable the vertex attribute arrays whose locations are not in the mask.
+    SetActiveAttributes(u32),
     CopyBufferToBuffer {
         src_buffer: n::RawBuffer,
         dst_buffer: n::RawBuffer,
@@ -128,9 +148,12 @@ pub enum Command {
         texture_target: n::TextureTarget,
         texture_format: n::TextureFormat,
         pixel_type: n::DataType,
+        /// Size of the copied data, if the texture has a compressed format.
+        compressed_size: Option<buffer::Offset>,
         data: command::BufferImageCopy,
     },
     CopyBufferToRenderbuffer(n::RawBuffer, n::Renderbuffer, command::BufferImageCopy),
+    /// Read a single layer or depth slice of a texture into a buffer.
     CopyTextureToBuffer {
         src_texture: n::Texture,
         texture_target: n::TextureTarget,
@@ -139,7 +162,13 @@ pub enum Command {
         dst_buffer: n::RawBuffer,
         data: command::BufferImageCopy,
     },
-    CopyRenderbufferToBuffer(n::Renderbuffer, n::RawBuffer, command::BufferImageCopy),
+    CopyRenderbufferToBuffer {
+        src_renderbuffer: n::Renderbuffer,
+        texture_format: n::TextureFormat,
+        pixel_type: n::DataType,
+        dst_buffer: n::RawBuffer,
+        data: command::BufferImageCopy,
+    },
     CopyImageToTexture(
         n::ImageType,
         n::Texture,
@@ -161,6 +190,9 @@ pub enum Command {
     SetStencilMask(pso::StencilValue),
     SetStencilMaskSeparate(pso::Sided<pso::StencilValue>),
     MemoryBarrier(u32),
+    PushDebugGroup(BufferSlice),
+    PopDebugGroup,
+    InsertDebugMarker(BufferSlice),
 }
 
 pub type FrameBufferTarget = u32;
@@ -192,8 +224,20 @@ struct Cache {
     primitive: Option<u32>,
     // Active index type and buffer range, set by the current index buffer.
     index_type_range: Option<(hal::IndexType, Range<buffer::Offset>)>,
-    // Stencil reference values (front, back).
-    stencil_ref: Option<(pso::StencilValue, pso::StencilValue)>,
+    // Rasterizer state, set by the current pipeline.
+    rasterizer: Option<pso::Rasterizer>,
+    // Depth test function, set by the current pipeline.
+    depth_fun: Option<Option<pso::Comparison>>,
+    // Stencil test of the current pipeline, with its dynamic states unresolved.
+    stencil: Option<pso::StencilTest>,
+    // Last bound stencil test, and the culled faces it was bound for.
+    bound_stencil: Option<(Option<pso::StencilTest>, pso::Face)>,
+    // Dynamic stencil reference values.
+    stencil_ref: Option<pso::Sided<pso::StencilValue>>,
+    // Dynamic stencil read masks.
+    stencil_read_mask: Option<pso::Sided<pso::StencilValue>>,
+    // Dynamic stencil write masks.
+    stencil_write_mask: Option<pso::Sided<pso::StencilValue>>,
     // Blend color.
     blend_color: Option<pso::ColorValue>,
     ///
@@ -203,6 +247,10 @@ struct Cache {
     error_state: bool,
     // Vertices per patch for tessellation primitives (patches).
     patch_size: Option<i32>,
+    // Number of enabled clip distances.
+    clip_distance_count: Option<u32>,
+    // Whether primitive restart is enabled.
+    primitive_restart: Option<bool>,
     // Active program name.
     program: Option<n::Program>,
     // Blend per attachment.
@@ -230,11 +278,19 @@ impl Cache {
         Cache {
             primitive: None,
             index_type_range: None,
+            rasterizer: None,
+            depth_fun: None,
+            stencil: None,
+            bound_stencil: None,
             stencil_ref: None,
+            stencil_read_mask: None,
+            stencil_write_mask: None,
             blend_color: None,
             framebuffer: None,
             error_state: false,
             patch_size: None,
+            clip_distance_count: None,
+            primitive_restart: None,
             program: None,
             blend_targets: Vec::new(),
             vertex_buffers: Vec::new(),
@@ -249,6 +305,42 @@ impl Cache {
     }
 }
 
+/// Set the dynamic stencil `value` of the given `faces`, keeping the one of the other face.
+fn update_sided(
+    sided: &mut Option<pso::Sided<pso::StencilValue>>,
+    faces: pso::Face,
+    value: pso::StencilValue,
+    default: pso::StencilValue,
+) {
+    assert!(!faces.is_empty());
+
+    let mut new = sided.unwrap_or(pso::Sided::new(default));
+    if faces.contains(pso::Face::FRONT) {
+        new.front = value;
+    }
+    if faces.contains(pso::Face::BACK) {
+        new.back = value;
+    }
+    *sided = Some(new);
+}
+
+/// Size of the data of `width` by `height` texels in a buffer, in whole blocks for compressed formats.
+fn image_data_size(desc: &hal::format::FormatDesc, width: u32, height: u32) -> buffer::Offset {
+    let (block_width, block_height) = desc.dim;
+    let row_blocks = (width + block_width as u32 - 1) / block_width as u32;
+    let rows = (height + block_height as u32 - 1) / block_height as u32;
+    (row_blocks * rows) as buffer::Offset * (desc.bits / 8) as buffer::Offset
+}
+
+/// Replace a dynamic stencil state by the values set on the command buffer.
+fn resolve_sided(
+    state: pso::StencilValues,
+    dynamic: Option<pso::Sided<pso::StencilValue>>,
+    default: pso::StencilValue,
+) -> pso::StencilValues {
+    pso::State::Static(state.static_or(dynamic.unwrap_or(pso::Sided::new(default))))
+}
+
 // This is a subset of the device limits stripped down to the ones needed
 // for command buffer validation.
 #[derive(Debug, Clone, Copy)]
@@ -489,14 +581,17 @@ impl CommandBuffer {
             ..
         } = self.cache;
 
+        let mut active_attributes = 0;
         for attribute in attributes {
             let binding = attribute.binding as usize;
 
-            if vertex_buffers.len() <= binding {
-                log::error!("No vertex buffer bound at {}", binding);
-            }
-
-            let (handle, range) = vertex_buffers[binding].as_ref().unwrap();
+            let (handle, range) = match vertex_buffers.get(binding) {
+                Some(&Some(ref bound)) => bound,
+                _ => {
+                    log::error!("No vertex buffer bound at {}", binding);
+                    continue;
+                }
+            };
 
             let mut attribute = attribute.clone();
             attribute.offset += range.start as u32;
@@ -507,6 +602,7 @@ impl CommandBuffer {
                         attribute.offset += desc.stride * first_instance as u32;
                     }
 
+                    active_attributes |= 1 << attribute.location;
                     self.data.push_cmd(Command::BindAttribute(
                         attribute,
                         *handle,
@@ -517,6 +613,10 @@ impl CommandBuffer {
                 _ => log::error!("No vertex buffer description bound at {}", binding),
             }
         }
+        // Attributes enabled by a previous pipeline would otherwise keep reading
+        // from their old buffers.
+        self.data
+            .push_cmd(Command::SetActiveAttributes(active_attributes));
     }
 
     fn begin_subpass(&mut self) {
@@ -630,6 +730,35 @@ impl CommandBuffer {
         }
     }
 
+    /// Bind the stencil test of the current pipeline, with its dynamic states
+    /// resolved, if it differs from the bound one.
+    fn update_stencil(&mut self) {
+        let cull_face = match self.cache.rasterizer {
+            Some(ref rasterizer) => rasterizer.cull_face,
+            // Dynamic states are only applied once a pipeline is bound.
+            None => return,
+        };
+        let cache = &self.cache;
+        let stencil = cache.stencil.map(|stencil| pso::StencilTest {
+            reference_values: resolve_sided(stencil.reference_values, cache.stencil_ref, 0),
+            read_masks: resolve_sided(stencil.read_masks, cache.stencil_read_mask, !0),
+            write_masks: resolve_sided(stencil.write_masks, cache.stencil_write_mask, !0),
+            ..stencil
+        });
+
+        if self.cache.bound_stencil != Some((stencil, cull_face)) {
+            self.cache.bound_stencil = Some((stencil, cull_face));
+            if let Some(pso::StencilTest {
+                write_masks: pso::State::Static(masks),
+                ..
+            }) = stencil
+            {
+                self.cache.stencil_mask = Some(masks);
+            }
+            self.data.push_cmd(Command::BindStencil(stencil, cull_face));
+        }
+    }
+
     fn update_sampler_states(&mut self, dirty_textures: u32, dirty_samplers: u32) {
         for (texture_index, slot) in self.cache.texture_slots.iter().enumerate() {
             if let Some(sampler_index) = slot.sampler_index {
@@ -664,10 +793,6 @@ impl CommandBuffer {
         I: Iterator<Item = &'a n::DescriptorSet>,
         J: Iterator<Item = command::DescriptorSetOffset>,
     {
-        if let Some(_) = offsets.next() {
-            log::warn!("Dynamic offsets are not supported yet");
-        }
-
         let mut dirty_textures = 0u32;
         let mut dirty_samplers = 0u32;
         let mut set = first_set as usize;
@@ -688,12 +813,23 @@ impl CommandBuffer {
                             n::BindingRegister::StorageBuffers => glow::SHADER_STORAGE_BUFFER,
                             n::BindingRegister::Textures => panic!("Wrong desc set binding"),
                         };
+                        // Dynamic offsets are consumed in the order of the sets and bindings.
+                        let dynamic_offset = match binding_layout.ty {
+                            pso::DescriptorType::Buffer {
+                                format:
+                                    pso::BufferDescriptorFormat::Structured {
+                                        dynamic_offset: true,
+                                    },
+                                ..
+                            } => offsets.next().expect("Missing dynamic offset"),
+                            _ => 0,
+                        };
                         self.data.push_cmd(Command::BindBufferRange(
                             bind_point,
                             binding,
                             buffer,
-                            offset as i32,
-                            size as i32,
+                            offset + dynamic_offset as i32,
+                            size,
                         ));
                     }
                     n::DescSetBindings::Texture(texture, textype) => {
@@ -771,11 +907,19 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
                     if access.start.contains(buffer::Access::SHADER_WRITE) {
                         mask |= glow::SHADER_STORAGE_BARRIER_BIT;
                     }
+                    // Persistently mapped memory sees the writes of the device
+                    // after the barrier, and the fence of the submission.
+                    if access.end.contains(buffer::Access::HOST_READ) {
+                        mask |= glow::CLIENT_MAPPED_BUFFER_BARRIER_BIT;
+                    }
                 }
                 memory::Barrier::Buffer { states, .. } => {
                     if states.start.contains(buffer::Access::SHADER_WRITE) {
                         mask |= glow::SHADER_STORAGE_BARRIER_BIT;
                     }
+                    if states.end.contains(buffer::Access::HOST_READ) {
+                        mask |= glow::CLIENT_MAPPED_BUFFER_BARRIER_BIT;
+                    }
                 }
                 memory::Barrier::AllImages(access) => {
                     if access.start.contains(image::Access::SHADER_WRITE) {
@@ -858,6 +1002,11 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
 
         //Note: we currently only expect one sub-pass.
 
+        if let Some(ref label) = render_pass.label {
+            let label = self.data.add_raw(label.as_bytes());
+            self.data.push_cmd(Command::PushDebugGroup(label));
+        }
+
         self.data.push_cmd(Command::BindFramebuffer {
             target: glow::DRAW_FRAMEBUFFER,
             framebuffer: framebuffer.raw,
@@ -875,7 +1024,21 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
     }
 
     unsafe fn end_render_pass(&mut self) {
-        // TODO
+        let labeled = self
+            .pass_cache
+            .as_ref()
+            .map_or(false, |cache| cache.render_pass.label.is_some());
+        if labeled {
+            self.data.push_cmd(Command::PopDebugGroup);
+        }
+    }
+
+    unsafe fn begin_rendering(&mut self, _info: &command::RenderingInfo<Backend>) {
+        unimplemented!()
+    }
+
+    unsafe fn end_rendering(&mut self) {
+        unimplemented!()
     }
 
     unsafe fn clear_image<T>(
@@ -914,6 +1077,7 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
                             target,
                             raw,
                             is_3d,
+                            skip_srgb: false,
                             sub: image::SubresourceRange {
                                 aspects: Aspects::COLOR,
                                 layer_start: 0,
@@ -1109,37 +1273,18 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
     }
 
     unsafe fn set_stencil_reference(&mut self, faces: pso::Face, value: pso::StencilValue) {
-        assert!(!faces.is_empty());
-
-        let mut front = 0;
-        let mut back = 0;
-
-        if let Some((last_front, last_back)) = self.cache.stencil_ref {
-            front = last_front;
-            back = last_back;
-        }
-
-        if faces.contains(pso::Face::FRONT) {
-            front = value;
-        }
-
-        if faces.contains(pso::Face::BACK) {
-            back = value;
-        }
-
-        // Only cache the stencil references values until
-        // we assembled all the pieces to set the stencil state
-        // from the pipeline.
-        self.cache.stencil_ref = Some((front, back));
+        update_sided(&mut self.cache.stencil_ref, faces, value, 0);
+        self.update_stencil();
     }
 
-    unsafe fn set_stencil_read_mask(&mut self, _faces: pso::Face, _value: pso::StencilValue) {
-        unimplemented!();
+    unsafe fn set_stencil_read_mask(&mut self, faces: pso::Face, value: pso::StencilValue) {
+        update_sided(&mut self.cache.stencil_read_mask, faces, value, !0);
+        self.update_stencil();
     }
 
-    unsafe fn set_stencil_write_mask(&mut self, _faces: pso::Face, _value: pso::StencilValue) {
-        // set self.cache.stencil_mask once implemented
-        unimplemented!();
+    unsafe fn set_stencil_write_mask(&mut self, faces: pso::Face, value: pso::StencilValue) {
+        update_sided(&mut self.cache.stencil_write_mask, faces, value, !0);
+        self.update_stencil();
     }
 
     unsafe fn set_blend_constants(&mut self, cv: pso::ColorValue) {
@@ -1161,6 +1306,53 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         unimplemented!()
     }
 
+    unsafe fn set_cull_face(&mut self, _faces: pso::Face) {
+        unimplemented!()
+    }
+
+    unsafe fn set_front_face(&mut self, _front_face: pso::FrontFace) {
+        unimplemented!()
+    }
+
+    unsafe fn set_primitive_topology(&mut self, _primitive: pso::Primitive) {
+        unimplemented!()
+    }
+
+    unsafe fn set_depth_test(&mut self, _depth: Option<pso::DepthTest>) {
+        unimplemented!()
+    }
+
+    unsafe fn set_stencil_faces(&mut self, _faces: pso::Face, _value: pso::StencilFace) {
+        unimplemented!()
+    }
+
+    unsafe fn set_vertex_input(
+        &mut self,
+        _buffers: &[pso::VertexBufferDesc],
+        _attributes: &[pso::AttributeDesc],
+    ) {
+        unimplemented!()
+    }
+
+    unsafe fn set_primitive_restart(&mut self, enable: bool) {
+        if self.cache.primitive_restart != Some(enable) {
+            self.cache.primitive_restart = Some(enable);
+            self.data.push_cmd(Command::SetPrimitiveRestart(enable));
+        }
+    }
+
+    unsafe fn set_fragment_shading_rate(
+        &mut self,
+        _fragment_size: hal::window::Extent2D,
+        _combiner_ops: [pso::ShadingRateCombinerOp; 2],
+    ) {
+        unimplemented!()
+    }
+
+    unsafe fn set_sample_locations(&mut self, _locations: &[pso::SampleLocation]) {
+        unimplemented!()
+    }
+
     unsafe fn bind_graphics_pipeline(&mut self, pipeline: &n::GraphicsPipeline) {
         if self.cache.primitive != Some(pipeline.primitive) {
             self.cache.primitive = Some(pipeline.primitive);
@@ -1173,6 +1365,16 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
             }
         }
 
+        if self.cache.clip_distance_count != Some(pipeline.clip_distance_count) {
+            self.cache.clip_distance_count = Some(pipeline.clip_distance_count);
+            self.data
+                .push_cmd(Command::SetClipDistances(pipeline.clip_distance_count));
+        }
+
+        if let Some(enable) = pipeline.primitive_restart {
+            self.set_primitive_restart(enable);
+        }
+
         if self.cache.program != Some(pipeline.program) {
             self.cache.program = Some(pipeline.program);
             self.data.push_cmd(Command::BindProgram(pipeline.program));
@@ -1185,15 +1387,27 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
 
         self.update_blend_targets(&pipeline.blend_targets);
 
-        self.data.push_cmd(Command::BindRasterizer {
-            rasterizer: pipeline.rasterizer,
-        });
-        self.data
-            .push_cmd(Command::BindDepth(pipeline.depth.map(|d| d.fun)));
-        self.data.push_cmd(Command::SetDepthMask(
-            pipeline.depth.map_or(true, |d| d.write),
-        ));
-        self.cache.depth_mask = pipeline.depth.map(|d| d.write);
+        if self.cache.rasterizer != Some(pipeline.rasterizer) {
+            self.cache.rasterizer = Some(pipeline.rasterizer);
+            self.data.push_cmd(Command::BindRasterizer {
+                rasterizer: pipeline.rasterizer,
+            });
+        }
+
+        let depth_fun = pipeline.depth.map(|d| d.fun);
+        if self.cache.depth_fun != Some(depth_fun) {
+            self.cache.depth_fun = Some(depth_fun);
+            self.data.push_cmd(Command::BindDepth(depth_fun));
+        }
+
+        let depth_mask = pipeline.depth.map_or(true, |d| d.write);
+        if self.cache.depth_mask != Some(depth_mask) {
+            self.cache.depth_mask = Some(depth_mask);
+            self.data.push_cmd(Command::SetDepthMask(depth_mask));
+        }
+
+        self.cache.stencil = pipeline.stencil;
+        self.update_stencil();
 
         if let Some(ref vp) = pipeline.baked_states.viewport {
             self.set_viewports(0, iter::once(vp.clone()));
@@ -1271,6 +1485,42 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         ));
     }
 
+    unsafe fn build_acceleration_structure(
+        &mut self,
+        _desc: &hal::acceleration_structure::BuildDesc<Backend>,
+        _ranges: &[hal::acceleration_structure::BuildRangeDesc],
+    ) {
+        unimplemented!()
+    }
+
+    unsafe fn bind_ray_tracing_pipeline(&mut self, _pipeline: &()) {
+        unimplemented!()
+    }
+
+    unsafe fn bind_ray_tracing_descriptor_sets<'a, I, J>(
+        &mut self,
+        _layout: &n::PipelineLayout,
+        _first_set: usize,
+        _sets: I,
+        _offsets: J,
+    ) where
+        I: Iterator<Item = &'a n::DescriptorSet>,
+        J: Iterator<Item = command::DescriptorSetOffset>,
+    {
+        unimplemented!()
+    }
+
+    unsafe fn trace_rays(
+        &mut self,
+        _raygen: &pso::ShaderBindingTable<Backend>,
+        _miss: Option<&pso::ShaderBindingTable<Backend>>,
+        _hit_group: Option<&pso::ShaderBindingTable<Backend>>,
+        _callable: Option<&pso::ShaderBindingTable<Backend>>,
+        _dimensions: [u32; 3],
+    ) {
+        unimplemented!()
+    }
+
     unsafe fn copy_buffer<T>(&mut self, src: &n::Buffer, dst: &n::Buffer, regions: T)
     where
         T: Iterator<Item = command::BufferCopy>,
@@ -1311,12 +1561,14 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
 
         for r in regions {
             let cmd = match dst.object_type {
-                n::ImageType::Renderbuffer { raw, format } => Command::CopyImageToRenderbuffer {
-                    src_image: src.object_type,
-                    dst_renderbuffer: raw,
-                    dst_format: format,
-                    data: r,
-                },
+                n::ImageType::Renderbuffer { raw, format, .. } => {
+                    Command::CopyImageToRenderbuffer {
+                        src_image: src.object_type,
+                        dst_renderbuffer: raw,
+                        dst_format: format,
+                        data: r,
+                    }
+                }
                 n::ImageType::Texture { raw, target, .. } => {
                     Command::CopyImageToTexture(src.object_type, raw, target, r)
                 }
@@ -1341,12 +1593,49 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         let old_size = self.data.buf.size;
 
         let src_bounded_buffer = src.as_bound();
+        // Compressed data is uploaded tightly packed, with its size.
+        let compressed_size = |data: &command::BufferImageCopy, layers: u32| {
+            if dst.format_desc.is_compressed() {
+                let extent = &data.image_extent;
+                let size = image_data_size(&dst.format_desc, extent.width, extent.height);
+                Some(size * layers as buffer::Offset)
+            } else {
+                None
+            }
+        };
         for mut r in regions {
             r.buffer_offset += src_bounded_buffer.range.start;
             let cmd = match dst.object_type {
                 n::ImageType::Renderbuffer { raw, .. } => {
                     Command::CopyBufferToRenderbuffer(src_bounded_buffer.raw, raw, r)
                 }
+                // Cube faces are separate images in GL, so they are copied one by one.
+                n::ImageType::Texture {
+                    raw,
+                    target: glow::TEXTURE_CUBE_MAP,
+                    format,
+                    pixel_type,
+                    ..
+                } => {
+                    let row_texels = r.buffer_width.max(r.image_extent.width);
+                    let layer_rows = r.buffer_height.max(r.image_extent.height);
+                    let layer_size = image_data_size(&dst.format_desc, row_texels, layer_rows);
+                    for (i, layer) in r.image_layers.layers.clone().enumerate() {
+                        let mut data = r.clone();
+                        data.buffer_offset += i as buffer::Offset * layer_size;
+                        data.image_layers.layers = layer..layer + 1;
+                        self.data.push_cmd(Command::CopyBufferToTexture {
+                            src_buffer: src_bounded_buffer.raw,
+                            dst_texture: raw,
+                            texture_target: glow::TEXTURE_CUBE_MAP,
+                            texture_format: format,
+                            pixel_type,
+                            compressed_size: compressed_size(&data, 1),
+                            data,
+                        });
+                    }
+                    continue;
+                }
                 n::ImageType::Texture {
                     raw,
                     target,
@@ -1359,6 +1648,13 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
                     texture_target: target,
                     texture_format: format,
                     pixel_type,
+                    compressed_size: compressed_size(
+                        &r,
+                        match target {
+                            glow::TEXTURE_3D => r.image_extent.depth,
+                            _ => r.image_layers.layers.len() as u32,
+                        },
+                    ),
                     data: r,
                 },
             };
@@ -1383,27 +1679,60 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         let dst_bounded_buffer = dst.as_bound();
 
         for mut r in regions {
+            if src.format_desc.is_compressed() {
+                log::error!("Copying compressed images to buffers is not implemented");
+                continue;
+            }
             r.buffer_offset += dst_bounded_buffer.range.start;
-            let cmd = match src.object_type {
-                n::ImageType::Renderbuffer { raw, .. } => {
-                    Command::CopyRenderbufferToBuffer(raw, dst_bounded_buffer.raw, r)
-                }
-                n::ImageType::Texture {
+            match src.object_type {
+                n::ImageType::Renderbuffer {
                     raw,
-                    target,
                     format,
                     pixel_type,
-                    ..
-                } => Command::CopyTextureToBuffer {
-                    src_texture: raw,
-                    texture_target: target,
+                } => self.data.push_cmd(Command::CopyRenderbufferToBuffer {
+                    src_renderbuffer: raw,
                     texture_format: format,
-                    pixel_type: pixel_type,
+                    pixel_type,
                     dst_buffer: dst_bounded_buffer.raw,
                     data: r,
-                },
-            };
-            self.data.push_cmd(cmd);
+                }),
+                // Layers and depth slices are attached to the read framebuffer one by one.
+                n::ImageType::Texture {
+                    raw,
+                    target,
+                    format,
+                    pixel_type,
+                    ..
+                } => {
+                    let row_texels = r.buffer_width.max(r.image_extent.width);
+                    let slice_rows = r.buffer_height.max(r.image_extent.height);
+                    let slice_size = image_data_size(&src.format_desc, row_texels, slice_rows);
+                    let slices = if target == glow::TEXTURE_3D {
+                        r.image_extent.depth
+                    } else {
+                        r.image_layers.layers.len() as u32
+                    };
+                    for i in 0..slices {
+                        let mut data = r.clone();
+                        data.buffer_offset += i as buffer::Offset * slice_size;
+                        if target == glow::TEXTURE_3D {
+                            data.image_offset.z += i as i32;
+                            data.image_extent.depth = 1;
+                        } else {
+                            let layer = r.image_layers.layers.start + i as image::Layer;
+                            data.image_layers.layers = layer..layer + 1;
+                        }
+                        self.data.push_cmd(Command::CopyTextureToBuffer {
+                            src_texture: raw,
+                            texture_target: target,
+                            texture_format: format,
+                            pixel_type,
+                            dst_buffer: dst_bounded_buffer.raw,
+                            data,
+                        });
+                    }
+                }
+            }
         }
 
         if self.data.buf.size == old_size {
@@ -1499,22 +1828,74 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
 
     unsafe fn draw_indirect(
         &mut self,
-        _buffer: &n::Buffer,
-        _offset: buffer::Offset,
-        _draw_count: hal::DrawCount,
-        _stride: buffer::Stride,
+        buffer: &n::Buffer,
+        offset: buffer::Offset,
+        draw_count: hal::DrawCount,
+        stride: buffer::Stride,
     ) {
-        unimplemented!()
+        // The first instance of each draw is read from the parameter buffer.
+        self.bind_attributes(0);
+
+        let bounded_buffer = buffer.as_bound();
+        match self.cache.primitive {
+            Some(primitive) => {
+                self.data.push_cmd(Command::DrawIndirect {
+                    primitive,
+                    buffer: bounded_buffer.raw,
+                    offset: bounded_buffer.range.start + offset,
+                    draw_count,
+                    stride,
+                });
+            }
+            None => {
+                log::warn!("No primitive bound. An active pipeline needs to be bound before calling `draw_indirect`.");
+                self.cache.error_state = true;
+            }
+        }
     }
 
     unsafe fn draw_indexed_indirect(
         &mut self,
-        _buffer: &n::Buffer,
-        _offset: buffer::Offset,
-        _draw_count: hal::DrawCount,
-        _stride: buffer::Stride,
+        buffer: &n::Buffer,
+        offset: buffer::Offset,
+        draw_count: hal::DrawCount,
+        stride: buffer::Stride,
     ) {
-        unimplemented!()
+        self.bind_attributes(0);
+
+        let index_type = match self.cache.index_type_range {
+            // The first index of each draw is relative to the start of the GL buffer.
+            Some((_, ref range)) if range.start != 0 => {
+                log::error!("Indexed indirect draws require the index buffer to start at the beginning of its memory");
+                self.cache.error_state = true;
+                return;
+            }
+            Some((hal::IndexType::U16, _)) => glow::UNSIGNED_SHORT,
+            Some((hal::IndexType::U32, _)) => glow::UNSIGNED_INT,
+            None => {
+                log::warn!("No index type bound. An index buffer needs to be bound before calling `draw_indexed_indirect`.");
+                self.cache.error_state = true;
+                return;
+            }
+        };
+
+        let bounded_buffer = buffer.as_bound();
+        match self.cache.primitive {
+            Some(primitive) => {
+                self.data.push_cmd(Command::DrawIndexedIndirect {
+                    primitive,
+                    index_type,
+                    buffer: bounded_buffer.raw,
+                    offset: bounded_buffer.range.start + offset,
+                    draw_count,
+                    stride,
+                });
+            }
+            None => {
+                log::warn!("No primitive bound. An active pipeline needs to be bound before calling `draw_indexed_indirect`.");
+                self.cache.error_state = true;
+            }
+        }
     }
 
     unsafe fn draw_indirect_count(
@@ -1610,6 +1991,19 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         unimplemented!()
     }
 
+    unsafe fn begin_conditional_rendering(
+        &mut self,
+        _buffer: &n::Buffer,
+        _offset: buffer::Offset,
+        _flags: command::ConditionalRenderingFlags,
+    ) {
+        unimplemented!()
+    }
+
+    unsafe fn end_conditional_rendering(&mut self) {
+        unimplemented!()
+    }
+
     unsafe fn push_graphics_constants(
         &mut self,
         _layout: &n::PipelineLayout,
@@ -1655,13 +2049,15 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         unimplemented!()
     }
 
-    unsafe fn insert_debug_marker(&mut self, _name: &str, _color: u32) {
-        //TODO
+    unsafe fn insert_debug_marker(&mut self, name: &str, _color: u32) {
+        let name = self.data.add_raw(name.as_bytes());
+        self.data.push_cmd(Command::InsertDebugMarker(name));
     }
-    unsafe fn begin_debug_marker(&mut self, _name: &str, _color: u32) {
-        //TODO
+    unsafe fn begin_debug_marker(&mut self, name: &str, _color: u32) {
+        let name = self.data.add_raw(name.as_bytes());
+        self.data.push_cmd(Command::PushDebugGroup(name));
     }
     unsafe fn end_debug_marker(&mut self) {
-        //TODO
+        self.data.push_cmd(Command::PopDebugGroup);
     }
 }
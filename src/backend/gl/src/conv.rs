@@ -1,5 +1,11 @@
-use crate::native::VertexAttribFunction;
-use hal::{format::Format, image as i, pso};
+use crate::{
+    info::TextureCompression,
+    native::{ShaderStage, VertexAttribFunction},
+};
+use hal::{
+    format::{ChannelType, Format, SurfaceType},
+    image as i, pso,
+};
 
 /*
 pub fn _image_kind_to_gl(kind: i::Kind) -> t::GLenum {
@@ -85,6 +91,17 @@ impl FormatDescription {
             va_fun,
         }
     }
+
+    /// Describe a compressed format, which is uploaded with its internal format.
+    pub fn compressed(tex_internal: u32) -> Self {
+        FormatDescription {
+            tex_internal,
+            tex_external: tex_internal,
+            data_type: 0,
+            num_components: 0,
+            va_fun: VertexAttribFunction::Float,
+        }
+    }
 }
 
 pub fn describe_format(format: Format) -> Option<FormatDescription> {
@@ -102,7 +119,8 @@ pub fn describe_format(format: Format) -> Option<FormatDescription> {
             Integer,
         ),
         R8Sint => FormatDescription::new(glow::R8I, glow::RED_INTEGER, glow::BYTE, 1, Integer),
-        R8Unorm => FormatDescription::new(glow::R8, glow::RED, glow::UNSIGNED_BYTE, 1, Float),
+        R8Unorm => FormatDescription::new(glow::R8, glow::RED, glow::UNSIGNED_BYTE, 1, Normalized),
+        R8Snorm => FormatDescription::new(glow::R8_SNORM, glow::RED, glow::BYTE, 1, Normalized),
         Rg8Uint => FormatDescription::new(
             glow::RG8UI,
             glow::RG_INTEGER,
@@ -111,6 +129,20 @@ pub fn describe_format(format: Format) -> Option<FormatDescription> {
             Integer,
         ),
         Rg8Sint => FormatDescription::new(glow::RG8I, glow::RG_INTEGER, glow::BYTE, 2, Integer),
+        Rg8Unorm => FormatDescription::new(glow::RG8, glow::RG, glow::UNSIGNED_BYTE, 2, Normalized),
+        Rg8Snorm => FormatDescription::new(glow::RG8_SNORM, glow::RG, glow::BYTE, 2, Normalized),
+        Rgb8Uint => FormatDescription::new(
+            glow::RGB8UI,
+            glow::RGB_INTEGER,
+            glow::UNSIGNED_BYTE,
+            3,
+            Integer,
+        ),
+        Rgb8Sint => FormatDescription::new(glow::RGB8I, glow::RGB_INTEGER, glow::BYTE, 3, Integer),
+        Rgb8Unorm => {
+            FormatDescription::new(glow::RGB8, glow::RGB, glow::UNSIGNED_BYTE, 3, Normalized)
+        }
+        Rgb8Snorm => FormatDescription::new(glow::RGB8_SNORM, glow::RGB, glow::BYTE, 3, Normalized),
         Rgba8Uint => FormatDescription::new(
             glow::RGBA8UI,
             glow::RGBA_INTEGER,
@@ -122,7 +154,10 @@ pub fn describe_format(format: Format) -> Option<FormatDescription> {
             FormatDescription::new(glow::RGBA8I, glow::RGBA_INTEGER, glow::BYTE, 4, Integer)
         }
         Rgba8Unorm => {
-            FormatDescription::new(glow::RGBA8, glow::RGBA, glow::UNSIGNED_BYTE, 4, Float)
+            FormatDescription::new(glow::RGBA8, glow::RGBA, glow::UNSIGNED_BYTE, 4, Normalized)
+        }
+        Rgba8Snorm => {
+            FormatDescription::new(glow::RGBA8_SNORM, glow::RGBA, glow::BYTE, 4, Normalized)
         }
         Rgb8Srgb => FormatDescription::new(glow::SRGB8, glow::RGB, glow::UNSIGNED_BYTE, 3, Float),
         Rgba8Srgb => FormatDescription::new(
@@ -132,7 +167,9 @@ pub fn describe_format(format: Format) -> Option<FormatDescription> {
             4,
             Float,
         ),
-        Bgra8Unorm => FormatDescription::new(glow::BGRA, glow::BGRA, glow::UNSIGNED_BYTE, 4, Float),
+        Bgra8Unorm => {
+            FormatDescription::new(glow::BGRA, glow::BGRA, glow::UNSIGNED_BYTE, 4, Normalized)
+        }
         Bgra8Srgb => FormatDescription::new(
             glow::SRGB8_ALPHA8,
             glow::BGRA,
@@ -149,7 +186,10 @@ pub fn describe_format(format: Format) -> Option<FormatDescription> {
         ),
         R16Sint => FormatDescription::new(glow::R16I, glow::RED_INTEGER, glow::SHORT, 1, Integer),
         R16Sfloat => FormatDescription::new(glow::R16F, glow::RED, glow::HALF_FLOAT, 1, Float),
-        R16Unorm => FormatDescription::new(glow::R16, glow::RED, glow::UNSIGNED_SHORT, 1, Float),
+        R16Unorm => {
+            FormatDescription::new(glow::R16, glow::RED, glow::UNSIGNED_SHORT, 1, Normalized)
+        }
+        R16Snorm => FormatDescription::new(glow::R16_SNORM, glow::RED, glow::SHORT, 1, Normalized),
         Rg16Uint => FormatDescription::new(
             glow::RG16UI,
             glow::RG_INTEGER,
@@ -158,8 +198,28 @@ pub fn describe_format(format: Format) -> Option<FormatDescription> {
             Integer,
         ),
         Rg16Sint => FormatDescription::new(glow::RG16I, glow::RG_INTEGER, glow::SHORT, 2, Integer),
-        Rg16Unorm => FormatDescription::new(glow::RG16, glow::RG, glow::UNSIGNED_SHORT, 2, Float),
+        Rg16Unorm => {
+            FormatDescription::new(glow::RG16, glow::RG, glow::UNSIGNED_SHORT, 2, Normalized)
+        }
+        Rg16Snorm => FormatDescription::new(glow::RG16_SNORM, glow::RG, glow::SHORT, 2, Normalized),
         Rg16Sfloat => FormatDescription::new(glow::RG16F, glow::RG, glow::HALF_FLOAT, 2, Float),
+        Rgb16Uint => FormatDescription::new(
+            glow::RGB16UI,
+            glow::RGB_INTEGER,
+            glow::UNSIGNED_SHORT,
+            3,
+            Integer,
+        ),
+        Rgb16Sint => {
+            FormatDescription::new(glow::RGB16I, glow::RGB_INTEGER, glow::SHORT, 3, Integer)
+        }
+        Rgb16Unorm => {
+            FormatDescription::new(glow::RGB16, glow::RGB, glow::UNSIGNED_SHORT, 3, Normalized)
+        }
+        Rgb16Snorm => {
+            FormatDescription::new(glow::RGB16_SNORM, glow::RGB, glow::SHORT, 3, Normalized)
+        }
+        Rgb16Sfloat => FormatDescription::new(glow::RGB16F, glow::RGB, glow::HALF_FLOAT, 3, Float),
         Rgba16Uint => FormatDescription::new(
             glow::RGBA16UI,
             glow::RGBA_INTEGER,
@@ -173,8 +233,15 @@ pub fn describe_format(format: Format) -> Option<FormatDescription> {
         Rgba16Sfloat => {
             FormatDescription::new(glow::RGBA16F, glow::RGBA, glow::HALF_FLOAT, 4, Float)
         }
-        Rgba16Unorm => {
-            FormatDescription::new(glow::RGBA16, glow::RGBA, glow::UNSIGNED_SHORT, 4, Float)
+        Rgba16Unorm => FormatDescription::new(
+            glow::RGBA16,
+            glow::RGBA,
+            glow::UNSIGNED_SHORT,
+            4,
+            Normalized,
+        ),
+        Rgba16Snorm => {
+            FormatDescription::new(glow::RGBA16_SNORM, glow::RGBA, glow::SHORT, 4, Normalized)
         }
         R32Uint => FormatDescription::new(
             glow::R32UI,
@@ -255,12 +322,203 @@ pub fn describe_format(format: Format) -> Option<FormatDescription> {
     })
 }
 
+/// Check if a texture buffer object can be created with the internal format,
+/// following the internal format table of `glTexBuffer`.
+pub fn is_texture_buffer_format(tex_internal: u32, is_embedded: bool) -> bool {
+    match tex_internal {
+        glow::R8
+        | glow::R16F
+        | glow::R32F
+        | glow::R8I
+        | glow::R16I
+        | glow::R32I
+        | glow::R8UI
+        | glow::R16UI
+        | glow::R32UI
+        | glow::RG8
+        | glow::RG16F
+        | glow::RG32F
+        | glow::RG8I
+        | glow::RG16I
+        | glow::RG32I
+        | glow::RG8UI
+        | glow::RG16UI
+        | glow::RG32UI
+        | glow::RGB32F
+        | glow::RGB32I
+        | glow::RGB32UI
+        | glow::RGBA8
+        | glow::RGBA16F
+        | glow::RGBA32F
+        | glow::RGBA8I
+        | glow::RGBA16I
+        | glow::RGBA32I
+        | glow::RGBA8UI
+        | glow::RGBA16UI
+        | glow::RGBA32UI => true,
+        // 16-bit normalized formats are missing from the GLES table.
+        glow::R16 | glow::RG16 | glow::RGBA16 => !is_embedded,
+        _ => false,
+    }
+}
+
+/// `GL_COMPRESSED_RGBA_ASTC_4x4_KHR`, the first of the ASTC formats, which aren't exposed by `glow`.
+const COMPRESSED_RGBA_ASTC_4X4: u32 = 0x93B0;
+/// `GL_COMPRESSED_SRGB8_ALPHA8_ASTC_4x4_KHR`.
+const COMPRESSED_SRGB8_ALPHA8_ASTC_4X4: u32 = 0x93D0;
+
+/// Describe a compressed format, with its internal format and the family of formats
+/// that has to be supported.
+///
+/// Compressed data is uploaded with the internal format, and without a pixel type.
+pub fn describe_compressed_format(format: Format) -> Option<(u32, TextureCompression)> {
+    use hal::format::Format::*;
+
+    Some(match format {
+        Bc1RgbUnorm => (glow::COMPRESSED_RGB_S3TC_DXT1_EXT, TextureCompression::S3TC),
+        Bc1RgbaUnorm => (
+            glow::COMPRESSED_RGBA_S3TC_DXT1_EXT,
+            TextureCompression::S3TC,
+        ),
+        Bc2Unorm => (
+            glow::COMPRESSED_RGBA_S3TC_DXT3_EXT,
+            TextureCompression::S3TC,
+        ),
+        Bc3Unorm => (
+            glow::COMPRESSED_RGBA_S3TC_DXT5_EXT,
+            TextureCompression::S3TC,
+        ),
+        Bc1RgbSrgb => (
+            glow::COMPRESSED_SRGB_S3TC_DXT1_EXT,
+            TextureCompression::S3TC_SRGB,
+        ),
+        Bc1RgbaSrgb => (
+            glow::COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT,
+            TextureCompression::S3TC_SRGB,
+        ),
+        Bc2Srgb => (
+            glow::COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT,
+            TextureCompression::S3TC_SRGB,
+        ),
+        Bc3Srgb => (
+            glow::COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT,
+            TextureCompression::S3TC_SRGB,
+        ),
+        Bc4Unorm => (glow::COMPRESSED_RED_RGTC1, TextureCompression::RGTC),
+        Bc4Snorm => (glow::COMPRESSED_SIGNED_RED_RGTC1, TextureCompression::RGTC),
+        Bc5Unorm => (glow::COMPRESSED_RG_RGTC2, TextureCompression::RGTC),
+        Bc5Snorm => (glow::COMPRESSED_SIGNED_RG_RGTC2, TextureCompression::RGTC),
+        Bc6hUfloat => (
+            glow::COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT,
+            TextureCompression::BPTC,
+        ),
+        Bc6hSfloat => (
+            glow::COMPRESSED_RGB_BPTC_SIGNED_FLOAT,
+            TextureCompression::BPTC,
+        ),
+        Bc7Unorm => (glow::COMPRESSED_RGBA_BPTC_UNORM, TextureCompression::BPTC),
+        Bc7Srgb => (
+            glow::COMPRESSED_SRGB_ALPHA_BPTC_UNORM,
+            TextureCompression::BPTC,
+        ),
+        Etc2R8g8b8Unorm => (glow::COMPRESSED_RGB8_ETC2, TextureCompression::ETC2),
+        Etc2R8g8b8Srgb => (glow::COMPRESSED_SRGB8_ETC2, TextureCompression::ETC2),
+        Etc2R8g8b8a1Unorm => (
+            glow::COMPRESSED_RGB8_PUNCHTHROUGH_ALPHA1_ETC2,
+            TextureCompression::ETC2,
+        ),
+        Etc2R8g8b8a1Srgb => (
+            glow::COMPRESSED_SRGB8_PUNCHTHROUGH_ALPHA1_ETC2,
+            TextureCompression::ETC2,
+        ),
+        Etc2R8g8b8a8Unorm => (glow::COMPRESSED_RGBA8_ETC2_EAC, TextureCompression::ETC2),
+        Etc2R8g8b8a8Srgb => (
+            glow::COMPRESSED_SRGB8_ALPHA8_ETC2_EAC,
+            TextureCompression::ETC2,
+        ),
+        EacR11Unorm => (glow::COMPRESSED_R11_EAC, TextureCompression::ETC2),
+        EacR11Snorm => (glow::COMPRESSED_SIGNED_R11_EAC, TextureCompression::ETC2),
+        EacR11g11Unorm => (glow::COMPRESSED_RG11_EAC, TextureCompression::ETC2),
+        EacR11g11Snorm => (glow::COMPRESSED_SIGNED_RG11_EAC, TextureCompression::ETC2),
+        _ => {
+            // The ASTC formats are ordered by block size, both in GL and in `hal`.
+            let block = match format.base_format().0 {
+                SurfaceType::ASTC_4x4 => 0,
+                SurfaceType::ASTC_5x4 => 1,
+                SurfaceType::ASTC_5x5 => 2,
+                SurfaceType::ASTC_6x5 => 3,
+                SurfaceType::ASTC_6x6 => 4,
+                SurfaceType::ASTC_8x5 => 5,
+                SurfaceType::ASTC_8x6 => 6,
+                SurfaceType::ASTC_8x8 => 7,
+                SurfaceType::ASTC_10x5 => 8,
+                SurfaceType::ASTC_10x6 => 9,
+                SurfaceType::ASTC_10x8 => 10,
+                SurfaceType::ASTC_10x10 => 11,
+                SurfaceType::ASTC_12x10 => 12,
+                SurfaceType::ASTC_12x12 => 13,
+                _ => return None,
+            };
+            let base = if format.base_format().1 == ChannelType::Srgb {
+                COMPRESSED_SRGB8_ALPHA8_ASTC_4X4
+            } else {
+                COMPRESSED_RGBA_ASTC_4X4
+            };
+            (base + block, TextureCompression::ASTC_LDR)
+        }
+    })
+}
+
+pub fn shader_stage_to_gl(stage: ShaderStage) -> u32 {
+    match stage {
+        ShaderStage::Vertex => glow::VERTEX_SHADER,
+        ShaderStage::TessControl => glow::TESS_CONTROL_SHADER,
+        ShaderStage::TessEvaluation => glow::TESS_EVALUATION_SHADER,
+        ShaderStage::Geometry => glow::GEOMETRY_SHADER,
+        ShaderStage::Fragment => glow::FRAGMENT_SHADER,
+        ShaderStage::Compute => glow::COMPUTE_SHADER,
+    }
+}
+
 #[cfg(feature = "cross")]
-pub fn map_naga_stage_to_cross(stage: naga::ShaderStage) -> spirv_cross::spirv::ExecutionModel {
+pub fn map_stage_to_cross(stage: ShaderStage) -> spirv_cross::spirv::ExecutionModel {
     use spirv_cross::spirv::ExecutionModel as Em;
     match stage {
-        naga::ShaderStage::Vertex => Em::Vertex,
-        naga::ShaderStage::Fragment => Em::Fragment,
-        naga::ShaderStage::Compute => Em::GlCompute,
+        ShaderStage::Vertex => Em::Vertex,
+        ShaderStage::TessControl => Em::TessellationControl,
+        ShaderStage::TessEvaluation => Em::TessellationEvaluation,
+        ShaderStage::Geometry => Em::Geometry,
+        ShaderStage::Fragment => Em::Fragment,
+        ShaderStage::Compute => Em::GlCompute,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vertex_attrib_functions() {
+        use crate::native::VertexAttribFunction::*;
+        let expected = [
+            (Format::R8Unorm, Normalized),
+            (Format::Rg8Snorm, Normalized),
+            (Format::Rgba8Unorm, Normalized),
+            (Format::Bgra8Unorm, Normalized),
+            (Format::R16Unorm, Normalized),
+            (Format::Rgba16Snorm, Normalized),
+            (Format::R8Uint, Integer),
+            (Format::Rgba8Sint, Integer),
+            (Format::Rg16Uint, Integer),
+            (Format::Rgb32Sint, Integer),
+            (Format::R16Sfloat, Float),
+            (Format::Rg32Sfloat, Float),
+            (Format::Rgb32Sfloat, Float),
+            (Format::Rgba32Sfloat, Float),
+        ];
+        for &(format, va_fun) in expected.iter() {
+            let desc = describe_format(format).unwrap();
+            assert_eq!(desc.va_fun, va_fun, "{:?}", format);
+        }
     }
 }
@@ -2,6 +2,7 @@ use crate::{
     command as cmd, conv,
     info::LegacyFeatures,
     native as n,
+    pipeline_cache::{self, PipelineCache},
     pool::{BufferMemory, CommandPool, OwnedBuffer},
     state, Backend as B, FastHashMap, GlContainer, GlContext, MemoryUsage, Share, Starc,
     MAX_TEXTURE_SLOTS,
@@ -18,7 +19,12 @@ use hal::{
 use glow::HasContext;
 use parking_lot::Mutex;
 
-use std::{ops::Range, slice, sync::Arc};
+use std::{
+    ops::Range,
+    slice,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 #[cfg(feature = "cross")]
 type CrossAst = spirv_cross::spirv::Ast<spirv_cross::glsl::Target>;
@@ -36,6 +42,50 @@ fn create_fbo_internal(
     }
 }
 
+/// Wait on the host for `sync` to be signaled, for at most `timeout_ns`.
+///
+/// The timeout of `glClientWaitSync` is a signed 32-bit value in `glow`,
+/// so longer timeouts are waited for in several steps.
+unsafe fn client_wait_sync(
+    gl: &GlContainer,
+    sync: <GlContext as glow::HasContext>::Fence,
+    mut timeout_ns: u64,
+) -> u32 {
+    loop {
+        let step = timeout_ns.min(i32::MAX as u64);
+        let status = gl.client_wait_sync(sync, glow::SYNC_FLUSH_COMMANDS_BIT, step as i32);
+        timeout_ns -= step;
+        if status != glow::TIMEOUT_EXPIRED || timeout_ns == 0 {
+            return status;
+        }
+    }
+}
+
+/// Advance the value of a timeline semaphore past the completed submissions.
+unsafe fn update_timeline(gl: &GlContainer, state: &mut n::TimelineState) {
+    // Submissions complete in order, so stop at the first pending one.
+    while let Some(&(value, sync)) = state.pending.first() {
+        if gl.get_sync_status(sync) != glow::SIGNALED {
+            break;
+        }
+        gl.delete_sync(sync);
+        state.value = state.value.max(value);
+        state.pending.remove(0);
+    }
+}
+
+/// Name a GL object in the debug output and the debugging tools.
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn set_object_label(share: &Share, identifier: u32, raw: u32, label: &str) {
+    if share.private_caps.debug {
+        share.context.object_label(identifier, raw, Some(label));
+    }
+}
+
+/// Objects can't be named on WebGL.
+#[cfg(target_arch = "wasm32")]
+unsafe fn set_object_label<T>(_share: &Share, _identifier: u32, _raw: T, _label: &str) {}
+
 struct CompilationContext<'a> {
     layout: &'a n::PipelineLayout,
     sampler_map: &'a mut n::SamplerBindMap,
@@ -92,16 +142,59 @@ impl Device {
         }
     }
 
+    /// Get a bindless handle for the texture of `view`, combined with `sampler` if any,
+    /// and make it resident, so that it can be written into uniform or storage buffers
+    /// and sampled by shaders enabling `GL_ARB_bindless_texture`.
+    ///
+    /// Returns `None` if bindless textures aren't supported, if the view is backed by a
+    /// renderbuffer, if the sampler isn't a GL sampler object, or if the driver fails
+    /// to create the handle.
+    /// The handle covers the whole texture of the view, and neither the texture nor the
+    /// sampler can be modified afterwards. It must be made non-resident with
+    /// [`Device::make_texture_handle_non_resident`] before either of them is destroyed.
+    pub unsafe fn make_texture_handle_resident(
+        &self,
+        view: &n::ImageView,
+        sampler: Option<&n::FatSampler>,
+    ) -> Option<u64> {
+        let fns = self.share.bindless_texture.as_ref()?;
+        let texture = match *view {
+            n::ImageView::Texture { raw, .. } => raw,
+            n::ImageView::Renderbuffer { .. } => return None,
+        };
+        let sampler = match sampler {
+            Some(&n::FatSampler::Sampler(raw)) => Some(raw),
+            Some(&n::FatSampler::Info(_)) => {
+                log::error!("Bindless handles require sampler objects");
+                return None;
+            }
+            None => None,
+        };
+        let handle = match fns.get_handle(texture, sampler) {
+            Some(handle) => handle,
+            None => {
+                log::error!("Unable to get a bindless handle for {:?}", view);
+                return None;
+            }
+        };
+        fns.make_resident(handle);
+        Some(handle)
+    }
+
+    /// Make a handle returned by [`Device::make_texture_handle_resident`] non-resident.
+    pub unsafe fn make_texture_handle_non_resident(&self, handle: u64) {
+        match self.share.bindless_texture {
+            Some(ref fns) => fns.make_non_resident(handle),
+            None => log::error!("Bindless textures are not supported"),
+        }
+    }
+
     fn create_shader_module_raw(
         gl: &GlContainer,
         shader: &str,
-        stage: naga::ShaderStage,
+        stage: n::ShaderStage,
     ) -> Result<n::Shader, d::ShaderError> {
-        let target = match stage {
-            naga::ShaderStage::Vertex => glow::VERTEX_SHADER,
-            naga::ShaderStage::Fragment => glow::FRAGMENT_SHADER,
-            naga::ShaderStage::Compute => glow::COMPUTE_SHADER,
-        };
+        let target = conv::shader_stage_to_gl(stage);
 
         let name = unsafe { gl.create_shader(target) }.unwrap();
         unsafe {
@@ -128,11 +221,11 @@ impl Device {
 
     fn create_shader_program(
         &self,
-        shaders: &[(naga::ShaderStage, Option<&pso::EntryPoint<B>>)],
+        shaders: &[(n::ShaderStage, Option<&pso::EntryPoint<B>>)],
         layout: &n::PipelineLayout,
+        cache: Option<&PipelineCache>,
     ) -> Result<(glow::Program, n::SamplerBindMap), pso::CreationError> {
         let gl = &self.share.context;
-        let program = unsafe { gl.create_program().unwrap() };
 
         let mut name_binding_map = FastHashMap::<String, (n::BindingRegister, u8)>::default();
         let mut sampler_map = [None; MAX_TEXTURE_SLOTS];
@@ -145,26 +238,35 @@ impl Device {
             name_binding_map: &mut name_binding_map,
         };
 
-        let mut shaders_to_delete = arrayvec::ArrayVec::<[_; 3]>::new();
+        let mut sources = arrayvec::ArrayVec::<[_; 5]>::new();
+
+        // The last stage before the rasterization outputs the final positions.
+        let last_vertex_stage = shaders
+            .iter()
+            .filter(|&&(stage, point)| point.is_some() && stage.is_vertex_processing())
+            .map(|&(stage, _)| stage)
+            .last();
 
         for &(stage, point_maybe) in shaders {
             if let Some(point) = point_maybe {
                 match stage {
-                    naga::ShaderStage::Vertex => has_vertex_stage = true,
-                    naga::ShaderStage::Fragment => has_fragment_stage = true,
-                    naga::ShaderStage::Compute => (),
+                    n::ShaderStage::Vertex => has_vertex_stage = true,
+                    n::ShaderStage::Fragment => has_fragment_stage = true,
+                    _ => (),
                 }
 
-                let shader = self
-                    .compile_shader(point, stage, context.reborrow())
+                let source = self
+                    .translate_shader(
+                        point,
+                        stage,
+                        last_vertex_stage == Some(stage),
+                        context.reborrow(),
+                    )
                     .map_err(|err| {
                         let error = format!("{} shader compilation failed: {:?}", err, stage);
                         pso::CreationError::ShaderCreationError(stage.into(), error)
                     })?;
-                unsafe {
-                    gl.attach_shader(program, shader);
-                    shaders_to_delete.push(shader);
-                }
+                sources.push((stage, source));
             }
         }
 
@@ -182,31 +284,78 @@ impl Device {
                 "Only vertex shader is present. Creating empty fragment shader:\n{}",
                 shader_src
             );
-            let shader = Self::create_shader_module_raw(
-                &self.share.context,
-                &shader_src,
-                naga::ShaderStage::Fragment,
-            )
-            .unwrap();
-            unsafe {
-                gl.attach_shader(program, shader);
-                shaders_to_delete.push(shader);
-            }
+            sources.push((n::ShaderStage::Fragment, shader_src));
         }
 
-        unsafe {
-            gl.link_program(program);
+        let program_binary = cache.and(self.share.program_binary.as_ref());
+        let key = program_binary.map(|_| {
+            let platform = &self.share.info.platform_name;
+            let driver = format!(
+                "{} {} {:?}",
+                platform.vendor, platform.renderer, self.share.info.version
+            );
+            pipeline_cache::program_key(&driver, sources.iter().map(|(_, src)| src.as_str()))
+        });
+
+        let program = unsafe { gl.create_program().unwrap() };
+
+        // Try loading the program from the cache, before compiling it.
+        let mut loaded = false;
+        if let (Some(cache), Some(fns), Some(key)) = (cache, program_binary, key) {
+            if let Some(binary) = cache.get(key) {
+                unsafe { fns.load_into(program, &binary) };
+                loaded = unsafe { gl.get_program_link_status(program) };
+                if loaded {
+                    log::info!("\tLoaded program {:?} from the pipeline cache", program);
+                } else {
+                    log::info!("\tProgram binary was rejected, compiling it again");
+                    cache.remove(key);
+                }
+            }
         }
 
-        for shader in shaders_to_delete {
+        if !loaded {
+            let mut shaders_to_delete = arrayvec::ArrayVec::<[_; 5]>::new();
+            for (stage, source) in sources.iter() {
+                let shader = Self::create_shader_module_raw(gl, source, *stage).map_err(|err| {
+                    let error = format!("{} shader compilation failed: {:?}", err, stage);
+                    pso::CreationError::ShaderCreationError((*stage).into(), error)
+                });
+                let shader = match shader {
+                    Ok(shader) => shader,
+                    Err(err) => {
+                        unsafe {
+                            for shader in shaders_to_delete {
+                                gl.delete_shader(shader);
+                            }
+                            gl.delete_program(program);
+                        }
+                        return Err(err);
+                    }
+                };
+                unsafe {
+                    gl.attach_shader(program, shader);
+                    shaders_to_delete.push(shader);
+                }
+            }
+
             unsafe {
-                gl.delete_shader(shader);
+                if let Some(fns) = program_binary {
+                    fns.set_retrievable(program);
+                }
+                gl.link_program(program);
             }
-        }
 
-        log::info!("\tLinked program {:?}", program);
-        if let Err(err) = self.share.check() {
-            panic!("Error linking program: {:?}", err);
+            for shader in shaders_to_delete {
+                unsafe {
+                    gl.delete_shader(shader);
+                }
+            }
+
+            log::info!("\tLinked program {:?}", program);
+            if let Err(err) = self.share.check() {
+                panic!("Error linking program: {:?}", err);
+            }
         }
 
         let linked_ok = unsafe { gl.get_program_link_status(program) };
@@ -218,6 +367,13 @@ impl Device {
                 error,
             ));
         }
+        if !loaded {
+            if let (Some(cache), Some(fns), Some(key)) = (cache, program_binary, key) {
+                if let Some(binary) = unsafe { fns.get(program) } {
+                    cache.insert(key, binary);
+                }
+            }
+        }
         if !log.is_empty() {
             log::warn!("\tLog: {}", log);
         }
@@ -252,6 +408,172 @@ impl Device {
         Ok((program, sampler_map))
     }
 
+    /// Returns the number of clip distances written by an entry point
+    /// of a vertex processing stage.
+    fn count_clip_distances(stage: n::ShaderStage, ep: &pso::EntryPoint<B>) -> u32 {
+        let (module, naga_stage) = match (&ep.module.naga, stage.to_naga()) {
+            (&Ok(ref shader), Some(naga_stage)) => (&shader.module, naga_stage),
+            #[cfg(feature = "cross")]
+            _ => return Self::count_clip_distances_spirv(stage, &ep.module.spv, ep.entry),
+            #[cfg(not(feature = "cross"))]
+            _ => return 0,
+        };
+        let is_clip_distance = |binding: &Option<naga::Binding>| {
+            matches!(
+                *binding,
+                Some(naga::Binding::BuiltIn(naga::BuiltIn::ClipDistance))
+            )
+        };
+        let array_length = |ty: naga::Handle<naga::Type>| match module.types[ty].inner {
+            naga::TypeInner::Array {
+                size: naga::ArraySize::Constant(length),
+                ..
+            } => match module.constants[length].inner {
+                naga::ConstantInner::Scalar {
+                    value: naga::ScalarValue::Uint(value),
+                    ..
+                } => value as u32,
+                naga::ConstantInner::Scalar {
+                    value: naga::ScalarValue::Sint(value),
+                    ..
+                } => value as u32,
+                _ => 0,
+            },
+            _ => 1,
+        };
+
+        module
+            .entry_points
+            .iter()
+            .find(|entry| entry.stage == naga_stage && entry.name == ep.entry)
+            .and_then(|entry| entry.function.result.as_ref())
+            .map_or(0, |result| {
+                if is_clip_distance(&result.binding) {
+                    return array_length(result.ty);
+                }
+                match module.types[result.ty].inner {
+                    naga::TypeInner::Struct { ref members, .. } => members
+                        .iter()
+                        .filter(|member| is_clip_distance(&member.binding))
+                        .map(|member| array_length(member.ty))
+                        .sum(),
+                    _ => 0,
+                }
+            })
+    }
+
+    /// Returns the number of clip distances written by an entry point,
+    /// for the modules and stages that naga can't handle.
+    #[cfg(feature = "cross")]
+    fn count_clip_distances_spirv(stage: n::ShaderStage, spv: &[u32], entry: &str) -> u32 {
+        const OP_ENTRY_POINT: u32 = 15;
+        const OP_TYPE_ARRAY: u32 = 28;
+        const OP_TYPE_STRUCT: u32 = 30;
+        const OP_TYPE_POINTER: u32 = 32;
+        const OP_CONSTANT: u32 = 43;
+        const OP_VARIABLE: u32 = 59;
+        const OP_DECORATE: u32 = 71;
+        const OP_MEMBER_DECORATE: u32 = 72;
+        const EXECUTION_MODEL_VERTEX: u32 = 0;
+        const EXECUTION_MODEL_TESSELLATION_EVALUATION: u32 = 2;
+        const EXECUTION_MODEL_GEOMETRY: u32 = 3;
+        const STORAGE_CLASS_OUTPUT: u32 = 3;
+        const DECORATION_BUILT_IN: u32 = 11;
+        const BUILT_IN_CLIP_DISTANCE: u32 = 3;
+
+        let execution_model = match stage {
+            n::ShaderStage::Vertex => EXECUTION_MODEL_VERTEX,
+            n::ShaderStage::TessEvaluation => EXECUTION_MODEL_TESSELLATION_EVALUATION,
+            n::ShaderStage::Geometry => EXECUTION_MODEL_GEOMETRY,
+            _ => return 0,
+        };
+        let mut interface = &[][..];
+        let mut clip_variables = Vec::new();
+        let mut clip_members = Vec::new();
+        let mut constants = FastHashMap::default();
+        let mut array_lengths = FastHashMap::default();
+        let mut struct_members = FastHashMap::default();
+        let mut output_pointers = FastHashMap::default();
+        let mut output_variables = FastHashMap::default();
+
+        let mut words = spv.get(5..).unwrap_or_default();
+        while let Some(&first) = words.first() {
+            let count = (first >> 16) as usize;
+            if count == 0 || count > words.len() {
+                break;
+            }
+            let (inst, rest) = words.split_at(count);
+            words = rest;
+            let operands = &inst[1..];
+            match (first & 0xFFFF, operands) {
+                (OP_ENTRY_POINT, &[model, _, ref tail @ ..]) if model == execution_model => {
+                    // The name is a nul-terminated string padded to words.
+                    let name_words = tail
+                        .iter()
+                        .position(|word| word >> 24 == 0)
+                        .map_or(tail.len(), |i| i + 1);
+                    let name = tail[..name_words]
+                        .iter()
+                        .flat_map(|word| word.to_le_bytes())
+                        .take_while(|&byte| byte != 0)
+                        .collect::<Vec<_>>();
+                    if name == entry.as_bytes() {
+                        interface = &tail[name_words..];
+                    }
+                }
+                (OP_TYPE_ARRAY, &[id, _, length]) => {
+                    array_lengths.insert(id, length);
+                }
+                (OP_TYPE_STRUCT, &[id, ref members @ ..]) => {
+                    struct_members.insert(id, members);
+                }
+                (OP_TYPE_POINTER, &[id, STORAGE_CLASS_OUTPUT, ty]) => {
+                    output_pointers.insert(id, ty);
+                }
+                (OP_CONSTANT, &[_, id, value, ..]) => {
+                    constants.insert(id, value);
+                }
+                (OP_VARIABLE, &[ty, id, STORAGE_CLASS_OUTPUT, ..]) => {
+                    output_variables.insert(id, ty);
+                }
+                (OP_DECORATE, &[id, DECORATION_BUILT_IN, BUILT_IN_CLIP_DISTANCE]) => {
+                    clip_variables.push(id);
+                }
+                (
+                    OP_MEMBER_DECORATE,
+                    &[id, member, DECORATION_BUILT_IN, BUILT_IN_CLIP_DISTANCE],
+                ) => {
+                    clip_members.push((id, member));
+                }
+                _ => {}
+            }
+        }
+
+        let array_length = |ty: &u32| {
+            array_lengths
+                .get(ty)
+                .map_or(1, |length| constants.get(length).cloned().unwrap_or(0))
+        };
+        interface
+            .iter()
+            .filter_map(|var| {
+                let ty = output_pointers.get(output_variables.get(var)?)?;
+                if clip_variables.contains(var) {
+                    return Some(array_length(ty));
+                }
+                let members = struct_members.get(ty)?;
+                Some(
+                    clip_members
+                        .iter()
+                        .filter(|&&(id, _)| id == *ty)
+                        .filter_map(|&(_, member)| members.get(member as usize))
+                        .map(array_length)
+                        .sum(),
+                )
+            })
+            .sum()
+    }
+
     fn _bind_target_compat(gl: &GlContainer, point: u32, attachment: u32, view: &n::ImageView) {
         match *view {
             n::ImageView::Renderbuffer { raw: rb, .. } => unsafe {
@@ -262,6 +584,7 @@ impl Device {
                 raw,
                 ref sub,
                 is_3d: false,
+                ..
             } => unsafe {
                 gl.bind_texture(target, Some(raw));
                 gl.framebuffer_texture_2d(
@@ -277,6 +600,7 @@ impl Device {
                 raw,
                 ref sub,
                 is_3d: true,
+                ..
             } => unsafe {
                 gl.bind_texture(target, Some(raw));
                 gl.framebuffer_texture_3d(
@@ -297,15 +621,22 @@ impl Device {
                 gl.framebuffer_renderbuffer(point, attachment, glow::RENDERBUFFER, Some(rb));
             },
             n::ImageView::Texture {
-                target: _,
+                target,
                 raw,
                 ref sub,
                 is_3d: false,
+                ..
             } => unsafe {
+                let tex_target = match target {
+                    glow::TEXTURE_CUBE_MAP => {
+                        glow::TEXTURE_CUBE_MAP_POSITIVE_X + sub.layer_start as u32
+                    }
+                    _ => glow::TEXTURE_2D,
+                };
                 gl.framebuffer_texture_2d(
                     point,
                     attachment,
-                    glow::TEXTURE_2D,
+                    tex_target,
                     Some(raw),
                     sub.level_start as _,
                 );
@@ -315,6 +646,7 @@ impl Device {
                 raw,
                 ref sub,
                 is_3d: true,
+                ..
             } => unsafe {
                 gl.framebuffer_texture_layer(
                     point,
@@ -349,7 +681,8 @@ impl Device {
     fn translate_spirv_cross(
         &self,
         ast: &mut CrossAst,
-        stage: naga::ShaderStage,
+        stage: n::ShaderStage,
+        last_vertex_stage: bool,
         entry_point: &str,
     ) -> Result<String, d::ShaderError> {
         use spirv_cross::{glsl, ErrorCode as Ec};
@@ -386,12 +719,12 @@ impl Device {
                 other => panic!("GLSL version is not recognized: {:?}", other),
             }
         };
-        compile_options.vertex.invert_y = !self.features.contains(hal::Features::NDC_Y_UP);
+        // Only the positions that are rasterized are inverted.
+        compile_options.vertex.invert_y =
+            last_vertex_stage && !self.features.contains(hal::Features::NDC_Y_UP);
         compile_options.force_zero_initialized_variables = true;
-        compile_options.entry_point = Some((
-            entry_point.to_string(),
-            conv::map_naga_stage_to_cross(stage),
-        ));
+        compile_options.entry_point =
+            Some((entry_point.to_string(), conv::map_stage_to_cross(stage)));
         log::debug!("SPIR-V options {:?}", compile_options);
 
         ast.set_compiler_options(&compile_options).map_err(|err| {
@@ -423,6 +756,27 @@ impl Device {
             layout,
             nb_map,
         );
+        // Separate images that are never paired with a sampler are texel buffers,
+        // which end up as `samplerBuffer` in GLSL and occupy a texture slot.
+        let combined_images = ast
+            .get_combined_image_samplers()
+            .unwrap()
+            .iter()
+            .map(|cis| cis.image_id)
+            .collect::<Vec<_>>();
+        let texel_buffers = res
+            .separate_images
+            .iter()
+            .filter(|res| !combined_images.contains(&res.id))
+            .cloned()
+            .collect::<Vec<_>>();
+        self.remap_binding(
+            ast,
+            &texel_buffers,
+            n::BindingRegister::Textures,
+            layout,
+            nb_map,
+        );
         self.remap_binding(
             ast,
             &res.uniform_buffers,
@@ -592,12 +946,11 @@ impl Device {
         }
     }
 
-    fn compile_shader_library_naga(
-        gl: &GlContainer,
+    fn translate_shader_naga(
         shader: &d::NagaShader,
         options: &naga::back::glsl::Options,
         context: CompilationContext,
-    ) -> Result<n::Shader, d::ShaderError> {
+    ) -> Result<String, d::ShaderError> {
         let mut output = String::new();
         let mut writer =
             naga::back::glsl::Writer::new(&mut output, &shader.module, &shader.info, options)
@@ -623,7 +976,7 @@ impl Device {
                     context,
                 );
                 log::debug!("Naga generated shader:\n{}", output);
-                Self::create_shader_module_raw(gl, &output, options.shader_stage)
+                Ok(output)
             }
             Err(e) => {
                 log::warn!("Naga GLSL write: {}", e);
@@ -632,36 +985,48 @@ impl Device {
         }
     }
 
-    fn compile_shader(
+    /// Translate an entry point into the GLSL source of a shader.
+    ///
+    /// `last_vertex_stage` is set for the last stage before the rasterization,
+    /// which outputs the final positions.
+    fn translate_shader(
         &self,
         ep: &pso::EntryPoint<B>,
-        stage: naga::ShaderStage,
+        stage: n::ShaderStage,
+        last_vertex_stage: bool,
         mut context: CompilationContext,
-    ) -> Result<n::Shader, d::ShaderError> {
-        let naga_options = naga::back::glsl::Options {
-            version: {
-                use naga::back::glsl::Version;
-                let sl = &self.share.info.shading_language;
-                let value = (sl.major * 100 + sl.minor * 10) as u16;
-                if sl.is_embedded {
-                    Version::Embedded(value)
-                } else {
-                    Version::Desktop(value)
-                }
-            },
-            shader_stage: stage,
-            entry_point: ep.entry.to_string(),
+    ) -> Result<String, d::ShaderError> {
+        // A vertex stage followed by tessellation or geometry stages is translated
+        // by SPIRV-Cross as well, so that the positions are adjusted only once.
+        let naga_stage = match stage {
+            n::ShaderStage::Vertex if !last_vertex_stage => None,
+            _ => stage.to_naga(),
         };
 
         #[cfg_attr(not(feature = "cross"), allow(unused_mut))]
-        let mut result = match ep.module.naga {
-            Ok(ref shader) => Self::compile_shader_library_naga(
-                &self.share.context,
-                shader,
-                &naga_options,
-                context.reborrow(),
-            ),
-            Err(ref e) => Err(d::ShaderError::CompilationFailed(e.clone())),
+        let mut result = match (naga_stage, &ep.module.naga) {
+            (Some(naga_stage), Ok(shader)) => {
+                let naga_options = naga::back::glsl::Options {
+                    version: {
+                        use naga::back::glsl::Version;
+                        let sl = &self.share.info.shading_language;
+                        let value = (sl.major * 100 + sl.minor * 10) as u16;
+                        if sl.is_embedded {
+                            Version::Embedded(value)
+                        } else {
+                            Version::Desktop(value)
+                        }
+                    },
+                    shader_stage: naga_stage,
+                    entry_point: ep.entry.to_string(),
+                };
+                Self::translate_shader_naga(shader, &naga_options, context.reborrow())
+            }
+            (None, _) => Err(d::ShaderError::CompilationFailed(format!(
+                "{:?} stage can't be translated by naga",
+                stage
+            ))),
+            (_, Err(e)) => Err(d::ShaderError::CompilationFailed(e.clone())),
         };
         #[cfg(feature = "cross")]
         if result.is_err() {
@@ -672,10 +1037,10 @@ impl Device {
             self.set_push_const_layout(&mut ast).unwrap();
 
             let glsl = self
-                .translate_spirv_cross(&mut ast, stage, ep.entry)
+                .translate_spirv_cross(&mut ast, stage, last_vertex_stage, ep.entry)
                 .unwrap();
             log::debug!("SPIRV-Cross generated shader:\n{}", glsl);
-            result = Self::create_shader_module_raw(&self.share.context, &glsl, stage);
+            result = Ok(glsl);
         }
         result
     }
@@ -825,6 +1190,7 @@ impl d::Device<B> for Device {
                     size,
                     map_flags,
                     emulate_map_allocation: None,
+                    persistent_mapping: None,
                 })
             }
 
@@ -836,6 +1202,7 @@ impl d::Device<B> for Device {
                     size,
                     map_flags: 0,
                     emulate_map_allocation: None,
+                    persistent_mapping: None,
                 })
             }
         }
@@ -904,6 +1271,7 @@ impl d::Device<B> for Device {
         Ok(n::RenderPass {
             attachments: attachments.collect::<Vec<_>>(),
             subpasses,
+            label: None,
         })
     }
 
@@ -934,13 +1302,19 @@ impl d::Device<B> for Device {
                     pso::DescriptorType::InputAttachment | pso::DescriptorType::Image { .. } => {
                         &mut num_textures
                     }
+                    pso::DescriptorType::Buffer {
+                        format: pso::BufferDescriptorFormat::Texel,
+                        ..
+                    } => &mut num_textures,
                     pso::DescriptorType::Buffer {
                         ty,
-                        format: _, //TODO
+                        format: pso::BufferDescriptorFormat::Structured { .. },
                     } => match ty {
                         pso::BufferDescriptorType::Uniform => &mut num_uniform_buffers,
                         pso::BufferDescriptorType::Storage { .. } => &mut num_storage_buffers,
                     },
+                    pso::DescriptorType::AccelerationStructure
+                    | pso::DescriptorType::Mutable { .. } => unimplemented!(),
                 };
 
                 bindings[binding.binding as usize] = (*counter).try_into().unwrap();
@@ -953,75 +1327,129 @@ impl d::Device<B> for Device {
             });
         }
 
+        if num_storage_buffers != 0 && !self.share.private_caps.storage_buffers {
+            log::error!("Storage buffers are not supported by the context");
+        }
+
         Ok(n::PipelineLayout { sets })
     }
 
-    unsafe fn create_pipeline_cache(&self, _data: Option<&[u8]>) -> Result<(), d::OutOfMemory> {
-        Ok(())
+    unsafe fn create_pipeline_cache(
+        &self,
+        data: Option<&[u8]>,
+    ) -> Result<PipelineCache, d::OutOfMemory> {
+        Ok(match data {
+            Some(data) if !data.is_empty() => PipelineCache::from_data(data),
+            _ => PipelineCache::default(),
+        })
     }
 
-    unsafe fn get_pipeline_cache_data(&self, _cache: &()) -> Result<Vec<u8>, d::OutOfMemory> {
-        //empty
-        Ok(Vec::new())
+    unsafe fn get_pipeline_cache_data(
+        &self,
+        cache: &PipelineCache,
+    ) -> Result<Vec<u8>, d::OutOfMemory> {
+        Ok(cache.to_data())
     }
 
-    unsafe fn destroy_pipeline_cache(&self, _: ()) {
+    unsafe fn destroy_pipeline_cache(&self, _: PipelineCache) {
         //empty
     }
 
-    unsafe fn merge_pipeline_caches<'a, I>(&self, _: &mut (), _: I) -> Result<(), d::OutOfMemory>
+    unsafe fn merge_pipeline_caches<'a, I>(
+        &self,
+        target: &mut PipelineCache,
+        sources: I,
+    ) -> Result<(), d::OutOfMemory>
     where
-        I: Iterator<Item = &'a ()>,
+        I: Iterator<Item = &'a PipelineCache>,
     {
-        //empty
+        for source in sources {
+            target.merge(source);
+        }
         Ok(())
     }
 
     unsafe fn create_graphics_pipeline<'a>(
         &self,
         desc: &pso::GraphicsPipelineDesc<'a, B>,
-        _cache: Option<&()>,
+        cache: Option<&PipelineCache>,
     ) -> Result<n::GraphicsPipeline, pso::CreationError> {
-        let (vertex_buffers, desc_attributes, input_assembler, vs) = match desc.primitive_assembler
-        {
-            pso::PrimitiveAssemblerDesc::Vertex {
-                buffers,
-                attributes,
-                ref input_assembler,
-                ref vertex,
-                ref tessellation,
-                ref geometry,
-            } => {
-                if tessellation.is_some() || geometry.is_some() {
-                    return Err(pso::CreationError::UnsupportedPipeline);
-                }
+        let (vertex_buffers, desc_attributes, input_assembler, vs, tessellation, geometry) =
+            match desc.primitive_assembler {
+                pso::PrimitiveAssemblerDesc::Vertex {
+                    buffers,
+                    attributes,
+                    ref input_assembler,
+                    ref vertex,
+                    ref tessellation,
+                    ref geometry,
+                } => {
+                    if tessellation.is_some()
+                        && !self.features.contains(hal::Features::TESSELLATION_SHADER)
+                        || geometry.is_some()
+                            && !self.features.contains(hal::Features::GEOMETRY_SHADER)
+                    {
+                        return Err(pso::CreationError::UnsupportedPipeline);
+                    }
 
-                let mut vertex_buffers = Vec::new();
-                for vb in buffers {
-                    while vertex_buffers.len() <= vb.binding as usize {
-                        vertex_buffers.push(None);
+                    let mut vertex_buffers = Vec::new();
+                    for vb in buffers {
+                        while vertex_buffers.len() <= vb.binding as usize {
+                            vertex_buffers.push(None);
+                        }
+                        vertex_buffers[vb.binding as usize] = Some(*vb);
                     }
-                    vertex_buffers[vb.binding as usize] = Some(*vb);
-                }
 
-                (vertex_buffers, attributes, input_assembler, vertex)
-            }
-            pso::PrimitiveAssemblerDesc::Mesh { .. } => {
-                return Err(pso::CreationError::UnsupportedPipeline);
-            }
-        };
+                    (
+                        vertex_buffers,
+                        attributes,
+                        input_assembler,
+                        vertex,
+                        tessellation.as_ref(),
+                        geometry.as_ref(),
+                    )
+                }
+                pso::PrimitiveAssemblerDesc::Mesh { .. } => {
+                    return Err(pso::CreationError::UnsupportedPipeline);
+                }
+            };
+        if desc
+            .dynamic_states
+            .contains(pso::DynamicStates::PRIMITIVE_RESTART)
+            && !self
+                .features
+                .contains(hal::Features::PRIMITIVE_RESTART_DYNAMIC_STATE)
+        {
+            return Err(pso::CreationError::UnsupportedPipeline);
+        }
 
         let shaders = [
-            (naga::ShaderStage::Vertex, Some(vs)),
-            (naga::ShaderStage::Fragment, desc.fragment.as_ref()),
+            (n::ShaderStage::Vertex, Some(vs)),
+            (n::ShaderStage::TessControl, tessellation.map(|(hs, _)| hs)),
+            (
+                n::ShaderStage::TessEvaluation,
+                tessellation.map(|(_, ds)| ds),
+            ),
+            (n::ShaderStage::Geometry, geometry),
+            (n::ShaderStage::Fragment, desc.fragment.as_ref()),
         ];
-        let (program, sampler_map) = self.create_shader_program(&shaders[..], &desc.layout)?;
+        let (program, sampler_map) =
+            self.create_shader_program(&shaders[..], &desc.layout, cache)?;
 
         let patch_size = match input_assembler.primitive {
             pso::Primitive::PatchList(size) => Some(size as _),
             _ => None,
         };
 
+        // Clip distances are taken from the last stage before the rasterization.
+        let (last_stage, last_ep) = match (geometry, tessellation) {
+            (Some(gs), _) => (n::ShaderStage::Geometry, gs),
+            (None, Some(&(_, ref ds))) => (n::ShaderStage::TessEvaluation, ds),
+            (None, None) => (n::ShaderStage::Vertex, vs),
+        };
+        let clip_distance_count = Self::count_clip_distances(last_stage, last_ep)
+            .min(self.share.private_caps.max_clip_distances);
+
         let mut uniforms = Vec::new();
         {
             let gl = &self.share.context;
@@ -1051,6 +1479,15 @@ impl d::Device<B> for Device {
             program,
             primitive: conv::input_assember_to_gl_primitive(input_assembler),
             patch_size,
+            clip_distance_count,
+            primitive_restart: if desc
+                .dynamic_states
+                .contains(pso::DynamicStates::PRIMITIVE_RESTART)
+            {
+                None
+            } else {
+                Some(input_assembler.restart_index.is_some())
+            },
             blend_targets: desc.blender.targets.clone(),
             vertex_buffers,
             attributes: desc_attributes
@@ -1070,6 +1507,7 @@ impl d::Device<B> for Device {
             uniforms,
             rasterizer: desc.rasterizer,
             depth: desc.depth_stencil.depth,
+            stencil: desc.depth_stencil.stencil,
             baked_states: desc.baked_states.clone(),
             sampler_map,
         })
@@ -1078,19 +1516,36 @@ impl d::Device<B> for Device {
     unsafe fn create_compute_pipeline<'a>(
         &self,
         desc: &pso::ComputePipelineDesc<'a, B>,
-        _cache: Option<&()>,
+        cache: Option<&PipelineCache>,
     ) -> Result<n::ComputePipeline, pso::CreationError> {
         if self.share.public_caps.limits.max_compute_work_group_count[0] == 0 {
             return Err(pso::CreationError::UnsupportedPipeline);
         }
-        let shader = (naga::ShaderStage::Compute, Some(&desc.shader));
-        let (program, sampler_map) = self.create_shader_program(&[shader], &desc.layout)?;
+        let shader = (n::ShaderStage::Compute, Some(&desc.shader));
+        let (program, sampler_map) = self.create_shader_program(&[shader], &desc.layout, cache)?;
         Ok(n::ComputePipeline {
             program,
             sampler_map,
         })
     }
 
+    unsafe fn create_ray_tracing_pipeline<'a>(
+        &self,
+        _desc: &pso::RayTracingPipelineDesc<'a, B>,
+        _cache: Option<&PipelineCache>,
+    ) -> Result<(), pso::CreationError> {
+        Err(pso::CreationError::UnsupportedPipeline)
+    }
+
+    unsafe fn get_ray_tracing_shader_group_handles(
+        &self,
+        _pipeline: &(),
+        _first_group: u32,
+        _group_count: u32,
+    ) -> Result<Vec<u8>, d::OutOfMemory> {
+        unimplemented!()
+    }
+
     unsafe fn create_framebuffer<I>(
         &self,
         _render_pass: &n::RenderPass,
@@ -1180,34 +1635,44 @@ impl d::Device<B> for Device {
         &self,
         raw_data: &[u32],
     ) -> Result<n::ShaderModule, d::ShaderError> {
+        let naga = if cfg!(feature = "cross") {
+            Err("Cross is enabled".into())
+        } else {
+            let options = naga::front::spv::Options {
+                adjust_coordinate_space: !self.features.contains(hal::Features::NDC_Y_UP),
+                strict_capabilities: true,
+                flow_graph_dump_prefix: None,
+            };
+            let parser = naga::front::spv::Parser::new(raw_data.iter().cloned(), &options);
+            match parser.parse() {
+                Ok(module) => {
+                    log::debug!("Naga module {:#?}", module);
+                    match naga::valid::Validator::new(
+                        naga::valid::ValidationFlags::empty(),
+                        naga::valid::Capabilities::empty(), //TODO: PUSH_CONSTANT
+                    )
+                    .validate(&module)
+                    {
+                        Ok(info) => Ok(d::NagaShader { module, info }),
+                        Err(e) => Err(format!("Naga validation: {}", e)),
+                    }
+                }
+                Err(e) => Err(format!("Naga parsing: {:?}", e)),
+            }
+        };
+
+        // Without SPIRV-Cross to fall back on, a module that naga can't
+        // translate would only fail later, when creating a pipeline.
+        #[cfg(not(feature = "cross"))]
+        if let Err(ref e) = naga {
+            log::warn!("Unable to translate SPIR-V to GLSL: {}", e);
+            return Err(d::ShaderError::CompilationFailed(e.clone()));
+        }
+
         Ok(n::ShaderModule {
             #[cfg(feature = "cross")]
             spv: raw_data.to_vec(),
-            naga: if cfg!(feature = "cross") {
-                Err("Cross is enabled".into())
-            } else {
-                let options = naga::front::spv::Options {
-                    adjust_coordinate_space: !self.features.contains(hal::Features::NDC_Y_UP),
-                    strict_capabilities: true,
-                    flow_graph_dump_prefix: None,
-                };
-                let parser = naga::front::spv::Parser::new(raw_data.iter().cloned(), &options);
-                match parser.parse() {
-                    Ok(module) => {
-                        log::debug!("Naga module {:#?}", module);
-                        match naga::valid::Validator::new(
-                            naga::valid::ValidationFlags::empty(),
-                            naga::valid::Capabilities::empty(), //TODO: PUSH_CONSTANT
-                        )
-                        .validate(&module)
-                        {
-                            Ok(info) => Ok(d::NagaShader { module, info }),
-                            Err(e) => Err(format!("Naga validation: {}", e)),
-                        }
-                    }
-                    Err(e) => Err(format!("Naga parsing: {:?}", e)),
-                }
-            },
+            naga,
         })
     }
 
@@ -1341,6 +1806,23 @@ impl d::Device<B> for Device {
                 ptr
             };
 
+            ptr.offset(offset as isize)
+        } else if memory.map_flags & glow::MAP_PERSISTENT_BIT != 0 {
+            // Persistent mappings stay valid while the device uses the buffer,
+            // so the whole buffer is mapped once, and every `map_memory` reuses it.
+            let ptr = match memory.persistent_mapping {
+                Some(ptr) => ptr,
+                None => {
+                    gl.bind_buffer(target, Some(buffer));
+                    let ptr = gl.map_buffer_range(target, 0, memory.size as i32, memory.map_flags);
+                    gl.bind_buffer(target, None);
+                    if !ptr.is_null() {
+                        memory.persistent_mapping = Some(ptr);
+                    }
+                    ptr
+                }
+            };
+
             ptr.offset(offset as isize)
         } else {
             gl.bind_buffer(target, Some(buffer));
@@ -1359,6 +1841,10 @@ impl d::Device<B> for Device {
     unsafe fn unmap_memory(&self, memory: &mut n::Memory) {
         let gl = &self.share.context;
         let (buffer, target) = memory.buffer.expect("cannot unmap image memory");
+        if memory.persistent_mapping.is_some() {
+            // The mapping is kept until the memory is freed.
+            return;
+        }
 
         gl.bind_buffer(target, Some(buffer));
 
@@ -1425,10 +1911,10 @@ impl d::Device<B> for Device {
                 let ptr = mem.emulate_map_allocation.unwrap();
                 let slice = slice::from_raw_parts_mut(ptr.offset(offset as isize), size as usize);
                 gl.get_buffer_sub_data(target, offset as i32, slice);
-            } else {
-                gl.invalidate_buffer_sub_data(target, offset as i32, size as i32);
-                gl.bind_buffer(target, None);
             }
+            // Otherwise, the CPU-visible memory is coherent, and there is nothing to invalidate.
+            // `glInvalidateBufferSubData` would discard the contents of the buffer instead.
+            gl.bind_buffer(target, None);
 
             if let Err(err) = self.share.check() {
                 panic!(
@@ -1443,10 +1929,79 @@ impl d::Device<B> for Device {
 
     unsafe fn create_buffer_view(
         &self,
-        _: &n::Buffer,
-        _: Option<Format>,
-        _: buffer::SubRange,
+        buffer: &n::Buffer,
+        format: Option<Format>,
+        sub: buffer::SubRange,
     ) -> Result<n::BufferView, buffer::ViewCreationError> {
+        let caps = &self.share.private_caps;
+        if !caps.texture_buffer {
+            return Err(buffer::ViewCreationError::UnsupportedFormat(format));
+        }
+        let is_embedded = self.share.info.version.is_embedded;
+        let desc = format
+            .and_then(conv::describe_format)
+            .filter(|desc| conv::is_texture_buffer_format(desc.tex_internal, is_embedded))
+            .ok_or(buffer::ViewCreationError::UnsupportedFormat(format))?;
+
+        let bounded_buffer = buffer.as_bound();
+        let range = crate::resolve_sub_range(&sub, bounded_buffer.range.clone());
+        // Without `glTexBufferRange` the view always starts at the beginning of the GL buffer.
+        let is_whole = range.start == 0;
+
+        let gl = &self.share.context;
+        let raw = gl.create_texture().unwrap();
+        gl.bind_texture(glow::TEXTURE_BUFFER, Some(raw));
+        if caps.texture_buffer_range {
+            gl.tex_buffer_range(
+                glow::TEXTURE_BUFFER,
+                desc.tex_internal,
+                Some(bounded_buffer.raw),
+                range.start as i32,
+                (range.end - range.start) as i32,
+            );
+        } else if is_whole {
+            gl.tex_buffer(
+                glow::TEXTURE_BUFFER,
+                desc.tex_internal,
+                Some(bounded_buffer.raw),
+            );
+        } else {
+            log::warn!(
+                "Texel buffer views of a sub-range {:?} are not supported",
+                range
+            );
+            gl.bind_texture(glow::TEXTURE_BUFFER, None);
+            gl.delete_texture(raw);
+            return Err(buffer::ViewCreationError::UnsupportedFormat(format));
+        }
+        gl.bind_texture(glow::TEXTURE_BUFFER, None);
+
+        if let Err(err) = self.share.check() {
+            panic!("Error creating buffer view: {:?} for {:?}", err, format);
+        }
+
+        Ok(n::BufferView { raw })
+    }
+
+    unsafe fn get_acceleration_structure_build_requirements(
+        &self,
+        _desc: &hal::acceleration_structure::GeometryDesc<B>,
+        _max_primitive_counts: &[u32],
+    ) -> hal::acceleration_structure::SizeRequirements {
+        unimplemented!()
+    }
+
+    unsafe fn create_acceleration_structure(
+        &self,
+        _desc: &hal::acceleration_structure::CreateDesc<B>,
+    ) -> Result<(), d::OutOfMemory> {
+        unimplemented!()
+    }
+
+    unsafe fn get_acceleration_structure_address(
+        &self,
+        _accel_struct: &(),
+    ) -> hal::acceleration_structure::DeviceAddress {
         unimplemented!()
     }
 
@@ -1458,15 +2013,32 @@ impl d::Device<B> for Device {
         _tiling: i::Tiling,
         usage: i::Usage,
         _sparse: memory::SparseFlags,
-        _view_caps: i::ViewCapabilities,
+        view_caps: i::ViewCapabilities,
     ) -> Result<n::Image, i::CreationError> {
         let gl = &self.share.context;
 
-        let desc = conv::describe_format(format).ok_or(i::CreationError::Format(format))?;
+        let desc = match conv::describe_compressed_format(format) {
+            Some((internal_format, compression)) => {
+                if !self
+                    .share
+                    .private_caps
+                    .texture_compression
+                    .contains(compression)
+                {
+                    return Err(i::CreationError::Format(format));
+                }
+                conv::FormatDescription::compressed(internal_format)
+            }
+            None => conv::describe_format(format).ok_or(i::CreationError::Format(format))?,
+        };
         let channel = format.base_format().1;
+        let surface_desc = format.base_format().0.desc();
 
         let mut pixel_count: u64 = 0;
-        let image = if num_levels > 1 || usage.intersects(i::Usage::STORAGE | i::Usage::SAMPLED) {
+        let image = if num_levels > 1
+            || usage.intersects(i::Usage::STORAGE | i::Usage::SAMPLED)
+            || surface_desc.is_compressed()
+        {
             let name = gl.create_texture().unwrap();
             let target = match kind {
                 i::Kind::D2(w, h, 1, 1) => {
@@ -1505,23 +2077,50 @@ impl d::Device<B> for Device {
                             h = std::cmp::max(h / 2, 1);
                         }
                     }
-                    match channel {
-                        ChannelType::Uint | ChannelType::Sint => {
-                            gl.tex_parameter_i32(
-                                glow::TEXTURE_2D,
-                                glow::TEXTURE_MIN_FILTER,
-                                glow::NEAREST as _,
-                            );
-                            gl.tex_parameter_i32(
-                                glow::TEXTURE_2D,
-                                glow::TEXTURE_MAG_FILTER,
-                                glow::NEAREST as _,
-                            );
-                        }
-                        _ => {}
-                    };
                     glow::TEXTURE_2D
                 }
+                // Cube-compatible images are created as cube maps, since there are
+                // no texture views to reinterpret a 2D array as a cube.
+                i::Kind::D2(w, h, 6, 1) if view_caps.contains(i::ViewCapabilities::KIND_CUBE) => {
+                    gl.bind_texture(glow::TEXTURE_CUBE_MAP, Some(name));
+                    if self.share.private_caps.image_storage {
+                        gl.tex_storage_2d(
+                            glow::TEXTURE_CUBE_MAP,
+                            num_levels as _,
+                            desc.tex_internal,
+                            w as _,
+                            h as _,
+                        );
+                        pixel_count += (w * h) as u64 * 6 * num_levels as u64;
+                    } else {
+                        gl.tex_parameter_i32(
+                            glow::TEXTURE_CUBE_MAP,
+                            glow::TEXTURE_MAX_LEVEL,
+                            (num_levels - 1) as _,
+                        );
+                        let mut w = w;
+                        let mut h = h;
+                        for i in 0..num_levels {
+                            for face in 0..6 {
+                                gl.tex_image_2d(
+                                    glow::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                                    i as _,
+                                    desc.tex_internal as i32,
+                                    w as _,
+                                    h as _,
+                                    0,
+                                    desc.tex_external,
+                                    desc.data_type,
+                                    None,
+                                );
+                            }
+                            pixel_count += (w * h) as u64 * 6;
+                            w = std::cmp::max(w / 2, 1);
+                            h = std::cmp::max(h / 2, 1);
+                        }
+                    }
+                    glow::TEXTURE_CUBE_MAP
+                }
                 i::Kind::D2(w, h, l, 1) => {
                     gl.bind_texture(glow::TEXTURE_2D_ARRAY, Some(name));
                     if self.share.private_caps.image_storage {
@@ -1560,25 +2159,60 @@ impl d::Device<B> for Device {
                             h = std::cmp::max(h / 2, 1);
                         }
                     }
-                    match channel {
-                        ChannelType::Uint | ChannelType::Sint => {
-                            gl.tex_parameter_i32(
-                                glow::TEXTURE_2D,
-                                glow::TEXTURE_MIN_FILTER,
-                                glow::NEAREST as _,
-                            );
-                            gl.tex_parameter_i32(
-                                glow::TEXTURE_2D,
-                                glow::TEXTURE_MAG_FILTER,
-                                glow::NEAREST as _,
+                    glow::TEXTURE_2D_ARRAY
+                }
+                i::Kind::D3(w, h, d) => {
+                    gl.bind_texture(glow::TEXTURE_3D, Some(name));
+                    if self.share.private_caps.image_storage {
+                        gl.tex_storage_3d(
+                            glow::TEXTURE_3D,
+                            num_levels as _,
+                            desc.tex_internal,
+                            w as _,
+                            h as _,
+                            d as _,
+                        );
+                    } else {
+                        gl.tex_parameter_i32(
+                            glow::TEXTURE_3D,
+                            glow::TEXTURE_MAX_LEVEL,
+                            (num_levels - 1) as _,
+                        );
+                    }
+                    let mut w = w;
+                    let mut h = h;
+                    let mut d = d;
+                    for i in 0..num_levels {
+                        if !self.share.private_caps.image_storage {
+                            gl.tex_image_3d(
+                                glow::TEXTURE_3D,
+                                i as _,
+                                desc.tex_internal as i32,
+                                w as _,
+                                h as _,
+                                d as _,
+                                0,
+                                desc.tex_external,
+                                desc.data_type,
+                                None,
                             );
                         }
-                        _ => {}
-                    };
-                    glow::TEXTURE_2D_ARRAY
+                        pixel_count += (w * h) as u64 * d as u64;
+                        w = std::cmp::max(w / 2, 1);
+                        h = std::cmp::max(h / 2, 1);
+                        d = std::cmp::max(d / 2, 1);
+                    }
+                    glow::TEXTURE_3D
                 }
                 _ => unimplemented!(),
             };
+            match channel {
+                ChannelType::Uint | ChannelType::Sint => {
+                    gl.tex_parameter_i32(target, glow::TEXTURE_MIN_FILTER, glow::NEAREST as _);
+                    gl.tex_parameter_i32(target, glow::TEXTURE_MAG_FILTER, glow::NEAREST as _);
+                }
+                _ => {}
+            };
             n::ImageType::Texture {
                 target,
                 raw: name,
@@ -1610,12 +2244,16 @@ impl d::Device<B> for Device {
             n::ImageType::Renderbuffer {
                 raw: name,
                 format: desc.tex_external,
+                pixel_type: desc.data_type,
             }
         };
 
-        let surface_desc = format.base_format().0.desc();
-        let bytes_per_texel = surface_desc.bits / 8;
-        let size = pixel_count as u64 * bytes_per_texel as u64;
+        let size = if surface_desc.is_compressed() {
+            let (block_width, block_height) = surface_desc.dim;
+            pixel_count * surface_desc.bits as u64 / (8 * block_width as u64 * block_height as u64)
+        } else {
+            pixel_count * (surface_desc.bits / 8) as u64
+        };
         let type_mask = self.share.image_memory_type_mask();
 
         if let Err(err) = self.share.check() {
@@ -1679,7 +2317,7 @@ impl d::Device<B> for Device {
         kind: i::ViewKind,
         view_format: Format,
         swizzle: Swizzle,
-        _usage: i::Usage,
+        usage: i::Usage,
         range: i::SubresourceRange,
     ) -> Result<n::ImageView, i::ViewCreationError> {
         assert_eq!(swizzle, Swizzle::NO);
@@ -1707,7 +2345,13 @@ impl d::Device<B> for Device {
                     i::ViewKind::D1 | i::ViewKind::D2 => false,
                     _ => true,
                 };
-                match conv::describe_format(view_format) {
+                let view_description = match conv::describe_compressed_format(view_format) {
+                    Some((internal_format, _)) => {
+                        Some(conv::FormatDescription::compressed(internal_format))
+                    }
+                    None => conv::describe_format(view_format),
+                };
+                match view_description {
                     Some(description) => {
                         let raw_view_format = description.tex_external;
                         if format != raw_view_format {
@@ -1722,11 +2366,29 @@ impl d::Device<B> for Device {
                         log::warn!("View format {:?} is not supported", view_format);
                     }
                 }
+
+                // The texture keeps its format, so only the encoding of the rendered colors
+                // can follow the format of the view, by toggling `FRAMEBUFFER_SRGB`.
+                let view_srgb = view_format.base_format().1 == ChannelType::Srgb;
+                let skip_srgb = image.channel == ChannelType::Srgb && !view_srgb;
+                if skip_srgb && usage.contains(i::Usage::SAMPLED) {
+                    log::warn!(
+                        "Linear view {:?} of an sRGB texture is sampled with sRGB decoding",
+                        view_format
+                    );
+                } else if view_srgb && image.channel != ChannelType::Srgb {
+                    log::warn!(
+                        "sRGB view {:?} of a linear texture isn't encoded nor decoded",
+                        view_format
+                    );
+                }
+
                 Ok(n::ImageView::Texture {
                     target,
                     raw,
                     is_3d,
                     sub: range,
+                    skip_srgb,
                 })
             }
         }
@@ -1827,7 +2489,12 @@ impl d::Device<B> for Device {
                     n::FatSampler::Sampler(sampler) => n::DescSetBindings::Sampler(sampler),
                     n::FatSampler::Info(ref info) => n::DescSetBindings::SamplerDesc(info.clone()),
                 },
-                pso::Descriptor::TexelBuffer(_view) => unimplemented!(),
+                pso::Descriptor::TexelBuffer(view) => {
+                    n::DescSetBindings::Texture(view.raw, glow::TEXTURE_BUFFER)
+                }
+                pso::Descriptor::AccelerationStructure(_)
+                | pso::Descriptor::Mutable(..)
+                | pso::Descriptor::Null(_) => unimplemented!(),
             };
 
             //TODO: overwrite instead of pushing on top
@@ -1864,11 +2531,111 @@ impl d::Device<B> for Device {
         Ok(n::Semaphore)
     }
 
+    fn create_timeline_semaphore(
+        &self,
+        initial_value: u64,
+    ) -> Result<n::TimelineSemaphore, d::OutOfMemory> {
+        Ok(n::TimelineSemaphore {
+            state: Mutex::new(n::TimelineState {
+                value: initial_value,
+                pending: Vec::new(),
+            }),
+            condvar: Default::default(),
+        })
+    }
+
+    unsafe fn get_timeline_semaphore_value(
+        &self,
+        semaphore: &n::TimelineSemaphore,
+    ) -> Result<u64, d::DeviceLost> {
+        let mut state = semaphore.state.lock();
+        update_timeline(&self.share.context, &mut state);
+        Ok(state.value)
+    }
+
+    unsafe fn signal_timeline_semaphore(
+        &self,
+        semaphore: &n::TimelineSemaphore,
+        value: u64,
+    ) -> Result<(), d::OutOfMemory> {
+        semaphore.state.lock().value = value;
+        semaphore.condvar.notify_all();
+        Ok(())
+    }
+
+    unsafe fn wait_for_timeline_semaphore(
+        &self,
+        semaphore: &n::TimelineSemaphore,
+        value: u64,
+        timeout_ns: u64,
+    ) -> Result<bool, d::WaitError> {
+        let gl = &self.share.context;
+        // The deadline is only computed if the value isn't signaled yet.
+        let mut deadline = None;
+        let mut timed_out = false;
+        // Wait for the value to be signaled on the host, or for a submission
+        // signaling a large enough value.
+        let sync = {
+            let mut state = semaphore.state.lock();
+            loop {
+                update_timeline(gl, &mut state);
+                if state.value >= value {
+                    return Ok(true);
+                }
+                if let Some(&(_, sync)) =
+                    state.pending.iter().find(|&&(pending, _)| pending >= value)
+                {
+                    break sync;
+                }
+                // WebGL has a single thread, so nothing else can signal the value.
+                if timed_out || timeout_ns == 0 || crate::is_webgl() {
+                    return Ok(false);
+                }
+                let deadline = *deadline.get_or_insert_with(|| {
+                    Instant::now().checked_add(Duration::from_nanos(timeout_ns))
+                });
+                match deadline {
+                    Some(deadline) => {
+                        timed_out = semaphore
+                            .condvar
+                            .wait_until(&mut state, deadline)
+                            .timed_out();
+                    }
+                    None => semaphore.condvar.wait(&mut state),
+                }
+            }
+        };
+        let remaining_ns = match deadline {
+            Some(Some(deadline)) => deadline
+                .saturating_duration_since(Instant::now())
+                .as_nanos() as u64,
+            Some(None) => !0,
+            None => timeout_ns,
+        };
+        match client_wait_sync(gl, sync, remaining_ns) {
+            glow::TIMEOUT_EXPIRED => Ok(false),
+            glow::WAIT_FAILED => {
+                if let Err(err) = self.share.check() {
+                    log::error!("Error when waiting on timeline semaphore: {:?}", err);
+                }
+                Ok(false)
+            }
+            glow::CONDITION_SATISFIED | glow::ALREADY_SIGNALED => {
+                self.get_timeline_semaphore_value(semaphore)?;
+                Ok(true)
+            }
+            _ => unreachable!(),
+        }
+    }
+
     fn create_fence(&self, signaled: bool) -> Result<n::Fence, d::OutOfMemory> {
         Ok(n::Fence::Idle { signaled })
     }
 
     unsafe fn reset_fence(&self, fence: &mut n::Fence) -> Result<(), d::OutOfMemory> {
+        if let n::Fence::Pending(sync) = *fence {
+            self.share.context.delete_sync(sync);
+        }
         *fence = n::Fence::Idle { signaled: false };
         Ok(())
     }
@@ -1891,8 +2658,7 @@ impl d::Device<B> for Device {
                 Ok(signaled)
             }
             n::Fence::Pending(sync) => {
-                // TODO: Could `wait_sync` be used here instead?
-                match gl.client_wait_sync(sync, glow::SYNC_FLUSH_COMMANDS_BIT, timeout_ns as i32) {
+                match client_wait_sync(gl, sync, timeout_ns) {
                     glow::TIMEOUT_EXPIRED => Ok(false),
                     glow::WAIT_FAILED => {
                         if let Err(err) = self.share.check() {
@@ -1982,7 +2748,19 @@ impl d::Device<B> for Device {
 
     unsafe fn free_memory(&self, memory: n::Memory) {
         if let Some((buffer, _)) = memory.buffer {
-            self.share.context.delete_buffer(buffer);
+            let gl = &self.share.context;
+            // Vertex arrays keep the buffer alive, and its name may be reused.
+            self.share
+                .vertex_arrays
+                .lock()
+                .retain(|bindings, &mut vao| {
+                    let used = bindings.iter().any(|binding| binding.buffer == buffer);
+                    if used {
+                        gl.delete_vertex_array(vao);
+                    }
+                    !used
+                });
+            gl.delete_buffer(buffer);
         }
     }
 
@@ -2009,6 +2787,10 @@ impl d::Device<B> for Device {
         unimplemented!()
     }
 
+    unsafe fn reset_query_pool(&self, _pool: &(), _queries: Range<query::Id>) {
+        unimplemented!()
+    }
+
     unsafe fn destroy_shader_module(&self, _: n::ShaderModule) {
         // Assumes compiled shaders are managed internally
     }
@@ -2025,10 +2807,49 @@ impl d::Device<B> for Device {
         self.share.context.delete_program(pipeline.program);
     }
 
+    unsafe fn create_graphics_pipeline_library<'a>(
+        &self,
+        _desc: &pso::GraphicsPipelineDesc<'a, B>,
+        _parts: pso::GraphicsPipelineLibraryParts,
+        _cache: Option<&PipelineCache>,
+    ) -> Result<(), pso::CreationError> {
+        unimplemented!()
+    }
+
+    unsafe fn link_graphics_pipeline<'a>(
+        &self,
+        _desc: &pso::GraphicsPipelineLinkDesc<'a, B>,
+        _cache: Option<&PipelineCache>,
+    ) -> Result<n::GraphicsPipeline, pso::CreationError> {
+        unimplemented!()
+    }
+
+    unsafe fn destroy_graphics_pipeline_library(&self, _library: ()) {
+        unimplemented!()
+    }
+
     unsafe fn destroy_compute_pipeline(&self, pipeline: n::ComputePipeline) {
         self.share.context.delete_program(pipeline.program);
     }
 
+    unsafe fn get_graphics_pipeline_executables(
+        &self,
+        _pipeline: &n::GraphicsPipeline,
+    ) -> Vec<pso::PipelineExecutable> {
+        unimplemented!()
+    }
+
+    unsafe fn get_compute_pipeline_executables(
+        &self,
+        _pipeline: &n::ComputePipeline,
+    ) -> Vec<pso::PipelineExecutable> {
+        unimplemented!()
+    }
+
+    unsafe fn destroy_ray_tracing_pipeline(&self, _pipeline: ()) {
+        unimplemented!()
+    }
+
     unsafe fn destroy_framebuffer(&self, framebuffer: n::Framebuffer) {
         self.share.context.delete_framebuffer(framebuffer.raw);
     }
@@ -2037,8 +2858,12 @@ impl d::Device<B> for Device {
         // Nothing to do
     }
 
-    unsafe fn destroy_buffer_view(&self, _: n::BufferView) {
-        // Nothing to do
+    unsafe fn destroy_buffer_view(&self, view: n::BufferView) {
+        self.share.context.delete_texture(view.raw);
+    }
+
+    unsafe fn destroy_acceleration_structure(&self, _accel_struct: ()) {
+        unimplemented!()
     }
 
     unsafe fn destroy_image(&self, image: n::Image) {
@@ -2082,6 +2907,12 @@ impl d::Device<B> for Device {
         // Nothing to do
     }
 
+    unsafe fn destroy_timeline_semaphore(&self, semaphore: n::TimelineSemaphore) {
+        for (_, sync) in semaphore.state.into_inner().pending {
+            self.share.context.delete_sync(sync);
+        }
+    }
+
     unsafe fn destroy_event(&self, _event: ()) {
         unimplemented!()
     }
@@ -2093,12 +2924,22 @@ impl d::Device<B> for Device {
         Ok(())
     }
 
-    unsafe fn set_image_name(&self, _image: &mut n::Image, _name: &str) {
-        // TODO
+    unsafe fn set_image_name(&self, image: &mut n::Image, name: &str) {
+        match image.object_type {
+            n::ImageType::Renderbuffer { raw, .. } => {
+                set_object_label(&self.share, glow::RENDERBUFFER, raw, name)
+            }
+            n::ImageType::Texture { raw, .. } => {
+                set_object_label(&self.share, glow::TEXTURE, raw, name)
+            }
+        }
     }
 
-    unsafe fn set_buffer_name(&self, _buffer: &mut n::Buffer, _name: &str) {
-        // TODO
+    unsafe fn set_buffer_name(&self, buffer: &mut n::Buffer, name: &str) {
+        // The GL buffer belongs to the memory, so it's named after the last buffer bound to it.
+        if let n::Buffer::Bound { buffer, .. } = *buffer {
+            set_object_label(&self.share, glow::BUFFER, buffer, name);
+        }
     }
 
     unsafe fn set_command_buffer_name(
@@ -2117,12 +2958,12 @@ impl d::Device<B> for Device {
         // TODO
     }
 
-    unsafe fn set_framebuffer_name(&self, _framebuffer: &mut n::Framebuffer, _name: &str) {
-        // TODO
+    unsafe fn set_framebuffer_name(&self, framebuffer: &mut n::Framebuffer, name: &str) {
+        set_object_label(&self.share, glow::FRAMEBUFFER, framebuffer.raw, name);
     }
 
-    unsafe fn set_render_pass_name(&self, _render_pass: &mut n::RenderPass, _name: &str) {
-        // TODO
+    unsafe fn set_render_pass_name(&self, render_pass: &mut n::RenderPass, name: &str) {
+        render_pass.label = Some(name.to_string());
     }
 
     unsafe fn set_descriptor_set_name(&self, _descriptor_set: &mut n::DescriptorSet, _name: &str) {
@@ -2145,6 +2986,27 @@ impl d::Device<B> for Device {
         // TODO
     }
 
+    unsafe fn create_private_data_slot(&self) -> Result<(), d::OutOfMemory> {
+        unimplemented!()
+    }
+
+    unsafe fn destroy_private_data_slot(&self, _slot: ()) {
+        unimplemented!()
+    }
+
+    unsafe fn set_private_data(
+        &self,
+        _object: d::PrivateDataObject<B>,
+        _slot: &(),
+        _data: u64,
+    ) -> Result<(), d::OutOfMemory> {
+        unimplemented!()
+    }
+
+    unsafe fn get_private_data(&self, _object: d::PrivateDataObject<B>, _slot: &()) -> u64 {
+        unimplemented!()
+    }
+
     unsafe fn set_display_power_state(
         &self,
         _display: &hal::display::Display<B>,
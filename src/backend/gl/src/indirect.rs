@@ -0,0 +1,86 @@
+//! Multi-draw indirect (`GL_ARB_multi_draw_indirect`).
+//!
+//! Several draws are read from the parameter buffer bound to `DRAW_INDIRECT_BUFFER`
+//! with a single call. Without these entry points, the draws are issued one by one
+//! with `glDraw*Indirect`.
+
+use std::fmt;
+
+/// Entry points of `GL_ARB_multi_draw_indirect`, which aren't exposed by `glow`.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct MultiDrawIndirectFns {
+    multi_draw_arrays_indirect: unsafe extern "system" fn(u32, *const std::ffi::c_void, i32, i32),
+    multi_draw_elements_indirect:
+        unsafe extern "system" fn(u32, u32, *const std::ffi::c_void, i32, i32),
+}
+
+/// Indirect draws are not available on WebGL.
+#[cfg(target_arch = "wasm32")]
+pub(crate) enum MultiDrawIndirectFns {}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl MultiDrawIndirectFns {
+    /// Load the entry points with `get_proc_address`, from the core version or
+    /// from `GL_EXT_multi_draw_indirect`.
+    pub(crate) unsafe fn load(
+        mut get_proc_address: impl FnMut(&str) -> *const std::ffi::c_void,
+    ) -> Option<Self> {
+        let mut load = |name: &str| {
+            let ptr = get_proc_address(name);
+            if ptr.is_null() {
+                get_proc_address(&format!("{}EXT", name))
+            } else {
+                ptr
+            }
+        };
+        let multi_draw_arrays_indirect = load("glMultiDrawArraysIndirect");
+        let multi_draw_elements_indirect = load("glMultiDrawElementsIndirect");
+        if multi_draw_arrays_indirect.is_null() || multi_draw_elements_indirect.is_null() {
+            return None;
+        }
+        Some(MultiDrawIndirectFns {
+            multi_draw_arrays_indirect: std::mem::transmute(multi_draw_arrays_indirect),
+            multi_draw_elements_indirect: std::mem::transmute(multi_draw_elements_indirect),
+        })
+    }
+
+    /// Draw `draw_count` non-indexed draws, read at `offset` in the bound parameter buffer.
+    pub(crate) unsafe fn draw_arrays(&self, mode: u32, offset: u64, draw_count: u32, stride: u32) {
+        (self.multi_draw_arrays_indirect)(mode, offset as _, draw_count as _, stride as _);
+    }
+
+    /// Draw `draw_count` indexed draws, read at `offset` in the bound parameter buffer.
+    pub(crate) unsafe fn draw_elements(
+        &self,
+        mode: u32,
+        index_type: u32,
+        offset: u64,
+        draw_count: u32,
+        stride: u32,
+    ) {
+        (self.multi_draw_elements_indirect)(
+            mode,
+            index_type,
+            offset as _,
+            draw_count as _,
+            stride as _,
+        );
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl MultiDrawIndirectFns {
+    pub(crate) unsafe fn draw_arrays(&self, _: u32, _: u64, _: u32, _: u32) {
+        match *self {}
+    }
+
+    pub(crate) unsafe fn draw_elements(&self, _: u32, _: u32, _: u64, _: u32, _: u32) {
+        match *self {}
+    }
+}
+
+impl fmt::Debug for MultiDrawIndirectFns {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("MultiDrawIndirectFns")
+    }
+}
@@ -3,6 +3,12 @@ use glow::HasContext;
 use hal::{DynamicStates, Features, Limits, PerformanceCaveats, PhysicalDeviceProperties};
 use std::{collections::HashSet, fmt, str};
 
+/// Number of dynamic buffers exposed per descriptor set.
+///
+/// Dynamic offsets are applied when recording `glBindBufferRange`, so there is
+/// no hard limit, and this is the minimum required by Vulkan for uniform buffers.
+const MAX_DYNAMIC_BUFFERS: u32 = 8;
+
 /// A version number for a specific component of an OpenGL implementation
 #[derive(Clone, Eq, Ord, PartialEq, PartialOrd)]
 pub struct Version {
@@ -225,10 +231,33 @@ pub struct PrivateCaps {
     pub draw_buffers: bool,
     /// Whether separate color masks per output buffer are supported.
     pub per_slot_color_mask: bool,
-    /// Reading from textures into CPU memory is supported.
-    pub get_tex_image: bool,
     /// Inserting memory barriers.
     pub memory_barrier: bool,
+    /// `FRAMEBUFFER_SRGB` can be toggled, to render linear views of sRGB textures.
+    pub framebuffer_srgb: bool,
+    /// Several indirect draws can be issued with a single call.
+    pub multi_draw_indirect: bool,
+    /// Debug output, object labels and debug groups (`KHR_debug`) are supported.
+    pub debug: bool,
+    /// Shader storage buffer objects are supported.
+    pub storage_buffers: bool,
+    /// Texture buffer objects (`glTexBuffer`) are supported.
+    pub texture_buffer: bool,
+    /// Texture buffer objects can be bound to a sub-range of a buffer (`glTexBufferRange`).
+    pub texture_buffer_range: bool,
+    /// Number of user clip planes (`GL_CLIP_DISTANCEi`) that can be enabled.
+    pub max_clip_distances: u32,
+    /// Primitive restart at the largest index value can be toggled
+    /// (`GL_PRIMITIVE_RESTART_FIXED_INDEX`). WebGL 2 always has it enabled.
+    pub primitive_restart_fixed_index: bool,
+    /// Linked programs can be retrieved and loaded as binaries (`GL_ARB_get_program_binary`),
+    /// in at least one format.
+    pub program_binary: bool,
+    /// Textures can be referenced by 64-bit handles (`GL_ARB_bindless_texture`).
+    pub bindless_texture: bool,
+    /// Supported families of compressed texture formats. They are only used
+    /// with immutable storage (`image_storage`).
+    pub texture_compression: TextureCompression,
 }
 
 /// OpenGL implementation information
@@ -279,6 +308,24 @@ bitflags::bitflags! {
     }
 }
 
+bitflags::bitflags! {
+    /// Families of compressed texture formats, which are exposed by separate extensions.
+    pub struct TextureCompression: u8 {
+        /// BC1, BC2 and BC3 (`GL_EXT_texture_compression_s3tc`).
+        const S3TC = 0x01;
+        /// sRGB variants of BC1, BC2 and BC3 (`GL_EXT_texture_sRGB`).
+        const S3TC_SRGB = 0x02;
+        /// BC4 and BC5 (`GL_ARB_texture_compression_rgtc`).
+        const RGTC = 0x04;
+        /// BC6H and BC7 (`GL_ARB_texture_compression_bptc`).
+        const BPTC = 0x08;
+        /// ETC2 and EAC, part of OpenGL ES 3.0.
+        const ETC2 = 0x10;
+        /// ASTC with low dynamic range (`GL_KHR_texture_compression_astc_ldr`).
+        const ASTC_LDR = 0x20;
+    }
+}
+
 #[derive(Copy, Clone)]
 pub enum Requirement<'a> {
     Core(u32, u32),
@@ -601,10 +648,21 @@ pub(crate) fn query_all(
     } else {
         get_usize(gl, glow::MAX_TEXTURE_BUFFER_SIZE).unwrap_or(0)
     };
-    let min_storage_buffer_offset_alignment = if crate::is_webgl() {
-        256
-    } else {
+    let min_texel_buffer_offset_alignment =
+        if info.is_supported(&[Core(4, 3), Es(3, 2), Ext("GL_ARB_texture_buffer_range")]) {
+            get_u64(gl, glow::TEXTURE_BUFFER_OFFSET_ALIGNMENT).unwrap_or(256)
+        } else {
+            1
+        };
+    let storage_buffers = info.is_supported(&[
+        Core(4, 3),
+        Es(3, 1),
+        Ext("GL_ARB_shader_storage_buffer_object"),
+    ]) && !crate::is_webgl();
+    let min_storage_buffer_offset_alignment = if storage_buffers {
         get_u64(gl, glow::SHADER_STORAGE_BUFFER_OFFSET_ALIGNMENT).unwrap_or(256)
+    } else {
+        256
     };
 
     let mut limits = Limits {
@@ -617,7 +675,7 @@ pub(crate) fn query_all(
         max_viewports: 1,
         optimal_buffer_copy_offset_alignment: 1,
         optimal_buffer_copy_pitch_alignment: 1,
-        min_texel_buffer_offset_alignment: 1,
+        min_texel_buffer_offset_alignment,
         min_uniform_buffer_offset_alignment: get_u64(gl, glow::UNIFORM_BUFFER_OFFSET_ALIGNMENT)
             .unwrap_or(1024),
         min_storage_buffer_offset_alignment,
@@ -629,7 +687,24 @@ pub(crate) fn query_all(
         ..Limits::default()
     };
 
-    if info.is_supported(&[Core(4, 0), Ext("GL_ARB_tessellation_shader")]) {
+    if storage_buffers {
+        // Vertex shaders may not have any storage blocks on GLES,
+        // so the per-stage limit is the one of the other stages.
+        let max_blocks = get_usize(gl, glow::MAX_FRAGMENT_SHADER_STORAGE_BLOCKS)
+            .unwrap_or(0)
+            .min(get_usize(gl, glow::MAX_COMPUTE_SHADER_STORAGE_BLOCKS).unwrap_or(0))
+            as u32;
+        let max_bindings =
+            get_usize(gl, glow::MAX_SHADER_STORAGE_BUFFER_BINDINGS).unwrap_or(0) as u32;
+        limits.max_per_stage_descriptor_storage_buffers = max_blocks;
+        limits.max_descriptor_set_storage_buffers = max_bindings;
+        limits.max_descriptor_set_storage_buffers_dynamic = max_bindings.min(MAX_DYNAMIC_BUFFERS);
+        limits.max_storage_buffer_range =
+            get_u64(gl, glow::MAX_SHADER_STORAGE_BLOCK_SIZE).unwrap_or(0);
+    }
+    limits.max_descriptor_set_uniform_buffers_dynamic = MAX_DYNAMIC_BUFFERS;
+
+    if info.is_supported(&[Core(4, 0), Es(3, 2), Ext("GL_ARB_tessellation_shader")]) {
         limits.max_patch_size = get_usize(gl, glow::MAX_PATCH_VERTICES).unwrap_or(0) as _;
     }
     if info.is_supported(&[Core(4, 1)]) {
@@ -656,6 +731,16 @@ pub(crate) fn query_all(
         }
     }
 
+    let max_clip_distances = if info.is_supported(&[
+        Core(3, 0),
+        Ext("GL_EXT_clip_cull_distance"),
+        Ext("GL_APPLE_clip_distance"),
+    ]) {
+        get_usize(gl, glow::MAX_CLIP_DISTANCES).unwrap_or(0) as u32
+    } else {
+        0
+    };
+
     let mut features = Features::NDC_Y_UP | Features::MUTABLE_COMPARISON_SAMPLER;
     // TODO: Fill out downlevel features correctly.
     let mut downlevel = hal::DownlevelProperties::all_enabled();
@@ -669,12 +754,36 @@ pub(crate) fn query_all(
     ]) {
         features |= Features::SAMPLER_ANISOTROPY;
     }
+    if max_clip_distances != 0 {
+        features |= Features::SHADER_CLIP_DISTANCE;
+    }
+    limits.max_clip_distances = max_clip_distances;
+    // Only SPIRV-Cross translates the tessellation and geometry stages.
+    if cfg!(feature = "cross") {
+        if info.is_supported(&[Core(4, 0), Es(3, 2), Ext("GL_ARB_tessellation_shader")]) {
+            features |= Features::TESSELLATION_SHADER;
+        }
+        // On ES 3.1, SPIRV-Cross enables `GL_EXT_geometry_shader` in the generated code.
+        if info.is_supported(&[Core(3, 2), Es(3, 2), Ext("GL_EXT_geometry_shader")]) {
+            features |= Features::GEOMETRY_SHADER;
+        }
+    }
     if info.is_supported(&[Core(4, 2), Es(3, 1)]) {
         legacy |= LegacyFeatures::EXPLICIT_LAYOUTS_IN_SHADER;
     }
     if info.is_supported(&[Core(3, 3), Es(3, 0), Ext("GL_ARB_instanced_arrays")]) {
         features |= Features::INSTANCE_RATE;
     }
+    // WebGL 2 always restarts at the largest index.
+    if info.is_supported(&[Core(4, 3), Es(3, 0), Ext("GL_ARB_ES3_compatibility")])
+        && !crate::is_webgl()
+    {
+        features |= Features::PRIMITIVE_RESTART_DYNAMIC_STATE;
+    }
+    // Timeline values are reached once the fence syncs of the submissions are signaled.
+    if info.is_supported(&[Core(3, 2), Es(3, 0), Ext("GL_ARB_sync")]) {
+        features |= Features::TIMELINE_SEMAPHORE;
+    }
     if info.is_supported(&[Core(3, 3)]) {
         // TODO: extension
         features |= Features::SAMPLER_MIP_LOD_BIAS;
@@ -690,10 +799,11 @@ pub(crate) fn query_all(
         features |= Features::INDEPENDENT_BLENDING;
     }
 
-    // TODO
-    if false && info.is_supported(&[Core(4, 3), Es(3, 1)]) {
-        // TODO: extension
+    if info.is_supported(&[Core(4, 0), Es(3, 1), Ext("GL_ARB_draw_indirect")]) && !crate::is_webgl()
+    {
         legacy |= LegacyFeatures::INDIRECT_EXECUTION;
+        // Draws are issued one by one without `GL_ARB_multi_draw_indirect`.
+        features |= Features::MULTI_DRAW_INDIRECT;
     }
     if info.is_supported(&[Core(3, 1), Es(3, 0), Ext("GL_ARB_draw_instanced")]) {
         legacy |= LegacyFeatures::DRAW_INSTANCED;
@@ -750,6 +860,60 @@ pub(crate) fn query_all(
         legacy |= LegacyFeatures::INSTANCED_ATTRIBUTE_BINDING;
     }
 
+    // WebGL extensions aren't listed, so compressed formats aren't exposed there.
+    let mut texture_compression = TextureCompression::empty();
+    if info.is_supported(&[Ext("GL_EXT_texture_compression_s3tc")]) {
+        texture_compression |= TextureCompression::S3TC;
+        if info.is_supported(&[
+            Ext("GL_EXT_texture_sRGB"),
+            Ext("GL_EXT_texture_compression_s3tc_srgb"),
+        ]) {
+            texture_compression |= TextureCompression::S3TC_SRGB;
+        }
+    }
+    if info.is_supported(&[
+        Core(3, 0),
+        Ext("GL_ARB_texture_compression_rgtc"),
+        Ext("GL_EXT_texture_compression_rgtc"),
+    ]) {
+        texture_compression |= TextureCompression::RGTC;
+    }
+    if info.is_supported(&[
+        Core(4, 2),
+        Ext("GL_ARB_texture_compression_bptc"),
+        Ext("GL_EXT_texture_compression_bptc"),
+    ]) {
+        texture_compression |= TextureCompression::BPTC;
+    }
+    if info.is_supported(&[Core(4, 3), Es(3, 0), Ext("GL_ARB_ES3_compatibility")])
+        && !crate::is_webgl()
+    {
+        texture_compression |= TextureCompression::ETC2;
+    }
+    if info.is_supported(&[Es(3, 2), Ext("GL_KHR_texture_compression_astc_ldr")])
+        && !crate::is_webgl()
+    {
+        texture_compression |= TextureCompression::ASTC_LDR;
+    }
+    // Compressed textures are only created with immutable storage.
+    if !info.is_supported(&[Core(4, 2), Es(3, 0), Ext("GL_ARB_texture_storage")]) {
+        texture_compression = TextureCompression::empty();
+    }
+    if texture_compression.contains(
+        TextureCompression::S3TC
+            | TextureCompression::S3TC_SRGB
+            | TextureCompression::RGTC
+            | TextureCompression::BPTC,
+    ) {
+        features |= Features::FORMAT_BC;
+    }
+    if texture_compression.contains(TextureCompression::ETC2) {
+        features |= Features::FORMAT_ETC2;
+    }
+    if texture_compression.contains(TextureCompression::ASTC_LDR) {
+        features |= Features::FORMAT_ASTC_LDR;
+    }
+
     let mut performance_caveats = PerformanceCaveats::empty();
     //TODO: extension
     if !info.is_supported(&[Core(4, 2)]) {
@@ -788,8 +952,43 @@ pub(crate) fn query_all(
         depth_range_f64_precision: !info.version.is_embedded, // TODO
         draw_buffers: info.is_supported(&[Core(2, 0), Es(3, 0)]),
         per_slot_color_mask: info.is_supported(&[Core(3, 0)]),
-        get_tex_image: !info.version.is_embedded,
         memory_barrier: info.is_supported(&[Core(4, 2), Es(3, 1)]),
+        storage_buffers,
+        debug: !crate::is_webgl() && gl.supports_debug(),
+        framebuffer_srgb: legacy.contains(LegacyFeatures::SRGB_COLOR) && !info.version.is_embedded
+            || info.is_supported(&[Ext("GL_EXT_sRGB_write_control")]),
+        multi_draw_indirect: info.is_supported(&[
+            Core(4, 3),
+            Ext("GL_ARB_multi_draw_indirect"),
+            Ext("GL_EXT_multi_draw_indirect"),
+        ]) && !crate::is_webgl(),
+        texture_buffer: max_texel_elements != 0
+            && info.is_supported(&[
+                Core(3, 1),
+                Es(3, 2),
+                Ext("GL_ARB_texture_buffer_object"),
+                Ext("GL_EXT_texture_buffer"),
+            ]),
+        texture_buffer_range: info.is_supported(&[
+            Core(4, 3),
+            Es(3, 2),
+            Ext("GL_ARB_texture_buffer_range"),
+        ]),
+        max_clip_distances,
+        primitive_restart_fixed_index: info.is_supported(&[
+            Core(4, 3),
+            Es(3, 0),
+            Ext("GL_ARB_ES3_compatibility"),
+        ]) && !crate::is_webgl(),
+        program_binary: info.is_supported(&[
+            Core(4, 1),
+            Es(3, 0),
+            Ext("GL_ARB_get_program_binary"),
+        ]) && !crate::is_webgl()
+            && get_usize(gl, glow::NUM_PROGRAM_BINARY_FORMATS).unwrap_or(0) != 0,
+        bindless_texture: info.is_supported(&[Ext("GL_ARB_bindless_texture")])
+            && !crate::is_webgl(),
+        texture_compression,
     };
 
     let filter = if info.is_supported(&[Es(3, 0)]) {
@@ -33,15 +33,19 @@ use std::{
 };
 
 use hal::{adapter, buffer, display, image, memory, queue as q};
+use parking_lot::Mutex;
 
 pub use self::device::Device;
 pub use self::info::{Info, PlatformName, Version};
 
+mod bindless;
 mod command;
 mod conv;
 mod device;
+mod indirect;
 mod info;
 mod native;
+mod pipeline_cache;
 mod pool;
 mod queue;
 mod state;
@@ -104,19 +108,24 @@ impl hal::Backend for Backend {
     type Image = native::Image;
     type ImageView = native::ImageView;
     type Sampler = native::FatSampler;
+    type AccelerationStructure = ();
 
     type ComputePipeline = native::ComputePipeline;
+    type RayTracingPipeline = ();
     type GraphicsPipeline = native::GraphicsPipeline;
+    type GraphicsPipelineLibrary = ();
     type PipelineLayout = native::PipelineLayout;
-    type PipelineCache = ();
+    type PipelineCache = pipeline_cache::PipelineCache;
     type DescriptorSetLayout = native::DescriptorSetLayout;
     type DescriptorPool = native::DescriptorPool;
     type DescriptorSet = native::DescriptorSet;
 
     type Fence = native::Fence;
     type Semaphore = native::Semaphore;
+    type TimelineSemaphore = native::TimelineSemaphore;
     type Event = ();
     type QueryPool = ();
+    type PrivateDataSlot = ();
 
     type Display = ();
     type DisplayMode = ();
@@ -155,16 +164,13 @@ fn debug_message_callback(source: u32, gltype: u32, id: u32, severity: u32, mess
         glow::DEBUG_SOURCE_THIRD_PARTY => "Third Party",
         glow::DEBUG_SOURCE_APPLICATION => "Application",
         glow::DEBUG_SOURCE_OTHER => "Other",
-        _ => unreachable!(),
+        _ => "Unknown",
     };
 
-    let log_severity = match severity {
-        glow::DEBUG_SEVERITY_HIGH => log::Level::Error,
-        glow::DEBUG_SEVERITY_MEDIUM => log::Level::Warn,
-        glow::DEBUG_SEVERITY_LOW => log::Level::Info,
-        glow::DEBUG_SEVERITY_NOTIFICATION => log::Level::Trace,
-        _ => unreachable!(),
-    };
+    let log_severity = DEBUG_SEVERITIES
+        .iter()
+        .find(|&&(value, _)| value == severity)
+        .map_or(log::Level::Warn, |&(_, level)| level);
 
     let type_str = match gltype {
         glow::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "Deprecated Behavior",
@@ -176,7 +182,7 @@ fn debug_message_callback(source: u32, gltype: u32, id: u32, severity: u32, mess
         glow::DEBUG_TYPE_PORTABILITY => "Portability",
         glow::DEBUG_TYPE_PUSH_GROUP => "Push Group",
         glow::DEBUG_TYPE_UNDEFINED_BEHAVIOR => "Undefined Behavior",
-        _ => unreachable!(),
+        _ => "Unknown",
     };
 
     log::log!(
@@ -189,6 +195,14 @@ fn debug_message_callback(source: u32, gltype: u32, id: u32, severity: u32, mess
     );
 }
 
+/// Severities of the debug messages, with the log level they are reported at.
+const DEBUG_SEVERITIES: [(u32, log::Level); 4] = [
+    (glow::DEBUG_SEVERITY_HIGH, log::Level::Error),
+    (glow::DEBUG_SEVERITY_MEDIUM, log::Level::Warn),
+    (glow::DEBUG_SEVERITY_LOW, log::Level::Info),
+    (glow::DEBUG_SEVERITY_NOTIFICATION, log::Level::Trace),
+];
+
 const DEVICE_LOCAL_HEAP: usize = 0;
 const CPU_VISIBLE_HEAP: usize = 1;
 
@@ -210,14 +224,26 @@ struct Share {
     private_caps: info::PrivateCaps,
     // Indicates if there is an active logical device.
     open: Cell<bool>,
+    // Errors are reported by the debug output instead of `glGetError`.
+    debug_output: Cell<bool>,
     memory_types: Vec<(adapter::MemoryType, MemoryUsage)>,
     texture_format_filter: info::TextureFormatFilter,
+    // Vertex array objects, keyed by the attributes they specify.
+    vertex_arrays: Mutex<FastHashMap<Vec<native::VertexAttributeBinding>, native::VertexArray>>,
+    // Entry points for program binaries, if supported.
+    program_binary: Option<pipeline_cache::ProgramBinaryFns>,
+    // Entry points for multi-draw indirect, if supported.
+    multi_draw_indirect: Option<indirect::MultiDrawIndirectFns>,
+    // Entry points for bindless textures, if supported.
+    bindless_texture: Option<bindless::BindlessTextureFns>,
 }
 
 impl Share {
     /// Fails during a debug build if the implementation's error flag was set.
+    ///
+    /// Errors are logged by the debug output instead, when it's enabled.
     fn check(&self) -> Result<(), Error> {
-        if cfg!(debug_assertions) {
+        if cfg!(debug_assertions) && !self.debug_output.get() {
             let gl = &self.context;
             let err = Error::from_error_code(unsafe { gl.get_error() });
             if err != Error::NoError {
@@ -354,7 +380,12 @@ unsafe impl<T: ?Sized> Sync for Wstarc<T> {}
 pub struct PhysicalDevice(Starc<Share>);
 
 impl PhysicalDevice {
-    fn new_adapter(context: GlContext) -> adapter::Adapter<Backend> {
+    fn new_adapter(
+        context: GlContext,
+        program_binary: Option<pipeline_cache::ProgramBinaryFns>,
+        multi_draw_indirect: Option<indirect::MultiDrawIndirectFns>,
+        bindless_texture: Option<bindless::BindlessTextureFns>,
+    ) -> adapter::Adapter<Backend> {
         let gl = GlContainer { context };
         // query information
         let (
@@ -439,6 +470,10 @@ impl PhysicalDevice {
 
         log::info!("Memory types: {:#?}", memory_types);
 
+        let program_binary = program_binary.filter(|_| private_caps.program_binary);
+        let multi_draw_indirect = multi_draw_indirect.filter(|_| private_caps.multi_draw_indirect);
+        let bindless_texture = bindless_texture.filter(|_| private_caps.bindless_texture);
+
         // create the shared context
         let share = Share {
             context: gl,
@@ -449,7 +484,12 @@ impl PhysicalDevice {
             texture_format_filter,
             private_caps,
             open: Cell::new(false),
+            debug_output: Cell::new(false),
             memory_types,
+            vertex_arrays: Mutex::new(FastHashMap::default()),
+            program_binary,
+            multi_draw_indirect,
+            bindless_texture,
         };
         if let Err(err) = share.check() {
             panic!("Error querying info: {:?}", err);
@@ -532,6 +572,13 @@ impl PhysicalDevice {
     pub fn legacy_features(&self) -> &info::LegacyFeatures {
         &self.0.legacy_features
     }
+
+    /// Check if textures can be referenced by bindless handles (`GL_ARB_bindless_texture`).
+    ///
+    /// See [`Device::make_texture_handle_resident`].
+    pub fn supports_bindless_textures(&self) -> bool {
+        self.0.bindless_texture.is_some()
+    }
 }
 
 impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
@@ -555,24 +602,34 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
         // initialize permanent states
         let gl = &self.0.context;
 
-        if cfg!(debug_assertions) && !cfg!(target_arch = "wasm32") && gl.supports_debug() {
+        if cfg!(debug_assertions) && self.0.private_caps.debug {
             log::info!("Debug output is enabled");
             gl.enable(glow::DEBUG_OUTPUT);
+            // Report the messages from the thread making the calls, right away.
+            gl.enable(glow::DEBUG_OUTPUT_SYNCHRONOUS);
+            // Only the messages that would be logged are generated by the driver.
+            for &(severity, level) in DEBUG_SEVERITIES.iter() {
+                gl.debug_message_control(
+                    glow::DONT_CARE,
+                    glow::DONT_CARE,
+                    severity,
+                    &[],
+                    log::log_enabled!(level),
+                );
+            }
             gl.debug_message_callback(debug_message_callback);
+            self.0.debug_output.set(true);
         }
 
-        if self
-            .0
-            .legacy_features
-            .contains(info::LegacyFeatures::SRGB_COLOR)
-            && !self.0.info.version.is_embedded
-        {
-            // `FRAMEBUFFER_SRGB` is enabled by default on embedded targets.
+        if self.0.private_caps.framebuffer_srgb {
+            // `FRAMEBUFFER_SRGB` is enabled by default on embedded targets,
+            // and can only be disabled with `GL_EXT_sRGB_write_control`.
             // TODO: Find way to emulate this on older Opengl versions.
             gl.enable(glow::FRAMEBUFFER_SRGB);
         }
 
         gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+        gl.pixel_store_i32(glow::PACK_ALIGNMENT, 1);
 
         // create main VAO and bind it
         let mut vao = None;
@@ -600,39 +657,83 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
         })
     }
 
-    fn format_properties(&self, _: Option<hal::format::Format>) -> hal::format::Properties {
+    fn format_properties(&self, format: Option<hal::format::Format>) -> hal::format::Properties {
         use hal::format::{BufferFeature as Bf, ImageFeature as If};
 
+        // Compressed formats can only be sampled, and uploaded.
+        if let Some((_, compression)) = format.and_then(conv::describe_compressed_format) {
+            let optimal_tiling = if self
+                .0
+                .private_caps
+                .texture_compression
+                .contains(compression)
+            {
+                If::TRANSFER_DST | If::SAMPLED | If::SAMPLED_LINEAR
+            } else {
+                If::empty()
+            };
+            return hal::format::Properties {
+                linear_tiling: If::empty(),
+                optimal_tiling,
+                buffer_features: Bf::empty(),
+            };
+        }
+
+        let is_embedded = self.0.info.version.is_embedded;
+        let texture_buffer_format = format
+            .and_then(conv::describe_format)
+            .map_or(false, |desc| {
+                conv::is_texture_buffer_format(desc.tex_internal, is_embedded)
+            });
+        let mut buffer_features = Bf::VERTEX;
+        if self.0.private_caps.texture_buffer && texture_buffer_format {
+            buffer_features |= Bf::UNIFORM_TEXEL;
+        }
+
         // TODO: These are for show
         hal::format::Properties {
             linear_tiling: If::TRANSFER_SRC | If::TRANSFER_DST | If::empty(),
             optimal_tiling: If::TRANSFER_SRC | If::TRANSFER_DST | If::SAMPLED,
-            buffer_features: Bf::VERTEX,
+            buffer_features,
         }
     }
 
     fn image_format_properties(
         &self,
         format: hal::format::Format,
-        _dimensions: u8,
-        _tiling: image::Tiling,
-        _usage: image::Usage,
+        dimensions: u8,
+        tiling: image::Tiling,
+        usage: image::Usage,
         _view_caps: image::ViewCapabilities,
     ) -> Option<image::FormatProperties> {
-        let conv::FormatDescription {
-            tex_external,
-            tex_internal,
-            data_type,
-            ..
-        } = conv::describe_format(format)?;
-
-        if !self
-            .0
-            .texture_format_filter
-            .check(tex_internal, tex_external, data_type)
-        {
-            /* This format is not supported. */
-            return None;
+        if let Some((_, compression)) = conv::describe_compressed_format(format) {
+            let supported = self
+                .0
+                .private_caps
+                .texture_compression
+                .contains(compression)
+                && dimensions == 2
+                && tiling == image::Tiling::Optimal
+                && (usage - image::Usage::SAMPLED - image::Usage::TRANSFER_DST).is_empty();
+            if !supported {
+                return None;
+            }
+        } else {
+            let conv::FormatDescription {
+                tex_external,
+                tex_internal,
+                data_type,
+                ..
+            } = conv::describe_format(format)?;
+
+            if !self
+                .0
+                .texture_format_filter
+                .check(tex_internal, tex_external, data_type)
+            {
+                /* This format is not supported. */
+                return None;
+            }
         }
 
         Some(image::FormatProperties {
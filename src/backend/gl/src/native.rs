@@ -1,4 +1,4 @@
-use crate::{Backend, GlContext, MAX_TEXTURE_SLOTS};
+use crate::{conv, Backend, GlContext, MAX_TEXTURE_SLOTS};
 
 use hal::{
     buffer, format, image as i,
@@ -6,6 +6,7 @@ use hal::{
     pass, pso, window as w,
 };
 
+use parking_lot::{Condvar, Mutex};
 use std::{borrow::Borrow, fmt, ops::Range, sync::Arc};
 
 pub type TextureTarget = u32;
@@ -69,7 +70,10 @@ impl Buffer {
 }
 
 #[derive(Debug)]
-pub struct BufferView;
+pub struct BufferView {
+    /// Texture object backed by the buffer range (TBO).
+    pub(crate) raw: Texture,
+}
 
 #[derive(Debug)]
 pub enum Fence {
@@ -80,6 +84,25 @@ pub enum Fence {
 unsafe impl Send for Fence {}
 unsafe impl Sync for Fence {}
 
+#[derive(Debug, Default)]
+pub(crate) struct TimelineState {
+    /// Value reached by the completed work.
+    pub(crate) value: u64,
+    /// Values signaled by the in-flight submissions, in submission order.
+    pub(crate) pending: Vec<(u64, <GlContext as glow::HasContext>::Fence)>,
+}
+
+#[derive(Debug)]
+// GL has a single queue executing in order, so only the host observes the value.
+pub struct TimelineSemaphore {
+    pub(crate) state: Mutex<TimelineState>,
+    /// Notified when the value is signaled on the host, or by a new submission.
+    pub(crate) condvar: Condvar,
+}
+
+unsafe impl Send for TimelineSemaphore {}
+unsafe impl Sync for TimelineSemaphore {}
+
 #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
 pub enum BindingRegister {
     Textures,
@@ -96,12 +119,17 @@ pub struct GraphicsPipeline {
     pub(crate) program: Program,
     pub(crate) primitive: u32,
     pub(crate) patch_size: Option<i32>,
+    /// Number of `GL_CLIP_DISTANCEi` planes to enable.
+    pub(crate) clip_distance_count: u32,
+    /// Whether primitive restart is enabled, `None` if it's set with commands.
+    pub(crate) primitive_restart: Option<bool>,
     pub(crate) blend_targets: Vec<pso::ColorBlendDesc>,
     pub(crate) attributes: Vec<AttributeDesc>,
     pub(crate) vertex_buffers: Vec<Option<pso::VertexBufferDesc>>,
     pub(crate) uniforms: Vec<UniformDesc>,
     pub(crate) rasterizer: pso::Rasterizer,
     pub(crate) depth: Option<pso::DepthTest>,
+    pub(crate) stencil: Option<pso::StencilTest>,
     pub(crate) baked_states: pso::BakedStates,
     pub(crate) sampler_map: SamplerBindMap,
 }
@@ -145,6 +173,7 @@ pub enum ImageType {
     Renderbuffer {
         raw: Renderbuffer,
         format: TextureFormat,
+        pixel_type: DataType,
     },
     Texture {
         target: TextureTarget,
@@ -175,6 +204,9 @@ pub enum ImageView {
         raw: Texture,
         is_3d: bool,
         sub: i::SubresourceRange,
+        /// The view has a linear format, on a texture with an sRGB format,
+        /// so rendering to it must not encode the colors.
+        skip_srgb: bool,
     },
 }
 
@@ -209,24 +241,20 @@ impl SwapchainImage {
     #[cfg(not(dummy))]
     pub(crate) fn new(
         renderbuffer: Renderbuffer,
-        format: TextureFormat,
+        format: format::Format,
         extent: w::Extent2D,
-        channel: format::ChannelType,
     ) -> Self {
+        let desc = conv::describe_format(format).unwrap();
         SwapchainImage {
             image: Image {
                 object_type: ImageType::Renderbuffer {
                     raw: renderbuffer,
-                    format,
+                    format: desc.tex_external,
+                    pixel_type: desc.data_type,
                 },
-                channel,
+                channel: format.base_format().1,
                 kind: i::Kind::D2(extent.width as u32, extent.height as u32, 1, 1),
-                format_desc: format::FormatDesc {
-                    bits: 0,
-                    dim: (0, 0),
-                    packed: false,
-                    aspects: format::Aspects::empty(),
-                },
+                format_desc: format.surface_desc(),
                 requirements: Requirements {
                     size: 0,
                     alignment: 1,
@@ -291,6 +319,51 @@ impl pso::DescriptorPool<Backend> for DescriptorPool {
     }
 }
 
+/// Stage of a program. Unlike `naga::ShaderStage`, it includes the tessellation
+/// and geometry stages, which are only translated by SPIRV-Cross.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShaderStage {
+    Vertex,
+    TessControl,
+    TessEvaluation,
+    Geometry,
+    Fragment,
+    Compute,
+}
+
+impl ShaderStage {
+    /// Get the corresponding `naga` stage, if `naga` can translate it.
+    pub(crate) fn to_naga(self) -> Option<naga::ShaderStage> {
+        match self {
+            ShaderStage::Vertex => Some(naga::ShaderStage::Vertex),
+            ShaderStage::Fragment => Some(naga::ShaderStage::Fragment),
+            ShaderStage::Compute => Some(naga::ShaderStage::Compute),
+            ShaderStage::TessControl | ShaderStage::TessEvaluation | ShaderStage::Geometry => None,
+        }
+    }
+
+    /// Check if the stage processes vertices before the rasterization.
+    pub(crate) fn is_vertex_processing(self) -> bool {
+        match self {
+            ShaderStage::Fragment | ShaderStage::Compute => false,
+            _ => true,
+        }
+    }
+}
+
+impl From<ShaderStage> for pso::ShaderStageFlags {
+    fn from(stage: ShaderStage) -> Self {
+        match stage {
+            ShaderStage::Vertex => Self::VERTEX,
+            ShaderStage::TessControl => Self::HULL,
+            ShaderStage::TessEvaluation => Self::DOMAIN,
+            ShaderStage::Geometry => Self::GEOMETRY,
+            ShaderStage::Fragment => Self::FRAGMENT,
+            ShaderStage::Compute => Self::COMPUTE,
+        }
+    }
+}
+
 pub struct ShaderModule {
     #[cfg(feature = "cross")]
     pub(crate) spv: Vec<u32>,
@@ -313,6 +386,8 @@ pub struct Memory {
     pub(crate) size: u64,
     pub(crate) map_flags: u32,
     pub(crate) emulate_map_allocation: Option<*mut u8>,
+    /// Mapping of the whole buffer of persistent memory, kept until the memory is freed.
+    pub(crate) persistent_mapping: Option<*mut u8>,
 }
 
 unsafe impl Send for Memory {}
@@ -322,6 +397,8 @@ unsafe impl Sync for Memory {}
 pub struct RenderPass {
     pub(crate) attachments: Vec<pass::Attachment>,
     pub(crate) subpasses: Vec<SubpassDesc>,
+    /// Name of the debug group around the render pass.
+    pub(crate) label: Option<String>,
 }
 
 #[derive(Clone, Debug)]
@@ -365,7 +442,7 @@ pub struct PipelineLayout {
 // No inter-queue synchronization required for GL.
 pub struct Semaphore;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct AttributeDesc {
     pub(crate) location: u32,
     pub(crate) offset: u32,
@@ -375,6 +452,15 @@ pub struct AttributeDesc {
     pub(crate) vertex_attrib_fn: VertexAttribFunction,
 }
 
+/// Vertex attribute sourced from a buffer, as specified in a vertex array object.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct VertexAttributeBinding {
+    pub(crate) attribute: AttributeDesc,
+    pub(crate) buffer: RawBuffer,
+    pub(crate) stride: i32,
+    pub(crate) rate: u32,
+}
+
 #[derive(Clone, Debug)]
 pub struct UniformDesc {
     pub(crate) location: UniformLocation,
@@ -382,9 +468,10 @@ pub struct UniformDesc {
     pub(crate) utype: u32,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum VertexAttribFunction {
-    Float,   // glVertexAttribPointer
-    Integer, // glVertexAttribIPointer
-    Double,  // glVertexAttribLPointer
+    Float,      // glVertexAttribPointer
+    Normalized, // glVertexAttribPointer with normalization
+    Integer,    // glVertexAttribIPointer
+    Double,     // glVertexAttribLPointer
 }
@@ -0,0 +1,303 @@
+//! Pipeline caches, made of the binaries of linked programs (`GL_ARB_get_program_binary`).
+//!
+//! Programs are keyed by a hash of the driver identity and of the GLSL sources
+//! of their stages, so that a binary is only reused for the exact same shaders,
+//! on the driver that produced it. Binaries rejected by the driver are compiled again.
+
+use crate::{native as n, FastHashMap};
+
+use parking_lot::Mutex;
+
+use std::{convert::TryInto, fmt};
+
+const MAGIC: &[u8; 4] = b"GLPC";
+const VERSION: u32 = 2;
+
+/// Binary of a linked program, in a driver-specific format.
+#[derive(Clone, Debug)]
+pub(crate) struct ProgramBinary {
+    pub(crate) format: u32,
+    pub(crate) data: Vec<u8>,
+}
+
+#[derive(Debug, Default)]
+pub struct PipelineCache {
+    programs: Mutex<FastHashMap<u64, ProgramBinary>>,
+}
+
+fn read_u32(data: &mut &[u8]) -> Option<u32> {
+    if data.len() < 4 {
+        return None;
+    }
+    let (value, rest) = data.split_at(4);
+    *data = rest;
+    Some(u32::from_le_bytes(value.try_into().unwrap()))
+}
+
+fn read_u64(data: &mut &[u8]) -> Option<u64> {
+    if data.len() < 8 {
+        return None;
+    }
+    let (value, rest) = data.split_at(8);
+    *data = rest;
+    Some(u64::from_le_bytes(value.try_into().unwrap()))
+}
+
+impl PipelineCache {
+    /// Load a cache from data returned by `to_data`.
+    ///
+    /// Data that isn't recognized is ignored, and gives an empty cache.
+    pub(crate) fn from_data(mut data: &[u8]) -> Self {
+        let cache = PipelineCache::default();
+        if !data.starts_with(MAGIC) {
+            log::warn!("Ignoring pipeline cache data of unknown format");
+            return cache;
+        }
+        data = &data[MAGIC.len()..];
+        if read_u32(&mut data) != Some(VERSION) {
+            log::warn!("Ignoring pipeline cache data of unknown version");
+            return cache;
+        }
+
+        let mut programs = cache.programs.lock();
+        while !data.is_empty() {
+            let header = (
+                read_u64(&mut data),
+                read_u32(&mut data),
+                read_u32(&mut data),
+            );
+            match header {
+                (Some(key), Some(format), Some(length)) if data.len() >= length as usize => {
+                    let (binary, rest) = data.split_at(length as usize);
+                    data = rest;
+                    programs.insert(
+                        key,
+                        ProgramBinary {
+                            format,
+                            data: binary.to_vec(),
+                        },
+                    );
+                }
+                _ => {
+                    log::warn!("Ignoring truncated pipeline cache data");
+                    break;
+                }
+            }
+        }
+        drop(programs);
+        cache
+    }
+
+    /// Serialize the cache, to be loaded again with `from_data`.
+    pub(crate) fn to_data(&self) -> Vec<u8> {
+        let programs = self.programs.lock();
+        let mut data = Vec::new();
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&VERSION.to_le_bytes());
+        for (key, binary) in programs.iter() {
+            data.extend_from_slice(&key.to_le_bytes());
+            data.extend_from_slice(&binary.format.to_le_bytes());
+            data.extend_from_slice(&(binary.data.len() as u32).to_le_bytes());
+            data.extend_from_slice(&binary.data);
+        }
+        data
+    }
+
+    /// Add the programs of `other` to this cache.
+    pub(crate) fn merge(&self, other: &Self) {
+        let other = other.programs.lock();
+        let mut programs = self.programs.lock();
+        for (&key, binary) in other.iter() {
+            programs.entry(key).or_insert_with(|| binary.clone());
+        }
+    }
+
+    pub(crate) fn get(&self, key: u64) -> Option<ProgramBinary> {
+        self.programs.lock().get(&key).cloned()
+    }
+
+    pub(crate) fn insert(&self, key: u64, binary: ProgramBinary) {
+        self.programs.lock().insert(key, binary);
+    }
+
+    pub(crate) fn remove(&self, key: u64) {
+        self.programs.lock().remove(&key);
+    }
+}
+
+/// Compute the cache key of a program linked by `driver` from the GLSL `sources` of its stages.
+///
+/// The key is stored in the cache data, so it's a 64-bit FNV-1a hash, which doesn't
+/// depend on the Rust version or on the process, unlike `DefaultHasher`.
+pub(crate) fn program_key<'a>(driver: &str, sources: impl Iterator<Item = &'a str>) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    let mut hash = OFFSET_BASIS;
+    let mut write = |bytes: &[u8]| {
+        for &byte in bytes {
+            hash = (hash ^ byte as u64).wrapping_mul(PRIME);
+        }
+    };
+    // Strings are prefixed with their length, so that they can't run into each other.
+    for string in Some(driver).into_iter().chain(sources) {
+        write(&(string.len() as u64).to_le_bytes());
+        write(string.as_bytes());
+    }
+    hash
+}
+
+/// Entry points of `GL_ARB_get_program_binary`, which aren't exposed by `glow`.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct ProgramBinaryFns {
+    program_parameteri: unsafe extern "system" fn(u32, u32, i32),
+    get_programiv: unsafe extern "system" fn(u32, u32, *mut i32),
+    get_program_binary:
+        unsafe extern "system" fn(u32, i32, *mut i32, *mut u32, *mut std::ffi::c_void),
+    program_binary: unsafe extern "system" fn(u32, u32, *const std::ffi::c_void, i32),
+}
+
+/// Program binaries are not available on WebGL.
+#[cfg(target_arch = "wasm32")]
+pub(crate) enum ProgramBinaryFns {}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ProgramBinaryFns {
+    /// Load the entry points with `get_proc_address`, if all of them are available.
+    pub(crate) unsafe fn load(
+        mut get_proc_address: impl FnMut(&str) -> *const std::ffi::c_void,
+    ) -> Option<Self> {
+        let program_parameteri = get_proc_address("glProgramParameteri");
+        let get_programiv = get_proc_address("glGetProgramiv");
+        let get_program_binary = get_proc_address("glGetProgramBinary");
+        let program_binary = get_proc_address("glProgramBinary");
+        if program_parameteri.is_null()
+            || get_programiv.is_null()
+            || get_program_binary.is_null()
+            || program_binary.is_null()
+        {
+            return None;
+        }
+        Some(ProgramBinaryFns {
+            program_parameteri: std::mem::transmute(program_parameteri),
+            get_programiv: std::mem::transmute(get_programiv),
+            get_program_binary: std::mem::transmute(get_program_binary),
+            program_binary: std::mem::transmute(program_binary),
+        })
+    }
+
+    /// Hint that the binary of `program` will be retrieved, before linking it.
+    ///
+    /// Some drivers don't keep the binaries of the programs linked without it.
+    pub(crate) unsafe fn set_retrievable(&self, program: n::Program) {
+        (self.program_parameteri)(
+            program,
+            glow::PROGRAM_BINARY_RETRIEVABLE_HINT,
+            glow::TRUE as i32,
+        );
+    }
+
+    /// Retrieve the binary of a linked `program`.
+    pub(crate) unsafe fn get(&self, program: n::Program) -> Option<ProgramBinary> {
+        let mut length = 0;
+        (self.get_programiv)(program, glow::PROGRAM_BINARY_LENGTH, &mut length);
+        if length <= 0 {
+            return None;
+        }
+        let mut data = vec![0u8; length as usize];
+        let mut format = 0;
+        let mut written = 0;
+        (self.get_program_binary)(
+            program,
+            length,
+            &mut written,
+            &mut format,
+            data.as_mut_ptr() as *mut _,
+        );
+        data.truncate(written.max(0) as usize);
+        if data.is_empty() {
+            None
+        } else {
+            Some(ProgramBinary { format, data })
+        }
+    }
+
+    /// Load `binary` into `program`, whose link status tells if it was accepted.
+    pub(crate) unsafe fn load_into(&self, program: n::Program, binary: &ProgramBinary) {
+        (self.program_binary)(
+            program,
+            binary.format,
+            binary.data.as_ptr() as *const _,
+            binary.data.len() as i32,
+        );
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl ProgramBinaryFns {
+    pub(crate) unsafe fn set_retrievable(&self, _program: n::Program) {
+        match *self {}
+    }
+
+    pub(crate) unsafe fn get(&self, _program: n::Program) -> Option<ProgramBinary> {
+        match *self {}
+    }
+
+    pub(crate) unsafe fn load_into(&self, _program: n::Program, _binary: &ProgramBinary) {
+        match *self {}
+    }
+}
+
+impl fmt::Debug for ProgramBinaryFns {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("ProgramBinaryFns")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn data_round_trip() {
+        let cache = PipelineCache::default();
+        cache.insert(
+            7,
+            ProgramBinary {
+                format: 3,
+                data: vec![1, 2, 3],
+            },
+        );
+        let loaded = PipelineCache::from_data(&cache.to_data());
+        let binary = loaded.get(7).unwrap();
+        assert_eq!(binary.format, 3);
+        assert_eq!(binary.data, [1, 2, 3]);
+    }
+
+    #[test]
+    fn truncated_data() {
+        let cache = PipelineCache::default();
+        cache.insert(
+            7,
+            ProgramBinary {
+                format: 3,
+                data: vec![1, 2, 3],
+            },
+        );
+        let mut data = cache.to_data();
+        data.pop();
+        assert!(PipelineCache::from_data(&data).get(7).is_none());
+        assert!(PipelineCache::from_data(b"junk").get(7).is_none());
+    }
+
+    #[test]
+    fn stable_program_key() {
+        // The keys are persisted, so they must not change between runs or builds.
+        let key = program_key("driver", ["vertex", "fragment"].iter().cloned());
+        assert_eq!(key, 0x6a9c_52e0_c8a0_3ae9);
+        assert_ne!(
+            key,
+            program_key("driver", ["vertexfragment"].iter().cloned())
+        );
+    }
+}
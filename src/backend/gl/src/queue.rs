@@ -1,12 +1,12 @@
 use crate::{
-    command as com, device, info::LegacyFeatures, native, state, Backend, Device, GlContext, Share,
-    Starc, Surface, MAX_COLOR_ATTACHMENTS,
+    command as com, device, info::LegacyFeatures, native, state, Backend, Device, GlContainer,
+    GlContext, Share, Starc, Surface, MAX_COLOR_ATTACHMENTS, MAX_TEXTURE_SLOTS,
 };
 
 use arrayvec::ArrayVec;
 use glow::HasContext;
 
-use std::{mem, slice};
+use std::{iter, mem, slice};
 
 // State caching system for command queue.
 //
@@ -20,6 +20,10 @@ struct State {
     // Indicate if the vertex array object is bound.
     // If VAOs are not supported, this will be also set to true.
     vao: bool,
+    // Cached vertex array object currently bound instead of the default one.
+    vertex_array: Option<native::VertexArray>,
+    // Attributes of the next vertex array object to bind.
+    pending_attributes: Vec<native::VertexAttributeBinding>,
     // Currently bound index/element buffer.
     // None denotes that we don't know what is currently bound.
     index_buffer: Option<native::RawBuffer>,
@@ -27,6 +31,22 @@ struct State {
     num_viewports: usize,
     // Currently set scissor rects.
     num_scissors: usize,
+    // Currently enabled clip distances.
+    num_clip_distances: u32,
+    // Mask of the currently enabled vertex attribute arrays.
+    active_attributes: u32,
+    // Currently enabled capabilities.
+    capabilities: state::CapabilityCache,
+    // Currently used program.
+    program: Option<native::Program>,
+    // Currently bound vertex buffer.
+    array_buffer: Option<native::RawBuffer>,
+    // Currently active texture unit.
+    active_texture: Option<u32>,
+    // Texture and its target, bound to each texture unit.
+    textures: [Option<(native::TextureTarget, native::Texture)>; MAX_TEXTURE_SLOTS],
+    // Sampler bound to each texture unit.
+    samplers: [Option<native::Sampler>; MAX_TEXTURE_SLOTS],
 }
 
 impl State {
@@ -35,9 +55,19 @@ impl State {
     fn new() -> Self {
         State {
             vao: false,
+            vertex_array: None,
+            pending_attributes: Vec::new(),
             index_buffer: None,
             num_viewports: 0,
             num_scissors: 0,
+            num_clip_distances: 0,
+            active_attributes: 0,
+            capabilities: state::CapabilityCache::default(),
+            program: None,
+            array_buffer: None,
+            active_texture: None,
+            textures: [None; MAX_TEXTURE_SLOTS],
+            samplers: [None; MAX_TEXTURE_SLOTS],
         }
     }
 
@@ -46,10 +76,124 @@ impl State {
     fn flush(&mut self) {
         self.vao = false;
         self.index_buffer = None;
+        self.capabilities.clear();
+        self.active_texture = None;
+        self.invalidate_bindings();
 
         // TOOD: reset viewports and scissors
         //       do we need to clear everything from 0..MAX_VIEWPORTS?
     }
+
+    // Invalidate the object bindings, which the device changes when creating
+    // objects, and which are reset by GL when the bound objects are deleted.
+    fn invalidate_bindings(&mut self) {
+        self.vao = false;
+        self.vertex_array = None;
+        self.program = None;
+        self.array_buffer = None;
+        self.textures = [None; MAX_TEXTURE_SLOTS];
+        self.samplers = [None; MAX_TEXTURE_SLOTS];
+    }
+
+    fn use_program(&mut self, gl: &GlContainer, program: native::Program) {
+        if self.program != Some(program) {
+            self.program = Some(program);
+            unsafe { gl.use_program(Some(program)) };
+        }
+    }
+
+    fn bind_array_buffer(&mut self, gl: &GlContainer, buffer: native::RawBuffer) {
+        if self.array_buffer != Some(buffer) {
+            self.array_buffer = Some(buffer);
+            unsafe { gl.bind_buffer(glow::ARRAY_BUFFER, Some(buffer)) };
+        }
+    }
+
+    fn specify_attribute(
+        &mut self,
+        gl: &GlContainer,
+        legacy_features: &LegacyFeatures,
+        binding: &native::VertexAttributeBinding,
+    ) {
+        use crate::native::VertexAttribFunction::*;
+
+        let &native::AttributeDesc {
+            location,
+            size,
+            format,
+            offset,
+            vertex_attrib_fn,
+            ..
+        } = &binding.attribute;
+        let stride = binding.stride;
+
+        self.bind_array_buffer(gl, binding.buffer);
+
+        unsafe {
+            match vertex_attrib_fn {
+                Float => gl.vertex_attrib_pointer_f32(
+                    location,
+                    size,
+                    format,
+                    false,
+                    stride,
+                    offset as i32,
+                ),
+                Normalized => gl.vertex_attrib_pointer_f32(
+                    location,
+                    size,
+                    format,
+                    true,
+                    stride,
+                    offset as i32,
+                ),
+                Integer => {
+                    gl.vertex_attrib_pointer_i32(location, size, format, stride, offset as i32)
+                }
+                Double => {
+                    gl.vertex_attrib_pointer_f64(location, size, format, stride, offset as i32)
+                }
+            }
+
+            if legacy_features.contains(LegacyFeatures::INSTANCED_ATTRIBUTE_BINDING) {
+                gl.vertex_attrib_divisor(location, binding.rate);
+            } else if binding.rate > 0 {
+                log::error!("Binding attribute with instanced input rate is not supported");
+            }
+
+            gl.enable_vertex_attrib_array(location);
+        }
+    }
+
+    fn set_active_texture(&mut self, gl: &GlContainer, unit: u32) {
+        if self.active_texture != Some(unit) {
+            self.active_texture = Some(unit);
+            unsafe { gl.active_texture(glow::TEXTURE0 + unit) };
+        }
+    }
+
+    fn bind_texture(
+        &mut self,
+        gl: &GlContainer,
+        unit: u32,
+        target: native::TextureTarget,
+        texture: native::Texture,
+    ) {
+        self.set_active_texture(gl, unit);
+        let slot = &mut self.textures[unit as usize];
+        if *slot != Some((target, texture)) {
+            *slot = Some((target, texture));
+            unsafe { gl.bind_texture(target, Some(texture)) };
+        }
+    }
+
+    fn bind_sampler(&mut self, gl: &GlContainer, unit: u32, sampler: native::Sampler) {
+        let slot = &mut self.samplers[unit as usize];
+        if *slot != Some(sampler) {
+            *slot = Some(sampler);
+            unsafe { gl.bind_sampler(unit, Some(sampler)) };
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -60,6 +204,8 @@ pub struct Queue {
     state: State,
     fill_buffer: native::RawBuffer,
     fill_data: Box<[u32]>,
+    /// Framebuffer the images are attached to, to be read into buffers.
+    read_framebuffer: Option<native::RawFramebuffer>,
 }
 
 const FILL_DATA_WORDS: usize = 16 << 10;
@@ -83,6 +229,11 @@ impl Queue {
             gl.bind_buffer(glow::COPY_READ_BUFFER, None);
             buffer
         };
+        let read_framebuffer = if share.private_caps.framebuffer {
+            Some(unsafe { gl.create_framebuffer() }.unwrap())
+        } else {
+            None
+        };
         Queue {
             share: share.clone(),
             features,
@@ -90,6 +241,7 @@ impl Queue {
             state: State::new(),
             fill_buffer,
             fill_data: vec![0; FILL_DATA_WORDS].into_boxed_slice(),
+            read_framebuffer,
         }
     }
 
@@ -175,6 +327,60 @@ impl Queue {
         Device::bind_target(&self.share.context, point, attachment, view)
     }
 
+    /// Attachment of the read framebuffer to read the `aspects` of an image from.
+    fn read_attachment(aspects: hal::format::Aspects) -> u32 {
+        if aspects == hal::format::Aspects::DEPTH {
+            glow::DEPTH_ATTACHMENT
+        } else if aspects == hal::format::Aspects::STENCIL {
+            glow::STENCIL_ATTACHMENT
+        } else if aspects.contains(hal::format::Aspects::COLOR) {
+            glow::COLOR_ATTACHMENT0
+        } else {
+            glow::DEPTH_STENCIL_ATTACHMENT
+        }
+    }
+
+    /// Read the pixels of a region from the image attached to the read framebuffer,
+    /// into the pixel pack buffer `dst_buffer`.
+    ///
+    /// The pixels are written by the device, so that the copy doesn't wait for rendering
+    /// to complete. They can be mapped once the fence of the submission is signaled.
+    unsafe fn read_pixels(
+        &self,
+        attachment: u32,
+        format: native::TextureFormat,
+        pixel_type: native::DataType,
+        dst_buffer: native::RawBuffer,
+        data: &hal::command::BufferImageCopy,
+    ) {
+        let gl = &self.share.context;
+        if attachment == glow::COLOR_ATTACHMENT0 {
+            gl.read_buffer(glow::COLOR_ATTACHMENT0);
+        }
+        let row_length = if data.buffer_width > data.image_extent.width {
+            data.buffer_width as i32
+        } else {
+            0
+        };
+        if row_length != 0 {
+            gl.pixel_store_i32(glow::PACK_ROW_LENGTH, row_length);
+        }
+        gl.bind_buffer(glow::PIXEL_PACK_BUFFER, Some(dst_buffer));
+        gl.read_pixels(
+            data.image_offset.x,
+            data.image_offset.y,
+            data.image_extent.width as _,
+            data.image_extent.height as _,
+            format,
+            pixel_type,
+            glow::PixelPackData::BufferOffset(data.buffer_offset as u32),
+        );
+        gl.bind_buffer(glow::PIXEL_PACK_BUFFER, None);
+        if row_length != 0 {
+            gl.pixel_store_i32(glow::PACK_ROW_LENGTH, 0);
+        }
+    }
+
     fn _unbind_target(&mut self, point: u32, attachment: u32) {
         let gl = &self.share.context;
         // TODO: Find workaround or use explicit `textarget` with the other `framebuffer_texture`
@@ -195,6 +401,36 @@ impl Queue {
         &data[ptr.offset as usize..(ptr.offset + ptr.size) as usize]
     }
 
+    // Bind the vertex array object specifying the pending attributes,
+    // creating it on first use.
+    fn bind_vertex_array(&mut self) {
+        let gl = &self.share.context;
+        let bindings = mem::replace(&mut self.state.pending_attributes, Vec::new());
+        let mut vertex_arrays = self.share.vertex_arrays.lock();
+        let (vao, is_new) = match vertex_arrays.get(&bindings) {
+            Some(&vao) => (vao, false),
+            None => (unsafe { gl.create_vertex_array() }.unwrap(), true),
+        };
+
+        if self.state.vertex_array != Some(vao) {
+            unsafe {
+                gl.bind_vertex_array(Some(vao));
+                // The index buffer binding is part of the vertex array state.
+                gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, self.state.index_buffer);
+            }
+            self.state.vertex_array = Some(vao);
+            self.state.vao = false;
+        }
+
+        if is_new {
+            for binding in &bindings {
+                self.state
+                    .specify_attribute(gl, &self.share.legacy_features, binding);
+            }
+            vertex_arrays.insert(bindings, vao);
+        }
+    }
+
     // Reset the state to match our _expected_ state before executing
     // a command buffer.
     fn reset_state(&mut self) {
@@ -205,7 +441,8 @@ impl Queue {
             if self.share.private_caps.vertex_array {
                 unsafe { gl.bind_vertex_array(self.vao) };
             }
-            self.state.vao = true
+            self.state.vao = true;
+            self.state.vertex_array = None;
         }
 
         // Reset indirect draw buffer
@@ -367,6 +604,57 @@ impl Queue {
                     log::error!("Instanced indexed drawing is not supported");
                 }
             }
+            com::Command::DrawIndirect {
+                primitive,
+                buffer,
+                offset,
+                draw_count,
+                stride,
+            } => {
+                let gl = &self.share.context;
+                unsafe {
+                    gl.bind_buffer(glow::DRAW_INDIRECT_BUFFER, Some(buffer));
+                    match self.share.multi_draw_indirect {
+                        Some(ref fns) if draw_count > 1 => {
+                            fns.draw_arrays(primitive, offset, draw_count, stride)
+                        }
+                        _ => {
+                            for i in 0..draw_count {
+                                let offset = offset + i as u64 * stride as u64;
+                                gl.draw_arrays_indirect_offset(primitive, offset as i32);
+                            }
+                        }
+                    }
+                }
+            }
+            com::Command::DrawIndexedIndirect {
+                primitive,
+                index_type,
+                buffer,
+                offset,
+                draw_count,
+                stride,
+            } => {
+                let gl = &self.share.context;
+                unsafe {
+                    gl.bind_buffer(glow::DRAW_INDIRECT_BUFFER, Some(buffer));
+                    match self.share.multi_draw_indirect {
+                        Some(ref fns) if draw_count > 1 => {
+                            fns.draw_elements(primitive, index_type, offset, draw_count, stride)
+                        }
+                        _ => {
+                            for i in 0..draw_count {
+                                let offset = offset + i as u64 * stride as u64;
+                                gl.draw_elements_indirect_offset(
+                                    primitive,
+                                    index_type,
+                                    offset as i32,
+                                );
+                            }
+                        }
+                    }
+                }
+            }
             com::Command::Dispatch(count) => {
                 // Capability support is given by which queue types will be exposed.
                 // If there is no compute support, this pattern should never be reached
@@ -380,7 +668,7 @@ impl Queue {
                 // because no queue with compute capability can be created.
                 let gl = &self.share.context;
                 unsafe {
-                    gl.bind_buffer(glow::DRAW_INDIRECT_BUFFER, Some(buffer));
+                    gl.bind_buffer(glow::DISPATCH_INDIRECT_BUFFER, Some(buffer));
                     // TODO: possible integer conversion issue
                     gl.dispatch_compute_indirect(offset as _);
                 }
@@ -495,6 +783,22 @@ impl Queue {
                 for (i, view) in colors.iter().enumerate() {
                     self.bind_target(target, glow::COLOR_ATTACHMENT0 + i as u32, view);
                 }
+                if target != glow::READ_FRAMEBUFFER && self.share.private_caps.framebuffer_srgb {
+                    // Linear views of sRGB textures are rendered without encoding the colors.
+                    let skip_srgb = |view: &n::ImageView| match *view {
+                        n::ImageView::Texture { skip_srgb, .. } => skip_srgb,
+                        n::ImageView::Renderbuffer { .. } => false,
+                    };
+                    let linear = colors.iter().any(skip_srgb);
+                    if linear && !colors.iter().all(skip_srgb) {
+                        log::warn!(
+                            "Framebuffer mixes sRGB views and linear views of sRGB textures"
+                        );
+                    }
+                    self.state
+                        .capabilities
+                        .set(gl, glow::FRAMEBUFFER_SRGB, !linear);
+                }
                 if let Some(ref view) = *depth_stencil {
                     let aspects = view.aspects();
                     let attachment = if aspects == hal::format::Aspects::DEPTH {
@@ -556,68 +860,74 @@ impl Queue {
                     .context
                     .patch_parameter_i32(glow::PATCH_VERTICES, num);
             },
-            com::Command::BindProgram(program) => unsafe {
-                self.share.context.use_program(Some(program));
+            com::Command::SetClipDistances(count) => unsafe {
+                let gl = &self.share.context;
+                for i in count..self.state.num_clip_distances {
+                    gl.disable(glow::CLIP_DISTANCE0 + i);
+                }
+                for i in self.state.num_clip_distances..count {
+                    gl.enable(glow::CLIP_DISTANCE0 + i);
+                }
+                self.state.num_clip_distances = count;
+            },
+            com::Command::SetPrimitiveRestart(enable) => unsafe {
+                if self.share.private_caps.primitive_restart_fixed_index {
+                    let gl = &self.share.context;
+                    self.state
+                        .capabilities
+                        .set(gl, glow::PRIMITIVE_RESTART_FIXED_INDEX, enable);
+                }
             },
+            com::Command::BindProgram(program) => {
+                self.state.use_program(&self.share.context, program);
+            }
             com::Command::SetBlend(ref blend) => {
-                state::set_blend(&self.share.context, blend);
+                state::set_blend(&self.share.context, &mut self.state.capabilities, blend);
             }
             com::Command::SetBlendSlot(slot, ref blend) => {
                 if self.share.private_caps.draw_buffers {
-                    state::set_blend_slot(&self.share.context, slot, blend, &self.features);
+                    state::set_blend_slot(
+                        &self.share.context,
+                        &mut self.state.capabilities,
+                        slot,
+                        blend,
+                        &self.features,
+                    );
                 } else {
                     log::warn!("Draw buffers are not supported");
                 }
             }
-            com::Command::BindAttribute(ref attribute, handle, stride, rate) => unsafe {
-                use crate::native::VertexAttribFunction::*;
-
-                let &native::AttributeDesc {
-                    location,
-                    size,
-                    format,
-                    offset,
-                    vertex_attrib_fn,
-                    ..
-                } = attribute;
-                let gl = &self.share.context;
-
-                gl.bind_buffer(glow::ARRAY_BUFFER, Some(handle));
-
-                match vertex_attrib_fn {
-                    Float => gl.vertex_attrib_pointer_f32(
-                        location,
-                        size,
-                        format,
-                        false,
-                        stride,
-                        offset as i32,
-                    ),
-                    Integer => {
-                        gl.vertex_attrib_pointer_i32(location, size, format, stride, offset as i32)
-                    }
-                    Double => {
-                        gl.vertex_attrib_pointer_f64(location, size, format, stride, offset as i32)
-                    }
+            com::Command::BindAttribute(ref attribute, handle, stride, rate) => {
+                let binding = native::VertexAttributeBinding {
+                    attribute: attribute.clone(),
+                    buffer: handle,
+                    stride,
+                    rate,
+                };
+                if self.share.private_caps.vertex_array {
+                    // Specified once the whole vertex array is known.
+                    self.state.pending_attributes.push(binding);
+                } else {
+                    let gl = &self.share.context;
+                    self.state
+                        .specify_attribute(gl, &self.share.legacy_features, &binding);
+                    self.state.active_attributes |= 1 << binding.attribute.location;
                 }
-
-                if self
-                    .share
-                    .legacy_features
-                    .contains(LegacyFeatures::INSTANCED_ATTRIBUTE_BINDING)
-                {
-                    gl.vertex_attrib_divisor(location, rate);
-                } else if rate > 0 {
-                    log::error!("Binding attribute with instanced input rate is not supported");
+            }
+            com::Command::SetActiveAttributes(mask) => {
+                if self.share.private_caps.vertex_array {
+                    self.bind_vertex_array();
+                } else {
+                    let gl = &self.share.context;
+                    let mut unused = self.state.active_attributes & !mask;
+                    while unused != 0 {
+                        let location = unused.trailing_zeros();
+                        unsafe { gl.disable_vertex_attrib_array(location) };
+                        unused &= !(1 << location);
+                    }
+                    self.state.active_attributes = mask;
                 }
-
-                gl.enable_vertex_attrib_array(location);
-                gl.bind_buffer(glow::ARRAY_BUFFER, None);
-            },
-            /*
-            com::Command::UnbindAttribute(ref attribute) => unsafe {
-                self.share.context.DisableVertexAttribArray(attribute.location);
-            }*/
+            }
             com::Command::CopyBufferToBuffer {
                 src_buffer,
                 dst_buffer,
@@ -669,47 +979,86 @@ impl Queue {
                 texture_target,
                 texture_format,
                 pixel_type,
+                compressed_size,
                 ref data,
             } => unsafe {
                 // TODO: Fix active texture
-                assert_eq!(data.image_offset.z, 0);
+                assert!(data.image_offset.z == 0 || texture_target == glow::TEXTURE_3D);
 
                 let gl = &self.share.context;
 
-                gl.active_texture(glow::TEXTURE0);
                 gl.bind_buffer(glow::PIXEL_UNPACK_BUFFER, Some(src_buffer));
 
+                // Compressed data is read from the range of the buffer, in the internal format.
+                let compressed_range = compressed_size
+                    .map(|size| data.buffer_offset as u32..(data.buffer_offset + size) as u32);
+                let layers = &data.image_layers.layers;
                 match texture_target {
-                    glow::TEXTURE_2D => {
-                        gl.bind_texture(glow::TEXTURE_2D, Some(dst_texture));
-                        gl.tex_sub_image_2d(
-                            glow::TEXTURE_2D,
-                            data.image_layers.level as _,
-                            data.image_offset.x,
-                            data.image_offset.y,
-                            data.image_extent.width as _,
-                            data.image_extent.height as _,
-                            texture_format,
-                            pixel_type,
-                            glow::PixelUnpackData::BufferOffset(data.buffer_offset as u32),
-                        );
+                    glow::TEXTURE_2D | glow::TEXTURE_CUBE_MAP => {
+                        self.state.bind_texture(gl, 0, texture_target, dst_texture);
+                        let target = if texture_target == glow::TEXTURE_CUBE_MAP {
+                            glow::TEXTURE_CUBE_MAP_POSITIVE_X + layers.start as u32
+                        } else {
+                            glow::TEXTURE_2D
+                        };
+                        match compressed_range {
+                            Some(range) => gl.compressed_tex_sub_image_2d(
+                                target,
+                                data.image_layers.level as _,
+                                data.image_offset.x,
+                                data.image_offset.y,
+                                data.image_extent.width as _,
+                                data.image_extent.height as _,
+                                texture_format,
+                                glow::CompressedPixelUnpackData::BufferRange(range),
+                            ),
+                            None => gl.tex_sub_image_2d(
+                                target,
+                                data.image_layers.level as _,
+                                data.image_offset.x,
+                                data.image_offset.y,
+                                data.image_extent.width as _,
+                                data.image_extent.height as _,
+                                texture_format,
+                                pixel_type,
+                                glow::PixelUnpackData::BufferOffset(data.buffer_offset as u32),
+                            ),
+                        }
                     }
-                    glow::TEXTURE_2D_ARRAY => {
-                        gl.bind_texture(glow::TEXTURE_2D_ARRAY, Some(dst_texture));
-                        gl.tex_sub_image_3d(
-                            glow::TEXTURE_2D_ARRAY,
-                            data.image_layers.level as _,
-                            data.image_offset.x,
-                            data.image_offset.y,
-                            data.image_layers.layers.start as i32,
-                            data.image_extent.width as _,
-                            data.image_extent.height as _,
-                            data.image_layers.layers.end as i32
-                                - data.image_layers.layers.start as i32,
-                            texture_format,
-                            pixel_type,
-                            glow::PixelUnpackData::BufferOffset(data.buffer_offset as u32),
-                        );
+                    glow::TEXTURE_2D_ARRAY | glow::TEXTURE_3D => {
+                        self.state.bind_texture(gl, 0, texture_target, dst_texture);
+                        let (z_offset, depth) = if texture_target == glow::TEXTURE_3D {
+                            (data.image_offset.z, data.image_extent.depth as i32)
+                        } else {
+                            (layers.start as i32, layers.end as i32 - layers.start as i32)
+                        };
+                        match compressed_range {
+                            Some(range) => gl.compressed_tex_sub_image_3d(
+                                texture_target,
+                                data.image_layers.level as _,
+                                data.image_offset.x,
+                                data.image_offset.y,
+                                z_offset,
+                                data.image_extent.width as _,
+                                data.image_extent.height as _,
+                                depth,
+                                texture_format,
+                                glow::CompressedPixelUnpackData::BufferRange(range),
+                            ),
+                            None => gl.tex_sub_image_3d(
+                                texture_target,
+                                data.image_layers.level as _,
+                                data.image_offset.x,
+                                data.image_offset.y,
+                                z_offset,
+                                data.image_extent.width as _,
+                                data.image_extent.height as _,
+                                depth,
+                                texture_format,
+                                pixel_type,
+                                glow::PixelUnpackData::BufferOffset(data.buffer_offset as u32),
+                            ),
+                        }
                     }
                     _ => unimplemented!(),
                 }
@@ -727,37 +1076,93 @@ impl Queue {
                 dst_buffer,
                 ref data,
             } => {
-                if self.share.private_caps.get_tex_image {
-                    // TODO: Fix active texture
-                    // TODO: handle partial copies gracefully
-                    assert_eq!(data.image_offset, hal::image::Offset { x: 0, y: 0, z: 0 });
-                    assert_eq!(texture_target, glow::TEXTURE_2D);
-                    let gl = &self.share.context;
-                    unsafe {
-                        gl.active_texture(glow::TEXTURE0);
-                        gl.bind_buffer(glow::PIXEL_PACK_BUFFER, Some(dst_buffer));
-                        gl.bind_texture(glow::TEXTURE_2D, Some(src_texture));
-                        gl.get_tex_image(
+                let read_framebuffer = match self.read_framebuffer {
+                    Some(framebuffer) => framebuffer,
+                    None => {
+                        log::error!("CopyTextureToBuffer requires framebuffer objects");
+                        return;
+                    }
+                };
+                let gl = &self.share.context;
+                let attachment = Self::read_attachment(data.image_layers.aspects);
+                let level = data.image_layers.level as i32;
+                unsafe {
+                    gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(read_framebuffer));
+                    match texture_target {
+                        glow::TEXTURE_2D => gl.framebuffer_texture_2d(
+                            glow::READ_FRAMEBUFFER,
+                            attachment,
                             glow::TEXTURE_2D,
-                            data.image_layers.level as _,
-                            //data.image_offset.x,
-                            //data.image_offset.y,
-                            //data.image_extent.width as _,
-                            //data.image_extent.height as _,
-                            texture_format,
-                            pixel_type,
-                            glow::PixelPackData::BufferOffset(data.buffer_offset as u32),
-                        );
-                        gl.bind_buffer(glow::PIXEL_PACK_BUFFER, None);
+                            Some(src_texture),
+                            level,
+                        ),
+                        glow::TEXTURE_CUBE_MAP => gl.framebuffer_texture_2d(
+                            glow::READ_FRAMEBUFFER,
+                            attachment,
+                            glow::TEXTURE_CUBE_MAP_POSITIVE_X
+                                + data.image_layers.layers.start as u32,
+                            Some(src_texture),
+                            level,
+                        ),
+                        glow::TEXTURE_3D => gl.framebuffer_texture_layer(
+                            glow::READ_FRAMEBUFFER,
+                            attachment,
+                            Some(src_texture),
+                            level,
+                            data.image_offset.z,
+                        ),
+                        _ => gl.framebuffer_texture_layer(
+                            glow::READ_FRAMEBUFFER,
+                            attachment,
+                            Some(src_texture),
+                            level,
+                            data.image_layers.layers.start as i32,
+                        ),
                     }
-                } else {
-                    //TODO: use FBO
-                    log::error!("CopyTextureToBuffer is not implemented on GLES");
+                    self.read_pixels(attachment, texture_format, pixel_type, dst_buffer, data);
+                    gl.framebuffer_texture_2d(
+                        glow::READ_FRAMEBUFFER,
+                        attachment,
+                        glow::TEXTURE_2D,
+                        None,
+                        0,
+                    );
+                    gl.bind_framebuffer(glow::READ_FRAMEBUFFER, None);
                 }
             }
-            com::Command::CopyRenderbufferToBuffer(..) => {
-                //TODO: use FBO
-                log::error!("CopyRenderbufferToBuffer is not implemented");
+            com::Command::CopyRenderbufferToBuffer {
+                src_renderbuffer,
+                texture_format,
+                pixel_type,
+                dst_buffer,
+                ref data,
+            } => {
+                let read_framebuffer = match self.read_framebuffer {
+                    Some(framebuffer) => framebuffer,
+                    None => {
+                        log::error!("CopyRenderbufferToBuffer requires framebuffer objects");
+                        return;
+                    }
+                };
+                let gl = &self.share.context;
+                let attachment = Self::read_attachment(data.image_layers.aspects);
+                unsafe {
+                    gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(read_framebuffer));
+                    gl.framebuffer_renderbuffer(
+                        glow::READ_FRAMEBUFFER,
+                        attachment,
+                        glow::RENDERBUFFER,
+                        Some(src_renderbuffer),
+                    );
+                    self.read_pixels(attachment, texture_format, pixel_type, dst_buffer, data);
+                    gl.framebuffer_renderbuffer(
+                        glow::READ_FRAMEBUFFER,
+                        attachment,
+                        glow::RENDERBUFFER,
+                        None,
+                    );
+                    gl.bind_framebuffer(glow::READ_FRAMEBUFFER, None);
+                }
             }
             com::Command::CopyImageToTexture(..) => {
                 //TODO: use FBO
@@ -782,6 +1187,7 @@ impl Queue {
                     native::ImageType::Renderbuffer {
                         raw: src_renderbuffer,
                         format: src_format,
+                        ..
                     } => {
                         if src_format != dst_format {
                             unimplemented!()
@@ -831,18 +1237,16 @@ impl Queue {
                 let gl = &self.share.context;
                 gl.bind_buffer_range(target, index, Some(buffer), offset, size);
             },
-            com::Command::BindTexture(index, texture, textype) => unsafe {
-                let gl = &self.share.context;
-                gl.active_texture(glow::TEXTURE0 + index);
-                gl.bind_texture(textype, Some(texture));
-            },
-            com::Command::BindSampler(index, sampler) => unsafe {
-                let gl = &self.share.context;
-                gl.bind_sampler(index, Some(sampler));
-            },
+            com::Command::BindTexture(index, texture, textype) => {
+                self.state
+                    .bind_texture(&self.share.context, index, textype, texture);
+            }
+            com::Command::BindSampler(index, sampler) => {
+                self.state.bind_sampler(&self.share.context, index, sampler);
+            }
             com::Command::SetTextureSamplerSettings(index, textype, ref sinfo) => unsafe {
                 let gl = &self.share.context;
-                gl.active_texture(glow::TEXTURE0 + index);
+                self.state.set_active_texture(gl, index);
                 // TODO: Optimization: only change texture properties that have changed.
                 device::set_sampler_info(
                     &sinfo,
@@ -984,19 +1388,16 @@ impl Queue {
                     })
                 };
 
+                let caps = &mut self.state.capabilities;
+                caps.set(gl, glow::CULL_FACE, !rasterizer.cull_face.is_empty());
                 if !rasterizer.cull_face.is_empty() {
                     unsafe {
-                        gl.enable(glow::CULL_FACE);
                         gl.cull_face(match rasterizer.cull_face {
                             hal::pso::Face::FRONT => glow::FRONT,
                             hal::pso::Face::BACK => glow::BACK,
                             _ => glow::FRONT_AND_BACK,
                         });
                     }
-                } else {
-                    unsafe {
-                        gl.disable(glow::CULL_FACE);
-                    }
                 }
 
                 let (_gl_draw, gl_offset) = match rasterizer.polygon_mode {
@@ -1017,18 +1418,15 @@ impl Queue {
 
                 match rasterizer.depth_bias {
                     Some(hal::pso::State::Static(bias)) => unsafe {
-                        gl.enable(gl_offset);
+                        caps.set(gl, gl_offset, true);
                         gl.polygon_offset(bias.slope_factor as _, bias.const_factor as _);
                     },
-                    _ => unsafe { gl.disable(gl_offset) },
+                    _ => caps.set(gl, gl_offset, false),
                 }
 
                 if !self.share.info.version.is_embedded {
-                    match false {
-                        //TODO
-                        true => unsafe { gl.enable(glow::MULTISAMPLE) },
-                        false => unsafe { gl.disable(glow::MULTISAMPLE) },
-                    }
+                    //TODO
+                    caps.set(gl, glow::MULTISAMPLE, false);
                 }
             }
             com::Command::BindDepth(depth_fun) => {
@@ -1038,7 +1436,7 @@ impl Queue {
 
                 match depth_fun {
                     Some(depth_fun) => unsafe {
-                        gl.enable(glow::DEPTH_TEST);
+                        self.state.capabilities.set(gl, glow::DEPTH_TEST, true);
 
                         let cmp = match depth_fun {
                             Never => glow::NEVER,
@@ -1053,11 +1451,17 @@ impl Queue {
 
                         gl.depth_func(cmp);
                     },
-                    None => unsafe {
-                        gl.disable(glow::DEPTH_TEST);
-                    },
+                    None => self.state.capabilities.set(gl, glow::DEPTH_TEST, false),
                 }
             }
+            com::Command::BindStencil(ref stencil, cull_face) => {
+                state::bind_stencil(
+                    &self.share.context,
+                    &mut self.state.capabilities,
+                    stencil,
+                    cull_face,
+                );
+            }
             com::Command::SetColorMask(slot, mask) => unsafe {
                 use hal::pso::ColorMask as Cm;
                 if let (true, Some(slot)) = (self.share.private_caps.per_slot_color_mask, slot) {
@@ -1103,6 +1507,39 @@ impl Queue {
                     }
                 }
             }
+            com::Command::PushDebugGroup(name) => {
+                if self.share.private_caps.debug {
+                    let name = std::str::from_utf8(Self::get_raw(data_buf, name)).unwrap();
+                    unsafe {
+                        self.share.context.push_debug_group(
+                            glow::DEBUG_SOURCE_APPLICATION,
+                            0,
+                            name,
+                        );
+                    }
+                }
+            }
+            com::Command::PopDebugGroup => {
+                if self.share.private_caps.debug {
+                    unsafe {
+                        self.share.context.pop_debug_group();
+                    }
+                }
+            }
+            com::Command::InsertDebugMarker(name) => {
+                if self.share.private_caps.debug {
+                    let name = std::str::from_utf8(Self::get_raw(data_buf, name)).unwrap();
+                    unsafe {
+                        self.share.context.debug_message_insert(
+                            glow::DEBUG_SOURCE_APPLICATION,
+                            glow::DEBUG_TYPE_MARKER,
+                            0,
+                            glow::DEBUG_SEVERITY_NOTIFICATION,
+                            name,
+                        );
+                    }
+                }
+            }
         }
         if let Err(err) = self.share.check() {
             panic!("Error {:?} executing command: {:?}", err, cmd)
@@ -1123,6 +1560,8 @@ impl hal::queue::Queue<Backend> for Queue {
         Is: Iterator<Item = &'a native::Semaphore>,
     {
         use crate::pool::BufferMemory;
+        // The device may have changed the bindings since the last submission.
+        self.state.invalidate_bindings();
         {
             for cmd_buf in command_buffers {
                 let cb = &cmd_buf.data;
@@ -1148,6 +1587,10 @@ impl hal::queue::Queue<Backend> for Queue {
         }
 
         if let Some(fence) = fence {
+            // The fence may still hold the sync object of a previous submission.
+            if let native::Fence::Pending(sync) = *fence {
+                self.share.context.delete_sync(sync);
+            }
             *fence = if self.share.private_caps.sync {
                 native::Fence::Pending(
                     self.share
@@ -1162,12 +1605,58 @@ impl hal::queue::Queue<Backend> for Queue {
         }
     }
 
+    unsafe fn submit_timeline<'a, Ic, Iw, Is>(
+        &mut self,
+        command_buffers: Ic,
+        _wait_semaphores: Iw,
+        signal_semaphores: Is,
+    ) where
+        Ic: Iterator<Item = &'a com::CommandBuffer>,
+        Iw: Iterator<Item = (&'a native::TimelineSemaphore, u64, hal::pso::PipelineStage)>,
+        Is: Iterator<Item = (&'a native::TimelineSemaphore, u64)>,
+    {
+        for (semaphore, value, _) in wait_semaphores {
+            // Submissions execute in order, so the values signaled by the previous
+            // ones are reached in time, and only the host can signal the others.
+            let mut state = semaphore.state.lock();
+            while state.value < value && !state.pending.iter().any(|&(pending, _)| pending >= value)
+            {
+                if crate::is_webgl() {
+                    log::error!("Timeline value {} is never signaled", value);
+                    break;
+                }
+                semaphore.condvar.wait(&mut state);
+            }
+        }
+
+        hal::queue::Queue::submit(self, command_buffers, iter::empty(), iter::empty(), None);
+
+        let gl = &self.share.context;
+        for (semaphore, value) in signal_semaphores {
+            let mut state = semaphore.state.lock();
+            if self.share.private_caps.sync {
+                let sync = gl.fence_sync(glow::SYNC_GPU_COMMANDS_COMPLETE, 0).unwrap();
+                state.pending.push((value, sync));
+            } else {
+                gl.finish();
+                state.value = value;
+            }
+            semaphore.condvar.notify_all();
+        }
+    }
+
     unsafe fn present(
         &mut self,
         surface: &mut Surface,
         image: native::SwapchainImage,
         _wait_semaphore: Option<&mut native::Semaphore>,
     ) -> Result<Option<hal::window::Suboptimal>, hal::window::PresentError> {
+        if self.share.private_caps.framebuffer_srgb {
+            // The presentation blit keeps the default encoding of the colors.
+            self.state
+                .capabilities
+                .set(&self.share.context, glow::FRAMEBUFFER_SRGB, true);
+        }
         surface.present(image, &self.share.context)
     }
 
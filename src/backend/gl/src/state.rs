@@ -2,6 +2,41 @@ use crate::{ColorSlot, GlContainer};
 use glow::HasContext;
 use hal::pso;
 
+/// Shadow of the enabled server-side capabilities, used to skip
+/// redundant `glEnable` and `glDisable` calls.
+#[derive(Debug, Default)]
+pub(crate) struct CapabilityCache {
+    known: Vec<(u32, bool)>,
+}
+
+impl CapabilityCache {
+    /// Enable or disable the capability `cap`, unless it's already in that state.
+    pub(crate) fn set(&mut self, gl: &GlContainer, cap: u32, enabled: bool) {
+        match self.known.iter_mut().find(|entry| entry.0 == cap) {
+            Some(entry) if entry.1 == enabled => return,
+            Some(entry) => entry.1 = enabled,
+            None => self.known.push((cap, enabled)),
+        }
+        unsafe {
+            if enabled {
+                gl.enable(cap);
+            } else {
+                gl.disable(cap);
+            }
+        }
+    }
+
+    /// Forget the state of `cap`, after it was changed by other means.
+    pub(crate) fn invalidate(&mut self, cap: u32) {
+        self.known.retain(|&(known, _)| known != cap);
+    }
+
+    /// Forget the state of all the capabilities.
+    pub(crate) fn clear(&mut self) {
+        self.known.clear();
+    }
+}
+
 pub fn map_comparison(cmp: pso::Comparison) -> u32 {
     use hal::pso::Comparison::*;
     match cmp {
@@ -16,7 +51,6 @@ pub fn map_comparison(cmp: pso::Comparison) -> u32 {
     }
 }
 
-#[allow(dead_code)]
 fn map_operation(op: pso::StencilOp) -> u32 {
     use hal::pso::StencilOp::*;
     match op {
@@ -31,8 +65,12 @@ fn map_operation(op: pso::StencilOp) -> u32 {
     }
 }
 
-#[allow(dead_code)]
-pub(crate) fn bind_stencil(gl: &GlContainer, stencil: &Option<pso::StencilTest>, cull: pso::Face) {
+pub(crate) fn bind_stencil(
+    gl: &GlContainer,
+    caps: &mut CapabilityCache,
+    stencil: &Option<pso::StencilTest>,
+    cull: pso::Face,
+) {
     fn bind_side(
         gl: &GlContainer,
         face: u32,
@@ -52,7 +90,7 @@ pub(crate) fn bind_stencil(gl: &GlContainer, stencil: &Option<pso::StencilTest>,
     }
     match *stencil {
         Some(ref stencil) => {
-            unsafe { gl.enable(glow::STENCIL_TEST) };
+            caps.set(gl, glow::STENCIL_TEST, true);
             let read_masks = stencil.read_masks.static_or(pso::Sided::new(!0));
             let ref_values = stencil.reference_values.static_or(pso::Sided::new(0));
             if !cull.contains(pso::Face::FRONT) {
@@ -84,9 +122,7 @@ pub(crate) fn bind_stencil(gl: &GlContainer, stencil: &Option<pso::StencilTest>,
                 }
             }
         }
-        None => unsafe {
-            gl.disable(glow::STENCIL_TEST);
-        },
+        None => caps.set(gl, glow::STENCIL_TEST, false),
     }
 }
 
@@ -129,23 +165,26 @@ fn map_blend_op(operation: pso::BlendOp) -> (u32, u32, u32) {
     }
 }
 
-pub(crate) fn set_blend(gl: &GlContainer, blend: &Option<pso::BlendState>) {
+pub(crate) fn set_blend(
+    gl: &GlContainer,
+    caps: &mut CapabilityCache,
+    blend: &Option<pso::BlendState>,
+) {
     match blend {
         Some(ref blend) => unsafe {
             let (color_eq, color_src, color_dst) = map_blend_op(blend.color);
             let (alpha_eq, alpha_src, alpha_dst) = map_blend_op(blend.alpha);
-            gl.enable(glow::BLEND);
+            caps.set(gl, glow::BLEND, true);
             gl.blend_equation_separate(color_eq, alpha_eq);
             gl.blend_func_separate(color_src, color_dst, alpha_src, alpha_dst);
         },
-        None => unsafe {
-            gl.disable(glow::BLEND);
-        },
+        None => caps.set(gl, glow::BLEND, false),
     };
 }
 
 pub(crate) fn set_blend_slot(
     gl: &GlContainer,
+    caps: &mut CapabilityCache,
     slot: ColorSlot,
     blend: &Option<pso::BlendState>,
     features: &hal::Features,
@@ -155,6 +194,9 @@ pub(crate) fn set_blend_slot(
         return;
     }
 
+    // The blending of the slots is no longer the same for all of them.
+    caps.invalidate(glow::BLEND);
+
     match blend {
         Some(ref blend) => unsafe {
             let (color_eq, color_src, color_dst) = map_blend_op(blend.color);
@@ -1,6 +1,9 @@
 //! EGL-based surface and swapchain.
 
-use crate::{conv, native, GlContainer, PhysicalDevice, Starc};
+use crate::{
+    bindless::BindlessTextureFns, conv, indirect::MultiDrawIndirectFns, native,
+    pipeline_cache::ProgramBinaryFns, GlContainer, PhysicalDevice, Starc,
+};
 use glow::HasContext;
 use hal::{image, window as w};
 use parking_lot::Mutex;
@@ -12,8 +15,7 @@ pub struct Swapchain {
     renderbuffer: glow::Renderbuffer,
     /// Extent because the window lies
     extent: w::Extent2D,
-    format: native::TextureFormat,
-    channel: hal::format::ChannelType,
+    format: hal::format::Format,
 }
 
 #[derive(Debug)]
@@ -41,6 +43,7 @@ unsafe impl Sync for Instance {}
 
 const EGL_PLATFORM_WAYLAND_KHR: u32 = 0x31D8;
 const EGL_PLATFORM_X11_KHR: u32 = 0x31D5;
+const EGL_PLATFORM_SURFACELESS_MESA: u32 = 0x31DD;
 
 type XOpenDisplayFun =
     unsafe extern "system" fn(display_name: *const raw::c_char) -> *mut raw::c_void;
@@ -105,17 +108,15 @@ fn test_wayland_display() -> Option<libloading::Library> {
     Some(library)
 }
 
-/// Choose GLES framebuffer configuration.
+/// Choose framebuffer configuration, renderable with the client API of `renderable_type`.
 fn choose_config(
     egl: &egl::DynamicInstance<egl::EGL1_4>,
     display: egl::Display,
+    renderable_type: egl::Int,
 ) -> Result<(egl::Config, bool), hal::UnsupportedBackend> {
     //TODO: EGL_SLOW_CONFIG
     let tiers = [
-        (
-            "off-screen",
-            &[egl::RENDERABLE_TYPE, egl::OPENGL_ES2_BIT][..],
-        ),
+        ("off-screen", &[egl::RENDERABLE_TYPE, renderable_type][..]),
         ("presentation", &[egl::SURFACE_TYPE, egl::WINDOW_BIT]),
         #[cfg(not(target_os = "android"))]
         ("native-render", &[egl::NATIVE_RENDERABLE, egl::TRUE as _]),
@@ -148,6 +149,58 @@ fn choose_config(
     Err(hal::UnsupportedBackend)
 }
 
+/// Create a context of the client `api`, either GLES 3.0 or higher,
+/// or desktop GL 3.3 or higher with the core profile.
+fn create_context(
+    egl: &egl::DynamicInstance<egl::EGL1_4>,
+    display: egl::Display,
+    api: egl::Enum,
+    debug: bool,
+) -> Result<(egl::Config, bool, egl::Context), hal::UnsupportedBackend> {
+    let (renderable_type, mut context_attributes) = if api == egl::OPENGL_ES_API {
+        (
+            egl::OPENGL_ES2_BIT,
+            vec![
+                egl::CONTEXT_CLIENT_VERSION,
+                3, // Request GLES 3.0 or higher
+            ],
+        )
+    } else {
+        (
+            egl::OPENGL_BIT,
+            vec![
+                egl::CONTEXT_MAJOR_VERSION,
+                3,
+                egl::CONTEXT_MINOR_VERSION,
+                3,
+                egl::CONTEXT_OPENGL_PROFILE_MASK,
+                egl::CONTEXT_OPENGL_CORE_PROFILE_BIT,
+            ],
+        )
+    };
+    let (config, supports_native_window) = choose_config(egl, display, renderable_type)?;
+    egl.bind_api(api).map_err(|_| hal::UnsupportedBackend)?;
+
+    //TODO: make it so `Device` == EGL Context
+    if debug {
+        //TODO: figure out why this is needed
+        context_attributes.push(egl::CONTEXT_OPENGL_DEBUG);
+        context_attributes.push(egl::TRUE as _);
+    }
+    context_attributes.push(egl::NONE as _);
+    match egl.create_context(display, config, None, &context_attributes) {
+        Ok(context) => Ok((config, supports_native_window, context)),
+        Err(e) => {
+            if api == egl::OPENGL_ES_API {
+                log::warn!("unable to create GLES 3.x context: {:?}", e);
+            } else {
+                log::warn!("unable to create GL 3.3 core context: {:?}", e);
+            }
+            Err(hal::UnsupportedBackend)
+        }
+    }
+}
+
 impl Inner {
     fn create(
         egl: Starc<egl::DynamicInstance<egl::EGL1_4>>,
@@ -184,27 +237,13 @@ impl Inner {
             }
         }
 
-        let (config, supports_native_window) = choose_config(&egl, display)?;
-        egl.bind_api(egl::OPENGL_ES_API).unwrap();
-
-        //TODO: make it so `Device` == EGL Context
-        let mut context_attributes = vec![
-            egl::CONTEXT_CLIENT_VERSION,
-            3, // Request GLES 3.0 or higher
-        ];
-        if cfg!(debug_assertions) && wsi_library.is_none() && !cfg!(target_os = "android") {
-            //TODO: figure out why this is needed
-            context_attributes.push(egl::CONTEXT_OPENGL_DEBUG);
-            context_attributes.push(egl::TRUE as _);
-        }
-        context_attributes.push(egl::NONE as _);
-        let context = match egl.create_context(display, config, None, &context_attributes) {
-            Ok(context) => context,
-            Err(e) => {
-                log::warn!("unable to create GLES 3.x context: {:?}", e);
-                return Err(hal::UnsupportedBackend);
-            }
-        };
+        let debug = cfg!(debug_assertions) && wsi_library.is_none() && !cfg!(target_os = "android");
+        let (config, supports_native_window, context) =
+            match create_context(&egl, display, egl::OPENGL_ES_API, debug) {
+                Ok(created) => created,
+                // Desktop GL is the fallback on the platforms without GLES drivers.
+                Err(_) => create_context(&egl, display, egl::OPENGL_API, debug)?,
+            };
 
         // Testing if context can be binded without surface
         // and creating dummy pbuffer surface if not.
@@ -304,7 +343,28 @@ impl hal::Instance<crate::Backend> for Instance {
             egl.get_display(egl::DEFAULT_DISPLAY).unwrap()
         };
 
-        let inner = Inner::create(egl.clone(), display, wsi_library.as_ref())?;
+        let inner = match Inner::create(egl.clone(), display, wsi_library.as_ref()) {
+            Ok(inner) => inner,
+            // Without a display server, the default platform may be unusable,
+            // while rendering off-screen is still possible (e.g. on CI machines).
+            Err(_)
+                if wsi_library.is_none()
+                    && client_ext_str.contains(&"EGL_MESA_platform_surfaceless") =>
+            {
+                let egl1_5 = egl.upcast::<egl::EGL1_5>().ok_or(hal::UnsupportedBackend)?;
+                log::info!("Using surfaceless platform");
+                let display_attributes = [egl::ATTRIB_NONE];
+                let display = egl1_5
+                    .get_platform_display(
+                        EGL_PLATFORM_SURFACELESS_MESA,
+                        egl::DEFAULT_DISPLAY,
+                        &display_attributes,
+                    )
+                    .map_err(|_| hal::UnsupportedBackend)?;
+                Inner::create(egl.clone(), display, None)?
+            }
+            Err(e) => return Err(e),
+        };
 
         Ok(Instance {
             inner: Mutex::new(inner),
@@ -324,16 +384,23 @@ impl hal::Instance<crate::Backend> for Instance {
             )
             .unwrap();
 
-        let context = unsafe {
-            glow::Context::from_loader_function(|name| {
-                inner
-                    .egl
-                    .get_proc_address(name)
-                    .map_or(ptr::null(), |p| p as *const _)
-            })
+        let get_proc_address = |name: &str| {
+            inner
+                .egl
+                .get_proc_address(name)
+                .map_or(ptr::null(), |p| p as *const std::ffi::c_void)
         };
+        let context = unsafe { glow::Context::from_loader_function(get_proc_address) };
+        let program_binary = unsafe { ProgramBinaryFns::load(get_proc_address) };
+        let multi_draw_indirect = unsafe { MultiDrawIndirectFns::load(get_proc_address) };
+        let bindless_texture = unsafe { BindlessTextureFns::load(get_proc_address) };
         // Create physical device
-        vec![PhysicalDevice::new_adapter(context)]
+        vec![PhysicalDevice::new_adapter(
+            context,
+            program_binary,
+            multi_draw_indirect,
+            bindless_texture,
+        )]
     }
 
     #[cfg_attr(target_os = "macos", allow(unused, unused_mut, unreachable_code))]
@@ -574,8 +641,7 @@ impl w::PresentationSurface<crate::Backend> for Surface {
             renderbuffer,
             framebuffer,
             extent: config.extent,
-            format: desc.tex_internal,
-            channel: config.format.base_format().1,
+            format: config.format,
         });
 
         Ok(())
@@ -594,8 +660,7 @@ impl w::PresentationSurface<crate::Backend> for Surface {
         _timeout_ns: u64,
     ) -> Result<(Self::SwapchainImage, Option<w::Suboptimal>), w::AcquireError> {
         let sc = self.swapchain.as_ref().unwrap();
-        let sc_image =
-            native::SwapchainImage::new(sc.renderbuffer, sc.format, sc.extent, sc.channel);
+        let sc_image = native::SwapchainImage::new(sc.renderbuffer, sc.format, sc.extent);
         Ok((sc_image, None))
     }
 }
@@ -609,6 +674,8 @@ impl w::Surface<crate::Backend> for Surface {
         w::SurfaceCapabilities {
             present_modes: w::PresentMode::FIFO,                  //TODO
             composite_alpha_modes: w::CompositeAlphaMode::OPAQUE, //TODO
+            current_transform: w::SurfaceTransform::IDENTITY,
+            transforms: w::SurfaceTransform::IDENTITY,
             image_count: 2..=2,
             current_extent: None,
             extents: w::Extent2D {
@@ -620,6 +687,8 @@ impl w::Surface<crate::Backend> for Surface {
             },
             max_image_layers: 1,
             usage: image::Usage::COLOR_ATTACHMENT,
+            swapchain_maintenance: false,
+            present_wait: false,
         }
     }
 
@@ -10,8 +10,7 @@ use wasm_bindgen::JsCast;
 #[derive(Clone, Debug)]
 pub struct Swapchain {
     pub(crate) extent: window::Extent2D,
-    pub(crate) channel: f::ChannelType,
-    pub(crate) raw_format: native::TextureFormat,
+    pub(crate) format: f::Format,
     pub(crate) framebuffer: native::RawFramebuffer,
 }
 
@@ -59,7 +58,7 @@ impl hal::Instance<B> for Instance {
             None => return Vec::new(),
         };
 
-        let adapter = PhysicalDevice::new_adapter(context);
+        let adapter = PhysicalDevice::new_adapter(context, None, None, None);
         vec![adapter]
     }
 
@@ -166,11 +165,15 @@ impl window::Surface<B> for Surface {
         window::SurfaceCapabilities {
             present_modes: window::PresentMode::FIFO, //TODO
             composite_alpha_modes: window::CompositeAlphaMode::OPAQUE, //TODO
+            current_transform: window::SurfaceTransform::IDENTITY,
+            transforms: window::SurfaceTransform::IDENTITY,
             image_count: 1..=1,
             current_extent: Some(extent),
             extents: extent..=extent,
             max_image_layers: 1,
             usage: image::Usage::COLOR_ATTACHMENT | image::Usage::TRANSFER_SRC,
+            swapchain_maintenance: false,
+            present_wait: false,
         }
     }
 
@@ -198,6 +201,11 @@ impl window::PresentationSurface<B> for Surface {
             self.renderbuffer = Some(gl.create_renderbuffer().unwrap());
         }
 
+        // The drawing buffer of the canvas has to match the swapchain,
+        // otherwise the blit on present gets clipped or leaves stale pixels.
+        self.canvas.set_width(config.extent.width);
+        self.canvas.set_height(config.extent.height);
+
         let desc = conv::describe_format(config.format).unwrap();
         gl.bind_renderbuffer(glow::RENDERBUFFER, self.renderbuffer);
         gl.renderbuffer_storage(
@@ -217,8 +225,7 @@ impl window::PresentationSurface<B> for Surface {
         );
         self.swapchain = Some(Swapchain {
             extent: config.extent,
-            channel: config.format.base_format().1,
-            raw_format: desc.tex_external,
+            format: config.format,
             framebuffer,
         });
         Ok(())
@@ -239,12 +246,8 @@ impl window::PresentationSurface<B> for Surface {
         _timeout_ns: u64,
     ) -> Result<(Self::SwapchainImage, Option<window::Suboptimal>), window::AcquireError> {
         let sc = self.swapchain.as_ref().unwrap();
-        let swapchain_image = native::SwapchainImage::new(
-            self.renderbuffer.unwrap(),
-            sc.raw_format,
-            sc.extent,
-            sc.channel,
-        );
+        let swapchain_image =
+            native::SwapchainImage::new(self.renderbuffer.unwrap(), sc.format, sc.extent);
         Ok((swapchain_image, None))
     }
 }
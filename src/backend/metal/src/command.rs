@@ -1,17 +1,20 @@
 use crate::{
     conversions as conv,
-    internal::{BlitVertex, ClearKey, ClearVertex},
-    native, soft, window, AsNative, Backend, BufferPtr, FastHashMap, OnlineRecording,
-    PrivateDisabilities, ResourceIndex, ResourcePtr, SamplerPtr, Shared, TexturePtr,
-    MAX_BOUND_DESCRIPTOR_SETS, MAX_COLOR_ATTACHMENTS,
+    counters::CounterPool,
+    internal::{BlitVertex, Channel, ClearKey, ClearVertex},
+    native, soft,
+    timing::{PassTimer, PassTiming},
+    window, AsNative, Backend, BufferPtr, FastHashMap, OnlineRecording, PrivateDisabilities,
+    ResourceIndex, ResourcePtr, SamplerPtr, Shared, TexturePtr, MAX_BOUND_DESCRIPTOR_SETS,
+    MAX_COLOR_ATTACHMENTS,
 };
 
 use hal::{
     buffer, command as com,
-    device::OutOfMemory,
+    device::{DeviceLost, OutOfMemory},
     format::{Aspects, FormatDesc},
     image as i, memory,
-    pass::AttachmentLoadOp,
+    pass::{AttachmentLoadOp, ResolveMode},
     pso, query,
     window::{PresentError, Suboptimal},
     DrawCount, IndexCount, IndexType, InstanceCount, TaskCount, VertexCount, VertexOffset,
@@ -25,21 +28,29 @@ use copyless::VecHelper;
 #[cfg(feature = "dispatch")]
 use dispatch;
 use foreign_types::ForeignType;
-use metal::{self, MTLIndexType, MTLPrimitiveType, MTLScissorRect, MTLSize, MTLViewport, NSRange};
-use objc::rc::autoreleasepool;
-use parking_lot::Mutex;
+use metal::{
+    self, MTLIndexType, MTLPrimitiveType, MTLScissorRect, MTLSize, MTLTextureType, MTLViewport,
+    NSRange,
+};
+use objc::{
+    rc::autoreleasepool,
+    runtime::{Object, BOOL, NO, YES},
+};
+use parking_lot::{Mutex, RwLock};
 
 #[cfg(feature = "dispatch")]
 use std::fmt;
 use std::{
     borrow::Borrow,
     cell::RefCell,
+    collections::VecDeque,
     iter, mem,
     ops::{Deref, Range},
+    os::raw::c_void,
     ptr, slice,
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc,
+        Arc, Weak,
     },
     thread, time,
 };
@@ -47,6 +58,15 @@ use std::{
 const INTERNAL_LABELS: bool = cfg!(debug_assertions);
 const WORD_SIZE: usize = 4;
 const WORD_ALIGNMENT: u64 = WORD_SIZE as _;
+/// Largest amount of push constant data that can be passed inline, in bytes.
+const PUSH_CONSTANTS_INLINE_LIMIT: usize = 0x1000;
+/// Size of a single buffer of the push constant ring.
+const PUSH_CONSTANT_RING_CHUNK: buffer::Offset = 0x40000;
+/// Alignment of the push constant blocks within the ring, required for constant buffers.
+const PUSH_CONSTANT_RING_ALIGNMENT: buffer::Offset = 256;
+/// Largest buffer fill with a value that isn't a repeated byte, which is copied from
+/// the staging data instead of running a compute pass, in bytes.
+const MAX_STAGED_FILL: buffer::Offset = 0x1000;
 /// Number of frames to average when reporting the performance counters.
 const COUNTERS_REPORT_WINDOW: usize = 0;
 
@@ -61,9 +81,11 @@ impl<T> fmt::Debug for NoDebug<T> {
 
 #[derive(Debug)]
 pub struct QueueInner {
-    raw: metal::CommandQueue,
+    pub(crate) raw: metal::CommandQueue,
     reserve: Range<usize>,
     debug_retain_references: bool,
+    /// Enqueued command buffer with the batched submissions, see `Queue::batch_submissions`.
+    pending: Option<metal::CommandBuffer>,
 }
 
 #[must_use]
@@ -88,11 +110,13 @@ impl QueueInner {
                 raw: device.new_command_queue_with_max_command_buffer_count(count as u64),
                 reserve: 0..count,
                 debug_retain_references: false,
+                pending: None,
             },
             None => QueueInner {
                 raw: device.new_command_queue(),
                 reserve: 0..64,
                 debug_retain_references: true,
+                pending: None,
             },
         }
     }
@@ -118,12 +142,26 @@ impl QueueInner {
         self.reserve.start -= 1;
     }
 
+    /// Commit the batched submissions, if any.
+    ///
+    /// The batch is enqueued ahead of anything submitted after it,
+    /// so it can be committed directly.
+    pub(crate) fn flush_pending(&mut self) {
+        if let Some(cmd_buf) = self.pending.take() {
+            cmd_buf.commit();
+        }
+    }
+
     /// Block until GPU is idle.
     pub(crate) fn wait_idle(queue: &Mutex<Self>) {
         debug!("waiting for idle");
         // note: we deliberately don't hold the Mutex lock while waiting,
         // since the completion handlers need to access it.
-        let (cmd_buf, token) = queue.lock().spawn();
+        let (cmd_buf, token) = {
+            let mut inner = queue.lock();
+            inner.flush_pending();
+            inner.spawn()
+        };
         if INTERNAL_LABELS {
             cmd_buf.set_label("empty");
         }
@@ -176,9 +214,110 @@ impl QueueBlocker {
     }
 }
 
+/// Maximum number of configured render pass descriptors kept for reuse.
+/// They retain their attachments, so the limit also bounds how long the textures
+/// of destroyed images are kept alive.
+const MAX_CONFIGURED_DESCRIPTORS: usize = 32;
+
+/// Maximum number of custom sample positions of a render pass, one per sample.
+const MAX_SAMPLE_POSITIONS: usize = 16;
+
+type SamplePositions = ArrayVec<[pso::SampleLocation; MAX_SAMPLE_POSITIONS]>;
+
+/// Configuration of a render pass descriptor attachment.
+#[derive(Debug, PartialEq)]
+struct AttachmentSetup<C> {
+    texture: TexturePtr,
+    resolve_texture: Option<TexturePtr>,
+    load_action: Option<metal::MTLLoadAction>,
+    store_action: Option<metal::MTLStoreAction>,
+    clear_value: Option<C>,
+}
+
+impl<C> AttachmentSetup<C> {
+    fn new(texture: &metal::TextureRef) -> Self {
+        AttachmentSetup {
+            texture: AsNative::from(texture),
+            resolve_texture: None,
+            load_action: None,
+            store_action: None,
+            clear_value: None,
+        }
+    }
+
+    fn apply(&self, desc: &metal::RenderPassAttachmentDescriptorRef) {
+        desc.set_texture(Some(self.texture.as_native()));
+        if let Some(ref resolve) = self.resolve_texture {
+            desc.set_resolve_texture(Some(resolve.as_native()));
+        }
+        if let Some(action) = self.load_action {
+            desc.set_load_action(action);
+        }
+        if let Some(action) = self.store_action {
+            desc.set_store_action(action);
+        }
+    }
+}
+
+/// Complete configuration of a render pass descriptor for a subpass,
+/// used to look up a cached descriptor that is already set up this way.
+#[derive(Debug, Default, PartialEq)]
+struct RenderPassSetup {
+    render_target_array_length: Option<NSUInteger>,
+    tile_layout: Option<native::TileLayout>,
+    sample_positions: SamplePositions,
+    colors: ArrayVec<[AttachmentSetup<[f64; 4]>; MAX_COLOR_ATTACHMENTS]>,
+    depth: Option<AttachmentSetup<f64>>,
+    depth_resolve_filter: Option<NSUInteger>,
+    stencil: Option<AttachmentSetup<u32>>,
+}
+
+impl RenderPassSetup {
+    fn apply(&self, descriptor: &metal::RenderPassDescriptorRef) {
+        if let Some(length) = self.render_target_array_length {
+            descriptor.set_render_target_array_length(length);
+        }
+        if let Some(ref layout) = self.tile_layout {
+            set_tile_layout(descriptor, layout);
+        }
+        if !self.sample_positions.is_empty() {
+            set_sample_positions(descriptor, &self.sample_positions);
+        }
+        for (i, at) in self.colors.iter().enumerate() {
+            let desc = descriptor.color_attachments().object_at(i as _).unwrap();
+            at.apply(desc);
+            if let Some([r, g, b, a]) = at.clear_value {
+                desc.set_clear_color(metal::MTLClearColor::new(r, g, b, a));
+            }
+        }
+        if let Some(ref at) = self.depth {
+            let desc = descriptor.depth_attachment().unwrap();
+            at.apply(desc);
+            if let Some(depth) = at.clear_value {
+                desc.set_clear_depth(depth);
+            }
+            if let Some(filter) = self.depth_resolve_filter {
+                let () = unsafe { msg_send![desc, setDepthResolveFilter: filter] };
+            }
+        }
+        if let Some(ref at) = self.stencil {
+            let desc = descriptor.stencil_attachment().unwrap();
+            at.apply(desc);
+            if let Some(stencil) = at.clear_value {
+                desc.set_clear_stencil(stencil);
+            }
+        }
+    }
+}
+
 #[derive(Debug, Default)]
 struct RenderPassDescriptorCache {
     spare_descriptors: Vec<metal::RenderPassDescriptor>,
+    /// Descriptors set up for render passes that are free to be reused
+    /// by a pass with the same setup, least recently used first.
+    configured_descriptors: VecDeque<(RenderPassSetup, metal::RenderPassDescriptor)>,
+    /// Setup of the configured descriptors that are currently in use.
+    active_setups: FastHashMap<*const metal::RenderPassDescriptorRef, RenderPassSetup>,
 }
 
 #[cfg(feature = "dispatch")]
@@ -191,13 +330,50 @@ impl RenderPassDescriptorCache {
         if let Some(rp_desc) = self.spare_descriptors.pop() {
             rp_desc
         } else {
-            let rp_desc = metal::RenderPassDescriptor::new();
+            // created retained, so that no autorelease pool is needed for recording
+            let rp_desc: metal::RenderPassDescriptor =
+                unsafe { msg_send![class!(MTLRenderPassDescriptor), new] };
             rp_desc.set_visibility_result_buffer(Some(&shared.visibility.buffer));
-            rp_desc.to_owned()
+            rp_desc
         }
     }
 
-    fn free(&mut self, rp_desc: metal::RenderPassDescriptor) {
+    /// Get a descriptor configured with `setup`, reusing a cached one if possible.
+    fn alloc_configured(
+        &mut self,
+        setup: RenderPassSetup,
+        shared: &Shared,
+    ) -> metal::RenderPassDescriptor {
+        let rp_desc = match self
+            .configured_descriptors
+            .iter()
+            .position(|&(ref cached, _)| *cached == setup)
+        {
+            Some(pos) => self.configured_descriptors.remove(pos).unwrap().1,
+            None => {
+                let rp_desc = self.alloc(shared);
+                setup.apply(&rp_desc);
+                rp_desc
+            }
+        };
+        self.active_setups.insert(&*rp_desc as *const _, setup);
+        rp_desc
+    }
+
+    /// Stop tracking the setup of a descriptor that is about to be modified,
+    /// so that it's not reused for the original setup.
+    fn forget_setup(&mut self, rp_desc: &metal::RenderPassDescriptorRef) {
+        self.active_setups.remove(&(rp_desc as *const _));
+    }
+
+    fn free(&mut self, mut rp_desc: metal::RenderPassDescriptor) {
+        if let Some(setup) = self.active_setups.remove(&(&*rp_desc as *const _)) {
+            self.configured_descriptors.push_back((setup, rp_desc));
+            if self.configured_descriptors.len() <= MAX_CONFIGURED_DESCRIPTORS {
+                return;
+            }
+            rp_desc = self.configured_descriptors.pop_front().unwrap().1;
+        }
         rp_desc.set_render_target_array_length(0);
         for i in 0..MAX_COLOR_ATTACHMENTS {
             let desc = rp_desc.color_attachments().object_at(i as _).unwrap();
@@ -216,6 +392,12 @@ impl RenderPassDescriptorCache {
             desc.set_level(0);
             desc.set_slice(0);
         }
+        if get_tile_layout(&rp_desc) != native::TileLayout::default() {
+            set_tile_layout(&rp_desc, &native::TileLayout::default());
+        }
+        if !get_sample_positions(&rp_desc).is_empty() {
+            set_sample_positions(&rp_desc, &[]);
+        }
         self.spare_descriptors.push(rp_desc);
     }
 }
@@ -223,6 +405,8 @@ impl RenderPassDescriptorCache {
 #[derive(Debug)]
 struct PoolShared {
     online_recording: OnlineRecording,
+    pass_timing: bool,
+    command_stats: bool,
     render_pass_descriptors: Mutex<RenderPassDescriptorCache>,
     #[cfg(feature = "dispatch")]
     dispatch_queue: Option<NoDebug<dispatch::Queue>>,
@@ -241,7 +425,12 @@ unsafe impl Send for CommandPool {}
 unsafe impl Sync for CommandPool {}
 
 impl CommandPool {
-    pub(crate) fn new(shared: &Arc<Shared>, online_recording: OnlineRecording) -> Self {
+    pub(crate) fn new(
+        shared: &Arc<Shared>,
+        online_recording: OnlineRecording,
+        pass_timing: bool,
+        command_stats: bool,
+    ) -> Self {
         let pool_shared = PoolShared {
             #[cfg(feature = "dispatch")]
             dispatch_queue: match online_recording {
@@ -251,6 +440,8 @@ impl CommandPool {
                 }
             },
             online_recording,
+            pass_timing,
+            command_stats,
             render_pass_descriptors: Mutex::new(RenderPassDescriptorCache::default()),
         };
         CommandPool {
@@ -268,9 +459,28 @@ pub struct CommandBuffer {
     inner: CommandBufferInnerPtr,
     state: State,
     temp: Temp,
+    stats: Option<CommandBufferStats>,
     pub name: String,
 }
 
+/// Counters of the commands recorded into a command buffer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CommandBufferStats {
+    /// Number of draw calls, including every draw of the indirect ones.
+    pub draws: u32,
+    /// Number of compute dispatches.
+    pub dispatches: u32,
+    /// Number of native render, compute, and blit encoders that were started.
+    pub encoder_switches: u32,
+    /// Number of graphics and compute pipeline binds.
+    pub pipeline_binds: u32,
+    /// Number of pipeline binds and dynamic state changes that didn't change anything.
+    pub redundant_state_changes: u32,
+    /// Number of autorelease pools drained while recording, each costing a few
+    /// Objective-C runtime calls.
+    pub autorelease_pools: u32,
+}
+
 unsafe impl Send for CommandBuffer {}
 unsafe impl Sync for CommandBuffer {}
 
@@ -288,8 +498,36 @@ type VertexBufferMaybeVec = Vec<Option<(pso::VertexBufferDesc, pso::ElemOffset)>
 struct RenderPipelineState {
     raw: metal::RenderPipelineState,
     ds_desc: pso::DepthStencilDesc,
+    dynamic_states: pso::DynamicStates,
     vertex_buffers: VertexBufferMaybeVec,
+    vertex_input: Option<Arc<native::VertexInputVariants>>,
     formats: native::SubpassFormats,
+    writes_storage: bool,
+}
+
+/// Values of the pipeline states set with commands,
+/// applied over the bound pipeline for the states it leaves dynamic.
+#[derive(Debug)]
+struct DynamicStateValues {
+    cull_mode: metal::MTLCullMode,
+    front_winding: metal::MTLWinding,
+    primitive_type: MTLPrimitiveType,
+    depth_test: Option<pso::DepthTest>,
+    stencil_faces: pso::Sided<pso::StencilFace>,
+    vertex_input: Option<native::VertexInputKey>,
+}
+
+impl Default for DynamicStateValues {
+    fn default() -> Self {
+        DynamicStateValues {
+            cull_mode: metal::MTLCullMode::None,
+            front_winding: metal::MTLWinding::CounterClockwise,
+            primitive_type: MTLPrimitiveType::Triangle,
+            depth_test: None,
+            stencil_faces: pso::Sided::default(),
+            vertex_input: None,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -299,12 +537,133 @@ struct SubpassInfo {
     formats: native::SubpassFormats,
     operations: native::SubpassData<native::AttachmentOps>,
     sample_count: i::NumSamples,
+    depth_resolve: Option<DepthResolve>,
+    /// Continue the active render encoder, ignoring the descriptor.
+    merged: bool,
+}
+
+/// Depth resolve that is done with a draw after the end of the subpass,
+/// for the filters that the render pass can't apply natively.
+#[derive(Debug)]
+struct DepthResolve {
+    source: metal::Texture,
+    destination: metal::Texture,
+    aspects: Aspects,
+    mode: ResolveMode,
 }
 
 #[derive(Debug, Default)]
 struct DescriptorSetInfo {
-    graphics_resources: Vec<(ResourcePtr, metal::MTLResourceUsage)>,
-    compute_resources: Vec<(ResourcePtr, metal::MTLResourceUsage)>,
+    graphics_resources: ResidentResources,
+    compute_resources: ResidentResources,
+}
+
+/// Resources referenced by an argument buffer, grouped by their usage,
+/// so that every group is made resident with a single call.
+#[derive(Clone, Debug, Default)]
+struct ResidentResources {
+    resources: Vec<ResourcePtr>,
+    groups: Vec<(metal::MTLResourceUsage, Range<usize>)>,
+    /// Pool range of an update-after-bind set, read when the commands are encoded.
+    live: Option<(
+        Arc<RwLock<native::DescriptorArgumentPoolInner>>,
+        Range<native::PoolResourceIndex>,
+    )>,
+}
+
+impl ResidentResources {
+    fn clear(&mut self) {
+        self.resources.clear();
+        self.groups.clear();
+        self.live = None;
+    }
+
+    fn is_empty(&self) -> bool {
+        self.resources.is_empty() && self.live.is_none()
+    }
+
+    fn track(
+        &mut self,
+        pool: &Arc<RwLock<native::DescriptorArgumentPoolInner>>,
+        range: Range<native::PoolResourceIndex>,
+    ) {
+        self.clear();
+        self.live = Some((Arc::clone(pool), range));
+    }
+
+    fn fill(&mut self, used: &[native::UsedResource]) {
+        self.clear();
+        let mut sorted = used
+            .iter()
+            .filter_map(|ur| ptr::NonNull::new(ur.ptr).map(|res| (ur.usage, res)))
+            .collect::<Vec<_>>();
+        sorted.sort_by_key(|&(usage, _)| usage.bits());
+
+        for (usage, resource) in sorted {
+            match self.groups.last_mut() {
+                Some(&mut (last_usage, ref mut range)) if last_usage == usage => range.end += 1,
+                _ => {
+                    let start = self.resources.len();
+                    self.groups.push((usage, start..start + 1));
+                }
+            }
+            self.resources.push(resource);
+        }
+    }
+
+    fn render_commands(&self) -> impl Iterator<Item = soft::RenderCommand<&soft::Ref>> {
+        self.groups
+            .iter()
+            .map(
+                move |&(usage, ref range)| soft::RenderCommand::UseResources {
+                    resources: &self.resources[range.clone()],
+                    usage,
+                },
+            )
+            .chain(self.live.iter().map(|&(ref pool, ref range)| {
+                soft::RenderCommand::UseArgumentResources {
+                    pool: Arc::clone(pool),
+                    range: range.clone(),
+                }
+            }))
+    }
+
+    fn compute_commands(&self) -> impl Iterator<Item = soft::ComputeCommand<&soft::Ref>> {
+        self.groups
+            .iter()
+            .map(
+                move |&(usage, ref range)| soft::ComputeCommand::UseResources {
+                    resources: &self.resources[range.clone()],
+                    usage,
+                },
+            )
+            .chain(self.live.iter().map(|&(ref pool, ref range)| {
+                soft::ComputeCommand::UseArgumentResources {
+                    pool: Arc::clone(pool),
+                    range: range.clone(),
+                }
+            }))
+    }
+
+    /// Declare the usage of the resources currently written into a pool range.
+    fn use_live(
+        encoder: &metal::CommandEncoderRef,
+        pool: &RwLock<native::DescriptorArgumentPoolInner>,
+        range: &Range<native::PoolResourceIndex>,
+    ) {
+        let mut resident = ResidentResources::default();
+        resident.fill(&pool.read().resources[range.start as usize..range.end as usize]);
+        for &(usage, ref group) in resident.groups.iter() {
+            let resources = &resident.resources[group.clone()];
+            let () = unsafe {
+                msg_send![encoder,
+                    useResources: resources.as_ptr()
+                    count: resources.len() as NSUInteger
+                    usage: usage.bits()
+                ]
+            };
+        }
+    }
 }
 
 #[derive(Debug, Default)]
@@ -341,20 +700,31 @@ struct State {
     render_pso_is_compatible: bool,
     compute_pso: Option<metal::ComputePipelineState>,
     work_group_size: MTLSize,
+    /// Buffer slot of the base workgroup of the bound compute pipeline.
+    compute_base_slot: Option<ResourceIndex>,
     primitive_type: MTLPrimitiveType,
     rasterizer_state: Option<native::RasterizerState>,
     depth_bias: pso::DepthBias,
     stencil: native::StencilState<pso::StencilValue>,
+    dynamic: DynamicStateValues,
     push_constants: Vec<u32>,
+    /// Location of the push constants in the ring, if they are too large to be inlined.
+    push_constants_spill: Option<(BufferPtr, buffer::Offset)>,
+    push_constant_ring: PushConstantRing,
     visibility_query: (metal::MTLVisibilityResultMode, buffer::Offset),
     target: TargetState,
     pending_subpasses: Vec<SubpassInfo>,
+    active_depth_resolve: Option<DepthResolve>,
+    tile_layout: Option<native::TileLayout>,
+    sample_positions: SamplePositions,
 
     // --------  Metal states --------- //
     resources_vs: StageResources,
     resources_ps: StageResources,
     resources_cs: StageResources,
     descriptor_sets: ArrayVec<[DescriptorSetInfo; MAX_BOUND_DESCRIPTOR_SETS]>,
+    /// Buffers that shaders can reach through their device addresses.
+    addressed_resources: ResidentResources,
     index_buffer: Option<IndexBuffer<BufferPtr>>,
     vertex_buffers: Vec<Option<(BufferPtr, u64)>>,
     active_depth_stencil_desc: pso::DepthStencilDesc,
@@ -378,8 +748,14 @@ impl State {
             read_masks: pso::Sided::new(!0),
             write_masks: pso::Sided::new(!0),
         };
+        self.dynamic = DynamicStateValues::default();
         self.push_constants.clear();
+        self.push_constants_spill = None;
+        self.push_constant_ring.rewind();
         self.pending_subpasses.clear();
+        self.active_depth_resolve = None;
+        self.tile_layout = None;
+        self.sample_positions.clear();
         self.resources_vs.clear();
         self.resources_ps.clear();
         self.resources_cs.clear();
@@ -387,6 +763,7 @@ impl State {
             ds.graphics_resources.clear();
             ds.compute_resources.clear();
         }
+        self.addressed_resources.clear();
         self.index_buffer = None;
         self.vertex_buffers.clear();
 
@@ -467,7 +844,6 @@ impl State {
 
         let render_resources = iter::once(&self.resources_vs).chain(iter::once(&self.resources_ps));
         let temp_sizes = iter::once(temp_sizes_vs).chain(iter::once(temp_sizes_ps));
-        let push_constants = self.push_constants.as_slice();
         let com_resources = [naga::ShaderStage::Vertex, naga::ShaderStage::Fragment]
             .iter()
             .zip(render_resources)
@@ -488,14 +864,9 @@ impl State {
                     index: 0,
                     samplers: &resources.samplers[..],
                 };
-                let com_push_constants =
-                    resources
-                        .push_constants
-                        .map(|pc| soft::RenderCommand::BindBufferData {
-                            stage,
-                            index: pc.buffer_index as _,
-                            words: &push_constants[..pc.count as usize],
-                        });
+                let com_push_constants = resources
+                    .push_constants
+                    .map(|pc| self.make_render_push_constants(stage, pc));
                 let com_sizes_buffer =
                     self.make_sizes_buffer_update(stage, temp_sizes)
                         .map(move |index| soft::RenderCommand::BindBufferData {
@@ -509,11 +880,11 @@ impl State {
                     .chain(com_push_constants)
                     .chain(com_sizes_buffer)
             });
-        let com_used_resources = self.descriptor_sets.iter().flat_map(|ds| {
-            ds.graphics_resources
-                .iter()
-                .map(|&(resource, usage)| soft::RenderCommand::UseResource { resource, usage })
-        });
+        let com_used_resources = self
+            .descriptor_sets
+            .iter()
+            .flat_map(|ds| ds.graphics_resources.render_commands())
+            .chain(self.addressed_resources.render_commands());
 
         com_vp
             .into_iter()
@@ -549,24 +920,20 @@ impl State {
             index: 0,
             samplers: &resources.samplers[..],
         };
-        let com_push_constants =
-            resources
-                .push_constants
-                .map(|pc| soft::ComputeCommand::BindBufferData {
-                    index: pc.buffer_index as _,
-                    words: &self.push_constants[..pc.count as usize],
-                });
+        let com_push_constants = resources
+            .push_constants
+            .map(|pc| self.make_compute_push_constants(pc));
         let com_sizes_buffer = self
             .make_sizes_buffer_update(naga::ShaderStage::Compute, temp_sizes_cs)
             .map(move |index| soft::ComputeCommand::BindBufferData {
                 index,
                 words: temp_sizes_cs.as_slice(),
             });
-        let com_used_resources = self.descriptor_sets.iter().flat_map(|ds| {
-            ds.compute_resources
-                .iter()
-                .map(|&(resource, usage)| soft::ComputeCommand::UseResource { resource, usage })
-        });
+        let com_used_resources = self
+            .descriptor_sets
+            .iter()
+            .flat_map(|ds| ds.compute_resources.compute_commands())
+            .chain(self.addressed_resources.compute_commands());
 
         com_pso
             .into_iter()
@@ -622,7 +989,21 @@ impl State {
     #[must_use]
     fn build_depth_stencil(&mut self) -> Option<pso::DepthStencilDesc> {
         let mut desc = match self.render_pso {
-            Some(ref rp) => rp.ds_desc,
+            Some(ref rp) => {
+                let mut desc = rp.ds_desc;
+                if rp.dynamic_states.contains(pso::DynamicStates::DEPTH_TEST) {
+                    desc.depth = self.dynamic.depth_test;
+                }
+                if rp
+                    .dynamic_states
+                    .contains(pso::DynamicStates::STENCIL_FACES)
+                {
+                    if let Some(ref mut stencil) = desc.stencil {
+                        stencil.faces = self.dynamic.stencil_faces;
+                    }
+                }
+                desc
+            }
             None => return None,
         };
 
@@ -651,6 +1032,22 @@ impl State {
         }
     }
 
+    /// Override the rasterizer state and primitive type of a newly bound pipeline
+    /// with the values of its dynamic states.
+    fn apply_dynamic_states(&mut self, states: pso::DynamicStates) {
+        if let Some(ref mut rs) = self.rasterizer_state {
+            if states.contains(pso::DynamicStates::CULL_FACE) {
+                rs.cull_mode = self.dynamic.cull_mode;
+            }
+            if states.contains(pso::DynamicStates::FRONT_FACE) {
+                rs.front_winding = self.dynamic.front_winding;
+            }
+        }
+        if states.contains(pso::DynamicStates::PRIMITIVE_TOPOLOGY) {
+            self.primitive_type = self.dynamic.primitive_type;
+        }
+    }
+
     fn set_depth_bias<'a>(
         &mut self,
         depth_bias: &pso::DepthBias,
@@ -664,11 +1061,7 @@ impl State {
         pc: native::PushConstantInfo,
     ) -> soft::RenderCommand<&soft::Ref> {
         self.resources_vs.push_constants = Some(pc);
-        soft::RenderCommand::BindBufferData {
-            stage: naga::ShaderStage::Vertex,
-            index: pc.buffer_index,
-            words: &self.push_constants[..pc.count as usize],
-        }
+        self.make_render_push_constants(naga::ShaderStage::Vertex, pc)
     }
 
     fn push_ps_constants(
@@ -676,11 +1069,7 @@ impl State {
         pc: native::PushConstantInfo,
     ) -> soft::RenderCommand<&soft::Ref> {
         self.resources_ps.push_constants = Some(pc);
-        soft::RenderCommand::BindBufferData {
-            stage: naga::ShaderStage::Fragment,
-            index: pc.buffer_index,
-            words: &self.push_constants[..pc.count as usize],
-        }
+        self.make_render_push_constants(naga::ShaderStage::Fragment, pc)
     }
 
     fn push_cs_constants(
@@ -688,9 +1077,43 @@ impl State {
         pc: native::PushConstantInfo,
     ) -> soft::ComputeCommand<&soft::Ref> {
         self.resources_cs.push_constants = Some(pc);
-        soft::ComputeCommand::BindBufferData {
-            index: pc.buffer_index,
-            words: &self.push_constants[..pc.count as usize],
+        self.make_compute_push_constants(pc)
+    }
+
+    fn make_render_push_constants(
+        &self,
+        stage: naga::ShaderStage,
+        pc: native::PushConstantInfo,
+    ) -> soft::RenderCommand<&soft::Ref> {
+        match self.push_constants_spill {
+            Some((buffer, offset)) => soft::RenderCommand::BindBuffer {
+                stage,
+                index: pc.buffer_index,
+                buffer,
+                offset,
+            },
+            None => soft::RenderCommand::BindBufferData {
+                stage,
+                index: pc.buffer_index,
+                words: &self.push_constants[..pc.count as usize],
+            },
+        }
+    }
+
+    fn make_compute_push_constants(
+        &self,
+        pc: native::PushConstantInfo,
+    ) -> soft::ComputeCommand<&soft::Ref> {
+        match self.push_constants_spill {
+            Some((buffer, offset)) => soft::ComputeCommand::BindBuffer {
+                index: pc.buffer_index,
+                buffer,
+                offset,
+            },
+            None => soft::ComputeCommand::BindBufferData {
+                index: pc.buffer_index,
+                words: &self.push_constants[..pc.count as usize],
+            },
         }
     }
 
@@ -755,7 +1178,13 @@ impl State {
         soft::RenderCommand::SetBlendColor(*color)
     }
 
-    fn update_push_constants(&mut self, offset: u32, constants: &[u32], total: u32) {
+    fn update_push_constants(
+        &mut self,
+        device: &metal::DeviceRef,
+        offset: u32,
+        constants: &[u32],
+        total: u32,
+    ) {
         assert_eq!(offset % WORD_ALIGNMENT as u32, 0);
         let offset = (offset / WORD_ALIGNMENT as u32) as usize;
         let data = &mut self.push_constants;
@@ -763,6 +1192,13 @@ impl State {
             data.resize(total as usize, 0);
         }
         data[offset..offset + constants.len()].copy_from_slice(constants);
+        // Anything bigger than the inline limit is copied into the ring,
+        // and bound as a regular buffer until the next update.
+        self.push_constants_spill = if data.len() * WORD_SIZE > PUSH_CONSTANTS_INLINE_LIMIT {
+            Some(self.push_constant_ring.upload(device, data))
+        } else {
+            None
+        };
     }
 
     fn make_sizes_buffer_update(
@@ -867,6 +1303,54 @@ impl State {
     }
 }
 
+/// Storage of the push constants that don't fit the inline limit.
+///
+/// The blocks are sub-allocated linearly and the buffers are reused by the
+/// next recording, since a command buffer can't be reset while it's pending.
+#[derive(Debug, Default)]
+struct PushConstantRing {
+    chunks: Vec<metal::Buffer>,
+    active: usize,
+    offset: buffer::Offset,
+}
+
+impl PushConstantRing {
+    fn upload(&mut self, device: &metal::DeviceRef, words: &[u32]) -> (BufferPtr, buffer::Offset) {
+        let size = (words.len() * WORD_SIZE) as buffer::Offset;
+        debug_assert!(size <= PUSH_CONSTANT_RING_CHUNK);
+        if self.offset + size > PUSH_CONSTANT_RING_CHUNK {
+            self.active += 1;
+            self.offset = 0;
+        }
+        if self.active == self.chunks.len() {
+            let raw = device.new_buffer(
+                PUSH_CONSTANT_RING_CHUNK,
+                metal::MTLResourceOptions::StorageModeShared
+                    | metal::MTLResourceOptions::CPUCacheModeWriteCombined,
+            );
+            if INTERNAL_LABELS {
+                raw.set_label("push_constants");
+            }
+            self.chunks.push(raw);
+        }
+
+        let raw = &self.chunks[self.active];
+        let offset = self.offset;
+        unsafe {
+            let dst = (raw.contents() as *mut u8).add(offset as usize);
+            ptr::copy_nonoverlapping(words.as_ptr(), dst as *mut u32, words.len());
+        }
+        self.offset = (offset + size + PUSH_CONSTANT_RING_ALIGNMENT - 1)
+            & !(PUSH_CONSTANT_RING_ALIGNMENT - 1);
+        (AsNative::from(raw.as_ref()), offset)
+    }
+
+    fn rewind(&mut self) {
+        self.active = 0;
+        self.offset = 0;
+    }
+}
+
 #[derive(Debug)]
 struct StageResources {
     buffers: Vec<Option<BufferPtr>>,
@@ -999,6 +1483,163 @@ impl EncodePass {
     }
 }
 
+/// Check if the render pass described by `next` can continue the encoder of `prev`.
+///
+/// This is the case when both passes render into the same attachments,
+/// `next` doesn't clear any of them, and `prev` doesn't resolve any,
+/// since the resolve would only happen after the commands of `next`.
+fn can_continue_render(
+    prev: &metal::RenderPassDescriptorRef,
+    next: &metal::RenderPassDescriptorRef,
+) -> bool {
+    fn compatible(
+        prev: &metal::RenderPassAttachmentDescriptorRef,
+        next: &metal::RenderPassAttachmentDescriptorRef,
+    ) -> bool {
+        let texture = |at: &metal::RenderPassAttachmentDescriptorRef| {
+            at.texture().map(|t| t as *const metal::TextureRef)
+        };
+        texture(prev) == texture(next)
+            && (next.texture().is_none()
+                || (prev.level() == next.level()
+                    && prev.slice() == next.slice()
+                    && prev.depth_plane() == next.depth_plane()
+                    && prev.resolve_texture().is_none()
+                    && next.load_action() != metal::MTLLoadAction::Clear))
+    }
+
+    let array_length = |desc: &metal::RenderPassDescriptorRef| -> NSUInteger {
+        unsafe {
+            let supported: BOOL =
+                msg_send![desc, respondsToSelector: sel!(renderTargetArrayLength)];
+            if supported == NO {
+                return 0;
+            }
+            msg_send![desc, renderTargetArrayLength]
+        }
+    };
+    let (prev_depth, next_depth) = (prev.depth_attachment(), next.depth_attachment());
+    let (prev_stencil, next_stencil) = (prev.stencil_attachment(), next.stencil_attachment());
+    array_length(prev) == array_length(next)
+        && get_tile_layout(prev) == get_tile_layout(next)
+        && get_sample_positions(prev) == get_sample_positions(next)
+        && (0..MAX_COLOR_ATTACHMENTS).all(|i| {
+            let prev_color = prev.color_attachments().object_at(i as _).unwrap();
+            let next_color = next.color_attachments().object_at(i as _).unwrap();
+            compatible(prev_color, next_color)
+        })
+        && match (prev_depth, next_depth) {
+            // the depth resolve filter is not carried over
+            (Some(p), Some(n)) => compatible(p, n) && n.resolve_texture().is_none(),
+            (p, n) => p.is_none() && n.is_none(),
+        }
+        && match (prev_stencil, next_stencil) {
+            (Some(p), Some(n)) => compatible(p, n) && n.resolve_texture().is_none(),
+            (p, n) => p.is_none() && n.is_none(),
+        }
+}
+
+/// Read the tile memory layout of a render pass descriptor.
+fn get_tile_layout(desc: &metal::RenderPassDescriptorRef) -> native::TileLayout {
+    unsafe {
+        let supported: BOOL = msg_send![desc, respondsToSelector: sel!(tileWidth)];
+        if supported == NO {
+            return native::TileLayout::default();
+        }
+        let width: NSUInteger = msg_send![desc, tileWidth];
+        let height: NSUInteger = msg_send![desc, tileHeight];
+        let imageblock_sample_length: NSUInteger = msg_send![desc, imageblockSampleLength];
+        let threadgroup_memory_length: NSUInteger = msg_send![desc, threadgroupMemoryLength];
+        native::TileLayout {
+            size: if width != 0 && height != 0 {
+                Some((width as u32, height as u32))
+            } else {
+                None
+            },
+            imageblock_sample_length: imageblock_sample_length as u32,
+            threadgroup_memory_length: threadgroup_memory_length as u32,
+        }
+    }
+}
+
+/// Set the tile memory layout of a render pass descriptor,
+/// which requires tile shading support.
+fn set_tile_layout(desc: &metal::RenderPassDescriptorRef, layout: &native::TileLayout) {
+    let (width, height) = layout.size.unwrap_or((0, 0));
+    unsafe {
+        let () = msg_send![desc, setTileWidth: width as NSUInteger];
+        let () = msg_send![desc, setTileHeight: height as NSUInteger];
+        let () = msg_send![desc,
+            setImageblockSampleLength: layout.imageblock_sample_length as NSUInteger
+        ];
+        let () = msg_send![desc,
+            setThreadgroupMemoryLength: layout.threadgroup_memory_length as NSUInteger
+        ];
+    }
+}
+
+/// Read the custom sample positions of a render pass descriptor,
+/// which are empty for the standard positions.
+fn get_sample_positions(desc: &metal::RenderPassDescriptorRef) -> SamplePositions {
+    let mut positions = SamplePositions::new();
+    unsafe {
+        let supported: BOOL = msg_send![desc, respondsToSelector: sel!(getSamplePositions:count:)];
+        if supported == NO {
+            return positions;
+        }
+        let mut raw = [[0f32; 2]; MAX_SAMPLE_POSITIONS];
+        let count: NSUInteger = msg_send![desc,
+            getSamplePositions: raw.as_mut_ptr()
+            count: MAX_SAMPLE_POSITIONS as NSUInteger
+        ];
+        for &[x, y] in raw.iter().take(count as usize) {
+            positions.push(pso::SampleLocation { x, y });
+        }
+    }
+    positions
+}
+
+/// Set the custom sample positions of a render pass descriptor,
+/// which requires programmable sample positions support.
+fn set_sample_positions(desc: &metal::RenderPassDescriptorRef, positions: &[pso::SampleLocation]) {
+    let raw = positions
+        .iter()
+        .map(|location| [location.x, location.y])
+        .collect::<ArrayVec<[[f32; 2]; MAX_SAMPLE_POSITIONS]>>();
+    unsafe {
+        let () = msg_send![desc,
+            setSamplePositions: raw.as_ptr()
+            count: raw.len() as NSUInteger
+        ];
+    }
+}
+
+/// Make `prev` store its attachments the way `next` does, after the encoder
+/// of `prev` is continued with the commands of `next`.
+fn adopt_render_stores(
+    prev: &metal::RenderPassDescriptorRef,
+    next: &metal::RenderPassDescriptorRef,
+) {
+    for i in 0..MAX_COLOR_ATTACHMENTS {
+        let prev_color = prev.color_attachments().object_at(i as _).unwrap();
+        let next_color = next.color_attachments().object_at(i as _).unwrap();
+        if next_color.texture().is_some() {
+            prev_color.set_resolve_texture(next_color.resolve_texture());
+            prev_color.set_store_action(next_color.store_action());
+        }
+    }
+    if let (Some(p), Some(n)) = (prev.depth_attachment(), next.depth_attachment()) {
+        if n.texture().is_some() {
+            p.set_store_action(n.store_action());
+        }
+    }
+    if let (Some(p), Some(n)) = (prev.stencil_attachment(), next.stencil_attachment()) {
+        if n.texture().is_some() {
+            p.set_store_action(n.store_action());
+        }
+    }
+}
+
 #[derive(Debug, Default)]
 struct Journal {
     resources: soft::Own,
@@ -1006,10 +1647,15 @@ struct Journal {
     render_commands: Vec<soft::RenderCommand<soft::Own>>,
     compute_commands: Vec<soft::ComputeCommand<soft::Own>>,
     blit_commands: Vec<soft::BlitCommand>,
+    /// The last render pass can't be continued by the next one, because a barrier
+    /// or an event is recorded after it, or it writes storage resources that
+    /// are only visible to the next pass once its encoder ends.
+    sealed: bool,
 }
 
 impl Journal {
     fn clear(&mut self, pool_shared: &PoolShared) {
+        self.sealed = false;
         self.resources.clear();
         self.render_commands.clear();
         self.compute_commands.clear();
@@ -1038,6 +1684,29 @@ impl Journal {
         };
     }
 
+    /// Continue the last recorded pass with the render pass described by `descriptor`,
+    /// if nothing was recorded after it and the pass can be fused. This saves storing
+    /// the attachments to memory and loading them back, which is expensive on tile-based GPUs.
+    fn continue_render(
+        &mut self,
+        descriptor: &metal::RenderPassDescriptorRef,
+        rp_desc_cache: &mut RenderPassDescriptorCache,
+    ) -> bool {
+        match self.passes.last_mut() {
+            Some(&mut (soft::Pass::Render(ref prev), ref mut range, _))
+                if !self.sealed
+                    && range.end == self.render_commands.len()
+                    && can_continue_render(prev, descriptor) =>
+            {
+                rp_desc_cache.forget_setup(prev);
+                adopt_render_stores(prev, descriptor);
+                range.end = 0;
+                true
+            }
+            _ => false,
+        }
+    }
+
     fn record(&self, command_buf: &metal::CommandBufferRef) {
         profiling::scope!("Journal::record");
         for (ref pass, ref range, ref label) in &self.passes {
@@ -1119,6 +1788,8 @@ impl Journal {
         self.blit_commands.extend_from_slice(&other.blit_commands);
 
         self.resources.extend(&other.resources);
+        // The descriptors of the passes are shared with `other`, so they can't be continued.
+        self.sealed |= other.sealed || !inherit_pass;
     }
 }
 
@@ -1130,6 +1801,7 @@ enum CommandSink {
         encoder_state: EncoderState,
         num_passes: usize,
         label: String,
+        timer: Option<PassTimer>,
     },
     Deferred {
         is_encoding: bool,
@@ -1144,6 +1816,7 @@ enum CommandSink {
         pool_shared: Arc<PoolShared>,
         token: Token,
         pass: Option<EncodePass>,
+        num_passes: usize,
         capacity: Capacity,
         label: String,
     },
@@ -1237,6 +1910,15 @@ impl<'a> PreCompute<'a> {
 }
 
 impl CommandSink {
+    fn num_passes(&self) -> usize {
+        match *self {
+            CommandSink::Immediate { num_passes, .. } => num_passes,
+            CommandSink::Deferred { ref journal, .. } => journal.passes.len(),
+            #[cfg(feature = "dispatch")]
+            CommandSink::Remote { num_passes, .. } => num_passes,
+        }
+    }
+
     fn label(&mut self, label: &str) -> &Self {
         match self {
             CommandSink::Immediate { label: l, .. } | CommandSink::Deferred { label: l, .. } => {
@@ -1281,6 +1963,16 @@ impl CommandSink {
         }
     }
 
+    /// Prevent the last render pass from being continued by the next one.
+    fn seal_render(&mut self) {
+        if let CommandSink::Deferred {
+            ref mut journal, ..
+        } = *self
+        {
+            journal.sealed = true;
+        }
+    }
+
     /// Start issuing pre-render commands. Those can be rejected, so the caller is responsible
     /// for updating the state cache accordingly, so that it's set upon the start of a next pass.
     fn pre_render(&mut self) -> PreRender {
@@ -1323,10 +2015,17 @@ impl CommandSink {
                 ref mut encoder_state,
                 ref mut num_passes,
                 ref label,
+                ref mut timer,
                 ..
             } => {
                 *num_passes += 1;
+                if let Some(ref mut timer) = *timer {
+                    timer.attach_render(&descriptor, label);
+                }
                 let encoder = cmd_buffer.new_render_command_encoder(&descriptor);
+                if let Some(ref timer) = *timer {
+                    timer.detach_render(&descriptor);
+                }
                 pool_shared.render_pass_descriptors.lock().free(descriptor);
                 if !label.is_empty() {
                     encoder.set_label(label);
@@ -1343,20 +2042,28 @@ impl CommandSink {
             } => {
                 assert!(!is_inheriting);
                 *is_encoding = true;
-                journal.passes.alloc().init((
-                    soft::Pass::Render(descriptor),
-                    journal.render_commands.len()..0,
-                    label.clone(),
-                ));
+                let mut rp_desc_cache = pool_shared.render_pass_descriptors.lock();
+                if journal.continue_render(&descriptor, &mut rp_desc_cache) {
+                    rp_desc_cache.free(descriptor);
+                } else {
+                    journal.sealed = false;
+                    journal.passes.alloc().init((
+                        soft::Pass::Render(descriptor),
+                        journal.render_commands.len()..0,
+                        label.clone(),
+                    ));
+                }
                 PreRender::Deferred(&mut journal.resources, &mut journal.render_commands)
             }
             #[cfg(feature = "dispatch")]
             CommandSink::Remote {
                 ref mut pass,
+                ref mut num_passes,
                 ref capacity,
                 ref label,
                 ..
             } => {
+                *num_passes += 1;
                 let list = Vec::with_capacity(capacity.render);
                 *pass = Some(EncodePass::Render(
                     list,
@@ -1415,11 +2122,16 @@ impl CommandSink {
                 ref cmd_buffer,
                 ref mut encoder_state,
                 ref mut num_passes,
+                ref label,
+                ref mut timer,
                 ..
             } => {
                 *num_passes += 1;
                 encoder_state.end();
-                let encoder = cmd_buffer.new_blit_command_encoder();
+                let encoder = match *timer {
+                    Some(ref mut timer) => timer.new_blit_encoder(cmd_buffer, label),
+                    None => cmd_buffer.new_blit_command_encoder(),
+                };
                 *encoder_state = EncoderState::Blit(encoder.to_owned());
                 PreBlit::Immediate(encoder)
             }
@@ -1453,6 +2165,7 @@ impl CommandSink {
                 queue: NoDebug(ref queue),
                 ref cmd_buffer,
                 ref mut pass,
+                ref mut num_passes,
                 ref mut capacity,
                 ref label,
                 ref pool_shared,
@@ -1462,6 +2175,7 @@ impl CommandSink {
                     pass.update(capacity);
                     pass.schedule(queue, cmd_buffer, pool_shared);
                 }
+                *num_passes += 1;
                 let list = Vec::with_capacity(capacity.blit);
                 *pass = Some(EncodePass::Blit(list, label.clone()));
                 match *pass {
@@ -1523,11 +2237,16 @@ impl CommandSink {
                 ref cmd_buffer,
                 ref mut encoder_state,
                 ref mut num_passes,
+                ref label,
+                ref mut timer,
                 ..
             } => {
                 *num_passes += 1;
                 encoder_state.end();
-                let encoder = cmd_buffer.new_compute_command_encoder();
+                let encoder = match *timer {
+                    Some(ref mut timer) => timer.new_compute_encoder(cmd_buffer, label),
+                    None => cmd_buffer.new_compute_command_encoder(),
+                };
                 *encoder_state = EncoderState::Compute(encoder.to_owned());
                 (PreCompute::Immediate(encoder), true)
             }
@@ -1566,6 +2285,7 @@ impl CommandSink {
                 queue: NoDebug(ref queue),
                 ref cmd_buffer,
                 ref mut pass,
+                ref mut num_passes,
                 ref mut capacity,
                 ref label,
                 ref pool_shared,
@@ -1575,6 +2295,7 @@ impl CommandSink {
                     pass.update(capacity);
                     pass.schedule(queue, cmd_buffer, pool_shared);
                 }
+                *num_passes += 1;
                 let list = Vec::with_capacity(capacity.compute);
                 *pass = Some(EncodePass::Compute(
                     list,
@@ -1615,21 +2336,90 @@ pub struct IndexBuffer<B> {
     stride: buffer::Stride,
 }
 
-/// This is an inner mutable part of the command buffer that is
-/// accessible by the owning command pool for one single reason:
-/// to reset it.
 #[derive(Debug)]
-pub struct CommandBufferInner {
-    sink: Option<CommandSink>,
-    level: com::Level,
-    backup_journal: Option<Journal>,
-    #[cfg(feature = "dispatch")]
+enum PendingWrite {
+    Fill {
+        dst: BufferPtr,
+        range: Range<buffer::Offset>,
+        value: u8,
+    },
+    Update {
+        dst: BufferPtr,
+        dst_offset: buffer::Offset,
+        src_offset: buffer::Offset,
+        size: buffer::Offset,
+    },
+}
+
+/// Buffer fills and updates that are recorded but not encoded yet.
+///
+/// The writes are encoded together in a single blit pass before the next command,
+/// copying the data of all the updates from one staging buffer, instead of
+/// allocating a buffer and switching the encoder for each of them.
+#[derive(Debug)]
+struct PendingWrites {
+    device: metal::Device,
+    data: Vec<u8>,
+    writes: Vec<PendingWrite>,
+}
+
+impl PendingWrites {
+    fn new(device: &metal::DeviceRef) -> Self {
+        PendingWrites {
+            device: device.to_owned(),
+            data: Vec::new(),
+            writes: Vec::new(),
+        }
+    }
+
+    fn fill(&mut self, dst: BufferPtr, range: Range<buffer::Offset>, value: u8) {
+        self.writes.push(PendingWrite::Fill { dst, range, value });
+    }
+
+    /// Add an update of `size` bytes at `dst_offset`, returning the staging data to fill.
+    fn update(
+        &mut self,
+        dst: BufferPtr,
+        dst_offset: buffer::Offset,
+        size: buffer::Offset,
+    ) -> &mut [u8] {
+        let start = (self.data.len() + WORD_SIZE - 1) & !(WORD_SIZE - 1);
+        self.data.resize(start + size as usize, 0);
+        self.writes.push(PendingWrite::Update {
+            dst,
+            dst_offset,
+            src_offset: start as _,
+            size,
+        });
+        &mut self.data[start..]
+    }
+
+    fn clear(&mut self) {
+        self.data.clear();
+        self.writes.clear();
+    }
+}
+
+/// This is an inner mutable part of the command buffer that is
+/// accessible by the owning command pool for one single reason:
+/// to reset it.
+#[derive(Debug)]
+pub struct CommandBufferInner {
+    sink: Option<CommandSink>,
+    level: com::Level,
+    backup_journal: Option<Journal>,
+    #[cfg(feature = "dispatch")]
     backup_capacity: Option<Capacity>,
     retained_buffers: Vec<metal::Buffer>,
     retained_textures: Vec<metal::Texture>,
+    pending_writes: PendingWrites,
     active_visibility_queries: Vec<query::Id>,
     events: Vec<(Arc<AtomicBool>, bool)>,
     host_events: Vec<Arc<AtomicBool>>,
+    /// Mirrors of the texel views used by the commands, refreshed on submission.
+    texel_mirrors: Vec<Arc<native::TexelMirror>>,
+    /// Timer of the passes, kept around between recordings for reuse.
+    pass_timer: Option<PassTimer>,
 }
 
 impl Drop for CommandBufferInner {
@@ -1646,10 +2436,14 @@ impl CommandBufferInner {
             Some(CommandSink::Immediate {
                 token,
                 mut encoder_state,
+                timer,
                 ..
             }) => {
                 encoder_state.end();
                 shared.queue.lock().release(token);
+                if timer.is_some() {
+                    self.pass_timer = timer;
+                }
             }
             Some(CommandSink::Deferred { mut journal, .. }) => {
                 if !release {
@@ -1670,13 +2464,76 @@ impl CommandBufferInner {
         };
         self.retained_buffers.clear();
         self.retained_textures.clear();
+        self.pending_writes.clear();
         self.active_visibility_queries.clear();
         self.events.clear();
+        self.texel_mirrors.clear();
+    }
+
+    fn use_texel_mirrors<I>(&mut self, mirrors: I)
+    where
+        I: IntoIterator<Item = Arc<native::TexelMirror>>,
+    {
+        for mirror in mirrors {
+            if !self.texel_mirrors.iter().any(|m| Arc::ptr_eq(m, &mirror)) {
+                self.texel_mirrors.push(mirror);
+            }
+        }
     }
 
     fn sink(&mut self) -> &mut CommandSink {
+        if !self.pending_writes.writes.is_empty() {
+            self.flush_writes();
+        }
         self.sink.as_mut().unwrap()
     }
+
+    /// Encode the pending buffer writes.
+    fn flush_writes(&mut self) {
+        let PendingWrites {
+            ref device,
+            ref mut data,
+            ref mut writes,
+        } = self.pending_writes;
+
+        let staging = if data.is_empty() {
+            None
+        } else {
+            let raw = device.new_buffer_with_data(
+                data.as_ptr() as _,
+                data.len() as _,
+                metal::MTLResourceOptions::CPUCacheModeWriteCombined,
+            );
+            if INTERNAL_LABELS {
+                raw.set_label("update_buffer");
+            }
+            data.clear();
+            Some(raw)
+        };
+        let src = staging.as_ref().map(|raw| AsNative::from(raw.as_ref()));
+
+        let commands = writes.drain(..).map(|write| match write {
+            PendingWrite::Fill { dst, range, value } => {
+                soft::BlitCommand::FillBuffer { dst, range, value }
+            }
+            PendingWrite::Update {
+                dst,
+                dst_offset,
+                src_offset,
+                size,
+            } => soft::BlitCommand::CopyBuffer {
+                src: src.unwrap(),
+                dst,
+                region: com::BufferCopy {
+                    src: src_offset,
+                    dst: dst_offset,
+                    size,
+                },
+            },
+        });
+        self.sink.as_mut().unwrap().blit_commands(commands);
+        self.retained_buffers.extend(staging);
+    }
 }
 
 #[derive(Debug)]
@@ -1708,7 +2565,11 @@ fn div(a: u32, b: u32) -> u32 {
     (a + b - 1) / b
 }
 
-fn compute_pitches(region: &com::BufferImageCopy, fd: FormatDesc, extent: &MTLSize) -> (u32, u32) {
+pub(crate) fn compute_pitches(
+    region: &com::BufferImageCopy,
+    fd: FormatDesc,
+    extent: &MTLSize,
+) -> (u32, u32) {
     let buffer_width = if region.buffer_width == 0 {
         extent.width as u32
     } else {
@@ -1728,6 +2589,7 @@ fn exec_render<R, C>(encoder: &metal::RenderCommandEncoderRef, command: C, resou
 where
     R: soft::Resources,
     R::Data: Borrow<[u32]>,
+    R::ResourceArray: Borrow<[ResourcePtr]>,
     R::BufferArray: soft::AsSlice<Option<BufferPtr>, R> + soft::AsSlice<buffer::Offset, R>,
     R::TextureArray: soft::AsSlice<Option<TexturePtr>, R>,
     R::SamplerArray: soft::AsSlice<Option<SamplerPtr>, R>,
@@ -1883,8 +2745,24 @@ where
         Cmd::BindPipeline(ref pipeline_state) => {
             encoder.set_render_pipeline_state(pipeline_state.borrow());
         }
-        Cmd::UseResource { resource, usage } => {
-            encoder.use_resource(resource.as_native(), usage);
+        Cmd::UseResources {
+            ref resources,
+            usage,
+        } => {
+            let resources = resources.borrow();
+            let () = unsafe {
+                msg_send![encoder,
+                    useResources: resources.as_ptr()
+                    count: resources.len() as NSUInteger
+                    usage: usage.bits()
+                ]
+            };
+        }
+        Cmd::UseArgumentResources {
+            ref pool,
+            ref range,
+        } => {
+            ResidentResources::use_live(encoder, pool, range);
         }
         Cmd::Draw {
             primitive_type,
@@ -1995,6 +2873,40 @@ where
         Cmd::PopDebugGroup => {
             encoder.pop_debug_group();
         }
+        Cmd::BindTileBuffer {
+            index,
+            buffer,
+            offset,
+        } => {
+            let () = unsafe {
+                msg_send![encoder,
+                    setTileBuffer: buffer.as_native()
+                    offset: offset as NSUInteger
+                    atIndex: index as NSUInteger
+                ]
+            };
+        }
+        Cmd::SetThreadgroupMemoryLength { index, length } => {
+            let () = unsafe {
+                msg_send![encoder,
+                    setThreadgroupMemoryLength: length as NSUInteger
+                    offset: 0 as NSUInteger
+                    atIndex: index as NSUInteger
+                ]
+            };
+        }
+        Cmd::DispatchTile { size } => {
+            let () = unsafe { msg_send![encoder, dispatchThreadsPerTile: size] };
+        }
+        Cmd::SampleCounters { buffer, index } => {
+            let () = unsafe {
+                msg_send![encoder,
+                    sampleCountersInBuffer: buffer.as_ptr()
+                    atSampleIndex: index
+                    withBarrier: YES
+                ]
+            };
+        }
     }
 }
 
@@ -2121,6 +3033,7 @@ fn exec_compute<R, C>(encoder: &metal::ComputeCommandEncoderRef, command: C, res
 where
     R: soft::Resources,
     R::Data: Borrow<[u32]>,
+    R::ResourceArray: Borrow<[ResourcePtr]>,
     R::BufferArray: soft::AsSlice<Option<BufferPtr>, R> + soft::AsSlice<buffer::Offset, R>,
     R::TextureArray: soft::AsSlice<Option<TexturePtr>, R>,
     R::SamplerArray: soft::AsSlice<Option<SamplerPtr>, R>,
@@ -2188,8 +3101,24 @@ where
         Cmd::BindPipeline(ref pipeline) => {
             encoder.set_compute_pipeline_state(pipeline.borrow());
         }
-        Cmd::UseResource { resource, usage } => {
-            encoder.use_resource(resource.as_native(), usage);
+        Cmd::UseResources {
+            ref resources,
+            usage,
+        } => {
+            let resources = resources.borrow();
+            let () = unsafe {
+                msg_send![encoder,
+                    useResources: resources.as_ptr()
+                    count: resources.len() as NSUInteger
+                    usage: usage.bits()
+                ]
+            };
+        }
+        Cmd::UseArgumentResources {
+            ref pool,
+            ref range,
+        } => {
+            ResidentResources::use_live(encoder, pool, range);
         }
         Cmd::Dispatch { wg_size, wg_count } => {
             encoder.dispatch_thread_groups(wg_count, wg_size);
@@ -2227,6 +3156,12 @@ pub struct Queue {
     pub stitch_deferred: bool,
     /// Hack around the Metal System Trace logic that ignores empty command buffers entirely.
     pub insert_dummy_encoders: bool,
+    /// Capture the command buffers of the next submission only, in a scope of this queue.
+    pub capture_next_submission: bool,
+    /// If true, deferred command buffers of submissions that don't signal a fence,
+    /// a semaphore, or an event are recorded into a shared Metal command buffer,
+    /// which is committed by the next signalling submission, presentation, or wait.
+    pub batch_submissions: bool,
 }
 
 unsafe impl Send for Queue {}
@@ -2246,6 +3181,8 @@ impl Queue {
             },
             stitch_deferred: true,
             insert_dummy_encoders: false,
+            capture_next_submission: false,
+            batch_submissions: false,
         }
     }
 
@@ -2256,48 +3193,237 @@ impl Queue {
         }
     }
 
+    /// Refresh the private copies of the texel buffer views on shared memory,
+    /// so that the following command buffers see the latest host writes.
+    fn upload_texel_mirrors(
+        &self,
+        mirrors: &[Arc<native::TexelMirror>],
+        cmd_queue: &mut QueueInner,
+        blocker: &mut QueueBlocker,
+    ) {
+        if mirrors.is_empty() {
+            return;
+        }
+
+        // the batched work is enqueued ahead, so it has to go before the upload
+        cmd_queue.flush_pending();
+        // the mirrors can be destroyed before the upload is done
+        let cmd_buffer = cmd_queue.raw.new_command_buffer();
+        if INTERNAL_LABELS {
+            cmd_buffer.set_label("texel mirrors");
+        }
+        let encoder = cmd_buffer.new_blit_command_encoder();
+        for mirror in mirrors.iter() {
+            encoder.copy_from_buffer(
+                &mirror.source,
+                mirror.offset as NSUInteger,
+                &mirror.raw,
+                0,
+                mirror.size as NSUInteger,
+            );
+        }
+        encoder.end_encoding();
+        blocker.submit_impl(cmd_buffer);
+    }
+
     fn wait<'a, T>(&mut self, wait_semaphores: T)
     where
         T: Iterator<Item = &'a native::Semaphore>,
     {
+        let mut shared_events = Vec::new();
         for sem in wait_semaphores {
             if let Some(ref system) = sem.system {
+                // the semaphore may be signalled by the batched work
+                self.shared.queue.lock().flush_pending();
                 system.wait(!0);
             }
+            if let Some(ref shared_event) = sem.shared_event {
+                shared_events.push(&**shared_event);
+            }
         }
+        self.encode_shared_events(&shared_events, false);
     }
-}
 
-impl hal::queue::Queue<Backend> for Queue {
-    unsafe fn submit<'a, Ic, Iw, Is>(
+    /// Wait for or signal the next values of the shared events on the GPU timeline,
+    /// with a command buffer ordered after all the previous submissions.
+    fn encode_shared_events(&self, shared_events: &[&native::SharedEventPayload], signal: bool) {
+        let values = shared_events
+            .iter()
+            .map(|shared_event| {
+                let counter = if signal {
+                    &shared_event.signaled
+                } else {
+                    &shared_event.waited
+                };
+                (
+                    &*shared_event.raw,
+                    counter.fetch_add(1, Ordering::AcqRel) + 1,
+                )
+            })
+            .collect::<Vec<_>>();
+        self.encode_event_values(&values, signal);
+    }
+
+    /// Wait for or signal the given values of the events on the GPU timeline,
+    /// with a command buffer ordered after all the previous submissions.
+    fn encode_event_values(&self, values: &[(&metal::SharedEventRef, u64)], signal: bool) {
+        if values.is_empty() {
+            return;
+        }
+        autoreleasepool(|| {
+            let mut cmd_queue = self.shared.queue.lock();
+            cmd_queue.flush_pending();
+            let cmd_buffer = cmd_queue.raw.new_command_buffer();
+            if INTERNAL_LABELS {
+                cmd_buffer.set_label(if signal {
+                    "signal shared events"
+                } else {
+                    "wait for shared events"
+                });
+            }
+            for &(event, value) in values {
+                if signal {
+                    cmd_buffer.encode_signal_event(event, value);
+                } else {
+                    cmd_buffer.encode_wait_for_event(event, value);
+                }
+            }
+            self.shared.queue_blocker.lock().submit_impl(cmd_buffer);
+        });
+    }
+
+    /// Present the drawable of the image, signaling the fence once the presenting
+    /// command buffer is completed, and the drawable is no longer referenced by it.
+    /// The identifier of the presentation is tracked by the surface once displayed.
+    unsafe fn present_impl(
         &mut self,
-        command_buffers: Ic,
-        wait_semaphores: Iw,
-        signal_semaphores: Is,
+        surface: &window::Surface,
+        mut image: window::SwapchainImage,
+        wait_semaphore: Option<&mut native::Semaphore>,
         fence: Option<&mut native::Fence>,
-    ) where
-        Ic: Iterator<Item = &'a CommandBuffer>,
-        Iw: Iterator<Item = (&'a native::Semaphore, pso::PipelineStage)>,
-        Is: Iterator<Item = &'a native::Semaphore>,
-    {
-        profiling::scope!("submit");
-        debug!("submitting with fence {:?}", fence);
-        self.wait(wait_semaphores.map(|(s, _)| s));
+        present_id: Option<u64>,
+    ) -> Result<Option<Suboptimal>, PresentError> {
+        profiling::scope!("present");
+        if self.shared.is_removed() {
+            return Err(DeviceLost.into());
+        }
+        if let Some(semaphore) = wait_semaphore {
+            if let Some(ref system) = semaphore.system {
+                system.wait(!0);
+            }
+            if let Some(ref shared_event) = semaphore.shared_event {
+                self.encode_shared_events(&[&**shared_event], false);
+            }
+        }
 
-        let system_semaphores = signal_semaphores
-            .filter_map(|sem| sem.system.clone())
-            .collect::<Vec<_>>();
+        let mut queue = self.shared.queue.lock();
+        queue.flush_pending();
+        autoreleasepool(|| {
+            let command_buffer = queue.raw.new_command_buffer();
+            if INTERNAL_LABELS {
+                command_buffer.set_label("present");
+            }
+            self.record_empty(command_buffer);
+
+            let responds: BOOL = msg_send![
+                image.drawable.as_ref(),
+                respondsToSelector: sel!(addPresentedHandler:)
+            ];
+            let tracker = Arc::clone(&surface.present_tracker);
+            let drawables = Arc::clone(&surface.drawables);
+            let block = ConcreteBlock::new(move |drawable: *mut Object| {
+                // the drawables that are never displayed have no presented time
+                let skipped = responds == YES && {
+                    let time: f64 = msg_send![drawable, presentedTime];
+                    time == 0.0
+                };
+                drawables.presented(skipped);
+                if let Some(present_id) = present_id {
+                    tracker.signal(present_id);
+                }
+            })
+            .copy();
+            if responds == YES {
+                let () = msg_send![
+                    image.drawable.as_ref(),
+                    addPresentedHandler: block.deref() as *const _
+                ];
+            } else {
+                // Approximate the display by the completion of the presenting command buffer
+                let () = msg_send![command_buffer, addCompletedHandler: block.deref() as *const _];
+            }
+            image.presented = true;
+
+            // https://developer.apple.com/documentation/quartzcore/cametallayer/1478157-presentswithtransaction?language=objc
+            if !image.present_with_transaction {
+                command_buffer.present_drawable(&image.drawable);
+            }
+
+            if let Some(fence) = fence {
+                let signal = Arc::new(native::FenceSignal::default());
+                let handler_signal = Arc::clone(&signal);
+                let block = ConcreteBlock::new(move |_cb: *mut ()| {
+                    handler_signal.signal();
+                })
+                .copy();
+                let () = msg_send![command_buffer, addCompletedHandler: block.deref() as *const _];
+                *fence = native::Fence::PendingSubmission {
+                    cmd_buffer: command_buffer.to_owned(),
+                    signal,
+                };
+            }
+
+            command_buffer.commit();
+
+            if image.present_with_transaction {
+                let () = msg_send![command_buffer, waitUntilScheduled];
+                image.drawable.present();
+            }
+        });
+
+        Ok(None)
+    }
 
+    /// Submit the command buffers, signaling the semaphores and the fence
+    /// from the completion handler of the last command buffer.
+    unsafe fn submit_signaling<'a, I>(
+        &mut self,
+        command_buffers: I,
+        system_semaphores: Vec<native::SystemSemaphore>,
+        fence: Option<&mut native::Fence>,
+    ) where
+        I: Iterator<Item = &'a CommandBuffer>,
+    {
         #[allow(unused_mut)]
         let (mut num_immediate, mut num_deferred, mut num_remote) = (0, 0, 0);
         let mut event_commands = Vec::new();
         let do_signal = fence.is_some() || !system_semaphores.is_empty();
+        let capture = mem::replace(&mut self.capture_next_submission, false);
 
         autoreleasepool(|| {
             // for command buffers
             let mut cmd_queue = self.shared.queue.lock();
+            let capture_scope = if capture {
+                let capture_manager = metal::CaptureManager::shared();
+                let scope = capture_manager.new_capture_scope_with_command_queue(&cmd_queue.raw);
+                capture_manager.start_capture_with_scope(&scope);
+                scope.begin_scope();
+                Some(scope)
+            } else {
+                None
+            };
             let mut blocker = self.shared.queue_blocker.lock();
-            let mut deferred_cmd_buffer = None::<&metal::CommandBufferRef>;
+            let command_buffers = command_buffers.collect::<Vec<_>>();
+            let mut mirrors = Vec::<Arc<native::TexelMirror>>::new();
+            for cmd_buffer in command_buffers.iter() {
+                for mirror in cmd_buffer.inner.borrow().texel_mirrors.iter() {
+                    if !mirrors.iter().any(|m| Arc::ptr_eq(m, mirror)) {
+                        mirrors.push(Arc::clone(mirror));
+                    }
+                }
+            }
+            self.upload_texel_mirrors(&mirrors, &mut cmd_queue, &mut blocker);
+            let mut deferred_cmd_buffer = cmd_queue.pending.take();
             let mut release_sinks = Vec::new();
 
             for cmd_buffer in command_buffers {
@@ -2349,12 +3475,19 @@ impl hal::queue::Queue<Backend> for Queue {
                         if num_passes != 0 {
                             // flush the deferred recording, if any
                             if let Some(cb) = deferred_cmd_buffer.take() {
-                                blocker.submit_impl(cb);
+                                blocker.submit_impl(&cb);
                             }
                             blocker.submit_impl(cmd_buffer);
                         }
-                        // destroy the sink with the associated command buffer
-                        release_sinks.extend(inner.sink.take());
+                        // destroy the sink with the associated command buffer,
+                        // keeping the pass timer around for the results
+                        let mut sink = inner.sink.take();
+                        if let Some(CommandSink::Immediate { ref mut timer, .. }) = sink {
+                            if timer.is_some() {
+                                inner.pass_timer = timer.take();
+                            }
+                        }
+                        release_sinks.extend(sink);
                     }
                     Some(CommandSink::Deferred { ref journal, .. }) => {
                         num_deferred += 1;
@@ -2368,13 +3501,13 @@ impl hal::queue::Queue<Backend> for Queue {
                                 if INTERNAL_LABELS {
                                     cmd_buffer.set_label("deferred");
                                 }
-                                cmd_buffer
+                                cmd_buffer.to_owned()
                             });
                             journal.record(&*cmd_buffer);
-                            if self.stitch_deferred {
+                            if self.stitch_deferred || self.batch_submissions {
                                 deferred_cmd_buffer = Some(cmd_buffer);
                             } else {
-                                blocker.submit_impl(cmd_buffer);
+                                blocker.submit_impl(&cmd_buffer);
                             }
                         }
                     }
@@ -2387,6 +3520,9 @@ impl hal::queue::Queue<Backend> for Queue {
                     }) => {
                         num_remote += 1;
                         trace!("\tremote {:?}", token);
+                        if let Some(cb) = deferred_cmd_buffer.take() {
+                            blocker.submit_impl(&cb);
+                        }
                         cmd_buffer.lock().enqueue();
                         let shared_cb = SharedCommandBuffer(Arc::clone(cmd_buffer));
                         //TODO: make this compatible with events
@@ -2410,6 +3546,12 @@ impl hal::queue::Queue<Backend> for Queue {
                     Some((Arc::clone(&self.shared), queries))
                 };
 
+                let fence_signal = fence
+                    .as_ref()
+                    .map(|_| Arc::new(native::FenceSignal::default()));
+                let handler_signal = fence_signal.clone();
+                let shared = Arc::clone(&self.shared);
+
                 let block = ConcreteBlock::new(move |_cb: *mut ()| {
                     // signal the semaphores
                     for semaphore in &system_semaphores {
@@ -2435,6 +3577,12 @@ impl hal::queue::Queue<Backend> for Queue {
                         let _ = vis.allocator.lock();
                         vis.condvar.notify_all();
                     }
+                    // wake up the fence waiters
+                    if let Some(ref signal) = handler_signal {
+                        signal.signal();
+                    }
+                    // the events set above may unblock pending submissions
+                    shared.queue_blocker.lock().triage();
                 })
                 .copy();
 
@@ -2444,17 +3592,23 @@ impl hal::queue::Queue<Backend> for Queue {
                         cmd_buffer.set_label("signal");
                     }
                     self.record_empty(cmd_buffer);
-                    cmd_buffer
+                    cmd_buffer.to_owned()
                 });
-                let () = msg_send![cmd_buffer, addCompletedHandler: block.deref() as *const _];
-                blocker.submit_impl(cmd_buffer);
+                let () = msg_send![&*cmd_buffer, addCompletedHandler: block.deref() as *const _];
+                blocker.submit_impl(&cmd_buffer);
 
-                if let Some(fence) = fence {
+                if let (Some(fence), Some(signal)) = (fence, fence_signal) {
                     debug!("\tmarking fence as pending");
-                    *fence = native::Fence::PendingSubmission(cmd_buffer.to_owned());
+                    *fence = native::Fence::PendingSubmission { cmd_buffer, signal };
                 }
             } else if let Some(cmd_buffer) = deferred_cmd_buffer {
-                blocker.submit_impl(cmd_buffer);
+                // keep the batch open for the following submissions,
+                // unless it would be queued behind a blocked one
+                if self.batch_submissions && blocker.submissions.is_empty() {
+                    cmd_queue.pending = Some(cmd_buffer);
+                } else {
+                    blocker.submit_impl(&cmd_buffer);
+                }
             }
 
             for sink in release_sinks {
@@ -2462,6 +3616,11 @@ impl hal::queue::Queue<Backend> for Queue {
                     cmd_queue.release(token);
                 }
             }
+
+            if let Some(scope) = capture_scope {
+                scope.end_scope();
+                metal::CaptureManager::shared().stop_capture();
+            }
         });
 
         debug!(
@@ -2477,42 +3636,91 @@ impl hal::queue::Queue<Backend> for Queue {
             }
         }
     }
+}
 
-    unsafe fn present(
+impl hal::queue::Queue<Backend> for Queue {
+    unsafe fn submit<'a, Ic, Iw, Is>(
         &mut self,
-        _surface: &mut window::Surface,
-        image: window::SwapchainImage,
-        wait_semaphore: Option<&mut native::Semaphore>,
-    ) -> Result<Option<Suboptimal>, PresentError> {
-        profiling::scope!("present");
-        if let Some(semaphore) = wait_semaphore {
-            if let Some(ref system) = semaphore.system {
-                system.wait(!0);
-            }
+        command_buffers: Ic,
+        wait_semaphores: Iw,
+        signal_semaphores: Is,
+        fence: Option<&mut native::Fence>,
+    ) where
+        Ic: Iterator<Item = &'a CommandBuffer>,
+        Iw: Iterator<Item = (&'a native::Semaphore, pso::PipelineStage)>,
+        Is: Iterator<Item = &'a native::Semaphore>,
+    {
+        profiling::scope!("submit");
+        debug!("submitting with fence {:?}", fence);
+        if self.shared.is_removed() {
+            warn!("Skipping a submission to the removed device");
+            return;
         }
+        self.wait(wait_semaphores.map(|(s, _)| s));
 
-        let queue = self.shared.queue.lock();
-        autoreleasepool(|| {
-            let command_buffer = queue.raw.new_command_buffer();
-            if INTERNAL_LABELS {
-                command_buffer.set_label("present");
-            }
-            self.record_empty(command_buffer);
+        let mut system_semaphores = Vec::new();
+        let mut shared_events = Vec::new();
+        for sem in signal_semaphores {
+            system_semaphores.extend(sem.system.clone());
+            shared_events.extend(sem.shared_event.as_deref());
+        }
+        self.submit_signaling(command_buffers, system_semaphores, fence);
+        self.encode_shared_events(&shared_events, true);
+    }
 
-            // https://developer.apple.com/documentation/quartzcore/cametallayer/1478157-presentswithtransaction?language=objc
-            if !image.present_with_transaction {
-                command_buffer.present_drawable(&image.drawable);
-            }
+    unsafe fn submit_timeline<'a, Ic, Iw, Is>(
+        &mut self,
+        command_buffers: Ic,
+        wait_semaphores: Iw,
+        signal_semaphores: Is,
+    ) where
+        Ic: Iterator<Item = &'a CommandBuffer>,
+        Iw: Iterator<Item = (&'a native::TimelineSemaphore, u64, pso::PipelineStage)>,
+        Is: Iterator<Item = (&'a native::TimelineSemaphore, u64)>,
+    {
+        profiling::scope!("submit_timeline");
+        if self.shared.is_removed() {
+            warn!("Skipping a submission to the removed device");
+            return;
+        }
+        let waits = wait_semaphores
+            .map(|(sem, value, _)| (&*sem.raw, value))
+            .collect::<Vec<_>>();
+        self.encode_event_values(&waits, false);
+        self.submit_signaling(command_buffers, Vec::new(), None);
+        let signals = signal_semaphores
+            .map(|(sem, value)| (&*sem.raw, value))
+            .collect::<Vec<_>>();
+        self.encode_event_values(&signals, true);
+    }
 
-            command_buffer.commit();
+    unsafe fn present(
+        &mut self,
+        surface: &mut window::Surface,
+        image: window::SwapchainImage,
+        wait_semaphore: Option<&mut native::Semaphore>,
+    ) -> Result<Option<Suboptimal>, PresentError> {
+        self.present_impl(surface, image, wait_semaphore, None, None)
+    }
 
-            if image.present_with_transaction {
-                let () = msg_send![command_buffer, waitUntilScheduled];
-                image.drawable.present();
-            }
-        });
+    unsafe fn present_with_fence(
+        &mut self,
+        surface: &mut window::Surface,
+        image: window::SwapchainImage,
+        wait_semaphore: Option<&mut native::Semaphore>,
+        fence: &mut native::Fence,
+    ) -> Result<Option<Suboptimal>, PresentError> {
+        self.present_impl(surface, image, wait_semaphore, Some(fence), None)
+    }
 
-        Ok(None)
+    unsafe fn present_with_id(
+        &mut self,
+        surface: &mut window::Surface,
+        image: window::SwapchainImage,
+        wait_semaphore: Option<&mut native::Semaphore>,
+        present_id: u64,
+    ) -> Result<Option<Suboptimal>, PresentError> {
+        self.present_impl(surface, image, wait_semaphore, None, Some(present_id))
     }
 
     fn wait_idle(&mut self) -> Result<(), OutOfMemory> {
@@ -2526,119 +3734,529 @@ impl hal::queue::Queue<Backend> for Queue {
     }
 }
 
-fn assign_sides(
-    this: &mut pso::Sided<pso::StencilValue>,
-    faces: pso::Face,
-    value: pso::StencilValue,
-) {
-    if faces.contains(pso::Face::FRONT) {
-        this.front = value;
-    }
-    if faces.contains(pso::Face::BACK) {
-        this.back = value;
+fn assign_sides(
+    this: &mut pso::Sided<pso::StencilValue>,
+    faces: pso::Face,
+    value: pso::StencilValue,
+) {
+    if faces.contains(pso::Face::FRONT) {
+        this.front = value;
+    }
+    if faces.contains(pso::Face::BACK) {
+        this.back = value;
+    }
+}
+
+impl hal::pool::CommandPool<Backend> for CommandPool {
+    unsafe fn reset(&mut self, release_resources: bool) {
+        for cmd_buffer in &self.allocated {
+            cmd_buffer
+                .borrow_mut()
+                .reset(&self.shared, &self.pool_shared, release_resources);
+        }
+    }
+
+    unsafe fn allocate_one(&mut self, level: com::Level) -> CommandBuffer {
+        //TODO: fail with OOM if we allocate more actual command buffers
+        // than our mega-queue supports.
+        let inner = Arc::new(RefCell::new(CommandBufferInner {
+            sink: None,
+            level,
+            backup_journal: None,
+            #[cfg(feature = "dispatch")]
+            backup_capacity: None,
+            retained_buffers: Vec::new(),
+            retained_textures: Vec::new(),
+            pending_writes: PendingWrites::new(&self.shared.device),
+            active_visibility_queries: Vec::new(),
+            events: Vec::new(),
+            host_events: Vec::new(),
+            texel_mirrors: Vec::new(),
+            pass_timer: None,
+        }));
+        self.allocated.push(Arc::clone(&inner));
+
+        CommandBuffer {
+            shared: Arc::clone(&self.shared),
+            pool_shared: Arc::clone(&self.pool_shared),
+            inner,
+            state: State {
+                viewport: None,
+                scissors: None,
+                blend_color: None,
+                render_pso: None,
+                render_pso_is_compatible: false,
+                compute_pso: None,
+                work_group_size: MTLSize {
+                    width: 0,
+                    height: 0,
+                    depth: 0,
+                },
+                compute_base_slot: None,
+                primitive_type: MTLPrimitiveType::Point,
+                resources_vs: StageResources::new(),
+                resources_ps: StageResources::new(),
+                resources_cs: StageResources::new(),
+                index_buffer: None,
+                rasterizer_state: None,
+                depth_bias: pso::DepthBias::default(),
+                stencil: native::StencilState {
+                    reference_values: pso::Sided::new(0),
+                    read_masks: pso::Sided::new(!0),
+                    write_masks: pso::Sided::new(!0),
+                },
+                dynamic: DynamicStateValues::default(),
+                push_constants: Vec::new(),
+                push_constants_spill: None,
+                push_constant_ring: PushConstantRing::default(),
+                vertex_buffers: Vec::new(),
+                target: TargetState::default(),
+                visibility_query: (metal::MTLVisibilityResultMode::Disabled, 0),
+                pending_subpasses: Vec::new(),
+                active_depth_resolve: None,
+                tile_layout: None,
+                sample_positions: SamplePositions::new(),
+                descriptor_sets: (0..MAX_BOUND_DESCRIPTOR_SETS)
+                    .map(|_| DescriptorSetInfo::default())
+                    .collect(),
+                addressed_resources: ResidentResources::default(),
+                active_depth_stencil_desc: pso::DepthStencilDesc::default(),
+                active_scissor: MTLScissorRect {
+                    x: 0,
+                    y: 0,
+                    width: 0,
+                    height: 0,
+                },
+                stage_infos: native::MultiStageData::default(),
+                storage_buffer_length_map: FastHashMap::default(),
+            },
+            temp: Temp::default(),
+            stats: if self.pool_shared.command_stats {
+                Some(CommandBufferStats::default())
+            } else {
+                None
+            },
+            name: String::new(),
+        }
+    }
+
+    /// Free command buffers which are allocated from this pool.
+    unsafe fn free<I>(&mut self, cmd_buffers: I)
+    where
+        I: Iterator<Item = CommandBuffer>,
+    {
+        use hal::command::CommandBuffer as _;
+        for mut cmd_buf in cmd_buffers {
+            cmd_buf.reset(true);
+            match self
+                .allocated
+                .iter_mut()
+                .position(|b| Arc::ptr_eq(b, &cmd_buf.inner))
+            {
+                Some(index) => {
+                    self.allocated.swap_remove(index);
+                }
+                None => error!("Unable to free a command buffer!"),
+            }
+        }
+    }
+}
+
+impl CommandBuffer {
+    /// Collect the mirrors of the texel views written into `desc_set`.
+    fn collect_texel_mirrors(
+        &self,
+        desc_set: &native::DescriptorSet,
+        mirrors: &mut Vec<Arc<native::TexelMirror>>,
+    ) {
+        let registry = self.shared.texel_mirrors.lock();
+        if registry.is_empty() {
+            return;
+        }
+        let textures = match *desc_set {
+            native::DescriptorSet::Emulated {
+                ref pool,
+                ref resources,
+                ..
+            } => pool.read().textures
+                [resources.textures.start as usize..resources.textures.end as usize]
+                .iter()
+                .filter_map(|&(_, texture, _)| texture)
+                .map(|texture| texture.as_ptr() as *mut c_void)
+                .collect::<Vec<_>>(),
+            native::DescriptorSet::ArgumentBuffer {
+                ref pool,
+                ref range,
+                ..
+            } => pool.read().resources[range.start as usize..range.end as usize]
+                .iter()
+                .map(|resource| resource.ptr as *mut c_void)
+                .collect::<Vec<_>>(),
+        };
+        mirrors.extend(
+            registry
+                .iter()
+                .filter_map(Weak::upgrade)
+                .filter(|mirror| textures.contains(&(mirror.texture.as_ptr() as *mut c_void))),
+        );
+    }
+
+    /// Refresh the mirrors of the texel views of `buffer`, after it's written
+    /// by the previously recorded transfer commands.
+    fn refresh_texel_mirrors(&self, inner: &mut CommandBufferInner, buffer: &metal::BufferRef) {
+        let mirrors = self
+            .shared
+            .texel_mirrors
+            .lock()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .filter(|mirror| mirror.source.as_ptr() == buffer.as_ptr())
+            .collect::<Vec<_>>();
+        if mirrors.is_empty() {
+            return;
+        }
+        let commands = mirrors
+            .iter()
+            .map(|mirror| soft::BlitCommand::CopyBuffer {
+                src: AsNative::from(mirror.source.as_ref()),
+                dst: AsNative::from(mirror.raw.as_ref()),
+                region: com::BufferCopy {
+                    src: mirror.offset,
+                    dst: 0,
+                    size: mirror.size,
+                },
+            })
+            .collect::<Vec<_>>();
+        // the pending writes are flushed ahead of the copies
+        inner.sink().blit_commands(commands.into_iter());
+        inner
+            .retained_buffers
+            .extend(mirrors.into_iter().map(|mirror| mirror.raw.clone()));
+    }
+
+    /// Decode the copied blocks with a compute pass writing into the uncompressed texture.
+    ///
+    /// Each region is written through an integer view of its mip level and layers.
+    fn copy_buffer_to_decompressed_image<T>(
+        &mut self,
+        src: &native::Buffer,
+        dst: &native::Image,
+        dst_raw: &metal::TextureRef,
+        regions: T,
+    ) where
+        T: Iterator<Item = com::BufferImageCopy>,
+    {
+        let block_format = dst.decompression.unwrap();
+        if dst.mtl_type == MTLTextureType::D3 {
+            error!(
+                "Unable to decompress {:?} blocks into a 3D image",
+                block_format
+            );
+            return;
+        }
+        let (src_raw, src_range) = src.as_bound();
+        let pso = self
+            .shared
+            .service_pipes
+            .decompress
+            .get(block_format, &self.shared.device);
+        let wg_size = MTLSize {
+            width: 8,
+            height: 8,
+            depth: 1,
+        };
+
+        let mut inner = self.inner.borrow_mut();
+        for r in regions {
+            if r.image_extent.is_empty() {
+                continue;
+            }
+            let layout = block_format.source_layout(&r);
+            let offset = src_range.start + r.buffer_offset;
+            if offset + layout.size > src_range.end {
+                error!(
+                    "Copy region {:?} reads {} bytes past the bound buffer range {:?}",
+                    r,
+                    offset + layout.size - src_range.end,
+                    src_range
+                );
+                continue;
+            }
+
+            let view = dst_raw.new_texture_view_from_slice(
+                block_format.view_format(),
+                MTLTextureType::D2Array,
+                NSRange {
+                    location: r.image_layers.level as _,
+                    length: 1,
+                },
+                NSRange {
+                    location: r.image_layers.layers.start as _,
+                    length: r.image_layers.layers.len() as _,
+                },
+            );
+            let params = [
+                r.image_offset.x as u32,
+                r.image_offset.y as u32,
+                r.image_extent.width,
+                r.image_extent.height,
+                layout.row_pitch,
+                layout.slice_pitch,
+                layout.num_slices,
+            ];
+            let wg_count = MTLSize {
+                width: ((r.image_extent.width as u64 + 3) / 4 + wg_size.width - 1) / wg_size.width,
+                height: ((r.image_extent.height as u64 + 3) / 4 + wg_size.height - 1)
+                    / wg_size.height,
+                depth: layout.num_slices as _,
+            };
+
+            let commands = [
+                soft::ComputeCommand::BindPipeline(&**pso),
+                soft::ComputeCommand::BindBuffer {
+                    index: 0,
+                    buffer: AsNative::from(src_raw),
+                    offset,
+                },
+                soft::ComputeCommand::BindBufferData {
+                    index: 1,
+                    words: &params[..],
+                },
+                soft::ComputeCommand::BindTextures {
+                    index: 0,
+                    textures: &[Some(AsNative::from(view.as_ref()))][..],
+                },
+                soft::ComputeCommand::Dispatch { wg_size, wg_count },
+            ];
+            inner
+                .sink()
+                .quick_compute("decompress_image", commands.iter().cloned());
+            inner.retained_textures.push(view);
+        }
+    }
+
+    /// Resolve the depth of the finished subpass with a draw into the resolve attachment.
+    fn resolve_depth(&mut self, resolve: DepthResolve) {
+        let service_pipes = &self.shared.service_pipes;
+        let format = resolve.destination.pixel_format();
+        let pso = service_pipes
+            .depth_resolves
+            .get((format, resolve.aspects, resolve.mode), &self.shared.device);
+        let ds_state = service_pipes.depth_stencil_states.get_write(Aspects::DEPTH);
+
+        let descriptor = {
+            let descriptor = self
+                .pool_shared
+                .render_pass_descriptors
+                .lock()
+                .alloc(&self.shared);
+            let att = descriptor.depth_attachment().unwrap();
+            att.set_texture(Some(&resolve.destination));
+            att.set_load_action(metal::MTLLoadAction::DontCare);
+            att.set_store_action(metal::MTLStoreAction::Store);
+            if resolve.aspects.contains(Aspects::STENCIL) {
+                let att = descriptor.stencil_attachment().unwrap();
+                att.set_texture(Some(&resolve.destination));
+                att.set_load_action(metal::MTLLoadAction::Load);
+                att.set_store_action(metal::MTLStoreAction::Store);
+            }
+            descriptor
+        };
+
+        let commands = [
+            soft::RenderCommand::BindPipeline(&**pso),
+            soft::RenderCommand::SetDepthStencilState(&**ds_state),
+            soft::RenderCommand::BindTextures {
+                stage: naga::ShaderStage::Fragment,
+                index: 0,
+                textures: &[Some(AsNative::from(resolve.source.as_ref()))][..],
+            },
+            soft::RenderCommand::Draw {
+                primitive_type: MTLPrimitiveType::Triangle,
+                vertices: 0..3,
+                instances: 0..1,
+            },
+        ];
+        self.inner.borrow_mut().sink().quick_render(
+            "resolve_depth",
+            descriptor,
+            &self.pool_shared,
+            commands.iter().cloned(),
+        );
+    }
+
+    /// Track the buffers that are currently addressable, so that the next pass makes
+    /// them resident. They are retained until the command buffer is reset.
+    ///
+    /// Returns true if the tracked buffers changed.
+    fn update_addressed_resources(&mut self) -> bool {
+        let addressed_buffers = self.shared.addressed_buffers.lock();
+        let unchanged = self
+            .state
+            .addressed_resources
+            .resources
+            .iter()
+            .map(|res| res.as_ptr())
+            .eq(addressed_buffers
+                .iter()
+                .map(|raw| raw.as_ptr() as *mut metal::MTLResource));
+        if unchanged {
+            return false;
+        }
+
+        let used = addressed_buffers
+            .iter()
+            .map(|raw| native::UsedResource {
+                ptr: raw.as_ptr() as *mut metal::MTLResource,
+                usage: metal::MTLResourceUsage::Read | metal::MTLResourceUsage::Write,
+            })
+            .collect::<Vec<_>>();
+        self.state.addressed_resources.fill(&used);
+        self.inner
+            .borrow_mut()
+            .retained_buffers
+            .extend(addressed_buffers.iter().cloned());
+        true
+    }
+
+    /// Start recording the next pending subpass.
+    fn begin_subpass(&mut self) {
+        if let Some(resolve) = self.state.active_depth_resolve.take() {
+            self.resolve_depth(resolve);
+        }
+        let sin = self.state.pending_subpasses.pop().unwrap();
+        self.state.active_depth_resolve = sin.depth_resolve;
+        self.update_addressed_resources();
+
+        self.state.render_pso_is_compatible = match self.state.render_pso {
+            Some(ref ps) => ps.formats == sin.formats,
+            None => false,
+        };
+        self.state.active_depth_stencil_desc = pso::DepthStencilDesc::default();
+        self.state.active_scissor = MTLScissorRect {
+            x: 0,
+            y: 0,
+            width: self.state.target.extent.width as u64,
+            height: self.state.target.extent.height as u64,
+        };
+        self.state.target.aspects = sin.combined_aspects;
+        self.state.target.formats = sin.formats.clone();
+        self.state.target.samples = sin.sample_count;
+
+        let com_scissor = self.state.reset_scissor();
+
+        let ds_store = &self.shared.service_pipes.depth_stencil_states;
+        let ds_state;
+        let com_ds = match self.state.build_depth_stencil() {
+            Some(desc) => {
+                ds_state = ds_store.get(desc, &self.shared.device);
+                Some(soft::RenderCommand::SetDepthStencilState(&**ds_state))
+            }
+            None => None,
+        };
+
+        let writes_storage = self
+            .state
+            .render_pso
+            .as_ref()
+            .map_or(false, |ps| ps.writes_storage);
+        let (mut temp_binding_sizes_vs, mut temp_binding_sizes_ps) = (Vec::new(), Vec::new()); //TODO: avoid the heap?
+        let init_commands = self
+            .state
+            .make_render_commands(
+                sin.combined_aspects,
+                &mut temp_binding_sizes_vs,
+                &mut temp_binding_sizes_ps,
+            )
+            .chain(com_scissor)
+            .chain(com_ds);
+
+        let mut inner = self.inner.borrow_mut();
+        if sin.merged {
+            self.pool_shared
+                .render_pass_descriptors
+                .lock()
+                .free(sin.descriptor);
+            inner.sink().pre_render().issue_many(init_commands);
+        } else {
+            inner
+                .sink()
+                .switch_render(sin.descriptor, &self.pool_shared)
+                .issue_many(init_commands);
+        }
+        if writes_storage {
+            inner.sink().seal_render();
+        }
     }
-}
 
-impl hal::pool::CommandPool<Backend> for CommandPool {
-    unsafe fn reset(&mut self, release_resources: bool) {
-        for cmd_buffer in &self.allocated {
-            cmd_buffer
-                .borrow_mut()
-                .reset(&self.shared, &self.pool_shared, release_resources);
+    /// Run `fun` within an autorelease pool when recording into a native command
+    /// buffer right away. Only the native encoders get autoreleased by recording,
+    /// so the pool is skipped otherwise.
+    fn with_autorelease_pool<T>(&mut self, fun: impl FnOnce(&mut Self) -> T) -> T {
+        let immediate = match self.inner.borrow().sink {
+            Some(CommandSink::Immediate { .. }) => true,
+            _ => false,
+        };
+        if immediate {
+            if let Some(ref mut stats) = self.stats {
+                stats.autorelease_pools += 1;
+            }
+            autoreleasepool(move || fun(self))
+        } else {
+            fun(self)
         }
     }
 
-    unsafe fn allocate_one(&mut self, level: com::Level) -> CommandBuffer {
-        //TODO: fail with OOM if we allocate more actual command buffers
-        // than our mega-queue supports.
-        let inner = Arc::new(RefCell::new(CommandBufferInner {
-            sink: None,
-            level,
-            backup_journal: None,
-            #[cfg(feature = "dispatch")]
-            backup_capacity: None,
-            retained_buffers: Vec::new(),
-            retained_textures: Vec::new(),
-            active_visibility_queries: Vec::new(),
-            events: Vec::new(),
-            host_events: Vec::new(),
-        }));
-        self.allocated.push(Arc::clone(&inner));
-
-        CommandBuffer {
-            shared: Arc::clone(&self.shared),
-            pool_shared: Arc::clone(&self.pool_shared),
-            inner,
-            state: State {
-                viewport: None,
-                scissors: None,
-                blend_color: None,
-                render_pso: None,
-                render_pso_is_compatible: false,
-                compute_pso: None,
-                work_group_size: MTLSize {
-                    width: 0,
-                    height: 0,
-                    depth: 0,
-                },
-                primitive_type: MTLPrimitiveType::Point,
-                resources_vs: StageResources::new(),
-                resources_ps: StageResources::new(),
-                resources_cs: StageResources::new(),
-                index_buffer: None,
-                rasterizer_state: None,
-                depth_bias: pso::DepthBias::default(),
-                stencil: native::StencilState {
-                    reference_values: pso::Sided::new(0),
-                    read_masks: pso::Sided::new(!0),
-                    write_masks: pso::Sided::new(!0),
-                },
-                push_constants: Vec::new(),
-                vertex_buffers: Vec::new(),
-                target: TargetState::default(),
-                visibility_query: (metal::MTLVisibilityResultMode::Disabled, 0),
-                pending_subpasses: Vec::new(),
-                descriptor_sets: (0..MAX_BOUND_DESCRIPTOR_SETS)
-                    .map(|_| DescriptorSetInfo::default())
-                    .collect(),
-                active_depth_stencil_desc: pso::DepthStencilDesc::default(),
-                active_scissor: MTLScissorRect {
-                    x: 0,
-                    y: 0,
-                    width: 0,
-                    height: 0,
-                },
-                stage_infos: native::MultiStageData::default(),
-                storage_buffer_length_map: FastHashMap::default(),
-            },
-            temp: Temp::default(),
-            name: String::new(),
+    fn update_dynamic_states(&mut self) {
+        let states = match self.state.render_pso {
+            Some(ref ps) => ps.dynamic_states,
+            None => return,
+        };
+        self.state.apply_dynamic_states(states);
+        if let Some(ref rs) = self.state.rasterizer_state {
+            let com = soft::RenderCommand::SetRasterizerState(rs.clone());
+            self.inner.borrow_mut().sink().pre_render().issue(com);
         }
     }
 
-    /// Free command buffers which are allocated from this pool.
-    unsafe fn free<I>(&mut self, cmd_buffers: I)
-    where
-        I: Iterator<Item = CommandBuffer>,
-    {
-        use hal::command::CommandBuffer as _;
-        for mut cmd_buf in cmd_buffers {
-            cmd_buf.reset(true);
-            match self
-                .allocated
-                .iter_mut()
-                .position(|b| Arc::ptr_eq(b, &cmd_buf.inner))
-            {
-                Some(index) => {
-                    self.allocated.swap_remove(index);
+    fn update_vertex_input(&mut self) {
+        let variants = match self.state.render_pso {
+            Some(RenderPipelineState {
+                vertex_input: Some(ref variants),
+                ..
+            }) => Arc::clone(variants),
+            _ => return,
+        };
+        let (raw, vertex_buffers) = match self.state.dynamic.vertex_input {
+            Some((ref buffers, ref attributes)) => {
+                match variants.get(&self.shared, buffers, attributes) {
+                    Ok(variant) => variant,
+                    Err(_) => return,
                 }
-                None => error!("Unable to free a command buffer!"),
             }
+            None => return,
+        };
+
+        let ps = self.state.render_pso.as_mut().unwrap();
+        ps.vertex_buffers.clear();
+        ps.vertex_buffers
+            .extend(vertex_buffers.into_iter().map(Some));
+        ps.raw = raw;
+        if !self.state.render_pso_is_compatible {
+            return;
+        }
+
+        let mut inner = self.inner.borrow_mut();
+        let mut pre = inner.sink().pre_render();
+        pre.issue(soft::RenderCommand::BindPipeline(&*ps.raw));
+        if let Some(command) = self
+            .state
+            .set_vertex_buffers(self.shared.private_caps.max_buffers_per_stage as usize)
+        {
+            pre.issue(command);
         }
     }
-}
 
-impl CommandBuffer {
     fn update_depth_stencil(&mut self) {
         let mut inner = self.inner.borrow_mut();
         let mut pre = inner.sink().pre_render();
@@ -2650,6 +4268,139 @@ impl CommandBuffer {
             }
         }
     }
+
+    /// Set the layout of the tile memory for the render passes begun afterwards,
+    /// or `None` to use the default one.
+    ///
+    /// The layout has to fit the imageblocks and threadgroup memory of
+    /// the tile pipelines dispatched within these passes.
+    pub fn set_tile_layout(&mut self, layout: Option<native::TileLayout>) {
+        if layout.is_some() && !self.shared.private_caps.tile_shaders {
+            error!("Tile layouts are not supported by the device");
+            return;
+        }
+        self.state.tile_layout = layout;
+    }
+
+    /// Bind a buffer to the tile functions at the given argument index.
+    ///
+    /// The binding is kept until the end of the current subpass.
+    pub fn bind_tile_buffer(
+        &mut self,
+        index: ResourceIndex,
+        buffer: &native::Buffer,
+        offset: buffer::Offset,
+    ) {
+        let (raw, range) = buffer.as_bound();
+        assert!(range.start + offset < range.end);
+        let mut inner = self.inner.borrow_mut();
+        inner
+            .sink()
+            .pre_render()
+            .issue(soft::RenderCommand::BindTileBuffer {
+                index,
+                buffer: AsNative::from(raw),
+                offset: range.start + offset,
+            });
+    }
+
+    /// Dispatch the tile function of `pipeline` over each tile of the current subpass,
+    /// using the given number of threads per tile, which can't exceed the tile size.
+    ///
+    /// The threadgroup memory of the pipeline is allocated before the dispatch, and the
+    /// bound graphics pipeline is restored after it.
+    pub fn dispatch_tile(&mut self, pipeline: &native::TilePipeline, threads: (u32, u32)) {
+        if let Some(ref mut stats) = self.stats {
+            stats.dispatches += 1;
+        }
+        let mut inner = self.inner.borrow_mut();
+        let mut pre = inner.sink().pre_render();
+        if pre.is_void() {
+            error!("Tile functions can only be dispatched within a render pass");
+            return;
+        }
+
+        pre.issue(soft::RenderCommand::BindPipeline(&*pipeline.raw));
+        for &(index, length) in pipeline.threadgroup_memory.iter() {
+            pre.issue(soft::RenderCommand::SetThreadgroupMemoryLength { index, length });
+        }
+        pre.issue(soft::RenderCommand::DispatchTile {
+            size: MTLSize {
+                width: threads.0 as _,
+                height: threads.1 as _,
+                depth: 1,
+            },
+        });
+        if let (Some(bind_pipeline), _) = self.state.make_pso_commands() {
+            pre.issue(bind_pipeline);
+        }
+    }
+
+    /// Dispatch compute work groups with the IDs starting at `base`, like `dispatch`
+    /// does when starting at zero.
+    ///
+    /// The bound pipeline needs to be created with `PipelineCreationFlags::DISPATCH_BASE`.
+    pub fn dispatch_base(&mut self, base: WorkGroupCount, count: WorkGroupCount) {
+        if self.state.compute_base_slot.is_none() && base != [0; 3] {
+            error!("Compute pipeline is not created for dispatches with a base");
+            return;
+        }
+        if let Some(ref mut stats) = self.stats {
+            stats.dispatches += 1;
+        }
+        let addressed_changed = self.update_addressed_resources();
+        let mut inner = self.inner.borrow_mut();
+        let (mut pre, init) = inner.sink().switch_compute();
+        if init {
+            pre.issue_many(
+                self.state
+                    .make_compute_commands(&mut self.temp.binding_sizes),
+            );
+        } else if addressed_changed {
+            pre.issue_many(self.state.addressed_resources.compute_commands());
+        }
+
+        if let Some(index) = self.state.compute_base_slot {
+            pre.issue(soft::ComputeCommand::BindBufferData {
+                index,
+                words: &[base[0], base[1], base[2], 0],
+            });
+        }
+        pre.issue(soft::ComputeCommand::Dispatch {
+            wg_size: self.state.work_group_size,
+            wg_count: MTLSize {
+                width: count[0] as _,
+                height: count[1] as _,
+                depth: count[2] as _,
+            },
+        });
+    }
+
+    /// Returns the GPU durations of the passes recorded in the last submission
+    /// of this command buffer, in the order they were started.
+    ///
+    /// Timing requires `Device::pass_timing` to be enabled when creating the pool,
+    /// immediate recording with `ONE_TIME_SUBMIT`, and hardware support for
+    /// sampling counters at stage boundaries. Returns `None` if timing is not
+    /// available or the command buffer hasn't completed execution yet.
+    pub fn pass_timings(&self) -> Option<Vec<PassTiming>> {
+        self.inner.borrow().pass_timer.as_ref()?.resolve()
+    }
+
+    /// Returns the command statistics of the last recording of this command buffer,
+    /// which stay available after it's submitted and until it's reset.
+    ///
+    /// Statistics require `Device::command_stats` to be enabled when creating the pool.
+    pub fn stats(&self) -> Option<CommandBufferStats> {
+        let mut stats = self.stats?;
+        stats.encoder_switches = self
+            .inner
+            .borrow()
+            .sink
+            .as_ref()
+            .map_or(0, |sink| sink.num_passes() as u32);
+        Some(stats)
+    }
 }
 
 impl com::CommandBuffer<Backend> for CommandBuffer {
@@ -2669,12 +4420,25 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                 if !self.name.is_empty() {
                     cmd_buffer.set_label(&self.name);
                 }
+                let timer = if self.pool_shared.pass_timing
+                    && self.shared.private_caps.supports_counter_sampling
+                {
+                    let device = &self.shared.device;
+                    let mut timer = inner.pass_timer.take().or_else(|| PassTimer::new(device));
+                    if let Some(ref mut timer) = timer {
+                        timer.begin(&cmd_buffer);
+                    }
+                    timer
+                } else {
+                    None
+                };
                 CommandSink::Immediate {
                     cmd_buffer,
                     token,
                     encoder_state: EncoderState::None,
                     num_passes: 0,
                     label: String::new(),
+                    timer,
                 }
             }
             #[cfg(feature = "dispatch")]
@@ -2692,6 +4456,7 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                     cmd_buffer: Arc::new(Mutex::new(cmd_buffer)),
                     token,
                     pass: None,
+                    num_passes: 0,
                     capacity: inner.backup_capacity.take().unwrap_or_default(),
                     label: String::new(),
                     pool_shared: Arc::clone(&self.pool_shared),
@@ -2762,6 +4527,9 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
 
     unsafe fn reset(&mut self, release_resources: bool) {
         self.state.reset();
+        if let Some(ref mut stats) = self.stats {
+            *stats = CommandBufferStats::default();
+        }
         self.inner
             .borrow_mut()
             .reset(&self.shared, &self.pool_shared, release_resources);
@@ -2775,6 +4543,10 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
     ) where
         T: Iterator<Item = memory::Barrier<'a, Backend>>,
     {
+        // Metal orders the passes, the render passes just shouldn't be fused across the barrier.
+        if let Some(ref mut sink) = self.inner.borrow_mut().sink {
+            sink.seal_render();
+        }
     }
 
     unsafe fn fill_buffer(&mut self, buffer: &native::Buffer, sub: buffer::SubRange, data: u32) {
@@ -2790,12 +4562,16 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         });
 
         if (data & 0xFF) * 0x0101_0101 == data {
-            let command = soft::BlitCommand::FillBuffer {
-                dst: AsNative::from(raw),
-                range: start..end,
-                value: data as u8,
-            };
-            inner.sink().blit_commands(iter::once(command));
+            inner
+                .pending_writes
+                .fill(AsNative::from(raw), start..end, data as u8);
+        } else if end - start <= MAX_STAGED_FILL {
+            let words = inner
+                .pending_writes
+                .update(AsNative::from(raw), start, end - start);
+            for word in words.chunks_exact_mut(WORD_SIZE) {
+                word.copy_from_slice(&data.to_ne_bytes());
+            }
         } else {
             let pso = &*self.shared.service_pipes.fill_buffer;
             let length = (end - start) / WORD_ALIGNMENT;
@@ -2834,37 +4610,23 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                 .sink()
                 .quick_compute("fill_buffer", commands.iter().cloned());
         }
+        self.refresh_texel_mirrors(&mut inner, raw);
     }
 
     unsafe fn update_buffer(&mut self, dst: &native::Buffer, offset: buffer::Offset, data: &[u8]) {
         let (dst_raw, dst_range) = dst.as_bound();
         assert!(dst_range.start + offset + data.len() as buffer::Offset <= dst_range.end);
 
-        let src = self.shared.device.lock().new_buffer_with_data(
-            data.as_ptr() as _,
-            data.len() as _,
-            metal::MTLResourceOptions::CPUCacheModeWriteCombined,
-        );
-        if INTERNAL_LABELS {
-            src.set_label("update_buffer");
-        }
-
         let mut inner = self.inner.borrow_mut();
-        {
-            let command = soft::BlitCommand::CopyBuffer {
-                src: AsNative::from(src.as_ref()),
-                dst: AsNative::from(dst_raw),
-                region: com::BufferCopy {
-                    src: 0,
-                    dst: dst_range.start + offset,
-                    size: data.len() as _,
-                },
-            };
-
-            inner.sink().blit_commands(iter::once(command));
-        }
-
-        inner.retained_buffers.push(src);
+        inner
+            .pending_writes
+            .update(
+                AsNative::from(dst_raw),
+                dst_range.start + offset,
+                data.len() as _,
+            )
+            .copy_from_slice(data);
+        self.refresh_texel_mirrors(&mut inner, dst_raw);
     }
 
     unsafe fn clear_image<T>(
@@ -2887,6 +4649,9 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         let base_extent = image.kind.extent();
         let is_layered = !self.shared.disabilities.broken_layered_clear_image;
 
+        if let Some(ref mut stats) = self.stats {
+            stats.autorelease_pools += 1;
+        }
         autoreleasepool(|| {
             let raw = image.like.as_texture();
             for sub in subresource_ranges {
@@ -3394,6 +5159,9 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         let layered_rendering = self.shared.private_caps.layered_rendering;
         let pool_shared = &self.pool_shared;
         let shared = &self.shared;
+        if let Some(ref mut stats) = self.stats {
+            stats.autorelease_pools += 1;
+        }
         autoreleasepool(|| {
             let dst_new = match dst_cubish {
                 Some(ref tex) => tex.as_ref(),
@@ -3532,7 +5300,13 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
             panic!("More than one viewport set; Metal supports only one viewport");
         }
 
+        let old_viewport = self.state.viewport.clone();
         let com = self.state.set_viewport(&vp, self.shared.disabilities);
+        if let Some(ref mut stats) = self.stats {
+            if self.state.viewport == old_viewport {
+                stats.redundant_state_changes += 1;
+            }
+        }
         self.inner.borrow_mut().sink().pre_render().issue(com);
     }
 
@@ -3552,12 +5326,22 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
             panic!("More than one scissor set; Metal supports only one viewport");
         }
 
-        if let Some(com) = self.state.set_hal_scissor(rect) {
-            self.inner.borrow_mut().sink().pre_render().issue(com);
+        match self.state.set_hal_scissor(rect) {
+            Some(com) => self.inner.borrow_mut().sink().pre_render().issue(com),
+            None => {
+                if let Some(ref mut stats) = self.stats {
+                    stats.redundant_state_changes += 1;
+                }
+            }
         }
     }
 
     unsafe fn set_blend_constants(&mut self, color: pso::ColorValue) {
+        if let Some(ref mut stats) = self.stats {
+            if self.state.blend_color == Some(color) {
+                stats.redundant_state_changes += 1;
+            }
+        }
         let com = self.state.set_blend_color(&color);
         self.inner.borrow_mut().sink().pre_render().issue(com);
     }
@@ -3574,12 +5358,89 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
     }
 
     unsafe fn set_depth_bias(&mut self, depth_bias: pso::DepthBias) {
+        if depth_bias.representation != pso::DepthBiasRepresentation::Format || depth_bias.exact {
+            warn!(
+                "Depth bias representation is not supported: {:?}",
+                depth_bias
+            );
+        }
+        if let Some(ref mut stats) = self.stats {
+            if self.state.depth_bias == depth_bias {
+                stats.redundant_state_changes += 1;
+            }
+        }
         let com = self.state.set_depth_bias(&depth_bias);
         self.inner.borrow_mut().sink().pre_render().issue(com);
     }
 
+    unsafe fn set_cull_face(&mut self, faces: pso::Face) {
+        self.state.dynamic.cull_mode = match conv::map_cull_face(faces) {
+            Some(mode) => mode,
+            None => {
+                error!("Culling both sides is not yet supported");
+                metal::MTLCullMode::None
+            }
+        };
+        self.update_dynamic_states();
+    }
+
+    unsafe fn set_front_face(&mut self, front_face: pso::FrontFace) {
+        self.state.dynamic.front_winding = conv::map_winding(front_face);
+        self.update_dynamic_states();
+    }
+
+    unsafe fn set_primitive_topology(&mut self, primitive: pso::Primitive) {
+        self.state.dynamic.primitive_type = conv::map_primitive_topology(primitive).1;
+        self.update_dynamic_states();
+    }
+
+    unsafe fn set_depth_test(&mut self, depth: Option<pso::DepthTest>) {
+        self.state.dynamic.depth_test = depth;
+        self.update_depth_stencil();
+    }
+
+    unsafe fn set_stencil_faces(&mut self, faces: pso::Face, value: pso::StencilFace) {
+        if faces.contains(pso::Face::FRONT) {
+            self.state.dynamic.stencil_faces.front = value;
+        }
+        if faces.contains(pso::Face::BACK) {
+            self.state.dynamic.stencil_faces.back = value;
+        }
+        self.update_depth_stencil();
+    }
+
+    unsafe fn set_vertex_input(
+        &mut self,
+        buffers: &[pso::VertexBufferDesc],
+        attributes: &[pso::AttributeDesc],
+    ) {
+        self.state.dynamic.vertex_input = Some((buffers.to_vec(), attributes.to_vec()));
+        self.update_vertex_input();
+    }
+
+    unsafe fn set_primitive_restart(&mut self, _enable: bool) {
+        // Metal always restarts strips, pipelines with this dynamic state are rejected.
+        unimplemented!()
+    }
+
+    unsafe fn set_fragment_shading_rate(
+        &mut self,
+        _fragment_size: hal::window::Extent2D,
+        _combiner_ops: [pso::ShadingRateCombinerOp; 2],
+    ) {
+        // Rasterization rate maps scale the render targets down instead of
+        // shading coarser, which doesn't match the semantics of the rates.
+        unimplemented!()
+    }
+
     unsafe fn set_stencil_reference(&mut self, faces: pso::Face, value: pso::StencilValue) {
+        let old_values = self.state.stencil.reference_values;
         assign_sides(&mut self.state.stencil.reference_values, faces, value);
+        if let Some(ref mut stats) = self.stats {
+            if self.state.stencil.reference_values == old_values {
+                stats.redundant_state_changes += 1;
+            }
+        }
         let com =
             soft::RenderCommand::SetStencilReferenceValues(self.state.stencil.reference_values);
         self.inner.borrow_mut().sink().pre_render().issue(com);
@@ -3595,6 +5456,18 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         self.update_depth_stencil();
     }
 
+    unsafe fn set_sample_locations(&mut self, locations: &[pso::SampleLocation]) {
+        if !locations.is_empty() && !self.shared.private_caps.programmable_sample_positions {
+            error!("Programmable sample positions are not supported by the device");
+            return;
+        }
+        if locations.len() > MAX_SAMPLE_POSITIONS {
+            error!("Too many sample locations: {}", locations.len());
+            return;
+        }
+        self.state.sample_positions = locations.iter().cloned().collect();
+    }
+
     unsafe fn begin_render_pass<'a, T>(
         &mut self,
         render_pass: &native::RenderPass,
@@ -3610,10 +5483,20 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         self.temp.render_attachments.clear();
         for attachment in attachments {
             let v = attachment.image_view.borrow();
+            if cfg!(debug_assertions) {
+                framebuffer.check_view(self.temp.render_attachments.len(), v);
+            }
             self.temp
                 .render_attachments
                 .push((v.texture.clone(), attachment.clear_value));
         }
+        if self.temp.render_attachments.len() != framebuffer.attachments.len() {
+            error!(
+                "Render pass begins with {} attachments, but the framebuffer expects {}",
+                self.temp.render_attachments.len(),
+                framebuffer.attachments.len()
+            );
+        }
 
         self.state.pending_subpasses.clear();
         self.state.target.extent = framebuffer.extent;
@@ -3621,78 +5504,129 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         //Note: we stack the subpasses in the opposite order
         for subpass in render_pass.subpasses.iter().rev() {
             let mut combined_aspects = Aspects::empty();
-            let descriptor = autoreleasepool(|| {
-                let descriptor = self
-                    .pool_shared
-                    .render_pass_descriptors
-                    .lock()
-                    .alloc(&self.shared);
-                if self.shared.private_caps.layered_rendering {
-                    descriptor.set_render_target_array_length(framebuffer.extent.depth as _);
+            let mut depth_resolve = None;
+            let mut setup = RenderPassSetup::default();
+            if self.shared.private_caps.layered_rendering {
+                setup.render_target_array_length = Some(framebuffer.extent.depth as _);
+            }
+            setup.tile_layout = self.state.tile_layout;
+            setup.sample_positions = self.state.sample_positions.clone();
+
+            for at in subpass.attachments.colors.iter() {
+                let rat = &render_pass.attachments[at.id];
+                let &(ref texture, ref clear_value) = &self.temp.render_attachments[at.id];
+                let mut color = AttachmentSetup::new(texture);
+
+                combined_aspects |= Aspects::COLOR;
+
+                if at.ops.contains(native::AttachmentOps::LOAD) {
+                    color.load_action = Some(conv::map_load_operation(rat.ops.load));
+                    if rat.ops.load == AttachmentLoadOp::Clear {
+                        let c = at.channel.interpret(clear_value.color);
+                        color.clear_value = Some([c.red, c.green, c.blue, c.alpha]);
+                    }
+                }
+                if let Some(id) = at.resolve_id {
+                    let &(ref resolve_texture, _) = &self.temp.render_attachments[id];
+                    //Note: the selection of levels and slices is already handled by `ImageView`
+                    color.resolve_texture = Some(AsNative::from(resolve_texture.as_ref()));
+                    color.store_action = Some(conv::map_resolved_store_operation(rat.ops.store));
+                } else if at.ops.contains(native::AttachmentOps::STORE) {
+                    color.store_action = Some(conv::map_store_operation(rat.ops.store));
                 }
+                setup.colors.push(color);
+            }
 
-                for (i, at) in subpass.attachments.colors.iter().enumerate() {
-                    let rat = &render_pass.attachments[at.id];
-                    let &(ref texture, ref clear_value) = &self.temp.render_attachments[at.id];
-                    let desc = descriptor.color_attachments().object_at(i as _).unwrap();
+            if let Some(ref at) = subpass.attachments.depth_stencil {
+                let rat = &render_pass.attachments[at.id];
+                let &(ref texture, ref clear_value) = &self.temp.render_attachments[at.id];
+                let aspects = rat.format.unwrap().surface_desc().aspects;
+                combined_aspects |= aspects;
+                let caps = &self.shared.private_caps;
+                let (depth_mode, stencil_mode) = subpass.depth_stencil_resolve_modes;
 
-                    combined_aspects |= Aspects::COLOR;
-                    desc.set_texture(Some(texture.as_ref()));
+                if aspects.contains(Aspects::DEPTH) {
+                    let mut depth = AttachmentSetup::new(texture);
 
                     if at.ops.contains(native::AttachmentOps::LOAD) {
-                        desc.set_load_action(conv::map_load_operation(rat.ops.load));
+                        depth.load_action = Some(conv::map_load_operation(rat.ops.load));
                         if rat.ops.load == AttachmentLoadOp::Clear {
-                            desc.set_clear_color(at.channel.interpret(clear_value.color));
+                            depth.clear_value = Some(clear_value.depth_stencil.depth as f64);
                         }
                     }
-                    if let Some(id) = at.resolve_id {
-                        let &(ref resolve_texture, _) = &self.temp.render_attachments[id];
-                        //Note: the selection of levels and slices is already handled by `ImageView`
-                        desc.set_resolve_texture(Some(resolve_texture.as_ref()));
-                        desc.set_store_action(conv::map_resolved_store_operation(rat.ops.store));
-                    } else if at.ops.contains(native::AttachmentOps::STORE) {
-                        desc.set_store_action(conv::map_store_operation(rat.ops.store));
+                    match (at.resolve_id, depth_mode) {
+                        (Some(id), Some(mode))
+                            if caps.depth_resolve
+                                && (mode == ResolveMode::SampleZero
+                                    || caps.depth_stencil_resolve_filter) =>
+                        {
+                            let &(ref resolve_texture, _) = &self.temp.render_attachments[id];
+                            depth.resolve_texture = Some(AsNative::from(resolve_texture.as_ref()));
+                            depth.store_action =
+                                Some(conv::map_resolved_store_operation(rat.ops.store));
+                            if caps.depth_stencil_resolve_filter {
+                                setup.depth_resolve_filter =
+                                    Some(conv::map_depth_resolve_filter(mode));
+                            }
+                        }
+                        (Some(id), Some(mode)) => {
+                            // keep the samples around for the resolve draw
+                            depth.store_action = Some(metal::MTLStoreAction::Store);
+                            let &(ref resolve_texture, _) = &self.temp.render_attachments[id];
+                            depth_resolve = Some(DepthResolve {
+                                source: texture.clone(),
+                                destination: resolve_texture.clone(),
+                                aspects,
+                                mode,
+                            });
+                        }
+                        _ => {
+                            if at.ops.contains(native::AttachmentOps::STORE) {
+                                depth.store_action = Some(conv::map_store_operation(rat.ops.store));
+                            }
+                        }
                     }
+                    setup.depth = Some(depth);
                 }
+                if aspects.contains(Aspects::STENCIL) {
+                    let mut stencil = AttachmentSetup::new(texture);
 
-                if let Some(ref at) = subpass.attachments.depth_stencil {
-                    let rat = &render_pass.attachments[at.id];
-                    let &(ref texture, ref clear_value) = &self.temp.render_attachments[at.id];
-                    let aspects = rat.format.unwrap().surface_desc().aspects;
-                    combined_aspects |= aspects;
-
-                    if aspects.contains(Aspects::DEPTH) {
-                        let desc = descriptor.depth_attachment().unwrap();
-                        desc.set_texture(Some(texture.as_ref()));
-
-                        if at.ops.contains(native::AttachmentOps::LOAD) {
-                            desc.set_load_action(conv::map_load_operation(rat.ops.load));
-                            if rat.ops.load == AttachmentLoadOp::Clear {
-                                desc.set_clear_depth(clear_value.depth_stencil.depth as f64);
-                            }
-                        }
-                        if at.ops.contains(native::AttachmentOps::STORE) {
-                            desc.set_store_action(conv::map_store_operation(rat.ops.store));
+                    if at.ops.contains(native::AttachmentOps::LOAD) {
+                        stencil.load_action = Some(conv::map_load_operation(rat.stencil_ops.load));
+                        if rat.stencil_ops.load == AttachmentLoadOp::Clear {
+                            stencil.clear_value = Some(clear_value.depth_stencil.stencil);
                         }
                     }
-                    if aspects.contains(Aspects::STENCIL) {
-                        let desc = descriptor.stencil_attachment().unwrap();
-                        desc.set_texture(Some(texture.as_ref()));
-
-                        if at.ops.contains(native::AttachmentOps::LOAD) {
-                            desc.set_load_action(conv::map_load_operation(rat.stencil_ops.load));
-                            if rat.stencil_ops.load == AttachmentLoadOp::Clear {
-                                desc.set_clear_stencil(clear_value.depth_stencil.stencil);
-                            }
+                    match (at.resolve_id, stencil_mode) {
+                        // only the sample zero can be taken for the stencil
+                        (Some(id), Some(ResolveMode::SampleZero))
+                            if caps.depth_stencil_resolve_filter =>
+                        {
+                            let &(ref resolve_texture, _) = &self.temp.render_attachments[id];
+                            stencil.resolve_texture =
+                                Some(AsNative::from(resolve_texture.as_ref()));
+                            stencil.store_action =
+                                Some(conv::map_resolved_store_operation(rat.stencil_ops.store));
                         }
-                        if at.ops.contains(native::AttachmentOps::STORE) {
-                            desc.set_store_action(conv::map_store_operation(rat.stencil_ops.store));
+                        (resolve_id, mode) => {
+                            if let (Some(_), Some(mode)) = (resolve_id, mode) {
+                                warn!("Stencil resolve with {:?} is not supported", mode);
+                            }
+                            if at.ops.contains(native::AttachmentOps::STORE) {
+                                stencil.store_action =
+                                    Some(conv::map_store_operation(rat.stencil_ops.store));
+                            }
                         }
                     }
+                    setup.stencil = Some(stencil);
                 }
+            }
 
-                descriptor
-            });
+            let descriptor = self
+                .pool_shared
+                .render_pass_descriptors
+                .lock()
+                .alloc_configured(setup, &self.shared);
 
             self.state.pending_subpasses.alloc().init(SubpassInfo {
                 descriptor,
@@ -3700,6 +5634,8 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                 formats: subpass.attachments.map(|at| (at.format, at.channel)),
                 operations: subpass.attachments.map(|at| at.ops),
                 sample_count: subpass.samples,
+                depth_resolve,
+                merged: subpass.merged,
             });
         }
 
@@ -3708,62 +5644,147 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
     }
 
     unsafe fn next_subpass(&mut self, _contents: com::SubpassContents) {
-        let sin = self.state.pending_subpasses.pop().unwrap();
+        self.with_autorelease_pool(|cmd_buffer| cmd_buffer.begin_subpass());
+    }
 
-        self.state.render_pso_is_compatible = match self.state.render_pso {
-            Some(ref ps) => ps.formats == sin.formats,
-            None => false,
-        };
-        self.state.active_depth_stencil_desc = pso::DepthStencilDesc::default();
-        self.state.active_scissor = MTLScissorRect {
-            x: 0,
-            y: 0,
-            width: self.state.target.extent.width as u64,
-            height: self.state.target.extent.height as u64,
-        };
-        self.state.target.aspects = sin.combined_aspects;
-        self.state.target.formats = sin.formats.clone();
-        self.state.target.samples = sin.sample_count;
+    unsafe fn end_render_pass(&mut self) {
+        self.inner.borrow_mut().sink().stop_encoding();
+        if let Some(resolve) = self.state.active_depth_resolve.take() {
+            self.with_autorelease_pool(|cmd_buffer| cmd_buffer.resolve_depth(resolve));
+        }
+    }
 
-        let com_scissor = self.state.reset_scissor();
+    unsafe fn begin_rendering(&mut self, info: &com::RenderingInfo<Backend>) {
+        profiling::scope!("begin_rendering");
+        let mut combined_aspects = Aspects::empty();
+        let mut depth_resolve = None;
+        let mut formats = native::SubpassFormats::default();
+        let mut setup = RenderPassSetup::default();
+        if self.shared.private_caps.layered_rendering {
+            setup.render_target_array_length = Some(info.layers as _);
+        }
+        setup.tile_layout = self.state.tile_layout;
+        setup.sample_positions = self.state.sample_positions.clone();
 
-        let ds_store = &self.shared.service_pipes.depth_stencil_states;
-        let ds_state;
-        let com_ds = match self.state.build_depth_stencil() {
-            Some(desc) => {
-                ds_state = ds_store.get(desc, &self.shared.device);
-                Some(soft::RenderCommand::SetDepthStencilState(&**ds_state))
+        for at in info.colors {
+            let channel = Channel::from(at.format.base_format().1);
+            let mut color = AttachmentSetup::new(&at.image_view.texture);
+            combined_aspects |= Aspects::COLOR;
+
+            color.load_action = Some(conv::map_load_operation(at.ops.load));
+            if at.ops.load == AttachmentLoadOp::Clear {
+                let c = channel.interpret(at.clear_value.color);
+                color.clear_value = Some([c.red, c.green, c.blue, c.alpha]);
+            }
+            if let Some((resolve_view, _)) = at.resolve {
+                color.resolve_texture = Some(AsNative::from(resolve_view.texture.as_ref()));
+                color.store_action = Some(conv::map_resolved_store_operation(at.ops.store));
+            } else {
+                color.store_action = Some(conv::map_store_operation(at.ops.store));
+            }
+            setup.colors.push(color);
+            formats.colors.push((at.image_view.mtl_format, channel));
+        }
+
+        if let Some(ref at) = info.depth_stencil {
+            let texture = &at.image_view.texture;
+            let aspects = at.format.surface_desc().aspects;
+            combined_aspects |= aspects;
+
+            if aspects.contains(Aspects::DEPTH) {
+                let mut depth = AttachmentSetup::new(texture);
+                depth.load_action = Some(conv::map_load_operation(at.ops.load));
+                if at.ops.load == AttachmentLoadOp::Clear {
+                    depth.clear_value = Some(at.clear_value.depth_stencil.depth as f64);
+                }
+                match at.resolve {
+                    Some((resolve_view, _)) if self.shared.private_caps.depth_resolve => {
+                        depth.resolve_texture = Some(AsNative::from(resolve_view.texture.as_ref()));
+                        depth.store_action = Some(conv::map_resolved_store_operation(at.ops.store));
+                    }
+                    Some((resolve_view, _)) => {
+                        // keep the samples around for the resolve draw
+                        depth.store_action = Some(metal::MTLStoreAction::Store);
+                        depth_resolve = Some(DepthResolve {
+                            source: texture.clone(),
+                            destination: resolve_view.texture.clone(),
+                            aspects,
+                            mode: ResolveMode::SampleZero,
+                        });
+                    }
+                    None => {
+                        depth.store_action = Some(conv::map_store_operation(at.ops.store));
+                    }
+                }
+                setup.depth = Some(depth);
+            }
+            if aspects.contains(Aspects::STENCIL) {
+                let mut stencil = AttachmentSetup::new(texture);
+                stencil.load_action = Some(conv::map_load_operation(at.stencil_ops.load));
+                if at.stencil_ops.load == AttachmentLoadOp::Clear {
+                    stencil.clear_value = Some(at.clear_value.depth_stencil.stencil);
+                }
+                match at.resolve {
+                    Some((resolve_view, _))
+                        if self.shared.private_caps.depth_stencil_resolve_filter =>
+                    {
+                        stencil.resolve_texture =
+                            Some(AsNative::from(resolve_view.texture.as_ref()));
+                        stencil.store_action =
+                            Some(conv::map_resolved_store_operation(at.stencil_ops.store));
+                    }
+                    _ => {
+                        stencil.store_action =
+                            Some(conv::map_store_operation(at.stencil_ops.store));
+                    }
+                }
+                setup.stencil = Some(stencil);
+            }
+            formats.depth_stencil = Some((at.image_view.mtl_format, Channel::Float));
+        }
+
+        let texture = match info.colors.first().or(info.depth_stencil.as_ref()) {
+            Some(at) => &at.image_view.texture,
+            None => {
+                error!("Rendering begins without attachments");
+                return;
             }
-            None => None,
+        };
+        self.state.target.extent = i::Extent {
+            width: texture.width() as _,
+            height: texture.height() as _,
+            depth: info.layers as _,
         };
 
-        let (mut temp_binding_sizes_vs, mut temp_binding_sizes_ps) = (Vec::new(), Vec::new()); //TODO: avoid the heap?
-        let init_commands = self
-            .state
-            .make_render_commands(
-                sin.combined_aspects,
-                &mut temp_binding_sizes_vs,
-                &mut temp_binding_sizes_ps,
-            )
-            .chain(com_scissor)
-            .chain(com_ds);
+        let descriptor = self
+            .pool_shared
+            .render_pass_descriptors
+            .lock()
+            .alloc_configured(setup, &self.shared);
 
-        autoreleasepool(|| {
-            self.inner
-                .borrow_mut()
-                .sink()
-                .switch_render(sin.descriptor, &self.pool_shared)
-                .issue_many(init_commands);
+        self.state.pending_subpasses.clear();
+        self.state.pending_subpasses.alloc().init(SubpassInfo {
+            descriptor,
+            combined_aspects,
+            operations: formats.map(|_| native::AttachmentOps::all()),
+            formats,
+            sample_count: info.samples,
+            depth_resolve,
+            merged: false,
         });
+        self.with_autorelease_pool(|cmd_buffer| cmd_buffer.begin_subpass());
     }
 
-    unsafe fn end_render_pass(&mut self) {
-        self.inner.borrow_mut().sink().stop_encoding();
+    unsafe fn end_rendering(&mut self) {
+        self.end_render_pass();
     }
 
     unsafe fn bind_graphics_pipeline(&mut self, pipeline: &native::GraphicsPipeline) {
         profiling::scope!("bind_graphics_pipeline");
         let mut inner = self.inner.borrow_mut();
+        if pipeline.writes_storage {
+            inner.sink().seal_render();
+        }
         let mut pre = inner.sink().pre_render();
 
         self.state.stage_infos.vs.assign_from(&pipeline.vs_info);
@@ -3793,27 +5814,40 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                 ps.vertex_buffers
                     .extend(pipeline.vertex_buffers.iter().cloned().map(Some));
                 ps.ds_desc = pipeline.depth_stencil_desc;
+                ps.dynamic_states = pipeline.dynamic_states;
+                ps.vertex_input = pipeline.vertex_input.clone();
                 ps.formats = pipeline.attachment_formats.clone();
+                ps.writes_storage = pipeline.writes_storage;
                 true
             }
             None => {
                 self.state.render_pso = Some(RenderPipelineState {
                     raw: pipeline.raw.to_owned(),
                     ds_desc: pipeline.depth_stencil_desc,
+                    dynamic_states: pipeline.dynamic_states,
                     vertex_buffers: pipeline.vertex_buffers.iter().cloned().map(Some).collect(),
+                    vertex_input: pipeline.vertex_input.clone(),
                     formats: pipeline.attachment_formats.clone(),
+                    writes_storage: pipeline.writes_storage,
                 });
                 true
             }
         };
+        if let Some(ref mut stats) = self.stats {
+            stats.pipeline_binds += 1;
+            if !set_pipeline {
+                stats.redundant_state_changes += 1;
+            }
+        }
 
         if self.state.render_pso_is_compatible {
             if set_pipeline {
                 self.state.rasterizer_state = pipeline.rasterizer_state.clone();
                 self.state.primitive_type = pipeline.primitive_type;
+                self.state.apply_dynamic_states(pipeline.dynamic_states);
 
                 pre.issue(soft::RenderCommand::BindPipeline(&*pipeline.raw));
-                if let Some(ref rs) = pipeline.rasterizer_state {
+                if let Some(ref rs) = self.state.rasterizer_state {
                     pre.issue(soft::RenderCommand::SetRasterizerState(rs.clone()))
                 }
                 // re-bind vertex buffers
@@ -3861,7 +5895,7 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                         words: &self.temp.binding_sizes,
                     });
                 }
-            } else {
+            } else if pipeline.dynamic_states.is_empty() {
                 debug_assert_eq!(self.state.rasterizer_state, pipeline.rasterizer_state);
                 debug_assert_eq!(self.state.primitive_type, pipeline.primitive_type);
             }
@@ -3876,6 +5910,7 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
             // (this overwriting these), or a new render pass started (thus using these).
             self.state.rasterizer_state = pipeline.rasterizer_state.clone();
             self.state.primitive_type = pipeline.primitive_type;
+            self.state.apply_dynamic_states(pipeline.dynamic_states);
         }
 
         if let pso::State::Static(value) = pipeline.depth_bias {
@@ -3894,6 +5929,12 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         if let Some(ref color) = pipeline.baked_states.blend_constants {
             pre.issue(self.state.set_blend_color(color));
         }
+
+        if pipeline.vertex_input.is_some() {
+            drop(pre);
+            drop(inner);
+            self.update_vertex_input();
+        }
     }
 
     unsafe fn bind_graphics_descriptor_sets<'a, I, J>(
@@ -3923,6 +5964,7 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
 
         let mut changes_sizes_buffer_stages = pso::ShaderStageFlags::empty();
         let mut dynamic_offset_iter = dynamic_offsets;
+        let mut texel_mirrors = Vec::new();
         let mut inner = self.inner.borrow_mut();
         let mut pre = inner.sink().pre_render();
         let mut bind_range = {
@@ -3936,6 +5978,7 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         for (set_offset, (info, desc_set)) in
             pipe_layout.infos[first_set..].iter().zip(sets).enumerate()
         {
+            self.collect_texel_mirrors(desc_set, &mut texel_mirrors);
             match *desc_set {
                 native::DescriptorSet::Emulated {
                     ref pool,
@@ -3975,6 +6018,7 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                     ref pool,
                     ref range,
                     stage_flags,
+                    update_after_bind,
                     ..
                 } => {
                     //Note: this is incompatible with the binding scheme below
@@ -4008,17 +6052,14 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                         let graphics_resources = &mut self.state.descriptor_sets
                             [first_set + set_offset]
                             .graphics_resources;
-                        graphics_resources.clear();
-                        graphics_resources.extend(
-                            pool.read().resources[range.start as usize..range.end as usize]
-                                .iter()
-                                .filter_map(|ur| {
-                                    ptr::NonNull::new(ur.ptr).map(|res| (res, ur.usage))
-                                }),
-                        );
-                        pre.issue_many(graphics_resources.iter().map(|&(resource, usage)| {
-                            soft::RenderCommand::UseResource { resource, usage }
-                        }));
+                        if update_after_bind {
+                            graphics_resources.track(pool, range.clone());
+                        } else {
+                            graphics_resources.fill(
+                                &pool.read().resources[range.start as usize..range.end as usize],
+                            );
+                        }
+                        pre.issue_many(graphics_resources.render_commands());
                     }
                 }
             }
@@ -4074,12 +6115,23 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                 }
             }
         }
+        inner.use_texel_mirrors(texel_mirrors);
     }
 
     unsafe fn bind_compute_pipeline(&mut self, pipeline: &native::ComputePipeline) {
         profiling::scope!("bind_compute_pipeline");
+        if let Some(ref mut stats) = self.stats {
+            stats.pipeline_binds += 1;
+            match self.state.compute_pso {
+                Some(ref pso) if pso.as_ptr() == pipeline.raw.as_ptr() => {
+                    stats.redundant_state_changes += 1;
+                }
+                _ => {}
+            }
+        }
         self.state.compute_pso = Some(pipeline.raw.clone());
         self.state.work_group_size = pipeline.work_group_size;
+        self.state.compute_base_slot = pipeline.base_slot;
         self.state.stage_infos.cs.assign_from(&pipeline.info);
 
         let mut inner = self.inner.borrow_mut();
@@ -4120,6 +6172,7 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
 
         let mut changes_sizes_buffer = false;
         let mut dynamic_offset_iter = dynamic_offsets;
+        let mut texel_mirrors = Vec::new();
         let mut inner = self.inner.borrow_mut();
         let mut pre = inner.sink().pre_compute();
         let mut bind_range = pipe_layout.infos[first_set].offsets.cs.map(|&i| i..i);
@@ -4128,6 +6181,7 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
             pipe_layout.infos[first_set..].iter().zip(sets).enumerate()
         {
             let res_offset = &info.offsets.cs;
+            self.collect_texel_mirrors(desc_set, &mut texel_mirrors);
             match *desc_set {
                 native::DescriptorSet::Emulated {
                     ref pool,
@@ -4162,6 +6216,7 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                     ref pool,
                     ref range,
                     stage_flags,
+                    update_after_bind,
                     ..
                 } => {
                     if stage_flags.contains(pso::ShaderStageFlags::COMPUTE) {
@@ -4178,17 +6233,14 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                         let compute_resources = &mut self.state.descriptor_sets
                             [first_set + set_offset]
                             .compute_resources;
-                        compute_resources.clear();
-                        compute_resources.extend(
-                            pool.read().resources[range.start as usize..range.end as usize]
-                                .iter()
-                                .filter_map(|ur| {
-                                    ptr::NonNull::new(ur.ptr).map(|res| (res, ur.usage))
-                                }),
-                        );
-                        pre.issue_many(compute_resources.iter().map(|&(resource, usage)| {
-                            soft::ComputeCommand::UseResource { resource, usage }
-                        }));
+                        if update_after_bind {
+                            compute_resources.track(pool, range.clone());
+                        } else {
+                            compute_resources.fill(
+                                &pool.read().resources[range.start as usize..range.end as usize],
+                            );
+                        }
+                        pre.issue_many(compute_resources.compute_commands());
                     }
                 }
             }
@@ -4230,29 +6282,18 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                 });
             }
         }
+        inner.use_texel_mirrors(texel_mirrors);
     }
 
     unsafe fn dispatch(&mut self, count: WorkGroupCount) {
-        let mut inner = self.inner.borrow_mut();
-        let (mut pre, init) = inner.sink().switch_compute();
-        if init {
-            pre.issue_many(
-                self.state
-                    .make_compute_commands(&mut self.temp.binding_sizes),
-            );
-        }
-
-        pre.issue(soft::ComputeCommand::Dispatch {
-            wg_size: self.state.work_group_size,
-            wg_count: MTLSize {
-                width: count[0] as _,
-                height: count[1] as _,
-                depth: count[2] as _,
-            },
-        });
+        self.dispatch_base([0; 3], count);
     }
 
     unsafe fn dispatch_indirect(&mut self, buffer: &native::Buffer, offset: buffer::Offset) {
+        if let Some(ref mut stats) = self.stats {
+            stats.dispatches += 1;
+        }
+        let addressed_changed = self.update_addressed_resources();
         let mut inner = self.inner.borrow_mut();
         let (mut pre, init) = inner.sink().switch_compute();
         if init {
@@ -4260,11 +6301,19 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                 self.state
                     .make_compute_commands(&mut self.temp.binding_sizes),
             );
+        } else if addressed_changed {
+            pre.issue_many(self.state.addressed_resources.compute_commands());
         }
 
         let (raw, range) = buffer.as_bound();
         assert!(range.start + offset < range.end);
 
+        if let Some(index) = self.state.compute_base_slot {
+            pre.issue(soft::ComputeCommand::BindBufferData {
+                index,
+                words: &[0; 4],
+            });
+        }
         pre.issue(soft::ComputeCommand::DispatchIndirect {
             wg_size: self.state.work_group_size,
             buffer: AsNative::from(raw),
@@ -4272,6 +6321,42 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         });
     }
 
+    unsafe fn build_acceleration_structure(
+        &mut self,
+        _desc: &hal::acceleration_structure::BuildDesc<Backend>,
+        _ranges: &[hal::acceleration_structure::BuildRangeDesc],
+    ) {
+        unimplemented!()
+    }
+
+    unsafe fn bind_ray_tracing_pipeline(&mut self, _pipeline: &()) {
+        unimplemented!()
+    }
+
+    unsafe fn bind_ray_tracing_descriptor_sets<'a, I, J>(
+        &mut self,
+        _layout: &native::PipelineLayout,
+        _first_set: usize,
+        _sets: I,
+        _offsets: J,
+    ) where
+        I: Iterator<Item = &'a native::DescriptorSet>,
+        J: Iterator<Item = com::DescriptorSetOffset>,
+    {
+        unimplemented!()
+    }
+
+    unsafe fn trace_rays(
+        &mut self,
+        _raygen: &pso::ShaderBindingTable<Backend>,
+        _miss: Option<&pso::ShaderBindingTable<Backend>>,
+        _hit_group: Option<&pso::ShaderBindingTable<Backend>>,
+        _callable: Option<&pso::ShaderBindingTable<Backend>>,
+        _dimensions: [u32; 3],
+    ) {
+        unimplemented!()
+    }
+
     unsafe fn copy_buffer<T>(&mut self, src: &native::Buffer, dst: &native::Buffer, regions: T)
     where
         T: Iterator<Item = com::BufferCopy>,
@@ -4359,6 +6444,7 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
             // first is bind PSO
             sink.quick_compute("copy_buffer", compute_commands.into_iter());
         }
+        self.refresh_texel_mirrors(&mut inner, dst_raw);
     }
 
     unsafe fn copy_image<T>(
@@ -4385,7 +6471,9 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                     ..
                 } = *self.inner.borrow_mut();
 
-                let new_dst = if src.mtl_format == dst.mtl_format {
+                // The planes of two-plane images are separate textures, copied as they are.
+                let planar = src.chroma_plane.is_some() || dst.chroma_plane.is_some();
+                let new_dst = if src.mtl_format == dst.mtl_format || planar {
                     dst_raw
                 } else {
                     assert_eq!(src.format_desc.bits, dst.format_desc.bits);
@@ -4397,6 +6485,12 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                 let commands = regions.filter_map(|r| {
                     if r.extent.is_empty() {
                         None
+                    } else if planar {
+                        Some(soft::BlitCommand::CopyImage {
+                            src: AsNative::from(src.plane(r.src_subresource.aspects).0),
+                            dst: AsNative::from(dst.plane(r.dst_subresource.aspects).0),
+                            region: r.clone(),
+                        })
                     } else {
                         Some(soft::BlitCommand::CopyImage {
                             src: AsNative::from(src_raw.as_ref()),
@@ -4468,16 +6562,20 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
             native::ImageLike::Unbound { .. } => {
                 panic!("Unexpected Image::Unbound");
             }
-            native::ImageLike::Texture(ref dst_raw) => {
+            native::ImageLike::Texture(ref dst_raw) if dst.decompression.is_some() => {
+                self.copy_buffer_to_decompressed_image(src, dst, dst_raw, regions);
+            }
+            native::ImageLike::Texture(_) => {
                 let (src_raw, src_range) = src.as_bound();
                 let commands = regions.filter_map(|r| {
                     if r.image_extent.is_empty() {
                         None
                     } else {
+                        let (dst_raw, dst_desc) = dst.plane(r.image_layers.aspects);
                         Some(soft::BlitCommand::CopyBufferToImage {
                             src: AsNative::from(src_raw),
-                            dst: AsNative::from(dst_raw.as_ref()),
-                            dst_desc: dst.format_desc,
+                            dst: AsNative::from(dst_raw),
+                            dst_desc,
                             region: com::BufferImageCopy {
                                 buffer_offset: r.buffer_offset + src_range.start,
                                 ..r.clone()
@@ -4487,15 +6585,19 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                 });
                 self.inner.borrow_mut().sink().blit_commands(commands);
             }
-            native::ImageLike::Buffer(ref dst_buffer) => self.copy_buffer(
-                src,
-                dst_buffer,
-                regions.map(|r| com::BufferCopy {
-                    src: r.buffer_offset,
-                    dst: dst.byte_offset(r.image_offset),
-                    size: dst.byte_extent(r.image_extent),
-                }),
-            ),
+            native::ImageLike::Buffer(ref dst_buffer) => {
+                let (_, src_range) = src.as_bound();
+                let src_size = src_range.end - src_range.start;
+                let copies = regions
+                    .flat_map(|r| dst.linear_copies(&r, src_size))
+                    .map(|c| com::BufferCopy {
+                        src: c.buffer,
+                        dst: c.image,
+                        size: c.size,
+                    })
+                    .collect::<Vec<_>>();
+                self.copy_buffer(src, dst_buffer, copies.into_iter())
+            }
         }
     }
 
@@ -4512,15 +6614,22 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
             native::ImageLike::Unbound { .. } => {
                 panic!("Unexpected Image::Unbound");
             }
-            native::ImageLike::Texture(ref src_raw) => {
+            native::ImageLike::Texture(_) if src.decompression.is_some() => {
+                error!(
+                    "Unable to copy from a decompressed {:?} image",
+                    src.decompression
+                );
+            }
+            native::ImageLike::Texture(_) => {
                 let (dst_raw, dst_range) = dst.as_bound();
                 let commands = regions.filter_map(|r| {
                     if r.image_extent.is_empty() {
                         None
                     } else {
+                        let (src_raw, src_desc) = src.plane(r.image_layers.aspects);
                         Some(soft::BlitCommand::CopyImageToBuffer {
-                            src: AsNative::from(src_raw.as_ref()),
-                            src_desc: src.format_desc,
+                            src: AsNative::from(src_raw),
+                            src_desc,
                             dst: AsNative::from(dst_raw),
                             region: com::BufferImageCopy {
                                 buffer_offset: r.buffer_offset + dst_range.start,
@@ -4529,17 +6638,23 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                         })
                     }
                 });
-                self.inner.borrow_mut().sink().blit_commands(commands);
+                let mut inner = self.inner.borrow_mut();
+                inner.sink().blit_commands(commands);
+                self.refresh_texel_mirrors(&mut inner, dst_raw);
+            }
+            native::ImageLike::Buffer(ref src_buffer) => {
+                let (_, dst_range) = dst.as_bound();
+                let dst_size = dst_range.end - dst_range.start;
+                let copies = regions
+                    .flat_map(|r| src.linear_copies(&r, dst_size))
+                    .map(|c| com::BufferCopy {
+                        src: c.image,
+                        dst: c.buffer,
+                        size: c.size,
+                    })
+                    .collect::<Vec<_>>();
+                self.copy_buffer(src_buffer, dst, copies.into_iter())
             }
-            native::ImageLike::Buffer(ref src_buffer) => self.copy_buffer(
-                src_buffer,
-                dst,
-                regions.map(|r| com::BufferCopy {
-                    src: src.byte_offset(r.image_offset),
-                    dst: r.buffer_offset,
-                    size: src.byte_extent(r.image_extent),
-                }),
-            ),
         }
     }
 
@@ -4549,6 +6664,9 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
             return;
         }
         profiling::scope!("draw");
+        if let Some(ref mut stats) = self.stats {
+            stats.draws += 1;
+        }
 
         let command = soft::RenderCommand::Draw {
             primitive_type: self.state.primitive_type,
@@ -4569,6 +6687,9 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
             return;
         }
         profiling::scope!("draw_indexed");
+        if let Some(ref mut stats) = self.stats {
+            stats.draws += 1;
+        }
 
         let command = soft::RenderCommand::DrawIndexed {
             primitive_type: self.state.primitive_type,
@@ -4584,6 +6705,71 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         self.inner.borrow_mut().sink().pre_render().issue(command);
     }
 
+    unsafe fn draw_multi<I>(&mut self, vertices: I, instances: Range<InstanceCount>)
+    where
+        I: Iterator<Item = Range<VertexCount>>,
+    {
+        debug_assert!(self.state.render_pso_is_compatible);
+        if instances.start == instances.end {
+            return;
+        }
+        profiling::scope!("draw_multi");
+
+        let primitive_type = self.state.primitive_type;
+        let stats = &mut self.stats;
+        let commands = vertices.map(|vertices| {
+            if let Some(ref mut stats) = *stats {
+                stats.draws += 1;
+            }
+            soft::RenderCommand::Draw {
+                primitive_type,
+                vertices,
+                instances: instances.clone(),
+            }
+        });
+        self.inner
+            .borrow_mut()
+            .sink()
+            .pre_render()
+            .issue_many(commands);
+    }
+
+    unsafe fn draw_multi_indexed<I>(&mut self, draws: I, instances: Range<InstanceCount>)
+    where
+        I: Iterator<Item = (Range<IndexCount>, VertexOffset)>,
+    {
+        debug_assert!(self.state.render_pso_is_compatible);
+        if instances.start == instances.end {
+            return;
+        }
+        profiling::scope!("draw_multi_indexed");
+
+        let primitive_type = self.state.primitive_type;
+        let index = self
+            .state
+            .index_buffer
+            .clone()
+            .expect("must bind index buffer");
+        let stats = &mut self.stats;
+        let commands = draws.map(|(indices, base_vertex)| {
+            if let Some(ref mut stats) = *stats {
+                stats.draws += 1;
+            }
+            soft::RenderCommand::DrawIndexed {
+                primitive_type,
+                index: index.clone(),
+                indices,
+                base_vertex,
+                instances: instances.clone(),
+            }
+        });
+        self.inner
+            .borrow_mut()
+            .sink()
+            .pre_render()
+            .issue_many(commands);
+    }
+
     unsafe fn draw_indirect(
         &mut self,
         buffer: &native::Buffer,
@@ -4594,6 +6780,9 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         assert_eq!(offset % WORD_ALIGNMENT, 0);
         assert_eq!(stride % WORD_ALIGNMENT as u32, 0);
         debug_assert!(self.state.render_pso_is_compatible);
+        if let Some(ref mut stats) = self.stats {
+            stats.draws += count;
+        }
         let (raw, range) = buffer.as_bound();
 
         let commands = (0..count).map(|i| soft::RenderCommand::DrawIndirect {
@@ -4619,6 +6808,9 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         assert_eq!(offset % WORD_ALIGNMENT, 0);
         assert_eq!(stride % WORD_ALIGNMENT as u32, 0);
         debug_assert!(self.state.render_pso_is_compatible);
+        if let Some(ref mut stats) = self.stats {
+            stats.draws += count;
+        }
         let (raw, range) = buffer.as_bound();
 
         let commands = (0..count).map(|i| soft::RenderCommand::DrawIndexedIndirect {
@@ -4694,6 +6886,9 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
             .borrow_mut()
             .events
             .push((Arc::clone(&event.0), true));
+        if let Some(ref mut sink) = self.inner.borrow_mut().sink {
+            sink.seal_render();
+        }
     }
 
     unsafe fn reset_event(&mut self, event: &native::Event, _: pso::PipelineStage) {
@@ -4701,6 +6896,9 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
             .borrow_mut()
             .events
             .push((Arc::clone(&event.0), false));
+        if let Some(ref mut sink) = self.inner.borrow_mut().sink {
+            sink.seal_render();
+        }
     }
 
     unsafe fn wait_events<'a, I, J>(
@@ -4749,6 +6947,14 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                 self.inner.borrow_mut().sink().pre_render().issue(com);
             }
             native::QueryPool::Timestamp => {}
+            native::QueryPool::PerformanceCounters(ref pool) => {
+                // the counters are sampled at the draw boundaries of render passes only
+                let com = soft::RenderCommand::SampleCounters {
+                    buffer: pool.sample_buffer(),
+                    index: CounterPool::sample_index(query.id),
+                };
+                self.inner.borrow_mut().sink().pre_render().issue(com);
+            }
         }
     }
 
@@ -4767,6 +6973,13 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                 inner.sink().pre_render().issue(com);
             }
             native::QueryPool::Timestamp => {}
+            native::QueryPool::PerformanceCounters(ref pool) => {
+                let com = soft::RenderCommand::SampleCounters {
+                    buffer: pool.sample_buffer(),
+                    index: CounterPool::sample_index(query.id) + 1,
+                };
+                self.inner.borrow_mut().sink().pre_render().issue(com);
+            }
         }
     }
 
@@ -4803,6 +7016,8 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                 inner.sink().blit_commands(commands);
             }
             native::QueryPool::Timestamp => {}
+            // the samples are overwritten when the queries are recorded again
+            native::QueryPool::PerformanceCounters(_) => {}
         }
     }
 
@@ -4923,6 +7138,9 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                     .sink()
                     .blit_commands(iter::once(command));
             }
+            native::QueryPool::PerformanceCounters(_) => {
+                error!("Performance counter results can't be copied to buffers");
+            }
         }
     }
 
@@ -4930,6 +7148,20 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         // nothing to do, timestamps are unsupported on Metal
     }
 
+    unsafe fn begin_conditional_rendering(
+        &mut self,
+        _buffer: &native::Buffer,
+        _offset: buffer::Offset,
+        _flags: com::ConditionalRenderingFlags,
+    ) {
+        // Metal has no predication of the encoded commands
+        unimplemented!()
+    }
+
+    unsafe fn end_conditional_rendering(&mut self) {
+        unimplemented!()
+    }
+
     unsafe fn push_graphics_constants(
         &mut self,
         layout: &native::PipelineLayout,
@@ -4937,8 +7169,12 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         offset: u32,
         constants: &[u32],
     ) {
-        self.state
-            .update_push_constants(offset, constants, layout.total_push_constants);
+        self.state.update_push_constants(
+            &self.shared.device,
+            offset,
+            constants,
+            layout.total_push_constants,
+        );
         if stages.intersects(pso::ShaderStageFlags::GRAPHICS) {
             let mut inner = self.inner.borrow_mut();
             let mut pre = inner.sink().pre_render();
@@ -4960,8 +7196,12 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         offset: u32,
         constants: &[u32],
     ) {
-        self.state
-            .update_push_constants(offset, constants, layout.total_push_constants);
+        self.state.update_push_constants(
+            &self.shared.device,
+            offset,
+            constants,
+            layout.total_push_constants,
+        );
         let pc = layout.push_constants.cs.unwrap();
 
         // Note: the whole range is re-uploaded, which may be inefficient
@@ -4995,13 +7235,10 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                 .zip(&cmd_buffer.state.descriptor_sets)
             {
                 if !b.graphics_resources.is_empty() {
-                    a.graphics_resources.clear();
-                    a.graphics_resources
-                        .extend_from_slice(&b.graphics_resources);
+                    a.graphics_resources.clone_from(&b.graphics_resources);
                 }
                 if !b.compute_resources.is_empty() {
-                    a.compute_resources.clear();
-                    a.compute_resources.extend_from_slice(&b.compute_resources);
+                    a.compute_resources.clone_from(&b.compute_resources);
                 }
             }
 
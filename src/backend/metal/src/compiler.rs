@@ -0,0 +1,201 @@
+//! Worker pool for translating and compiling shader libraries.
+//!
+//! Translating a shader stage to MSL and compiling the result takes the bulk of
+//! the pipeline creation time. The work is handed to a few worker threads, so that
+//! the stages of a pipeline, as well as the pipelines created from different threads,
+//! are compiled concurrently instead of one after another.
+//!
+//! Libraries are shared by key: a module that is requested again with the same
+//! options, while it's still being compiled or afterwards, is only compiled once.
+
+use crate::{native as n, FastHashMap};
+
+use objc::rc::autoreleasepool;
+use parking_lot::{Condvar, Mutex};
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+    thread,
+};
+
+/// Maximum number of worker threads of a device.
+const MAX_WORKERS: usize = 4;
+
+pub(crate) type LibraryResult = Result<n::ModuleInfo, String>;
+
+type Job = Box<dyn FnOnce() -> LibraryResult + Send>;
+
+/// Shader module and options a library is compiled with.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub(crate) struct LibraryKey {
+    pub(crate) module: n::ModuleKey,
+    pub(crate) options: naga::back::msl::Options,
+    pub(crate) pipeline_options: naga::back::msl::PipelineOptions,
+    /// Entry point that is compiled with raster order groups, if any.
+    pub(crate) raster_ordered_entry: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct Slot {
+    result: Mutex<Option<LibraryResult>>,
+    ready: Condvar,
+}
+
+// Metal libraries can be used from any thread.
+unsafe impl Send for Slot {}
+unsafe impl Sync for Slot {}
+
+impl Slot {
+    fn fill(&self, result: LibraryResult) {
+        *self.result.lock() = Some(result);
+        self.ready.notify_all();
+    }
+}
+
+/// Library that is produced by the pool.
+#[derive(Debug)]
+pub(crate) struct PendingLibrary(Arc<Slot>);
+
+impl PendingLibrary {
+    /// Wrap a result that is known without compiling anything.
+    pub(crate) fn ready(result: LibraryResult) -> Self {
+        let slot = Slot::default();
+        *slot.result.lock() = Some(result);
+        PendingLibrary(Arc::new(slot))
+    }
+
+    /// Block until the library is compiled.
+    pub(crate) fn wait(self) -> LibraryResult {
+        let mut result = self.0.result.lock();
+        while result.is_none() {
+            self.0.ready.wait(&mut result);
+        }
+        result.clone().unwrap()
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    jobs: VecDeque<(Job, Arc<Slot>)>,
+    idle_workers: usize,
+    shutdown: bool,
+}
+
+#[derive(Default)]
+struct Inner {
+    queue: Mutex<Queue>,
+    available: Condvar,
+}
+
+impl Inner {
+    fn work(&self) {
+        let mut queue = self.queue.lock();
+        loop {
+            if let Some((job, slot)) = queue.jobs.pop_front() {
+                drop(queue);
+                // Drain the objects autoreleased by the compiler after each job,
+                // the worker threads live as long as the device.
+                let result = autoreleasepool(|| {
+                    panic::catch_unwind(AssertUnwindSafe(job))
+                        .unwrap_or_else(|_| Err("Shader compilation panicked".to_string()))
+                });
+                slot.fill(result);
+                queue = self.queue.lock();
+            } else if queue.shutdown {
+                break;
+            } else {
+                queue.idle_workers += 1;
+                self.available.wait(&mut queue);
+                queue.idle_workers -= 1;
+            }
+        }
+    }
+}
+
+/// Bounded pool of threads compiling shader libraries.
+#[derive(Default)]
+pub(crate) struct ShaderCompiler {
+    inner: Arc<Inner>,
+    workers: Mutex<Vec<thread::JoinHandle<()>>>,
+    /// Shared libraries by their key.
+    libraries: Mutex<FastHashMap<LibraryKey, Arc<Slot>>>,
+}
+
+impl fmt::Debug for ShaderCompiler {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "ShaderCompiler({} workers)",
+            self.workers.lock().len()
+        )
+    }
+}
+
+impl ShaderCompiler {
+    /// Start producing a library with `job` on a worker thread.
+    ///
+    /// If `shared` provides the library key, the library is shared
+    /// with the other requests of the same key.
+    pub(crate) fn compile<F>(&self, shared: Option<LibraryKey>, job: F) -> PendingLibrary
+    where
+        F: FnOnce() -> LibraryResult + Send + 'static,
+    {
+        let slot = match shared {
+            Some(key) => {
+                let mut libraries = self.libraries.lock();
+                if let Some(slot) = libraries.get(&key) {
+                    return PendingLibrary(Arc::clone(slot));
+                }
+                let slot = Arc::new(Slot::default());
+                libraries.insert(key, Arc::clone(&slot));
+                slot
+            }
+            None => Arc::new(Slot::default()),
+        };
+
+        let mut queue = self.inner.queue.lock();
+        queue.jobs.push_back((Box::new(job), Arc::clone(&slot)));
+        if queue.idle_workers != 0 {
+            self.inner.available.notify_one();
+        } else {
+            let mut workers = self.workers.lock();
+            if workers.len() < MAX_WORKERS {
+                let inner = Arc::clone(&self.inner);
+                let spawned = thread::Builder::new()
+                    .name(format!("gfx-metal shader compiler {}", workers.len()))
+                    .spawn(move || inner.work());
+                match spawned {
+                    Ok(handle) => workers.push(handle),
+                    Err(e) if workers.is_empty() => {
+                        warn!("Unable to spawn a shader compiler thread: {}", e);
+                        let (job, slot) = queue.jobs.pop_back().unwrap();
+                        drop(queue);
+                        slot.fill(job());
+                    }
+                    Err(e) => warn!("Unable to spawn a shader compiler thread: {}", e),
+                }
+            }
+        }
+        PendingLibrary(slot)
+    }
+
+    /// Drop the shared libraries of a shader module.
+    pub(crate) fn forget_module(&self, module_key: &n::ModuleKey) {
+        self.libraries
+            .lock()
+            .retain(|key, _| key.module != *module_key);
+    }
+}
+
+impl Drop for ShaderCompiler {
+    fn drop(&mut self) {
+        self.inner.queue.lock().shutdown = true;
+        self.inner.available.notify_all();
+        for worker in self.workers.get_mut().drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
@@ -85,6 +85,20 @@ impl PrivateCapabilities {
             f::Bc6hSfloat if self.format_bc => BC6H_RGBFloat,
             f::Bc7Unorm if self.format_bc => BC7_RGBAUnorm,
             f::Bc7Srgb if self.format_bc => BC7_RGBAUnorm_sRGB,
+            f::Bc1RgbUnorm | f::Bc1RgbaUnorm | f::Bc2Unorm | f::Bc3Unorm
+                if self.format_bc_decompression =>
+            {
+                RGBA8Unorm
+            }
+            f::Bc1RgbSrgb | f::Bc1RgbaSrgb | f::Bc2Srgb | f::Bc3Srgb
+                if self.format_bc_decompression =>
+            {
+                RGBA8Unorm_sRGB
+            }
+            f::Bc4Unorm if self.format_bc_decompression => R8Unorm,
+            f::Bc4Snorm if self.format_bc_decompression => R8Snorm,
+            f::Bc5Unorm if self.format_bc_decompression => RG8Unorm,
+            f::Bc5Snorm if self.format_bc_decompression => RG8Snorm,
             f::EacR11Unorm if self.format_eac_etc => EAC_R11Unorm,
             f::EacR11Snorm if self.format_eac_etc => EAC_R11Snorm,
             f::EacR11g11Unorm if self.format_eac_etc => EAC_RG11Unorm,
@@ -143,6 +157,10 @@ impl PrivateCapabilities {
             // bgra10_xr_srgb
             // bgr10_xr
             // bgr10_xr_srgb
+            // Two-plane images are the texture of their luma plane,
+            // the chroma plane having a texture of its own.
+            f::G8B8r8TwoPlane420Unorm => R8Unorm,
+            f::G10x6B10x6r10x6TwoPlane420Unorm => R16Unorm,
             _ => return None,
         })
     }
@@ -164,9 +182,10 @@ impl PrivateCapabilities {
                 let bits = format.base_format().0.describe_bits();
                 if swizzle != Swizzle::NO && !(bits.alpha == 0 && swizzle == Swizzle(R, G, B, One))
                 {
-                    error!("Unsupported swizzle {:?} for format {:?}", swizzle, format);
+                    None
+                } else {
+                    self.map_format(format)
                 }
-                self.map_format(format)
             }
         }
     }
@@ -184,6 +203,18 @@ impl PrivateCapabilities {
             MTLReadWriteTextureTier::Tier2 => (If::STORAGE_READ_WRITE, If::STORAGE_READ_WRITE),
         };
 
+        if !format.planes().is_empty() {
+            // The planes are only sampled with a conversion, and copied one at a time.
+            return Properties {
+                buffer_features: Bf::empty(),
+                optimal_tiling: If::SAMPLED
+                    | If::SAMPLED_LINEAR
+                    | If::TRANSFER_SRC
+                    | If::TRANSFER_DST,
+                linear_tiling: If::empty(),
+            };
+        }
+
         let mtl_format = match self.map_format(format) {
             Some(mtl_format) => mtl_format,
             None => {
@@ -473,6 +504,37 @@ impl PrivateCapabilities {
     }
 }
 
+/// Equivalent of `MTLTextureSwizzleChannels`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct TextureSwizzleChannels {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: u8,
+}
+
+pub fn map_swizzle(swizzle: Swizzle) -> TextureSwizzleChannels {
+    use hal::format::Component;
+    // Values of `MTLTextureSwizzle`.
+    fn map_component(component: Component) -> u8 {
+        match component {
+            Component::Zero => 0,
+            Component::One => 1,
+            Component::R => 2,
+            Component::G => 3,
+            Component::B => 4,
+            Component::A => 5,
+        }
+    }
+    TextureSwizzleChannels {
+        red: map_component(swizzle.0),
+        green: map_component(swizzle.1),
+        blue: map_component(swizzle.2),
+        alpha: map_component(swizzle.3),
+    }
+}
+
 pub fn map_load_operation(operation: pass::AttachmentLoadOp) -> MTLLoadAction {
     use self::pass::AttachmentLoadOp::*;
 
@@ -501,6 +563,15 @@ pub fn map_resolved_store_operation(operation: pass::AttachmentStoreOp) -> MTLSt
     }
 }
 
+/// Maps to `MTLMultisampleDepthResolveFilter`.
+pub fn map_depth_resolve_filter(mode: pass::ResolveMode) -> NSUInteger {
+    match mode {
+        pass::ResolveMode::SampleZero => 0,
+        pass::ResolveMode::Min => 1,
+        pass::ResolveMode::Max => 2,
+    }
+}
+
 pub fn map_write_mask(mask: pso::ColorMask) -> MTLColorWriteMask {
     let mut mtl_mask = MTLColorWriteMask::empty();
 
@@ -775,6 +846,28 @@ pub fn map_winding(face: pso::FrontFace) -> MTLWinding {
     }
 }
 
+pub fn map_primitive_topology(
+    primitive: pso::Primitive,
+) -> (MTLPrimitiveTopologyClass, MTLPrimitiveType) {
+    match primitive {
+        pso::Primitive::PointList => (MTLPrimitiveTopologyClass::Point, MTLPrimitiveType::Point),
+        pso::Primitive::LineList => (MTLPrimitiveTopologyClass::Line, MTLPrimitiveType::Line),
+        pso::Primitive::LineStrip => (MTLPrimitiveTopologyClass::Line, MTLPrimitiveType::LineStrip),
+        pso::Primitive::TriangleList => (
+            MTLPrimitiveTopologyClass::Triangle,
+            MTLPrimitiveType::Triangle,
+        ),
+        pso::Primitive::TriangleStrip => (
+            MTLPrimitiveTopologyClass::Triangle,
+            MTLPrimitiveType::TriangleStrip,
+        ),
+        pso::Primitive::PatchList(_) => (
+            MTLPrimitiveTopologyClass::Unspecified,
+            MTLPrimitiveType::Point,
+        ),
+    }
+}
+
 pub fn map_polygon_mode(mode: pso::PolygonMode) -> MTLTriangleFillMode {
     match mode {
         pso::PolygonMode::Point => {
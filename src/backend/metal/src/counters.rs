@@ -0,0 +1,203 @@
+//! Performance counter query pools.
+//!
+//! Every query of a pool takes a pair of samples of the counter set, at the draw
+//! boundaries of the render pass where the query begins and ends. The results
+//! are the differences between the values of the two samples.
+
+use hal::query;
+
+use cocoa_foundation::foundation::NSUInteger;
+use metal::NSRange;
+use objc::{
+    rc::autoreleasepool,
+    runtime::{Object, BOOL, YES},
+};
+
+use std::{ffi::CStr, mem, ops::Range, os::raw::c_char, ptr, ptr::NonNull, slice};
+
+/// Equivalent of `MTLCounterSamplingPointAtDrawBoundary`.
+const SAMPLING_POINT_AT_DRAW_BOUNDARY: NSUInteger = 1;
+/// Equivalent of `MTLCounterErrorValue`, left in the samples that weren't taken.
+const ERROR_VALUE: u64 = !0;
+
+pub(crate) type CounterSampleBufferPtr = NonNull<Object>;
+
+unsafe fn to_string(ns_string: *mut Object) -> String {
+    let utf8: *const c_char = msg_send![ns_string, UTF8String];
+    CStr::from_ptr(utf8).to_string_lossy().into_owned()
+}
+
+/// Check if the counters of the device can be sampled at draw boundaries.
+pub(crate) fn is_supported(device: &metal::DeviceRef) -> bool {
+    unsafe {
+        let responds: BOOL = msg_send![device, respondsToSelector: sel!(supportsCounterSampling:)];
+        if responds != YES {
+            return false;
+        }
+        let supported: BOOL =
+            msg_send![device, supportsCounterSampling: SAMPLING_POINT_AT_DRAW_BOUNDARY];
+        supported == YES
+    }
+}
+
+/// Get the native counter sets of the device, if they can be sampled.
+unsafe fn native_counter_sets(device: &metal::DeviceRef) -> Vec<*mut Object> {
+    if !is_supported(device) {
+        return Vec::new();
+    }
+    let counter_sets: *mut Object = msg_send![device, counterSets];
+    if counter_sets.is_null() {
+        return Vec::new();
+    }
+    let count: NSUInteger = msg_send![counter_sets, count];
+    (0..count)
+        .map(|i| -> *mut Object { msg_send![counter_sets, objectAtIndex: i] })
+        .collect()
+}
+
+/// Enumerate the counter sets that can be queried on the device.
+pub(crate) fn counter_sets(device: &metal::DeviceRef) -> Vec<query::CounterSet> {
+    autoreleasepool(|| unsafe {
+        native_counter_sets(device)
+            .into_iter()
+            .map(|set| {
+                let name: *mut Object = msg_send![set, name];
+                let counters: *mut Object = msg_send![set, counters];
+                let count: NSUInteger = msg_send![counters, count];
+                query::CounterSet {
+                    name: to_string(name),
+                    counters: (0..count)
+                        .map(|i| {
+                            let counter: *mut Object = msg_send![counters, objectAtIndex: i];
+                            let name: *mut Object = msg_send![counter, name];
+                            to_string(name)
+                        })
+                        .collect(),
+                }
+            })
+            .collect()
+    })
+}
+
+#[derive(Debug)]
+pub struct CounterPool {
+    sample_buffer: CounterSampleBufferPtr,
+    /// Number of counters in the set.
+    counters: usize,
+}
+
+unsafe impl Send for CounterPool {}
+unsafe impl Sync for CounterPool {}
+
+impl Drop for CounterPool {
+    fn drop(&mut self) {
+        unsafe {
+            let () = msg_send![self.sample_buffer.as_ptr(), release];
+        }
+    }
+}
+
+impl CounterPool {
+    pub(crate) fn new(
+        device: &metal::DeviceRef,
+        set: query::CounterSetId,
+        count: query::Id,
+    ) -> Result<Self, query::CreationError> {
+        autoreleasepool(|| unsafe {
+            let counter_set = match native_counter_sets(device).get(set as usize) {
+                Some(&counter_set) => counter_set,
+                None => {
+                    return Err(query::CreationError::Unsupported(
+                        query::Type::PerformanceCounters(set),
+                    ))
+                }
+            };
+            let counters: *mut Object = msg_send![counter_set, counters];
+            let num_counters: NSUInteger = msg_send![counters, count];
+
+            let descriptor: *mut Object = msg_send![class!(MTLCounterSampleBufferDescriptor), new];
+            let () = msg_send![descriptor, setCounterSet: counter_set];
+            let () = msg_send![descriptor, setStorageMode: metal::MTLStorageMode::Shared];
+            let () = msg_send![descriptor, setSampleCount: 2 * count as NSUInteger];
+            let mut error: *mut Object = ptr::null_mut();
+            let sample_buffer: *mut Object = msg_send![
+                device,
+                newCounterSampleBufferWithDescriptor: descriptor
+                error: &mut error
+            ];
+            let () = msg_send![descriptor, release];
+
+            match NonNull::new(sample_buffer) {
+                Some(sample_buffer) => Ok(CounterPool {
+                    sample_buffer,
+                    counters: num_counters as usize,
+                }),
+                None => {
+                    error!("Unable to create a counter sample buffer");
+                    Err(hal::device::OutOfMemory::Device.into())
+                }
+            }
+        })
+    }
+
+    pub(crate) fn sample_buffer(&self) -> CounterSampleBufferPtr {
+        self.sample_buffer
+    }
+
+    /// Number of values in the results of each query.
+    pub(crate) fn counters(&self) -> usize {
+        self.counters
+    }
+
+    /// Index of the sample taken when the query begins,
+    /// the one taken when it ends follows it.
+    pub(crate) fn sample_index(id: query::Id) -> NSUInteger {
+        2 * id as NSUInteger
+    }
+
+    /// Resolve the counter values of the queries, `None` for the ones
+    /// whose samples haven't been taken yet.
+    pub(crate) fn resolve(&self, queries: Range<query::Id>) -> Vec<Option<Vec<u64>>> {
+        let num_queries = (queries.end - queries.start) as usize;
+        if num_queries == 0 {
+            return Vec::new();
+        }
+        autoreleasepool(|| unsafe {
+            let data: *mut Object = msg_send![
+                self.sample_buffer.as_ptr(),
+                resolveCounterRange: NSRange::new(
+                    Self::sample_index(queries.start),
+                    2 * num_queries as NSUInteger
+                )
+            ];
+            if data.is_null() {
+                return vec![None; num_queries];
+            }
+            let bytes: *const u64 = msg_send![data, bytes];
+            let length: NSUInteger = msg_send![data, length];
+            let values = slice::from_raw_parts(bytes, length as usize / mem::size_of::<u64>());
+            // The samples are laid out like the result structures of the set,
+            // which list the values in the order of its counters.
+            let sample_size = values.len() / (2 * num_queries);
+            if sample_size == 0 {
+                return vec![None; num_queries];
+            }
+            values
+                .chunks(2 * sample_size)
+                .map(|pair| {
+                    let (start, end) = pair.split_at(sample_size);
+                    if start.contains(&ERROR_VALUE) || end.contains(&ERROR_VALUE) {
+                        return None;
+                    }
+                    let mut result = start
+                        .iter()
+                        .zip(end)
+                        .map(|(&start, &end)| end.saturating_sub(start))
+                        .collect::<Vec<_>>();
+                    result.resize(self.counters, 0);
+                    Some(result)
+                })
+                .collect()
+        })
+    }
+}
@@ -0,0 +1,175 @@
+//! Decompression of BC1-BC5 textures for devices without native BC support.
+//!
+//! Images of these formats are backed by uncompressed textures, and the block data
+//! is decoded by a compute pass (see `decompress.msl`) when it's copied from a buffer
+//! into the image.
+
+use hal::{buffer, command as com, format::Format};
+
+/// Block compression scheme of an image that is decompressed on upload.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub(crate) enum BlockFormat {
+    Bc1,
+    Bc2,
+    Bc3,
+    Bc4 { signed: bool },
+    Bc5 { signed: bool },
+}
+
+impl BlockFormat {
+    pub(crate) fn from_format(format: Format) -> Option<Self> {
+        use hal::format::Format as f;
+        Some(match format {
+            f::Bc1RgbUnorm | f::Bc1RgbSrgb | f::Bc1RgbaUnorm | f::Bc1RgbaSrgb => BlockFormat::Bc1,
+            f::Bc2Unorm | f::Bc2Srgb => BlockFormat::Bc2,
+            f::Bc3Unorm | f::Bc3Srgb => BlockFormat::Bc3,
+            f::Bc4Unorm => BlockFormat::Bc4 { signed: false },
+            f::Bc4Snorm => BlockFormat::Bc4 { signed: true },
+            f::Bc5Unorm => BlockFormat::Bc5 { signed: false },
+            f::Bc5Snorm => BlockFormat::Bc5 { signed: true },
+            _ => return None,
+        })
+    }
+
+    fn block_size(self) -> u32 {
+        match self {
+            BlockFormat::Bc1 | BlockFormat::Bc4 { .. } => 8,
+            BlockFormat::Bc2 | BlockFormat::Bc3 | BlockFormat::Bc5 { .. } => 16,
+        }
+    }
+
+    fn channels(self) -> u32 {
+        match self {
+            BlockFormat::Bc4 { .. } => 1,
+            BlockFormat::Bc5 { .. } => 2,
+            BlockFormat::Bc1 | BlockFormat::Bc2 | BlockFormat::Bc3 => 4,
+        }
+    }
+
+    /// Integer format of the texture view the decoded texels are written through,
+    /// so that the bytes are stored as-is regardless of the image format.
+    pub(crate) fn view_format(self) -> metal::MTLPixelFormat {
+        match self.channels() {
+            1 => metal::MTLPixelFormat::R8Uint,
+            2 => metal::MTLPixelFormat::RG8Uint,
+            _ => metal::MTLPixelFormat::RGBA8Uint,
+        }
+    }
+
+    /// Name of the compute function decoding this format in `decompress.msl`.
+    pub(crate) fn function_name(self) -> &'static str {
+        match self {
+            BlockFormat::Bc1 => "cs_decompress_bc1",
+            BlockFormat::Bc2 => "cs_decompress_bc2",
+            BlockFormat::Bc3 => "cs_decompress_bc3",
+            BlockFormat::Bc4 { signed: false } => "cs_decompress_bc4_unorm",
+            BlockFormat::Bc4 { signed: true } => "cs_decompress_bc4_snorm",
+            BlockFormat::Bc5 { signed: false } => "cs_decompress_bc5_unorm",
+            BlockFormat::Bc5 { signed: true } => "cs_decompress_bc5_snorm",
+        }
+    }
+
+    /// Layout of the blocks read by a copy region, relative to its buffer offset.
+    pub(crate) fn source_layout(self, region: &com::BufferImageCopy) -> SourceLayout {
+        let extent = region.image_extent;
+        let buffer_width = match region.buffer_width {
+            0 => extent.width,
+            width => width,
+        };
+        let buffer_height = match region.buffer_height {
+            0 => extent.height,
+            height => height,
+        };
+        let row_pitch = (buffer_width + 3) / 4 * self.block_size();
+        let slice_pitch = (buffer_height + 3) / 4 * row_pitch;
+        let num_slices = extent.depth * region.image_layers.layers.len() as u32;
+        let last_slice_size = ((extent.height + 3) / 4).saturating_sub(1) as buffer::Offset
+            * row_pitch as buffer::Offset
+            + ((extent.width + 3) / 4 * self.block_size()) as buffer::Offset;
+
+        SourceLayout {
+            row_pitch,
+            slice_pitch,
+            num_slices,
+            size: num_slices.saturating_sub(1) as buffer::Offset * slice_pitch as buffer::Offset
+                + last_slice_size,
+        }
+    }
+}
+
+/// Block layout of the source data of a copy region.
+#[derive(Debug, PartialEq)]
+pub(crate) struct SourceLayout {
+    /// Bytes between rows of blocks.
+    pub row_pitch: u32,
+    /// Bytes between layers.
+    pub slice_pitch: u32,
+    pub num_slices: u32,
+    /// Bytes read past the buffer offset.
+    pub size: buffer::Offset,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BlockFormat, SourceLayout};
+    use hal::{command as com, format, image};
+
+    fn region(width: u32, height: u32) -> com::BufferImageCopy {
+        com::BufferImageCopy {
+            buffer_offset: 0,
+            buffer_width: 0,
+            buffer_height: 0,
+            image_layers: image::SubresourceLayers {
+                aspects: format::Aspects::COLOR,
+                level: 0,
+                layers: 0..1,
+            },
+            image_offset: image::Offset::ZERO,
+            image_extent: image::Extent {
+                width,
+                height,
+                depth: 1,
+            },
+        }
+    }
+
+    #[test]
+    fn source_layout_tight() {
+        // 10x6 texels are covered by 3x2 blocks.
+        let layout = BlockFormat::Bc1.source_layout(&region(10, 6));
+        assert_eq!(
+            layout,
+            SourceLayout {
+                row_pitch: 24,
+                slice_pitch: 48,
+                num_slices: 1,
+                size: 48,
+            }
+        );
+    }
+
+    #[test]
+    fn source_layout_padded_rows() {
+        // The last row of blocks is only as wide as the region.
+        let r = com::BufferImageCopy {
+            buffer_width: 16,
+            buffer_height: 8,
+            image_layers: image::SubresourceLayers {
+                aspects: format::Aspects::COLOR,
+                level: 0,
+                layers: 0..2,
+            },
+            ..region(4, 8)
+        };
+        let layout = BlockFormat::Bc3.source_layout(&r);
+        assert_eq!(
+            layout,
+            SourceLayout {
+                row_pitch: 64,
+                slice_pitch: 128,
+                num_slices: 2,
+                size: 128 + 64 + 16,
+            }
+        );
+    }
+}
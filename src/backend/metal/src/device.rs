@@ -1,9 +1,10 @@
 #[cfg(feature = "pipeline-cache")]
 use crate::pipeline_cache;
 use crate::{
-    command, conversions as conv, internal::Channel, native as n, AsNative, Backend, FastHashMap,
-    OnlineRecording, QueueFamily, ResourceIndex, Shared, VisibilityShared,
-    MAX_BOUND_DESCRIPTOR_SETS, MAX_COLOR_ATTACHMENTS,
+    command, compiler, conversions as conv, counters, decompress::BlockFormat, dispatch_base,
+    internal::Channel, native as n, pulling::VertexPulling, ycbcr, AsNative, Backend,
+    CaptureTarget, FastHashMap, OnlineRecording, QueueFamily, ResourceIndex, ShaderTranslator,
+    Shared, VisibilityShared, MAX_BOUND_DESCRIPTOR_SETS, MAX_COLOR_ATTACHMENTS,
 };
 
 use arrayvec::ArrayVec;
@@ -11,7 +12,18 @@ use cocoa_foundation::foundation::NSUInteger;
 use copyless::VecHelper;
 use foreign_types::{ForeignType, ForeignTypeRef};
 use hal::{
-    adapter, buffer, device as d, display, format, image, memory,
+    adapter, buffer, command as com,
+    deferred::DeferredOperation,
+    device as d, display,
+    external_memory::{
+        ExternalMemoryError, ExternalMemoryHandle, ExternalMemoryHandleTypes,
+        ExternalMemoryProperties,
+    },
+    external_sync::{
+        ExternalSyncError, ExternalSyncHandle, ExternalSyncHandleType, ExternalSyncHandleTypes,
+        ExternalSyncProperties,
+    },
+    format, image, memory,
     memory::Properties,
     pass,
     pool::CommandPoolCreateFlags,
@@ -21,32 +33,49 @@ use hal::{
     queue::{QueueFamilyId, QueueGroup, QueuePriority},
 };
 use metal::{
-    CaptureManager, MTLCPUCacheMode, MTLLanguageVersion, MTLPrimitiveTopologyClass,
-    MTLPrimitiveType, MTLResourceOptions, MTLSamplerMipFilter, MTLStorageMode, MTLTextureType,
-    MTLVertexStepFunction, NSRange,
+    CaptureManager, MTLCPUCacheMode, MTLGPUFamily, MTLLanguageVersion, MTLPrimitiveTopologyClass,
+    MTLResourceOptions, MTLSamplerMipFilter, MTLStorageMode, MTLTextureType, MTLVertexStepFunction,
+    NSRange,
 };
 use objc::{
     rc::autoreleasepool,
-    runtime::{Object, BOOL, NO},
+    runtime::{Object, BOOL, NO, YES},
 };
-use parking_lot::Mutex;
+use parking_lot::{Mutex, RwLockWriteGuard};
+use range_alloc::RangeAllocator;
 
 use std::collections::BTreeMap;
 #[cfg(feature = "pipeline-cache")]
 use std::io::Write;
 use std::{
-    cmp, iter, mem,
+    cmp,
+    ffi::CStr,
+    iter, mem,
     ops::Range,
+    os::raw::c_char,
     ptr,
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
         Arc,
     },
     thread, time,
 };
 
+/// Number of buffers or textures that can be accessed through tier 2 argument buffers.
+const ARGUMENT_BUFFER_TIER2_RESOURCES: u32 = 500_000;
+/// Number of samplers that can be accessed through tier 2 argument buffers.
+const ARGUMENT_BUFFER_TIER2_SAMPLERS: u32 = 1024;
 const STRIDE_GRANULARITY: pso::ElemStride = 4; //TODO: work around?
 const SHADER_STAGE_COUNT: u32 = 3;
+/// Size of the CPU buffers that small public memory allocations are sub-allocated from.
+const PUBLIC_SLAB_SIZE: u64 = 4 << 20;
+/// Largest shared memory allocation that is sub-allocated instead of getting its own buffer.
+///
+/// Managed memory is never sub-allocated: invalidating a range of it synchronizes
+/// the whole CPU buffer.
+const MAX_PUBLIC_SUBALLOCATION: u64 = 64 << 10;
+/// Alignment of the sub-allocations, satisfies both buffer offsets and texel buffer rows.
+const PUBLIC_SLAB_ALIGNMENT: u64 = 256;
 
 #[derive(Clone, Debug)]
 enum FunctionError {
@@ -55,6 +84,389 @@ enum FunctionError {
     BadSpecialization,
 }
 
+/// Check if a subpass dependency only orders the attachment accesses of
+/// the fragment work at the same location, which a render encoder guarantees.
+fn is_framebuffer_local(dep: &pass::SubpassDependency) -> bool {
+    let fragment_stages = pso::PipelineStage::EARLY_FRAGMENT_TESTS
+        | pso::PipelineStage::FRAGMENT_SHADER
+        | pso::PipelineStage::LATE_FRAGMENT_TESTS
+        | pso::PipelineStage::COLOR_ATTACHMENT_OUTPUT;
+    let attachment_accesses = image::Access::COLOR_ATTACHMENT_READ
+        | image::Access::COLOR_ATTACHMENT_WRITE
+        | image::Access::DEPTH_STENCIL_ATTACHMENT_READ
+        | image::Access::DEPTH_STENCIL_ATTACHMENT_WRITE;
+    dep.flags.contains(memory::Dependencies::BY_REGION)
+        && fragment_stages.contains(dep.stages.start | dep.stages.end)
+        && attachment_accesses.contains(dep.accesses.start | dep.accesses.end)
+}
+
+/// Get the handle types that images can import their memory from. An `IOSurface` backs
+/// a single 2D texture, and host memory only backs linear images, which are buffers.
+fn external_image_handle_types(dimensions: u8, tiling: image::Tiling) -> ExternalMemoryHandleTypes {
+    let mut handle_types = ExternalMemoryHandleTypes::empty();
+    if dimensions == 2 {
+        handle_types |= ExternalMemoryHandleTypes::IO_SURFACE;
+    }
+    if let image::Tiling::Linear = tiling {
+        handle_types |= ExternalMemoryHandleTypes::HOST_POINTER;
+    }
+    handle_types
+}
+
+/// Put the resources of the fragment function `name` that are bound with
+/// the given attributes, like `buffer(2)`, into the first raster order group.
+fn order_raster_accesses(source: &str, name: &str, slots: &[String]) -> Option<String> {
+    let prefix = format!(" {}(", name);
+    let start = source
+        .match_indices("fragment ")
+        .map(|(i, _)| i)
+        .find(|&i| {
+            let line_end = source[i..].find('\n').map_or(source.len(), |e| i + e);
+            (i == 0 || source[..i].ends_with('\n')) && source[i..line_end].contains(&prefix)
+        })?;
+    let end = start + source[start..].find('{')?;
+    let mut signature = source[start..end].to_string();
+    for slot in slots {
+        signature = signature.replace(
+            &format!("[[{}]]", slot),
+            &format!("[[{}, raster_order_group(0)]]", slot),
+        );
+    }
+    Some(format!(
+        "{}{}{}",
+        &source[..start],
+        signature,
+        &source[end..]
+    ))
+}
+
+/// Argument buffer updates of consecutive resources of the same kind,
+/// which are encoded with a single call.
+#[derive(Default)]
+struct ArgumentRun<'a> {
+    start: NSUInteger,
+    textures: Vec<&'a metal::TextureRef>,
+    buffers: Vec<&'a metal::BufferRef>,
+    offsets: Vec<NSUInteger>,
+    samplers: Vec<&'a metal::SamplerStateRef>,
+}
+
+impl<'a> ArgumentRun<'a> {
+    /// Flush the run, unless a resource of the kind already in it
+    /// can be added at `index`.
+    fn prepare(&mut self, encoder: &metal::ArgumentEncoderRef, index: NSUInteger, len: usize) {
+        let total = self.textures.len() + self.buffers.len() + self.samplers.len();
+        if len != total || index != self.start + total as NSUInteger {
+            self.flush(encoder);
+            self.start = index;
+        }
+    }
+
+    fn push_texture(
+        &mut self,
+        encoder: &metal::ArgumentEncoderRef,
+        index: NSUInteger,
+        texture: &'a metal::TextureRef,
+    ) {
+        self.prepare(encoder, index, self.textures.len());
+        self.textures.push(texture);
+    }
+
+    fn push_buffer(
+        &mut self,
+        encoder: &metal::ArgumentEncoderRef,
+        index: NSUInteger,
+        buffer: &'a metal::BufferRef,
+        offset: buffer::Offset,
+    ) {
+        self.prepare(encoder, index, self.buffers.len());
+        self.buffers.push(buffer);
+        self.offsets.push(offset as NSUInteger);
+    }
+
+    fn push_sampler(
+        &mut self,
+        encoder: &metal::ArgumentEncoderRef,
+        index: NSUInteger,
+        sampler: &'a metal::SamplerStateRef,
+    ) {
+        self.prepare(encoder, index, self.samplers.len());
+        self.samplers.push(sampler);
+    }
+
+    fn flush(&mut self, encoder: &metal::ArgumentEncoderRef) {
+        if !self.textures.is_empty() {
+            encoder.set_textures(self.start, &self.textures);
+            self.textures.clear();
+        }
+        if !self.buffers.is_empty() {
+            encoder.set_buffers(self.start, &self.buffers, &self.offsets);
+            self.buffers.clear();
+            self.offsets.clear();
+        }
+        if !self.samplers.is_empty() {
+            encoder.set_sampler_states(self.start, &self.samplers);
+            self.samplers.clear();
+        }
+    }
+}
+
+/// Creates the vertex descriptor of a vertex layout, along with the mapping
+/// from Metal vertex buffers to the layout ones.
+fn create_vertex_descriptor(
+    max_buffers: ResourceIndex,
+    reserved_buffers: ResourceIndex,
+    desc_vertex_buffers: &[pso::VertexBufferDesc],
+    attributes: &[pso::AttributeDesc],
+) -> Result<(metal::VertexDescriptor, n::VertexBufferVec), pso::CreationError> {
+    let vertex_descriptor = metal::VertexDescriptor::new();
+    let mut vertex_buffers: n::VertexBufferVec = Vec::new();
+    trace!("Vertex attribute remapping started");
+
+    for &pso::AttributeDesc {
+        location,
+        binding,
+        element,
+    } in attributes
+    {
+        let original = desc_vertex_buffers
+            .iter()
+            .find(|vb| vb.binding == binding)
+            .expect("no associated vertex buffer found");
+        // handle wrapping offsets
+        let elem_size = element.format.surface_desc().bits as pso::ElemOffset / 8;
+        let (cut_offset, base_offset) =
+            if original.stride == 0 || element.offset + elem_size <= original.stride {
+                (element.offset, 0)
+            } else {
+                let remainder = element.offset % original.stride;
+                if remainder + elem_size <= original.stride {
+                    (remainder, element.offset - remainder)
+                } else {
+                    (0, element.offset)
+                }
+            };
+        let relative_index = vertex_buffers
+            .iter()
+            .position(|(ref vb, offset)| vb.binding == binding && base_offset == *offset)
+            .unwrap_or_else(|| {
+                vertex_buffers.alloc().init((original.clone(), base_offset));
+                vertex_buffers.len() - 1
+            });
+        let mtl_buffer_index = max_buffers - 1 - (relative_index as ResourceIndex);
+        if mtl_buffer_index < reserved_buffers {
+            error!(
+                "Attribute offset {} exceeds the stride {}, and there is no room for replacement.",
+                element.offset, original.stride
+            );
+            return Err(pso::CreationError::Other);
+        }
+        trace!(
+            "\tAttribute[{}] is mapped to vertex buffer[{}] with binding {} and offsets {} + {}",
+            location,
+            binding,
+            mtl_buffer_index,
+            base_offset,
+            cut_offset
+        );
+        // pass the refined data to Metal
+        let mtl_attribute_desc = vertex_descriptor
+            .attributes()
+            .object_at(location as u64)
+            .expect("too many vertex attributes");
+        let mtl_vertex_format =
+            conv::map_vertex_format(element.format).expect("unsupported vertex format");
+        mtl_attribute_desc.set_format(mtl_vertex_format);
+        mtl_attribute_desc.set_buffer_index(mtl_buffer_index as _);
+        mtl_attribute_desc.set_offset(cut_offset as _);
+    }
+
+    for (i, (vb, _)) in vertex_buffers.iter().enumerate() {
+        let mtl_buffer_desc = vertex_descriptor
+            .layouts()
+            .object_at(max_buffers as u64 - 1 - i as u64)
+            .expect("too many vertex descriptor layouts");
+        if vb.stride % STRIDE_GRANULARITY != 0 {
+            error!(
+                "Stride ({}) must be a multiple of {}",
+                vb.stride, STRIDE_GRANULARITY
+            );
+            return Err(pso::CreationError::Other);
+        }
+        if vb.stride != 0 {
+            mtl_buffer_desc.set_stride(vb.stride as u64);
+            match vb.rate {
+                VertexInputRate::Vertex => {
+                    mtl_buffer_desc.set_step_function(MTLVertexStepFunction::PerVertex);
+                }
+                VertexInputRate::Instance(divisor) => {
+                    mtl_buffer_desc.set_step_function(MTLVertexStepFunction::PerInstance);
+                    mtl_buffer_desc.set_step_rate(divisor as u64);
+                }
+            }
+        } else {
+            mtl_buffer_desc.set_stride(256); // big enough to fit all the elements
+            mtl_buffer_desc.set_step_function(MTLVertexStepFunction::PerInstance);
+            mtl_buffer_desc.set_step_rate(!0);
+        }
+    }
+    Ok((vertex_descriptor, vertex_buffers))
+}
+
+impl n::VertexInputVariants {
+    /// Get the pipeline state for the given vertex layout, creating it if needed.
+    pub(crate) fn get(
+        &self,
+        shared: &Shared,
+        desc_vertex_buffers: &[pso::VertexBufferDesc],
+        attributes: &[pso::AttributeDesc],
+    ) -> Result<(metal::RenderPipelineState, n::VertexBufferVec), pso::CreationError> {
+        let mut states = self.states.lock();
+        let key = (desc_vertex_buffers.to_vec(), attributes.to_vec());
+        if let Some(&(ref raw, ref vertex_buffers)) = states.get(&key) {
+            return Ok((raw.clone(), vertex_buffers.clone()));
+        }
+
+        if VertexPulling::is_required(
+            desc_vertex_buffers,
+            attributes,
+            STRIDE_GRANULARITY,
+            |format| conv::map_vertex_format(format).is_some(),
+        ) {
+            error!("Vertex layout requires vertex pulling, which is not supported with dynamic vertex input");
+            return Err(pso::CreationError::UnsupportedPipeline);
+        }
+        let (vertex_descriptor, vertex_buffers) = create_vertex_descriptor(
+            shared.private_caps.max_buffers_per_stage,
+            self.reserved_buffers,
+            desc_vertex_buffers,
+            attributes,
+        )?;
+        self.descriptor
+            .set_vertex_descriptor(if vertex_buffers.is_empty() {
+                None
+            } else {
+                Some(&vertex_descriptor)
+            });
+
+        profiling::scope!("Metal::new_render_pipeline_state");
+        let raw = shared
+            .device
+            .new_render_pipeline_state(&self.descriptor)
+            .map_err(|err| {
+                error!("PSO creation failed: {}", err);
+                pso::CreationError::Other
+            })?;
+        states.insert(key, (raw.clone(), vertex_buffers.clone()));
+        Ok((raw, vertex_buffers))
+    }
+}
+
+fn executable_statistic(
+    name: &str,
+    description: &str,
+    value: pso::ExecutableStatisticValue,
+) -> pso::ExecutableStatistic {
+    pso::ExecutableStatistic {
+        name: name.to_string(),
+        description: description.to_string(),
+        value,
+    }
+}
+
+/// Describe the executable of a shader stage, keeping the statistics
+/// and representations only if the pipeline flags capture them.
+fn describe_executable(
+    stage: naga::ShaderStage,
+    shader: &CompiledShader,
+    flags: pso::PipelineCreationFlags,
+    push_constants: Option<n::PushConstantInfo>,
+    mut statistics: Vec<pso::ExecutableStatistic>,
+) -> pso::PipelineExecutable {
+    let name = shader.function.name().to_string();
+    if flags.contains(pso::PipelineCreationFlags::CAPTURE_STATISTICS) {
+        statistics.push(executable_statistic(
+            "Push constants",
+            "Number of words of push constants passed to the function",
+            pso::ExecutableStatisticValue::UInt(push_constants.map_or(0, |pc| pc.count as u64)),
+        ));
+        statistics.push(executable_statistic(
+            "Sized bindings",
+            "Number of runtime-sized storage bindings, whose sizes are passed in a buffer",
+            pso::ExecutableStatisticValue::UInt(shader.sized_bindings.len() as u64),
+        ));
+    } else {
+        statistics.clear();
+    }
+
+    let representations =
+        if flags.contains(pso::PipelineCreationFlags::CAPTURE_INTERNAL_REPRESENTATIONS) {
+            vec![
+                pso::ExecutableRepresentation {
+                    name: "MSL".to_string(),
+                    description: "Metal Shading Language source of the library".to_string(),
+                    text: shader.source.to_string(),
+                },
+                pso::ExecutableRepresentation {
+                    name: "Reflection".to_string(),
+                    description: "Interface of the function reflected from the shader".to_string(),
+                    text: format!(
+                        "function: {}\nworkgroup size: {}x{}x{}\nsized bindings: {:?}\n",
+                        name,
+                        shader.wg_size.width,
+                        shader.wg_size.height,
+                        shader.wg_size.depth,
+                        shader.sized_bindings,
+                    ),
+                },
+            ]
+        } else {
+            Vec::new()
+        };
+
+    pso::PipelineExecutable {
+        stages: stage.into(),
+        description: format!(
+            "{:?} function translated with {:?}",
+            stage, shader.translator
+        ),
+        name,
+        statistics,
+        representations,
+    }
+}
+
+/// Get the bindings of the buffers ending with runtime-sized arrays,
+/// whose sizes are provided to the shaders.
+fn collect_sized_bindings(module: &naga::Module) -> Vec<naga::ResourceBinding> {
+    let mut sized_bindings = Vec::new();
+    for (_handle, var) in module.global_variables.iter() {
+        if let naga::TypeInner::Struct { ref members, .. } = module.types[var.ty].inner {
+            if let Some(member) = members.last() {
+                if let naga::TypeInner::Array {
+                    size: naga::ArraySize::Dynamic,
+                    ..
+                } = module.types[member.ty].inner
+                {
+                    // Note: unwraps are fine, since the MSL is already generated
+                    let br = var.binding.clone().unwrap();
+                    sized_bindings.push(br);
+                }
+            }
+        }
+    }
+    sized_bindings
+}
+
+fn object_private_data<'a>(object: d::PrivateDataObject<'a, Backend>) -> &'a n::PrivateData {
+    match object {
+        d::PrivateDataObject::Buffer(buffer) => buffer.private_data(),
+        d::PrivateDataObject::Image(image) => &image.private_data,
+        d::PrivateDataObject::GraphicsPipeline(pipeline) => &pipeline.private_data,
+        d::PrivateDataObject::ComputePipeline(pipeline) => &pipeline.private_data,
+    }
+}
+
 fn get_final_function(
     library: &metal::LibraryRef,
     entry: &str,
@@ -140,6 +552,19 @@ struct CompiledShader {
     wg_size: metal::MTLSize,
     rasterizing: bool,
     sized_bindings: Vec<naga::ResourceBinding>,
+    /// Library source, entry point and specialization of the function.
+    function_key: n::FunctionKey,
+    translator: ShaderTranslator,
+    source: Arc<str>,
+}
+
+/// Rewrite of a naga module that a pipeline needs before the translation.
+#[derive(Clone, Copy, Debug)]
+enum ShaderRewrite<'a> {
+    /// Fetch the vertex attributes in the vertex shader.
+    VertexPulling(&'a VertexPulling),
+    /// Offset the workgroup IDs of the compute shader by the base in the given buffer slot.
+    DispatchBase(ResourceIndex),
 }
 
 #[derive(Debug)]
@@ -147,8 +572,29 @@ pub struct Device {
     pub(crate) shared: Arc<Shared>,
     invalidation_queue: command::QueueInner,
     memory_types: Vec<adapter::MemoryType>,
+    public_slabs: Mutex<Vec<n::PublicSlab>>,
     features: hal::Features,
     pub online_recording: OnlineRecording,
+    /// Measure the GPU time of each pass in command buffers of pools created afterwards,
+    /// see `CommandBuffer::pass_timings`.
+    pub pass_timing: bool,
+    /// Count the recorded commands in command buffers of pools created afterwards,
+    /// see `CommandBuffer::stats`.
+    pub command_stats: bool,
+    /// Scope of the frame captures started with `start_capture`.
+    pub capture_target: CaptureTarget,
+    /// Translator of the shader modules created afterwards. `None` picks SPIRV-Cross
+    /// for SPIR-V modules when the `cross` feature is enabled and naga otherwise,
+    /// falling back to SPIRV-Cross, if enabled, when naga fails to translate.
+    pub shader_translator: Option<ShaderTranslator>,
+    /// Render pipeline states by their descriptor, shared between the graphics
+    /// pipelines that are created with identical descriptors, along with the
+    /// number of those pipelines.
+    render_pipeline_states:
+        Mutex<FastHashMap<Arc<n::RenderPipelineKey>, (metal::RenderPipelineState, usize)>>,
+    shader_compiler: compiler::ShaderCompiler,
+    /// Identifier of the next private data slot.
+    next_private_data_slot: AtomicU64,
     #[cfg(any(feature = "pipeline-cache", feature = "cross"))]
     spv_options: naga::back::spv::Options,
 }
@@ -191,6 +637,19 @@ pub struct PhysicalDevice {
 unsafe impl Send for PhysicalDevice {}
 unsafe impl Sync for PhysicalDevice {}
 
+/// Metal-specific properties of a physical device.
+#[derive(Clone, Copy, Debug)]
+pub struct DeviceProperties {
+    /// CPU and GPU share the same memory, so staging copies are not needed.
+    pub has_unified_memory: bool,
+    /// Device is an external GPU, which can be detached while in use.
+    pub is_removable: bool,
+    /// Identifier of the device in the IORegistry, stable across processes.
+    pub registry_id: u64,
+    /// Highest GPU family supported by the device, if the OS is able to report it.
+    pub gpu_family: Option<MTLGPUFamily>,
+}
+
 impl PhysicalDevice {
     pub(crate) fn new(shared: Arc<Shared>) -> Self {
         let memory_types = if shared.private_caps.os_is_mac {
@@ -232,12 +691,82 @@ impl PhysicalDevice {
         }
     }
 
+    /// Return the Metal-specific properties of the device.
+    pub fn device_properties(&self) -> DeviceProperties {
+        const FAMILIES: &[MTLGPUFamily] = &[
+            MTLGPUFamily::Apple7,
+            MTLGPUFamily::Apple6,
+            MTLGPUFamily::Apple5,
+            MTLGPUFamily::Apple4,
+            MTLGPUFamily::Apple3,
+            MTLGPUFamily::Apple2,
+            MTLGPUFamily::Apple1,
+            MTLGPUFamily::Mac2,
+            MTLGPUFamily::Mac1,
+        ];
+
+        let caps = &self.shared.private_caps;
+        let device = &self.shared.device;
+        let (family_api, unified_memory_api, registry_api) = if caps.os_is_mac {
+            (
+                caps.has_version_at_least(10, 15),
+                caps.has_version_at_least(10, 15),
+                caps.has_version_at_least(10, 13),
+            )
+        } else {
+            (
+                caps.has_version_at_least(13, 0),
+                caps.has_version_at_least(13, 0),
+                caps.has_version_at_least(11, 0),
+            )
+        };
+
+        DeviceProperties {
+            has_unified_memory: if unified_memory_api {
+                let result: BOOL = unsafe { msg_send![*device, hasUnifiedMemory] };
+                result != NO
+            } else {
+                !caps.os_is_mac
+            },
+            is_removable: caps.os_is_mac && registry_api && {
+                let result: BOOL = unsafe { msg_send![*device, isRemovable] };
+                result != NO
+            },
+            registry_id: if registry_api {
+                unsafe { msg_send![*device, registryID] }
+            } else {
+                0
+            },
+            gpu_family: if family_api {
+                FAMILIES
+                    .iter()
+                    .cloned()
+                    .find(|&family| device.supports_family(family))
+            } else {
+                None
+            },
+        }
+    }
+
+    /// Return true if the device has been removed from the system, e.g. an external GPU
+    /// that got unplugged. All the work submitted to it will fail with `DeviceLost`.
+    pub fn is_removed(&self) -> bool {
+        self.shared.is_removed()
+    }
+
+    /// Return true if the system asked to stop using the device, because it's about
+    /// to be removed, e.g. an external GPU that the user wants to eject. The device
+    /// keeps working until it's removed, so the application should release its
+    /// resources and move on to another adapter.
+    pub fn is_removal_requested(&self) -> bool {
+        self.shared.is_removal_requested()
+    }
+
     /// Return true if the specified format-swizzle pair is supported natively.
     pub fn supports_swizzle(&self, format: format::Format, swizzle: format::Swizzle) -> bool {
-        self.shared
-            .private_caps
-            .map_format_with_swizzle(format, swizzle)
-            .is_some()
+        let caps = &self.shared.private_caps;
+        caps.map_format_with_swizzle(format, swizzle).is_some()
+            || (caps.texture_swizzle && caps.map_format(format).is_some())
     }
 }
 
@@ -259,7 +788,7 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
             return Err(d::CreationError::MissingFeature);
         }
 
-        let device = self.shared.device.lock();
+        let device = &self.shared.device;
 
         assert_eq!(families.len(), 1);
         assert_eq!(families[0].1.len(), 1);
@@ -289,8 +818,16 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
             shared: self.shared.clone(),
             invalidation_queue: command::QueueInner::new(&*device, Some(1)),
             memory_types: self.memory_types.clone(),
+            public_slabs: Mutex::new(Vec::new()),
             features: requested_features,
             online_recording: OnlineRecording::default(),
+            pass_timing: false,
+            command_stats: false,
+            capture_target: CaptureTarget::default(),
+            shader_translator: None,
+            render_pipeline_states: Mutex::new(FastHashMap::default()),
+            shader_compiler: compiler::ShaderCompiler::default(),
+            next_private_data_slot: AtomicU64::new(0),
             #[cfg(any(feature = "pipeline-cache", feature = "cross"))]
             spv_options,
         };
@@ -343,6 +880,32 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
             // Can't create 2D/2DArray views of 3D textures
             return None;
         }
+        if !format.planes().is_empty() {
+            // Two-plane images are single 2D textures, with the chroma plane on the side.
+            if dimensions != 2
+                || tiling != image::Tiling::Optimal
+                || !view_caps.is_empty()
+                || usage.intersects(
+                    !(image::Usage::SAMPLED
+                        | image::Usage::TRANSFER_SRC
+                        | image::Usage::TRANSFER_DST),
+                )
+            {
+                return None;
+            }
+            let max_dimension = self.shared.private_caps.max_texture_size as _;
+            return Some(image::FormatProperties {
+                max_extent: image::Extent {
+                    width: max_dimension,
+                    height: max_dimension,
+                    depth: 1,
+                },
+                max_levels: 1,
+                max_layers: 1,
+                sample_count_mask: 1,
+                max_resource_size: self.shared.private_caps.max_buffer_size as _,
+            });
+        }
         let max_dimension = if dimensions == 3 {
             self.shared.private_caps.max_texture_3d_size as _
         } else {
@@ -375,6 +938,65 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
             })
     }
 
+    fn image_compression_properties(
+        &self,
+        format: format::Format,
+        _dimensions: u8,
+        tiling: image::Tiling,
+        _usage: image::Usage,
+        _view_caps: image::ViewCapabilities,
+    ) -> image::CompressionProperties {
+        // Linear images are backed by buffers, and block-compressed formats are never
+        // compressed further.
+        let implicit = tiling == image::Tiling::Optimal && !format.surface_desc().is_compressed();
+        image::CompressionProperties {
+            implicit,
+            can_disable: !implicit || self.shared.private_caps.gpu_optimized_contents,
+        }
+    }
+
+    fn external_buffer_properties(
+        &self,
+        _usage: buffer::Usage,
+        _sparse: memory::SparseFlags,
+    ) -> ExternalMemoryProperties {
+        ExternalMemoryProperties {
+            exportable: ExternalMemoryHandleTypes::empty(),
+            importable: ExternalMemoryHandleTypes::HOST_POINTER,
+        }
+    }
+
+    fn external_image_properties(
+        &self,
+        format: format::Format,
+        dimensions: u8,
+        tiling: image::Tiling,
+        usage: image::Usage,
+        view_caps: image::ViewCapabilities,
+    ) -> ExternalMemoryProperties {
+        let importable =
+            match self.image_format_properties(format, dimensions, tiling, usage, view_caps) {
+                Some(_) => external_image_handle_types(dimensions, tiling),
+                None => ExternalMemoryHandleTypes::empty(),
+            };
+        ExternalMemoryProperties {
+            exportable: ExternalMemoryHandleTypes::empty(),
+            importable,
+        }
+    }
+
+    fn external_semaphore_properties(&self) -> ExternalSyncProperties {
+        let handle_types = if self.shared.private_caps.shared_events {
+            ExternalSyncHandleTypes::METAL_SHARED_EVENT
+        } else {
+            ExternalSyncHandleTypes::empty()
+        };
+        ExternalSyncProperties {
+            exportable: handle_types,
+            importable: handle_types,
+        }
+    }
+
     fn memory_properties(&self) -> adapter::MemoryProperties {
         adapter::MemoryProperties {
             memory_heaps: vec![
@@ -397,8 +1019,8 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
             | F::INDEPENDENT_BLENDING
             | F::DRAW_INDIRECT_FIRST_INSTANCE
             | F::DEPTH_CLAMP
+            | F::DEPTH_BIAS_CLAMP
             | F::SAMPLER_ANISOTROPY
-            | F::FORMAT_BC
             | F::PRECISE_OCCLUSION_QUERY
             | F::SHADER_STORAGE_BUFFER_ARRAY_DYNAMIC_INDEXING
             | F::VERTEX_STORES_AND_ATOMICS
@@ -407,8 +1029,37 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
             | F::SEPARATE_STENCIL_REF_VALUES
             | F::SHADER_CLIP_DISTANCE
             | F::MUTABLE_UNNORMALIZED_SAMPLER
+            | F::DEPTH_STENCIL_RESOLVE
+            | F::SAMPLER_YCBCR_CONVERSION
+            | F::DYNAMIC_RENDERING
+            | F::EXTENDED_DYNAMIC_STATE
+            | F::VERTEX_INPUT_DYNAMIC_STATE
+            | F::PIPELINE_EXECUTABLE_INFO
+            | F::HOST_QUERY_RESET
+            | F::PRIVATE_DATA
+            | F::MUTABLE_DESCRIPTOR_TYPE
+            | F::GRAPHICS_PIPELINE_LIBRARY
+            | F::EXTERNAL_MEMORY
+            | F::HOST_IMAGE_COPY
             | F::NDC_Y_UP;
 
+        features.set(
+            F::SHADER_MODULE_IDENTIFIER,
+            cfg!(feature = "pipeline-cache"),
+        );
+        features.set(F::EXTERNAL_SYNC, self.shared.private_caps.shared_events);
+        // Timeline semaphores are backed by `MTLSharedEvent`.
+        features.set(
+            F::TIMELINE_SEMAPHORE,
+            self.shared.private_caps.shared_events,
+        );
+        features.set(
+            F::PERFORMANCE_COUNTER_QUERY,
+            counters::is_supported(&self.shared.device),
+        );
+        features.set(F::FORMAT_BC, self.shared.private_caps.format_bc);
+        features.set(F::FORMAT_ETC2, self.shared.private_caps.format_eac_etc);
+        features.set(F::FORMAT_ASTC_LDR, self.shared.private_caps.format_astc);
         features.set(
             F::IMAGE_CUBE_ARRAY,
             self.shared.private_caps.texture_cube_array,
@@ -421,12 +1072,29 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
             F::NON_FILL_POLYGON_MODE,
             self.shared.private_caps.expose_line_mode,
         );
+        // Naga rejects the SPIR-V capabilities of the narrow scalar types, so the shaders
+        // using them fall back to SPIRV-Cross, which maps them to `half`, `short` and `char`.
+        if cfg!(feature = "cross") {
+            features |= F::NARROW_TYPES_MASK | F::SHADER_INT16;
+        }
         if self.shared.private_caps.msl_version >= MTLLanguageVersion::V2_0 {
             features |= F::TEXTURE_DESCRIPTOR_ARRAY
                 | F::SHADER_SAMPLED_IMAGE_ARRAY_DYNAMIC_INDEXING
                 | F::SAMPLED_TEXTURE_DESCRIPTOR_INDEXING
                 | F::STORAGE_TEXTURE_DESCRIPTOR_INDEXING;
         }
+        if self.shared.private_caps.argument_buffers_tier2 {
+            features |= F::BUFFER_DESCRIPTOR_ARRAY
+                | F::SHADER_UNIFORM_BUFFER_ARRAY_DYNAMIC_INDEXING
+                | F::UNIFORM_BUFFER_DESCRIPTOR_INDEXING
+                | F::STORAGE_BUFFER_DESCRIPTOR_INDEXING
+                | F::UNSIZED_DESCRIPTOR_ARRAY;
+        }
+        if self.shared.private_caps.argument_buffers {
+            features |= F::DESCRIPTOR_BINDING_UPDATE_AFTER_BIND
+                | F::DESCRIPTOR_BINDING_PARTIALLY_BOUND
+                | F::DESCRIPTOR_BINDING_VARIABLE_COUNT;
+        }
         features.set(
             F::SAMPLER_BORDER_COLOR,
             self.shared.private_caps.sampler_clamp_to_border,
@@ -435,20 +1103,90 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
             F::MUTABLE_COMPARISON_SAMPLER,
             self.shared.private_caps.mutable_comparison_samplers,
         );
+        features.set(
+            F::FRAGMENT_SHADER_INTERLOCK,
+            self.shared.private_caps.raster_order_groups,
+        );
 
         //TODO: F::DEPTH_BOUNDS
         //TODO: F::SAMPLER_MIRROR_CLAMP_EDGE
+        //TODO: F::PROVOKING_VERTEX_LAST, which needs the flat-shaded attributes of
+        // indexed draws and strips to come from the last vertex as well
         features
     }
 
     fn properties(&self) -> hal::PhysicalDeviceProperties {
         let pc = &self.shared.private_caps;
-        let device = self.shared.device.lock();
+        let device = &self.shared.device;
 
         let mut caveats = hal::PerformanceCaveats::empty();
         if !self.shared.private_caps.base_vertex_instance_drawing {
             caveats |= hal::PerformanceCaveats::BASE_VERTEX_INSTANCE_DRAWING;
         }
+
+        // Naga doesn't parse the SPIR-V subgroup operations, so the shaders using them
+        // are translated to the SIMD-group functions by SPIRV-Cross.
+        let mut subgroup_operations = hal::SubgroupOperations::empty();
+        if cfg!(feature = "cross") {
+            if pc.simd_group_operations {
+                subgroup_operations |= hal::SubgroupOperations::BASIC
+                    | hal::SubgroupOperations::VOTE
+                    | hal::SubgroupOperations::ARITHMETIC
+                    | hal::SubgroupOperations::BALLOT
+                    | hal::SubgroupOperations::SHUFFLE
+                    | hal::SubgroupOperations::SHUFFLE_RELATIVE;
+            }
+            if pc.quad_group_operations {
+                subgroup_operations |= hal::SubgroupOperations::QUAD;
+            }
+        }
+
+        // Resources in argument buffers don't occupy the per-stage binding slots.
+        let descriptor_limits = if pc.argument_buffers_tier2 {
+            hal::DescriptorLimits {
+                max_per_stage_descriptor_samplers: ARGUMENT_BUFFER_TIER2_SAMPLERS,
+                max_per_stage_descriptor_uniform_buffers: ARGUMENT_BUFFER_TIER2_RESOURCES,
+                max_per_stage_descriptor_storage_buffers: ARGUMENT_BUFFER_TIER2_RESOURCES,
+                max_per_stage_descriptor_sampled_images: ARGUMENT_BUFFER_TIER2_RESOURCES,
+                max_per_stage_descriptor_storage_images: pc.max_textures_per_stage,
+                max_per_stage_descriptor_input_attachments: pc.max_textures_per_stage,
+                max_per_stage_resources: ARGUMENT_BUFFER_TIER2_RESOURCES,
+                max_descriptor_set_samplers: ARGUMENT_BUFFER_TIER2_SAMPLERS,
+                max_descriptor_set_uniform_buffers: ARGUMENT_BUFFER_TIER2_RESOURCES,
+                max_descriptor_set_uniform_buffers_dynamic: 0,
+                max_descriptor_set_storage_buffers: ARGUMENT_BUFFER_TIER2_RESOURCES,
+                max_descriptor_set_storage_buffers_dynamic: 0,
+                max_descriptor_set_sampled_images: ARGUMENT_BUFFER_TIER2_RESOURCES,
+                max_descriptor_set_storage_images: pc.max_textures_per_stage * SHADER_STAGE_COUNT,
+                max_descriptor_set_input_attachments: pc.max_textures_per_stage
+                    * SHADER_STAGE_COUNT,
+            }
+        } else {
+            hal::DescriptorLimits {
+                max_per_stage_descriptor_samplers: pc.max_samplers_per_stage,
+                max_per_stage_descriptor_uniform_buffers: pc.max_buffers_per_stage,
+                max_per_stage_descriptor_storage_buffers: pc.max_buffers_per_stage,
+                max_per_stage_descriptor_sampled_images: pc
+                    .max_textures_per_stage
+                    .min(pc.max_samplers_per_stage)
+                    as u32,
+                max_per_stage_descriptor_storage_images: pc.max_textures_per_stage,
+                max_per_stage_descriptor_input_attachments: pc.max_textures_per_stage, //TODO
+                max_per_stage_resources: 0x100,                                        //TODO
+                max_descriptor_set_samplers: pc.max_samplers_per_stage * SHADER_STAGE_COUNT,
+                max_descriptor_set_uniform_buffers: pc.max_buffers_per_stage * SHADER_STAGE_COUNT,
+                max_descriptor_set_uniform_buffers_dynamic: 8 * SHADER_STAGE_COUNT,
+                max_descriptor_set_storage_buffers: pc.max_buffers_per_stage * SHADER_STAGE_COUNT,
+                max_descriptor_set_storage_buffers_dynamic: 4 * SHADER_STAGE_COUNT,
+                max_descriptor_set_sampled_images: pc
+                    .max_textures_per_stage
+                    .min(pc.max_samplers_per_stage)
+                    * SHADER_STAGE_COUNT,
+                max_descriptor_set_storage_images: pc.max_textures_per_stage * SHADER_STAGE_COUNT,
+                max_descriptor_set_input_attachments: pc.max_textures_per_stage
+                    * SHADER_STAGE_COUNT,
+            }
+        };
         hal::PhysicalDeviceProperties {
             limits: hal::Limits {
                 max_image_1d_size: pc.max_texture_size as _,
@@ -459,37 +1197,11 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
                 max_texel_elements: (pc.max_texture_size * pc.max_texture_size) as usize,
                 max_uniform_buffer_range: pc.max_buffer_size,
                 max_storage_buffer_range: pc.max_buffer_size,
-                // "Maximum length of an inlined constant data buffer, per graphics or compute function"
-                max_push_constants_size: 0x1000,
+                // Anything above the inline data limit of 0x1000 is spilled into a buffer.
+                max_push_constants_size: 0x10000,
                 max_sampler_allocation_count: !0,
                 max_bound_descriptor_sets: MAX_BOUND_DESCRIPTOR_SETS as _,
-                descriptor_limits: hal::DescriptorLimits {
-                    max_per_stage_descriptor_samplers: pc.max_samplers_per_stage,
-                    max_per_stage_descriptor_uniform_buffers: pc.max_buffers_per_stage,
-                    max_per_stage_descriptor_storage_buffers: pc.max_buffers_per_stage,
-                    max_per_stage_descriptor_sampled_images: pc
-                        .max_textures_per_stage
-                        .min(pc.max_samplers_per_stage)
-                        as u32,
-                    max_per_stage_descriptor_storage_images: pc.max_textures_per_stage,
-                    max_per_stage_descriptor_input_attachments: pc.max_textures_per_stage, //TODO
-                    max_per_stage_resources: 0x100,                                        //TODO
-                    max_descriptor_set_samplers: pc.max_samplers_per_stage * SHADER_STAGE_COUNT,
-                    max_descriptor_set_uniform_buffers: pc.max_buffers_per_stage
-                        * SHADER_STAGE_COUNT,
-                    max_descriptor_set_uniform_buffers_dynamic: 8 * SHADER_STAGE_COUNT,
-                    max_descriptor_set_storage_buffers: pc.max_buffers_per_stage
-                        * SHADER_STAGE_COUNT,
-                    max_descriptor_set_storage_buffers_dynamic: 4 * SHADER_STAGE_COUNT,
-                    max_descriptor_set_sampled_images: pc
-                        .max_textures_per_stage
-                        .min(pc.max_samplers_per_stage)
-                        * SHADER_STAGE_COUNT,
-                    max_descriptor_set_storage_images: pc.max_textures_per_stage
-                        * SHADER_STAGE_COUNT,
-                    max_descriptor_set_input_attachments: pc.max_textures_per_stage
-                        * SHADER_STAGE_COUNT,
-                },
+                descriptor_limits,
                 max_fragment_input_components: pc.max_fragment_input_components as usize,
                 max_framebuffer_layers: 2048, // TODO: Determine is this is the correct value
                 max_memory_allocation_count: 4096, // TODO: Determine is this is the correct value
@@ -526,6 +1238,7 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
                 max_vertex_input_attribute_offset: 255, // TODO
                 max_vertex_input_binding_stride: 256,   // TODO
                 max_vertex_output_components: pc.max_fragment_input_components as usize,
+                max_clip_distances: 8,
 
                 framebuffer_color_sample_counts: 0b101, // TODO
                 framebuffer_depth_sample_counts: 0b101, // TODO
@@ -537,18 +1250,57 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
                 // and those need to operate on sizes being multiples of 4.
                 non_coherent_atom_size: 4,
                 max_sampler_anisotropy: 16.,
-                min_vertex_input_binding_stride_alignment: STRIDE_GRANULARITY as u64,
+                // Other strides are handled by vertex pulling, which requires Naga.
+                min_vertex_input_binding_stride_alignment: if cfg!(feature = "cross") {
+                    STRIDE_GRANULARITY as u64
+                } else {
+                    1
+                },
 
                 ..hal::Limits::default() // TODO!
             },
             downlevel: hal::DownlevelProperties::all_enabled(),
             performance_caveats: caveats,
             dynamic_pipeline_states: hal::DynamicStates::all(),
+            subgroup: hal::SubgroupProperties {
+                size: pc.simd_group_size,
+                supported_stages: if subgroup_operations.is_empty() {
+                    pso::ShaderStageFlags::empty()
+                } else {
+                    pso::ShaderStageFlags::FRAGMENT | pso::ShaderStageFlags::COMPUTE
+                },
+                supported_operations: subgroup_operations,
+                quad_operations_in_all_stages: false,
+            },
+            robustness: hal::RobustnessProperties {
+                // The shader translation doesn't check the accesses against the binding sizes.
+                robust_buffer_access_2: false,
+                null_descriptor: true,
+            },
+            sample_locations: if pc.programmable_sample_positions {
+                hal::SampleLocationsProperties {
+                    sample_counts: pc.sample_count_mask,
+                    // The positions are specified on a 16x16 grid.
+                    sub_pixel_bits: 4,
+                }
+            } else {
+                hal::SampleLocationsProperties::default()
+            },
+            // Naga doesn't parse the physical storage buffer pointers, so the shaders using
+            // them are translated to device pointers by SPIRV-Cross.
+            buffer_device_address: hal::BufferDeviceAddressProperties {
+                buffer_device_address: pc.buffer_gpu_address,
+                shader_pointers: pc.buffer_gpu_address && cfg!(feature = "cross"),
+            },
 
             ..hal::PhysicalDeviceProperties::default()
         }
     }
 
+    fn performance_counter_sets(&self) -> Vec<query::CounterSet> {
+        counters::counter_sets(&self.shared.device)
+    }
+
     unsafe fn enumerate_displays(
         &self,
     ) -> Vec<hal::display::Display<crate::Backend>> {
@@ -595,16 +1347,141 @@ impl Device {
     fn _is_heap_coherent(&self, heap: &n::MemoryHeap) -> bool {
         match *heap {
             n::MemoryHeap::Private => false,
-            n::MemoryHeap::Public(memory_type, _) => self.memory_types[memory_type.0]
+            n::MemoryHeap::Public(memory_type, ..) => self.memory_types[memory_type.0]
                 .properties
                 .contains(Properties::COHERENT),
             n::MemoryHeap::Native(ref heap) => heap.storage_mode() == MTLStorageMode::Shared,
+            n::MemoryHeap::IOSurface(_) => false,
+        }
+    }
+
+    /// Get the external semaphore handle types that are supported by the device.
+    fn external_semaphore_handle_types(&self) -> ExternalSyncHandleTypes {
+        if self.shared.private_caps.shared_events {
+            ExternalSyncHandleTypes::METAL_SHARED_EVENT
+        } else {
+            ExternalSyncHandleTypes::empty()
+        }
+    }
+
+    /// Get the memory types that textures copied on the host can be bound to.
+    fn host_transfer_memory_types(&self) -> MemoryTypes {
+        // Textures can't have shared storage on macOS
+        if self.shared.private_caps.os_is_mac {
+            MemoryTypes::MANAGED_UPLOAD
+        } else {
+            MemoryTypes::SHARED
+        }
+    }
+
+    /// Create the texture of the chroma plane of an image bound to `memory`.
+    unsafe fn create_chroma_texture(
+        &self,
+        memory: &n::Memory,
+        descriptor: &metal::TextureDescriptorRef,
+        name: &str,
+    ) -> Result<metal::Texture, d::BindError> {
+        let texture = match memory.heap {
+            n::MemoryHeap::Native(ref heap) => {
+                let resource_options = conv::resource_options_from_storage_and_cache(
+                    heap.storage_mode(),
+                    heap.cpu_cache_mode(),
+                );
+                descriptor.set_resource_options(resource_options);
+                heap.new_texture(descriptor)
+                    .unwrap_or_else(|| self.shared.device.new_texture(descriptor))
+            }
+            n::MemoryHeap::Private => {
+                descriptor.set_storage_mode(MTLStorageMode::Private);
+                self.shared.device.new_texture(descriptor)
+            }
+            n::MemoryHeap::IOSurface(ref surface) => {
+                let raw: *mut metal::MTLTexture = msg_send![
+                    self.shared.device.as_ref(),
+                    newTextureWithDescriptor: descriptor
+                    iosurface: surface.0.as_ptr()
+                    plane: 1 as NSUInteger
+                ];
+                if raw.is_null() {
+                    return Err(d::BindError::WrongMemory);
+                }
+                metal::Texture::from_ptr(raw)
+            }
+            // two-plane images are never host visible
+            n::MemoryHeap::Public(..) => return Err(d::BindError::WrongMemory),
+        };
+        texture.set_label(&format!("{} chroma", name));
+        Ok(texture)
+    }
+
+    /// Make the writes of the GPU to a managed resource visible to the host.
+    fn synchronize_managed_resource(&self, resource: &metal::ResourceRef) {
+        if resource.storage_mode() != MTLStorageMode::Managed {
+            return;
+        }
+        let cmd_buffer = self.invalidation_queue.spawn_temp();
+        autoreleasepool(|| {
+            let encoder = cmd_buffer.new_blit_command_encoder();
+            encoder.synchronize_resource(resource);
+            encoder.end_encoding();
+        });
+        cmd_buffer.set_label("synchronize_managed_resource");
+        cmd_buffer.commit();
+        cmd_buffer.wait_until_completed();
+    }
+
+    /// Sub-allocate a range of a shared CPU buffer, creating a new one when all are full.
+    ///
+    /// Only used for `Shared` storage, see `MAX_PUBLIC_SUBALLOCATION`.
+    fn suballocate_public(
+        &self,
+        device: &metal::DeviceRef,
+        memory_type: hal::MemoryTypeId,
+        size: u64,
+    ) -> (metal::Buffer, u64) {
+        let size = (size + PUBLIC_SLAB_ALIGNMENT - 1) & !(PUBLIC_SLAB_ALIGNMENT - 1);
+        let mut slabs = self.public_slabs.lock();
+        for slab in slabs.iter_mut() {
+            if slab.memory_type != memory_type {
+                continue;
+            }
+            if let Ok(range) = slab.allocator.allocate_range(size) {
+                return (slab.raw.clone(), range.start);
+            }
+        }
+
+        let (storage, cache) = MemoryTypes::describe(memory_type.0);
+        let options = conv::resource_options_from_storage_and_cache(storage, cache);
+        let raw = device.new_buffer(PUBLIC_SLAB_SIZE, options);
+        debug!("\tcreated public slab {:?}", raw.as_ptr());
+        let mut allocator = RangeAllocator::new(0..PUBLIC_SLAB_SIZE);
+        let range = allocator.allocate_range(size).unwrap();
+        slabs.push(n::PublicSlab {
+            memory_type,
+            raw: raw.clone(),
+            allocator,
+        });
+        (raw, range.start)
+    }
+
+    /// Return a sub-allocated range to its buffer, releasing the buffer once it's unused.
+    fn free_public(&self, cpu_buffer: &metal::BufferRef, offset: u64, size: u64) {
+        let size = (size + PUBLIC_SLAB_ALIGNMENT - 1) & !(PUBLIC_SLAB_ALIGNMENT - 1);
+        let mut slabs = self.public_slabs.lock();
+        let index = slabs
+            .iter()
+            .position(|slab| slab.raw.as_ptr() == cpu_buffer.as_ptr())
+            .expect("Public memory doesn't belong to any slab");
+        slabs[index].allocator.free_range(offset..offset + size);
+        if slabs[index].allocator.is_empty() {
+            debug!("\treleasing public slab {:?}", cpu_buffer.as_ptr());
+            slabs.swap_remove(index);
         }
     }
 
     #[cfg(feature = "cross")]
     fn compile_shader_library_cross(
-        device: &Mutex<metal::Device>,
+        device: &metal::DeviceRef,
         raw_data: &[u32],
         compiler_options: &spirv_cross::msl::CompilerOptions,
         msl_version: MTLLanguageVersion,
@@ -682,23 +1559,26 @@ impl Device {
         let library = {
             profiling::scope!("Metal::new_library_with_source");
             device
-                .lock()
                 .new_library_with_source(shader_code.as_ref(), &options)
                 .map_err(|err| err.to_string())?
         };
 
         Ok(n::ModuleInfo {
             library,
+            source_hash: fxhash::hash64(&(&shader_code, msl_version as u64)),
+            source: shader_code.into(),
             entry_point_map,
             rasterization_enabled,
+            sized_bindings: Vec::new(),
         })
     }
 
     fn compile_shader_library_naga(
-        device: &Mutex<metal::Device>,
-        shader: &d::NagaShader,
+        device: &metal::DeviceRef,
+        shader: Option<&d::NagaShader>,
         naga_options: &naga::back::msl::Options,
         pipeline_options: &naga::back::msl::PipelineOptions,
+        raster_ordered_entry: Option<&str>,
         #[cfg(feature = "pipeline-cache")] spv_hash: u64,
         #[cfg(feature = "pipeline-cache")] spv_to_msl_cache: Option<&pipeline_cache::SpvToMsl>,
     ) -> Result<n::ModuleInfo, String> {
@@ -707,6 +1587,11 @@ impl Device {
         let get_module_info = || {
             profiling::scope!("naga::msl::write_string");
 
+            let shader = match shader {
+                Some(shader) => shader,
+                None => return Err("The shader module has no code to compile".to_string()),
+            };
+
             let (source, info) = match naga::back::msl::write_string(
                 &shader.module,
                 &shader.info,
@@ -742,26 +1627,59 @@ impl Device {
                 source,
                 entry_point_map,
                 rasterization_enabled: true, //TODO
+                sized_bindings: collect_sized_bindings(&shader.module),
             })
         };
 
         #[cfg(feature = "pipeline-cache")]
-        let module_info = if let Some(spv_to_msl_cache) = spv_to_msl_cache {
+        let mut module_info = if let Some(spv_to_msl_cache) = spv_to_msl_cache {
             let key = pipeline_cache::SpvToMslKey {
                 options: naga_options.clone(),
                 pipeline_options: pipeline_options.clone(),
                 spv_hash,
             };
 
-            spv_to_msl_cache
-                .get_or_create_with(&key, || get_module_info().unwrap())
-                .clone()
+            if shader.is_some() {
+                spv_to_msl_cache
+                    .get_or_create_with(&key, || get_module_info().unwrap())
+                    .clone()
+            } else {
+                spv_to_msl_cache
+                    .whole_write()
+                    .get(&key)
+                    .cloned()
+                    .ok_or_else(|| "The shader is not in the pipeline cache".to_string())?
+            }
         } else {
             get_module_info()?
         };
 
         #[cfg(not(feature = "pipeline-cache"))]
-        let module_info = get_module_info()?;
+        let mut module_info = get_module_info()?;
+
+        if let Some(entry) = raster_ordered_entry {
+            let slots = naga_options
+                .binding_map
+                .iter()
+                .filter(|&(source, target)| {
+                    source.stage == naga::ShaderStage::Fragment && target.mutable
+                })
+                .flat_map(|(_, target)| {
+                    let buffer = target.buffer.map(|slot| format!("buffer({})", slot));
+                    let texture = target.texture.map(|slot| format!("texture({})", slot));
+                    buffer.into_iter().chain(texture)
+                })
+                .collect::<Vec<_>>();
+            let ordered = module_info
+                .entry_point_map
+                .get(&(naga::ShaderStage::Fragment, entry.to_string()))
+                .and_then(|ep| ep.internal_name.as_ref().ok())
+                .and_then(|name| order_raster_accesses(&module_info.source, name, &slots));
+            match ordered {
+                Some(source) => module_info.source = source,
+                None => return Err(format!("Unable to order the accesses of {:?}", entry)),
+            }
+        }
 
         let options = metal::CompileOptions::new();
         let msl_version = match naga_options.lang_version {
@@ -779,7 +1697,6 @@ impl Device {
         let library = {
             profiling::scope!("Metal::new_library_with_source");
             device
-                .lock()
                 .new_library_with_source(module_info.source.as_ref(), &options)
                 .map_err(|err| {
                     warn!("Naga generated shader:\n{}", module_info.source);
@@ -790,12 +1707,14 @@ impl Device {
 
         Ok(n::ModuleInfo {
             library,
+            source_hash: fxhash::hash64(&(&module_info.source, msl_version as u64)),
+            source: module_info.source.into(),
             entry_point_map: module_info.entry_point_map,
             rasterization_enabled: module_info.rasterization_enabled,
+            sized_bindings: module_info.sized_bindings,
         })
     }
 
-    #[cfg_attr(not(feature = "pipeline-cache"), allow(unused_variables))]
     fn load_shader(
         &self,
         ep: &pso::EntryPoint<Backend>,
@@ -803,52 +1722,165 @@ impl Device {
         primitive_class: MTLPrimitiveTopologyClass,
         pipeline_cache: Option<&n::PipelineCache>,
         stage: naga::ShaderStage,
+        rewrite: Option<ShaderRewrite>,
+        raster_ordered: bool,
     ) -> Result<CompiledShader, pso::CreationError> {
-        let _profiling_tag = match stage {
-            naga::ShaderStage::Vertex => "vertex",
-            naga::ShaderStage::Fragment => "fragment",
-            naga::ShaderStage::Compute => "compute",
-        };
-        profiling::scope!("load_shader", _profiling_tag);
-
-        let device = &self.shared.device;
+        let library = self.request_library(
+            ep,
+            layout,
+            primitive_class,
+            pipeline_cache,
+            rewrite,
+            raster_ordered,
+        );
+        self.finish_shader(ep, layout, primitive_class, stage, rewrite, library)
+    }
 
-        #[cfg(feature = "cross")]
-        let mut compiler_options = layout.spirv_cross_options.clone();
-        #[cfg(feature = "cross")]
-        {
-            compiler_options.entry_point =
-                Some((ep.entry.to_string(), conv::map_naga_stage_to_cross(stage)));
-            compiler_options.enable_point_size_builtin =
-                primitive_class == MTLPrimitiveTopologyClass::Point;
-        }
+    /// Start producing the library of a shader stage on the compiler threads,
+    /// to be picked up by `finish_shader`.
+    #[cfg_attr(not(feature = "pipeline-cache"), allow(unused_variables))]
+    fn request_library(
+        &self,
+        ep: &pso::EntryPoint<Backend>,
+        layout: &n::PipelineLayout,
+        primitive_class: MTLPrimitiveTopologyClass,
+        pipeline_cache: Option<&n::PipelineCache>,
+        rewrite: Option<ShaderRewrite>,
+        raster_ordered: bool,
+    ) -> compiler::PendingLibrary {
+        let shader = match ep.module.naga {
+            Ok(ref shader) => Some(Arc::clone(shader)),
+            Err(_) if ep.module.from_identifier => None,
+            Err(ref e) => return compiler::PendingLibrary::ready(Err(e.clone())),
+        };
         let pipeline_options = naga::back::msl::PipelineOptions {
             allow_point_size: match primitive_class {
                 MTLPrimitiveTopologyClass::Point => true,
                 _ => false,
             },
         };
+        let shared = Arc::clone(&self.shared);
+        #[cfg(feature = "pipeline-cache")]
+        let spv_hash = ep.module.spv_hash;
 
-        let info = {
-            #[cfg_attr(not(feature = "cross"), allow(unused_mut))]
-            let mut result = match ep.module.naga {
-                Ok(ref shader) => Self::compile_shader_library_naga(
-                    device,
-                    shader,
-                    &layout.naga_options,
+        if rewrite.is_some() || !layout.ycbcr_bindings.is_empty() {
+            // The rewritten shader doesn't match the SPIR-V, so it's neither cached nor shared.
+            let shader = match shader {
+                Some(shader) => shader,
+                None => {
+                    return compiler::PendingLibrary::ready(Err(
+                        "The shader module has no code to rewrite".to_string(),
+                    ))
+                }
+            };
+            let rewritten = |shader: &d::NagaShader, options: &naga::back::msl::Options| {
+                Some(match rewrite? {
+                    ShaderRewrite::VertexPulling(pulling) => pulling.transform(
+                        shader,
+                        ep.entry,
+                        options,
+                        self.shared.private_caps.max_buffers_per_stage,
+                    ),
+                    ShaderRewrite::DispatchBase(slot) => {
+                        dispatch_base::transform(shader, ep.entry, options, slot)
+                    }
+                })
+            };
+            // Convert the samples first, the other rewrites only wrap the entry point.
+            let transformed = if layout.ycbcr_bindings.is_empty() {
+                rewritten(&shader, &layout.naga_options).unwrap()
+            } else {
+                ycbcr::transform(&shader, &layout.naga_options, &layout.ycbcr_bindings).and_then(
+                    |(shader, options)| match rewritten(&shader, &options) {
+                        Some(result) => result,
+                        None => Ok((shader, options)),
+                    },
+                )
+            };
+            return self.shader_compiler.compile(None, move || {
+                let (shader, naga_options) = transformed?;
+                Self::compile_shader_library_naga(
+                    &shared.device,
+                    Some(&shader),
+                    &naga_options,
                     &pipeline_options,
+                    None,
                     #[cfg(feature = "pipeline-cache")]
-                    ep.module.spv_hash,
+                    spv_hash,
                     #[cfg(feature = "pipeline-cache")]
-                    pipeline_cache.as_ref().map(|cache| &cache.spv_to_msl),
-                ),
-                Err(ref e) => Err(e.clone()),
-            };
+                    None,
+                )
+            });
+        }
+
+        let naga_options = layout.naga_options.clone();
+        let raster_ordered_entry = if raster_ordered {
+            Some(ep.entry.to_string())
+        } else {
+            None
+        };
+        let key = compiler::LibraryKey {
+            module: ep.module.key.clone(),
+            options: naga_options.clone(),
+            pipeline_options: pipeline_options.clone(),
+            raster_ordered_entry: raster_ordered_entry.clone(),
+        };
+        #[cfg(feature = "pipeline-cache")]
+        let spv_to_msl = pipeline_cache.map(|cache| Arc::clone(&cache.spv_to_msl));
+
+        self.shader_compiler.compile(Some(key), move || {
+            Self::compile_shader_library_naga(
+                &shared.device,
+                shader.as_deref(),
+                &naga_options,
+                &pipeline_options,
+                raster_ordered_entry.as_deref(),
+                #[cfg(feature = "pipeline-cache")]
+                spv_hash,
+                #[cfg(feature = "pipeline-cache")]
+                spv_to_msl.as_deref(),
+            )
+        })
+    }
+
+    /// Wait for the library requested with `request_library` and get the entry point.
+    #[cfg_attr(not(feature = "cross"), allow(unused_variables))]
+    fn finish_shader(
+        &self,
+        ep: &pso::EntryPoint<Backend>,
+        layout: &n::PipelineLayout,
+        primitive_class: MTLPrimitiveTopologyClass,
+        stage: naga::ShaderStage,
+        rewrite: Option<ShaderRewrite>,
+        library: compiler::PendingLibrary,
+    ) -> Result<CompiledShader, pso::CreationError> {
+        let _profiling_tag = match stage {
+            naga::ShaderStage::Vertex => "vertex",
+            naga::ShaderStage::Fragment => "fragment",
+            naga::ShaderStage::Compute => "compute",
+        };
+        profiling::scope!("finish_shader", _profiling_tag);
+
+        #[cfg_attr(not(feature = "cross"), allow(unused_mut))]
+        let mut translator = ShaderTranslator::Naga;
+        let info = {
+            #[cfg_attr(not(feature = "cross"), allow(unused_mut))]
+            let mut result = library.wait();
 
             #[cfg(feature = "cross")]
-            if result.is_err() {
+            if result.is_err()
+                && rewrite.is_none()
+                && layout.ycbcr_bindings.is_empty()
+                && ep.module.translator != Some(ShaderTranslator::Naga)
+            {
+                translator = ShaderTranslator::SpirvCross;
+                let mut compiler_options = layout.spirv_cross_options.clone();
+                compiler_options.entry_point =
+                    Some((ep.entry.to_string(), conv::map_naga_stage_to_cross(stage)));
+                compiler_options.enable_point_size_builtin =
+                    primitive_class == MTLPrimitiveTopologyClass::Point;
                 result = Self::compile_shader_library_cross(
-                    device,
+                    &self.shared.device,
                     &ep.module.spv,
                     &compiler_options,
                     self.shared.private_caps.msl_version,
@@ -861,28 +1893,13 @@ impl Device {
                 pso::CreationError::ShaderCreationError(stage.into(), error)
             })?
         };
+        debug!("{:?} shader is translated with {:?}", stage, translator);
 
-        // collect sizes indices
-        let mut sized_bindings = Vec::new();
-        if let Ok(ref shader) = ep.module.naga {
-            for (_handle, var) in shader.module.global_variables.iter() {
-                if let naga::TypeInner::Struct { ref members, .. } =
-                    shader.module.types[var.ty].inner
-                {
-                    if let Some(member) = members.last() {
-                        if let naga::TypeInner::Array {
-                            size: naga::ArraySize::Dynamic,
-                            ..
-                        } = shader.module.types[member.ty].inner
-                        {
-                            // Note: unwraps are fine, since the MSL is already generated
-                            let br = var.binding.clone().unwrap();
-                            sized_bindings.push(br);
-                        }
-                    }
-                }
-            }
-        }
+        // collect sizes indices, the modules without code get them from the cache
+        let sized_bindings = match ep.module.naga {
+            Ok(ref shader) => collect_sized_bindings(&shader.module),
+            Err(_) => info.sized_bindings.clone(),
+        };
 
         let lib = info.library.clone();
         let entry_key = (stage, ep.entry.to_string());
@@ -925,12 +1942,23 @@ impl Device {
             pso::CreationError::ShaderCreationError(stage.into(), error)
         })?;
 
+        let function_key = n::FunctionKey {
+            source_hash: info.source_hash,
+            source: Arc::clone(&info.source),
+            name: name.to_string(),
+            constants: ep.specialization.constants.to_vec(),
+            data: ep.specialization.data.to_vec(),
+        };
+
         Ok(CompiledShader {
             library: lib,
             function: mtl_function,
             wg_size,
             rasterizing: info.rasterization_enabled,
             sized_bindings,
+            function_key,
+            translator,
+            source: Arc::clone(&info.source),
         })
     }
 
@@ -1000,6 +2028,404 @@ impl Device {
 
         Some(descriptor)
     }
+
+    /// Create a pipeline that runs a tile function within the given subpass,
+    /// see `CommandBuffer::dispatch_tile`.
+    ///
+    /// Tile functions are only supported by Apple GPUs of family 4 and newer.
+    pub unsafe fn create_tile_pipeline(
+        &self,
+        desc: &n::TilePipelineDesc,
+    ) -> Result<n::TilePipeline, pso::CreationError> {
+        profiling::scope!("create_tile_pipeline");
+        let caps = &self.shared.private_caps;
+        if !caps.tile_shaders {
+            error!("Tile functions are not supported by the device");
+            return Err(pso::CreationError::UnsupportedPipeline);
+        }
+        let subpass = desc
+            .subpass
+            .main_pass
+            .subpasses
+            .get(desc.subpass.index as usize)
+            .ok_or(pso::CreationError::InvalidSubpass(desc.subpass.index))?;
+
+        let options = metal::CompileOptions::new();
+        options.set_language_version(caps.msl_version);
+        let library = self
+            .shared
+            .device
+            .new_library_with_source(desc.source, &options)
+            .map_err(|err| {
+                pso::CreationError::ShaderCreationError(pso::ShaderStageFlags::COMPUTE, err)
+            })?;
+        let function = library
+            .get_function(desc.entry, None)
+            .map_err(|_| pso::CreationError::MissingEntryPoint(desc.entry.to_string()))?;
+
+        profiling::scope!("Metal::new_tile_pipeline_state");
+        let raw = autoreleasepool(|| {
+            let descriptor: *mut Object = msg_send![class!(MTLTileRenderPipelineDescriptor), new];
+            let () = msg_send![descriptor, setTileFunction: function.as_ref()];
+            let colors: *mut Object = msg_send![descriptor, colorAttachments];
+            for (i, at) in subpass.attachments.colors.iter().enumerate() {
+                let color: *mut Object =
+                    msg_send![colors, objectAtIndexedSubscript: i as NSUInteger];
+                let () = msg_send![color, setPixelFormat: at.format];
+            }
+            let () = msg_send![descriptor, setRasterSampleCount: subpass.samples as NSUInteger];
+            let matches = if desc.threadgroup_size_matches_tile_size {
+                YES
+            } else {
+                NO
+            };
+            let () = msg_send![descriptor, setThreadgroupSizeMatchesTileSize: matches];
+
+            let mut err: *mut Object = ptr::null_mut();
+            let raw: *mut metal::MTLRenderPipelineState = msg_send![
+                self.shared.device.as_ref(),
+                newRenderPipelineStateWithTileDescriptor: descriptor
+                options: 0 as NSUInteger
+                reflection: ptr::null_mut::<*mut Object>()
+                error: &mut err
+            ];
+            let () = msg_send![descriptor, release];
+            if raw.is_null() {
+                let description: *mut Object = msg_send![err, localizedDescription];
+                let utf8: *const c_char = msg_send![description, UTF8String];
+                error!(
+                    "Tile PSO creation failed: {}",
+                    CStr::from_ptr(utf8).to_string_lossy()
+                );
+                Err(pso::CreationError::Other)
+            } else {
+                Ok(metal::RenderPipelineState::from_ptr(raw))
+            }
+        })?;
+
+        Ok(n::TilePipeline {
+            raw,
+            threadgroup_memory: desc.threadgroup_memory.to_vec(),
+        })
+    }
+
+    /// Write a batch of descriptor set updates, as if they were given one by one
+    /// to `write_descriptor_set`.
+    ///
+    /// Consecutive writes into the sets of the same pool only lock the pool once,
+    /// and consecutive resources of an argument buffer are encoded together.
+    pub unsafe fn write_descriptor_sets<'a, I, J>(&self, writes: J)
+    where
+        I: Iterator<Item = pso::Descriptor<'a, Backend>>,
+        J: IntoIterator<Item = pso::DescriptorSetWrite<'a, Backend, I>>,
+    {
+        debug!("write_descriptor_sets");
+        // pools locked by the previous writes
+        let mut emulated_lock: Option<(&Arc<_>, RwLockWriteGuard<_>)> = None;
+        let mut argument_lock: Option<(&Arc<_>, RwLockWriteGuard<_>)> = None;
+        let mut run = ArgumentRun::default();
+
+        for op in writes {
+            let set: &'a n::DescriptorSet = op.set;
+            match *set {
+                n::DescriptorSet::Emulated {
+                    ref pool,
+                    ref layouts,
+                    ref resources,
+                } => {
+                    match emulated_lock {
+                        Some((locked, _)) if Arc::ptr_eq(locked, pool) => {}
+                        _ => emulated_lock = Some((pool, pool.write())),
+                    }
+                    let data = &mut emulated_lock.as_mut().unwrap().1;
+                    let start = layouts
+                        .binary_search_by_key(&(op.binding, op.array_offset), |dl| {
+                            (dl.binding, dl.array_index)
+                        })
+                        .unwrap();
+                    let offset = &layouts[start].offset;
+                    let mut counters = n::ResourceData {
+                        buffers: resources.buffers.start + offset.buffers,
+                        textures: resources.textures.start + offset.textures,
+                        samplers: resources.samplers.start + offset.samplers,
+                    };
+
+                    for (layout, descriptor) in layouts[start..].iter().zip(op.descriptors) {
+                        trace!("\t{:?}", layout);
+                        let descriptor = match descriptor {
+                            pso::Descriptor::Mutable(_ty, inner) => inner.clone(),
+                            other => other,
+                        };
+                        match descriptor {
+                            pso::Descriptor::Sampler(sam) => {
+                                debug_assert!(!layout
+                                    .content
+                                    .contains(n::DescriptorContent::IMMUTABLE_SAMPLER));
+                                data.samplers[counters.samplers as usize] = (
+                                    layout.stages,
+                                    Some(AsNative::from(sam.raw.as_ref().unwrap().as_ref())),
+                                );
+                            }
+                            pso::Descriptor::Image(view, il) => {
+                                data.textures[counters.textures as usize] = (
+                                    layout.stages,
+                                    Some(AsNative::from(view.texture.as_ref())),
+                                    il,
+                                );
+                            }
+                            pso::Descriptor::CombinedImageSampler(view, il, sam) => {
+                                if !layout
+                                    .content
+                                    .contains(n::DescriptorContent::IMMUTABLE_SAMPLER)
+                                {
+                                    data.samplers[counters.samplers as usize] = (
+                                        layout.stages,
+                                        Some(AsNative::from(sam.raw.as_ref().unwrap().as_ref())),
+                                    );
+                                }
+                                data.textures[counters.textures as usize] = (
+                                    layout.stages,
+                                    Some(AsNative::from(view.texture.as_ref())),
+                                    il,
+                                );
+                                if layout.content.contains(n::DescriptorContent::CHROMA_PLANE) {
+                                    data.textures[counters.textures as usize + 1] = (
+                                        layout.stages,
+                                        view.chroma.as_ref().map(|t| AsNative::from(t.as_ref())),
+                                        il,
+                                    );
+                                }
+                            }
+                            pso::Descriptor::TexelBuffer(view) => {
+                                data.textures[counters.textures as usize] = (
+                                    layout.stages,
+                                    Some(AsNative::from(view.raw.as_ref())),
+                                    image::Layout::General,
+                                );
+                            }
+                            pso::Descriptor::Buffer(buf, ref sub) => {
+                                let (raw, range) = buf.as_bound();
+                                debug_assert!(
+                                    range.start + sub.offset + sub.size.unwrap_or(0) <= range.end
+                                );
+                                let raw_binding_size = match sub.size {
+                                    Some(size) => size,
+                                    None => range.end - range.start - sub.offset,
+                                };
+                                data.buffers[counters.buffers as usize] = (
+                                    layout.stages,
+                                    Some(AsNative::from(raw)),
+                                    range.start + sub.offset,
+                                    layout.binding,
+                                    if layout.content.contains(n::DescriptorContent::SIZED_BUFFER) {
+                                        raw_binding_size.min(u32::MAX as buffer::Offset - 1) as u32
+                                    } else {
+                                        !0
+                                    },
+                                );
+                            }
+                            pso::Descriptor::Null(pso::DescriptorType::Buffer {
+                                format: pso::BufferDescriptorFormat::Structured { .. },
+                                ..
+                            }) => {
+                                data.buffers[counters.buffers as usize] = (
+                                    layout.stages,
+                                    None,
+                                    0,
+                                    layout.binding,
+                                    if layout.content.contains(n::DescriptorContent::SIZED_BUFFER) {
+                                        0
+                                    } else {
+                                        !0
+                                    },
+                                );
+                            }
+                            pso::Descriptor::Null(pso::DescriptorType::Image { .. })
+                            | pso::Descriptor::Null(pso::DescriptorType::InputAttachment)
+                            | pso::Descriptor::Null(pso::DescriptorType::Buffer { .. }) => {
+                                data.textures[counters.textures as usize] =
+                                    (layout.stages, None, image::Layout::General);
+                                if layout.content.contains(n::DescriptorContent::CHROMA_PLANE) {
+                                    data.textures[counters.textures as usize + 1] =
+                                        (layout.stages, None, image::Layout::General);
+                                }
+                            }
+                            pso::Descriptor::AccelerationStructure(_)
+                            | pso::Descriptor::Mutable(..)
+                            | pso::Descriptor::Null(_) => unimplemented!(),
+                        }
+                        counters.add(layout.content);
+                    }
+                }
+                n::DescriptorSet::ArgumentBuffer {
+                    ref raw,
+                    raw_offset,
+                    ref pool,
+                    ref range,
+                    ref encoder,
+                    ref bindings,
+                    ..
+                } => {
+                    debug_assert!(self.shared.private_caps.argument_buffers);
+                    match argument_lock {
+                        Some((locked, _)) if Arc::ptr_eq(locked, pool) => {}
+                        _ => argument_lock = Some((pool, pool.write())),
+                    }
+                    let data = &mut argument_lock.as_mut().unwrap().1;
+
+                    encoder.set_argument_buffer(raw, raw_offset);
+                    let (mut arg_index, texture_offset) = {
+                        let binding = &bindings[&op.binding];
+                        debug_assert!((op.array_offset as usize) < binding.count);
+                        // The textures of mutable descriptors follow their buffers.
+                        let texture_offset = if binding
+                            .content
+                            .contains(n::DescriptorContent::BUFFER | n::DescriptorContent::TEXTURE)
+                        {
+                            binding.count as NSUInteger
+                        } else {
+                            0
+                        };
+                        (
+                            (binding.res_offset as NSUInteger) + (op.array_offset as NSUInteger),
+                            texture_offset,
+                        )
+                    };
+
+                    for (data, descriptor) in data.resources
+                        [range.start as usize + arg_index as usize..range.end as usize]
+                        .iter_mut()
+                        .zip(op.descriptors)
+                    {
+                        let descriptor = match descriptor {
+                            pso::Descriptor::Mutable(_ty, inner) => inner.clone(),
+                            other => other,
+                        };
+                        match descriptor {
+                            pso::Descriptor::Sampler(sampler) => {
+                                debug_assert!(!bindings[&op.binding]
+                                    .content
+                                    .contains(n::DescriptorContent::IMMUTABLE_SAMPLER));
+                                run.push_sampler(encoder, arg_index, sampler.raw.as_ref().unwrap());
+                                arg_index += 1;
+                            }
+                            pso::Descriptor::Image(image, _layout) => {
+                                let tex_ref = image.texture.as_ref();
+                                run.push_texture(encoder, arg_index + texture_offset, tex_ref);
+                                data.ptr = (&**tex_ref).as_ptr();
+                                arg_index += 1;
+                            }
+                            pso::Descriptor::CombinedImageSampler(image, _il, sampler) => {
+                                let binding = &bindings[&op.binding];
+                                if !binding
+                                    .content
+                                    .contains(n::DescriptorContent::IMMUTABLE_SAMPLER)
+                                {
+                                    //TODO: supporting arrays of combined image-samplers can be tricky.
+                                    // We need to scan both sampler and image sections of the encoder
+                                    // at the same time.
+                                    assert!(
+                                        arg_index
+                                            < (binding.res_offset as NSUInteger)
+                                                + (binding.count as NSUInteger)
+                                    );
+                                    encoder.set_sampler_state(
+                                        arg_index + binding.count as NSUInteger,
+                                        sampler.raw.as_ref().unwrap(),
+                                    );
+                                }
+                                let tex_ref = image.texture.as_ref();
+                                run.push_texture(encoder, arg_index, tex_ref);
+                                data.ptr = (&**tex_ref).as_ptr();
+                            }
+                            pso::Descriptor::TexelBuffer(view) => {
+                                run.push_texture(encoder, arg_index + texture_offset, &view.raw);
+                                data.ptr = (&**view.raw).as_ptr();
+                                arg_index += 1;
+                            }
+                            pso::Descriptor::Buffer(buffer, ref sub) => {
+                                let (buf_raw, buf_range) = buffer.as_bound();
+                                run.push_buffer(
+                                    encoder,
+                                    arg_index,
+                                    buf_raw,
+                                    buf_range.start + sub.offset,
+                                );
+                                data.ptr = (&**buf_raw).as_ptr();
+                                arg_index += 1;
+                            }
+                            pso::Descriptor::Null(pso::DescriptorType::Buffer {
+                                format: pso::BufferDescriptorFormat::Structured { .. },
+                                ..
+                            }) => {
+                                run.flush(encoder);
+                                let () = msg_send![encoder.as_ptr(),
+                                    setBuffer: ptr::null_mut::<Object>()
+                                    offset: 0 as NSUInteger
+                                    atIndex: arg_index
+                                ];
+                                data.ptr = ptr::null_mut();
+                                arg_index += 1;
+                            }
+                            pso::Descriptor::Null(pso::DescriptorType::Image { .. })
+                            | pso::Descriptor::Null(pso::DescriptorType::InputAttachment)
+                            | pso::Descriptor::Null(pso::DescriptorType::Buffer { .. }) => {
+                                run.flush(encoder);
+                                let () = msg_send![encoder.as_ptr(),
+                                    setTexture: ptr::null_mut::<Object>()
+                                    atIndex: arg_index + texture_offset
+                                ];
+                                data.ptr = ptr::null_mut();
+                                arg_index += 1;
+                            }
+                            pso::Descriptor::AccelerationStructure(_)
+                            | pso::Descriptor::Mutable(..)
+                            | pso::Descriptor::Null(_) => unimplemented!(),
+                        }
+                    }
+                    // the encoder is re-targeted by the next write
+                    run.flush(encoder);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "pipeline-cache")]
+fn create_binary_archive(
+    shared: &Shared,
+    data: &[u8],
+) -> Result<Option<pipeline_cache::BinaryArchive>, d::OutOfMemory> {
+    if !shared.private_caps.supports_binary_archives {
+        return Ok(None);
+    }
+    let descriptor = metal::BinaryArchiveDescriptor::new();
+
+    // We need to keep the temp file alive so that it doesn't get deleted until after a
+    // binary archive has been created.
+    let _temp_file = if !data.is_empty() {
+        // It would be nice to use a `data:text/plain;base64` url here and just pass in a
+        // base64-encoded version of the data, but metal validation doesn't like that:
+        // -[MTLDebugDevice newBinaryArchiveWithDescriptor:error:]:1046: failed assertion `url, if not nil, must be a file URL.'
+
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        temp_file.as_file().write_all(&data).unwrap();
+
+        let url = metal::URL::new_with_string(&format!("file://{}", temp_file.path().display()));
+        descriptor.set_url(&url);
+
+        Some(temp_file)
+    } else {
+        None
+    };
+
+    Ok(Some(pipeline_cache::BinaryArchive {
+        inner: shared
+            .device
+            .new_binary_archive_with_descriptor(&descriptor)
+            .map_err(|_| d::OutOfMemory::Device)?,
+        is_empty: AtomicBool::new(data.is_empty()),
+    }))
 }
 
 impl hal::device::Device<Backend> for Device {
@@ -1011,6 +2437,8 @@ impl hal::device::Device<Backend> for Device {
         Ok(command::CommandPool::new(
             &self.shared,
             self.online_recording.clone(),
+            self.pass_timing,
+            self.command_stats,
         ))
     }
 
@@ -1023,11 +2451,12 @@ impl hal::device::Device<Backend> for Device {
         &self,
         attachments: Ia,
         subpasses: Is,
-        _dependencies: Id,
+        dependencies: Id,
     ) -> Result<n::RenderPass, d::OutOfMemory>
     where
         Ia: Iterator<Item = pass::Attachment>,
         Is: Iterator<Item = pass::SubpassDesc<'a>>,
+        Id: Iterator<Item = pass::SubpassDependency>,
     {
         let attachments: Vec<pass::Attachment> = attachments.collect();
 
@@ -1056,11 +2485,14 @@ impl hal::device::Device<Backend> for Device {
                         color.resolve_id = Some(resolve_id);
                     }
                 }
+                let depth_stencil_resolve = sub
+                    .depth_stencil_resolve
+                    .filter(|resolve| resolve.attachment.0 != pass::ATTACHMENT_UNUSED);
                 let depth_stencil = sub.depth_stencil.map(|&(id, _)| {
                     let hal_format = attachments[id].format.expect("No format!");
                     n::AttachmentInfo {
                         id,
-                        resolve_id: None,
+                        resolve_id: depth_stencil_resolve.map(|resolve| resolve.attachment.0),
                         ops: n::AttachmentOps::empty(),
                         format: self
                             .shared
@@ -1083,12 +2515,43 @@ impl hal::device::Device<Backend> for Device {
                         colors,
                         depth_stencil,
                     },
+                    depth_stencil_resolve_modes: match depth_stencil_resolve {
+                        Some(resolve) => (resolve.depth_mode, resolve.stencil_mode),
+                        None => (None, None),
+                    },
                     inputs: sub.inputs.iter().map(|&(id, _)| id).collect(),
                     samples,
+                    merged: false,
                 }
             })
             .collect();
 
+        // A subpass rendering into the same attachments can continue the encoder of the
+        // previous one, as long as its dependencies on the subpasses sharing that encoder
+        // are framebuffer-local. Everything else requires a new encoder, which is enough
+        // to synchronize with the previous ones, since all the resources are tracked.
+        let dependencies = dependencies.collect::<Vec<_>>();
+        let mut encoder_start = 0;
+        for index in 1..subpasses.len() {
+            let merged = subpasses[index].inputs.is_empty()
+                && subpasses[index].shares_targets(&subpasses[index - 1])
+                && dependencies.iter().all(|dep| match dep.passes {
+                    Range {
+                        start: Some(src),
+                        end: Some(dst),
+                    } if dst == index as pass::SubpassId
+                        && src >= encoder_start as pass::SubpassId =>
+                    {
+                        is_framebuffer_local(dep)
+                    }
+                    _ => true,
+                });
+            subpasses[index].merged = merged;
+            if !merged {
+                encoder_start = index;
+            }
+        }
+
         // sprinkle load operations
         // an attachment receives LOAD flag on a subpass if it's the first sub-pass that uses it
         let mut use_mask = 0u64;
@@ -1122,6 +2585,20 @@ impl hal::device::Device<Backend> for Device {
                 }
             }
         }
+        // merged subpasses are stored by the encoder of the first one
+        for index in (1..subpasses.len()).rev() {
+            if subpasses[index].merged {
+                let (head, tail) = subpasses.split_at_mut(index);
+                let (prev, sub) = (&mut head[index - 1].attachments, &tail[0].attachments);
+                for (prev_at, at) in prev.colors.iter_mut().zip(sub.colors.iter()) {
+                    prev_at.ops |= at.ops & n::AttachmentOps::STORE;
+                }
+                if let (Some(prev_at), Some(at)) = (prev.depth_stencil.as_mut(), &sub.depth_stencil)
+                {
+                    prev_at.ops |= at.ops & n::AttachmentOps::STORE;
+                }
+            }
+        }
 
         Ok(n::RenderPass {
             attachments,
@@ -1173,6 +2650,8 @@ impl hal::device::Device<Backend> for Device {
         let mut binding_map = BTreeMap::default();
         let mut argument_buffer_bindings = FastHashMap::default();
         let mut inline_samplers = Vec::new();
+        let mut ycbcr_bindings = Vec::new();
+        let mut graphics_writes_storage = false;
         #[cfg(feature = "cross")]
         let mut cross_const_samplers = BTreeMap::new();
         let mut infos = Vec::new();
@@ -1235,6 +2714,9 @@ impl hal::device::Device<Backend> for Device {
                         );
                     }
                     for layout in desc_layouts.iter() {
+                        graphics_writes_storage |=
+                            layout.content.contains(n::DescriptorContent::WRITABLE)
+                                && layout.stages.intersects(pso::ShaderStageFlags::GRAPHICS);
                         if layout.content.contains(n::DescriptorContent::SIZED_BUFFER) {
                             sized_buffer_bindings.push((layout.binding, layout.stages));
                             if layout.stages.contains(pso::ShaderStageFlags::VERTEX) {
@@ -1304,6 +2786,7 @@ impl hal::device::Device<Backend> for Device {
                                 },
                                 mutable: layout.content.contains(n::DescriptorContent::WRITABLE),
                             };
+                            let chroma_slot = info.counters.textures + 1;
                             info.counters.add(layout.content);
                             if layout.array_index == 0 {
                                 let source = naga::back::msl::BindSource {
@@ -1311,16 +2794,29 @@ impl hal::device::Device<Backend> for Device {
                                     group: set_index as _,
                                     binding: layout.binding,
                                 };
+                                if layout.content.contains(n::DescriptorContent::CHROMA_PLANE) {
+                                    let sampler = &immutable_samplers[&layout.binding];
+                                    ycbcr_bindings.push(ycbcr::YcbcrBinding {
+                                        source: source.clone(),
+                                        chroma_slot: chroma_slot as _,
+                                        conversion: sampler.ycbcr_conversion.unwrap(),
+                                    });
+                                }
                                 binding_map.insert(source, target);
                             }
                         }
                     }
                 }
                 n::DescriptorSetLayout::ArgumentBuffer {
-                    bindings: _,
+                    ref bindings,
                     stage_flags,
                     ..
                 } => {
+                    graphics_writes_storage |= stage_flags
+                        .intersects(pso::ShaderStageFlags::GRAPHICS)
+                        && bindings
+                            .values()
+                            .any(|arg| arg.content.contains(n::DescriptorContent::WRITABLE));
                     for info in stage_infos.iter_mut() {
                         if !stage_flags.contains(info.stage.into()) {
                             continue;
@@ -1487,6 +2983,8 @@ impl hal::device::Device<Backend> for Device {
             #[cfg(feature = "cross")]
             spirv_cross_options,
             naga_options,
+            ycbcr_bindings,
+            graphics_writes_storage,
             infos,
             total: n::MultiStageResourceCounters {
                 vs: stage_infos[0].counters.clone(),
@@ -1530,60 +3028,60 @@ impl hal::device::Device<Backend> for Device {
         &self,
         data: Option<&[u8]>,
     ) -> Result<n::PipelineCache, d::OutOfMemory> {
-        let device = self.shared.device.lock();
-
-        let create_binary_archive = |data: &[u8]| {
-            if self.shared.private_caps.supports_binary_archives {
-                let descriptor = metal::BinaryArchiveDescriptor::new();
-
-                // We need to keep the temp file alive so that it doesn't get deleted until after a
-                // binary archive has been created.
-                let _temp_file = if !data.is_empty() {
-                    // It would be nice to use a `data:text/plain;base64` url here and just pass in a
-                    // base64-encoded version of the data, but metal validation doesn't like that:
-                    // -[MTLDebugDevice newBinaryArchiveWithDescriptor:error:]:1046: failed assertion `url, if not nil, must be a file URL.'
-
-                    let temp_file = tempfile::NamedTempFile::new().unwrap();
-                    temp_file.as_file().write_all(&data).unwrap();
-
-                    let url = metal::URL::new_with_string(&format!(
-                        "file://{}",
-                        temp_file.path().display()
-                    ));
-                    descriptor.set_url(&url);
-
-                    Some(temp_file)
-                } else {
-                    None
-                };
-
-                Ok(Some(pipeline_cache::BinaryArchive {
-                    inner: device
-                        .new_binary_archive_with_descriptor(&descriptor)
-                        .map_err(|_| d::OutOfMemory::Device)?,
-                    is_empty: AtomicBool::new(data.is_empty()),
-                }))
-            } else {
-                Ok(None)
-            }
-        };
-
         if let Some(data) = data.filter(|data| !data.is_empty()) {
             let pipeline_cache: pipeline_cache::SerializablePipelineCache =
                 bincode::deserialize(data).unwrap();
 
             Ok(n::PipelineCache {
-                binary_archive: create_binary_archive(&pipeline_cache.binary_archive)?,
-                spv_to_msl: pipeline_cache::load_spv_to_msl_cache(pipeline_cache.spv_to_msl),
+                binary_archive: create_binary_archive(
+                    &self.shared,
+                    &pipeline_cache.binary_archive,
+                )?,
+                spv_to_msl: Arc::new(pipeline_cache::load_spv_to_msl_cache(
+                    pipeline_cache.spv_to_msl,
+                )),
             })
         } else {
             Ok(n::PipelineCache {
-                binary_archive: create_binary_archive(&[])?,
+                binary_archive: create_binary_archive(&self.shared, &[])?,
                 spv_to_msl: Default::default(),
             })
         }
     }
 
+    #[cfg(feature = "pipeline-cache")]
+    unsafe fn create_pipeline_cache_deferred(
+        &self,
+        data: Option<Vec<u8>>,
+    ) -> DeferredOperation<Result<n::PipelineCache, d::OutOfMemory>> {
+        let data = match data.filter(|data| !data.is_empty()) {
+            Some(data) => data,
+            None => return DeferredOperation::ready(self.create_pipeline_cache(None)),
+        };
+
+        // The translated shaders are loaded by a task, and the binary archive is created
+        // from its part of the data once they are, all on the joining threads.
+        let loaded = Arc::new(Mutex::new(None));
+        let task_loaded = Arc::clone(&loaded);
+        let task = move || {
+            let pipeline_cache: pipeline_cache::SerializablePipelineCache =
+                bincode::deserialize(&data).unwrap();
+            *task_loaded.lock() = Some((
+                pipeline_cache.binary_archive.to_vec(),
+                pipeline_cache::load_spv_to_msl_cache(pipeline_cache.spv_to_msl),
+            ));
+        };
+        let shared = Arc::clone(&self.shared);
+        let finish = move || -> Result<_, d::OutOfMemory> {
+            let (binary_archive, spv_to_msl) = loaded.lock().take().unwrap();
+            Ok(n::PipelineCache {
+                binary_archive: create_binary_archive(&shared, &binary_archive)?,
+                spv_to_msl: Arc::new(spv_to_msl),
+            })
+        };
+        DeferredOperation::new(vec![Box::new(task)], Box::new(finish))
+    }
+
     #[cfg(not(feature = "pipeline-cache"))]
     unsafe fn get_pipeline_cache_data(
         &self,
@@ -1656,12 +3154,22 @@ impl hal::device::Device<Backend> for Device {
         trace!("create_graphics_pipeline {:#?}", pipeline_desc);
 
         let pipeline = metal::RenderPipelineDescriptor::new();
+        // everything that is set on the descriptor, except for the label
+        let mut state_key = n::RenderPipelineKey::default();
         let pipeline_layout = &pipeline_desc.layout;
         let (rp_attachments, subpass) = {
             let pass::Subpass { main_pass, index } = pipeline_desc.subpass;
             (&main_pass.attachments, &main_pass.subpasses[index as usize])
         };
 
+        let raster_ordered = pipeline_desc
+            .flags
+            .contains(pso::PipelineCreationFlags::RASTER_ORDERED);
+        if raster_ordered && !self.shared.private_caps.raster_order_groups {
+            error!("Raster order groups are not supported");
+            return Err(pso::CreationError::UnsupportedPipeline);
+        }
+
         let (desc_vertex_buffers, attributes, input_assembler, vs_ep) =
             match pipeline_desc.primitive_assembler {
                 pso::PrimitiveAssemblerDesc::Vertex {
@@ -1691,58 +3199,97 @@ impl hal::device::Device<Backend> for Device {
                 } => (buffers, attributes, input_assembler, vertex),
             };
 
-        let (primitive_class, primitive_type) = match input_assembler.primitive {
-            pso::Primitive::PointList => {
-                (MTLPrimitiveTopologyClass::Point, MTLPrimitiveType::Point)
-            }
-            pso::Primitive::LineList => (MTLPrimitiveTopologyClass::Line, MTLPrimitiveType::Line),
-            pso::Primitive::LineStrip => {
-                (MTLPrimitiveTopologyClass::Line, MTLPrimitiveType::LineStrip)
-            }
-            pso::Primitive::TriangleList => (
-                MTLPrimitiveTopologyClass::Triangle,
-                MTLPrimitiveType::Triangle,
-            ),
-            pso::Primitive::TriangleStrip => (
-                MTLPrimitiveTopologyClass::Triangle,
-                MTLPrimitiveType::TriangleStrip,
-            ),
-            pso::Primitive::PatchList(_) => (
-                MTLPrimitiveTopologyClass::Unspecified,
-                MTLPrimitiveType::Point,
-            ),
-        };
+        // Metal always restarts strips at the largest index, it can't be turned off by commands.
+        if pipeline_desc
+            .dynamic_states
+            .contains(pso::DynamicStates::PRIMITIVE_RESTART)
+        {
+            error!("Primitive restart can't be disabled");
+            return Err(pso::CreationError::UnsupportedPipeline);
+        }
+
+        let (primitive_class, primitive_type) =
+            conv::map_primitive_topology(input_assembler.primitive);
         if self.shared.private_caps.layered_rendering {
             pipeline.set_input_primitive_topology(primitive_class);
+            state_key.primitive_class = Some(primitive_class as u64);
         }
 
-        // Vertex shader
-        let vs = self.load_shader(
+        // With dynamic vertex input, the layout of the description is only used
+        // for the initial pipeline state, and the shader can't be rewritten per layout.
+        let dynamic_vertex_input = pipeline_desc
+            .dynamic_states
+            .contains(pso::DynamicStates::VERTEX_INPUT);
+        let vertex_pulling = if !dynamic_vertex_input
+            && VertexPulling::is_required(
+                desc_vertex_buffers,
+                attributes,
+                STRIDE_GRANULARITY,
+                |format| conv::map_vertex_format(format).is_some(),
+            ) {
+            debug!("Vertex layout requires vertex pulling");
+            Some(
+                VertexPulling::new(desc_vertex_buffers, attributes).map_err(|e| {
+                    error!("{}", e);
+                    pso::CreationError::Other
+                })?,
+            )
+        } else {
+            None
+        };
+
+        // Request both stages before waiting on either, so that they are compiled concurrently.
+        let vs_library = self.request_library(
             vs_ep,
             pipeline_layout,
             primitive_class,
             cache,
+            vertex_pulling.as_ref().map(ShaderRewrite::VertexPulling),
+            false,
+        );
+        let fs_library = pipeline_desc.fragment.as_ref().map(|ep| {
+            self.request_library(
+                ep,
+                pipeline_layout,
+                primitive_class,
+                cache,
+                None,
+                raster_ordered,
+            )
+        });
+
+        // Vertex shader
+        let vs = self.finish_shader(
+            vs_ep,
+            pipeline_layout,
+            primitive_class,
             naga::ShaderStage::Vertex,
+            vertex_pulling.as_ref().map(ShaderRewrite::VertexPulling),
+            vs_library,
         )?;
 
         pipeline.set_vertex_function(Some(&vs.function));
+        state_key.vertex = Some(vs.function_key.clone());
 
         // Fragment shader
-        let fs = match pipeline_desc.fragment {
-            Some(ref ep) => Some(self.load_shader(
+        let fs = match (&pipeline_desc.fragment, fs_library) {
+            (&Some(ref ep), Some(library)) => Some(self.finish_shader(
                 ep,
                 pipeline_layout,
                 primitive_class,
-                cache,
                 naga::ShaderStage::Fragment,
+                None,
+                library,
             )?),
-            None => {
+            _ => {
                 // TODO: This is a workaround for what appears to be a Metal validation bug
                 // A pixel format is required even though no attachments are provided
                 if subpass.attachments.colors.is_empty()
                     && subpass.attachments.depth_stencil.is_none()
                 {
                     pipeline.set_depth_attachment_pixel_format(metal::MTLPixelFormat::Depth32Float);
+                    state_key.depth_stencil =
+                        Some((None, metal::MTLPixelFormat::Depth32Float as u64));
                 }
                 None
             }
@@ -1750,8 +3297,10 @@ impl hal::device::Device<Backend> for Device {
 
         if let Some(ref compiled) = fs {
             pipeline.set_fragment_function(Some(&compiled.function));
+            state_key.fragment = Some(compiled.function_key.clone());
         }
         pipeline.set_rasterization_enabled(vs.rasterizing);
+        state_key.rasterizing = vs.rasterizing;
 
         // Assign target formats
         let blend_targets = pipeline_desc
@@ -1773,6 +3322,7 @@ impl hal::device::Device<Backend> for Device {
 
             desc.set_pixel_format(at.format);
             desc.set_write_mask(conv::map_write_mask(color_desc.mask));
+            state_key.colors.push((at.format as u64, *color_desc));
 
             if let Some(ref blend) = color_desc.blend {
                 desc.set_blending_enabled(true);
@@ -1796,96 +3346,39 @@ impl hal::device::Device<Backend> for Device {
             if orig_format.is_stencil() {
                 pipeline.set_stencil_attachment_pixel_format(at.format);
             }
+            state_key.depth_stencil = Some((Some(orig_format), at.format as u64));
         }
 
         // Vertex buffers
-        let vertex_descriptor = metal::VertexDescriptor::new();
         let mut vertex_buffers: n::VertexBufferVec = Vec::new();
-        trace!("Vertex attribute remapping started");
 
-        for &pso::AttributeDesc {
-            location,
-            binding,
-            element,
-        } in attributes
-        {
-            let original = desc_vertex_buffers
-                .iter()
-                .find(|vb| vb.binding == binding)
-                .expect("no associated vertex buffer found");
-            // handle wrapping offsets
-            let elem_size = element.format.surface_desc().bits as pso::ElemOffset / 8;
-            let (cut_offset, base_offset) =
-                if original.stride == 0 || element.offset + elem_size <= original.stride {
-                    (element.offset, 0)
-                } else {
-                    let remainder = element.offset % original.stride;
-                    if remainder + elem_size <= original.stride {
-                        (remainder, element.offset - remainder)
-                    } else {
-                        (0, element.offset)
-                    }
-                };
-            let relative_index = vertex_buffers
-                .iter()
-                .position(|(ref vb, offset)| vb.binding == binding && base_offset == *offset)
-                .unwrap_or_else(|| {
-                    vertex_buffers.alloc().init((original.clone(), base_offset));
-                    vertex_buffers.len() - 1
-                });
-            let mtl_buffer_index = self.shared.private_caps.max_buffers_per_stage
-                - 1
-                - (relative_index as ResourceIndex);
-            if mtl_buffer_index < pipeline_layout.total.vs.buffers {
-                error!("Attribute offset {} exceeds the stride {}, and there is no room for replacement.",
-                    element.offset, original.stride);
-                return Err(pso::CreationError::Other);
+        if let Some(ref pulling) = vertex_pulling {
+            // The buffers are bound the same way, but read by the shader directly.
+            for vb in pulling.buffers.iter() {
+                vertex_buffers.alloc().init((vb.clone(), 0));
             }
-            trace!("\tAttribute[{}] is mapped to vertex buffer[{}] with binding {} and offsets {} + {}",
-                location, binding, mtl_buffer_index, base_offset, cut_offset);
-            // pass the refined data to Metal
-            let mtl_attribute_desc = vertex_descriptor
-                .attributes()
-                .object_at(location as u64)
-                .expect("too many vertex attributes");
-            let mtl_vertex_format =
-                conv::map_vertex_format(element.format).expect("unsupported vertex format");
-            mtl_attribute_desc.set_format(mtl_vertex_format);
-            mtl_attribute_desc.set_buffer_index(mtl_buffer_index as _);
-            mtl_attribute_desc.set_offset(cut_offset as _);
-        }
-
-        for (i, (vb, _)) in vertex_buffers.iter().enumerate() {
-            let mtl_buffer_desc = vertex_descriptor
-                .layouts()
-                .object_at(self.shared.private_caps.max_buffers_per_stage as u64 - 1 - i as u64)
-                .expect("too many vertex descriptor layouts");
-            if vb.stride % STRIDE_GRANULARITY != 0 {
+            let max_buffers = self.shared.private_caps.max_buffers_per_stage;
+            if max_buffers - (vertex_buffers.len() as ResourceIndex)
+                < pipeline_layout.total.vs.buffers
+            {
                 error!(
-                    "Stride ({}) must be a multiple of {}",
-                    vb.stride, STRIDE_GRANULARITY
+                    "There is no room for {} pulled vertex buffers",
+                    vertex_buffers.len()
                 );
                 return Err(pso::CreationError::Other);
             }
-            if vb.stride != 0 {
-                mtl_buffer_desc.set_stride(vb.stride as u64);
-                match vb.rate {
-                    VertexInputRate::Vertex => {
-                        mtl_buffer_desc.set_step_function(MTLVertexStepFunction::PerVertex);
-                    }
-                    VertexInputRate::Instance(divisor) => {
-                        mtl_buffer_desc.set_step_function(MTLVertexStepFunction::PerInstance);
-                        mtl_buffer_desc.set_step_rate(divisor as u64);
-                    }
-                }
-            } else {
-                mtl_buffer_desc.set_stride(256); // big enough to fit all the elements
-                mtl_buffer_desc.set_step_function(MTLVertexStepFunction::PerInstance);
-                mtl_buffer_desc.set_step_rate(!0);
+        } else {
+            let (vertex_descriptor, buffers) = create_vertex_descriptor(
+                self.shared.private_caps.max_buffers_per_stage,
+                pipeline_layout.total.vs.buffers,
+                desc_vertex_buffers,
+                attributes,
+            )?;
+            state_key.vertex_input = Some((desc_vertex_buffers.to_vec(), attributes.to_vec()));
+            if !buffers.is_empty() {
+                pipeline.set_vertex_descriptor(Some(&vertex_descriptor));
             }
-        }
-        if !vertex_buffers.is_empty() {
-            pipeline.set_vertex_descriptor(Some(&vertex_descriptor));
+            vertex_buffers = buffers;
         }
 
         if let pso::State::Static(w) = pipeline_desc.rasterizer.line_width {
@@ -1893,6 +3386,17 @@ impl hal::device::Device<Backend> for Device {
                 warn!("Unsupported line width: {:?}", w);
             }
         }
+        // Metal doesn't specify how lines are rasterized, nor supports stippling them.
+        if pipeline_desc.rasterizer.line_rasterization_mode != pso::LineRasterizationMode::Default
+            || pipeline_desc.rasterizer.line_stipple.is_some()
+        {
+            error!(
+                "Unsupported line rasterization: {:?} with stipple {:?}",
+                pipeline_desc.rasterizer.line_rasterization_mode,
+                pipeline_desc.rasterizer.line_stipple
+            );
+            return Err(pso::CreationError::UnsupportedPipeline);
+        }
 
         let rasterizer_state = Some(n::RasterizerState {
             front_winding: conv::map_winding(pipeline_desc.rasterizer.front_face),
@@ -1921,9 +3425,15 @@ impl hal::device::Device<Backend> for Device {
             .rasterizer
             .depth_bias
             .unwrap_or(pso::State::Static(pso::DepthBias::default()));
+        if let pso::State::Static(ref bias) = depth_bias {
+            if bias.representation != pso::DepthBiasRepresentation::Format || bias.exact {
+                error!("Depth bias representation is not supported: {:?}", bias);
+                return Err(pso::CreationError::UnsupportedPipeline);
+            }
+        }
 
         // prepare the depth-stencil state now
-        let device = self.shared.device.lock();
+        let device = &self.shared.device;
         self.shared
             .service_pipes
             .depth_stencil_states
@@ -1933,6 +3443,11 @@ impl hal::device::Device<Backend> for Device {
             pipeline.set_sample_count(multisampling.rasterization_samples as u64);
             pipeline.set_alpha_to_coverage_enabled(multisampling.alpha_coverage);
             pipeline.set_alpha_to_one_enabled(multisampling.alpha_to_one);
+            state_key.multisampling = Some((
+                multisampling.rasterization_samples,
+                multisampling.alpha_coverage,
+                multisampling.alpha_to_one,
+            ));
             // TODO: sample_mask
             // TODO: sample_shading
             multisampling.rasterization_samples
@@ -1951,15 +3466,86 @@ impl hal::device::Device<Backend> for Device {
             pipeline.set_binary_archives(&[&binary_archive.inner]);
         }
 
+        let translation = n::TranslationReport {
+            vertex: Some(vs.translator),
+            fragment: fs.as_ref().map(|compiled| compiled.translator),
+            compute: None,
+        };
+        let mut executables = vec![describe_executable(
+            naga::ShaderStage::Vertex,
+            &vs,
+            pipeline_desc.flags,
+            pipeline_desc.layout.push_constants.vs,
+            vec![
+                executable_statistic(
+                    "Vertex buffers",
+                    "Number of Metal vertex buffers the attributes are fetched from",
+                    pso::ExecutableStatisticValue::UInt(vertex_buffers.len() as u64),
+                ),
+                executable_statistic(
+                    "Vertex pulling",
+                    "Whether the function fetches the vertex attributes itself",
+                    pso::ExecutableStatisticValue::Bool(vertex_pulling.is_some()),
+                ),
+            ],
+        )];
+        if let Some(ref compiled) = fs {
+            executables.push(describe_executable(
+                naga::ShaderStage::Fragment,
+                compiled,
+                pipeline_desc.flags,
+                pipeline_desc.layout.push_constants.ps,
+                Vec::new(),
+            ));
+        }
         let (fs_lib, ps_sized_bindings) = match fs {
             Some(compiled) => (Some(compiled.library), compiled.sized_bindings),
             None => (None, Vec::new()),
         };
 
-        let pipeline_state = device
-            // Replace this with `new_render_pipeline_state_with_fail_on_binary_archive_miss`
-            // to debug that the cache is actually working.
-            .new_render_pipeline_state(&pipeline)
+        let state_key = Arc::new(state_key);
+        let cached = self.render_pipeline_states.lock().get_mut(&state_key).map(
+            |&mut (ref raw, ref mut users)| {
+                *users += 1;
+                raw.clone()
+            },
+        );
+        let raw = match cached {
+            Some(raw) => {
+                debug!(
+                    "Sharing the render pipeline state of {:?}",
+                    pipeline_desc.label
+                );
+                Ok(raw)
+            }
+            None => device
+                // Replace this with `new_render_pipeline_state_with_fail_on_binary_archive_miss`
+                // to debug that the cache is actually working.
+                .new_render_pipeline_state(&pipeline)
+                .map(|raw| {
+                    let mut states = self.render_pipeline_states.lock();
+                    let &mut (ref raw, ref mut users) =
+                        states.entry(Arc::clone(&state_key)).or_insert((raw, 0));
+                    *users += 1;
+                    raw.clone()
+                }),
+        };
+        let vertex_input = match raw {
+            Ok(ref raw) if dynamic_vertex_input => {
+                let mut states = FastHashMap::default();
+                states.insert(
+                    (desc_vertex_buffers.to_vec(), attributes.to_vec()),
+                    (raw.clone(), vertex_buffers.clone()),
+                );
+                Some(Arc::new(n::VertexInputVariants {
+                    descriptor: pipeline.to_owned(),
+                    reserved_buffers: pipeline_layout.total.vs.buffers,
+                    states: Mutex::new(states),
+                }))
+            }
+            _ => None,
+        };
+        let pipeline_state = raw
             .map(|raw| n::GraphicsPipeline {
                 vs_lib: vs.library,
                 fs_lib,
@@ -1989,9 +3575,16 @@ impl hal::device::Device<Backend> for Device {
                 depth_bias,
                 depth_stencil_desc: pipeline_desc.depth_stencil.clone(),
                 baked_states: pipeline_desc.baked_states.clone(),
+                dynamic_states: pipeline_desc.dynamic_states,
                 vertex_buffers,
+                vertex_input,
                 attachment_formats: subpass.attachments.map(|at| (at.format, at.channel)),
                 samples,
+                translation,
+                executables,
+                private_data: n::PrivateData::default(),
+                state_key,
+                writes_storage: pipeline_layout.graphics_writes_storage,
             })
             .map_err(|err| {
                 error!("PSO creation failed: {}", err);
@@ -2013,6 +3606,194 @@ impl hal::device::Device<Backend> for Device {
         Ok(pipeline_state)
     }
 
+    unsafe fn create_graphics_pipeline_library<'a>(
+        &self,
+        desc: &pso::GraphicsPipelineDesc<'a, Backend>,
+        parts: pso::GraphicsPipelineLibraryParts,
+        cache: Option<&n::PipelineCache>,
+    ) -> Result<n::GraphicsPipelineLibrary, pso::CreationError> {
+        profiling::scope!("create_graphics_pipeline_library");
+        trace!("create_graphics_pipeline_library {:?} {:#?}", parts, desc);
+
+        let (buffers, attributes, input_assembler, vertex) = match desc.primitive_assembler {
+            pso::PrimitiveAssemblerDesc::Vertex {
+                buffers,
+                attributes,
+                ref input_assembler,
+                ref vertex,
+                tessellation: None,
+                geometry: None,
+            } => (buffers, attributes, input_assembler, vertex),
+            _ => {
+                error!("Pipeline libraries only support vertex and fragment shaders");
+                return Err(pso::CreationError::UnsupportedPipeline);
+            }
+        };
+        if desc
+            .dynamic_states
+            .contains(pso::DynamicStates::PRIMITIVE_RESTART)
+        {
+            error!("Primitive restart can't be disabled");
+            return Err(pso::CreationError::UnsupportedPipeline);
+        }
+        let (primitive_class, _) = conv::map_primitive_topology(input_assembler.primitive);
+        let raster_ordered = desc
+            .flags
+            .contains(pso::PipelineCreationFlags::RASTER_ORDERED);
+
+        // Compile the shaders now, so that linking only has to create the pipeline state.
+        // A vertex shader that ends up pulling its vertices is rewritten when linking.
+        let vs_library =
+            if parts.contains(pso::GraphicsPipelineLibraryParts::PRE_RASTERIZATION_SHADERS) {
+                Some(self.request_library(vertex, desc.layout, primitive_class, cache, None, false))
+            } else {
+                None
+            };
+        let fs_library = match desc.fragment {
+            Some(ref ep) if parts.contains(pso::GraphicsPipelineLibraryParts::FRAGMENT_SHADER) => {
+                Some((
+                    ep,
+                    self.request_library(
+                        ep,
+                        desc.layout,
+                        primitive_class,
+                        cache,
+                        None,
+                        raster_ordered,
+                    ),
+                ))
+            }
+            _ => None,
+        };
+        if let Some(library) = vs_library {
+            self.finish_shader(
+                vertex,
+                desc.layout,
+                primitive_class,
+                naga::ShaderStage::Vertex,
+                None,
+                library,
+            )?;
+        }
+        if let Some((ep, library)) = fs_library {
+            self.finish_shader(
+                ep,
+                desc.layout,
+                primitive_class,
+                naga::ShaderStage::Fragment,
+                None,
+                library,
+            )?;
+        }
+
+        let mut library = n::GraphicsPipelineLibrary {
+            vertex_input: None,
+            pre_rasterization: None,
+            fragment: None,
+            fragment_output: None,
+            baked_states: pso::BakedStates::default(),
+            dynamic_states: pso::DynamicStates::empty(),
+        };
+        if parts.contains(pso::GraphicsPipelineLibraryParts::VERTEX_INPUT_INTERFACE) {
+            library.vertex_input = Some(n::LibraryVertexInput {
+                buffers: buffers.to_vec(),
+                attributes: attributes.to_vec(),
+                input_assembler: input_assembler.clone(),
+            });
+            library.dynamic_states |= desc.dynamic_states
+                & (pso::DynamicStates::PRIMITIVE_TOPOLOGY | pso::DynamicStates::VERTEX_INPUT);
+        }
+        if parts.contains(pso::GraphicsPipelineLibraryParts::PRE_RASTERIZATION_SHADERS) {
+            library.pre_rasterization =
+                Some((n::LibraryEntryPoint::new(vertex), desc.rasterizer.clone()));
+            library.baked_states.viewport = desc.baked_states.viewport.clone();
+            library.baked_states.scissor = desc.baked_states.scissor;
+            library.dynamic_states |= desc.dynamic_states
+                & (pso::DynamicStates::CULL_FACE | pso::DynamicStates::FRONT_FACE);
+        }
+        if parts.contains(pso::GraphicsPipelineLibraryParts::FRAGMENT_SHADER) {
+            library.fragment = Some((
+                desc.fragment.as_ref().map(n::LibraryEntryPoint::new),
+                desc.depth_stencil.clone(),
+            ));
+            library.baked_states.depth_bounds = desc.baked_states.depth_bounds.clone();
+            library.dynamic_states |= desc.dynamic_states
+                & (pso::DynamicStates::DEPTH_TEST | pso::DynamicStates::STENCIL_FACES);
+        }
+        if parts.contains(pso::GraphicsPipelineLibraryParts::FRAGMENT_OUTPUT_INTERFACE) {
+            library.fragment_output = Some((desc.blender.clone(), desc.multisampling.clone()));
+            library.baked_states.blend_constants = desc.baked_states.blend_constants;
+        }
+        Ok(library)
+    }
+
+    unsafe fn link_graphics_pipeline<'a>(
+        &self,
+        desc: &pso::GraphicsPipelineLinkDesc<'a, Backend>,
+        cache: Option<&n::PipelineCache>,
+    ) -> Result<n::GraphicsPipeline, pso::CreationError> {
+        profiling::scope!("link_graphics_pipeline");
+
+        let mut vertex_input = None;
+        let mut pre_rasterization = None;
+        let mut fragment = None;
+        let mut fragment_output = None;
+        let mut baked_states = pso::BakedStates::default();
+        let mut dynamic_states = pso::DynamicStates::empty();
+        for library in desc.libraries.iter() {
+            vertex_input = vertex_input.or(library.vertex_input.as_ref());
+            pre_rasterization = pre_rasterization.or(library.pre_rasterization.as_ref());
+            fragment = fragment.or(library.fragment.as_ref());
+            fragment_output = fragment_output.or(library.fragment_output.as_ref());
+            let baked = &library.baked_states;
+            baked_states = pso::BakedStates {
+                viewport: baked_states.viewport.or_else(|| baked.viewport.clone()),
+                scissor: baked_states.scissor.or(baked.scissor),
+                blend_constants: baked_states.blend_constants.or(baked.blend_constants),
+                depth_bounds: baked_states
+                    .depth_bounds
+                    .or_else(|| baked.depth_bounds.clone()),
+            };
+            dynamic_states |= library.dynamic_states;
+        }
+
+        let (
+            vertex_input,
+            (vertex, rasterizer),
+            (fragment, depth_stencil),
+            (blender, multisampling),
+        ) = match (vertex_input, pre_rasterization, fragment, fragment_output) {
+            (Some(a), Some(b), Some(c), Some(d)) => (a, b, c, d),
+            _ => {
+                error!("The pipeline libraries don't hold all the parts of the pipeline");
+                return Err(pso::CreationError::Other);
+            }
+        };
+        let pipeline_desc = pso::GraphicsPipelineDesc {
+            label: desc.label,
+            primitive_assembler: pso::PrimitiveAssemblerDesc::Vertex {
+                buffers: &vertex_input.buffers,
+                attributes: &vertex_input.attributes,
+                input_assembler: vertex_input.input_assembler.clone(),
+                vertex: vertex.entry_point(),
+                tessellation: None,
+                geometry: None,
+            },
+            rasterizer: rasterizer.clone(),
+            fragment: fragment.as_ref().map(n::LibraryEntryPoint::entry_point),
+            blender: blender.clone(),
+            depth_stencil: depth_stencil.clone(),
+            multisampling: multisampling.clone(),
+            baked_states,
+            dynamic_states,
+            layout: desc.layout,
+            subpass: desc.subpass,
+            flags: desc.flags,
+            parent: pso::BasePipeline::None,
+        };
+        self.create_graphics_pipeline(&pipeline_desc, cache)
+    }
+
     unsafe fn create_compute_pipeline<'a>(
         &self,
         pipeline_desc: &pso::ComputePipelineDesc<'a, Backend>,
@@ -2022,12 +3803,28 @@ impl hal::device::Device<Backend> for Device {
         trace!("create_compute_pipeline {:?}", pipeline_desc);
         let pipeline = metal::ComputePipelineDescriptor::new();
 
+        let base_slot = if pipeline_desc
+            .flags
+            .contains(pso::PipelineCreationFlags::DISPATCH_BASE)
+        {
+            let slot = self.shared.private_caps.max_buffers_per_stage - 1;
+            if pipeline_desc.layout.total.cs.buffers > slot {
+                error!("There is no room for the dispatch base buffer");
+                return Err(pso::CreationError::Other);
+            }
+            Some(slot)
+        } else {
+            None
+        };
+
         let cs = self.load_shader(
             &pipeline_desc.shader,
             &pipeline_desc.layout,
             MTLPrimitiveTopologyClass::Unspecified,
             cache,
             naga::ShaderStage::Compute,
+            base_slot.map(ShaderRewrite::DispatchBase),
+            false,
         )?;
         pipeline.set_compute_function(Some(&cs.function));
         if let Some(name) = pipeline_desc.label {
@@ -2044,9 +3841,28 @@ impl hal::device::Device<Backend> for Device {
         let pipeline_state = self
             .shared
             .device
-            .lock()
             .new_compute_pipeline_state(&pipeline)
             .map(|raw| n::ComputePipeline {
+                executables: vec![describe_executable(
+                    naga::ShaderStage::Compute,
+                    &cs,
+                    pipeline_desc.flags,
+                    pipeline_desc.layout.push_constants.cs,
+                    vec![
+                        executable_statistic(
+                            "Thread execution width",
+                            "Number of threads executed in lockstep",
+                            pso::ExecutableStatisticValue::UInt(raw.thread_execution_width()),
+                        ),
+                        executable_statistic(
+                            "Max threads per threadgroup",
+                            "Maximum number of threads in a threadgroup of the pipeline",
+                            pso::ExecutableStatisticValue::UInt(
+                                raw.max_total_threads_per_threadgroup(),
+                            ),
+                        ),
+                    ],
+                )],
                 cs_lib: cs.library,
                 raw,
                 work_group_size: cs.wg_size,
@@ -2060,6 +3876,12 @@ impl hal::device::Device<Backend> for Device {
                         .sizes_buffer,
                     sized_bindings: cs.sized_bindings,
                 },
+                base_slot,
+                translation: n::TranslationReport {
+                    compute: Some(cs.translator),
+                    ..n::TranslationReport::default()
+                },
+                private_data: n::PrivateData::default(),
             })
             .map_err(|err| {
                 error!("PSO creation failed: {}", err);
@@ -2080,13 +3902,65 @@ impl hal::device::Device<Backend> for Device {
         Ok(pipeline_state)
     }
 
+    unsafe fn create_ray_tracing_pipeline<'a>(
+        &self,
+        _desc: &pso::RayTracingPipelineDesc<'a, Backend>,
+        _cache: Option<&n::PipelineCache>,
+    ) -> Result<(), pso::CreationError> {
+        Err(pso::CreationError::UnsupportedPipeline)
+    }
+
+    unsafe fn get_ray_tracing_shader_group_handles(
+        &self,
+        _pipeline: &(),
+        _first_group: u32,
+        _group_count: u32,
+    ) -> Result<Vec<u8>, d::OutOfMemory> {
+        unimplemented!()
+    }
+
     unsafe fn create_framebuffer<I>(
         &self,
-        _render_pass: &n::RenderPass,
-        _attachments: I,
+        render_pass: &n::RenderPass,
+        attachments: I,
         extent: image::Extent,
     ) -> Result<n::Framebuffer, d::OutOfMemory> {
-        Ok(n::Framebuffer { extent })
+        let attachments = attachments.collect::<Vec<_>>();
+        if attachments.len() != render_pass.attachments.len() {
+            error!(
+                "Framebuffer has {} attachments, but render pass {:?} expects {}",
+                attachments.len(),
+                render_pass.name,
+                render_pass.attachments.len()
+            );
+        }
+        for (i, (fat, rat)) in attachments
+            .iter()
+            .zip(render_pass.attachments.iter())
+            .enumerate()
+        {
+            if let Some(format) = rat.format {
+                if format != fat.format {
+                    error!(
+                        "Framebuffer attachment {} has format {:?}, but the render pass expects {:?}",
+                        i, fat.format, format
+                    );
+                }
+            }
+            if !fat
+                .usage
+                .intersects(image::Usage::COLOR_ATTACHMENT | image::Usage::DEPTH_STENCIL_ATTACHMENT)
+            {
+                error!(
+                    "Framebuffer attachment {} is missing an attachment usage: {:?}",
+                    i, fat.usage
+                );
+            }
+        }
+        Ok(n::Framebuffer {
+            extent,
+            attachments,
+        })
     }
 
     unsafe fn create_shader_module(
@@ -2094,13 +3968,27 @@ impl hal::device::Device<Backend> for Device {
         raw_data: &[u32],
     ) -> Result<n::ShaderModule, d::ShaderError> {
         profiling::scope!("create_shader_module");
+        let translator = self
+            .shader_translator
+            .unwrap_or(if cfg!(feature = "cross") {
+                ShaderTranslator::SpirvCross
+            } else {
+                ShaderTranslator::Naga
+            });
+        if translator == ShaderTranslator::SpirvCross && !cfg!(feature = "cross") {
+            error!("SPIRV-Cross translation requires the `cross` feature");
+            return Err(d::ShaderError::Unsupported);
+        }
         Ok(n::ShaderModule {
             #[cfg(feature = "cross")]
             spv: raw_data.to_vec(),
             #[cfg(feature = "pipeline-cache")]
             spv_hash: fxhash::hash64(raw_data),
-            naga: if cfg!(feature = "cross") {
-                Err("Cross is enabled".into())
+            key: n::ModuleKey::spirv(raw_data),
+            translator: self.shader_translator,
+            from_identifier: false,
+            naga: if translator == ShaderTranslator::SpirvCross {
+                Err("SPIRV-Cross is selected".into())
             } else {
                 let options = naga::front::spv::Options {
                     adjust_coordinate_space: !self.features.contains(hal::Features::NDC_Y_UP),
@@ -2121,7 +4009,7 @@ impl hal::device::Device<Backend> for Device {
                         )
                         .validate(&module)
                         {
-                            Ok(info) => Ok(d::NagaShader { module, info }),
+                            Ok(info) => Ok(Arc::new(d::NagaShader { module, info })),
                             Err(e) => Err(format!("Naga validation: {}", e)),
                         }
                     }
@@ -2137,6 +4025,11 @@ impl hal::device::Device<Backend> for Device {
     ) -> Result<n::ShaderModule, (d::ShaderError, d::NagaShader)> {
         profiling::scope!("create_shader_module_from_naga");
 
+        if self.shader_translator == Some(ShaderTranslator::SpirvCross) && !cfg!(feature = "cross")
+        {
+            error!("SPIRV-Cross translation requires the `cross` feature");
+            return Err((d::ShaderError::Unsupported, shader));
+        }
         #[cfg(any(feature = "pipeline-cache", feature = "cross"))]
         let spv = match naga::back::spv::write_vec(&shader.module, &shader.info, &self.spv_options)
         {
@@ -2149,22 +4042,69 @@ impl hal::device::Device<Backend> for Device {
             spv_hash: fxhash::hash64(&spv),
             #[cfg(feature = "cross")]
             spv,
-            naga: Ok(shader),
+            key: n::ModuleKey::unique(),
+            translator: self.shader_translator,
+            from_identifier: false,
+            naga: if self.shader_translator == Some(ShaderTranslator::SpirvCross) {
+                Err("SPIRV-Cross is selected".into())
+            } else {
+                Ok(Arc::new(shader))
+            },
         })
     }
 
+    #[cfg_attr(not(feature = "pipeline-cache"), allow(unused_variables))]
+    unsafe fn get_shader_module_identifier(
+        &self,
+        shader: &n::ShaderModule,
+    ) -> Option<d::ShaderModuleIdentifier> {
+        // The modules are identified by the hash of their SPIR-V,
+        // which is the key of their libraries in the pipeline caches.
+        #[cfg(feature = "pipeline-cache")]
+        let identifier = Some(d::ShaderModuleIdentifier(
+            shader.spv_hash.to_le_bytes().to_vec(),
+        ));
+        #[cfg(not(feature = "pipeline-cache"))]
+        let identifier = None;
+        identifier
+    }
+
+    #[cfg_attr(not(feature = "pipeline-cache"), allow(unused_variables))]
+    unsafe fn create_shader_module_from_identifier(
+        &self,
+        identifier: &d::ShaderModuleIdentifier,
+    ) -> Result<n::ShaderModule, d::ShaderError> {
+        #[cfg(feature = "pipeline-cache")]
+        if identifier.0.len() == 8 {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(&identifier.0);
+            let spv_hash = u64::from_le_bytes(bytes);
+            return Ok(n::ShaderModule {
+                #[cfg(feature = "cross")]
+                spv: Vec::new(),
+                spv_hash,
+                key: n::ModuleKey::Identifier(spv_hash),
+                translator: Some(ShaderTranslator::Naga),
+                from_identifier: true,
+                naga: Err("The shader module is created from an identifier".into()),
+            });
+        }
+        Err(d::ShaderError::Unsupported)
+    }
+
     unsafe fn create_sampler(
         &self,
         info: &image::SamplerDesc,
     ) -> Result<n::Sampler, d::AllocationError> {
         Ok(n::Sampler {
             raw: match self.make_sampler_descriptor(info) {
-                Some(ref descriptor) => Some(self.shared.device.lock().new_sampler(descriptor)),
+                Some(ref descriptor) => Some(self.shared.device.new_sampler(descriptor)),
                 None => None,
             },
             data: conv::map_sampler_data_to_naga(info),
             #[cfg(feature = "cross")]
             cross_data: conv::map_sampler_data_to_cross(info),
+            ycbcr_conversion: info.ycbcr_conversion,
         })
     }
 
@@ -2179,8 +4119,12 @@ impl hal::device::Device<Backend> for Device {
         debug!("map_memory of size {} at {:?}", memory.size, range);
 
         let base_ptr = match memory.heap {
-            n::MemoryHeap::Public(_, ref cpu_buffer) => cpu_buffer.contents() as *mut u8,
-            n::MemoryHeap::Native(_) | n::MemoryHeap::Private => panic!("Unable to map memory!"),
+            n::MemoryHeap::Public(_, ref cpu_buffer, base) => {
+                (cpu_buffer.contents() as *mut u8).offset(base as _)
+            }
+            n::MemoryHeap::Native(_) | n::MemoryHeap::Private | n::MemoryHeap::IOSurface(_) => {
+                panic!("Unable to map memory!")
+            }
         };
         Ok(base_ptr.offset(range.start as _))
     }
@@ -2200,16 +4144,17 @@ impl hal::device::Device<Backend> for Device {
 
             match memory.heap {
                 n::MemoryHeap::Native(_) => unimplemented!(),
-                n::MemoryHeap::Public(mt, ref cpu_buffer)
+                n::MemoryHeap::Public(mt, ref cpu_buffer, base)
                     if 1 << mt.0 != MemoryTypes::SHARED.bits() as usize =>
                 {
                     cpu_buffer.did_modify_range(NSRange {
-                        location: range.start as _,
+                        location: (base + range.start) as _,
                         length: (range.end - range.start) as _,
                     });
                 }
                 n::MemoryHeap::Public(..) => continue,
                 n::MemoryHeap::Private => panic!("Can't map private memory!"),
+                n::MemoryHeap::IOSurface(_) => panic!("Can't map IOSurface memory!"),
             };
         }
 
@@ -2236,14 +4181,16 @@ impl hal::device::Device<Backend> for Device {
 
                 match memory.heap {
                     n::MemoryHeap::Native(_) => unimplemented!(),
-                    n::MemoryHeap::Public(mt, ref cpu_buffer)
+                    n::MemoryHeap::Public(mt, ref cpu_buffer, _)
                         if 1 << mt.0 != MemoryTypes::SHARED.bits() as usize =>
                     {
+                        // managed memory owns its buffer, so this doesn't touch other allocations
                         num_syncs += 1;
                         encoder.synchronize_resource(cpu_buffer);
                     }
                     n::MemoryHeap::Public(..) => continue,
                     n::MemoryHeap::Private => panic!("Can't map private memory!"),
+                    n::MemoryHeap::IOSurface(_) => panic!("Can't map IOSurface memory!"),
                 };
             }
             encoder.end_encoding();
@@ -2268,9 +4215,68 @@ impl hal::device::Device<Backend> for Device {
             } else {
                 None
             },
+            shared_event: None,
         })
     }
 
+    fn create_exportable_semaphore(
+        &self,
+        handle_types: ExternalSyncHandleTypes,
+    ) -> Result<n::Semaphore, ExternalSyncError> {
+        let unsupported = handle_types - self.external_semaphore_handle_types();
+        if !unsupported.is_empty() {
+            return Err(ExternalSyncError::UnsupportedHandleTypes(unsupported));
+        }
+        let mut semaphore = self.create_semaphore()?;
+        if handle_types.contains(ExternalSyncHandleTypes::METAL_SHARED_EVENT) {
+            let raw = self.shared.device.new_shared_event();
+            semaphore.shared_event = Some(Arc::new(n::SharedEventPayload::new(raw)));
+        }
+        Ok(semaphore)
+    }
+
+    unsafe fn import_semaphore(
+        &self,
+        semaphore: &mut n::Semaphore,
+        handle: ExternalSyncHandle,
+    ) -> Result<(), ExternalSyncError> {
+        match handle {
+            ExternalSyncHandle::MetalSharedEvent(event)
+                if self
+                    .external_semaphore_handle_types()
+                    .contains(ExternalSyncHandleTypes::METAL_SHARED_EVENT) =>
+            {
+                let raw = event.as_ptr() as *mut metal::MTLSharedEvent;
+                let _: *mut Object = msg_send![raw, retain];
+                let payload = n::SharedEventPayload::new(metal::SharedEvent::from_ptr(raw));
+                semaphore.shared_event = Some(Arc::new(payload));
+                Ok(())
+            }
+            other => Err(ExternalSyncError::UnsupportedHandleTypes(
+                other.handle_type().into(),
+            )),
+        }
+    }
+
+    unsafe fn export_semaphore(
+        &self,
+        semaphore: &n::Semaphore,
+        handle_type: ExternalSyncHandleType,
+    ) -> Result<ExternalSyncHandle, ExternalSyncError> {
+        match (handle_type, &semaphore.shared_event) {
+            (ExternalSyncHandleType::MetalSharedEvent, &Some(ref shared_event)) => {
+                let raw = shared_event.raw.as_ptr();
+                let _: *mut Object = msg_send![raw, retain];
+                Ok(ExternalSyncHandle::MetalSharedEvent(
+                    ptr::NonNull::new_unchecked(raw as *mut _),
+                ))
+            }
+            _ => Err(ExternalSyncError::UnsupportedHandleTypes(
+                handle_type.into(),
+            )),
+        }
+    }
+
     unsafe fn create_descriptor_pool<I>(
         &self,
         max_sets: usize,
@@ -2296,7 +4302,7 @@ impl hal::device::Device<Backend> for Device {
                 }
             }
 
-            let device = self.shared.device.lock();
+            let device = &self.shared.device;
             let (array_ref, total_resources) = arguments.build();
             let encoder = device.new_argument_encoder(array_ref);
 
@@ -2331,11 +4337,23 @@ impl hal::device::Device<Backend> for Device {
         I: Iterator<Item = pso::DescriptorSetLayoutBinding>,
         J: Iterator<Item = &'a n::Sampler>,
     {
-        if self.shared.private_caps.argument_buffers {
+        let binding_descs = binding_iter.collect::<Vec<_>>();
+        let immutable_samplers = immutable_samplers.collect::<Vec<_>>();
+        // The chroma planes sampled with a conversion are bound next to the images,
+        // which argument buffers don't have room for.
+        let has_ycbcr = immutable_samplers
+            .iter()
+            .any(|sm| sm.ycbcr_conversion.is_some());
+
+        if self.shared.private_caps.argument_buffers && !has_ycbcr {
             let mut stage_flags = pso::ShaderStageFlags::empty();
             let mut arguments = n::ArgumentArray::default();
             let mut bindings = FastHashMap::default();
-            for desc in binding_iter {
+            // A variable descriptor count is only allowed in the highest binding,
+            // which then gets the last resources of the layout.
+            let mut binding_descs = binding_descs;
+            binding_descs.sort_by_key(|desc| desc.binding);
+            for desc in binding_descs {
                 //TODO: have the API providing the dimensions and MSAA flag
                 // for textures in an argument buffer
                 match desc.ty {
@@ -2359,6 +4377,14 @@ impl hal::device::Device<Backend> for Device {
                         //TODO: bind storage buffers and images separately
                         error!("Storage images are not yet supported in argument buffers!");
                     }
+                    pso::DescriptorType::Mutable { types }
+                        if types.intersects(
+                            pso::MutableDescriptorTypes::STORAGE_IMAGE
+                                | pso::MutableDescriptorTypes::STORAGE_TEXEL_BUFFER,
+                        ) =>
+                    {
+                        error!("Storage images are not yet supported in argument buffers!");
+                    }
                     _ => {}
                 }
 
@@ -2408,12 +4434,13 @@ impl hal::device::Device<Backend> for Device {
                         count: desc.count,
                         usage,
                         content,
+                        flags: desc.flags,
                     },
                 );
             }
 
             let (array_ref, arg_total) = arguments.build();
-            let encoder = self.shared.device.lock().new_argument_encoder(array_ref);
+            let encoder = self.shared.device.new_argument_encoder(array_ref);
 
             Ok(n::DescriptorSetLayout::ArgumentBuffer {
                 encoder,
@@ -2427,17 +4454,17 @@ impl hal::device::Device<Backend> for Device {
                 binding: pso::DescriptorBinding,
                 array_index: pso::DescriptorArrayIndex,
             }
-            let mut immutable_sampler_iter = immutable_samplers;
+            let mut immutable_sampler_iter = immutable_samplers.into_iter();
             let mut tmp_samplers = Vec::new();
             let mut desc_layouts = Vec::new();
             let mut total = n::ResourceData::new();
 
-            for slb in binding_iter {
+            for slb in binding_descs {
                 let mut content = n::DescriptorContent::from(slb.ty);
-                total.add_many(content, slb.count as _);
 
                 #[cfg_attr(not(feature = "cross"), allow(unused_variables))]
                 if slb.immutable_samplers {
+                    let start = tmp_samplers.len();
                     tmp_samplers.extend(
                         immutable_sampler_iter
                             .by_ref()
@@ -2448,19 +4475,28 @@ impl hal::device::Device<Backend> for Device {
                                     data: sm.data.clone(),
                                     #[cfg(feature = "cross")]
                                     cross_data: sm.cross_data.clone(),
+                                    ycbcr_conversion: sm.ycbcr_conversion,
                                 },
                                 binding: slb.binding,
                                 array_index,
                             }),
                     );
                     content |= n::DescriptorContent::IMMUTABLE_SAMPLER;
+                    if tmp_samplers[start..]
+                        .iter()
+                        .any(|ts| ts.data.ycbcr_conversion.is_some())
+                    {
+                        content |= n::DescriptorContent::CHROMA_PLANE;
+                    }
                 }
+                total.add_many(content, slb.count as _);
 
                 desc_layouts.extend((0..slb.count).map(|array_index| n::DescriptorLayout {
                     content,
                     stages: slb.stage_flags,
                     binding: slb.binding,
                     array_index,
+                    offset: n::ResourceData::new(),
                 }));
             }
 
@@ -2478,6 +4514,11 @@ impl hal::device::Device<Backend> for Device {
                     false
                 }
             });
+            let mut offset = n::ResourceData::new();
+            for dl in desc_layouts.iter_mut() {
+                dl.offset = offset.clone();
+                offset.add(dl.content);
+            }
 
             Ok(n::DescriptorSetLayout::Emulated {
                 layouts: Arc::new(desc_layouts),
@@ -2494,166 +4535,7 @@ impl hal::device::Device<Backend> for Device {
     where
         I: Iterator<Item = pso::Descriptor<'a, Backend>>,
     {
-        debug!("write_descriptor_set");
-        match *op.set {
-            n::DescriptorSet::Emulated {
-                ref pool,
-                ref layouts,
-                ref resources,
-            } => {
-                let mut counters = resources.map(|r| r.start);
-                let mut start = None; //TODO: can pre-compute this
-                for (i, layout) in layouts.iter().enumerate() {
-                    if layout.binding == op.binding && layout.array_index == op.array_offset {
-                        start = Some(i);
-                        break;
-                    }
-                    counters.add(layout.content);
-                }
-                let mut data = pool.write();
-
-                for (layout, descriptor) in layouts[start.unwrap()..].iter().zip(op.descriptors) {
-                    trace!("\t{:?}", layout);
-                    match descriptor {
-                        pso::Descriptor::Sampler(sam) => {
-                            debug_assert!(!layout
-                                .content
-                                .contains(n::DescriptorContent::IMMUTABLE_SAMPLER));
-                            data.samplers[counters.samplers as usize] = (
-                                layout.stages,
-                                Some(AsNative::from(sam.raw.as_ref().unwrap().as_ref())),
-                            );
-                        }
-                        pso::Descriptor::Image(view, il) => {
-                            data.textures[counters.textures as usize] = (
-                                layout.stages,
-                                Some(AsNative::from(view.texture.as_ref())),
-                                il,
-                            );
-                        }
-                        pso::Descriptor::CombinedImageSampler(view, il, sam) => {
-                            if !layout
-                                .content
-                                .contains(n::DescriptorContent::IMMUTABLE_SAMPLER)
-                            {
-                                data.samplers[counters.samplers as usize] = (
-                                    layout.stages,
-                                    Some(AsNative::from(sam.raw.as_ref().unwrap().as_ref())),
-                                );
-                            }
-                            data.textures[counters.textures as usize] = (
-                                layout.stages,
-                                Some(AsNative::from(view.texture.as_ref())),
-                                il,
-                            );
-                        }
-                        pso::Descriptor::TexelBuffer(view) => {
-                            data.textures[counters.textures as usize] = (
-                                layout.stages,
-                                Some(AsNative::from(view.raw.as_ref())),
-                                image::Layout::General,
-                            );
-                        }
-                        pso::Descriptor::Buffer(buf, ref sub) => {
-                            let (raw, range) = buf.as_bound();
-                            debug_assert!(
-                                range.start + sub.offset + sub.size.unwrap_or(0) <= range.end
-                            );
-                            let raw_binding_size = match sub.size {
-                                Some(size) => size,
-                                None => range.end - range.start - sub.offset,
-                            };
-                            data.buffers[counters.buffers as usize] = (
-                                layout.stages,
-                                Some(AsNative::from(raw)),
-                                range.start + sub.offset,
-                                layout.binding,
-                                if layout.content.contains(n::DescriptorContent::SIZED_BUFFER) {
-                                    raw_binding_size.min(u32::MAX as buffer::Offset - 1) as u32
-                                } else {
-                                    !0
-                                },
-                            );
-                        }
-                    }
-                    counters.add(layout.content);
-                }
-            }
-            n::DescriptorSet::ArgumentBuffer {
-                ref raw,
-                raw_offset,
-                ref pool,
-                ref range,
-                ref encoder,
-                ref bindings,
-                ..
-            } => {
-                debug_assert!(self.shared.private_caps.argument_buffers);
-
-                encoder.set_argument_buffer(raw, raw_offset);
-                let mut arg_index = {
-                    let binding = &bindings[&op.binding];
-                    debug_assert!((op.array_offset as usize) < binding.count);
-                    (binding.res_offset as NSUInteger) + (op.array_offset as NSUInteger)
-                };
-
-                for (data, descriptor) in pool.write().resources
-                    [range.start as usize + arg_index as usize..range.end as usize]
-                    .iter_mut()
-                    .zip(op.descriptors)
-                {
-                    match descriptor {
-                        pso::Descriptor::Sampler(sampler) => {
-                            debug_assert!(!bindings[&op.binding]
-                                .content
-                                .contains(n::DescriptorContent::IMMUTABLE_SAMPLER));
-                            encoder.set_sampler_state(arg_index, sampler.raw.as_ref().unwrap());
-                            arg_index += 1;
-                        }
-                        pso::Descriptor::Image(image, _layout) => {
-                            let tex_ref = image.texture.as_ref();
-                            encoder.set_texture(arg_index, tex_ref);
-                            data.ptr = (&**tex_ref).as_ptr();
-                            arg_index += 1;
-                        }
-                        pso::Descriptor::CombinedImageSampler(image, _il, sampler) => {
-                            let binding = &bindings[&op.binding];
-                            if !binding
-                                .content
-                                .contains(n::DescriptorContent::IMMUTABLE_SAMPLER)
-                            {
-                                //TODO: supporting arrays of combined image-samplers can be tricky.
-                                // We need to scan both sampler and image sections of the encoder
-                                // at the same time.
-                                assert!(
-                                    arg_index
-                                        < (binding.res_offset as NSUInteger)
-                                            + (binding.count as NSUInteger)
-                                );
-                                encoder.set_sampler_state(
-                                    arg_index + binding.count as NSUInteger,
-                                    sampler.raw.as_ref().unwrap(),
-                                );
-                            }
-                            let tex_ref = image.texture.as_ref();
-                            encoder.set_texture(arg_index, tex_ref);
-                            data.ptr = (&**tex_ref).as_ptr();
-                        }
-                        pso::Descriptor::TexelBuffer(view) => {
-                            encoder.set_texture(arg_index, &view.raw);
-                            data.ptr = (&**view.raw).as_ptr();
-                            arg_index += 1;
-                        }
-                        pso::Descriptor::Buffer(buffer, ref sub) => {
-                            let (buf_raw, buf_range) = buffer.as_bound();
-                            encoder.set_buffer(arg_index, buf_raw, buf_range.start + sub.offset);
-                            data.ptr = (&**buf_raw).as_ptr();
-                            arg_index += 1;
-                        }
-                    }
-                }
-            }
-        }
+        self.write_descriptor_sets(iter::once(op));
     }
 
     unsafe fn copy_descriptor_set<'a>(&self, _op: pso::DescriptorSetCopy<'a, Backend>) {
@@ -2666,17 +4548,99 @@ impl hal::device::Device<Backend> for Device {
 
     unsafe fn destroy_pipeline_layout(&self, _pipeline_layout: n::PipelineLayout) {}
 
-    unsafe fn destroy_shader_module(&self, _module: n::ShaderModule) {}
+    unsafe fn destroy_shader_module(&self, module: n::ShaderModule) {
+        self.shader_compiler.forget_module(&module.key);
+    }
 
     unsafe fn destroy_render_pass(&self, _pass: n::RenderPass) {}
 
-    unsafe fn destroy_graphics_pipeline(&self, _pipeline: n::GraphicsPipeline) {}
+    unsafe fn destroy_graphics_pipeline(&self, pipeline: n::GraphicsPipeline) {
+        let mut states = self.render_pipeline_states.lock();
+        let last = match states.get_mut(&pipeline.state_key) {
+            Some(&mut (_, ref mut users)) => {
+                *users -= 1;
+                *users == 0
+            }
+            None => false,
+        };
+        if last {
+            states.remove(&pipeline.state_key);
+        }
+    }
+
+    unsafe fn destroy_graphics_pipeline_library(&self, _library: n::GraphicsPipelineLibrary) {}
 
     unsafe fn destroy_compute_pipeline(&self, _pipeline: n::ComputePipeline) {}
 
+    unsafe fn get_graphics_pipeline_executables(
+        &self,
+        pipeline: &n::GraphicsPipeline,
+    ) -> Vec<pso::PipelineExecutable> {
+        pipeline.executables.clone()
+    }
+
+    unsafe fn get_compute_pipeline_executables(
+        &self,
+        pipeline: &n::ComputePipeline,
+    ) -> Vec<pso::PipelineExecutable> {
+        pipeline.executables.clone()
+    }
+
+    unsafe fn destroy_ray_tracing_pipeline(&self, _pipeline: ()) {
+        unimplemented!()
+    }
+
     unsafe fn destroy_framebuffer(&self, _framebuffer: n::Framebuffer) {}
 
-    unsafe fn destroy_semaphore(&self, _semaphore: n::Semaphore) {}
+    unsafe fn destroy_semaphore(&self, _semaphore: n::Semaphore) {}
+
+    fn create_timeline_semaphore(
+        &self,
+        initial_value: u64,
+    ) -> Result<n::TimelineSemaphore, d::OutOfMemory> {
+        let raw = self.shared.device.new_shared_event();
+        let semaphore = n::TimelineSemaphore::new(raw);
+        semaphore.signal(initial_value);
+        Ok(semaphore)
+    }
+
+    unsafe fn destroy_timeline_semaphore(&self, _semaphore: n::TimelineSemaphore) {}
+
+    unsafe fn get_timeline_semaphore_value(
+        &self,
+        semaphore: &n::TimelineSemaphore,
+    ) -> Result<u64, d::DeviceLost> {
+        if self.shared.is_removed() {
+            return Err(d::DeviceLost);
+        }
+        Ok(semaphore.value())
+    }
+
+    unsafe fn signal_timeline_semaphore(
+        &self,
+        semaphore: &n::TimelineSemaphore,
+        value: u64,
+    ) -> Result<(), d::OutOfMemory> {
+        semaphore.signal(value);
+        Ok(())
+    }
+
+    unsafe fn wait_for_timeline_semaphore(
+        &self,
+        semaphore: &n::TimelineSemaphore,
+        value: u64,
+        timeout_ns: u64,
+    ) -> Result<bool, d::WaitError> {
+        if self.shared.is_removed() {
+            return Err(d::DeviceLost.into());
+        }
+        let timeout = if timeout_ns == !0 {
+            None
+        } else {
+            Some(time::Duration::from_nanos(timeout_ns))
+        };
+        Ok(semaphore.wait(value, timeout))
+    }
 
     unsafe fn allocate_memory(
         &self,
@@ -2685,7 +4649,7 @@ impl hal::device::Device<Backend> for Device {
     ) -> Result<n::Memory, d::AllocationError> {
         profiling::scope!("allocate_memory");
         let (storage, cache) = MemoryTypes::describe(memory_type.0);
-        let device = self.shared.device.lock();
+        let device = &self.shared.device;
         debug!("allocate_memory type {:?} of size {}", memory_type, size);
 
         // Heaps cannot be used for CPU coherent resources
@@ -2702,21 +4666,80 @@ impl hal::device::Device<Backend> for Device {
             n::MemoryHeap::Native(heap_raw)
         } else if storage == MTLStorageMode::Private {
             n::MemoryHeap::Private
+        } else if storage == MTLStorageMode::Shared && size <= MAX_PUBLIC_SUBALLOCATION {
+            let (cpu_buffer, offset) = self.suballocate_public(&device, memory_type, size);
+            debug!(
+                "\tsub-allocated from cpu buffer {:?} at {}",
+                cpu_buffer.as_ptr(),
+                offset
+            );
+            n::MemoryHeap::Public(memory_type, cpu_buffer, offset)
         } else {
             let options = conv::resource_options_from_storage_and_cache(storage, cache);
             let cpu_buffer = device.new_buffer(size, options);
             debug!("\tbacked by cpu buffer {:?}", cpu_buffer.as_ptr());
-            n::MemoryHeap::Public(memory_type, cpu_buffer)
+            n::MemoryHeap::Public(memory_type, cpu_buffer, 0)
         };
 
         Ok(n::Memory::new(heap, size))
     }
 
+    unsafe fn import_external_memory(
+        &self,
+        memory_type: hal::MemoryTypeId,
+        size: u64,
+        handle: ExternalMemoryHandle,
+    ) -> Result<n::Memory, ExternalMemoryError> {
+        debug!(
+            "import_external_memory type {:?} of size {} from {:?}",
+            memory_type, size, handle
+        );
+        let heap = match handle {
+            ExternalMemoryHandle::HostPointer(pointer) => {
+                let (storage, cache) = MemoryTypes::describe(memory_type.0);
+                if storage == MTLStorageMode::Private {
+                    return Err(ExternalMemoryError::UnsupportedHandleTypes(
+                        ExternalMemoryHandleTypes::HOST_POINTER,
+                    ));
+                }
+                let options = conv::resource_options_from_storage_and_cache(storage, cache);
+                let cpu_buffer = self.shared.device.new_buffer_with_bytes_no_copy(
+                    pointer.as_ptr() as *const _,
+                    size,
+                    options,
+                    None,
+                );
+                n::MemoryHeap::Public(memory_type, cpu_buffer, 0)
+            }
+            ExternalMemoryHandle::IOSurface(surface) => {
+                n::MemoryHeap::IOSurface(n::IOSurface::retain(surface))
+            }
+            other => {
+                return Err(ExternalMemoryError::UnsupportedHandleTypes(
+                    other.handle_type().into(),
+                ))
+            }
+        };
+
+        Ok(n::Memory {
+            heap,
+            size,
+            imported: true,
+        })
+    }
+
     unsafe fn free_memory(&self, memory: n::Memory) {
         profiling::scope!("free_memory");
         debug!("free_memory of size {}", memory.size);
-        if let n::MemoryHeap::Public(_, ref cpu_buffer) = memory.heap {
+        if let n::MemoryHeap::Public(memory_type, ref cpu_buffer, offset) = memory.heap {
             debug!("\tbacked by cpu buffer {:?}", cpu_buffer.as_ptr());
+            let (storage, _) = MemoryTypes::describe(memory_type.0);
+            if storage == MTLStorageMode::Shared
+                && memory.size <= MAX_PUBLIC_SUBALLOCATION
+                && !memory.imported
+            {
+                self.free_public(cpu_buffer, offset, memory.size);
+            }
         }
     }
 
@@ -2731,9 +4754,24 @@ impl hal::device::Device<Backend> for Device {
             usage,
             size,
             name: String::new(),
+            private_data: n::PrivateData::default(),
         })
     }
 
+    unsafe fn create_external_buffer(
+        &self,
+        size: u64,
+        usage: buffer::Usage,
+        sparse: memory::SparseFlags,
+        handle_types: ExternalMemoryHandleTypes,
+    ) -> Result<n::Buffer, ExternalMemoryError> {
+        let unsupported = handle_types - ExternalMemoryHandleTypes::HOST_POINTER;
+        if !unsupported.is_empty() {
+            return Err(ExternalMemoryError::UnsupportedHandleTypes(unsupported));
+        }
+        Ok(self.create_buffer(size, usage, sparse)?)
+    }
+
     unsafe fn get_buffer_requirements(&self, buffer: &n::Buffer) -> memory::Requirements {
         let (size, usage) = match *buffer {
             n::Buffer::Unbound { size, usage, .. } => (size, usage),
@@ -2748,11 +4786,7 @@ impl hal::device::Device<Backend> for Device {
             for (i, _mt) in self.memory_types.iter().enumerate() {
                 let (storage, cache) = MemoryTypes::describe(i);
                 let options = conv::resource_options_from_storage_and_cache(storage, cache);
-                let requirements = self
-                    .shared
-                    .device
-                    .lock()
-                    .heap_buffer_size_and_align(size, options);
+                let requirements = self.shared.device.heap_buffer_size_and_align(size, options);
                 max_size = cmp::max(max_size, requirements.size);
                 max_alignment = cmp::max(max_alignment, requirements.align);
             }
@@ -2761,13 +4795,16 @@ impl hal::device::Device<Backend> for Device {
         // based on Metal validation error for view creation:
         // failed assertion `BytesPerRow of a buffer-backed texture with pixelFormat(XXX) must be aligned to 256 bytes
         const SIZE_MASK: u64 = 0xFF;
-        let supports_texel_view =
-            usage.intersects(buffer::Usage::UNIFORM_TEXEL | buffer::Usage::STORAGE_TEXEL);
+
+        // Uniform texel views of buffers in shared memory are mirrored if the textures
+        // can't be created from them directly. Shader writes through storage texel views
+        // would only land in the mirror, so these buffers can't be in shared memory.
+        let storage_texel = usage.contains(buffer::Usage::STORAGE_TEXEL);
 
         memory::Requirements {
             size: (max_size + SIZE_MASK) & !SIZE_MASK,
             alignment: max_alignment,
-            type_mask: if !supports_texel_view || self.shared.private_caps.shared_textures {
+            type_mask: if !storage_texel || self.shared.private_caps.shared_textures {
                 MemoryTypes::all().bits()
             } else {
                 (MemoryTypes::all() ^ MemoryTypes::SHARED).bits()
@@ -2782,10 +4819,17 @@ impl hal::device::Device<Backend> for Device {
         buffer: &mut n::Buffer,
     ) -> Result<(), d::BindError> {
         profiling::scope!("bind_buffer_memory");
-        let (size, name) = match buffer {
-            n::Buffer::Unbound { size, name, .. } => (*size, name),
+        let (size, usage, name, private_data) = match buffer {
+            n::Buffer::Unbound {
+                size,
+                usage,
+                name,
+                private_data,
+            } => (*size, *usage, name, mem::take(private_data)),
             n::Buffer::Bound { .. } => panic!("Unexpected Buffer::Bound"),
         };
+        let addressed = usage.contains(buffer::Usage::SHADER_DEVICE_ADDRESS)
+            && self.shared.private_caps.buffer_gpu_address;
         debug!("bind_buffer_memory of size {} at offset {}", size, offset);
         *buffer = match memory.heap {
             n::MemoryHeap::Native(ref heap) => {
@@ -2795,22 +4839,25 @@ impl hal::device::Device<Backend> for Device {
                 );
                 let raw = heap.new_buffer(size, options).unwrap_or_else(|| {
                     // TODO: disable hazard tracking?
-                    self.shared.device.lock().new_buffer(size, options)
+                    self.shared.device.new_buffer(size, options)
                 });
                 raw.set_label(name);
                 n::Buffer::Bound {
                     raw,
                     options,
                     range: 0..size, //TODO?
+                    addressed,
+                    private_data,
                 }
             }
-            n::MemoryHeap::Public(mt, ref cpu_buffer) => {
+            n::MemoryHeap::Public(mt, ref cpu_buffer, base) => {
                 debug!(
                     "\tmapped to public heap with address {:?}",
                     cpu_buffer.as_ptr()
                 );
                 let (storage, cache) = MemoryTypes::describe(mt.0);
                 let options = conv::resource_options_from_storage_and_cache(storage, cache);
+                let offset = base + offset;
                 if offset == 0x0 && size == cpu_buffer.length() {
                     cpu_buffer.set_label(name);
                 } else if self.shared.private_caps.supports_debug_markers {
@@ -2826,32 +4873,69 @@ impl hal::device::Device<Backend> for Device {
                     raw: cpu_buffer.clone(),
                     options,
                     range: offset..offset + size,
+                    addressed,
+                    private_data,
                 }
             }
             n::MemoryHeap::Private => {
                 //TODO: check for aliasing
                 let options = MTLResourceOptions::StorageModePrivate
                     | MTLResourceOptions::CPUCacheModeDefaultCache;
-                let raw = self.shared.device.lock().new_buffer(size, options);
+                let raw = self.shared.device.new_buffer(size, options);
                 raw.set_label(name);
                 n::Buffer::Bound {
                     raw,
                     options,
                     range: 0..size,
+                    addressed,
+                    private_data,
                 }
             }
+            n::MemoryHeap::IOSurface(_) => return Err(d::BindError::WrongMemory),
         };
+        if addressed {
+            let (raw, _) = buffer.as_bound();
+            self.shared.addressed_buffers.lock().push(raw.to_owned());
+        }
 
         Ok(())
     }
 
+    unsafe fn get_buffer_device_address(
+        &self,
+        buffer: &n::Buffer,
+    ) -> Option<buffer::DeviceAddress> {
+        if !self.shared.private_caps.buffer_gpu_address {
+            return None;
+        }
+        let (raw, range) = buffer.as_bound();
+        // Public heap buffers share the raw buffer of their memory, at an offset.
+        let address: u64 = msg_send![raw, gpuAddress];
+        Some(address + range.start)
+    }
+
     unsafe fn destroy_buffer(&self, buffer: n::Buffer) {
-        if let n::Buffer::Bound { raw, range, .. } = buffer {
+        if let n::Buffer::Bound {
+            raw,
+            range,
+            addressed,
+            ..
+        } = buffer
+        {
             debug!(
                 "destroy_buffer {:?} occupying memory {:?}",
                 raw.as_ptr(),
                 range
             );
+            if addressed {
+                let mut addressed_buffers = self.shared.addressed_buffers.lock();
+                if let Some(pos) = addressed_buffers
+                    .iter()
+                    .position(|b| b.as_ptr() == raw.as_ptr())
+                {
+                    addressed_buffers.swap_remove(pos);
+                }
+            }
         }
     }
 
@@ -2866,6 +4950,7 @@ impl hal::device::Device<Backend> for Device {
                 ref raw,
                 ref range,
                 options,
+                ..
             } => (raw, range, options),
             n::Buffer::Unbound { .. } => panic!("Unexpected Buffer::Unbound"),
         };
@@ -2907,15 +4992,71 @@ impl hal::device::Device<Backend> for Device {
         let align_mask = self.shared.private_caps.buffer_alignment - 1;
         let stride = (col_count * (format_desc.bits as u64 / 8) + align_mask) & !align_mask;
 
+        if raw.storage_mode() == MTLStorageMode::Shared && !self.shared.private_caps.shared_textures
+        {
+            // Only uniform texel buffers can be in shared memory here,
+            // see `get_buffer_requirements`.
+            debug!("Mirroring the texel view of a buffer in shared memory");
+            let mirror_raw = self
+                .shared
+                .device
+                .new_buffer(stride * row_count, MTLResourceOptions::StorageModePrivate);
+            mirror_raw.set_label("texel mirror");
+            descriptor.set_resource_options(MTLResourceOptions::StorageModePrivate);
+            descriptor.set_storage_mode(MTLStorageMode::Private);
+            let texture = mirror_raw.new_texture_with_descriptor(&descriptor, 0, stride);
+            let mirror = Arc::new(n::TexelMirror {
+                source: raw.clone(),
+                offset: start,
+                raw: mirror_raw,
+                size: cmp::min(size_rough, raw.length() - start) & !3,
+                texture: AsNative::from(texture.as_ref()),
+            });
+            let mut registry = self.shared.texel_mirrors.lock();
+            registry.retain(|mirror| mirror.strong_count() != 0);
+            registry.push(Arc::downgrade(&mirror));
+            return Ok(n::BufferView {
+                raw: texture,
+                mirror: Some(mirror),
+            });
+        }
+
         Ok(n::BufferView {
             raw: raw.new_texture_with_descriptor(&descriptor, start, stride),
+            mirror: None,
         })
     }
 
+    unsafe fn get_acceleration_structure_build_requirements(
+        &self,
+        _desc: &hal::acceleration_structure::GeometryDesc<Backend>,
+        _max_primitive_counts: &[u32],
+    ) -> hal::acceleration_structure::SizeRequirements {
+        unimplemented!()
+    }
+
+    unsafe fn create_acceleration_structure(
+        &self,
+        _desc: &hal::acceleration_structure::CreateDesc<Backend>,
+    ) -> Result<(), d::OutOfMemory> {
+        unimplemented!()
+    }
+
+    unsafe fn get_acceleration_structure_address(
+        &self,
+        _accel_struct: &(),
+    ) -> hal::acceleration_structure::DeviceAddress {
+        unimplemented!()
+    }
+
     unsafe fn destroy_buffer_view(&self, _view: n::BufferView) {
         //nothing to do
     }
 
+    unsafe fn destroy_acceleration_structure(&self, _accel_struct: ()) {
+        unimplemented!()
+    }
+
     unsafe fn create_image(
         &self,
         kind: image::Kind,
@@ -2983,6 +5124,20 @@ impl hal::device::Device<Backend> for Device {
             }
         };
 
+        let planes = format.planes();
+        if !planes.is_empty() {
+            if mtl_type != MTLTextureType::D2 || mip_levels != 1 {
+                error!(
+                    "Two-plane {:?} images need to be 2D with a single level",
+                    format
+                );
+                return Err(image::CreationError::Kind);
+            }
+            if tiling != image::Tiling::Optimal || usage.contains(image::Usage::HOST_TRANSFER) {
+                return Err(image::CreationError::Usage(usage));
+            }
+        }
+
         descriptor.set_texture_type(mtl_type);
         if let Some(count) = num_layers {
             descriptor.set_array_length(count as u64);
@@ -2993,30 +5148,72 @@ impl hal::device::Device<Backend> for Device {
         descriptor.set_depth(extent.depth as u64);
         descriptor.set_mipmap_level_count(mip_levels as u64);
         descriptor.set_pixel_format(mtl_format);
-        descriptor.set_usage(conv::map_texture_usage(usage, tiling, view_caps));
 
         let base = format.base_format();
-        let format_desc = base.0.desc();
-        let mip_sizes = (0..mip_levels)
+        let format_desc = match planes.first() {
+            Some(luma) => luma.surface_desc(),
+            None => base.0.desc(),
+        };
+        let decompression = if self.shared.private_caps.format_bc_decompression {
+            BlockFormat::from_format(format)
+        } else {
+            None
+        };
+        let mut texture_usage = conv::map_texture_usage(usage, tiling, view_caps);
+        if decompression.is_some() && usage.contains(image::Usage::TRANSFER_DST) {
+            // the blocks are decoded by a compute pass writing through an integer view
+            texture_usage |=
+                metal::MTLTextureUsage::ShaderWrite | metal::MTLTextureUsage::PixelFormatView;
+        }
+        descriptor.set_usage(texture_usage);
+        let mut mip_sizes = (0..mip_levels)
             .map(|level| {
                 let pitches = n::Image::pitches_impl(extent.at_level(level), format_desc);
                 num_layers.unwrap_or(1) as buffer::Offset * pitches[3]
             })
-            .collect();
+            .collect::<Vec<_>>();
+
+        let chroma_plane = planes.get(1).map(|&chroma_format| {
+            // chroma is subsampled horizontally and vertically
+            let chroma_extent = image::Extent {
+                width: (extent.width + 1) / 2,
+                height: (extent.height + 1) / 2,
+                depth: 1,
+            };
+            let format_desc = chroma_format.surface_desc();
+            let mtl_format = self.shared.private_caps.map_format(chroma_format).unwrap();
+            let descriptor = metal::TextureDescriptor::new();
+            descriptor.set_texture_type(MTLTextureType::D2);
+            descriptor.set_width(chroma_extent.width as u64);
+            descriptor.set_height(chroma_extent.height as u64);
+            descriptor.set_pixel_format(mtl_format);
+            descriptor.set_usage(texture_usage);
+            mip_sizes[0] += n::Image::pitches_impl(chroma_extent, format_desc)[3];
+            n::ChromaPlane {
+                descriptor,
+                texture: None,
+                format_desc,
+                mtl_format,
+            }
+        });
 
-        let host_usage = image::Usage::TRANSFER_SRC | image::Usage::TRANSFER_DST;
+        let host_usage =
+            image::Usage::TRANSFER_SRC | image::Usage::TRANSFER_DST | image::Usage::HOST_TRANSFER;
         let host_visible = mtl_type == MTLTextureType::D2
             && mip_levels == 1
             && num_layers.is_none()
             && format_desc.aspects.contains(format::Aspects::COLOR)
             && tiling == image::Tiling::Linear
-            && host_usage.contains(usage);
+            && host_usage.contains(usage)
+            && decompression.is_none();
+        let host_transfer = usage.contains(image::Usage::HOST_TRANSFER) && !host_visible;
 
         Ok(n::Image {
             like: n::ImageLike::Unbound {
                 descriptor,
                 mip_sizes,
                 host_visible,
+                host_transfer,
                 name: String::new(),
             },
             kind,
@@ -3025,17 +5222,79 @@ impl hal::device::Device<Backend> for Device {
             shader_channel: base.1.into(),
             mtl_format,
             mtl_type,
+            decompression,
+            chroma_plane,
+            private_data: n::PrivateData::default(),
         })
     }
 
+    unsafe fn create_image_with_compression(
+        &self,
+        kind: image::Kind,
+        mip_levels: image::Level,
+        format: format::Format,
+        tiling: image::Tiling,
+        usage: image::Usage,
+        sparse: memory::SparseFlags,
+        view_caps: image::ViewCapabilities,
+        compression: image::Compression,
+    ) -> Result<n::Image, image::CreationError> {
+        let image =
+            self.create_image(kind, mip_levels, format, tiling, usage, sparse, view_caps)?;
+        match image.like {
+            // Host visible images are backed by buffers, and block-compressed formats are
+            // never compressed further, so there is nothing to disable for them.
+            n::ImageLike::Unbound {
+                ref descriptor,
+                host_visible: false,
+                ..
+            } if compression == image::Compression::Disabled
+                && !image.format_desc.is_compressed() =>
+            {
+                if !self.shared.private_caps.gpu_optimized_contents {
+                    return Err(image::CreationError::Compression(compression));
+                }
+                let () = msg_send![descriptor.as_ref(), setAllowGPUOptimizedContents: NO];
+            }
+            _ => {}
+        }
+        Ok(image)
+    }
+
+    unsafe fn create_external_image(
+        &self,
+        kind: image::Kind,
+        mip_levels: image::Level,
+        format: format::Format,
+        tiling: image::Tiling,
+        usage: image::Usage,
+        sparse: memory::SparseFlags,
+        view_caps: image::ViewCapabilities,
+        handle_types: ExternalMemoryHandleTypes,
+    ) -> Result<n::Image, ExternalMemoryError> {
+        let dimensions = match kind {
+            image::Kind::D1(..) => 1,
+            image::Kind::D2(..) => 2,
+            image::Kind::D3(..) => 3,
+        };
+        let supported = external_image_handle_types(dimensions, tiling);
+        if !supported.contains(handle_types) {
+            return Err(ExternalMemoryError::UnsupportedHandleTypes(
+                handle_types - supported,
+            ));
+        }
+        Ok(self.create_image(kind, mip_levels, format, tiling, usage, sparse, view_caps)?)
+    }
+
     unsafe fn get_image_requirements(&self, image: &n::Image) -> memory::Requirements {
-        let (descriptor, mip_sizes, host_visible) = match image.like {
+        let (descriptor, mip_sizes, host_visible, host_transfer) = match image.like {
             n::ImageLike::Unbound {
                 ref descriptor,
                 ref mip_sizes,
                 host_visible,
+                host_transfer,
                 ..
-            } => (descriptor, mip_sizes, host_visible),
+            } => (descriptor, mip_sizes, host_visible, host_transfer),
             n::ImageLike::Texture(..) | n::ImageLike::Buffer(..) => {
                 panic!("Expected Image::Unbound")
             }
@@ -3048,6 +5307,8 @@ impl hal::device::Device<Backend> for Device {
             let mut max_alignment = 0;
             let types = if host_visible {
                 MemoryTypes::all()
+            } else if host_transfer {
+                self.host_transfer_memory_types()
             } else {
                 MemoryTypes::PRIVATE
             };
@@ -3059,11 +5320,19 @@ impl hal::device::Device<Backend> for Device {
                 descriptor.set_storage_mode(storage);
                 descriptor.set_cpu_cache_mode(cache_mode);
 
-                let requirements = self
-                    .shared
-                    .device
-                    .lock()
-                    .heap_texture_size_and_align(descriptor);
+                let mut requirements = self.shared.device.heap_texture_size_and_align(descriptor);
+                if let Some(ref plane) = image.chroma_plane {
+                    plane.descriptor.set_storage_mode(storage);
+                    plane.descriptor.set_cpu_cache_mode(cache_mode);
+                    let chroma = self
+                        .shared
+                        .device
+                        .heap_texture_size_and_align(&plane.descriptor);
+                    // the chroma texture is placed after the luma one
+                    let mask = chroma.align - 1;
+                    requirements.size = ((requirements.size + mask) & !mask) + chroma.size;
+                    requirements.align = cmp::max(requirements.align, chroma.align);
+                }
                 max_size = cmp::max(max_size, requirements.size);
                 max_alignment = cmp::max(max_alignment, requirements.align);
             }
@@ -3081,10 +5350,15 @@ impl hal::device::Device<Backend> for Device {
                 type_mask: MemoryTypes::all().bits(),
             }
         } else {
+            let types = if host_transfer {
+                self.host_transfer_memory_types()
+            } else {
+                MemoryTypes::PRIVATE
+            };
             memory::Requirements {
                 size: mip_sizes.iter().sum(),
                 alignment: 4,
-                type_mask: MemoryTypes::PRIVATE.bits(),
+                type_mask: types.bits(),
             }
         }
     }
@@ -3116,20 +5390,21 @@ impl hal::device::Device<Backend> for Device {
         image: &mut n::Image,
     ) -> Result<(), d::BindError> {
         profiling::scope!("bind_image_memory");
-        let like = {
-            let (descriptor, mip_sizes, name) = match image.like {
+        let (like, chroma) = {
+            let (descriptor, mip_sizes, host_transfer, name) = match image.like {
                 n::ImageLike::Unbound {
                     ref descriptor,
                     ref mip_sizes,
+                    host_transfer,
                     ref name,
                     ..
-                } => (descriptor, mip_sizes, name),
+                } => (descriptor, mip_sizes, host_transfer, name),
                 n::ImageLike::Texture(..) | n::ImageLike::Buffer(..) => {
                     panic!("Expected Image::Unbound")
                 }
             };
 
-            match memory.heap {
+            let like = match memory.heap {
                 n::MemoryHeap::Native(ref heap) => {
                     let resource_options = conv::resource_options_from_storage_and_cache(
                         heap.storage_mode(),
@@ -3138,13 +5413,23 @@ impl hal::device::Device<Backend> for Device {
                     descriptor.set_resource_options(resource_options);
                     n::ImageLike::Texture(heap.new_texture(descriptor).unwrap_or_else(|| {
                         // TODO: disable hazard tracking?
-                        let texture = self.shared.device.lock().new_texture(&descriptor);
+                        let texture = self.shared.device.new_texture(&descriptor);
                         texture.set_label(name);
                         texture
                     }))
                 }
-                n::MemoryHeap::Public(_memory_type, ref cpu_buffer) => {
+                n::MemoryHeap::Public(memory_type, ..) if host_transfer => {
+                    // The texture has its own storage, accessible by the host
+                    let (storage, cache) = MemoryTypes::describe(memory_type.0);
+                    descriptor.set_storage_mode(storage);
+                    descriptor.set_cpu_cache_mode(cache);
+                    let texture = self.shared.device.new_texture(descriptor);
+                    texture.set_label(name);
+                    n::ImageLike::Texture(texture)
+                }
+                n::MemoryHeap::Public(_memory_type, ref cpu_buffer, base) => {
                     assert_eq!(mip_sizes.len(), 1);
+                    let offset = base + offset;
                     if offset == 0x0 && cpu_buffer.length() == mip_sizes[0] {
                         cpu_buffer.set_label(name);
                     } else if self.shared.private_caps.supports_debug_markers {
@@ -3160,20 +5445,175 @@ impl hal::device::Device<Backend> for Device {
                         raw: cpu_buffer.clone(),
                         range: offset..offset + mip_sizes[0] as u64,
                         options: MTLResourceOptions::StorageModeShared,
+                        addressed: false,
+                        private_data: n::PrivateData::default(),
                     })
                 }
                 n::MemoryHeap::Private => {
                     descriptor.set_storage_mode(MTLStorageMode::Private);
-                    let texture = self.shared.device.lock().new_texture(descriptor);
+                    let texture = self.shared.device.new_texture(descriptor);
                     texture.set_label(name);
                     n::ImageLike::Texture(texture)
                 }
-            }
+                n::MemoryHeap::IOSurface(ref surface) => {
+                    if offset != 0 || descriptor.texture_type() != MTLTextureType::D2 {
+                        return Err(d::BindError::WrongMemory);
+                    }
+                    let raw: *mut metal::MTLTexture = msg_send![
+                        self.shared.device.as_ref(),
+                        newTextureWithDescriptor: descriptor.as_ref()
+                        iosurface: surface.0.as_ptr()
+                        plane: 0 as NSUInteger
+                    ];
+                    if raw.is_null() {
+                        return Err(d::BindError::WrongMemory);
+                    }
+                    let texture = metal::Texture::from_ptr(raw);
+                    texture.set_label(name);
+                    n::ImageLike::Texture(texture)
+                }
+            };
+            let chroma = match image.chroma_plane {
+                Some(ref plane) => {
+                    Some(self.create_chroma_texture(memory, &plane.descriptor, name)?)
+                }
+                None => None,
+            };
+            (like, chroma)
         };
 
+        if let Some(ref mut plane) = image.chroma_plane {
+            plane.texture = chroma;
+        }
         Ok(image.like = like)
     }
 
+    unsafe fn copy_memory_to_image<T>(
+        &self,
+        data: &[u8],
+        image: &n::Image,
+        regions: T,
+    ) -> Result<(), d::HostCopyError>
+    where
+        T: Iterator<Item = com::BufferImageCopy>,
+    {
+        match image.like {
+            n::ImageLike::Unbound { .. } => panic!("Unexpected Image::Unbound"),
+            n::ImageLike::Texture(_) if image.decompression.is_some() => {
+                error!(
+                    "Unable to copy to a decompressed {:?} image on the host",
+                    image.decompression
+                );
+                return Err(d::HostCopyError::Access);
+            }
+            n::ImageLike::Texture(ref raw) => {
+                for r in regions.filter(|r| !r.image_extent.is_empty()) {
+                    let extent = conv::map_extent(r.image_extent);
+                    let (row_pitch, slice_pitch) =
+                        command::compute_pitches(&r, image.format_desc, &extent);
+                    let region = metal::MTLRegion {
+                        origin: conv::map_offset(r.image_offset),
+                        size: extent,
+                    };
+                    let layers = &r.image_layers.layers;
+                    for layer in layers.clone() {
+                        let offset = r.buffer_offset as usize
+                            + slice_pitch as usize * (layer - layers.start) as usize;
+                        let () = msg_send![
+                            raw.as_ref(),
+                            replaceRegion: region
+                            mipmapLevel: r.image_layers.level as NSUInteger
+                            slice: layer as NSUInteger
+                            withBytes: data[offset..].as_ptr()
+                            bytesPerRow: row_pitch as NSUInteger
+                            bytesPerImage: slice_pitch as NSUInteger
+                        ];
+                    }
+                }
+            }
+            n::ImageLike::Buffer(ref buffer) => {
+                let (raw, range) = buffer.as_bound();
+                let base_ptr = (raw.contents() as *mut u8).offset(range.start as isize);
+                let data_size = data.len() as buffer::Offset;
+                for c in regions.flat_map(|r| image.linear_copies(&r, data_size)) {
+                    ptr::copy_nonoverlapping(
+                        data[c.buffer as usize..].as_ptr(),
+                        base_ptr.offset(c.image as isize),
+                        c.size as usize,
+                    );
+                    if raw.storage_mode() == MTLStorageMode::Managed {
+                        raw.did_modify_range(NSRange {
+                            location: range.start + c.image,
+                            length: c.size,
+                        });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    unsafe fn copy_image_to_memory<T>(
+        &self,
+        image: &n::Image,
+        data: &mut [u8],
+        regions: T,
+    ) -> Result<(), d::HostCopyError>
+    where
+        T: Iterator<Item = com::BufferImageCopy>,
+    {
+        match image.like {
+            n::ImageLike::Unbound { .. } => panic!("Unexpected Image::Unbound"),
+            n::ImageLike::Texture(_) if image.decompression.is_some() => {
+                error!(
+                    "Unable to copy from a decompressed {:?} image on the host",
+                    image.decompression
+                );
+                return Err(d::HostCopyError::Access);
+            }
+            n::ImageLike::Texture(ref raw) => {
+                self.synchronize_managed_resource(raw);
+                for r in regions.filter(|r| !r.image_extent.is_empty()) {
+                    let extent = conv::map_extent(r.image_extent);
+                    let (row_pitch, slice_pitch) =
+                        command::compute_pitches(&r, image.format_desc, &extent);
+                    let region = metal::MTLRegion {
+                        origin: conv::map_offset(r.image_offset),
+                        size: extent,
+                    };
+                    let layers = &r.image_layers.layers;
+                    for layer in layers.clone() {
+                        let offset = r.buffer_offset as usize
+                            + slice_pitch as usize * (layer - layers.start) as usize;
+                        let () = msg_send![
+                            raw.as_ref(),
+                            getBytes: data[offset..].as_mut_ptr()
+                            bytesPerRow: row_pitch as NSUInteger
+                            bytesPerImage: slice_pitch as NSUInteger
+                            fromRegion: region
+                            mipmapLevel: r.image_layers.level as NSUInteger
+                            slice: layer as NSUInteger
+                        ];
+                    }
+                }
+            }
+            n::ImageLike::Buffer(ref buffer) => {
+                let (raw, range) = buffer.as_bound();
+                self.synchronize_managed_resource(raw);
+                let base_ptr = (raw.contents() as *const u8).offset(range.start as isize);
+                let data_size = data.len() as buffer::Offset;
+                for c in regions.flat_map(|r| image.linear_copies(&r, data_size)) {
+                    ptr::copy_nonoverlapping(
+                        base_ptr.offset(c.image as isize),
+                        data[c.buffer as usize..].as_mut_ptr(),
+                        c.size as usize,
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
     unsafe fn destroy_image(&self, _image: n::Image) {
         //nothing to do
     }
@@ -3189,20 +5629,35 @@ impl hal::device::Device<Backend> for Device {
     ) -> Result<n::ImageView, image::ViewCreationError> {
         profiling::scope!("create_image_view");
 
-        let mtl_format = match self
-            .shared
-            .private_caps
-            .map_format_with_swizzle(format, swizzle)
-        {
-            Some(f) => f,
-            None => {
-                error!("failed to swizzle format {:?} with {:?}", format, swizzle);
-                return Err(image::ViewCreationError::BadFormat(format));
-            }
+        let caps = &self.shared.private_caps;
+        // Swizzle natively if the format can't be remapped to express it.
+        let (mtl_format, native_swizzle) = match caps.map_format_with_swizzle(format, swizzle) {
+            Some(f) => (f, None),
+            None => match caps.map_format(format) {
+                Some(f) if caps.texture_swizzle => (f, Some(conv::map_swizzle(swizzle))),
+                _ => {
+                    error!("failed to swizzle format {:?} with {:?}", format, swizzle);
+                    return Err(image::ViewCreationError::BadFormat(format));
+                }
+            },
+        };
+        // The planes of two-plane images are separate textures, which have a single
+        // level and layer, so the views of whole planes can use them directly.
+        let (raw, image_mtl_format, full_aspects) = match image.chroma_plane {
+            Some(ref plane) if range.aspects.contains(format::Aspects::PLANE_1) => (
+                plane.texture.as_deref().expect("Expected bound image!"),
+                plane.mtl_format,
+                range.aspects,
+            ),
+            Some(_) => (image.like.as_texture(), image.mtl_format, range.aspects),
+            None => (
+                image.like.as_texture(),
+                image.mtl_format,
+                image.format_desc.aspects,
+            ),
         };
-        let raw = image.like.as_texture();
         let full_range = image::SubresourceRange {
-            aspects: image.format_desc.aspects,
+            aspects: full_aspects,
             ..Default::default()
         };
         let mtl_type = if image.mtl_type == MTLTextureType::D2Multisample {
@@ -3214,7 +5669,7 @@ impl hal::device::Device<Backend> for Device {
             conv::map_texture_type(kind)
         };
 
-        let texture = if mtl_format == image.mtl_format
+        let texture = if mtl_format == image_mtl_format
             && mtl_type == image.mtl_type
             && swizzle == format::Swizzle::NO
             && range == full_range
@@ -3223,23 +5678,41 @@ impl hal::device::Device<Backend> for Device {
             // Also helps working around Metal bugs with aliased array textures.
             raw.to_owned()
         } else {
-            raw.new_texture_view_from_slice(
-                mtl_format,
-                mtl_type,
-                NSRange {
-                    location: range.level_start as _,
-                    length: range.resolve_level_count(image.mip_levels) as _,
-                },
-                NSRange {
-                    location: range.layer_start as _,
-                    length: range.resolve_layer_count(image.kind.num_layers()) as _,
-                },
-            )
+            let levels = NSRange {
+                location: range.level_start as _,
+                length: range.resolve_level_count(image.mip_levels) as _,
+            };
+            let slices = NSRange {
+                location: range.layer_start as _,
+                length: range.resolve_layer_count(image.kind.num_layers()) as _,
+            };
+            match native_swizzle {
+                Some(channels) => {
+                    let view: *mut metal::MTLTexture = msg_send![
+                        raw,
+                        newTextureViewWithPixelFormat: mtl_format
+                        textureType: mtl_type
+                        levels: levels
+                        slices: slices
+                        swizzle: channels
+                    ];
+                    metal::Texture::from_ptr(view)
+                }
+                None => raw.new_texture_view_from_slice(mtl_format, mtl_type, levels, slices),
+            }
+        };
+
+        let chroma = match image.chroma_plane {
+            Some(ref plane) if range.aspects.contains(format::Aspects::COLOR) => {
+                plane.texture.clone()
+            }
+            _ => None,
         };
 
         Ok(n::ImageView {
             texture,
             mtl_format,
+            chroma,
         })
     }
 
@@ -3261,11 +5734,10 @@ impl hal::device::Device<Backend> for Device {
         fence: &n::Fence,
         timeout_ns: u64,
     ) -> Result<bool, d::WaitError> {
-        unsafe fn to_ns(duration: time::Duration) -> u64 {
-            duration.as_secs() * 1_000_000_000 + duration.subsec_nanos() as u64
-        }
-
         debug!("wait_for_fence {:?} for {} ms", fence, timeout_ns);
+        if self.shared.is_removed() {
+            return Err(d::DeviceLost.into());
+        }
         match *fence {
             n::Fence::Idle { signaled } => {
                 if !signaled {
@@ -3273,33 +5745,37 @@ impl hal::device::Device<Backend> for Device {
                 }
                 Ok(signaled)
             }
-            n::Fence::PendingSubmission(ref cmd_buf) => {
-                if timeout_ns == !0 {
-                    cmd_buf.wait_until_completed();
-                    return Ok(true);
+            n::Fence::PendingSubmission {
+                ref cmd_buffer,
+                ref signal,
+            } => {
+                let timeout = if timeout_ns == !0 {
+                    None
+                } else {
+                    Some(time::Duration::from_nanos(timeout_ns))
+                };
+                // the completion handler also fires for failed command buffers,
+                // which is what happens to the pending work of a removed device
+                if !signal.wait(timeout) {
+                    return Ok(false);
                 }
-                let start = time::Instant::now();
-                loop {
-                    if let metal::MTLCommandBufferStatus::Completed = cmd_buf.status() {
-                        return Ok(true);
+                match cmd_buffer.status() {
+                    metal::MTLCommandBufferStatus::Error if self.shared.is_removed() => {
+                        Err(d::DeviceLost.into())
                     }
-                    if to_ns(start.elapsed()) >= timeout_ns {
-                        return Ok(false);
-                    }
-                    thread::sleep(time::Duration::from_millis(1));
-                    self.shared.queue_blocker.lock().triage();
+                    _ => Ok(true),
                 }
             }
         }
     }
 
     unsafe fn get_fence_status(&self, fence: &n::Fence) -> Result<bool, d::DeviceLost> {
+        if self.shared.is_removed() {
+            return Err(d::DeviceLost);
+        }
         Ok(match *fence {
             n::Fence::Idle { signaled } => signaled,
-            n::Fence::PendingSubmission(ref cmd_buf) => match cmd_buf.status() {
-                metal::MTLCommandBufferStatus::Completed => true,
-                _ => false,
-            },
+            n::Fence::PendingSubmission { ref signal, .. } => signal.is_signaled(),
         })
     }
 
@@ -3312,6 +5788,9 @@ impl hal::device::Device<Backend> for Device {
     }
 
     unsafe fn get_event_status(&self, event: &n::Event) -> Result<bool, d::WaitError> {
+        if self.shared.is_removed() {
+            return Err(d::DeviceLost.into());
+        }
         Ok(event.0.load(Ordering::Acquire))
     }
 
@@ -3353,6 +5832,10 @@ impl hal::device::Device<Backend> for Device {
                 Ok(n::QueryPool::Timestamp)
             }
             query::Type::PipelineStatistics(..) => Err(query::CreationError::Unsupported(ty)),
+            query::Type::PerformanceCounters(set) => {
+                let pool = counters::CounterPool::new(&self.shared.device, set, count)?;
+                Ok(n::QueryPool::PerformanceCounters(pool))
+            }
         }
     }
 
@@ -3361,7 +5844,7 @@ impl hal::device::Device<Backend> for Device {
             n::QueryPool::Occlusion(range) => {
                 self.shared.visibility.allocator.lock().free_range(range);
             }
-            n::QueryPool::Timestamp => {}
+            n::QueryPool::Timestamp | n::QueryPool::PerformanceCounters(_) => {}
         }
     }
 
@@ -3431,11 +5914,57 @@ impl hal::device::Device<Backend> for Device {
                 }
                 true
             }
+            n::QueryPool::PerformanceCounters(ref pool) => {
+                let mut results = pool.resolve(queries.clone());
+                if flags.contains(query::ResultFlags::WAIT) {
+                    // the samples aren't tracked by the submissions, so poll until they are taken
+                    while results.iter().any(Option::is_none) {
+                        thread::sleep(time::Duration::from_millis(1));
+                        results = pool.resolve(queries.clone());
+                    }
+                }
+
+                for (i, result) in results.iter().enumerate() {
+                    let data_ptr = data[i * stride as usize..].as_mut_ptr();
+                    let write = |index: usize, value: u64| {
+                        if flags.contains(query::ResultFlags::BITS_64) {
+                            *(data_ptr as *mut u64).add(index) = value;
+                        } else {
+                            *(data_ptr as *mut u32).add(index) = value as u32;
+                        }
+                    };
+                    if let Some(ref values) = *result {
+                        for (index, &value) in values.iter().enumerate() {
+                            write(index, value);
+                        }
+                    }
+                    if flags.contains(query::ResultFlags::WITH_AVAILABILITY) {
+                        write(pool.counters(), result.is_some() as u64);
+                    }
+                }
+
+                results.iter().all(Option::is_some)
+            }
         };
 
         Ok(is_ready)
     }
 
+    unsafe fn reset_query_pool(&self, pool: &n::QueryPool, queries: Range<query::Id>) {
+        match *pool {
+            n::QueryPool::Occlusion(ref pool_range) => {
+                let visibility = &self.shared.visibility;
+                let base = visibility.buffer.contents() as *mut u8;
+                let start = (pool_range.start + queries.start) as isize;
+                let count = (queries.end - queries.start) as usize;
+                ptr::write_bytes((base as *mut u64).offset(start), 0, count);
+                let availability = base.offset(visibility.availability_offset as isize);
+                ptr::write_bytes((availability as *mut u32).offset(start), 0, count);
+            }
+            n::QueryPool::Timestamp | n::QueryPool::PerformanceCounters(_) => {}
+        }
+    }
+
     fn wait_idle(&self) -> Result<(), d::OutOfMemory> {
         command::QueueInner::wait_idle(&self.shared.queue);
         Ok(())
@@ -3449,8 +5978,14 @@ impl hal::device::Device<Backend> for Device {
             } => self.set_buffer_name(buf, name),
             n::Image {
                 like: n::ImageLike::Texture(ref tex),
+                ref chroma_plane,
                 ..
-            } => tex.set_label(name),
+            } => {
+                tex.set_label(name);
+                if let Some(texture) = chroma_plane.as_ref().and_then(|p| p.texture.as_ref()) {
+                    texture.set_label(&format!("{} chroma", name));
+                }
+            }
             n::Image {
                 like:
                     n::ImageLike::Unbound {
@@ -3526,6 +6061,33 @@ impl hal::device::Device<Backend> for Device {
         // TODO
     }
 
+    unsafe fn create_private_data_slot(&self) -> Result<n::PrivateDataSlot, d::OutOfMemory> {
+        let id = self.next_private_data_slot.fetch_add(1, Ordering::Relaxed);
+        Ok(n::PrivateDataSlot(id))
+    }
+
+    unsafe fn destroy_private_data_slot(&self, _slot: n::PrivateDataSlot) {
+        // Identifiers are never reused, so the values left in the objects are unreachable.
+    }
+
+    unsafe fn set_private_data(
+        &self,
+        object: d::PrivateDataObject<Backend>,
+        slot: &n::PrivateDataSlot,
+        data: u64,
+    ) -> Result<(), d::OutOfMemory> {
+        object_private_data(object).set(slot, data);
+        Ok(())
+    }
+
+    unsafe fn get_private_data(
+        &self,
+        object: d::PrivateDataObject<Backend>,
+        slot: &n::PrivateDataSlot,
+    ) -> u64 {
+        object_private_data(object).get(slot)
+    }
+
     unsafe fn set_display_power_state(
         &self,
         _display: &display::Display<Backend>,
@@ -3552,9 +6114,17 @@ impl hal::device::Device<Backend> for Device {
     }
 
     fn start_capture(&self) {
-        let device = self.shared.device.lock();
         let shared_capture_manager = CaptureManager::shared();
-        let default_capture_scope = shared_capture_manager.new_capture_scope_with_device(&device);
+        let default_capture_scope = match self.capture_target {
+            CaptureTarget::Device => {
+                let device = &self.shared.device;
+                shared_capture_manager.new_capture_scope_with_device(&device)
+            }
+            CaptureTarget::Queue => {
+                let queue = self.shared.queue.lock();
+                shared_capture_manager.new_capture_scope_with_command_queue(&queue.raw)
+            }
+        };
         shared_capture_manager.set_default_capture_scope(&default_capture_scope);
         shared_capture_manager.start_capture_with_scope(&default_capture_scope);
         default_capture_scope.begin_scope();
@@ -3574,3 +6144,35 @@ fn test_send_sync() {
     fn foo<T: Send + Sync>() {}
     foo::<Device>()
 }
+
+#[test]
+fn test_order_raster_accesses() {
+    let source = "\
+vertex main1Output main1(
+  device Data& data [[buffer(0)]]
+) {}
+fragment main2Output main2(
+  main2Input varyings [[stage_in]]
+, device Data& data [[buffer(0)]]
+, metal::texture2d<float, metal::access::read_write> image [[texture(1)]]
+, metal::texture2d<float, metal::access::sample> color [[texture(2)]]
+) {}
+";
+    let slots = ["buffer(0)".to_string(), "texture(1)".to_string()];
+    let ordered = order_raster_accesses(source, "main2", &slots).unwrap();
+    assert_eq!(
+        ordered,
+        "\
+vertex main1Output main1(
+  device Data& data [[buffer(0)]]
+) {}
+fragment main2Output main2(
+  main2Input varyings [[stage_in]]
+, device Data& data [[buffer(0), raster_order_group(0)]]
+, metal::texture2d<float, metal::access::read_write> image [[texture(1), raster_order_group(0)]]
+, metal::texture2d<float, metal::access::sample> color [[texture(2)]]
+) {}
+"
+    );
+    assert_eq!(order_raster_accesses(source, "main1", &slots), None);
+}
@@ -0,0 +1,253 @@
+//! Compute dispatches with a base workgroup, which Metal has no equivalent for.
+//!
+//! The compute entry point is rewritten to read the base workgroup from a small
+//! buffer, set with each dispatch the same way push constants are, and to offset
+//! the workgroup and global invocation IDs by it before calling the original
+//! entry point. The other built-in inputs are passed through unchanged.
+
+use crate::ResourceIndex;
+
+use hal::device::NagaShader;
+use naga::{
+    back::msl, Arena, BinaryOperator as Bo, Binding, BuiltIn, Constant, ConstantInner, Expression,
+    Function, FunctionArgument, GlobalVariable, Handle, ResourceBinding, ScalarKind, ScalarValue,
+    Statement, StorageAccess, StorageClass, StructMember, Type, TypeInner, VectorSize,
+};
+
+use std::mem;
+
+/// Descriptor set index of the base buffer in the rewritten shader.
+const BASE_GROUP: u32 = !0;
+
+/// Rewrite the compute entry point of a shader to be dispatched with a base workgroup,
+/// and extend the MSL options with the binding of the base buffer at `slot`.
+pub(crate) fn transform(
+    shader: &NagaShader,
+    entry_point: &str,
+    options: &msl::Options,
+    slot: ResourceIndex,
+) -> Result<(NagaShader, msl::Options), String> {
+    let mut module = shader.module.clone();
+    rewrite(&mut module, entry_point)?;
+    let info = naga::valid::Validator::new(
+        naga::valid::ValidationFlags::empty(),
+        naga::valid::Capabilities::PUSH_CONSTANT,
+    )
+    .validate(&module)
+    .map_err(|e| format!("Naga validation of the based compute shader: {}", e))?;
+
+    let mut options = options.clone();
+    let source = msl::BindSource {
+        stage: naga::ShaderStage::Compute,
+        group: BASE_GROUP,
+        binding: 0,
+    };
+    let target = msl::BindTarget {
+        buffer: Some(slot as msl::Slot),
+        texture: None,
+        sampler: None,
+        mutable: false,
+    };
+    options.binding_map.insert(source, target);
+    Ok((NagaShader { module, info }, options))
+}
+
+fn rewrite(module: &mut naga::Module, entry_point: &str) -> Result<(), String> {
+    let ep_index = module
+        .entry_points
+        .iter()
+        .position(|ep| ep.stage == naga::ShaderStage::Compute && ep.name == entry_point)
+        .ok_or_else(|| format!("Compute entry point {} is not found", entry_point))?;
+
+    let uvec3_ty = module.types.fetch_or_append(Type {
+        name: None,
+        inner: TypeInner::Vector {
+            size: VectorSize::Tri,
+            kind: ScalarKind::Uint,
+            width: 4,
+        },
+    });
+    let buffer_ty = module.types.fetch_or_append(Type {
+        name: Some("DispatchBase".to_string()),
+        inner: TypeInner::Struct {
+            level: naga::StructLevel::Root,
+            members: vec![StructMember {
+                name: Some("workgroup".to_string()),
+                ty: uvec3_ty,
+                binding: None,
+                offset: 0,
+            }],
+            span: 16,
+        },
+    });
+    let global = module.global_variables.append(GlobalVariable {
+        name: Some("dispatch_base".to_string()),
+        class: StorageClass::Uniform,
+        binding: Some(ResourceBinding {
+            group: BASE_GROUP,
+            binding: 0,
+        }),
+        ty: buffer_ty,
+        init: None,
+        storage_access: StorageAccess::empty(),
+    });
+
+    let mut workgroup_size = Vec::with_capacity(3);
+    for &size in module.entry_points[ep_index].workgroup_size.iter() {
+        workgroup_size.push(module.constants.fetch_or_append(Constant {
+            name: None,
+            specialization: None,
+            inner: ConstantInner::Scalar {
+                width: 4,
+                value: ScalarValue::Uint(size as u64),
+            },
+        }));
+    }
+    let workgroup_size = module.constants.fetch_or_append(Constant {
+        name: None,
+        specialization: None,
+        inner: ConstantInner::Composite {
+            ty: uvec3_ty,
+            components: workgroup_size,
+        },
+    });
+
+    // Demote the entry point to a regular function, called by the new entry point.
+    let ep = &mut module.entry_points[ep_index];
+    let mut original = mem::take(&mut ep.function);
+    let inputs = original
+        .arguments
+        .iter_mut()
+        .map(|arg| (arg.binding.take(), arg.ty))
+        .collect::<Vec<_>>();
+    original.name = Some(format!("{}_unbased", entry_point));
+    let function = module.functions.append(original);
+
+    let mut builder = Builder {
+        arguments: Vec::new(),
+        expressions: Arena::new(),
+        body: Vec::new(),
+        global,
+        workgroup_size,
+        base: None,
+    };
+    let mut call_arguments = Vec::with_capacity(inputs.len());
+    for (binding, ty) in inputs {
+        let value = match binding {
+            Some(ref binding) => builder.input(binding, ty)?,
+            None => match module.types[ty].inner {
+                TypeInner::Struct { ref members, .. } => {
+                    let mut components = Vec::with_capacity(members.len());
+                    for member in members {
+                        let binding = member
+                            .binding
+                            .as_ref()
+                            .ok_or("Compute input member has no binding")?;
+                        components.push(builder.input(binding, member.ty)?);
+                    }
+                    builder.emit(Expression::Compose { ty, components })
+                }
+                _ => return Err("Compute input has no binding".to_string()),
+            },
+        };
+        call_arguments.push(value);
+    }
+    builder.body.push(Statement::Call {
+        function,
+        arguments: call_arguments,
+        result: None,
+    });
+    builder.body.push(Statement::Return { value: None });
+
+    module.entry_points[ep_index].function = Function {
+        name: Some(entry_point.to_string()),
+        arguments: builder.arguments,
+        result: None,
+        local_variables: Arena::new(),
+        expressions: builder.expressions,
+        body: builder.body,
+    };
+    Ok(())
+}
+
+/// Generator of the body of the based entry point.
+struct Builder {
+    arguments: Vec<FunctionArgument>,
+    expressions: Arena<Expression>,
+    body: Vec<Statement>,
+    global: Handle<GlobalVariable>,
+    workgroup_size: Handle<Constant>,
+    /// Base workgroup loaded from the buffer.
+    base: Option<Handle<Expression>>,
+}
+
+impl Builder {
+    /// Append an expression that needs to be evaluated in the body.
+    fn emit(&mut self, expression: Expression) -> Handle<Expression> {
+        let start = self.expressions.len();
+        let handle = self.expressions.append(expression);
+        self.body
+            .push(Statement::Emit(self.expressions.range_from(start)));
+        handle
+    }
+
+    fn base(&mut self) -> Handle<Expression> {
+        if let Some(base) = self.base {
+            return base;
+        }
+        let global = self
+            .expressions
+            .append(Expression::GlobalVariable(self.global));
+        let pointer = self.emit(Expression::AccessIndex {
+            base: global,
+            index: 0,
+        });
+        let base = self.emit(Expression::Load { pointer });
+        self.base = Some(base);
+        base
+    }
+
+    /// Produce the value of an input of the original entry point.
+    fn input(&mut self, binding: &Binding, ty: Handle<Type>) -> Result<Handle<Expression>, String> {
+        let builtin = match *binding {
+            Binding::BuiltIn(builtin) => builtin,
+            Binding::Location { .. } => return Err("Compute input has a location".to_string()),
+        };
+        self.arguments.push(FunctionArgument {
+            name: None,
+            ty,
+            binding: Some(binding.clone()),
+        });
+        let value = self.expressions.append(Expression::FunctionArgument(
+            self.arguments.len() as u32 - 1,
+        ));
+
+        Ok(match builtin {
+            BuiltIn::WorkGroupId => {
+                let base = self.base();
+                self.emit(Expression::Binary {
+                    op: Bo::Add,
+                    left: value,
+                    right: base,
+                })
+            }
+            BuiltIn::GlobalInvocationId => {
+                let base = self.base();
+                let size = self
+                    .expressions
+                    .append(Expression::Constant(self.workgroup_size));
+                let offset = self.emit(Expression::Binary {
+                    op: Bo::Multiply,
+                    left: base,
+                    right: size,
+                });
+                self.emit(Expression::Binary {
+                    op: Bo::Add,
+                    left: value,
+                    right: offset,
+                })
+            }
+            _ => value,
+        })
+    }
+}
@@ -0,0 +1,155 @@
+//! Display configuration change notifications on macOS.
+//!
+//! Core Graphics reports display changes through reconfiguration callbacks,
+//! which are collected here so that applications can poll them and re-create
+//! their swapchains before presentation starts failing.
+
+use parking_lot::Mutex;
+
+use std::os::raw::c_void;
+
+type CGDirectDisplayID = u32;
+type CGDisplayChangeSummaryFlags = u32;
+type CGDisplayModeRef = *mut c_void;
+type CGError = i32;
+type ReconfigurationCallback =
+    extern "C" fn(CGDirectDisplayID, CGDisplayChangeSummaryFlags, *mut c_void);
+
+const BEGIN_CONFIGURATION_FLAG: CGDisplayChangeSummaryFlags = 1 << 0;
+const SET_MODE_FLAG: CGDisplayChangeSummaryFlags = 1 << 3;
+const ADD_FLAG: CGDisplayChangeSummaryFlags = 1 << 4;
+const REMOVE_FLAG: CGDisplayChangeSummaryFlags = 1 << 5;
+const ENABLED_FLAG: CGDisplayChangeSummaryFlags = 1 << 8;
+const DISABLED_FLAG: CGDisplayChangeSummaryFlags = 1 << 9;
+
+#[link(name = "CoreGraphics", kind = "framework")]
+extern "C" {
+    fn CGDisplayRegisterReconfigurationCallback(
+        callback: ReconfigurationCallback,
+        user_info: *mut c_void,
+    ) -> CGError;
+    fn CGDisplayRemoveReconfigurationCallback(
+        callback: ReconfigurationCallback,
+        user_info: *mut c_void,
+    ) -> CGError;
+    fn CGDisplayCopyDisplayMode(display: CGDirectDisplayID) -> CGDisplayModeRef;
+    fn CGDisplayModeGetPixelWidth(mode: CGDisplayModeRef) -> usize;
+    fn CGDisplayModeGetPixelHeight(mode: CGDisplayModeRef) -> usize;
+    fn CGDisplayModeGetRefreshRate(mode: CGDisplayModeRef) -> f64;
+    fn CGDisplayModeRelease(mode: CGDisplayModeRef);
+}
+
+/// Kind of a display configuration change.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DisplayEventKind {
+    /// The display was connected or enabled.
+    Connected,
+    /// The display was disconnected or disabled.
+    Disconnected,
+    /// The display switched to a new mode.
+    ModeChanged {
+        width: u32,
+        height: u32,
+        /// Refresh rate in Hz, zero if the display doesn't report one.
+        refresh_rate: f64,
+    },
+}
+
+/// Configuration change of a single display.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DisplayEvent {
+    /// Core Graphics identifier of the display.
+    pub display: u32,
+    pub kind: DisplayEventKind,
+}
+
+/// Receiver of display connection and mode change events.
+///
+/// Core Graphics only delivers the notifications while the main run loop of
+/// the application is running.
+#[derive(Debug)]
+pub struct DisplayMonitor {
+    events: Box<Mutex<Vec<DisplayEvent>>>,
+}
+
+impl DisplayMonitor {
+    /// Start listening to display reconfigurations, returns `None` if the
+    /// callback could not be registered.
+    pub fn new() -> Option<Self> {
+        let events = Box::new(Mutex::new(Vec::new()));
+        let error = unsafe {
+            CGDisplayRegisterReconfigurationCallback(reconfiguration_callback, user_info(&events))
+        };
+        if error != 0 {
+            error!(
+                "Unable to register display reconfiguration callback: {}",
+                error
+            );
+            return None;
+        }
+        Some(DisplayMonitor { events })
+    }
+
+    /// Take all the events received since the last call.
+    pub fn poll_events(&self) -> Vec<DisplayEvent> {
+        self.events.lock().drain(..).collect()
+    }
+}
+
+impl Drop for DisplayMonitor {
+    fn drop(&mut self) {
+        unsafe {
+            CGDisplayRemoveReconfigurationCallback(
+                reconfiguration_callback,
+                user_info(&self.events),
+            );
+        }
+    }
+}
+
+fn user_info(events: &Mutex<Vec<DisplayEvent>>) -> *mut c_void {
+    events as *const Mutex<_> as *mut c_void
+}
+
+fn display_mode(display: CGDirectDisplayID) -> Option<DisplayEventKind> {
+    unsafe {
+        let mode = CGDisplayCopyDisplayMode(display);
+        if mode.is_null() {
+            return None;
+        }
+        let kind = DisplayEventKind::ModeChanged {
+            width: CGDisplayModeGetPixelWidth(mode) as u32,
+            height: CGDisplayModeGetPixelHeight(mode) as u32,
+            refresh_rate: CGDisplayModeGetRefreshRate(mode),
+        };
+        CGDisplayModeRelease(mode);
+        Some(kind)
+    }
+}
+
+extern "C" fn reconfiguration_callback(
+    display: CGDirectDisplayID,
+    flags: CGDisplayChangeSummaryFlags,
+    user_info: *mut c_void,
+) {
+    // The callback is invoked both before and after the change, only the
+    // latter carries the summary of what happened.
+    if flags & BEGIN_CONFIGURATION_FLAG != 0 {
+        return;
+    }
+    let kind = if flags & (REMOVE_FLAG | DISABLED_FLAG) != 0 {
+        DisplayEventKind::Disconnected
+    } else if flags & (ADD_FLAG | ENABLED_FLAG) != 0 {
+        DisplayEventKind::Connected
+    } else if flags & SET_MODE_FLAG != 0 {
+        match display_mode(display) {
+            Some(kind) => kind,
+            None => return,
+        }
+    } else {
+        return;
+    };
+
+    let events = unsafe { &*(user_info as *const Mutex<Vec<DisplayEvent>>) };
+    events.lock().push(DisplayEvent { display, kind });
+}
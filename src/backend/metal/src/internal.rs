@@ -1,9 +1,13 @@
-use crate::{conversions as conv, FastHashMap, PrivateCapabilities, MAX_COLOR_ATTACHMENTS};
+use crate::{
+    conversions as conv, decompress::BlockFormat, FastHashMap, PrivateCapabilities,
+    MAX_COLOR_ATTACHMENTS,
+};
 
 use hal::{
     command::ClearColor,
     format::{Aspects, ChannelType},
     image::{Filter, NumSamples},
+    pass::ResolveMode,
     pso,
 };
 
@@ -195,11 +199,11 @@ impl DepthStencilStates {
     pub fn get(
         &self,
         desc: pso::DepthStencilDesc,
-        device: &Mutex<metal::Device>,
+        device: &metal::DeviceRef,
     ) -> FastStorageGuard<metal::DepthStencilState> {
         self.map.get_or_create_with(&desc, || {
             let raw_desc = Self::create_desc(&desc).expect("Incomplete descriptor provided");
-            device.lock().new_depth_stencil_state(&raw_desc)
+            device.new_depth_stencil_state(&raw_desc)
         })
     }
 
@@ -272,11 +276,11 @@ impl ImageClearPipes {
         &self,
         key: ClearKey,
         library: &Mutex<metal::Library>,
-        device: &Mutex<metal::Device>,
+        device: &metal::DeviceRef,
         private_caps: &PrivateCapabilities,
     ) -> FastStorageGuard<metal::RenderPipelineState> {
         self.map.get_or_create_with(&key, || {
-            Self::create(key, &*library.lock(), &*device.lock(), private_caps)
+            Self::create(key, &*library.lock(), device, private_caps)
         })
     }
 
@@ -361,11 +365,11 @@ impl ImageBlitPipes {
         &self,
         key: BlitKey,
         library: &Mutex<metal::Library>,
-        device: &Mutex<metal::Device>,
+        device: &metal::DeviceRef,
         private_caps: &PrivateCapabilities,
     ) -> FastStorageGuard<metal::RenderPipelineState> {
         self.map.get_or_create_with(&key, || {
-            Self::create(key, &*library.lock(), &*device.lock(), private_caps)
+            Self::create(key, &*library.lock(), device, private_caps)
         })
     }
 
@@ -440,6 +444,90 @@ impl ImageBlitPipes {
     }
 }
 
+pub type DepthResolveKey = (metal::MTLPixelFormat, Aspects, ResolveMode);
+
+/// Pipelines resolving depth attachments with a draw, used when the render pass
+/// can't do it natively with the requested filter.
+#[derive(Debug)]
+pub struct DepthResolvePipes {
+    library: Mutex<Option<metal::Library>>,
+    map: FastStorageMap<DepthResolveKey, metal::RenderPipelineState>,
+}
+
+impl DepthResolvePipes {
+    pub(crate) fn get(
+        &self,
+        key: DepthResolveKey,
+        device: &metal::DeviceRef,
+    ) -> FastStorageGuard<metal::RenderPipelineState> {
+        self.map.get_or_create_with(&key, || {
+            let mut library = self.library.lock();
+            let library = library.get_or_insert_with(|| {
+                let source = include_str!("./../shaders/resolve.msl");
+                device
+                    .new_library_with_source(source, &metal::CompileOptions::new())
+                    .unwrap()
+            });
+            Self::create(key, library, device)
+        })
+    }
+
+    fn create(
+        key: DepthResolveKey,
+        library: &metal::LibraryRef,
+        device: &metal::DeviceRef,
+    ) -> metal::RenderPipelineState {
+        let pipeline = metal::RenderPipelineDescriptor::new();
+
+        let ps_name = match key.2 {
+            ResolveMode::SampleZero => "ps_resolve_depth_sample_zero",
+            ResolveMode::Min => "ps_resolve_depth_min",
+            ResolveMode::Max => "ps_resolve_depth_max",
+        };
+        let vs_resolve = library.get_function("vs_resolve", None).unwrap();
+        let ps_resolve = library.get_function(ps_name, None).unwrap();
+        pipeline.set_vertex_function(Some(&vs_resolve));
+        pipeline.set_fragment_function(Some(&ps_resolve));
+
+        pipeline.set_depth_attachment_pixel_format(key.0);
+        if key.1.contains(Aspects::STENCIL) {
+            pipeline.set_stencil_attachment_pixel_format(key.0);
+        }
+
+        device.new_render_pipeline_state(&pipeline).unwrap()
+    }
+}
+
+/// Pipelines decoding block-compressed data into the uncompressed backing
+/// texture of an image, for the devices without native support of the format.
+#[derive(Debug)]
+pub struct DecompressPipes {
+    library: Mutex<Option<metal::Library>>,
+    map: FastStorageMap<BlockFormat, metal::ComputePipelineState>,
+}
+
+impl DecompressPipes {
+    pub(crate) fn get(
+        &self,
+        format: BlockFormat,
+        device: &metal::DeviceRef,
+    ) -> FastStorageGuard<metal::ComputePipelineState> {
+        self.map.get_or_create_with(&format, || {
+            let mut library = self.library.lock();
+            let library = library.get_or_insert_with(|| {
+                let source = include_str!("./../shaders/decompress.msl");
+                device
+                    .new_library_with_source(source, &metal::CompileOptions::new())
+                    .unwrap()
+            });
+            let pipeline = metal::ComputePipelineDescriptor::new();
+            let cs_decompress = library.get_function(format.function_name(), None).unwrap();
+            pipeline.set_compute_function(Some(&cs_decompress));
+            device.new_compute_pipeline_state(&pipeline).unwrap()
+        })
+    }
+}
+
 #[derive(Debug)]
 pub struct ServicePipes {
     pub library: Mutex<metal::Library>,
@@ -447,6 +535,8 @@ pub struct ServicePipes {
     pub depth_stencil_states: DepthStencilStates,
     pub clears: ImageClearPipes,
     pub blits: ImageBlitPipes,
+    pub depth_resolves: DepthResolvePipes,
+    pub decompress: DecompressPipes,
     pub copy_buffer: metal::ComputePipelineState,
     pub fill_buffer: metal::ComputePipelineState,
 }
@@ -475,6 +565,14 @@ impl ServicePipes {
             blits: ImageBlitPipes {
                 map: FastStorageMap::default(),
             },
+            depth_resolves: DepthResolvePipes {
+                library: Mutex::new(None),
+                map: FastStorageMap::default(),
+            },
+            decompress: DecompressPipes {
+                library: Mutex::new(None),
+                map: FastStorageMap::default(),
+            },
             copy_buffer,
             fill_buffer,
         }
@@ -47,8 +47,11 @@ fired in one command buffer and waited in another one within the same submission
 
 Waiting for an event from a different submission is accomplished similar to waiting
 for the host. We block all the submissions until the host blockers are resolved, and
-these are checked at certain points like setting an event by the device, or waiting
-for a fence.
+these are checked at certain points like setting an event by the host, or completing
+a submission that signals a fence.
+
+Fences are signalled from the completion handler of the last command buffer in a
+submission, which wakes up any thread waiting on them without polling.
 !*/
 
 #[macro_use]
@@ -59,12 +62,12 @@ extern crate objc;
 extern crate log;
 
 use hal::{
-    adapter::{Adapter, AdapterInfo, DeviceType},
+    adapter::{Adapter, AdapterInfo, DeviceGroup, DeviceType},
     queue::{QueueFamilyId, QueueType},
 };
 use range_alloc::RangeAllocator;
 
-use cocoa_foundation::foundation::NSInteger;
+use cocoa_foundation::foundation::{NSInteger, NSUInteger};
 #[cfg(feature = "dispatch")]
 use dispatch;
 use foreign_types::ForeignTypeRef;
@@ -84,22 +87,39 @@ use std::{
     mem,
     os::raw::c_void,
     ptr::NonNull,
-    sync::{Arc, Once},
+    sync::{Arc, Once, Weak},
 };
 
 mod command;
+mod compiler;
 mod conversions;
+mod counters;
+mod decompress;
 mod device;
+mod dispatch_base;
+#[cfg(target_os = "macos")]
+mod display;
 mod internal;
 mod native;
+#[cfg(target_os = "macos")]
+mod observer;
 #[cfg(feature = "pipeline-cache")]
 mod pipeline_cache;
+mod pulling;
 mod soft;
+mod timing;
 mod window;
-
-pub use crate::command::CommandPool;
-pub use crate::device::{Device, LanguageVersion, PhysicalDevice};
-pub use crate::window::Surface;
+mod ycbcr;
+
+pub use crate::command::{CommandBufferStats, CommandPool};
+pub use crate::device::{Device, DeviceProperties, LanguageVersion, PhysicalDevice};
+#[cfg(target_os = "macos")]
+pub use crate::display::{DisplayEvent, DisplayEventKind, DisplayMonitor};
+pub use crate::native::{TileLayout, TilePipeline, TilePipelineDesc, TranslationReport};
+#[cfg(target_os = "macos")]
+pub use crate::observer::{DeviceEvent, DeviceEventKind, DeviceObserver};
+pub use crate::timing::{PassKind, PassTiming};
+pub use crate::window::{Hdr10Metadata, Surface, ToneMapping};
 
 pub type GraphicsCommandPool = CommandPool;
 type FastHashMap<K, V> = HashMap<K, V, BuildHasherDefault<fxhash::FxHasher>>;
@@ -143,6 +163,30 @@ impl CGRect {
     }
 }
 
+/// Work captured by `Device::start_capture`.
+#[derive(Clone, Copy, Debug, Hash, PartialEq)]
+pub enum CaptureTarget {
+    /// Capture the work of all the queues of the device.
+    Device,
+    /// Capture only the work submitted to the queue of the device.
+    Queue,
+}
+
+impl Default for CaptureTarget {
+    fn default() -> Self {
+        CaptureTarget::Device
+    }
+}
+
+/// Translator of the shaders from SPIR-V to MSL, see `Device::shader_translator`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ShaderTranslator {
+    /// Translate with naga.
+    Naga,
+    /// Translate with SPIRV-Cross, requires the `cross` feature.
+    SpirvCross,
+}
+
 /// Method of recording one-time-submit command buffers.
 #[derive(Clone, Debug, Hash, PartialEq)]
 pub enum OnlineRecording {
@@ -196,13 +240,21 @@ struct VisibilityShared {
 
 #[derive(Debug)]
 struct Shared {
-    device: Mutex<metal::Device>,
+    /// `MTLDevice` is thread-safe, so it's used without any locking.
+    device: metal::Device,
     queue: Mutex<command::QueueInner>,
     queue_blocker: Mutex<command::QueueBlocker>,
     service_pipes: internal::ServicePipes,
     disabilities: PrivateDisabilities,
     private_caps: PrivateCapabilities,
     visibility: VisibilityShared,
+    /// Texel buffer views that are mirrored from shared memory.
+    texel_mirrors: Mutex<Vec<Weak<native::TexelMirror>>>,
+    /// Buffers that shaders can access through their addresses, without binding them,
+    /// so they are made resident in every pass.
+    addressed_buffers: Mutex<Vec<metal::Buffer>>,
+    #[cfg(target_os = "macos")]
+    removal: Option<observer::RemovalWatch>,
 }
 
 unsafe impl Send for Shared {}
@@ -226,6 +278,18 @@ impl Shared {
                 as hal::buffer::Offset,
             condvar: Condvar::new(),
         };
+        #[cfg(target_os = "macos")]
+        let removal = if private_caps.has_version_at_least(10, 13) {
+            let is_removable: BOOL = unsafe { msg_send![device, isRemovable] };
+            if is_removable == YES {
+                let registry_id: u64 = unsafe { msg_send![device, registryID] };
+                Some(observer::RemovalWatch::new(registry_id))
+            } else {
+                None
+            }
+        } else {
+            None
+        };
         Shared {
             queue: Mutex::new(command::QueueInner::new(
                 &device,
@@ -239,8 +303,40 @@ impl Shared {
                 broken_layered_clear_image: device.name().starts_with("Intel"),
             },
             private_caps,
-            device: Mutex::new(device),
+            device,
             visibility,
+            texel_mirrors: Mutex::new(Vec::new()),
+            addressed_buffers: Mutex::new(Vec::new()),
+            #[cfg(target_os = "macos")]
+            removal,
+        }
+    }
+
+    /// Return true if the device has been unplugged.
+    fn is_removed(&self) -> bool {
+        #[cfg(target_os = "macos")]
+        {
+            self.removal
+                .as_ref()
+                .map_or(false, observer::RemovalWatch::is_removed)
+        }
+        #[cfg(not(target_os = "macos"))]
+        {
+            false
+        }
+    }
+
+    /// Return true if the system asked to stop using the device before unplugging it.
+    fn is_removal_requested(&self) -> bool {
+        #[cfg(target_os = "macos")]
+        {
+            self.removal
+                .as_ref()
+                .map_or(false, observer::RemovalWatch::is_removal_requested)
+        }
+        #[cfg(not(target_os = "macos"))]
+        {
+            false
         }
     }
 }
@@ -248,11 +344,30 @@ impl Shared {
 #[derive(Clone, Debug, Default)]
 pub struct Experiments {
     pub argument_buffers: bool,
+    /// Decompress BC1-BC5 images on upload when they are not supported natively.
+    pub bc_decompression: bool,
+}
+
+/// Preferred kind of GPU, which determines the order of the enumerated adapters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PowerPreference {
+    /// Prefer discrete GPUs over integrated ones.
+    HighPerformance,
+    /// Prefer integrated GPUs, which don't force the system to switch
+    /// to the discrete GPU on machines with automatic graphics switching.
+    LowPower,
+}
+
+impl Default for PowerPreference {
+    fn default() -> Self {
+        PowerPreference::HighPerformance
+    }
 }
 
 #[derive(Debug)]
 pub struct Instance {
     pub experiments: Experiments,
+    pub power_preference: PowerPreference,
     gfx_managed_metal_layer_delegate: GfxManagedMetalLayerDelegate,
 }
 
@@ -260,6 +375,7 @@ impl hal::Instance<Backend> for Instance {
     fn create(_: &str, _: u32) -> Result<Self, hal::UnsupportedBackend> {
         Ok(Instance {
             experiments: Experiments::default(),
+            power_preference: PowerPreference::default(),
             gfx_managed_metal_layer_delegate: GfxManagedMetalLayerDelegate::new(),
         })
     }
@@ -268,35 +384,45 @@ impl hal::Instance<Backend> for Instance {
         let devices = metal::Device::all();
         let mut adapters: Vec<Adapter<Backend>> = devices
             .into_iter()
-            .map(|dev| {
-                let name = dev.name().into();
-                let shared = Shared::new(dev, &self.experiments);
-                let physical_device = device::PhysicalDevice::new(Arc::new(shared));
-                Adapter {
-                    info: AdapterInfo {
-                        name,
-                        vendor: 0,
-                        device: 0,
-                        device_type: if physical_device.shared.private_caps.low_power {
-                            DeviceType::IntegratedGpu
-                        } else {
-                            DeviceType::DiscreteGpu
-                        },
-                    },
-                    physical_device,
-                    queue_families: vec![QueueFamily {}],
-                }
-            })
+            .map(|dev| self.create_adapter(dev))
             .collect();
+        let prefer_low_power = self.power_preference == PowerPreference::LowPower;
         adapters.sort_by_key(|adapt| {
             (
-                adapt.physical_device.shared.private_caps.low_power,
+                adapt.physical_device.shared.private_caps.low_power != prefer_low_power,
                 adapt.physical_device.shared.private_caps.headless,
             )
         });
         adapters
     }
 
+    fn enumerate_device_groups(&self) -> Vec<DeviceGroup<Backend>> {
+        // The GPUs of a peer group, like the ones of a Mac Pro linked with Infinity Fabric,
+        // are grouped together in the order of their peer indices.
+        let mut groups: Vec<(u64, Vec<Adapter<Backend>>)> = Vec::new();
+        for adapter in self.enumerate_adapters() {
+            let peer_group_id = adapter.physical_device.shared.private_caps.peer_group_id;
+            match groups
+                .iter_mut()
+                .find(|&&mut (id, _)| id != 0 && id == peer_group_id)
+            {
+                Some(&mut (_, ref mut adapters)) => adapters.push(adapter),
+                None => groups.push((peer_group_id, vec![adapter])),
+            }
+        }
+        groups
+            .into_iter()
+            .map(|(_, mut adapters)| {
+                adapters
+                    .sort_by_key(|adapter| adapter.physical_device.shared.private_caps.peer_index);
+                DeviceGroup {
+                    adapters,
+                    subset_allocation: false,
+                }
+            })
+            .collect()
+    }
+
     unsafe fn create_surface(
         &self,
         has_handle: &impl raw_window_handle::HasRawWindowHandle,
@@ -465,6 +591,31 @@ impl Instance {
     pub fn create_surface_from_uiview(&self, uiview: *mut c_void) -> Surface {
         unsafe { self.create_from_uiview(uiview) }
     }
+
+    /// Create an adapter from an existing Metal device, for example the one used
+    /// by a host application, instead of enumerating the system devices.
+    ///
+    /// Resources created through the adapter belong to the same device,
+    /// so they can be shared with the host.
+    pub fn create_adapter(&self, device: metal::Device) -> Adapter<Backend> {
+        let name = device.name().into();
+        let shared = Shared::new(device, &self.experiments);
+        let physical_device = device::PhysicalDevice::new(Arc::new(shared));
+        Adapter {
+            info: AdapterInfo {
+                name,
+                vendor: 0,
+                device: 0,
+                device_type: if physical_device.shared.private_caps.low_power {
+                    DeviceType::IntegratedGpu
+                } else {
+                    DeviceType::DiscreteGpu
+                },
+            },
+            physical_device,
+            queue_families: vec![QueueFamily {}],
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
@@ -491,9 +642,12 @@ impl hal::Backend for Backend {
     type Image = native::Image;
     type ImageView = native::ImageView;
     type Sampler = native::Sampler;
+    type AccelerationStructure = ();
 
     type ComputePipeline = native::ComputePipeline;
+    type RayTracingPipeline = ();
     type GraphicsPipeline = native::GraphicsPipeline;
+    type GraphicsPipelineLibrary = native::GraphicsPipelineLibrary;
     type PipelineCache = native::PipelineCache;
     type PipelineLayout = native::PipelineLayout;
     type DescriptorSetLayout = native::DescriptorSetLayout;
@@ -502,8 +656,10 @@ impl hal::Backend for Backend {
 
     type Fence = native::Fence;
     type Semaphore = native::Semaphore;
+    type TimelineSemaphore = native::TimelineSemaphore;
     type Event = native::Event;
     type QueryPool = native::QueryPool;
+    type PrivateDataSlot = native::PrivateDataSlot;
 
     type Display = ();
     type DisplayMode = ();
@@ -674,6 +830,24 @@ const LAYERED_RENDERING_SUPPORT: &[MTLFeatureSet] = &[
     MTLFeatureSet::macOS_GPUFamily2_v1,
 ];
 
+const DEPTH_RESOLVE_SUPPORT: &[MTLFeatureSet] = &[
+    MTLFeatureSet::iOS_GPUFamily3_v1,
+    MTLFeatureSet::iOS_GPUFamily4_v1,
+    MTLFeatureSet::iOS_GPUFamily5_v1,
+    MTLFeatureSet::tvOS_GPUFamily2_v1,
+    MTLFeatureSet::macOS_GPUFamily1_v4,
+    MTLFeatureSet::macOS_GPUFamily2_v1,
+];
+
+const DEPTH_STENCIL_RESOLVE_FILTER_SUPPORT: &[MTLFeatureSet] = &[
+    MTLFeatureSet::iOS_GPUFamily3_v4,
+    MTLFeatureSet::iOS_GPUFamily4_v2,
+    MTLFeatureSet::iOS_GPUFamily5_v1,
+    MTLFeatureSet::tvOS_GPUFamily2_v2,
+    MTLFeatureSet::macOS_GPUFamily1_v4,
+    MTLFeatureSet::macOS_GPUFamily2_v1,
+];
+
 const FUNCTION_SPECIALIZATION_SUPPORT: &[MTLFeatureSet] = &[
     MTLFeatureSet::iOS_GPUFamily1_v3,
     MTLFeatureSet::iOS_GPUFamily2_v3,
@@ -704,6 +878,8 @@ struct PrivateCapabilities {
     expose_line_mode: bool,
     resource_heaps: bool,
     argument_buffers: bool,
+    /// Argument buffers can hold large arrays of resources that are indexed dynamically.
+    argument_buffers_tier2: bool,
     shared_textures: bool,
     mutable_comparison_samplers: bool,
     sampler_clamp_to_border: bool,
@@ -713,6 +889,26 @@ struct PrivateCapabilities {
     low_power: bool,
     headless: bool,
     layered_rendering: bool,
+    /// Multisampled depth can be resolved by the render pass, using the sample zero.
+    depth_resolve: bool,
+    /// Depth can be resolved with min/max filters, and stencil can be resolved too.
+    depth_stencil_resolve_filter: bool,
+    /// Tile functions can be dispatched within render passes, with imageblocks in tile memory.
+    tile_shaders: bool,
+    /// Fragment shader accesses to resources can be ordered with raster order groups.
+    raster_order_groups: bool,
+    /// The locations of the samples can be set on the render passes.
+    programmable_sample_positions: bool,
+    /// Number of threads executed together in a SIMD-group.
+    simd_group_size: u32,
+    /// SIMD-group functions (vote, ballot, shuffle and reductions) are supported.
+    simd_group_operations: bool,
+    /// Quad-group functions are supported.
+    quad_group_operations: bool,
+    /// Identifier of the peer group of the device, zero if it doesn't belong to one.
+    peer_group_id: u64,
+    /// Index of the device within its peer group.
+    peer_index: u32,
     function_specialization: bool,
     depth_clip_mode: bool,
     texture_cube_array: bool,
@@ -722,6 +918,7 @@ struct PrivateCapabilities {
     format_min_srgb_channels: u8,
     format_b5: bool,
     format_bc: bool,
+    format_bc_decompression: bool,
     format_eac_etc: bool,
     format_astc: bool,
     format_any8_unorm_srgb_all: bool,
@@ -773,6 +970,14 @@ struct PrivateCapabilities {
     sample_count_mask: u8,
     supports_debug_markers: bool,
     supports_binary_archives: bool,
+    supports_counter_sampling: bool,
+    /// `MTLSharedEvent` is available for synchronizing with other devices and processes.
+    shared_events: bool,
+    texture_swizzle: bool,
+    /// Buffers expose their `gpuAddress`, which shaders can dereference.
+    buffer_gpu_address: bool,
+    /// Textures can opt out of lossless compression with `allowGPUOptimizedContents`.
+    gpu_optimized_contents: bool,
 }
 
 impl PrivateCapabilities {
@@ -787,6 +992,22 @@ impl PrivateCapabilities {
             .any(|x| raw.supports_feature_set(x))
     }
 
+    /// Query the SIMD-group size as the execution width of a trivial compute pipeline.
+    fn query_simd_group_size(raw: &metal::DeviceRef) -> u32 {
+        const SOURCE: &str = "kernel void cs_simd_group_size() {}";
+        let pipeline = raw
+            .new_library_with_source(SOURCE, &metal::CompileOptions::new())
+            .and_then(|library| library.get_function("cs_simd_group_size", None))
+            .and_then(|function| raw.new_compute_pipeline_state_with_function(&function));
+        match pipeline {
+            Ok(pipeline) => pipeline.thread_execution_width() as u32,
+            Err(err) => {
+                warn!("Unable to query the SIMD-group size: {}", err);
+                32
+            }
+        }
+    }
+
     fn new(device: &metal::Device, experiments: &Experiments) -> Self {
         #[repr(C)]
         #[derive(Clone, Copy, Debug)]
@@ -804,6 +1025,36 @@ impl PrivateCapabilities {
         let major = version.major as u32;
         let minor = version.minor as u32;
         let os_is_mac = device.supports_feature_set(MTLFeatureSet::macOS_GPUFamily1_v1);
+        // GPU families can only be queried on macOS 10.15+ and iOS 13+,
+        // which is how Apple GPUs are detected on macOS.
+        let supports_gpu_families = if os_is_mac {
+            Self::version_at_least(major, minor, 10, 15)
+        } else {
+            Self::version_at_least(major, minor, 13, 0)
+        };
+        let apple_family = |family| supports_gpu_families && device.supports_family(family);
+        let argument_buffers =
+            experiments.argument_buffers && Self::supports_any(&device, ARGUMENT_BUFFER_SUPPORT);
+
+        let raster_order_groups_supported = if os_is_mac {
+            Self::version_at_least(major, minor, 10, 13)
+        } else {
+            Self::version_at_least(major, minor, 11, 0)
+        } && {
+            let supported: BOOL =
+                unsafe { msg_send![device.as_ref(), areRasterOrderGroupsSupported] };
+            supported == YES
+        };
+
+        let programmable_sample_positions_supported = if os_is_mac {
+            Self::version_at_least(major, minor, 10, 13)
+        } else {
+            Self::version_at_least(major, minor, 11, 0)
+        } && {
+            let supported: BOOL =
+                unsafe { msg_send![device.as_ref(), areProgrammableSamplePositionsSupported] };
+            supported == YES
+        };
 
         let mut sample_count_mask: u8 = 1 | 4; // 1 and 4 samples are supported on all devices
         if device.supports_texture_sample_count(2) {
@@ -847,8 +1098,13 @@ impl PrivateCapabilities {
             read_write_texture_tier: device.read_write_texture_support(),
             expose_line_mode: true,
             resource_heaps: Self::supports_any(&device, RESOURCE_HEAP_SUPPORT),
-            argument_buffers: experiments.argument_buffers
-                && Self::supports_any(&device, ARGUMENT_BUFFER_SUPPORT),
+            argument_buffers,
+            argument_buffers_tier2: argument_buffers
+                && unsafe {
+                    // MTLArgumentBuffersTier2
+                    let tier: NSUInteger = msg_send![device.as_ref(), argumentBuffersSupport];
+                    tier == 1
+                },
             shared_textures: !os_is_mac,
             mutable_comparison_samplers: Self::supports_any(
                 &device,
@@ -861,6 +1117,35 @@ impl PrivateCapabilities {
             low_power: !os_is_mac || device.is_low_power(),
             headless: os_is_mac && device.is_headless(),
             layered_rendering: Self::supports_any(&device, LAYERED_RENDERING_SUPPORT),
+            depth_resolve: Self::supports_any(&device, DEPTH_RESOLVE_SUPPORT),
+            depth_stencil_resolve_filter: Self::supports_any(
+                &device,
+                DEPTH_STENCIL_RESOLVE_FILTER_SUPPORT,
+            ),
+            tile_shaders: apple_family(MTLGPUFamily::Apple4),
+            raster_order_groups: raster_order_groups_supported,
+            programmable_sample_positions: programmable_sample_positions_supported,
+            simd_group_size: Self::query_simd_group_size(&device),
+            simd_group_operations: if os_is_mac {
+                Self::version_at_least(major, minor, 10, 13)
+            } else {
+                apple_family(MTLGPUFamily::Apple6)
+            },
+            quad_group_operations: if os_is_mac {
+                Self::version_at_least(major, minor, 10, 13)
+            } else {
+                Self::supports_any(&device, &[MTLFeatureSet::iOS_GPUFamily4_v1])
+            },
+            peer_group_id: if os_is_mac && Self::version_at_least(major, minor, 10, 15) {
+                unsafe { msg_send![device.as_ref(), peerGroupID] }
+            } else {
+                0
+            },
+            peer_index: if os_is_mac && Self::version_at_least(major, minor, 10, 15) {
+                unsafe { msg_send![device.as_ref(), peerIndex] }
+            } else {
+                0
+            },
             function_specialization: Self::supports_any(&device, FUNCTION_SPECIALIZATION_SUPPORT),
             depth_clip_mode: Self::supports_any(&device, DEPTH_CLIP_MODE),
             texture_cube_array: Self::supports_any(&device, TEXTURE_CUBE_ARRAY_SUPPORT),
@@ -870,8 +1155,10 @@ impl PrivateCapabilities {
             format_min_srgb_channels: if os_is_mac { 4 } else { 1 },
             format_b5: !os_is_mac,
             format_bc: os_is_mac,
-            format_eac_etc: !os_is_mac,
-            format_astc: Self::supports_any(&device, ASTC_PIXEL_FORMAT_FEATURES),
+            format_bc_decompression: !os_is_mac && experiments.bc_decompression,
+            format_eac_etc: !os_is_mac || apple_family(MTLGPUFamily::Apple1),
+            format_astc: Self::supports_any(&device, ASTC_PIXEL_FORMAT_FEATURES)
+                || apple_family(MTLGPUFamily::Apple2),
             format_any8_unorm_srgb_all: Self::supports_any(&device, ANY8_UNORM_SRGB_ALL),
             format_any8_unorm_srgb_no_write: !Self::supports_any(&device, ANY8_UNORM_SRGB_ALL)
                 && !os_is_mac,
@@ -1072,6 +1359,36 @@ impl PrivateCapabilities {
             supports_binary_archives: cfg!(feature = "pipeline-cache")
                 && (device.supports_family(MTLGPUFamily::Apple3)
                     || device.supports_family(MTLGPUFamily::Mac1)),
+            supports_counter_sampling: if os_is_mac {
+                Self::version_at_least(major, minor, 11, 0)
+            } else {
+                Self::version_at_least(major, minor, 14, 0)
+            } && unsafe {
+                // MTLCounterSamplingPointAtStageBoundary
+                let supported: BOOL =
+                    msg_send![device.as_ref(), supportsCounterSampling: 0 as NSUInteger];
+                supported == YES
+            },
+            shared_events: if os_is_mac {
+                Self::version_at_least(major, minor, 10, 14)
+            } else {
+                Self::version_at_least(major, minor, 12, 0)
+            },
+            texture_swizzle: if os_is_mac {
+                Self::version_at_least(major, minor, 10, 15)
+            } else {
+                Self::version_at_least(major, minor, 13, 0)
+            },
+            buffer_gpu_address: if os_is_mac {
+                Self::version_at_least(major, minor, 13, 0)
+            } else {
+                Self::version_at_least(major, minor, 16, 0)
+            },
+            gpu_optimized_contents: if os_is_mac {
+                Self::version_at_least(major, minor, 10, 15)
+            } else {
+                Self::version_at_least(major, minor, 13, 0)
+            },
         }
     }
 
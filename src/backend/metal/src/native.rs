@@ -1,27 +1,37 @@
 use crate::{
-    internal::Channel, Backend, BufferPtr, FastHashMap, ResourceIndex, SamplerPtr, TexturePtr,
+    counters::CounterPool, decompress::BlockFormat, internal::Channel, ycbcr::YcbcrBinding,
+    Backend, BufferPtr, FastHashMap, ResourceIndex, SamplerPtr, ShaderTranslator, TexturePtr,
     MAX_COLOR_ATTACHMENTS,
 };
 
 use hal::{
-    buffer,
-    format::FormatDesc,
+    buffer, command as com,
+    format::{self, FormatDesc},
     image,
     memory::Segment,
-    pass::{Attachment, AttachmentId},
+    pass::{self, Attachment, AttachmentId},
     pso, MemoryTypeId,
 };
 use range_alloc::RangeAllocator;
 
 use arrayvec::ArrayVec;
+use block::ConcreteBlock;
 use metal;
-use parking_lot::RwLock;
+use objc::runtime::Object;
+use parking_lot::{Condvar, Mutex, MutexGuard, RwLock};
 
 use std::{
-    fmt, ops,
+    borrow::Cow,
+    fmt,
+    hash::{Hash, Hasher},
+    ops,
     os::raw::{c_long, c_void},
     ptr,
-    sync::{atomic::AtomicBool, Arc},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time,
 };
 
 #[derive(Clone, Debug, PartialEq)]
@@ -38,12 +48,71 @@ pub type EntryPointMap = FastHashMap<(naga::ShaderStage, String), EntryPoint>;
 /// An index of a resource within descriptor pool.
 pub type PoolResourceIndex = u32;
 
+#[derive(Clone)]
 pub struct ShaderModule {
     #[cfg(feature = "cross")]
     pub(crate) spv: Vec<u32>,
     #[cfg(feature = "pipeline-cache")]
     pub(crate) spv_hash: u64,
-    pub(crate) naga: Result<hal::device::NagaShader, String>,
+    /// Identifies the contents of the module, to share the compiled libraries.
+    pub(crate) key: ModuleKey,
+    /// Translator forced for the module, if any.
+    pub(crate) translator: Option<ShaderTranslator>,
+    pub(crate) naga: Result<Arc<hal::device::NagaShader>, String>,
+    /// The module is created from an identifier, so its libraries
+    /// can only be taken from a pipeline cache.
+    pub(crate) from_identifier: bool,
+}
+
+/// Contents of a shader module, which the libraries compiled from it are shared by.
+#[derive(Clone, Debug)]
+pub(crate) enum ModuleKey {
+    /// SPIR-V of the module, along with its hash.
+    Spirv(u64, Arc<[u32]>),
+    /// Hash of the SPIR-V of a module created from an identifier.
+    Identifier(u64),
+    /// Module that has no SPIR-V to be compared, which is only equal to itself.
+    Unique(u64),
+}
+
+impl ModuleKey {
+    pub(crate) fn spirv(words: &[u32]) -> Self {
+        ModuleKey::Spirv(fxhash::hash64(words), words.into())
+    }
+
+    pub(crate) fn unique() -> Self {
+        static NEXT_KEY: AtomicU64 = AtomicU64::new(0);
+        ModuleKey::Unique(NEXT_KEY.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl PartialEq for ModuleKey {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                &ModuleKey::Spirv(hash, ref words),
+                &ModuleKey::Spirv(other_hash, ref other_words),
+            ) => hash == other_hash && words == other_words,
+            (&ModuleKey::Identifier(hash), &ModuleKey::Identifier(other_hash)) => {
+                hash == other_hash
+            }
+            (&ModuleKey::Unique(id), &ModuleKey::Unique(other_id)) => id == other_id,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for ModuleKey {}
+
+impl Hash for ModuleKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // the SPIR-V is only compared, its hash stands for it
+        match *self {
+            ModuleKey::Spirv(hash, _) => (0u8, hash).hash(state),
+            ModuleKey::Identifier(hash) => (1u8, hash).hash(state),
+            ModuleKey::Unique(id) => (2u8, id).hash(state),
+        }
+    }
 }
 
 impl fmt::Debug for ShaderModule {
@@ -98,8 +167,31 @@ pub struct AttachmentInfo {
 #[derive(Debug)]
 pub struct Subpass {
     pub attachments: SubpassData<AttachmentInfo>,
+    /// Resolve modes of the depth and stencil aspects, used when the
+    /// depth/stencil attachment has a `resolve_id`.
+    pub depth_stencil_resolve_modes: (Option<pass::ResolveMode>, Option<pass::ResolveMode>),
     pub inputs: Vec<AttachmentId>,
     pub samples: image::NumSamples,
+    /// Continues the render encoder of the previous subpass instead of starting a new one.
+    pub merged: bool,
+}
+
+impl Subpass {
+    /// Check if this subpass renders into the same attachments as `other`,
+    /// without resolving any of them.
+    pub(crate) fn shares_targets(&self, other: &Self) -> bool {
+        let ids = |data: &SubpassData<AttachmentInfo>| {
+            data.colors
+                .iter()
+                .chain(data.depth_stencil.as_ref())
+                .map(|at| (at.id, at.resolve_id))
+                .collect::<ArrayVec<[_; MAX_COLOR_ATTACHMENTS + 1]>>()
+        };
+        let targets = ids(&self.attachments);
+        self.attachments.colors.len() == other.attachments.colors.len()
+            && targets == ids(&other.attachments)
+            && targets.iter().all(|&(_, resolve_id)| resolve_id.is_none())
+    }
 }
 
 #[derive(Debug)]
@@ -112,6 +204,40 @@ pub struct RenderPass {
 #[derive(Debug)]
 pub struct Framebuffer {
     pub(crate) extent: image::Extent,
+    /// Descriptions of the image views provided at the beginning of a render pass.
+    pub(crate) attachments: Vec<image::FramebufferAttachment>,
+}
+
+impl Framebuffer {
+    /// Check that an image view provided at the beginning of a render pass
+    /// is compatible with the description of its attachment.
+    pub(crate) fn check_view(&self, index: usize, view: &ImageView) {
+        if self.attachments.get(index).is_none() {
+            error!("Framebuffer has no attachment {}", index);
+            return;
+        }
+        let texture = &view.texture;
+        if (texture.width() as u32) < self.extent.width
+            || (texture.height() as u32) < self.extent.height
+        {
+            error!(
+                "Image view of attachment {} is {}x{}, smaller than the framebuffer {:?}",
+                index,
+                texture.width(),
+                texture.height(),
+                self.extent
+            );
+        }
+        if !texture
+            .usage()
+            .contains(metal::MTLTextureUsage::RenderTarget)
+        {
+            error!(
+                "Image view of attachment {} can't be used as a render target",
+                index
+            );
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -160,6 +286,9 @@ impl ResourceData<PoolResourceIndex> {
         if content.contains(DescriptorContent::SAMPLER) {
             self.samplers += count;
         }
+        if content.contains(DescriptorContent::CHROMA_PLANE) {
+            self.textures += count;
+        }
     }
     #[inline]
     pub fn add(&mut self, content: DescriptorContent) {
@@ -205,6 +334,10 @@ pub struct PipelineLayout {
     #[cfg(feature = "cross")]
     pub(crate) spirv_cross_options: spirv_cross::msl::CompilerOptions,
     pub(crate) naga_options: naga::back::msl::Options,
+    /// Combined image samplers that sample with a YCbCr conversion.
+    pub(crate) ycbcr_bindings: Vec<YcbcrBinding>,
+    /// The vertex or fragment stage can write storage resources.
+    pub(crate) graphics_writes_storage: bool,
     pub(crate) infos: Vec<DescriptorSetInfo>,
     pub(crate) total: MultiStageResourceCounters,
     pub(crate) push_constants: MultiStageData<Option<PushConstantInfo>>,
@@ -214,8 +347,14 @@ pub struct PipelineLayout {
 #[derive(Clone, Debug)]
 pub struct ModuleInfo {
     pub library: metal::Library,
+    /// Hash of the MSL source and language version the library is compiled from.
+    pub source_hash: u64,
+    /// The MSL source, reported in the pipeline executables.
+    pub source: Arc<str>,
     pub entry_point_map: EntryPointMap,
     pub rasterization_enabled: bool,
+    /// Bindings of the buffers ending with runtime-sized arrays.
+    pub sized_bindings: Vec<naga::ResourceBinding>,
 }
 
 #[derive(Clone, Debug)]
@@ -227,6 +366,7 @@ pub struct SerializableModuleInfo {
     pub source: String,
     pub entry_point_map: EntryPointMap,
     pub rasterization_enabled: bool,
+    pub sized_bindings: Vec<naga::ResourceBinding>,
 }
 
 #[cfg(not(feature = "pipeline-cache"))]
@@ -300,30 +440,231 @@ pub struct GraphicsPipeline {
     pub(crate) depth_bias: pso::State<pso::DepthBias>,
     pub(crate) depth_stencil_desc: pso::DepthStencilDesc,
     pub(crate) baked_states: pso::BakedStates,
+    pub(crate) dynamic_states: pso::DynamicStates,
     /// The mapping from Metal vertex buffers to Vulkan ones.
     /// This is needed because Vulkan allows attribute offsets to exceed the strides,
     /// while Metal does not. Thus, we register extra vertex buffer bindings with
     /// adjusted offsets to cover this use case.
     pub(crate) vertex_buffers: VertexBufferVec,
+    /// Pipeline states for the vertex layouts set with commands,
+    /// if the vertex input is dynamic.
+    pub(crate) vertex_input: Option<Arc<VertexInputVariants>>,
     /// Tracked attachment formats
     pub(crate) attachment_formats: SubpassFormats,
     pub(crate) samples: image::NumSamples,
+    pub(crate) translation: TranslationReport,
+    pub(crate) executables: Vec<pso::PipelineExecutable>,
+    pub(crate) private_data: PrivateData,
+    /// Key of `raw` in the render pipeline states shared by the device,
+    /// which are released when the last pipeline using them is destroyed.
+    pub(crate) state_key: Arc<RenderPipelineKey>,
+    /// The shaders can write storage resources, which the next render pass
+    /// can only see after the encoder of this one ends.
+    pub(crate) writes_storage: bool,
 }
 
 unsafe impl Send for GraphicsPipeline {}
 unsafe impl Sync for GraphicsPipeline {}
 
+pub(crate) type VertexInputKey = (Vec<pso::VertexBufferDesc>, Vec<pso::AttributeDesc>);
+
+/// Specialized function of a compiled shader library.
+#[derive(Clone, Debug)]
+pub(crate) struct FunctionKey {
+    /// Hash of `source`, which is hashed instead of the whole source.
+    pub(crate) source_hash: u64,
+    pub(crate) source: Arc<str>,
+    pub(crate) name: String,
+    pub(crate) constants: Vec<pso::SpecializationConstant>,
+    pub(crate) data: Vec<u8>,
+}
+
+impl PartialEq for FunctionKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.source_hash == other.source_hash
+            && self.name == other.name
+            && self.constants == other.constants
+            && self.data == other.data
+            && self.source == other.source
+    }
+}
+
+impl Eq for FunctionKey {}
+
+impl Hash for FunctionKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.source_hash, &self.name, &self.constants, &self.data).hash(state);
+    }
+}
+
+/// Everything that is set on a render pipeline descriptor, except for the label.
+///
+/// Graphics pipelines created with equal keys share their render pipeline state.
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub(crate) struct RenderPipelineKey {
+    pub(crate) primitive_class: Option<u64>,
+    pub(crate) vertex: Option<FunctionKey>,
+    pub(crate) fragment: Option<FunctionKey>,
+    pub(crate) rasterizing: bool,
+    pub(crate) colors: Vec<(u64, pso::ColorBlendDesc)>,
+    /// Format of the depth-stencil attachment in the render pass, if any, and its pixel format.
+    pub(crate) depth_stencil: Option<(Option<format::Format>, u64)>,
+    pub(crate) vertex_input: Option<VertexInputKey>,
+    pub(crate) multisampling: Option<(image::NumSamples, bool, bool)>,
+}
+
+/// Render pipeline states of a pipeline with dynamic vertex input,
+/// created on demand for each vertex layout it is drawn with.
+///
+/// The states only differ in the vertex descriptor, so they share the pipeline descriptor.
+#[derive(Debug)]
+pub(crate) struct VertexInputVariants {
+    pub(crate) descriptor: metal::RenderPipelineDescriptor,
+    /// Number of vertex stage buffers used by the pipeline layout.
+    pub(crate) reserved_buffers: ResourceIndex,
+    pub(crate) states:
+        Mutex<FastHashMap<VertexInputKey, (metal::RenderPipelineState, VertexBufferVec)>>,
+}
+
+unsafe impl Send for VertexInputVariants {}
+unsafe impl Sync for VertexInputVariants {}
+
+impl GraphicsPipeline {
+    /// Get the translators that produced the shader stages of the pipeline.
+    pub fn translation_report(&self) -> TranslationReport {
+        self.translation
+    }
+}
+
+/// Shader entry point owned by a graphics pipeline library.
+#[derive(Debug)]
+pub struct LibraryEntryPoint {
+    entry: String,
+    /// Copy of the module, sharing the libraries compiled for it.
+    module: ShaderModule,
+    constants: Vec<pso::SpecializationConstant>,
+    data: Vec<u8>,
+}
+
+impl LibraryEntryPoint {
+    pub(crate) fn new(ep: &pso::EntryPoint<Backend>) -> Self {
+        LibraryEntryPoint {
+            entry: ep.entry.to_string(),
+            module: ep.module.clone(),
+            constants: ep.specialization.constants.to_vec(),
+            data: ep.specialization.data.to_vec(),
+        }
+    }
+
+    pub(crate) fn entry_point(&self) -> pso::EntryPoint<Backend> {
+        pso::EntryPoint {
+            entry: &self.entry,
+            module: &self.module,
+            specialization: pso::Specialization {
+                constants: Cow::Borrowed(&self.constants),
+                data: Cow::Borrowed(&self.data),
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct LibraryVertexInput {
+    pub(crate) buffers: Vec<pso::VertexBufferDesc>,
+    pub(crate) attributes: Vec<pso::AttributeDesc>,
+    pub(crate) input_assembler: pso::InputAssemblerDesc,
+}
+
+/// Parts of a graphics pipeline, linked by building the description of the pipeline.
+///
+/// The shaders are compiled when the library is created, so the linked pipelines
+/// pick their functions from the libraries shared by the shader compiler.
+#[derive(Debug)]
+pub struct GraphicsPipelineLibrary {
+    pub(crate) vertex_input: Option<LibraryVertexInput>,
+    pub(crate) pre_rasterization: Option<(LibraryEntryPoint, pso::Rasterizer)>,
+    pub(crate) fragment: Option<(Option<LibraryEntryPoint>, pso::DepthStencilDesc)>,
+    pub(crate) fragment_output: Option<(pso::BlendDesc, Option<pso::Multisampling>)>,
+    /// Baked states of the parts held by the library.
+    pub(crate) baked_states: pso::BakedStates,
+    /// Dynamic states of the parts held by the library.
+    pub(crate) dynamic_states: pso::DynamicStates,
+}
+
 #[derive(Debug)]
 pub struct ComputePipeline {
     pub(crate) cs_lib: metal::Library,
     pub(crate) raw: metal::ComputePipelineState,
     pub(crate) work_group_size: metal::MTLSize,
     pub(crate) info: PipelineStageInfo,
+    /// Buffer slot of the base workgroup, if the pipeline can be dispatched with one.
+    pub(crate) base_slot: Option<ResourceIndex>,
+    pub(crate) translation: TranslationReport,
+    pub(crate) executables: Vec<pso::PipelineExecutable>,
+    pub(crate) private_data: PrivateData,
 }
 
 unsafe impl Send for ComputePipeline {}
 unsafe impl Sync for ComputePipeline {}
 
+impl ComputePipeline {
+    /// Get the translator that produced the shader of the pipeline.
+    pub fn translation_report(&self) -> TranslationReport {
+        self.translation
+    }
+}
+
+/// Translators that produced the MSL of the shader stages of a pipeline,
+/// to diagnose which compiler a problematic pipeline went through.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct TranslationReport {
+    /// Translator of the vertex shader.
+    pub vertex: Option<ShaderTranslator>,
+    /// Translator of the fragment shader.
+    pub fragment: Option<ShaderTranslator>,
+    /// Translator of the compute shader.
+    pub compute: Option<ShaderTranslator>,
+}
+
+/// Description of a tile pipeline, see `Device::create_tile_pipeline`.
+#[derive(Debug)]
+pub struct TilePipelineDesc<'a> {
+    /// Metal Shading Language source containing the tile function.
+    pub source: &'a str,
+    /// Name of the `[[kernel]]` function to run per tile.
+    pub entry: &'a str,
+    /// The subpass the tile function is dispatched in, which provides
+    /// the color formats and the sample count of the imageblock.
+    pub subpass: pass::Subpass<'a, Backend>,
+    /// Threadgroup memory to allocate for each dispatch, including explicit
+    /// imageblocks, as pairs of the argument index and the length in bytes.
+    pub threadgroup_memory: &'a [(ResourceIndex, u32)],
+    /// Promise that every dispatch uses the tile size as the threadgroup size.
+    pub threadgroup_size_matches_tile_size: bool,
+}
+
+/// A compute function that runs per tile, within a render pass, with access
+/// to the imageblock of the tile.
+#[derive(Debug)]
+pub struct TilePipeline {
+    pub(crate) raw: metal::RenderPipelineState,
+    pub(crate) threadgroup_memory: Vec<(ResourceIndex, u32)>,
+}
+
+unsafe impl Send for TilePipeline {}
+unsafe impl Sync for TilePipeline {}
+
+/// Layout of the tile memory of the render passes, see `CommandBuffer::set_tile_layout`.
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub struct TileLayout {
+    /// Width and height of a tile in pixels, or `None` to let Metal pick them.
+    pub size: Option<(u32, u32)>,
+    /// Bytes of the implicit imageblock per sample, or 0 to derive it from the attachments.
+    pub imageblock_sample_length: u32,
+    /// Bytes of threadgroup memory per tile, shared by the tile dispatches of a pass.
+    pub threadgroup_memory_length: u32,
+}
+
 #[derive(Debug)]
 pub enum ImageLike {
     /// This image has not yet been bound to memory.
@@ -331,6 +672,8 @@ pub enum ImageLike {
         descriptor: metal::TextureDescriptor,
         mip_sizes: Vec<buffer::Offset>,
         host_visible: bool,
+        /// The texture is copied on the host, so it can't have private storage.
+        host_transfer: bool,
         name: String,
     },
     /// This is a linearly tiled HOST-visible image, which is represented by a buffer.
@@ -348,6 +691,16 @@ impl ImageLike {
     }
 }
 
+/// Contiguous byte range copied between a buffer and a linear image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct LinearCopy {
+    /// Offset relative to the start of the buffer range.
+    pub buffer: buffer::Offset,
+    /// Offset relative to the start of the image range.
+    pub image: buffer::Offset,
+    pub size: buffer::Offset,
+}
+
 #[derive(Debug)]
 pub struct Image {
     pub(crate) like: ImageLike,
@@ -357,9 +710,34 @@ pub struct Image {
     pub(crate) shader_channel: Channel,
     pub(crate) mtl_format: metal::MTLPixelFormat,
     pub(crate) mtl_type: metal::MTLTextureType,
+    /// Block format decompressed on upload, if not supported natively.
+    pub(crate) decompression: Option<BlockFormat>,
+    /// Second plane of a two-plane format, the first one being the image itself.
+    pub(crate) chroma_plane: Option<ChromaPlane>,
+    pub(crate) private_data: PrivateData,
+}
+
+/// Chroma plane of a two-plane image, stored in a texture of its own.
+#[derive(Debug)]
+pub(crate) struct ChromaPlane {
+    pub(crate) descriptor: metal::TextureDescriptor,
+    /// Created when the image is bound to memory.
+    pub(crate) texture: Option<metal::Texture>,
+    pub(crate) format_desc: FormatDesc,
+    pub(crate) mtl_format: metal::MTLPixelFormat,
 }
 
 impl Image {
+    /// Texture and format of the plane selected by `aspects`.
+    pub(crate) fn plane(&self, aspects: format::Aspects) -> (&metal::TextureRef, FormatDesc) {
+        match self.chroma_plane {
+            Some(ref plane) if aspects.contains(format::Aspects::PLANE_1) => {
+                let texture = plane.texture.as_ref().expect("Expected bound image!");
+                (texture, plane.format_desc)
+            }
+            _ => (self.like.as_texture(), self.format_desc),
+        }
+    }
     pub(crate) fn pitches_impl(
         extent: image::Extent,
         format_desc: FormatDesc,
@@ -385,6 +763,116 @@ impl Image {
             + pitches[1] * offset.y as buffer::Offset
             + pitches[2] * offset.z as buffer::Offset
     }
+    /// Split a buffer-image copy region into contiguous ranges of a linear image,
+    /// merging consecutive rows, slices, and layers that are tightly packed on both sides.
+    ///
+    /// Returns `None` if the region doesn't fit into `buffer_size` bytes of the buffer
+    /// or into the range the image is bound to.
+    pub(crate) fn linear_copies_impl(
+        extent: image::Extent,
+        format_desc: FormatDesc,
+        region: &com::BufferImageCopy,
+        buffer_size: buffer::Offset,
+        image_range: &ops::Range<buffer::Offset>,
+    ) -> Option<Vec<LinearCopy>> {
+        debug_assert!(!format_desc.is_compressed());
+        debug_assert_eq!(region.image_layers.level, 0);
+        let offset = region.image_offset;
+        if offset.x < 0
+            || offset.y < 0
+            || offset.z < 0
+            || offset.x as image::Size + region.image_extent.width > extent.width
+            || offset.y as image::Size + region.image_extent.height > extent.height
+            || offset.z as image::Size + region.image_extent.depth > extent.depth
+        {
+            return None;
+        }
+        let pitches = Self::pitches_impl(extent, format_desc);
+        let bytes_per_texel = pitches[0];
+        let ext = region.image_extent;
+        let row_size = ext.width as buffer::Offset * bytes_per_texel;
+        let buffer_row_pitch = match region.buffer_width {
+            0 => row_size,
+            width => width as buffer::Offset * bytes_per_texel,
+        };
+        let buffer_slice_pitch = match region.buffer_height {
+            0 => ext.height,
+            height => height,
+        } as buffer::Offset
+            * buffer_row_pitch;
+        let buffer_layer_pitch = ext.depth as buffer::Offset * buffer_slice_pitch;
+        let image_base = bytes_per_texel * region.image_offset.x as buffer::Offset
+            + pitches[1] * region.image_offset.y as buffer::Offset
+            + pitches[2] * region.image_offset.z as buffer::Offset;
+
+        let mut copies = Vec::<LinearCopy>::new();
+        if row_size == 0 {
+            return Some(copies);
+        }
+        for (i, layer) in region.image_layers.layers.clone().enumerate() {
+            for z in 0..ext.depth as buffer::Offset {
+                for y in 0..ext.height as buffer::Offset {
+                    let copy = LinearCopy {
+                        buffer: region.buffer_offset
+                            + i as buffer::Offset * buffer_layer_pitch
+                            + z * buffer_slice_pitch
+                            + y * buffer_row_pitch,
+                        image: image_base
+                            + layer as buffer::Offset * pitches[3]
+                            + z * pitches[2]
+                            + y * pitches[1],
+                        size: row_size,
+                    };
+                    match copies.last_mut() {
+                        Some(last)
+                            if last.buffer + last.size == copy.buffer
+                                && last.image + last.size == copy.image =>
+                        {
+                            last.size += copy.size;
+                        }
+                        _ => copies.push(copy),
+                    }
+                }
+            }
+        }
+        // Offsets grow with each copy, so checking the last one covers the whole region.
+        let image_size = image_range.end - image_range.start;
+        match copies.last() {
+            Some(last)
+                if last.buffer + last.size > buffer_size || last.image + last.size > image_size =>
+            {
+                None
+            }
+            _ => Some(copies),
+        }
+    }
+    /// Split a copy region of this linear image, see `linear_copies_impl`.
+    ///
+    /// Invalid regions are reported and skipped.
+    pub(crate) fn linear_copies(
+        &self,
+        region: &com::BufferImageCopy,
+        buffer_size: buffer::Offset,
+    ) -> Vec<LinearCopy> {
+        let image_range = match self.like {
+            ImageLike::Buffer(ref buffer) => buffer.as_bound().1,
+            _ => panic!("Expected a linear image!"),
+        };
+        Self::linear_copies_impl(
+            self.kind.extent(),
+            self.format_desc,
+            region,
+            buffer_size,
+            image_range,
+        )
+        .unwrap_or_else(|| {
+            error!(
+                "Skipping copy region {:?} out of the bounds of the buffer or the image",
+                region
+            );
+            Vec::new()
+        })
+    }
     pub(crate) fn byte_extent(&self, extent: image::Extent) -> buffer::Offset {
         let bytes_per_texel = self.format_desc.bits as image::Size >> 3;
         (bytes_per_texel * extent.width * extent.height * extent.depth) as _
@@ -418,15 +906,37 @@ unsafe impl Sync for Image {}
 #[derive(Debug)]
 pub struct BufferView {
     pub(crate) raw: metal::Texture,
+    /// Private copy of the viewed range, which the texture is created from
+    /// when the buffer is in shared memory that can't back textures.
+    pub(crate) mirror: Option<Arc<TexelMirror>>,
 }
 
 unsafe impl Send for BufferView {}
 unsafe impl Sync for BufferView {}
 
+/// Private buffer mirroring a range of a shared one, for a uniform texel view.
+///
+/// It's refreshed before the submissions using the view, and after the transfer
+/// commands writing to the source buffer.
+#[derive(Debug)]
+pub(crate) struct TexelMirror {
+    pub(crate) source: metal::Buffer,
+    pub(crate) offset: buffer::Offset,
+    pub(crate) raw: metal::Buffer,
+    pub(crate) size: buffer::Offset,
+    /// Texture of the view, as it's written into the descriptor sets.
+    pub(crate) texture: TexturePtr,
+}
+
+unsafe impl Send for TexelMirror {}
+unsafe impl Sync for TexelMirror {}
+
 #[derive(Debug)]
 pub struct ImageView {
     pub(crate) texture: metal::Texture,
     pub(crate) mtl_format: metal::MTLPixelFormat,
+    /// Chroma plane, sampled along with the texture by a YCbCr conversion.
+    pub(crate) chroma: Option<metal::Texture>,
 }
 
 unsafe impl Send for ImageView {}
@@ -438,6 +948,7 @@ pub struct Sampler {
     #[cfg(feature = "cross")]
     pub(crate) cross_data: spirv_cross::msl::SamplerData,
     pub(crate) data: naga::back::msl::sampler::InlineSampler,
+    pub(crate) ycbcr_conversion: Option<image::SamplerYcbcrConversion>,
 }
 
 unsafe impl Send for Sampler {}
@@ -446,6 +957,35 @@ unsafe impl Sync for Sampler {}
 #[derive(Clone, Debug)]
 pub struct Semaphore {
     pub(crate) system: Option<SystemSemaphore>,
+    pub(crate) shared_event: Option<Arc<SharedEventPayload>>,
+}
+
+/// `MTLSharedEvent` that the payload of an external semaphore lives in.
+///
+/// Every signal operation sets the event to the next value, and every wait operation
+/// waits for the next value, so the payload can be shared with other devices and
+/// processes following the same protocol.
+#[derive(Debug)]
+pub(crate) struct SharedEventPayload {
+    pub(crate) raw: metal::SharedEvent,
+    /// Value set by the last signal operation.
+    pub(crate) signaled: AtomicU64,
+    /// Value waited for by the last wait operation.
+    pub(crate) waited: AtomicU64,
+}
+
+unsafe impl Send for SharedEventPayload {}
+unsafe impl Sync for SharedEventPayload {}
+
+impl SharedEventPayload {
+    pub(crate) fn new(raw: metal::SharedEvent) -> Self {
+        let value = raw.signaled_value();
+        SharedEventPayload {
+            raw,
+            signaled: AtomicU64::new(value),
+            waited: AtomicU64::new(value),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -454,11 +994,15 @@ pub enum Buffer {
         size: u64,
         usage: buffer::Usage,
         name: String,
+        private_data: PrivateData,
     },
     Bound {
         raw: metal::Buffer,
         range: ops::Range<u64>,
         options: metal::MTLResourceOptions,
+        /// The raw buffer is registered as resident for the accesses through its address.
+        addressed: bool,
+        private_data: PrivateData,
     },
 }
 
@@ -475,6 +1019,34 @@ impl Buffer {
             } => (raw, range),
         }
     }
+
+    pub(crate) fn private_data(&self) -> &PrivateData {
+        match *self {
+            Buffer::Unbound {
+                ref private_data, ..
+            }
+            | Buffer::Bound {
+                ref private_data, ..
+            } => private_data,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PrivateDataSlot(pub(crate) u64);
+
+/// Values of the private data slots attached to an object.
+#[derive(Debug, Default)]
+pub struct PrivateData(Mutex<FastHashMap<u64, u64>>);
+
+impl PrivateData {
+    pub(crate) fn get(&self, slot: &PrivateDataSlot) -> u64 {
+        self.0.lock().get(&slot.0).cloned().unwrap_or(0)
+    }
+
+    pub(crate) fn set(&self, slot: &PrivateDataSlot, data: u64) {
+        self.0.lock().insert(slot.0, data);
+    }
 }
 
 /// Actual binding size for storage buffers, and !0 otherwise.
@@ -577,12 +1149,11 @@ impl DescriptorPool {
             }
         }
     }
-}
 
-impl pso::DescriptorPool<Backend> for DescriptorPool {
-    unsafe fn allocate_one(
+    fn allocate_set(
         &mut self,
         set_layout: &DescriptorSetLayout,
+        variable_count: Option<pso::DescriptorArrayIndex>,
     ) -> Result<DescriptorSet, pso::AllocationError> {
         self.report_available();
         match *self {
@@ -695,6 +1266,23 @@ impl pso::DescriptorPool<Backend> for DescriptorPool {
                     } => (encoder, stage_flags, bindings, total),
                     _ => return Err(pso::AllocationError::IncompatibleLayout),
                 };
+                let mut total = total;
+                let mut update_after_bind = false;
+                for arg in bindings.values() {
+                    update_after_bind |= arg
+                        .flags
+                        .contains(pso::DescriptorBindingFlags::UPDATE_AFTER_BIND);
+                    if let Some(count) = variable_count {
+                        if arg
+                            .flags
+                            .contains(pso::DescriptorBindingFlags::VARIABLE_DESCRIPTOR_COUNT)
+                        {
+                            // The variable binding has the last resources of the layout,
+                            // so the unused ones are cut from the end of the set.
+                            total -= (arg.count - count.min(arg.count)) as PoolResourceIndex;
+                        }
+                    }
+                }
                 let range = res_allocator
                     .allocate_range(total as PoolResourceIndex)
                     .map_err(|_| pso::AllocationError::OutOfPoolMemory)?;
@@ -704,13 +1292,19 @@ impl pso::DescriptorPool<Backend> for DescriptorPool {
                     .expect("Argument encoding length is inconsistent!");
                 let raw_offset = (raw_range.start + alignment - 1) & !(alignment - 1);
 
-                #[cfg(feature = "cross")]
                 {
                     let mut data = inner.write();
+                    // The descriptors left unwritten in partially bound sets
+                    // must not be made resident.
+                    for ur in data.resources[range.start as usize..range.end as usize].iter_mut() {
+                        ur.ptr = ptr::null_mut();
+                    }
+                    #[cfg(feature = "cross")]
                     for arg in bindings.values() {
                         if arg.bind_target.buffer.is_some() || arg.bind_target.texture.is_some() {
                             let pos = (range.start + arg.res_offset) as usize;
-                            for ur in data.resources[pos..pos + arg.count].iter_mut() {
+                            let end = (pos + arg.count).min(range.end as usize);
+                            for ur in data.resources[pos..end].iter_mut() {
                                 ur.usage = arg.usage;
                             }
                         }
@@ -725,10 +1319,28 @@ impl pso::DescriptorPool<Backend> for DescriptorPool {
                     encoder: encoder.clone(),
                     bindings: Arc::clone(bindings),
                     stage_flags,
+                    update_after_bind,
                 })
             }
         }
     }
+}
+
+impl pso::DescriptorPool<Backend> for DescriptorPool {
+    unsafe fn allocate_one(
+        &mut self,
+        set_layout: &DescriptorSetLayout,
+    ) -> Result<DescriptorSet, pso::AllocationError> {
+        self.allocate_set(set_layout, None)
+    }
+
+    unsafe fn allocate_one_with_variable_count(
+        &mut self,
+        set_layout: &DescriptorSetLayout,
+        variable_count: pso::DescriptorArrayIndex,
+    ) -> Result<DescriptorSet, pso::AllocationError> {
+        self.allocate_set(set_layout, Some(variable_count))
+    }
 
     unsafe fn free<I>(&mut self, descriptor_sets: I)
     where
@@ -876,6 +1488,8 @@ bitflags! {
         const IMMUTABLE_SAMPLER = 1<<5;
         /// Resource is a writable storage.
         const WRITABLE = 1 << 6;
+        /// Texture is followed by the chroma plane of a YCbCr image.
+        const CHROMA_PLANE = 1 << 7;
     }
 }
 
@@ -914,6 +1528,13 @@ impl From<pso::DescriptorType> for DescriptorContent {
                 base | storage
             }
             pso::DescriptorType::InputAttachment => DescriptorContent::TEXTURE,
+            pso::DescriptorType::AccelerationStructure => DescriptorContent::BUFFER,
+            // Mutable descriptors have a slot for each kind of resource they can hold.
+            pso::DescriptorType::Mutable { types } => types
+                .descriptor_types()
+                .fold(DescriptorContent::empty(), |content, ty| {
+                    content | DescriptorContent::from(ty)
+                }),
         }
     }
 }
@@ -925,6 +1546,8 @@ pub struct DescriptorLayout {
     pub stages: pso::ShaderStageFlags,
     pub binding: pso::DescriptorBinding,
     pub array_index: pso::DescriptorArrayIndex,
+    /// Number of the resources preceding this descriptor in the set.
+    pub offset: ResourceData<PoolResourceIndex>,
 }
 
 #[derive(Debug)]
@@ -934,6 +1557,7 @@ pub struct ArgumentLayout {
     pub(crate) count: pso::DescriptorArrayIndex,
     pub(crate) usage: metal::MTLResourceUsage,
     pub(crate) content: DescriptorContent,
+    pub(crate) flags: pso::DescriptorBindingFlags,
 }
 
 #[derive(Debug)]
@@ -941,6 +1565,7 @@ pub struct ImmutableSampler {
     pub(crate) data: naga::back::msl::sampler::InlineSampler,
     #[cfg(feature = "cross")]
     pub(crate) cross_data: spirv_cross::msl::SamplerData,
+    pub(crate) ycbcr_conversion: Option<image::SamplerYcbcrConversion>,
 }
 
 #[derive(Debug)]
@@ -983,6 +1608,9 @@ pub enum DescriptorSet {
         encoder: metal::ArgumentEncoder,
         bindings: Arc<FastHashMap<pso::DescriptorBinding, ArgumentLayout>>,
         stage_flags: pso::ShaderStageFlags,
+        /// Resources are read when the commands are encoded, since the
+        /// descriptors can be written after the set is bound.
+        update_after_bind: bool,
     },
 }
 unsafe impl Send for DescriptorSet {}
@@ -992,11 +1620,17 @@ unsafe impl Sync for DescriptorSet {}
 pub struct Memory {
     pub(crate) heap: MemoryHeap,
     pub(crate) size: u64,
+    /// Whether the memory was imported, so its CPU buffer isn't sub-allocated.
+    pub(crate) imported: bool,
 }
 
 impl Memory {
     pub(crate) fn new(heap: MemoryHeap, size: u64) -> Self {
-        Memory { heap, size }
+        Memory {
+            heap,
+            size,
+            imported: false,
+        }
     }
 
     pub(crate) fn resolve(&self, range: &Segment) -> ops::Range<u64> {
@@ -1010,8 +1644,40 @@ unsafe impl Sync for Memory {}
 #[derive(Debug)]
 pub(crate) enum MemoryHeap {
     Private,
-    Public(MemoryTypeId, metal::Buffer),
+    /// Host-visible memory at the given offset of a CPU buffer, which is
+    /// shared between allocations for small sizes.
+    Public(MemoryTypeId, metal::Buffer, u64),
     Native(metal::Heap),
+    /// Imported `IOSurfaceRef`, which only 2D images can be bound to.
+    IOSurface(IOSurface),
+}
+
+/// Retained reference to an `IOSurface`.
+#[derive(Debug)]
+pub(crate) struct IOSurface(pub(crate) ptr::NonNull<c_void>);
+
+impl IOSurface {
+    pub(crate) unsafe fn retain(raw: ptr::NonNull<c_void>) -> Self {
+        let _: *mut objc::runtime::Object =
+            msg_send![raw.as_ptr() as *mut objc::runtime::Object, retain];
+        IOSurface(raw)
+    }
+}
+
+impl Drop for IOSurface {
+    fn drop(&mut self) {
+        unsafe {
+            let () = msg_send![self.0.as_ptr() as *mut objc::runtime::Object, release];
+        }
+    }
+}
+
+/// Large CPU buffer that small allocations of public memory are carved from.
+#[derive(Debug)]
+pub(crate) struct PublicSlab {
+    pub(crate) memory_type: MemoryTypeId,
+    pub(crate) raw: metal::Buffer,
+    pub(crate) allocator: RangeAllocator<u64>,
 }
 
 #[derive(Default)]
@@ -1041,6 +1707,12 @@ impl ArgumentArray {
                 },
             },
             Dt::InputAttachment => MTLResourceUsage::Sample,
+            Dt::AccelerationStructure => MTLResourceUsage::Read,
+            Dt::Mutable { types } => types
+                .descriptor_types()
+                .fold(MTLResourceUsage::empty(), |usage, ty| {
+                    usage | Self::describe_usage(ty)
+                }),
         }
     }
 
@@ -1082,17 +1754,137 @@ impl ArgumentArray {
 pub enum QueryPool {
     Occlusion(ops::Range<u32>),
     Timestamp,
+    PerformanceCounters(CounterPool),
+}
+
+/// Block on `condvar` while `pending` holds for the guarded value, or until the timeout expires.
+/// Timeouts that don't fit into a deadline are waited on without one.
+pub(crate) fn wait_while<T>(
+    condvar: &Condvar,
+    guard: &mut MutexGuard<T>,
+    timeout: Option<time::Duration>,
+    pending: impl Fn(&T) -> bool,
+) {
+    match timeout.and_then(|duration| time::Instant::now().checked_add(duration)) {
+        Some(deadline) => {
+            while pending(&**guard) {
+                if condvar.wait_until(guard, deadline).timed_out() {
+                    break;
+                }
+            }
+        }
+        None => {
+            while pending(&**guard) {
+                condvar.wait(guard);
+            }
+        }
+    }
+}
+
+/// Completion flag of a submission, raised by the completion handler
+/// of the command buffer that signals the fence.
+#[derive(Debug, Default)]
+pub struct FenceSignal {
+    completed: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl FenceSignal {
+    pub(crate) fn signal(&self) {
+        *self.completed.lock() = true;
+        self.condvar.notify_all();
+    }
+
+    pub(crate) fn is_signaled(&self) -> bool {
+        *self.completed.lock()
+    }
+
+    /// Block until the signal is raised, or the timeout expires.
+    /// Returns the final state of the signal.
+    pub(crate) fn wait(&self, timeout: Option<time::Duration>) -> bool {
+        let mut completed = self.completed.lock();
+        wait_while(&self.condvar, &mut completed, timeout, |&completed| {
+            !completed
+        });
+        *completed
+    }
 }
 
 #[derive(Debug)]
 pub enum Fence {
-    Idle { signaled: bool },
-    PendingSubmission(metal::CommandBuffer),
+    Idle {
+        signaled: bool,
+    },
+    PendingSubmission {
+        cmd_buffer: metal::CommandBuffer,
+        signal: Arc<FenceSignal>,
+    },
 }
 
 unsafe impl Send for Fence {}
 unsafe impl Sync for Fence {}
 
+/// Timeline semaphore living in a `MTLSharedEvent`, so that the queue waits for
+/// and signals the values on the GPU timeline.
+#[derive(Debug)]
+pub struct TimelineSemaphore {
+    pub(crate) raw: metal::SharedEvent,
+    /// Dispatches the notifications of the host waits.
+    listener: *mut Object,
+}
+
+unsafe impl Send for TimelineSemaphore {}
+unsafe impl Sync for TimelineSemaphore {}
+
+impl TimelineSemaphore {
+    pub(crate) fn new(raw: metal::SharedEvent) -> Self {
+        let listener = unsafe { msg_send![class!(MTLSharedEventListener), new] };
+        TimelineSemaphore { raw, listener }
+    }
+
+    pub(crate) fn value(&self) -> u64 {
+        self.raw.signaled_value()
+    }
+
+    pub(crate) fn signal(&self, value: u64) {
+        unsafe {
+            let () = msg_send![self.raw.as_ref(), setSignaledValue: value];
+        }
+    }
+
+    /// Block until the event reaches `value`, or the timeout expires.
+    /// Returns true if the value was reached.
+    pub(crate) fn wait(&self, value: u64, timeout: Option<time::Duration>) -> bool {
+        if self.value() >= value {
+            return true;
+        }
+        let signal = Arc::new(FenceSignal::default());
+        let handler_signal = Arc::clone(&signal);
+        let block = ConcreteBlock::new(move |_event: *mut Object, _value: u64| {
+            handler_signal.signal();
+        })
+        .copy();
+        unsafe {
+            let () = msg_send![
+                self.raw.as_ref(),
+                notifyListener: self.listener
+                atValue: value
+                block: &*block as *const _
+            ];
+        }
+        signal.wait(timeout);
+        self.value() >= value
+    }
+}
+
+impl Drop for TimelineSemaphore {
+    fn drop(&mut self) {
+        unsafe {
+            let () = msg_send![self.listener, release];
+        }
+    }
+}
+
 //TODO: review the atomic ordering
 //TODO: reconsider if Arc<Atomic> is needed
 #[derive(Debug)]
@@ -1177,3 +1969,199 @@ impl Signpost {
         let _ = (code, args);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{FenceSignal, Image, LinearCopy};
+    use hal::{command as com, format, image};
+    use std::time;
+
+    const RGBA8: format::FormatDesc = format::FormatDesc {
+        bits: 32,
+        dim: (1, 1),
+        packed: false,
+        aspects: format::Aspects::COLOR,
+    };
+    const EXTENT: image::Extent = image::Extent {
+        width: 16,
+        height: 8,
+        depth: 1,
+    };
+
+    fn region(
+        buffer_offset: u64,
+        buffer_width: u32,
+        layers: std::ops::Range<image::Layer>,
+        offset: (i32, i32),
+        extent: (u32, u32),
+    ) -> com::BufferImageCopy {
+        com::BufferImageCopy {
+            buffer_offset,
+            buffer_width,
+            buffer_height: 0,
+            image_layers: image::SubresourceLayers {
+                aspects: format::Aspects::COLOR,
+                level: 0,
+                layers,
+            },
+            image_offset: image::Offset {
+                x: offset.0,
+                y: offset.1,
+                z: 0,
+            },
+            image_extent: image::Extent {
+                width: extent.0,
+                height: extent.1,
+                depth: 1,
+            },
+        }
+    }
+
+    /// Bytes of a whole `EXTENT` image with 4 layers.
+    const IMAGE_SIZE: u64 = 4 * 16 * 8 * 4;
+
+    fn linear_copies(region: com::BufferImageCopy) -> Vec<LinearCopy> {
+        Image::linear_copies_impl(EXTENT, RGBA8, &region, !0, &(0..IMAGE_SIZE)).unwrap()
+    }
+
+    #[test]
+    fn full_rows_are_merged() {
+        let copies = linear_copies(region(256, 0, 0..1, (0, 2), (16, 4)));
+        assert_eq!(
+            copies,
+            vec![LinearCopy {
+                buffer: 256,
+                image: 2 * 64,
+                size: 4 * 64,
+            }]
+        );
+    }
+
+    #[test]
+    fn partial_rows_are_split() {
+        let copies = linear_copies(region(4, 0, 0..1, (3, 1), (2, 2)));
+        assert_eq!(
+            copies,
+            vec![
+                LinearCopy {
+                    buffer: 4,
+                    image: 64 + 12,
+                    size: 8,
+                },
+                LinearCopy {
+                    buffer: 12,
+                    image: 2 * 64 + 12,
+                    size: 8,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn buffer_row_pitch_is_respected() {
+        let copies = linear_copies(region(0, 32, 0..1, (0, 0), (16, 2)));
+        assert_eq!(
+            copies,
+            vec![
+                LinearCopy {
+                    buffer: 0,
+                    image: 0,
+                    size: 64,
+                },
+                LinearCopy {
+                    buffer: 128,
+                    image: 64,
+                    size: 64,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn whole_layers_are_merged() {
+        let copies = linear_copies(region(64, 0, 1..3, (0, 0), (16, 8)));
+        assert_eq!(
+            copies,
+            vec![LinearCopy {
+                buffer: 64,
+                image: 512,
+                size: 1024,
+            }]
+        );
+    }
+
+    #[test]
+    fn empty_region() {
+        let copies = linear_copies(region(0, 0, 0..1, (0, 0), (0, 4)));
+        assert!(copies.is_empty());
+    }
+
+    #[test]
+    fn image_at_buffer_offset() {
+        // The image is bound to the second half of a shared buffer,
+        // copies stay relative to the start of its range.
+        let image_range = IMAGE_SIZE..2 * IMAGE_SIZE;
+        let copies = Image::linear_copies_impl(
+            EXTENT,
+            RGBA8,
+            &region(0, 0, 3..4, (0, 4), (16, 4)),
+            256,
+            &image_range,
+        );
+        assert_eq!(
+            copies,
+            Some(vec![LinearCopy {
+                buffer: 0,
+                image: 3 * 512 + 4 * 64,
+                size: 256,
+            }])
+        );
+    }
+
+    #[test]
+    fn image_range_overflow() {
+        // The underlying buffer has room for a fifth layer,
+        // but the range the image is bound to doesn't.
+        let image_range = 64..64 + IMAGE_SIZE;
+        let copies = Image::linear_copies_impl(
+            EXTENT,
+            RGBA8,
+            &region(0, 0, 3..5, (0, 0), (16, 8)),
+            !0,
+            &image_range,
+        );
+        assert_eq!(copies, None);
+    }
+
+    #[test]
+    fn buffer_overflow() {
+        let copies = Image::linear_copies_impl(
+            EXTENT,
+            RGBA8,
+            &region(64, 0, 0..1, (0, 0), (16, 8)),
+            512,
+            &(0..IMAGE_SIZE),
+        );
+        assert_eq!(copies, None);
+    }
+
+    #[test]
+    fn region_outside_of_image() {
+        let copies = Image::linear_copies_impl(
+            EXTENT,
+            RGBA8,
+            &region(0, 0, 0..1, (12, 0), (8, 1)),
+            !0,
+            &(0..IMAGE_SIZE),
+        );
+        assert_eq!(copies, None);
+    }
+
+    #[test]
+    fn huge_fence_timeout() {
+        let signal = FenceSignal::default();
+        assert!(!signal.wait(Some(time::Duration::from_nanos(0))));
+        signal.signal();
+        assert!(signal.wait(Some(time::Duration::from_secs(!0))));
+    }
+}
@@ -0,0 +1,280 @@
+//! Metal device hot-plug notifications on macOS.
+//!
+//! External GPUs can be attached and detached at any time, and the system asks
+//! applications to stop using a device before it's removed. On systems with
+//! automatic graphics switching, the device driving the displays also changes
+//! when the system switches between the integrated and the discrete GPU.
+
+use block::{Block, ConcreteBlock};
+use objc::runtime::{Object, BOOL, NO};
+use parking_lot::Mutex;
+
+use std::{
+    os::raw::c_void,
+    ptr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+type DeviceNotificationHandler = Block<(*mut Object, *mut Object), ()>;
+
+#[link(name = "Metal", kind = "framework")]
+extern "C" {
+    fn MTLCopyAllDevicesWithObserver(
+        observer: *mut *mut Object,
+        handler: &DeviceNotificationHandler,
+    ) -> *mut Object;
+    fn MTLRemoveDeviceObserver(observer: *mut Object);
+
+    static MTLDeviceWasAddedNotification: *mut Object;
+    static MTLDeviceRemovalRequestedNotification: *mut Object;
+    static MTLDeviceWasRemovedNotification: *mut Object;
+}
+
+type DisplayReconfigurationCallback =
+    extern "C" fn(display: u32, flags: u32, user_info: *mut c_void);
+
+/// `kCGDisplayBeginConfigurationFlag`, set when the callback is called before the change.
+const DISPLAY_BEGIN_CONFIGURATION_FLAG: u32 = 1;
+
+#[link(name = "CoreGraphics", kind = "framework")]
+extern "C" {
+    fn CGMainDisplayID() -> u32;
+    fn CGDirectDisplayCopyCurrentMetalDevice(display: u32) -> *mut Object;
+    fn CGDisplayRegisterReconfigurationCallback(
+        callback: DisplayReconfigurationCallback,
+        user_info: *mut c_void,
+    ) -> i32;
+    fn CGDisplayRemoveReconfigurationCallback(
+        callback: DisplayReconfigurationCallback,
+        user_info: *mut c_void,
+    ) -> i32;
+}
+
+/// Kind of a device change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DeviceEventKind {
+    /// A new device became available, adapters need to be enumerated again to use it.
+    Added,
+    /// The device is about to be removed, all its resources should be released.
+    RemovalRequested,
+    /// The device was removed and can no longer be used.
+    Removed,
+    /// The device now drives the main display, e.g. after the system switched between
+    /// the integrated and the discrete GPU. Presenting to windows on that display is
+    /// the most efficient with this device.
+    MainDisplayChanged,
+}
+
+/// Change of a single Metal device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DeviceEvent {
+    /// Registry identifier of the device, see `DeviceProperties::registry_id`.
+    pub registry_id: u64,
+    pub kind: DeviceEventKind,
+}
+
+/// Registration of a device notification handler, removed on drop.
+#[derive(Debug)]
+struct Registration(*mut Object);
+
+unsafe impl Send for Registration {}
+unsafe impl Sync for Registration {}
+
+impl Registration {
+    fn new<F>(callback: F) -> Self
+    where
+        F: Fn(DeviceEvent) + 'static,
+    {
+        let handler = ConcreteBlock::new(move |device: *mut Object, name: *mut Object| {
+            let kind = unsafe {
+                let is_name = |other: *mut Object| {
+                    let result: BOOL = msg_send![name, isEqualToString: other];
+                    result != NO
+                };
+                if is_name(MTLDeviceWasAddedNotification) {
+                    DeviceEventKind::Added
+                } else if is_name(MTLDeviceRemovalRequestedNotification) {
+                    DeviceEventKind::RemovalRequested
+                } else if is_name(MTLDeviceWasRemovedNotification) {
+                    DeviceEventKind::Removed
+                } else {
+                    return;
+                }
+            };
+            let registry_id: u64 = unsafe { msg_send![device, registryID] };
+            callback(DeviceEvent { registry_id, kind });
+        })
+        .copy();
+
+        let mut observer = ptr::null_mut();
+        unsafe {
+            let devices = MTLCopyAllDevicesWithObserver(&mut observer, &handler);
+            let () = msg_send![devices, release];
+        }
+        Registration(observer)
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        unsafe {
+            MTLRemoveDeviceObserver(self.0);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct DisplayWatch {
+    events: Arc<Mutex<Vec<DeviceEvent>>>,
+    /// Registry identifier of the device driving the main display.
+    main_device: Mutex<u64>,
+}
+
+impl DisplayWatch {
+    fn main_device() -> u64 {
+        unsafe {
+            let device = CGDirectDisplayCopyCurrentMetalDevice(CGMainDisplayID());
+            if device.is_null() {
+                return 0;
+            }
+            let registry_id: u64 = msg_send![device, registryID];
+            let () = msg_send![device, release];
+            registry_id
+        }
+    }
+}
+
+extern "C" fn display_reconfigured(_display: u32, flags: u32, user_info: *mut c_void) {
+    if flags & DISPLAY_BEGIN_CONFIGURATION_FLAG != 0 {
+        return;
+    }
+    let watch = unsafe { &*(user_info as *const DisplayWatch) };
+    let registry_id = DisplayWatch::main_device();
+    let mut main_device = watch.main_device.lock();
+    if registry_id != 0 && registry_id != *main_device {
+        *main_device = registry_id;
+        watch.events.lock().push(DeviceEvent {
+            registry_id,
+            kind: DeviceEventKind::MainDisplayChanged,
+        });
+    }
+}
+
+/// Registration of a display reconfiguration callback, removed on drop.
+#[derive(Debug)]
+struct DisplayRegistration(Box<DisplayWatch>);
+
+impl DisplayRegistration {
+    fn new(events: Arc<Mutex<Vec<DeviceEvent>>>) -> Self {
+        let watch = Box::new(DisplayWatch {
+            events,
+            main_device: Mutex::new(DisplayWatch::main_device()),
+        });
+        unsafe {
+            CGDisplayRegisterReconfigurationCallback(
+                display_reconfigured,
+                &*watch as *const DisplayWatch as *mut c_void,
+            );
+        }
+        DisplayRegistration(watch)
+    }
+}
+
+impl Drop for DisplayRegistration {
+    fn drop(&mut self) {
+        unsafe {
+            CGDisplayRemoveReconfigurationCallback(
+                display_reconfigured,
+                &*self.0 as *const DisplayWatch as *mut c_void,
+            );
+        }
+    }
+}
+
+/// Receiver of Metal device addition and removal events, and of the changes
+/// of the device driving the main display.
+///
+/// Available on macOS 10.13 and newer. The display changes are only received
+/// while the main run loop of the application runs.
+#[derive(Debug)]
+pub struct DeviceObserver {
+    _registration: Registration,
+    _display_registration: DisplayRegistration,
+    events: Arc<Mutex<Vec<DeviceEvent>>>,
+}
+
+impl DeviceObserver {
+    /// Start listening to device changes.
+    pub fn new() -> Self {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let handler_events = Arc::clone(&events);
+        DeviceObserver {
+            _registration: Registration::new(move |event| handler_events.lock().push(event)),
+            _display_registration: DisplayRegistration::new(Arc::clone(&events)),
+            events,
+        }
+    }
+
+    /// Take all the events received since the last call.
+    pub fn poll_events(&self) -> Vec<DeviceEvent> {
+        self.events.lock().drain(..).collect()
+    }
+}
+
+impl Default for DeviceObserver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Default)]
+struct RemovalState {
+    requested: AtomicBool,
+    removed: AtomicBool,
+}
+
+/// Tracks whether a single device is about to be removed or has been removed from the system.
+#[derive(Debug)]
+pub(crate) struct RemovalWatch {
+    _registration: Registration,
+    state: Arc<RemovalState>,
+}
+
+impl RemovalWatch {
+    pub(crate) fn new(registry_id: u64) -> Self {
+        let state = Arc::new(RemovalState::default());
+        let handler_state = Arc::clone(&state);
+        let registration = Registration::new(move |event| {
+            if event.registry_id != registry_id {
+                return;
+            }
+            match event.kind {
+                DeviceEventKind::Added | DeviceEventKind::MainDisplayChanged => {}
+                DeviceEventKind::RemovalRequested => {
+                    warn!("Device {:x} is requested to be removed", registry_id);
+                    handler_state.requested.store(true, Ordering::Release);
+                }
+                DeviceEventKind::Removed => {
+                    warn!("Device {:x} is removed", registry_id);
+                    handler_state.removed.store(true, Ordering::Release);
+                }
+            }
+        });
+        RemovalWatch {
+            _registration: registration,
+            state,
+        }
+    }
+
+    /// The device keeps working until it's removed, but should no longer be used.
+    pub(crate) fn is_removal_requested(&self) -> bool {
+        self.state.requested.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn is_removed(&self) -> bool {
+        self.state.removed.load(Ordering::Acquire)
+    }
+}
@@ -1,7 +1,7 @@
 use crate::internal::FastStorageMap;
 use crate::native::SerializableModuleInfo;
 use std::fmt;
-use std::sync::atomic::AtomicBool;
+use std::sync::{atomic::AtomicBool, Arc};
 
 pub(crate) struct BinaryArchive {
     pub(crate) inner: metal::BinaryArchive,
@@ -48,7 +48,7 @@ pub(crate) struct SerializablePipelineCache<'a> {
 
 pub struct PipelineCache {
     pub(crate) binary_archive: Option<BinaryArchive>,
-    pub(crate) spv_to_msl: SpvToMsl,
+    pub(crate) spv_to_msl: Arc<SpvToMsl>,
 }
 
 impl fmt::Debug for PipelineCache {
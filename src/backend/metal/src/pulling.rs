@@ -0,0 +1,688 @@
+//! Vertex pulling for layouts that Metal vertex descriptors can't express.
+//!
+//! Vertex formats without an `MTLVertexFormat` equivalent, and strides that aren't
+//! a multiple of 4, are supported by rewriting the vertex entry point: the original
+//! function is called from a new entry point that loads the attributes from the
+//! vertex buffers, bound as storage buffers, and unpacks them by hand.
+//!
+//! This costs a few ALU instructions per attribute component and disables the
+//! fixed-function vertex fetch, so it's only used when there is no other choice.
+//! The offsets that vertex buffers are bound with need to be multiples of 4.
+
+use crate::ResourceIndex;
+
+use hal::{
+    device::NagaShader,
+    format::{ChannelType, Format, SurfaceType},
+    pso::{self, VertexInputRate},
+};
+use naga::{
+    back::msl, Arena, BinaryOperator as Bo, Binding, BuiltIn, Constant, ConstantInner, Expression,
+    Function, FunctionArgument, GlobalVariable, Handle, MathFunction, ResourceBinding, ScalarKind,
+    ScalarValue, Statement, StorageAccess, StorageClass, StructMember, Type, TypeInner,
+};
+
+use std::mem;
+
+/// Descriptor set index of the vertex buffers in the rewritten shader.
+const PULLING_GROUP: u32 = !0;
+
+/// Location of a single component within an attribute element.
+#[derive(Clone, Copy, Debug)]
+struct Component {
+    /// Byte offset of the unit containing the component.
+    byte: u32,
+    /// Size of the unit in bytes.
+    size: u32,
+    /// Bit offset of the component within the unit.
+    shift: u32,
+    bits: u32,
+}
+
+impl Component {
+    const fn plain(index: u32, size: u32) -> Self {
+        Component {
+            byte: index * size,
+            size,
+            shift: 0,
+            bits: size * 8,
+        }
+    }
+
+    const fn packed(shift: u32, bits: u32) -> Self {
+        Component {
+            byte: 0,
+            size: 4,
+            shift,
+            bits,
+        }
+    }
+}
+
+/// Return the components of a vertex format in RGBA order.
+fn components(surface: SurfaceType) -> Option<Vec<Component>> {
+    use hal::format::SurfaceType as S;
+    let plain = |count: u32, size: u32| (0..count).map(|i| Component::plain(i, size)).collect();
+    Some(match surface {
+        S::R8 => plain(1, 1),
+        S::R8_G8 => plain(2, 1),
+        S::R8_G8_B8 => plain(3, 1),
+        S::R8_G8_B8_A8 => plain(4, 1),
+        S::B8_G8_R8 => vec![
+            Component::plain(2, 1),
+            Component::plain(1, 1),
+            Component::plain(0, 1),
+        ],
+        S::B8_G8_R8_A8 => vec![
+            Component::plain(2, 1),
+            Component::plain(1, 1),
+            Component::plain(0, 1),
+            Component::plain(3, 1),
+        ],
+        S::A8_B8_G8_R8 => vec![
+            Component::packed(0, 8),
+            Component::packed(8, 8),
+            Component::packed(16, 8),
+            Component::packed(24, 8),
+        ],
+        S::A2_B10_G10_R10 => vec![
+            Component::packed(0, 10),
+            Component::packed(10, 10),
+            Component::packed(20, 10),
+            Component::packed(30, 2),
+        ],
+        S::A2_R10_G10_B10 => vec![
+            Component::packed(20, 10),
+            Component::packed(10, 10),
+            Component::packed(0, 10),
+            Component::packed(30, 2),
+        ],
+        S::R16 => plain(1, 2),
+        S::R16_G16 => plain(2, 2),
+        S::R16_G16_B16 => plain(3, 2),
+        S::R16_G16_B16_A16 => plain(4, 2),
+        S::R32 => plain(1, 4),
+        S::R32_G32 => plain(2, 4),
+        S::R32_G32_B32 => plain(3, 4),
+        S::R32_G32_B32_A32 => plain(4, 4),
+        _ => return None,
+    })
+}
+
+/// Return true if the format can be fetched by vertex pulling.
+fn is_supported(format: Format) -> bool {
+    let base = format.base_format();
+    let bits = match components(base.0) {
+        Some(components) => components[0].bits,
+        None => return false,
+    };
+    match base.1 {
+        ChannelType::Sfloat => bits == 16 || bits == 32,
+        ChannelType::Ufloat => false,
+        _ => true,
+    }
+}
+
+#[derive(Debug)]
+struct PulledAttribute {
+    location: u32,
+    /// Index into `VertexPulling::buffers`.
+    buffer: usize,
+    offset: u32,
+    format: Format,
+}
+
+/// Vertex layout of a pipeline that fetches its attributes in the shader.
+#[derive(Debug)]
+pub(crate) struct VertexPulling {
+    /// Vertex buffers in the order they are bound, starting from the last buffer slot.
+    pub(crate) buffers: Vec<pso::VertexBufferDesc>,
+    attributes: Vec<PulledAttribute>,
+}
+
+impl VertexPulling {
+    /// Return true if the layout can't be expressed with a Metal vertex descriptor.
+    pub(crate) fn is_required(
+        buffers: &[pso::VertexBufferDesc],
+        attributes: &[pso::AttributeDesc],
+        stride_granularity: pso::ElemStride,
+        map_format: impl Fn(Format) -> bool,
+    ) -> bool {
+        buffers.iter().any(|vb| vb.stride % stride_granularity != 0)
+            || attributes
+                .iter()
+                .any(|attribute| !map_format(attribute.element.format))
+    }
+
+    pub(crate) fn new(
+        buffers: &[pso::VertexBufferDesc],
+        attributes: &[pso::AttributeDesc],
+    ) -> Result<Self, String> {
+        let mut pulling = VertexPulling {
+            buffers: Vec::new(),
+            attributes: Vec::new(),
+        };
+        for attribute in attributes {
+            if !is_supported(attribute.element.format) {
+                return Err(format!(
+                    "Vertex format {:?} is not supported",
+                    attribute.element.format
+                ));
+            }
+            let buffer = match pulling
+                .buffers
+                .iter()
+                .position(|vb| vb.binding == attribute.binding)
+            {
+                Some(index) => index,
+                None => {
+                    let vb = buffers
+                        .iter()
+                        .find(|vb| vb.binding == attribute.binding)
+                        .ok_or_else(|| {
+                            format!("No vertex buffer for binding {}", attribute.binding)
+                        })?;
+                    pulling.buffers.push(vb.clone());
+                    pulling.buffers.len() - 1
+                }
+            };
+            pulling.attributes.push(PulledAttribute {
+                location: attribute.location,
+                buffer,
+                offset: attribute.element.offset,
+                format: attribute.element.format,
+            });
+        }
+        Ok(pulling)
+    }
+
+    /// Rewrite the vertex entry point of a shader to fetch the attributes itself,
+    /// and extend the MSL options with the vertex buffer bindings.
+    pub(crate) fn transform(
+        &self,
+        shader: &NagaShader,
+        entry_point: &str,
+        options: &msl::Options,
+        max_buffers_per_stage: ResourceIndex,
+    ) -> Result<(NagaShader, msl::Options), String> {
+        let mut module = shader.module.clone();
+        self.rewrite(&mut module, entry_point)?;
+        let info = naga::valid::Validator::new(
+            naga::valid::ValidationFlags::empty(),
+            naga::valid::Capabilities::PUSH_CONSTANT,
+        )
+        .validate(&module)
+        .map_err(|e| format!("Naga validation of the pulled vertex shader: {}", e))?;
+
+        let mut options = options.clone();
+        for index in 0..self.buffers.len() {
+            let source = msl::BindSource {
+                stage: naga::ShaderStage::Vertex,
+                group: PULLING_GROUP,
+                binding: index as u32,
+            };
+            let target = msl::BindTarget {
+                buffer: Some((max_buffers_per_stage - 1 - index as ResourceIndex) as msl::Slot),
+                texture: None,
+                sampler: None,
+                mutable: false,
+            };
+            options.binding_map.insert(source, target);
+        }
+        Ok((NagaShader { module, info }, options))
+    }
+
+    fn rewrite(&self, module: &mut naga::Module, entry_point: &str) -> Result<(), String> {
+        let ep_index = module
+            .entry_points
+            .iter()
+            .position(|ep| ep.stage == naga::ShaderStage::Vertex && ep.name == entry_point)
+            .ok_or_else(|| format!("Vertex entry point {} is not found", entry_point))?;
+
+        let uint_ty = module.types.fetch_or_append(Type {
+            name: None,
+            inner: TypeInner::Scalar {
+                kind: ScalarKind::Uint,
+                width: 4,
+            },
+        });
+        let array_ty = module.types.fetch_or_append(Type {
+            name: None,
+            inner: TypeInner::Array {
+                base: uint_ty,
+                size: naga::ArraySize::Dynamic,
+                stride: 4,
+            },
+        });
+        let buffer_ty = module.types.fetch_or_append(Type {
+            name: Some("PulledVertexBuffer".to_string()),
+            inner: TypeInner::Struct {
+                level: naga::StructLevel::Root,
+                members: vec![StructMember {
+                    name: Some("data".to_string()),
+                    ty: array_ty,
+                    binding: None,
+                    offset: 0,
+                }],
+                span: 4,
+            },
+        });
+        let globals = (0..self.buffers.len())
+            .map(|index| {
+                module.global_variables.append(GlobalVariable {
+                    name: Some(format!("vertex_buffer{}", index)),
+                    class: StorageClass::Storage,
+                    binding: Some(ResourceBinding {
+                        group: PULLING_GROUP,
+                        binding: index as u32,
+                    }),
+                    ty: buffer_ty,
+                    init: None,
+                    storage_access: StorageAccess::LOAD,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // Demote the entry point to a regular function, called by the new entry point.
+        let ep = &mut module.entry_points[ep_index];
+        let mut original = mem::take(&mut ep.function);
+        let inputs = original
+            .arguments
+            .iter_mut()
+            .map(|arg| (arg.binding.take(), arg.ty))
+            .collect::<Vec<_>>();
+        let result = original.result.clone();
+        if let Some(ref mut result) = original.result {
+            result.binding = None;
+        }
+        original.name = Some(format!("{}_unpulled", entry_point));
+        let function = module.functions.append(original);
+
+        let mut builder = Builder {
+            types: &module.types,
+            constants: &mut module.constants,
+            uint_ty,
+            arguments: Vec::new(),
+            expressions: Arena::new(),
+            body: Vec::new(),
+            pulling: self,
+            globals,
+            buffer_offsets: vec![None; self.buffers.len()],
+        };
+        let mut call_arguments = Vec::with_capacity(inputs.len());
+        for (binding, ty) in inputs {
+            let value = match binding {
+                Some(ref binding) => builder.input(binding, ty)?,
+                None => match builder.types[ty].inner {
+                    TypeInner::Struct { ref members, .. } => {
+                        let members = members
+                            .iter()
+                            .map(|member| (member.binding.clone(), member.ty))
+                            .collect::<Vec<_>>();
+                        let mut components = Vec::with_capacity(members.len());
+                        for (binding, member_ty) in members {
+                            let binding = binding.ok_or("Vertex input member has no binding")?;
+                            components.push(builder.input(&binding, member_ty)?);
+                        }
+                        builder.emit(Expression::Compose { ty, components })
+                    }
+                    _ => return Err("Vertex input has no binding".to_string()),
+                },
+            };
+            call_arguments.push(value);
+        }
+
+        let result_expr = result
+            .as_ref()
+            .map(|_| builder.expressions.append(Expression::Call(function)));
+        builder.body.push(Statement::Call {
+            function,
+            arguments: call_arguments,
+            result: result_expr,
+        });
+        builder.body.push(Statement::Return { value: result_expr });
+
+        module.entry_points[ep_index].function = Function {
+            name: Some(entry_point.to_string()),
+            arguments: builder.arguments,
+            result,
+            local_variables: Arena::new(),
+            expressions: builder.expressions,
+            body: builder.body,
+        };
+        Ok(())
+    }
+}
+
+/// Generator of the body of the pulling entry point.
+struct Builder<'a> {
+    types: &'a Arena<Type>,
+    constants: &'a mut Arena<Constant>,
+    uint_ty: Handle<Type>,
+    arguments: Vec<FunctionArgument>,
+    expressions: Arena<Expression>,
+    body: Vec<Statement>,
+    pulling: &'a VertexPulling,
+    globals: Vec<Handle<GlobalVariable>>,
+    /// Byte offset of the current element in each vertex buffer.
+    buffer_offsets: Vec<Option<Handle<Expression>>>,
+}
+
+impl Builder<'_> {
+    /// Append an expression that needs to be evaluated in the body.
+    fn emit(&mut self, expression: Expression) -> Handle<Expression> {
+        let start = self.expressions.len();
+        let handle = self.expressions.append(expression);
+        self.body
+            .push(Statement::Emit(self.expressions.range_from(start)));
+        handle
+    }
+
+    fn constant(&mut self, value: ScalarValue) -> Handle<Expression> {
+        let constant = self.constants.fetch_or_append(Constant {
+            name: None,
+            specialization: None,
+            inner: ConstantInner::Scalar { width: 4, value },
+        });
+        self.expressions.append(Expression::Constant(constant))
+    }
+
+    fn uint(&mut self, value: u32) -> Handle<Expression> {
+        self.constant(ScalarValue::Uint(value as u64))
+    }
+
+    fn binary(
+        &mut self,
+        op: Bo,
+        left: Handle<Expression>,
+        right: Handle<Expression>,
+    ) -> Handle<Expression> {
+        self.emit(Expression::Binary { op, left, right })
+    }
+
+    fn binary_uint(&mut self, op: Bo, left: Handle<Expression>, right: u32) -> Handle<Expression> {
+        let right = self.uint(right);
+        self.binary(op, left, right)
+    }
+
+    fn cast(
+        &mut self,
+        expr: Handle<Expression>,
+        kind: ScalarKind,
+        convert: bool,
+    ) -> Handle<Expression> {
+        self.emit(Expression::As {
+            expr,
+            kind,
+            convert,
+        })
+    }
+
+    fn builtin(&mut self, builtin: BuiltIn, ty: Handle<Type>) -> Handle<Expression> {
+        let binding = Binding::BuiltIn(builtin);
+        let index = match self
+            .arguments
+            .iter()
+            .position(|arg| arg.binding.as_ref() == Some(&binding))
+        {
+            Some(index) => index,
+            None => {
+                self.arguments.push(FunctionArgument {
+                    name: None,
+                    ty,
+                    binding: Some(binding),
+                });
+                self.arguments.len() - 1
+            }
+        };
+        self.expressions
+            .append(Expression::FunctionArgument(index as u32))
+    }
+
+    /// Produce the value of an input of the original entry point.
+    fn input(&mut self, binding: &Binding, ty: Handle<Type>) -> Result<Handle<Expression>, String> {
+        let location = match *binding {
+            Binding::BuiltIn(builtin) => return Ok(self.builtin(builtin, ty)),
+            Binding::Location { location, .. } => location,
+        };
+        let (kind, size) = match self.types[ty].inner {
+            TypeInner::Scalar { kind, width: 4 } => (kind, None),
+            TypeInner::Vector {
+                size,
+                kind,
+                width: 4,
+            } => (kind, Some(size)),
+            ref other => return Err(format!("Unsupported vertex input type {:?}", other)),
+        };
+        let count = size.map_or(1, |size| size as usize);
+
+        let attribute = self
+            .pulling
+            .attributes
+            .iter()
+            .position(|attribute| attribute.location == location);
+        let mut components = match attribute {
+            Some(index) => self.fetch(index, kind)?,
+            None => Vec::new(),
+        };
+        components.truncate(count);
+        while components.len() < count {
+            let value = if components.len() == 3 { 1 } else { 0 };
+            let value = match kind {
+                ScalarKind::Float => ScalarValue::Float(value as f64),
+                ScalarKind::Sint => ScalarValue::Sint(value),
+                _ => ScalarValue::Uint(value as u64),
+            };
+            components.push(self.constant(value));
+        }
+
+        Ok(match size {
+            Some(_) => self.emit(Expression::Compose { ty, components }),
+            None => components[0],
+        })
+    }
+
+    /// Return the byte offset of the current element in a vertex buffer.
+    fn buffer_offset(&mut self, buffer: usize) -> Handle<Expression> {
+        if let Some(offset) = self.buffer_offsets[buffer] {
+            return offset;
+        }
+        let desc = &self.pulling.buffers[buffer];
+        let (stride, rate) = (desc.stride, desc.rate);
+        let uint_ty = self.uint_ty;
+        let offset = match rate {
+            _ if stride == 0 => self.uint(0),
+            VertexInputRate::Vertex => {
+                let index = self.builtin(BuiltIn::VertexIndex, uint_ty);
+                self.binary_uint(Bo::Multiply, index, stride)
+            }
+            VertexInputRate::Instance(0) => self.uint(0),
+            VertexInputRate::Instance(divisor) => {
+                let mut index = self.builtin(BuiltIn::InstanceIndex, uint_ty);
+                if divisor != 1 {
+                    index = self.binary_uint(Bo::Divide, index, divisor);
+                }
+                self.binary_uint(Bo::Multiply, index, stride)
+            }
+        };
+        self.buffer_offsets[buffer] = Some(offset);
+        offset
+    }
+
+    fn load_word(&mut self, buffer: usize, address: Handle<Expression>) -> Handle<Expression> {
+        let global = self
+            .expressions
+            .append(Expression::GlobalVariable(self.globals[buffer]));
+        let data = self.emit(Expression::AccessIndex {
+            base: global,
+            index: 0,
+        });
+        let index = self.binary_uint(Bo::ShiftRight, address, 2);
+        let pointer = self.emit(Expression::Access { base: data, index });
+        self.emit(Expression::Load { pointer })
+    }
+
+    /// Load `size` bytes at the given offset of the current element as an unsigned integer.
+    ///
+    /// `alignment` is the known alignment of the address, up to 4.
+    fn load_unit(
+        &mut self,
+        buffer: usize,
+        offset: u32,
+        alignment: u32,
+        size: u32,
+    ) -> Handle<Expression> {
+        let base = self.buffer_offset(buffer);
+        if alignment < size {
+            // The unit may straddle two words, assemble it from bytes.
+            let mut value = None;
+            for i in 0..size {
+                let byte = self.load_unit(buffer, offset + i, 1, 1);
+                let byte = match i {
+                    0 => byte,
+                    _ => self.binary_uint(Bo::ShiftLeft, byte, 8 * i),
+                };
+                value = Some(match value {
+                    Some(value) => self.binary(Bo::InclusiveOr, value, byte),
+                    None => byte,
+                });
+            }
+            return value.unwrap();
+        }
+
+        let address = match offset {
+            0 => base,
+            _ => self.binary_uint(Bo::Add, base, offset),
+        };
+        let word = self.load_word(buffer, address);
+        if size == 4 {
+            return word;
+        }
+        let byte_shift = self.binary_uint(Bo::And, address, 3);
+        let shift = self.binary_uint(Bo::ShiftLeft, byte_shift, 3);
+        let shifted = self.binary(Bo::ShiftRight, word, shift);
+        self.binary_uint(Bo::And, shifted, (1 << (8 * size)) - 1)
+    }
+
+    /// Fetch and convert the components of an attribute.
+    fn fetch(&mut self, index: usize, kind: ScalarKind) -> Result<Vec<Handle<Expression>>, String> {
+        let attribute = &self.pulling.attributes[index];
+        let (buffer, offset, format) = (attribute.buffer, attribute.offset, attribute.format);
+        let stride = self.pulling.buffers[buffer].stride;
+        let base = format.base_format();
+        let components = components(base.0).ok_or("Unsupported vertex format")?;
+
+        let mut values = Vec::with_capacity(components.len());
+        for component in components {
+            let offset = offset + component.byte;
+            // The largest power of two dividing both the stride and the offset.
+            let alignment = match stride | offset {
+                0 => 4,
+                bits => (bits & bits.wrapping_neg()).min(4),
+            };
+            let mut raw = self.load_unit(buffer, offset, alignment, component.size);
+            if component.shift != 0 {
+                raw = self.binary_uint(Bo::ShiftRight, raw, component.shift);
+            }
+            if component.bits != component.size * 8 {
+                raw = self.binary_uint(Bo::And, raw, (1 << component.bits) - 1);
+            }
+            let (value, value_kind) = self.convert(raw, component.bits, base.1)?;
+            values.push(match (value_kind, kind) {
+                _ if value_kind == kind => value,
+                (ScalarKind::Float, _) | (_, ScalarKind::Float) => self.cast(value, kind, true),
+                _ => self.cast(value, kind, false),
+            });
+        }
+        Ok(values)
+    }
+
+    fn sign_extend(&mut self, raw: Handle<Expression>, bits: u32) -> Handle<Expression> {
+        if bits == 32 {
+            return self.cast(raw, ScalarKind::Sint, false);
+        }
+        let shifted = self.binary_uint(Bo::ShiftLeft, raw, 32 - bits);
+        let signed = self.cast(shifted, ScalarKind::Sint, false);
+        self.binary_uint(Bo::ShiftRight, signed, 32 - bits)
+    }
+
+    fn float(&mut self, value: f64) -> Handle<Expression> {
+        self.constant(ScalarValue::Float(value))
+    }
+
+    /// Convert the raw bits of a component according to the channel type.
+    fn convert(
+        &mut self,
+        raw: Handle<Expression>,
+        bits: u32,
+        channel: ChannelType,
+    ) -> Result<(Handle<Expression>, ScalarKind), String> {
+        Ok(match channel {
+            ChannelType::Uint => (raw, ScalarKind::Uint),
+            ChannelType::Sint => (self.sign_extend(raw, bits), ScalarKind::Sint),
+            ChannelType::Uscaled => (self.cast(raw, ScalarKind::Float, true), ScalarKind::Float),
+            ChannelType::Sscaled => {
+                let signed = self.sign_extend(raw, bits);
+                (
+                    self.cast(signed, ScalarKind::Float, true),
+                    ScalarKind::Float,
+                )
+            }
+            ChannelType::Unorm | ChannelType::Srgb => {
+                let value = self.cast(raw, ScalarKind::Float, true);
+                let max = self.float(((1u64 << bits) - 1) as f64);
+                (self.binary(Bo::Divide, value, max), ScalarKind::Float)
+            }
+            ChannelType::Snorm => {
+                let signed = self.sign_extend(raw, bits);
+                let value = self.cast(signed, ScalarKind::Float, true);
+                let max = self.float(((1u64 << (bits - 1)) - 1) as f64);
+                let scaled = self.binary(Bo::Divide, value, max);
+                let min = self.float(-1.0);
+                let clamped = self.emit(Expression::Math {
+                    fun: MathFunction::Max,
+                    arg: scaled,
+                    arg1: Some(min),
+                    arg2: None,
+                });
+                (clamped, ScalarKind::Float)
+            }
+            ChannelType::Sfloat if bits == 32 => {
+                (self.cast(raw, ScalarKind::Float, false), ScalarKind::Float)
+            }
+            ChannelType::Sfloat if bits == 16 => (self.half_to_float(raw), ScalarKind::Float),
+            _ => return Err(format!("Unsupported channel type {:?}", channel)),
+        })
+    }
+
+    /// Expand a half-precision float, denormals are flushed to zero.
+    fn half_to_float(&mut self, raw: Handle<Expression>) -> Handle<Expression> {
+        let sign_bit = self.binary_uint(Bo::And, raw, 0x8000);
+        let sign = self.binary_uint(Bo::ShiftLeft, sign_bit, 16);
+        let exponent_bits = self.binary_uint(Bo::ShiftRight, raw, 10);
+        let exponent = self.binary_uint(Bo::And, exponent_bits, 0x1F);
+        let mantissa_bits = self.binary_uint(Bo::And, raw, 0x3FF);
+        let mantissa = self.binary_uint(Bo::ShiftLeft, mantissa_bits, 13);
+
+        let rebiased = self.binary_uint(Bo::Add, exponent, 127 - 15);
+        let normal_exponent = self.binary_uint(Bo::ShiftLeft, rebiased, 23);
+        let normal = self.binary(Bo::InclusiveOr, normal_exponent, mantissa);
+        let special = self.binary_uint(Bo::InclusiveOr, mantissa, 0x7F80_0000);
+
+        let is_special = self.binary_uint(Bo::Equal, exponent, 0x1F);
+        let magnitude = self.emit(Expression::Select {
+            condition: is_special,
+            accept: special,
+            reject: normal,
+        });
+        let is_zero = self.binary_uint(Bo::Equal, exponent, 0);
+        let zero = self.uint(0);
+        let magnitude = self.emit(Expression::Select {
+            condition: is_zero,
+            accept: zero,
+            reject: magnitude,
+        });
+        let bits = self.binary(Bo::InclusiveOr, sign, magnitude);
+        self.cast(bits, ScalarKind::Float, false)
+    }
+}
@@ -1,12 +1,16 @@
 use crate::{
-    command::IndexBuffer, native::RasterizerState, BufferPtr, ResourceIndex, ResourcePtr,
-    SamplerPtr, TexturePtr,
+    command::IndexBuffer,
+    counters::CounterSampleBufferPtr,
+    native::{DescriptorArgumentPoolInner, PoolResourceIndex, RasterizerState},
+    BufferPtr, ResourceIndex, ResourcePtr, SamplerPtr, TexturePtr,
 };
 
+use cocoa_foundation::foundation::NSUInteger;
 use hal;
 use metal;
+use parking_lot::RwLock;
 
-use std::{fmt::Debug, ops::Range};
+use std::{fmt::Debug, ops::Range, sync::Arc};
 
 pub type CacheResourceIndex = u32;
 
@@ -15,6 +19,7 @@ pub trait Resources: Debug {
     type BufferArray: Debug;
     type TextureArray: Debug;
     type SamplerArray: Debug;
+    type ResourceArray: Debug;
     type DepthStencil: Debug;
     type RenderPipeline: Debug;
     type ComputePipeline: Debug;
@@ -34,6 +39,7 @@ impl Resources for Own {
     type BufferArray = Range<CacheResourceIndex>;
     type TextureArray = Range<CacheResourceIndex>;
     type SamplerArray = Range<CacheResourceIndex>;
+    type ResourceArray = Vec<ResourcePtr>;
     type DepthStencil = metal::DepthStencilState;
     type RenderPipeline = metal::RenderPipelineState;
     type ComputePipeline = metal::ComputePipelineState;
@@ -47,6 +53,7 @@ impl<'a> Resources for &'a Ref {
     type BufferArray = (&'a [Option<BufferPtr>], &'a [hal::buffer::Offset]);
     type TextureArray = &'a [Option<TexturePtr>];
     type SamplerArray = &'a [Option<SamplerPtr>];
+    type ResourceArray = &'a [ResourcePtr];
     type DepthStencil = &'a metal::DepthStencilStateRef;
     type RenderPipeline = &'a metal::RenderPipelineStateRef;
     type ComputePipeline = &'a metal::ComputePipelineStateRef;
@@ -92,10 +99,16 @@ pub enum RenderCommand<R: Resources> {
         samplers: R::SamplerArray,
     },
     BindPipeline(R::RenderPipeline),
-    UseResource {
-        resource: ResourcePtr,
+    UseResources {
+        resources: R::ResourceArray,
         usage: metal::MTLResourceUsage,
     },
+    /// Use the resources of an update-after-bind descriptor set,
+    /// as they are at the time the command is encoded.
+    UseArgumentResources {
+        pool: Arc<RwLock<DescriptorArgumentPoolInner>>,
+        range: Range<PoolResourceIndex>,
+    },
     Draw {
         primitive_type: metal::MTLPrimitiveType,
         vertices: Range<hal::VertexCount>,
@@ -126,6 +139,22 @@ pub enum RenderCommand<R: Resources> {
         name: R::Marker,
     },
     PopDebugGroup,
+    BindTileBuffer {
+        index: ResourceIndex,
+        buffer: BufferPtr,
+        offset: hal::buffer::Offset,
+    },
+    SetThreadgroupMemoryLength {
+        index: ResourceIndex,
+        length: u32,
+    },
+    DispatchTile {
+        size: metal::MTLSize,
+    },
+    SampleCounters {
+        buffer: CounterSampleBufferPtr,
+        index: NSUInteger,
+    },
 }
 
 #[derive(Clone, Debug)]
@@ -183,10 +212,16 @@ pub enum ComputeCommand<R: Resources> {
         samplers: R::SamplerArray,
     },
     BindPipeline(R::ComputePipeline),
-    UseResource {
-        resource: ResourcePtr,
+    UseResources {
+        resources: R::ResourceArray,
         usage: metal::MTLResourceUsage,
     },
+    /// Use the resources of an update-after-bind descriptor set,
+    /// as they are at the time the command is encoded.
+    UseArgumentResources {
+        pool: Arc<RwLock<DescriptorArgumentPoolInner>>,
+        range: Range<PoolResourceIndex>,
+    },
     Dispatch {
         wg_size: metal::MTLSize,
         wg_count: metal::MTLSize,
@@ -285,7 +320,11 @@ impl Own {
                 },
             },
             BindPipeline(pso) => BindPipeline(pso.to_owned()),
-            UseResource { resource, usage } => UseResource { resource, usage },
+            UseResources { resources, usage } => UseResources {
+                resources: resources.to_vec(),
+                usage,
+            },
+            UseArgumentResources { pool, range } => UseArgumentResources { pool, range },
             Draw {
                 primitive_type,
                 vertices,
@@ -335,6 +374,20 @@ impl Own {
                 name: name.to_owned(),
             },
             PopDebugGroup => PopDebugGroup,
+            BindTileBuffer {
+                index,
+                buffer,
+                offset,
+            } => BindTileBuffer {
+                index,
+                buffer,
+                offset,
+            },
+            SetThreadgroupMemoryLength { index, length } => {
+                SetThreadgroupMemoryLength { index, length }
+            }
+            DispatchTile { size } => DispatchTile { size },
+            SampleCounters { buffer, index } => SampleCounters { buffer, index },
         }
     }
 
@@ -383,7 +436,11 @@ impl Own {
                 },
             },
             BindPipeline(pso) => BindPipeline(pso.to_owned()),
-            UseResource { resource, usage } => UseResource { resource, usage },
+            UseResources { resources, usage } => UseResources {
+                resources: resources.to_vec(),
+                usage,
+            },
+            UseArgumentResources { pool, range } => UseArgumentResources { pool, range },
             Dispatch { wg_size, wg_count } => Dispatch { wg_size, wg_count },
             DispatchIndirect {
                 wg_size,
@@ -429,14 +486,19 @@ impl Own {
                 samplers.end += self.samplers.len() as CacheResourceIndex;
             }
             BindPipeline(..)
-            | UseResource { .. }
+            | UseResources { .. }
+            | UseArgumentResources { .. }
             | Draw { .. }
             | DrawIndexed { .. }
             | DrawIndirect { .. }
             | DrawIndexedIndirect { .. }
             | InsertDebugMarker { .. }
             | PushDebugMarker { .. }
-            | PopDebugGroup => {}
+            | PopDebugGroup
+            | BindTileBuffer { .. }
+            | SetThreadgroupMemoryLength { .. }
+            | DispatchTile { .. }
+            | SampleCounters { .. } => {}
         }
     }
 
@@ -463,7 +525,11 @@ impl Own {
                 samplers.start += self.samplers.len() as CacheResourceIndex;
                 samplers.end += self.samplers.len() as CacheResourceIndex;
             }
-            BindPipeline(..) | UseResource { .. } | Dispatch { .. } | DispatchIndirect { .. } => {}
+            BindPipeline(..)
+            | UseResources { .. }
+            | UseArgumentResources { .. }
+            | Dispatch { .. }
+            | DispatchIndirect { .. } => {}
         }
     }
 
@@ -0,0 +1,231 @@
+//! Opt-in GPU timing of individual passes.
+//!
+//! Every native encoder started by an immediately recorded command buffer gets
+//! a pair of timestamp samples attached to it, which are resolved once the
+//! command buffer has completed execution on the GPU.
+
+use cocoa_foundation::foundation::NSUInteger;
+use foreign_types::ForeignTypeRef;
+use metal::NSRange;
+use objc::{rc::autoreleasepool, runtime::Object};
+
+use std::{ffi::CStr, os::raw::c_char, ptr, slice};
+
+/// Equivalent of `MTLCounterDontSample`.
+const DONT_SAMPLE: NSUInteger = NSUInteger::max_value();
+/// Maximum number of passes that are timed within a single command buffer.
+const MAX_TIMED_PASSES: usize = 64;
+
+/// Kind of the pass that was timed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PassKind {
+    Render,
+    Compute,
+    Blit,
+}
+
+/// GPU execution time of a single pass.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PassTiming {
+    pub kind: PassKind,
+    /// Debug label that was active when the pass was started.
+    pub label: String,
+    /// Duration in GPU timestamp units, see `Queue::timestamp_period`.
+    pub duration: u64,
+}
+
+#[derive(Debug)]
+pub(crate) struct PassTimer {
+    sample_buffer: *mut Object,
+    command_buffer: Option<metal::CommandBuffer>,
+    passes: Vec<(PassKind, String)>,
+}
+
+impl Drop for PassTimer {
+    fn drop(&mut self) {
+        unsafe {
+            let () = msg_send![self.sample_buffer, release];
+        }
+    }
+}
+
+impl PassTimer {
+    /// Create a timer for the device, returns `None` if timestamp counters are not available.
+    pub(crate) fn new(device: &metal::DeviceRef) -> Option<Self> {
+        unsafe {
+            let counter_sets: *mut Object = msg_send![device, counterSets];
+            if counter_sets.is_null() {
+                return None;
+            }
+            let count: NSUInteger = msg_send![counter_sets, count];
+            let timestamp_set = (0..count)
+                .map(|i| -> *mut Object { msg_send![counter_sets, objectAtIndex: i] })
+                .find(|&set| {
+                    let name: *mut Object = msg_send![set, name];
+                    let utf8: *const c_char = msg_send![name, UTF8String];
+                    CStr::from_ptr(utf8).to_bytes() == b"timestamp"
+                })?;
+
+            let descriptor: *mut Object = msg_send![class!(MTLCounterSampleBufferDescriptor), new];
+            let () = msg_send![descriptor, setCounterSet: timestamp_set];
+            let () = msg_send![descriptor, setStorageMode: metal::MTLStorageMode::Shared];
+            let () = msg_send![descriptor, setSampleCount: (2 * MAX_TIMED_PASSES) as NSUInteger];
+            let mut error: *mut Object = ptr::null_mut();
+            let sample_buffer: *mut Object = msg_send![
+                device,
+                newCounterSampleBufferWithDescriptor: descriptor
+                error: &mut error
+            ];
+            let () = msg_send![descriptor, release];
+
+            if sample_buffer.is_null() {
+                warn!("Unable to create a counter sample buffer for pass timing");
+                return None;
+            }
+            Some(PassTimer {
+                sample_buffer,
+                command_buffer: None,
+                passes: Vec::new(),
+            })
+        }
+    }
+
+    /// Start timing the passes of a new command buffer.
+    pub(crate) fn begin(&mut self, command_buffer: &metal::CommandBufferRef) {
+        self.command_buffer = Some(command_buffer.to_owned());
+        self.passes.clear();
+    }
+
+    fn next_sample_index(&mut self, kind: PassKind, label: &str) -> Option<NSUInteger> {
+        if self.passes.len() == MAX_TIMED_PASSES {
+            debug!(
+                "Pass timing capacity exceeded, skipping {:?} {:?}",
+                kind, label
+            );
+            return None;
+        }
+        self.passes.push((kind, label.to_string()));
+        Some(2 * (self.passes.len() - 1) as NSUInteger)
+    }
+
+    /// Attach the sample buffer to a render pass descriptor about to be encoded.
+    pub(crate) fn attach_render(
+        &mut self,
+        descriptor: &metal::RenderPassDescriptorRef,
+        label: &str,
+    ) {
+        let index = self.next_sample_index(PassKind::Render, label);
+        unsafe {
+            let attachments: *mut Object = msg_send![descriptor, sampleBufferAttachments];
+            let attachment: *mut Object =
+                msg_send![attachments, objectAtIndexedSubscript: 0 as NSUInteger];
+            match index {
+                Some(index) => {
+                    let () = msg_send![attachment, setSampleBuffer: self.sample_buffer];
+                    let () = msg_send![attachment, setStartOfVertexSampleIndex: index];
+                    let () = msg_send![attachment, setEndOfVertexSampleIndex: DONT_SAMPLE];
+                    let () = msg_send![attachment, setStartOfFragmentSampleIndex: DONT_SAMPLE];
+                    let () = msg_send![attachment, setEndOfFragmentSampleIndex: index + 1];
+                }
+                None => {
+                    let () = msg_send![attachment, setSampleBuffer: ptr::null_mut::<Object>()];
+                }
+            }
+        }
+    }
+
+    /// Detach the sample buffer from a render pass descriptor before it gets reused.
+    pub(crate) fn detach_render(&self, descriptor: &metal::RenderPassDescriptorRef) {
+        unsafe {
+            let attachments: *mut Object = msg_send![descriptor, sampleBufferAttachments];
+            let attachment: *mut Object =
+                msg_send![attachments, objectAtIndexedSubscript: 0 as NSUInteger];
+            let () = msg_send![attachment, setSampleBuffer: ptr::null_mut::<Object>()];
+        }
+    }
+
+    /// Start a new timed compute encoder.
+    pub(crate) fn new_compute_encoder<'a>(
+        &mut self,
+        command_buffer: &'a metal::CommandBufferRef,
+        label: &str,
+    ) -> &'a metal::ComputeCommandEncoderRef {
+        let index = match self.next_sample_index(PassKind::Compute, label) {
+            Some(index) => index,
+            None => return command_buffer.new_compute_command_encoder(),
+        };
+        unsafe {
+            let descriptor: *mut Object =
+                msg_send![class!(MTLComputePassDescriptor), computePassDescriptor];
+            self.encoder_attachment(descriptor, index);
+            let encoder: *mut metal::MTLComputeCommandEncoder =
+                msg_send![command_buffer, computeCommandEncoderWithDescriptor: descriptor];
+            metal::ComputeCommandEncoderRef::from_ptr(encoder)
+        }
+    }
+
+    /// Start a new timed blit encoder.
+    pub(crate) fn new_blit_encoder<'a>(
+        &mut self,
+        command_buffer: &'a metal::CommandBufferRef,
+        label: &str,
+    ) -> &'a metal::BlitCommandEncoderRef {
+        let index = match self.next_sample_index(PassKind::Blit, label) {
+            Some(index) => index,
+            None => return command_buffer.new_blit_command_encoder(),
+        };
+        unsafe {
+            let descriptor: *mut Object =
+                msg_send![class!(MTLBlitPassDescriptor), blitPassDescriptor];
+            self.encoder_attachment(descriptor, index);
+            let encoder: *mut metal::MTLBlitCommandEncoder =
+                msg_send![command_buffer, blitCommandEncoderWithDescriptor: descriptor];
+            metal::BlitCommandEncoderRef::from_ptr(encoder)
+        }
+    }
+
+    /// Configure the first sample buffer attachment of a compute or blit pass descriptor.
+    unsafe fn encoder_attachment(&self, descriptor: *mut Object, index: NSUInteger) {
+        let attachments: *mut Object = msg_send![descriptor, sampleBufferAttachments];
+        let attachment: *mut Object =
+            msg_send![attachments, objectAtIndexedSubscript: 0 as NSUInteger];
+        let () = msg_send![attachment, setSampleBuffer: self.sample_buffer];
+        let () = msg_send![attachment, setStartOfEncoderSampleIndex: index];
+        let () = msg_send![attachment, setEndOfEncoderSampleIndex: index + 1];
+    }
+
+    /// Resolve the timings, returns `None` if the command buffer hasn't completed yet.
+    pub(crate) fn resolve(&self) -> Option<Vec<PassTiming>> {
+        let command_buffer = self.command_buffer.as_ref()?;
+        if command_buffer.status() != metal::MTLCommandBufferStatus::Completed {
+            return None;
+        }
+        if self.passes.is_empty() {
+            return Some(Vec::new());
+        }
+
+        let count = 2 * self.passes.len();
+        autoreleasepool(|| unsafe {
+            let data: *mut Object = msg_send![
+                self.sample_buffer,
+                resolveCounterRange: NSRange::new(0, count as u64)
+            ];
+            if data.is_null() {
+                return None;
+            }
+            let bytes: *const u64 = msg_send![data, bytes];
+            let stamps = slice::from_raw_parts(bytes, count);
+            Some(
+                self.passes
+                    .iter()
+                    .zip(stamps.chunks(2))
+                    .map(|(&(kind, ref label), pair)| PassTiming {
+                        kind,
+                        label: label.clone(),
+                        duration: pair[1].saturating_sub(pair[0]),
+                    })
+                    .collect(),
+            )
+        })
+    }
+}
@@ -7,13 +7,86 @@ use crate::{
 use hal::{format, image, window as w};
 
 use crate::CGRect;
+use cocoa_foundation::{
+    base::{id, nil},
+    foundation::{NSString, NSUInteger},
+};
 use objc::rc::autoreleasepool;
-use objc::runtime::Object;
-use parking_lot::Mutex;
+use objc::runtime::{Class, Object, BOOL, NO, YES};
+use parking_lot::{Condvar, Mutex};
 
 use std::borrow::Borrow;
+use std::os::raw::c_void;
 use std::ptr::NonNull;
-use std::thread;
+use std::sync::Arc;
+use std::{thread, time};
+
+#[link(name = "CoreGraphics", kind = "framework")]
+extern "C" {
+    fn CGColorSpaceCreateWithName(name: id) -> *mut c_void;
+    fn CGColorSpaceRelease(space: *mut c_void);
+}
+
+/// Static HDR10 metadata of the presented content, as defined by SMPTE ST 2086
+/// for the mastering display, and CTA-861.3 for the content light levels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hdr10Metadata {
+    /// CIE 1931 xy chromaticity coordinates of the red, green, and blue
+    /// primaries of the mastering display.
+    pub display_primaries: [[f32; 2]; 3],
+    /// CIE 1931 xy chromaticity coordinates of the mastering display white point.
+    pub white_point: [f32; 2],
+    /// Maximum luminance of the mastering display, in nits.
+    pub max_mastering_luminance: f32,
+    /// Minimum luminance of the mastering display, in nits.
+    pub min_mastering_luminance: f32,
+    /// Maximum light level of any pixel of the content, in nits.
+    pub max_content_light_level: u16,
+    /// Maximum average light level of any frame of the content, in nits.
+    pub max_frame_average_light_level: u16,
+    /// Luminance in nits that corresponds to the linear value 1.0.
+    pub optical_output_scale: f32,
+}
+
+impl Hdr10Metadata {
+    /// Mastering display colour volume, big-endian as in the HEVC SEI message.
+    fn display_info(&self) -> [u8; 24] {
+        let chromaticity = |value: f32| ((value * 50_000.0).round() as u16).to_be_bytes();
+        let luminance = |value: f32| ((value * 10_000.0).round() as u32).to_be_bytes();
+        let mut data = [0u8; 24];
+        // the SEI message lists green, blue, then red
+        for (i, &primary) in [1, 2, 0].iter().enumerate() {
+            let [x, y] = self.display_primaries[primary];
+            data[i * 4..i * 4 + 2].copy_from_slice(&chromaticity(x));
+            data[i * 4 + 2..i * 4 + 4].copy_from_slice(&chromaticity(y));
+        }
+        data[12..14].copy_from_slice(&chromaticity(self.white_point[0]));
+        data[14..16].copy_from_slice(&chromaticity(self.white_point[1]));
+        data[16..20].copy_from_slice(&luminance(self.max_mastering_luminance));
+        data[20..24].copy_from_slice(&luminance(self.min_mastering_luminance));
+        data
+    }
+
+    /// Content light level information, big-endian as in the HEVC SEI message.
+    fn content_info(&self) -> [u8; 4] {
+        let mut data = [0u8; 4];
+        data[..2].copy_from_slice(&self.max_content_light_level.to_be_bytes());
+        data[2..].copy_from_slice(&self.max_frame_average_light_level.to_be_bytes());
+        data
+    }
+}
+
+/// How the system maps the presented content to the capabilities of the display.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToneMapping {
+    /// Standard dynamic range, no tone mapping is applied.
+    None,
+    /// Content is encoded with the PQ transfer function and tone mapped
+    /// according to the provided metadata.
+    Hdr10(Hdr10Metadata),
+    /// Content is encoded with the HLG transfer function.
+    Hlg,
+}
 
 #[derive(Debug)]
 pub struct Surface {
@@ -22,6 +95,13 @@ pub struct Surface {
     swapchain_format: metal::MTLPixelFormat,
     swapchain_format_desc: format::FormatDesc,
     main_thread_id: thread::ThreadId,
+    /// Number of drawables the layer is configured to provide.
+    image_count: u32,
+    /// Drawables that are taken from the layer and not returned to it yet.
+    pub(crate) drawables: Arc<DrawableTracker>,
+    can_set_next_drawable_timeout: bool,
+    /// Identifier of the last presentation that was displayed.
+    pub(crate) present_tracker: Arc<PresentTracker>,
     // Useful for UI-intensive applications that are sensitive to
     // window resizing.
     pub present_with_transaction: bool,
@@ -43,6 +123,10 @@ impl Surface {
                 aspects: format::Aspects::empty(),
             },
             main_thread_id: thread::current().id(),
+            image_count: 0,
+            drawables: Arc::new(DrawableTracker::default()),
+            can_set_next_drawable_timeout: false,
+            present_tracker: Arc::new(PresentTracker::default()),
             present_with_transaction: false,
         }
     }
@@ -53,7 +137,7 @@ impl Surface {
         }
     }
 
-    fn configure(&self, shared: &Shared, config: &w::SwapchainConfig) -> metal::MTLPixelFormat {
+    fn configure(&mut self, shared: &Shared, config: &w::SwapchainConfig) -> metal::MTLPixelFormat {
         info!("build swapchain {:?}", config);
 
         let caps = &shared.private_caps;
@@ -76,11 +160,13 @@ impl Surface {
 
         match config.composite_alpha_mode {
             w::CompositeAlphaMode::OPAQUE => render_layer.set_opaque(true),
-            w::CompositeAlphaMode::POSTMULTIPLIED => render_layer.set_opaque(false),
+            w::CompositeAlphaMode::PREMULTIPLIED | w::CompositeAlphaMode::POSTMULTIPLIED => {
+                render_layer.set_opaque(false)
+            }
             _ => (),
         }
 
-        let device_raw = shared.device.lock();
+        let device_raw = &shared.device;
         unsafe {
             // On iOS, unless the user supplies a view with a CAMetalLayer, we
             // create one as a sublayer. However, when the view changes size,
@@ -110,10 +196,82 @@ impl Surface {
                 let () = msg_send![*render_layer, setDisplaySyncEnabled: display_sync];
             }
         };
+        drop(render_layer);
 
+        self.image_count = config.image_count;
+        self.can_set_next_drawable_timeout = can_set_next_drawable_timeout;
         mtl_format
     }
 
+    /// Set the tone mapping of the presented images, for displays that support
+    /// extended dynamic range. Images need to be in a format with enough precision,
+    /// such as `Rgba16Sfloat` or `A2b10g10r10Unorm`.
+    ///
+    /// Requires macOS 10.15 or newer, returns `false` if the tone mapping
+    /// can't be applied to the layer.
+    pub fn set_tone_mapping(&self, tone_mapping: ToneMapping) -> bool {
+        let metadata_class = match Class::get("CAEDRMetadata") {
+            Some(class) => class,
+            None => return false,
+        };
+        let render_layer = self.render_layer.lock();
+        unsafe {
+            let layer = render_layer.as_ref();
+            let supported: BOOL = msg_send![layer, respondsToSelector: sel!(setEDRMetadata:)];
+            if supported == NO {
+                return false;
+            }
+
+            let (metadata, color_space_name): (id, Option<&str>) = match tone_mapping {
+                ToneMapping::None => (nil, None),
+                ToneMapping::Hdr10(ref hdr10) => {
+                    let display_info = hdr10.display_info();
+                    let content_info = hdr10.content_info();
+                    let display_data: id = msg_send![class!(NSData),
+                        dataWithBytes: display_info.as_ptr() as *const c_void
+                        length: display_info.len() as NSUInteger
+                    ];
+                    let content_data: id = msg_send![class!(NSData),
+                        dataWithBytes: content_info.as_ptr() as *const c_void
+                        length: content_info.len() as NSUInteger
+                    ];
+                    let metadata: id = msg_send![metadata_class,
+                        HDR10MetadataWithDisplayInfo: display_data
+                        contentInfo: content_data
+                        opticalOutputScale: hdr10.optical_output_scale
+                    ];
+                    (metadata, Some("kCGColorSpaceITUR_2100_PQ"))
+                }
+                ToneMapping::Hlg => {
+                    let metadata: id = msg_send![metadata_class, HLGMetadata];
+                    (metadata, Some("kCGColorSpaceITUR_2100_HLG"))
+                }
+            };
+            if metadata.is_null() && tone_mapping != ToneMapping::None {
+                warn!("Unable to create EDR metadata for {:?}", tone_mapping);
+                return false;
+            }
+
+            let color_space = match color_space_name {
+                Some(name) => {
+                    let name = NSString::alloc(nil).init_str(name);
+                    let color_space = CGColorSpaceCreateWithName(name);
+                    let () = msg_send![name, release];
+                    color_space
+                }
+                None => std::ptr::null_mut(),
+            };
+            let () = msg_send![layer, setColorspace: color_space];
+            if !color_space.is_null() {
+                CGColorSpaceRelease(color_space);
+            }
+            let edr = if metadata.is_null() { NO } else { YES };
+            let () = msg_send![layer, setWantsExtendedDynamicRangeContent: edr];
+            let () = msg_send![layer, setEDRMetadata: metadata];
+        }
+        true
+    }
+
     fn dimensions(&self) -> w::Extent2D {
         let (size, scale): (metal::CGSize, metal::CGFloat) = match self.view {
             Some(view) if !cfg!(target_os = "macos") => unsafe {
@@ -147,12 +305,100 @@ impl Surface {
     }
 }
 
+/// Identifier of the last displayed presentation, advanced by the presented
+/// handlers of the drawables.
+#[derive(Debug, Default)]
+pub struct PresentTracker {
+    presented_id: Mutex<u64>,
+    condvar: Condvar,
+}
+
+impl PresentTracker {
+    pub(crate) fn signal(&self, present_id: u64) {
+        let mut presented_id = self.presented_id.lock();
+        if present_id > *presented_id {
+            *presented_id = present_id;
+            self.condvar.notify_all();
+        }
+    }
+
+    /// Block until the presentation is displayed, or the timeout expires.
+    /// Returns whether the presentation was displayed.
+    pub(crate) fn wait(&self, present_id: u64, timeout: Option<time::Duration>) -> bool {
+        let mut presented_id = self.presented_id.lock();
+        native::wait_while(&self.condvar, &mut presented_id, timeout, |&id| {
+            id < present_id
+        });
+        *presented_id >= present_id
+    }
+}
+
+#[derive(Debug, Default)]
+struct DrawableCount {
+    /// Drawables held by the application, queued for display or displayed.
+    in_use: u32,
+    /// A presented drawable is displayed, and is only returned to the layer
+    /// when the next one replaces it.
+    displaying: bool,
+}
+
+/// Number of drawables that are taken from the layer and not returned to it yet,
+/// either held by the application or by the compositor.
+#[derive(Debug, Default)]
+pub(crate) struct DrawableTracker {
+    count: Mutex<DrawableCount>,
+    condvar: Condvar,
+}
+
+impl DrawableTracker {
+    fn acquire(&self) {
+        self.count.lock().in_use += 1;
+    }
+
+    fn release(&self, count: &mut DrawableCount) {
+        count.in_use -= 1;
+        self.condvar.notify_all();
+    }
+
+    /// A presented drawable is done with, which returns the one it replaced on the
+    /// display to the layer, or itself if the compositor `skipped` it.
+    pub(crate) fn presented(&self, skipped: bool) {
+        let mut count = self.count.lock();
+        if skipped || count.displaying {
+            self.release(&mut count);
+        } else {
+            count.displaying = true;
+        }
+    }
+
+    /// Block until less than `limit` drawables are in use, or the timeout expires.
+    /// Returns whether a drawable is available.
+    fn wait_available(&self, limit: u32, timeout: time::Duration) -> bool {
+        let mut count = self.count.lock();
+        native::wait_while(&self.condvar, &mut count, Some(timeout), |count| {
+            count.in_use >= limit
+        });
+        count.in_use < limit
+    }
+}
+
 #[derive(Debug)]
 pub struct SwapchainImage {
     image: native::Image,
     view: native::ImageView,
     pub(crate) drawable: metal::MetalDrawable,
     pub(crate) present_with_transaction: bool,
+    /// The drawable is handed to the compositor, which returns it to the layer.
+    pub(crate) presented: bool,
+    drawables: Arc<DrawableTracker>,
+}
+
+impl Drop for SwapchainImage {
+    fn drop(&mut self) {
+        if !self.presented {
+            self.drawables.release(&mut self.drawables.count.lock());
+        }
+    }
 }
 
 unsafe impl Send for SwapchainImage {}
@@ -198,8 +444,11 @@ impl w::Surface<Backend> for Surface {
                 w::PresentMode::FIFO
             },
             composite_alpha_modes: w::CompositeAlphaMode::OPAQUE
+                | w::CompositeAlphaMode::PREMULTIPLIED
                 | w::CompositeAlphaMode::POSTMULTIPLIED
                 | w::CompositeAlphaMode::INHERIT,
+            current_transform: w::SurfaceTransform::IDENTITY,
+            transforms: w::SurfaceTransform::IDENTITY,
             //Note: this is hardcoded in `CAMetalLayer` documentation
             image_count: if can_set_maximum_drawables_count {
                 2..=3
@@ -221,6 +470,12 @@ impl w::Surface<Backend> for Surface {
             //| image::Usage::SAMPLED
             //| image::Usage::TRANSFER_SRC
             //| image::Usage::TRANSFER_DST,
+            swapchain_maintenance: true,
+            present_wait: if device_caps.os_is_mac {
+                device_caps.has_version_at_least(10, 15)
+            } else {
+                device_caps.has_version_at_least(10, 3)
+            },
         }
     }
 
@@ -258,16 +513,45 @@ impl w::PresentationSurface<Backend> for Surface {
         self.swapchain_format = metal::MTLPixelFormat::Invalid;
     }
 
+    /// Acquire the next drawable of the layer.
+    ///
+    /// `CAMetalLayer` only supports a fixed timeout of one second, so a finite
+    /// timeout is waited for by tracking the drawables that are held by the
+    /// application and by the compositor, which are returned to the layer when
+    /// the presented handlers of the drawables are called. `!0` blocks in the
+    /// layer until a drawable becomes available.
     unsafe fn acquire_image(
         &mut self,
-        _timeout_ns: u64, //TODO: use the timeout
+        timeout_ns: u64,
     ) -> Result<(Self::SwapchainImage, Option<w::Suboptimal>), w::AcquireError> {
+        if timeout_ns != !0
+            && !self
+                .drawables
+                .wait_available(self.image_count, time::Duration::from_nanos(timeout_ns))
+        {
+            return Err(w::AcquireError::NotReady {
+                timeout: timeout_ns != 0,
+            });
+        }
+
         let render_layer = self.render_layer.lock();
-        let (drawable, texture) = autoreleasepool(|| {
-            let drawable = render_layer.next_drawable().unwrap();
-            (drawable.to_owned(), drawable.texture().to_owned())
-        });
+        let allow_timeout = self.can_set_next_drawable_timeout && timeout_ns != !0;
+        if self.can_set_next_drawable_timeout {
+            let () = msg_send![*render_layer, setAllowsNextDrawableTimeout: allow_timeout];
+        }
+        let (drawable, texture) = match autoreleasepool(|| {
+            render_layer
+                .next_drawable()
+                .map(|drawable| (drawable.to_owned(), drawable.texture().to_owned()))
+        }) {
+            Some(pair) => pair,
+            None if allow_timeout => return Err(w::AcquireError::NotReady { timeout: true }),
+            // Without a timeout, the layer only fails to provide a drawable
+            // if it's not in a presentable state, e.g. has a zero size.
+            None => return Err(w::OutOfDate.into()),
+        };
         let size = render_layer.drawable_size();
+        self.drawables.acquire();
 
         let sc_image = SwapchainImage {
             image: native::Image {
@@ -278,14 +562,74 @@ impl w::PresentationSurface<Backend> for Surface {
                 shader_channel: Channel::Float,
                 mtl_format: self.swapchain_format,
                 mtl_type: metal::MTLTextureType::D2,
+                decompression: None,
+                chroma_plane: None,
+                private_data: native::PrivateData::default(),
             },
             view: native::ImageView {
                 texture,
                 mtl_format: self.swapchain_format,
+                chroma: None,
             },
             drawable,
             present_with_transaction: self.present_with_transaction,
+            presented: false,
+            drawables: Arc::clone(&self.drawables),
         };
         Ok((sc_image, None))
     }
+
+    unsafe fn release_images<I>(&mut self, images: I)
+    where
+        I: Iterator<Item = SwapchainImage>,
+    {
+        // Drawables that are never presented return to the layer once released
+        for image in images {
+            drop(image);
+        }
+    }
+
+    unsafe fn wait_for_present(
+        &self,
+        present_id: u64,
+        timeout_ns: u64,
+    ) -> Result<bool, w::PresentError> {
+        let timeout = if timeout_ns == !0 {
+            None
+        } else {
+            Some(time::Duration::from_nanos(timeout_ns))
+        };
+        Ok(self.present_tracker.wait(present_id, timeout))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Hdr10Metadata;
+
+    #[test]
+    fn hdr10_metadata_encoding() {
+        // BT.2020 primaries with a D65 white point
+        let metadata = Hdr10Metadata {
+            display_primaries: [[0.708, 0.292], [0.170, 0.797], [0.131, 0.046]],
+            white_point: [0.3127, 0.3290],
+            max_mastering_luminance: 1000.0,
+            min_mastering_luminance: 0.0001,
+            max_content_light_level: 1000,
+            max_frame_average_light_level: 400,
+            optical_output_scale: 100.0,
+        };
+        assert_eq!(
+            metadata.display_info(),
+            [
+                0x21, 0x34, 0x9B, 0xAA, // green
+                0x19, 0x96, 0x08, 0xFC, // blue
+                0x8A, 0x48, 0x39, 0x08, // red
+                0x3D, 0x13, 0x40, 0x42, // white point
+                0x00, 0x98, 0x96, 0x80, // max luminance
+                0x00, 0x00, 0x00, 0x01, // min luminance
+            ]
+        );
+        assert_eq!(metadata.content_info(), [0x03, 0xE8, 0x01, 0x90]);
+    }
 }
@@ -0,0 +1,670 @@
+//! Sampling of multi-planar images with a YCbCr conversion.
+//!
+//! Metal has no sampler conversions, and the planes of an image are separate
+//! textures. A combined image sampler binding with a conversion takes two texture
+//! slots, the luma plane followed by the chroma one, and the shaders using it are
+//! rewritten: each sample of the image is replaced with a call to a generated
+//! function, which samples both planes with the same sampler and converts the
+//! result to RGB. Chroma is filtered at the midpoints of the luma texels.
+
+use hal::{
+    device::NagaShader,
+    format::Format,
+    image::{SamplerYcbcrConversion, YcbcrModel, YcbcrRange},
+};
+use naga::{
+    back::msl, Arena, BinaryOperator as Bo, Constant, ConstantInner, Expression, Function,
+    FunctionArgument, FunctionResult, GlobalVariable, Handle, ImageClass, ImageDimension,
+    SampleLevel, ScalarKind, ScalarValue, Statement, StorageAccess, StorageClass, Type, TypeInner,
+    VectorSize,
+};
+
+use crate::FastHashMap;
+use std::mem;
+
+/// Descriptor set index of the chroma planes in the rewritten shader.
+const CHROMA_GROUP: u32 = !1;
+
+/// Combined image sampler binding of a pipeline layout that samples with a conversion.
+#[derive(Clone, Debug)]
+pub(crate) struct YcbcrBinding {
+    /// Binding of the luma plane, which the sampler is bound with.
+    pub source: msl::BindSource,
+    /// Texture slot of the chroma plane.
+    pub chroma_slot: msl::Slot,
+    pub conversion: SamplerYcbcrConversion,
+}
+
+/// Conversion of the sampled planes to RGB, in the form of
+/// `y' = y * luma.0 + luma.1`, `c' = c * chroma.0 + chroma.1` for the range
+/// expansion, followed by the model matrix, without its identity part.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Coefficients {
+    luma: (f32, f32),
+    chroma: (f32, f32),
+    red_cr: f32,
+    green_cb: f32,
+    green_cr: f32,
+    blue_cb: f32,
+}
+
+impl Coefficients {
+    fn new(conversion: &SamplerYcbcrConversion) -> Self {
+        // The planes are sampled as normalized 8 or 16-bit values, the 10-bit
+        // components being stored in the high bits of the latter.
+        let (bits, max) = match conversion.format {
+            Format::G10x6B10x6r10x6TwoPlane420Unorm => (10, 65535.0 / 64.0),
+            _ => (8, 255.0),
+        };
+        let unit = (1u32 << (bits - 8)) as f32;
+        let (luma, chroma) = match conversion.range {
+            YcbcrRange::Full => ((1.0, 0.0), (1.0, -(128.0 * unit) / max)),
+            YcbcrRange::Narrow => (
+                (max / (219.0 * unit), -16.0 / 219.0),
+                (max / (224.0 * unit), -128.0 / 224.0),
+            ),
+        };
+        let (kr, kb) = match conversion.model {
+            YcbcrModel::Bt601 => (0.299, 0.114),
+            YcbcrModel::Bt709 => (0.2126, 0.0722),
+            YcbcrModel::Bt2020 => (0.2627, 0.0593),
+        };
+        let kg = 1.0 - kr - kb;
+        Coefficients {
+            luma,
+            chroma,
+            red_cr: 2.0 * (1.0 - kr),
+            green_cb: -2.0 * kb * (1.0 - kb) / kg,
+            green_cr: -2.0 * kr * (1.0 - kr) / kg,
+            blue_cb: 2.0 * (1.0 - kb),
+        }
+    }
+}
+
+/// Image that is sampled with a conversion.
+struct Planes {
+    luma: Handle<GlobalVariable>,
+    chroma: Handle<GlobalVariable>,
+    coefficients: Coefficients,
+}
+
+/// Rewrite the samples of the images bound with a conversion, and extend the MSL
+/// options with the bindings of their chroma planes.
+pub(crate) fn transform(
+    shader: &NagaShader,
+    options: &msl::Options,
+    bindings: &[YcbcrBinding],
+) -> Result<(NagaShader, msl::Options), String> {
+    let mut module = shader.module.clone();
+    let mut options = options.clone();
+
+    let luma_globals = module
+        .global_variables
+        .iter()
+        .filter_map(|(handle, var)| {
+            let rb = var.binding.as_ref()?;
+            bindings
+                .iter()
+                .find(|b| b.source.group == rb.group && b.source.binding == rb.binding)
+                .map(|b| (handle, b.conversion))
+        })
+        .collect::<Vec<_>>();
+    let mut planes = Vec::with_capacity(luma_globals.len());
+    for (index, (luma, conversion)) in luma_globals.into_iter().enumerate() {
+        let var = &module.global_variables[luma];
+        match module.types[var.ty].inner {
+            TypeInner::Image {
+                dim: ImageDimension::D2,
+                arrayed: false,
+                class:
+                    ImageClass::Sampled {
+                        kind: ScalarKind::Float,
+                        multi: false,
+                    },
+            } => {}
+            _ => return Err("YCbCr images can only be sampled as 2D float textures".to_string()),
+        }
+        let rb = var.binding.clone().unwrap();
+        let chroma = GlobalVariable {
+            name: var.name.as_ref().map(|name| format!("{}_chroma", name)),
+            class: StorageClass::Handle,
+            binding: Some(naga::ResourceBinding {
+                group: CHROMA_GROUP,
+                binding: index as u32,
+            }),
+            ty: var.ty,
+            init: None,
+            storage_access: StorageAccess::empty(),
+        };
+        let chroma = module.global_variables.append(chroma);
+
+        // the binding is there once per stage that uses it
+        for binding in bindings
+            .iter()
+            .filter(|b| b.source.group == rb.group && b.source.binding == rb.binding)
+        {
+            let source = msl::BindSource {
+                stage: binding.source.stage,
+                group: CHROMA_GROUP,
+                binding: index as u32,
+            };
+            let target = msl::BindTarget {
+                buffer: None,
+                texture: Some(binding.chroma_slot),
+                sampler: None,
+                mutable: false,
+            };
+            options.binding_map.insert(source, target);
+        }
+        planes.push(Planes {
+            luma,
+            chroma,
+            coefficients: Coefficients::new(&conversion),
+        });
+    }
+
+    rewrite(&mut module, &planes)?;
+    let info = naga::valid::Validator::new(
+        naga::valid::ValidationFlags::empty(),
+        naga::valid::Capabilities::PUSH_CONSTANT,
+    )
+    .validate(&module)
+    .map_err(|e| format!("Naga validation of the YCbCr sampling shader: {}", e))?;
+    Ok((NagaShader { module, info }, options))
+}
+
+/// Sample of a converted image, to be replaced with a call.
+struct Site {
+    expression: Handle<Expression>,
+    planes: usize,
+    sampler: Handle<GlobalVariable>,
+    coordinate: Handle<Expression>,
+    level: SampleLevel,
+}
+
+fn find_sites(function: &Function, planes: &[Planes]) -> Result<Vec<Site>, String> {
+    let plane_of = |expr: Handle<Expression>| match function.expressions[expr] {
+        Expression::GlobalVariable(var) => planes.iter().position(|p| p.luma == var),
+        _ => None,
+    };
+    let mut sites = Vec::new();
+    for (handle, expr) in function.expressions.iter() {
+        if let Expression::ImageSample {
+            image,
+            sampler,
+            coordinate,
+            array_index,
+            offset,
+            ref level,
+            depth_ref,
+        } = *expr
+        {
+            let planes = match plane_of(image) {
+                Some(planes) => planes,
+                None => continue,
+            };
+            if array_index.is_some() || offset.is_some() || depth_ref.is_some() {
+                return Err(
+                    "YCbCr images can't be sampled with an array index, offset, or depth reference"
+                        .to_string(),
+                );
+            }
+            let sampler = match function.expressions[sampler] {
+                Expression::GlobalVariable(var) => var,
+                _ => return Err("YCbCr image is sampled with a sampler argument".to_string()),
+            };
+            sites.push(Site {
+                expression: handle,
+                planes,
+                sampler,
+                coordinate,
+                level: level.clone(),
+            });
+        }
+    }
+    Ok(sites)
+}
+
+fn rewrite(module: &mut naga::Module, planes: &[Planes]) -> Result<(), String> {
+    let float_ty = module.types.fetch_or_append(Type {
+        name: None,
+        inner: TypeInner::Scalar {
+            kind: ScalarKind::Float,
+            width: 4,
+        },
+    });
+    let vec2_ty = module.types.fetch_or_append(Type {
+        name: None,
+        inner: TypeInner::Vector {
+            size: VectorSize::Bi,
+            kind: ScalarKind::Float,
+            width: 4,
+        },
+    });
+    let vec4_ty = module.types.fetch_or_append(Type {
+        name: None,
+        inner: TypeInner::Vector {
+            size: VectorSize::Quad,
+            kind: ScalarKind::Float,
+            width: 4,
+        },
+    });
+
+    // Generate the sampling functions, one per site. They have to precede their
+    // callers, so the functions of the module are appended after them.
+    let function_sites = module
+        .functions
+        .iter()
+        .map(|(_, fun)| find_sites(fun, planes))
+        .collect::<Result<Vec<_>, _>>()?;
+    let entry_sites = module
+        .entry_points
+        .iter()
+        .map(|ep| find_sites(&ep.function, planes))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut helpers = Vec::new();
+    for site in function_sites.iter().chain(entry_sites.iter()).flatten() {
+        let mut builder = Builder {
+            constants: &mut module.constants,
+            arguments: Vec::new(),
+            expressions: Arena::new(),
+            body: Vec::new(),
+        };
+        let helper = builder.sample(&planes[site.planes], site, float_ty, vec2_ty, vec4_ty);
+        helpers.push(helper);
+    }
+
+    let old_functions = mem::replace(&mut module.functions, Arena::new());
+    let helpers = helpers
+        .into_iter()
+        .enumerate()
+        .map(|(i, builder)| {
+            module.functions.append(Function {
+                name: Some(format!("ycbcr_sample_{}", i)),
+                arguments: builder.arguments,
+                result: Some(FunctionResult {
+                    ty: vec4_ty,
+                    binding: None,
+                }),
+                local_variables: Arena::new(),
+                expressions: builder.expressions,
+                body: builder.body,
+            })
+        })
+        .collect::<Vec<_>>();
+    let remap = old_functions
+        .iter()
+        .map(|(_, fun)| module.functions.append(fun.clone()))
+        .collect::<Vec<_>>();
+
+    let mut next_helper = helpers.iter().cloned();
+    let mut rewrite_function = |fun: &mut Function, sites: &[Site]| -> Result<(), String> {
+        let mut calls = FastHashMap::default();
+        for site in sites {
+            let helper = next_helper.next().unwrap();
+            let mut arguments = vec![site.coordinate];
+            match site.level {
+                SampleLevel::Auto | SampleLevel::Zero => {}
+                SampleLevel::Exact(level) | SampleLevel::Bias(level) => arguments.push(level),
+                SampleLevel::Gradient { x, y } => arguments.extend_from_slice(&[x, y]),
+            }
+            fun.expressions[site.expression] = Expression::Call(helper);
+            calls.insert(
+                site.expression,
+                Statement::Call {
+                    function: helper,
+                    arguments,
+                    result: Some(site.expression),
+                },
+            );
+        }
+        for handle in fun.expressions.iter().map(|(h, _)| h).collect::<Vec<_>>() {
+            if let Expression::Call(ref mut function) = fun.expressions[handle] {
+                if !helpers.contains(function) {
+                    *function = remap[function.index()];
+                }
+            }
+        }
+        let mut body = mem::take(&mut fun.body);
+        let expressions = &fun.expressions;
+        let luma_args = |arguments: &[Handle<Expression>]| {
+            arguments.iter().any(|&arg| match expressions[arg] {
+                Expression::GlobalVariable(var) => planes.iter().any(|p| p.luma == var),
+                _ => false,
+            })
+        };
+        rewrite_block(&mut body, &remap, &calls, &luma_args)?;
+        fun.body = body;
+        Ok(())
+    };
+    for (&handle, sites) in remap.iter().zip(function_sites.iter()) {
+        rewrite_function(&mut module.functions[handle], sites)?;
+    }
+    for (ep, sites) in module.entry_points.iter_mut().zip(entry_sites.iter()) {
+        rewrite_function(&mut ep.function, sites)?;
+    }
+    Ok(())
+}
+
+/// Remap the called functions in a block, and evaluate the replaced samples with
+/// calls in place of their emission.
+fn rewrite_block(
+    block: &mut Vec<Statement>,
+    remap: &[Handle<Function>],
+    calls: &FastHashMap<Handle<Expression>, Statement>,
+    luma_args: &impl Fn(&[Handle<Expression>]) -> bool,
+) -> Result<(), String> {
+    let old = mem::take(block);
+    for mut statement in old {
+        match statement {
+            Statement::Emit(ref range) => {
+                let handles = range.clone().collect::<Vec<_>>();
+                if handles.iter().any(|h| calls.contains_key(h)) {
+                    let mut start = handles[0].index();
+                    for handle in handles.iter() {
+                        if let Some(call) = calls.get(handle) {
+                            if start < handle.index() {
+                                block.push(emit_range(start, handle.index()));
+                            }
+                            block.push(call.clone());
+                            start = handle.index() + 1;
+                        }
+                    }
+                    let end = handles.last().unwrap().index() + 1;
+                    if start < end {
+                        block.push(emit_range(start, end));
+                    }
+                    continue;
+                }
+            }
+            Statement::Call {
+                ref mut function,
+                ref arguments,
+                ..
+            } => {
+                if luma_args(arguments) {
+                    return Err("YCbCr image is passed to a function".to_string());
+                }
+                *function = remap[function.index()];
+            }
+            Statement::Block(ref mut inner) => rewrite_block(inner, remap, calls, luma_args)?,
+            Statement::If {
+                ref mut accept,
+                ref mut reject,
+                ..
+            } => {
+                rewrite_block(accept, remap, calls, luma_args)?;
+                rewrite_block(reject, remap, calls, luma_args)?;
+            }
+            Statement::Switch {
+                ref mut cases,
+                ref mut default,
+                ..
+            } => {
+                for case in cases.iter_mut() {
+                    rewrite_block(&mut case.body, remap, calls, luma_args)?;
+                }
+                rewrite_block(default, remap, calls, luma_args)?;
+            }
+            Statement::Loop {
+                ref mut body,
+                ref mut continuing,
+            } => {
+                rewrite_block(body, remap, calls, luma_args)?;
+                rewrite_block(continuing, remap, calls, luma_args)?;
+            }
+            _ => {}
+        }
+        block.push(statement);
+    }
+    Ok(())
+}
+
+/// Emit the expressions with indices from `start` up to `end`.
+fn emit_range(start: usize, end: usize) -> Statement {
+    // Ranges can only be made by an arena, which has to be as long as `end` for it.
+    let mut arena = Arena::new();
+    for _ in 0..end {
+        arena.append(Expression::FunctionArgument(0));
+    }
+    Statement::Emit(arena.range_from(start))
+}
+
+/// Generator of the body of a sampling function.
+struct Builder<'a> {
+    constants: &'a mut Arena<Constant>,
+    arguments: Vec<FunctionArgument>,
+    expressions: Arena<Expression>,
+    body: Vec<Statement>,
+}
+
+impl Builder<'_> {
+    /// Append an expression that needs to be evaluated in the body.
+    fn emit(&mut self, expression: Expression) -> Handle<Expression> {
+        let start = self.expressions.len();
+        let handle = self.expressions.append(expression);
+        self.body
+            .push(Statement::Emit(self.expressions.range_from(start)));
+        handle
+    }
+
+    fn argument(&mut self, ty: Handle<Type>) -> Handle<Expression> {
+        self.arguments.push(FunctionArgument {
+            name: None,
+            ty,
+            binding: None,
+        });
+        self.expressions.append(Expression::FunctionArgument(
+            self.arguments.len() as u32 - 1,
+        ))
+    }
+
+    fn float(&mut self, value: f32) -> Handle<Expression> {
+        let constant = self.constants.fetch_or_append(Constant {
+            name: None,
+            specialization: None,
+            inner: ConstantInner::Scalar {
+                width: 4,
+                value: ScalarValue::Float(value as f64),
+            },
+        });
+        self.expressions.append(Expression::Constant(constant))
+    }
+
+    /// `value * scale + bias`
+    fn scale_bias(
+        &mut self,
+        value: Handle<Expression>,
+        (scale, bias): (f32, f32),
+    ) -> Handle<Expression> {
+        let scale = self.float(scale);
+        let bias = self.float(bias);
+        let scaled = self.emit(Expression::Binary {
+            op: Bo::Multiply,
+            left: value,
+            right: scale,
+        });
+        self.emit(Expression::Binary {
+            op: Bo::Add,
+            left: scaled,
+            right: bias,
+        })
+    }
+
+    /// `base + value * factor`
+    fn add_scaled(
+        &mut self,
+        base: Handle<Expression>,
+        value: Handle<Expression>,
+        factor: f32,
+    ) -> Handle<Expression> {
+        let factor = self.float(factor);
+        let scaled = self.emit(Expression::Binary {
+            op: Bo::Multiply,
+            left: value,
+            right: factor,
+        });
+        self.emit(Expression::Binary {
+            op: Bo::Add,
+            left: base,
+            right: scaled,
+        })
+    }
+
+    fn sample_plane(
+        &mut self,
+        plane: Handle<GlobalVariable>,
+        sampler: Handle<Expression>,
+        coordinate: Handle<Expression>,
+        level: &SampleLevel,
+    ) -> Handle<Expression> {
+        let image = self.expressions.append(Expression::GlobalVariable(plane));
+        self.emit(Expression::ImageSample {
+            image,
+            sampler,
+            coordinate,
+            array_index: None,
+            offset: None,
+            level: level.clone(),
+            depth_ref: None,
+        })
+    }
+
+    /// Generate the body of the function replacing a sample, taking the coordinate
+    /// and the level arguments of the original sample.
+    fn sample(
+        mut self,
+        planes: &Planes,
+        site: &Site,
+        float_ty: Handle<Type>,
+        vec2_ty: Handle<Type>,
+        vec4_ty: Handle<Type>,
+    ) -> Self {
+        let coordinate = self.argument(vec2_ty);
+        let level = match site.level {
+            SampleLevel::Auto => SampleLevel::Auto,
+            SampleLevel::Zero => SampleLevel::Zero,
+            SampleLevel::Exact(_) => SampleLevel::Exact(self.argument(float_ty)),
+            SampleLevel::Bias(_) => SampleLevel::Bias(self.argument(float_ty)),
+            SampleLevel::Gradient { .. } => SampleLevel::Gradient {
+                x: self.argument(vec2_ty),
+                y: self.argument(vec2_ty),
+            },
+        };
+        let sampler = self
+            .expressions
+            .append(Expression::GlobalVariable(site.sampler));
+
+        let luma = self.sample_plane(planes.luma, sampler, coordinate, &level);
+        let chroma = self.sample_plane(planes.chroma, sampler, coordinate, &level);
+
+        let c = planes.coefficients;
+        let y = self.emit(Expression::AccessIndex {
+            base: luma,
+            index: 0,
+        });
+        let cb = self.emit(Expression::AccessIndex {
+            base: chroma,
+            index: 0,
+        });
+        let cr = self.emit(Expression::AccessIndex {
+            base: chroma,
+            index: 1,
+        });
+        let y = self.scale_bias(y, c.luma);
+        let cb = self.scale_bias(cb, c.chroma);
+        let cr = self.scale_bias(cr, c.chroma);
+
+        let red = self.add_scaled(y, cr, c.red_cr);
+        let green = self.add_scaled(y, cb, c.green_cb);
+        let green = self.add_scaled(green, cr, c.green_cr);
+        let blue = self.add_scaled(y, cb, c.blue_cb);
+        let alpha = self.float(1.0);
+        let value = self.emit(Expression::Compose {
+            ty: vec4_ty,
+            components: vec![red, green, blue, alpha],
+        });
+        self.body.push(Statement::Return { value: Some(value) });
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(conversion: SamplerYcbcrConversion, (y, cb, cr): (f32, f32, f32)) -> [f32; 3] {
+        let c = Coefficients::new(&conversion);
+        let y = y * c.luma.0 + c.luma.1;
+        let cb = cb * c.chroma.0 + c.chroma.1;
+        let cr = cr * c.chroma.0 + c.chroma.1;
+        [
+            y + cr * c.red_cr,
+            y + cb * c.green_cb + cr * c.green_cr,
+            y + cb * c.blue_cb,
+        ]
+    }
+
+    fn assert_close(actual: [f32; 3], expected: [f32; 3]) {
+        for (a, e) in actual.iter().zip(expected.iter()) {
+            assert!((a - e).abs() < 5e-3, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn narrow_range_8bit() {
+        let conversion = SamplerYcbcrConversion {
+            format: Format::G8B8r8TwoPlane420Unorm,
+            model: YcbcrModel::Bt601,
+            range: YcbcrRange::Narrow,
+        };
+        let code = |v: f32| v / 255.0;
+        assert_close(
+            convert(conversion, (code(235.0), code(128.0), code(128.0))),
+            [1.0; 3],
+        );
+        assert_close(
+            convert(conversion, (code(16.0), code(128.0), code(128.0))),
+            [0.0; 3],
+        );
+        // BT.601 red
+        assert_close(
+            convert(conversion, (code(81.0), code(90.0), code(240.0))),
+            [1.0, 0.0, 0.0],
+        );
+    }
+
+    #[test]
+    fn narrow_range_10bit() {
+        let conversion = SamplerYcbcrConversion {
+            format: Format::G10x6B10x6r10x6TwoPlane420Unorm,
+            model: YcbcrModel::Bt709,
+            range: YcbcrRange::Narrow,
+        };
+        // 10-bit codes in the high bits of 16-bit components
+        let code = |v: f32| v * 64.0 / 65535.0;
+        assert_close(
+            convert(conversion, (code(940.0), code(512.0), code(512.0))),
+            [1.0; 3],
+        );
+        assert_close(
+            convert(conversion, (code(64.0), code(512.0), code(512.0))),
+            [0.0; 3],
+        );
+    }
+
+    #[test]
+    fn full_range_neutral_chroma() {
+        let conversion = SamplerYcbcrConversion {
+            format: Format::G8B8r8TwoPlane420Unorm,
+            model: YcbcrModel::Bt2020,
+            range: YcbcrRange::Full,
+        };
+        let gray = 100.0 / 255.0;
+        assert_close(
+            convert(conversion, (gray, 128.0 / 255.0, 128.0 / 255.0)),
+            [gray; 3],
+        );
+    }
+}
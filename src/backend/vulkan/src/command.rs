@@ -209,6 +209,18 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
             Ok(()),
             self.device.raw.begin_command_buffer(self.raw, &info)
         );
+
+        // The rate is dynamic in all the pipelines, start from the one they would have by default.
+        if let Some(ref fragment_shading_rate) = self.device.extension_fns.fragment_shading_rate {
+            fragment_shading_rate.cmd_set_fragment_shading_rate_khr(
+                self.raw,
+                &vk::Extent2D {
+                    width: 1,
+                    height: 1,
+                },
+                &[vk::FragmentShadingRateCombinerOpKHR::KEEP; 2],
+            );
+        }
     }
 
     unsafe fn finish(&mut self) {
@@ -305,6 +317,14 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         self.device.raw.cmd_end_render_pass(self.raw);
     }
 
+    unsafe fn begin_rendering(&mut self, _info: &com::RenderingInfo<Backend>) {
+        unimplemented!()
+    }
+
+    unsafe fn end_rendering(&mut self) {
+        unimplemented!()
+    }
+
     unsafe fn pipeline_barrier<'a, T>(
         &mut self,
         stages: Range<pso::PipelineStage>,
@@ -611,6 +631,67 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         );
     }
 
+    unsafe fn set_cull_face(&mut self, _faces: pso::Face) {
+        unimplemented!()
+    }
+
+    unsafe fn set_front_face(&mut self, _front_face: pso::FrontFace) {
+        unimplemented!()
+    }
+
+    unsafe fn set_primitive_topology(&mut self, _primitive: pso::Primitive) {
+        unimplemented!()
+    }
+
+    unsafe fn set_depth_test(&mut self, _depth: Option<pso::DepthTest>) {
+        unimplemented!()
+    }
+
+    unsafe fn set_stencil_faces(&mut self, _faces: pso::Face, _value: pso::StencilFace) {
+        unimplemented!()
+    }
+
+    unsafe fn set_vertex_input(
+        &mut self,
+        _buffers: &[pso::VertexBufferDesc],
+        _attributes: &[pso::AttributeDesc],
+    ) {
+        unimplemented!()
+    }
+
+    unsafe fn set_primitive_restart(&mut self, _enable: bool) {
+        unimplemented!()
+    }
+
+    unsafe fn set_fragment_shading_rate(
+        &mut self,
+        fragment_size: hal::window::Extent2D,
+        combiner_ops: [pso::ShadingRateCombinerOp; 2],
+    ) {
+        self.device
+            .extension_fns
+            .fragment_shading_rate
+            .as_ref()
+            .expect("Fragment shading rate not supported. You must request feature PIPELINE_FRAGMENT_SHADING_RATE.")
+            .cmd_set_fragment_shading_rate_khr(
+                self.raw,
+                &vk::Extent2D {
+                    width: fragment_size.width,
+                    height: fragment_size.height,
+                },
+                &[
+                    conv::map_shading_rate_combiner_op(combiner_ops[0]),
+                    conv::map_shading_rate_combiner_op(combiner_ops[1]),
+                ],
+            );
+    }
+
+    unsafe fn set_sample_locations(&mut self, _locations: &[pso::SampleLocation]) {
+        // `VK_EXT_sample_locations` needs the custom locations to be enabled
+        // when creating the graphics pipelines, which isn't exposed.
+        unimplemented!()
+    }
+
     unsafe fn bind_graphics_pipeline(&mut self, pipeline: &n::GraphicsPipeline) {
         self.device
             .raw
@@ -673,6 +754,42 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
             .cmd_dispatch_indirect(self.raw, buffer.raw, offset)
     }
 
+    unsafe fn build_acceleration_structure(
+        &mut self,
+        _desc: &hal::acceleration_structure::BuildDesc<Backend>,
+        _ranges: &[hal::acceleration_structure::BuildRangeDesc],
+    ) {
+        unimplemented!()
+    }
+
+    unsafe fn bind_ray_tracing_pipeline(&mut self, _pipeline: &()) {
+        unimplemented!()
+    }
+
+    unsafe fn bind_ray_tracing_descriptor_sets<'a, I, J>(
+        &mut self,
+        _layout: &n::PipelineLayout,
+        _first_set: usize,
+        _sets: I,
+        _offsets: J,
+    ) where
+        I: Iterator<Item = &'a n::DescriptorSet>,
+        J: Iterator<Item = com::DescriptorSetOffset>,
+    {
+        unimplemented!()
+    }
+
+    unsafe fn trace_rays(
+        &mut self,
+        _raygen: &pso::ShaderBindingTable<Backend>,
+        _miss: Option<&pso::ShaderBindingTable<Backend>>,
+        _hit_group: Option<&pso::ShaderBindingTable<Backend>>,
+        _callable: Option<&pso::ShaderBindingTable<Backend>>,
+        _dimensions: [u32; 3],
+    ) {
+        unimplemented!()
+    }
+
     unsafe fn copy_buffer<T>(&mut self, src: &n::Buffer, dst: &n::Buffer, regions: T)
     where
         T: Iterator<Item = com::BufferCopy>,
@@ -1048,6 +1165,33 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         )
     }
 
+    unsafe fn begin_conditional_rendering(
+        &mut self,
+        buffer: &n::Buffer,
+        offset: buffer::Offset,
+        flags: com::ConditionalRenderingFlags,
+    ) {
+        let info = vk::ConditionalRenderingBeginInfoEXT::builder()
+            .buffer(buffer.raw)
+            .offset(offset)
+            .flags(vk::ConditionalRenderingFlagsEXT::from_raw(flags.bits()));
+        self.device
+            .extension_fns
+            .conditional_rendering
+            .as_ref()
+            .expect("Conditional rendering not supported. You must request feature CONDITIONAL_RENDERING.")
+            .cmd_begin_conditional_rendering_ext(self.raw, &*info);
+    }
+
+    unsafe fn end_conditional_rendering(&mut self) {
+        self.device
+            .extension_fns
+            .conditional_rendering
+            .as_ref()
+            .expect("Conditional rendering not supported. You must request feature CONDITIONAL_RENDERING.")
+            .cmd_end_conditional_rendering_ext(self.raw);
+    }
+
     unsafe fn push_compute_constants(
         &mut self,
         layout: &n::PipelineLayout,
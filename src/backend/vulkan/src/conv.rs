@@ -3,24 +3,41 @@ use crate::native as n;
 use ash::vk;
 
 use hal::{
-    buffer, command, format, image, memory,
+    buffer, command,
+    external_memory::ExternalMemoryHandleTypes,
+    external_sync::ExternalSyncHandleTypes,
+    format, image, memory,
     memory::Segment,
     pass, pso, query,
-    window::{CompositeAlphaMode, PresentMode},
+    window::{CompositeAlphaMode, PresentMode, SurfaceTransform},
     IndexType,
 };
 
 use std::mem;
 
 pub fn map_format(format: format::Format) -> vk::Format {
-    vk::Format::from_raw(format as i32)
+    match format {
+        format::Format::G8B8r8TwoPlane420Unorm => vk::Format::G8_B8R8_2PLANE_420_UNORM,
+        format::Format::G10x6B10x6r10x6TwoPlane420Unorm => {
+            vk::Format::G10X6_B10X6R10X6_2PLANE_420_UNORM_3PACK16
+        }
+        _ => vk::Format::from_raw(format as i32),
+    }
 }
 
 pub fn map_vk_format(vk_format: vk::Format) -> Option<format::Format> {
-    if (vk_format.as_raw() as usize) < format::NUM_FORMATS && vk_format != vk::Format::UNDEFINED {
-        Some(unsafe { mem::transmute(vk_format) })
-    } else {
-        None
+    match vk_format {
+        vk::Format::G8_B8R8_2PLANE_420_UNORM => Some(format::Format::G8B8r8TwoPlane420Unorm),
+        vk::Format::G10X6_B10X6R10X6_2PLANE_420_UNORM_3PACK16 => {
+            Some(format::Format::G10x6B10x6r10x6TwoPlane420Unorm)
+        }
+        // the other formats match the core Vulkan ones
+        _ if (vk_format.as_raw() as usize) <= format::Format::Astc12x12Srgb as usize
+            && vk_format != vk::Format::UNDEFINED =>
+        {
+            Some(unsafe { mem::transmute(vk_format) })
+        }
+        _ => None,
     }
 }
 
@@ -76,6 +93,10 @@ pub fn map_image_aspects(aspects: format::Aspects) -> vk::ImageAspectFlags {
     vk::ImageAspectFlags::from_raw(aspects.bits() as u32)
 }
 
+pub fn map_vk_image_aspects(aspects: vk::ImageAspectFlags) -> format::Aspects {
+    format::Aspects::from_bits_truncate(aspects.as_raw() as u8)
+}
+
 pub fn map_offset(offset: image::Offset) -> vk::Offset3D {
     vk::Offset3D {
         x: offset.x,
@@ -156,7 +177,50 @@ pub fn map_buffer_usage(usage: buffer::Usage) -> vk::BufferUsageFlags {
     vk::BufferUsageFlags::from_raw(usage.bits())
 }
 
+pub fn map_external_memory_handle_types(
+    types: ExternalMemoryHandleTypes,
+) -> vk::ExternalMemoryHandleTypeFlags {
+    let mut flags = vk::ExternalMemoryHandleTypeFlags::empty();
+    if types.contains(ExternalMemoryHandleTypes::OPAQUE_FD) {
+        flags |= vk::ExternalMemoryHandleTypeFlags::EXTERNAL_MEMORY_HANDLE_TYPE_OPAQUE_FD;
+    }
+    if types.contains(ExternalMemoryHandleTypes::OPAQUE_WIN32) {
+        flags |= vk::ExternalMemoryHandleTypeFlags::EXTERNAL_MEMORY_HANDLE_TYPE_OPAQUE_WIN32;
+    }
+    if types.contains(ExternalMemoryHandleTypes::HOST_POINTER) {
+        flags |= vk::ExternalMemoryHandleTypeFlags::EXTERNAL_MEMORY_HANDLE_TYPE_HOST_ALLOCATION_EXT;
+    }
+    flags
+}
+
+pub fn map_external_fence_handle_types(
+    types: ExternalSyncHandleTypes,
+) -> vk::ExternalFenceHandleTypeFlags {
+    let mut flags = vk::ExternalFenceHandleTypeFlags::empty();
+    if types.contains(ExternalSyncHandleTypes::SYNC_FD) {
+        flags |= vk::ExternalFenceHandleTypeFlags::EXTERNAL_FENCE_HANDLE_TYPE_SYNC_FD;
+    }
+    if types.contains(ExternalSyncHandleTypes::OPAQUE_WIN32) {
+        flags |= vk::ExternalFenceHandleTypeFlags::EXTERNAL_FENCE_HANDLE_TYPE_OPAQUE_WIN32;
+    }
+    flags
+}
+
+pub fn map_external_semaphore_handle_types(
+    types: ExternalSyncHandleTypes,
+) -> vk::ExternalSemaphoreHandleTypeFlags {
+    let mut flags = vk::ExternalSemaphoreHandleTypeFlags::empty();
+    if types.contains(ExternalSyncHandleTypes::SYNC_FD) {
+        flags |= vk::ExternalSemaphoreHandleTypeFlags::EXTERNAL_SEMAPHORE_HANDLE_TYPE_SYNC_FD;
+    }
+    if types.contains(ExternalSyncHandleTypes::OPAQUE_WIN32) {
+        flags |= vk::ExternalSemaphoreHandleTypeFlags::EXTERNAL_SEMAPHORE_HANDLE_TYPE_OPAQUE_WIN32;
+    }
+    flags
+}
+
 pub fn map_buffer_create_flags(sparse: memory::SparseFlags) -> vk::BufferCreateFlags {
+    // The sparse and protected bits match the Vulkan ones.
     vk::BufferCreateFlags::from_raw(sparse.bits())
 }
 
@@ -199,6 +263,10 @@ pub fn map_descriptor_type(ty: pso::DescriptorType) -> vk::DescriptorType {
             },
         },
         pso::DescriptorType::InputAttachment => vk::DescriptorType::INPUT_ATTACHMENT,
+        pso::DescriptorType::AccelerationStructure => {
+            vk::DescriptorType::ACCELERATION_STRUCTURE_KHR
+        }
+        pso::DescriptorType::Mutable { .. } => unimplemented!(),
     }
 }
 
@@ -277,6 +345,19 @@ pub fn map_front_face(ff: pso::FrontFace) -> vk::FrontFace {
     }
 }
 
+pub fn map_line_rasterization_mode(
+    mode: pso::LineRasterizationMode,
+) -> vk::LineRasterizationModeEXT {
+    match mode {
+        pso::LineRasterizationMode::Default => vk::LineRasterizationModeEXT::DEFAULT,
+        pso::LineRasterizationMode::Rectangular => vk::LineRasterizationModeEXT::RECTANGULAR,
+        pso::LineRasterizationMode::Bresenham => vk::LineRasterizationModeEXT::BRESENHAM,
+        pso::LineRasterizationMode::RectangularSmooth => {
+            vk::LineRasterizationModeEXT::RECTANGULAR_SMOOTH
+        }
+    }
+}
+
 pub fn map_comparison(fun: pso::Comparison) -> vk::CompareOp {
     use hal::pso::Comparison::*;
     match fun {
@@ -373,6 +454,19 @@ pub fn map_blend_op(operation: pso::BlendOp) -> (vk::BlendOp, vk::BlendFactor, v
     }
 }
 
+pub fn map_shading_rate_combiner_op(
+    op: pso::ShadingRateCombinerOp,
+) -> vk::FragmentShadingRateCombinerOpKHR {
+    use hal::pso::ShadingRateCombinerOp::*;
+    match op {
+        Keep => vk::FragmentShadingRateCombinerOpKHR::KEEP,
+        Replace => vk::FragmentShadingRateCombinerOpKHR::REPLACE,
+        Min => vk::FragmentShadingRateCombinerOpKHR::MIN,
+        Max => vk::FragmentShadingRateCombinerOpKHR::MAX,
+        Mul => vk::FragmentShadingRateCombinerOpKHR::MUL,
+    }
+}
+
 pub fn map_pipeline_statistics(
     statistics: query::PipelineStatistic,
 ) -> vk::QueryPipelineStatisticFlags {
@@ -538,7 +632,14 @@ pub fn map_view_capabilities_sparse(
     sparse: memory::SparseFlags,
     caps: image::ViewCapabilities,
 ) -> vk::ImageCreateFlags {
-    vk::ImageCreateFlags::from_raw(sparse.bits() | caps.bits())
+    // The protected bit is shared with `MUTABLE_FORMAT`, and needs to be moved.
+    let mut flags = vk::ImageCreateFlags::from_raw(
+        (sparse - memory::SparseFlags::PROTECTED).bits() | caps.bits(),
+    );
+    if sparse.contains(memory::SparseFlags::PROTECTED) {
+        flags |= vk::ImageCreateFlags::PROTECTED;
+    }
+    flags
 }
 
 pub fn map_present_mode(mode: PresentMode) -> vk::PresentModeKHR {
@@ -580,6 +681,10 @@ pub fn map_vk_composite_alpha(composite_alpha: vk::CompositeAlphaFlagsKHR) -> Co
     CompositeAlphaMode::from_bits_truncate(composite_alpha.as_raw())
 }
 
+pub fn map_vk_surface_transform(transform: vk::SurfaceTransformFlagsKHR) -> SurfaceTransform {
+    SurfaceTransform::from_bits_truncate(transform.as_raw())
+}
+
 pub fn map_descriptor_pool_create_flags(
     flags: pso::DescriptorPoolCreateFlags,
 ) -> vk::DescriptorPoolCreateFlags {
@@ -609,6 +714,9 @@ pub fn map_vk_memory_properties(flags: vk::MemoryPropertyFlags) -> hal::memory::
     if flags.contains(vk::MemoryPropertyFlags::LAZILY_ALLOCATED) {
         properties |= Properties::LAZILY_ALLOCATED;
     }
+    if flags.contains(vk::MemoryPropertyFlags::PROTECTED) {
+        properties |= Properties::PROTECTED;
+    }
 
     properties
 }
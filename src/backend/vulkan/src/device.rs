@@ -1,9 +1,20 @@
 use arrayvec::ArrayVec;
-use ash::{extensions::khr, version::DeviceV1_0, vk};
+use ash::{
+    extensions::khr,
+    version::{DeviceV1_0, DeviceV1_2},
+    vk,
+};
 use inplace_it::inplace_or_alloc_from_iter;
 use smallvec::SmallVec;
 
 use hal::{
+    external_memory::{
+        ExternalMemoryError, ExternalMemoryHandle, ExternalMemoryHandleType,
+        ExternalMemoryHandleTypes,
+    },
+    external_sync::{
+        ExternalSyncError, ExternalSyncHandle, ExternalSyncHandleType, ExternalSyncHandleTypes,
+    },
     memory,
     memory::{Requirements, Segment},
     pool::CommandPoolCreateFlags,
@@ -14,9 +25,23 @@ use hal::{
 
 use std::{ffi::CString, marker::PhantomData, mem, ops::Range, ptr, sync::Arc};
 
-use crate::{command as cmd, conv, native as n, pool::RawCommandPool, window as w, Backend as B};
+use crate::{
+    command as cmd, conv, native as n, pool::RawCommandPool, window as w, Backend as B, ExtensionFn,
+};
 use ash::vk::Handle;
 
+fn map_external_sync_result(result: vk::Result) -> Result<(), ExternalSyncError> {
+    match result {
+        vk::Result::SUCCESS => Ok(()),
+        vk::Result::ERROR_TOO_MANY_OBJECTS | vk::Result::ERROR_OUT_OF_HOST_MEMORY => {
+            Err(d::OutOfMemory::Host.into())
+        }
+        vk::Result::ERROR_OUT_OF_DEVICE_MEMORY => Err(d::OutOfMemory::Device.into()),
+        vk::Result::ERROR_INVALID_EXTERNAL_HANDLE => Err(ExternalSyncError::InvalidHandle),
+        _ => unreachable!(),
+    }
+}
+
 #[derive(Debug, Default)]
 struct GraphicsPipelineInfoBuf<'a> {
     // 10 is the max amount of dynamic states
@@ -39,6 +64,7 @@ struct GraphicsPipelineInfoBuf<'a> {
     viewport_state: vk::PipelineViewportStateCreateInfo,
     rasterization_state: vk::PipelineRasterizationStateCreateInfo,
     rasterization_conservative_state: vk::PipelineRasterizationConservativeStateCreateInfoEXT, // May be unused or may be pointed to by rasterization_state
+    rasterization_line_state: vk::PipelineRasterizationLineStateCreateInfoEXT, // May be unused or may be pointed to by rasterization_state
     multisample_state: vk::PipelineMultisampleStateCreateInfo,
     depth_stencil_state: vk::PipelineDepthStencilStateCreateInfo,
     color_blend_state: vk::PipelineColorBlendStateCreateInfo,
@@ -199,6 +225,16 @@ impl<'a> GraphicsPipelineInfoBuf<'a> {
                 })
                 .build();
 
+        let stipple = desc.rasterizer.line_stipple;
+        this.rasterization_line_state = vk::PipelineRasterizationLineStateCreateInfoEXT::builder()
+            .line_rasterization_mode(conv::map_line_rasterization_mode(
+                desc.rasterizer.line_rasterization_mode,
+            ))
+            .stippled_line_enable(stipple.is_some())
+            .line_stipple_factor(stipple.map_or(1, |s| s.factor))
+            .line_stipple_pattern(stipple.map_or(!0, |s| s.pattern))
+            .build();
+
         this.rasterization_state = {
             let mut rasterization_state_builder =
                 vk::PipelineRasterizationStateCreateInfo::builder()
@@ -230,6 +266,12 @@ impl<'a> GraphicsPipelineInfoBuf<'a> {
                 rasterization_state_builder = rasterization_state_builder
                     .push_next(&mut this.rasterization_conservative_state);
             }
+            if desc.rasterizer.line_rasterization_mode != pso::LineRasterizationMode::Default
+                || stipple.is_some()
+            {
+                rasterization_state_builder =
+                    rasterization_state_builder.push_next(&mut this.rasterization_line_state);
+            }
 
             rasterization_state_builder.build()
         };
@@ -413,6 +455,11 @@ impl<'a> GraphicsPipelineInfoBuf<'a> {
             })
             .build();
 
+        if device.extension_fns.fragment_shading_rate.is_some() {
+            this.dynamic_states
+                .push(vk::DynamicState::FRAGMENT_SHADING_RATE_KHR);
+        }
+
         this.pipeline_dynamic_state = vk::PipelineDynamicStateCreateInfo::builder()
             .flags(vk::PipelineDynamicStateCreateFlags::empty())
             .dynamic_states(&this.dynamic_states)
@@ -460,9 +507,14 @@ impl d::Device<B> for super::Device {
         mem_type: MemoryTypeId,
         size: u64,
     ) -> Result<n::Memory, d::AllocationError> {
-        let info = vk::MemoryAllocateInfo::builder()
+        let mut flags_info =
+            vk::MemoryAllocateFlagsInfo::builder().flags(vk::MemoryAllocateFlags::DEVICE_ADDRESS);
+        let mut info = vk::MemoryAllocateInfo::builder()
             .allocation_size(size)
             .memory_type_index(self.get_ash_memory_type_index(mem_type));
+        if self.shared.buffer_device_address {
+            info = info.push_next(&mut flags_info);
+        }
 
         let result = self.shared.raw.allocate_memory(&info, None);
 
@@ -475,6 +527,129 @@ impl d::Device<B> for super::Device {
         }
     }
 
+    unsafe fn allocate_exportable_memory(
+        &self,
+        memory_type: MemoryTypeId,
+        size: u64,
+        handle_types: ExternalMemoryHandleTypes,
+    ) -> Result<n::Memory, ExternalMemoryError> {
+        self.check_external_memory_handle_types(handle_types)?;
+        let mut export_info = vk::ExportMemoryAllocateInfo::builder()
+            .handle_types(conv::map_external_memory_handle_types(handle_types));
+        let info = vk::MemoryAllocateInfo::builder()
+            .allocation_size(size)
+            .memory_type_index(self.get_ash_memory_type_index(memory_type))
+            .push_next(&mut export_info);
+
+        self.allocate_external_memory(&info)
+    }
+
+    unsafe fn import_external_memory(
+        &self,
+        memory_type: MemoryTypeId,
+        size: u64,
+        handle: ExternalMemoryHandle,
+    ) -> Result<n::Memory, ExternalMemoryError> {
+        let extension_fns = &self.shared.extension_fns;
+        let mut fd_info = vk::ImportMemoryFdInfoKHR::builder();
+        let mut win32_info = vk::ImportMemoryWin32HandleInfoKHR::builder();
+        let mut host_pointer_info = vk::ImportMemoryHostPointerInfoEXT::builder();
+        let mut info = vk::MemoryAllocateInfo::builder()
+            .allocation_size(size)
+            .memory_type_index(self.get_ash_memory_type_index(memory_type));
+
+        match handle {
+            ExternalMemoryHandle::OpaqueFd(fd) if extension_fns.external_memory_fd.is_some() => {
+                fd_info = fd_info
+                    .handle_type(
+                        vk::ExternalMemoryHandleTypeFlags::EXTERNAL_MEMORY_HANDLE_TYPE_OPAQUE_FD,
+                    )
+                    .fd(fd);
+                info = info.push_next(&mut fd_info);
+            }
+            ExternalMemoryHandle::OpaqueWin32(handle)
+                if extension_fns.external_memory_win32.is_some() =>
+            {
+                win32_info = win32_info
+                    .handle_type(
+                        vk::ExternalMemoryHandleTypeFlags::EXTERNAL_MEMORY_HANDLE_TYPE_OPAQUE_WIN32,
+                    )
+                    .handle(handle);
+                info = info.push_next(&mut win32_info);
+            }
+            ExternalMemoryHandle::HostPointer(pointer)
+                if extension_fns.external_memory_host.is_some() =>
+            {
+                host_pointer_info = host_pointer_info
+                    .handle_type(
+                        vk::ExternalMemoryHandleTypeFlags::EXTERNAL_MEMORY_HANDLE_TYPE_HOST_ALLOCATION_EXT,
+                    )
+                    .host_pointer(pointer.as_ptr() as *mut _);
+                info = info.push_next(&mut host_pointer_info);
+            }
+            other => {
+                return Err(ExternalMemoryError::UnsupportedHandleTypes(
+                    other.handle_type().into(),
+                ))
+            }
+        }
+
+        self.allocate_external_memory(&info)
+    }
+
+    unsafe fn export_memory(
+        &self,
+        memory: &n::Memory,
+        handle_type: ExternalMemoryHandleType,
+    ) -> Result<ExternalMemoryHandle, ExternalMemoryError> {
+        let extension_fns = &self.shared.extension_fns;
+        let (result, handle) = match handle_type {
+            ExternalMemoryHandleType::OpaqueFd if extension_fns.external_memory_fd.is_some() => {
+                let info = vk::MemoryGetFdInfoKHR::builder()
+                    .memory(memory.raw)
+                    .handle_type(
+                        vk::ExternalMemoryHandleTypeFlags::EXTERNAL_MEMORY_HANDLE_TYPE_OPAQUE_FD,
+                    );
+                let mut fd = -1;
+                let result = extension_fns
+                    .external_memory_fd
+                    .as_ref()
+                    .unwrap()
+                    .get_memory_fd_khr(self.shared.raw.handle(), &*info, &mut fd);
+                (result, ExternalMemoryHandle::OpaqueFd(fd))
+            }
+            ExternalMemoryHandleType::OpaqueWin32
+                if extension_fns.external_memory_win32.is_some() =>
+            {
+                let info = vk::MemoryGetWin32HandleInfoKHR::builder()
+                    .memory(memory.raw)
+                    .handle_type(
+                        vk::ExternalMemoryHandleTypeFlags::EXTERNAL_MEMORY_HANDLE_TYPE_OPAQUE_WIN32,
+                    );
+                let mut handle = ptr::null_mut();
+                let result = extension_fns
+                    .external_memory_win32
+                    .as_ref()
+                    .unwrap()
+                    .get_memory_win32_handle_khr(self.shared.raw.handle(), &*info, &mut handle);
+                (result, ExternalMemoryHandle::OpaqueWin32(handle))
+            }
+            _ => {
+                return Err(ExternalMemoryError::UnsupportedHandleTypes(
+                    handle_type.into(),
+                ))
+            }
+        };
+
+        match result {
+            vk::Result::SUCCESS => Ok(handle),
+            vk::Result::ERROR_TOO_MANY_OBJECTS | vk::Result::ERROR_OUT_OF_HOST_MEMORY => {
+                Err(d::OutOfMemory::Host.into())
+            }
+            _ => unreachable!(),
+        }
+    }
+
     unsafe fn create_command_pool(
         &self,
         family: queue::QueueFamilyId,
@@ -487,6 +662,9 @@ impl d::Device<B> for super::Device {
         if create_flags.contains(CommandPoolCreateFlags::RESET_INDIVIDUAL) {
             flags |= vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER;
         }
+        if create_flags.contains(CommandPoolCreateFlags::PROTECTED) {
+            flags |= vk::CommandPoolCreateFlags::PROTECTED;
+        }
 
         let info = vk::CommandPoolCreateInfo::builder()
             .flags(flags)
@@ -736,6 +914,17 @@ impl d::Device<B> for super::Device {
         cache: Option<&n::PipelineCache>,
     ) -> Result<n::GraphicsPipeline, pso::CreationError> {
         debug!("create_graphics_pipeline {:?}", desc);
+        // Primitive restart can't be toggled with commands.
+        if desc
+            .dynamic_states
+            .contains(pso::DynamicStates::PRIMITIVE_RESTART)
+            && !self
+                .shared
+                .features
+                .contains(hal::Features::PRIMITIVE_RESTART_DYNAMIC_STATE)
+        {
+            return Err(pso::CreationError::UnsupportedPipeline);
+        }
         let buf = GraphicsPipelineInfoBuf::new(desc, &self.shared);
 
         let info = {
@@ -879,6 +1068,23 @@ impl d::Device<B> for super::Device {
         }
     }
 
+    unsafe fn create_ray_tracing_pipeline<'a>(
+        &self,
+        _desc: &pso::RayTracingPipelineDesc<'a, B>,
+        _cache: Option<&n::PipelineCache>,
+    ) -> Result<(), pso::CreationError> {
+        Err(pso::CreationError::UnsupportedPipeline)
+    }
+
+    unsafe fn get_ray_tracing_shader_group_handles(
+        &self,
+        _pipeline: &(),
+        _first_group: u32,
+        _group_count: u32,
+    ) -> Result<Vec<u8>, d::OutOfMemory> {
+        unimplemented!()
+    }
+
     unsafe fn create_framebuffer<T>(
         &self,
         renderpass: &n::RenderPass,
@@ -1035,20 +1241,28 @@ impl d::Device<B> for super::Device {
         usage: buffer::Usage,
         sparse: memory::SparseFlags,
     ) -> Result<n::Buffer, buffer::CreationError> {
-        let info = vk::BufferCreateInfo::builder()
-            .flags(conv::map_buffer_create_flags(sparse))
-            .size(size)
-            .usage(conv::map_buffer_usage(usage))
-            .sharing_mode(vk::SharingMode::EXCLUSIVE); // TODO:
-
-        let result = self.shared.raw.create_buffer(&info, None);
+        self.create_buffer_impl(
+            size,
+            usage,
+            sparse,
+            vk::ExternalMemoryHandleTypeFlags::empty(),
+        )
+    }
 
-        match result {
-            Ok(raw) => Ok(n::Buffer { raw }),
-            Err(vk::Result::ERROR_OUT_OF_HOST_MEMORY) => Err(d::OutOfMemory::Host.into()),
-            Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY) => Err(d::OutOfMemory::Device.into()),
-            _ => unreachable!(),
-        }
+    unsafe fn create_external_buffer(
+        &self,
+        size: u64,
+        usage: buffer::Usage,
+        sparse: memory::SparseFlags,
+        handle_types: ExternalMemoryHandleTypes,
+    ) -> Result<n::Buffer, ExternalMemoryError> {
+        self.check_external_memory_handle_types(handle_types)?;
+        Ok(self.create_buffer_impl(
+            size,
+            usage,
+            sparse,
+            conv::map_external_memory_handle_types(handle_types),
+        )?)
     }
 
     unsafe fn get_buffer_requirements(&self, buffer: &n::Buffer) -> Requirements {
@@ -1080,6 +1294,17 @@ impl d::Device<B> for super::Device {
         }
     }
 
+    unsafe fn get_buffer_device_address(
+        &self,
+        buffer: &n::Buffer,
+    ) -> Option<buffer::DeviceAddress> {
+        if !self.shared.buffer_device_address {
+            return None;
+        }
+        let info = vk::BufferDeviceAddressInfo::builder().buffer(buffer.raw);
+        Some(self.shared.raw.get_buffer_device_address(&info))
+    }
+
     unsafe fn create_buffer_view(
         &self,
         buffer: &n::Buffer,
@@ -1103,6 +1328,28 @@ impl d::Device<B> for super::Device {
         }
     }
 
+    unsafe fn get_acceleration_structure_build_requirements(
+        &self,
+        _desc: &hal::acceleration_structure::GeometryDesc<B>,
+        _max_primitive_counts: &[u32],
+    ) -> hal::acceleration_structure::SizeRequirements {
+        unimplemented!()
+    }
+
+    unsafe fn create_acceleration_structure(
+        &self,
+        _desc: &hal::acceleration_structure::CreateDesc<B>,
+    ) -> Result<(), d::OutOfMemory> {
+        unimplemented!()
+    }
+
+    unsafe fn get_acceleration_structure_address(
+        &self,
+        _accel_struct: &(),
+    ) -> hal::acceleration_structure::DeviceAddress {
+        unimplemented!()
+    }
+
     unsafe fn create_image(
         &self,
         kind: image::Kind,
@@ -1113,48 +1360,40 @@ impl d::Device<B> for super::Device {
         sparse: memory::SparseFlags,
         view_caps: image::ViewCapabilities,
     ) -> Result<n::Image, image::CreationError> {
-        let flags = conv::map_view_capabilities_sparse(sparse, view_caps);
-        let extent = conv::map_extent(kind.extent());
-        let array_layers = kind.num_layers();
-        let samples = kind.num_samples();
-        let image_type = match kind {
-            image::Kind::D1(..) => vk::ImageType::TYPE_1D,
-            image::Kind::D2(..) => vk::ImageType::TYPE_2D,
-            image::Kind::D3(..) => vk::ImageType::TYPE_3D,
-        };
-
-        //Note: this is a hack, we should expose this in the API instead
-        let layout = match tiling {
-            image::Tiling::Linear => vk::ImageLayout::PREINITIALIZED,
-            image::Tiling::Optimal => vk::ImageLayout::UNDEFINED,
-        };
-
-        let info = vk::ImageCreateInfo::builder()
-            .flags(flags)
-            .image_type(image_type)
-            .format(conv::map_format(format))
-            .extent(extent.clone())
-            .mip_levels(mip_levels as u32)
-            .array_layers(array_layers as u32)
-            .samples(conv::map_sample_count_flags(samples))
-            .tiling(conv::map_tiling(tiling))
-            .usage(conv::map_image_usage(usage))
-            .sharing_mode(vk::SharingMode::EXCLUSIVE) // TODO:
-            .initial_layout(layout);
-
-        let result = self.shared.raw.create_image(&info, None);
+        self.create_image_impl(
+            kind,
+            mip_levels,
+            format,
+            tiling,
+            usage,
+            sparse,
+            view_caps,
+            vk::ExternalMemoryHandleTypeFlags::empty(),
+        )
+    }
 
-        match result {
-            Ok(raw) => Ok(n::Image {
-                raw,
-                ty: image_type,
-                flags,
-                extent,
-            }),
-            Err(vk::Result::ERROR_OUT_OF_HOST_MEMORY) => Err(d::OutOfMemory::Host.into()),
-            Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY) => Err(d::OutOfMemory::Device.into()),
-            _ => unreachable!(),
-        }
+    unsafe fn create_external_image(
+        &self,
+        kind: image::Kind,
+        mip_levels: image::Level,
+        format: format::Format,
+        tiling: image::Tiling,
+        usage: image::Usage,
+        sparse: memory::SparseFlags,
+        view_caps: image::ViewCapabilities,
+        handle_types: ExternalMemoryHandleTypes,
+    ) -> Result<n::Image, ExternalMemoryError> {
+        self.check_external_memory_handle_types(handle_types)?;
+        Ok(self.create_image_impl(
+            kind,
+            mip_levels,
+            format,
+            tiling,
+            usage,
+            sparse,
+            view_caps,
+            conv::map_external_memory_handle_types(handle_types),
+        )?)
     }
 
     unsafe fn get_image_requirements(&self, image: &n::Image) -> Requirements {
@@ -1183,6 +1422,33 @@ impl d::Device<B> for super::Device {
         }
     }
 
+    unsafe fn get_image_sparse_requirements(
+        &self,
+        image: &n::Image,
+    ) -> Vec<image::SparseRequirements> {
+        self.shared
+            .raw
+            .get_image_sparse_memory_requirements(image.raw)
+            .into_iter()
+            .map(|req| image::SparseRequirements {
+                aspects: conv::map_vk_image_aspects(req.format_properties.aspect_mask),
+                granularity: image::Extent {
+                    width: req.format_properties.image_granularity.width,
+                    height: req.format_properties.image_granularity.height,
+                    depth: req.format_properties.image_granularity.depth,
+                },
+                single_mip_tail: req
+                    .format_properties
+                    .flags
+                    .contains(vk::SparseImageFormatFlags::SINGLE_MIPTAIL),
+                mip_tail_first_level: req.image_mip_tail_first_lod as image::Level,
+                mip_tail_size: req.image_mip_tail_size,
+                mip_tail_offset: req.image_mip_tail_offset,
+                mip_tail_stride: req.image_mip_tail_stride,
+            })
+            .collect()
+    }
+
     unsafe fn bind_image_memory(
         &self,
         memory: &n::Memory,
@@ -1404,6 +1670,38 @@ impl d::Device<B> for super::Device {
                 pso::Descriptor::TexelBuffer(view) => {
                     texel_buffer_views.push(view.raw);
                 }
+                pso::Descriptor::AccelerationStructure(_) => unimplemented!(),
+                pso::Descriptor::Mutable(..) => unimplemented!(),
+                pso::Descriptor::Null(ty) => match ty {
+                    pso::DescriptorType::Image { .. } | pso::DescriptorType::InputAttachment => {
+                        image_infos.push(
+                            vk::DescriptorImageInfo::builder()
+                                .sampler(vk::Sampler::null())
+                                .image_view(vk::ImageView::null())
+                                .image_layout(vk::ImageLayout::GENERAL)
+                                .build(),
+                        );
+                    }
+                    pso::DescriptorType::Buffer {
+                        format: pso::BufferDescriptorFormat::Structured { .. },
+                        ..
+                    } => {
+                        buffer_infos.push(
+                            vk::DescriptorBufferInfo::builder()
+                                .buffer(vk::Buffer::null())
+                                .offset(0)
+                                .range(vk::WHOLE_SIZE)
+                                .build(),
+                        );
+                    }
+                    pso::DescriptorType::Buffer {
+                        format: pso::BufferDescriptorFormat::Texel,
+                        ..
+                    } => {
+                        texel_buffer_views.push(vk::BufferView::null());
+                    }
+                    _ => panic!("Null descriptors of type {:?} are not supported", ty),
+                },
             }
         }
 
@@ -1515,35 +1813,124 @@ impl d::Device<B> for super::Device {
     }
 
     fn create_semaphore(&self) -> Result<n::Semaphore, d::OutOfMemory> {
-        let info = vk::SemaphoreCreateInfo::builder().flags(vk::SemaphoreCreateFlags::empty());
+        self.create_semaphore_impl(vk::ExternalSemaphoreHandleTypeFlags::empty())
+    }
+
+    fn create_timeline_semaphore(
+        &self,
+        initial_value: u64,
+    ) -> Result<n::TimelineSemaphore, d::OutOfMemory> {
+        let mut type_info = vk::SemaphoreTypeCreateInfo::builder()
+            .semaphore_type(vk::SemaphoreType::TIMELINE)
+            .initial_value(initial_value);
+        let info = vk::SemaphoreCreateInfo::builder().push_next(&mut type_info);
 
         let result = unsafe { self.shared.raw.create_semaphore(&info, None) };
 
         match result {
-            Ok(semaphore) => Ok(n::Semaphore(semaphore)),
+            Ok(semaphore) => Ok(n::TimelineSemaphore(semaphore)),
             Err(vk::Result::ERROR_OUT_OF_HOST_MEMORY) => Err(d::OutOfMemory::Host.into()),
             Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY) => Err(d::OutOfMemory::Device.into()),
             _ => unreachable!(),
         }
     }
 
-    fn create_fence(&self, signaled: bool) -> Result<n::Fence, d::OutOfMemory> {
-        let info = vk::FenceCreateInfo::builder().flags(if signaled {
-            vk::FenceCreateFlags::SIGNALED
-        } else {
-            vk::FenceCreateFlags::empty()
-        });
+    unsafe fn get_timeline_semaphore_value(
+        &self,
+        semaphore: &n::TimelineSemaphore,
+    ) -> Result<u64, d::DeviceLost> {
+        let device = self.shared.raw.handle();
+        let mut value = 0;
+        let result = match self
+            .shared
+            .extension_fns
+            .timeline_semaphore
+            .as_ref()
+            .expect("Feature TIMELINE_SEMAPHORE must be enabled to use timeline semaphores")
+        {
+            ExtensionFn::Extension(t) => {
+                t.get_semaphore_counter_value_khr(device, semaphore.0, &mut value)
+            }
+            ExtensionFn::Promoted => self.shared.raw.fp_v1_2().get_semaphore_counter_value(
+                device,
+                semaphore.0,
+                &mut value,
+            ),
+        };
+        match result {
+            vk::Result::SUCCESS => Ok(value),
+            vk::Result::ERROR_DEVICE_LOST => Err(d::DeviceLost),
+            _ => unreachable!(),
+        }
+    }
 
-        let result = unsafe { self.shared.raw.create_fence(&info, None) };
+    unsafe fn signal_timeline_semaphore(
+        &self,
+        semaphore: &n::TimelineSemaphore,
+        value: u64,
+    ) -> Result<(), d::OutOfMemory> {
+        let device = self.shared.raw.handle();
+        let info = vk::SemaphoreSignalInfo::builder()
+            .semaphore(semaphore.0)
+            .value(value);
+        let result = match self
+            .shared
+            .extension_fns
+            .timeline_semaphore
+            .as_ref()
+            .expect("Feature TIMELINE_SEMAPHORE must be enabled to use timeline semaphores")
+        {
+            ExtensionFn::Extension(t) => t.signal_semaphore_khr(device, &*info),
+            ExtensionFn::Promoted => self.shared.raw.fp_v1_2().signal_semaphore(device, &*info),
+        };
+        match result {
+            vk::Result::SUCCESS => Ok(()),
+            vk::Result::ERROR_OUT_OF_HOST_MEMORY => Err(d::OutOfMemory::Host),
+            vk::Result::ERROR_OUT_OF_DEVICE_MEMORY => Err(d::OutOfMemory::Device),
+            _ => unreachable!(),
+        }
+    }
 
+    unsafe fn wait_for_timeline_semaphore(
+        &self,
+        semaphore: &n::TimelineSemaphore,
+        value: u64,
+        timeout_ns: u64,
+    ) -> Result<bool, d::WaitError> {
+        let device = self.shared.raw.handle();
+        let semaphores = [semaphore.0];
+        let values = [value];
+        let info = vk::SemaphoreWaitInfo::builder()
+            .semaphores(&semaphores)
+            .values(&values);
+        let result = match self
+            .shared
+            .extension_fns
+            .timeline_semaphore
+            .as_ref()
+            .expect("Feature TIMELINE_SEMAPHORE must be enabled to use timeline semaphores")
+        {
+            ExtensionFn::Extension(t) => t.wait_semaphores_khr(device, &*info, timeout_ns),
+            ExtensionFn::Promoted => self
+                .shared
+                .raw
+                .fp_v1_2()
+                .wait_semaphores(device, &*info, timeout_ns),
+        };
         match result {
-            Ok(fence) => Ok(n::Fence(fence)),
-            Err(vk::Result::ERROR_OUT_OF_HOST_MEMORY) => Err(d::OutOfMemory::Host.into()),
-            Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY) => Err(d::OutOfMemory::Device.into()),
+            vk::Result::SUCCESS => Ok(true),
+            vk::Result::TIMEOUT => Ok(false),
+            vk::Result::ERROR_DEVICE_LOST => Err(d::DeviceLost.into()),
+            vk::Result::ERROR_OUT_OF_HOST_MEMORY => Err(d::OutOfMemory::Host.into()),
+            vk::Result::ERROR_OUT_OF_DEVICE_MEMORY => Err(d::OutOfMemory::Device.into()),
             _ => unreachable!(),
         }
     }
 
+    fn create_fence(&self, signaled: bool) -> Result<n::Fence, d::OutOfMemory> {
+        self.create_fence_impl(signaled, vk::ExternalFenceHandleTypeFlags::empty())
+    }
+
     unsafe fn reset_fence(&self, fence: &mut n::Fence) -> Result<(), d::OutOfMemory> {
         match self.shared.raw.reset_fences(&[fence.0]) {
             Ok(()) => Ok(()),
@@ -1658,6 +2045,9 @@ impl d::Device<B> for super::Device {
                 vk::QueryType::TIMESTAMP,
                 vk::QueryPipelineStatisticFlags::empty(),
             ),
+            query::Type::PerformanceCounters(_) => {
+                return Err(query::CreationError::Unsupported(ty))
+            }
         };
 
         let info = vk::QueryPoolCreateInfo::builder()
@@ -1705,6 +2095,10 @@ impl d::Device<B> for super::Device {
         }
     }
 
+    unsafe fn reset_query_pool(&self, _pool: &n::QueryPool, _queries: Range<query::Id>) {
+        unimplemented!()
+    }
+
     unsafe fn destroy_query_pool(&self, pool: n::QueryPool) {
         self.shared.raw.destroy_query_pool(pool.0, None);
     }
@@ -1725,10 +2119,49 @@ impl d::Device<B> for super::Device {
         self.shared.raw.destroy_pipeline(pipeline.0, None);
     }
 
+    unsafe fn create_graphics_pipeline_library<'a>(
+        &self,
+        _desc: &pso::GraphicsPipelineDesc<'a, B>,
+        _parts: pso::GraphicsPipelineLibraryParts,
+        _cache: Option<&n::PipelineCache>,
+    ) -> Result<(), pso::CreationError> {
+        unimplemented!()
+    }
+
+    unsafe fn link_graphics_pipeline<'a>(
+        &self,
+        _desc: &pso::GraphicsPipelineLinkDesc<'a, B>,
+        _cache: Option<&n::PipelineCache>,
+    ) -> Result<n::GraphicsPipeline, pso::CreationError> {
+        unimplemented!()
+    }
+
+    unsafe fn destroy_graphics_pipeline_library(&self, _library: ()) {
+        unimplemented!()
+    }
+
     unsafe fn destroy_compute_pipeline(&self, pipeline: n::ComputePipeline) {
         self.shared.raw.destroy_pipeline(pipeline.0, None);
     }
 
+    unsafe fn get_graphics_pipeline_executables(
+        &self,
+        _pipeline: &n::GraphicsPipeline,
+    ) -> Vec<pso::PipelineExecutable> {
+        unimplemented!()
+    }
+
+    unsafe fn get_compute_pipeline_executables(
+        &self,
+        _pipeline: &n::ComputePipeline,
+    ) -> Vec<pso::PipelineExecutable> {
+        unimplemented!()
+    }
+
+    unsafe fn destroy_ray_tracing_pipeline(&self, _pipeline: ()) {
+        unimplemented!()
+    }
+
     unsafe fn destroy_framebuffer(&self, fb: n::Framebuffer) {
         match fb {
             n::Framebuffer::ImageLess(raw) => {
@@ -1750,6 +2183,10 @@ impl d::Device<B> for super::Device {
         self.shared.raw.destroy_buffer_view(view.raw, None);
     }
 
+    unsafe fn destroy_acceleration_structure(&self, _accel_struct: ()) {
+        unimplemented!()
+    }
+
     unsafe fn destroy_image(&self, image: n::Image) {
         self.shared.raw.destroy_image(image.raw, None);
     }
@@ -1776,10 +2213,212 @@ impl d::Device<B> for super::Device {
         self.shared.raw.destroy_fence(fence.0, None);
     }
 
+    fn create_exportable_fence(
+        &self,
+        signaled: bool,
+        handle_types: ExternalSyncHandleTypes,
+    ) -> Result<n::Fence, ExternalSyncError> {
+        let unsupported = handle_types - self.external_fence_handle_types();
+        if !unsupported.is_empty() {
+            return Err(ExternalSyncError::UnsupportedHandleTypes(unsupported));
+        }
+        Ok(self.create_fence_impl(
+            signaled,
+            conv::map_external_fence_handle_types(handle_types),
+        )?)
+    }
+
+    unsafe fn import_fence(
+        &self,
+        fence: &mut n::Fence,
+        handle: ExternalSyncHandle,
+    ) -> Result<(), ExternalSyncError> {
+        let extension_fns = &self.shared.extension_fns;
+        let vk_handle_type = conv::map_external_fence_handle_types(handle.handle_type().into());
+        let result = match handle {
+            ExternalSyncHandle::SyncFd(fd) if extension_fns.external_fence_fd.is_some() => {
+                let info = vk::ImportFenceFdInfoKHR::builder()
+                    .fence(fence.0)
+                    .flags(vk::FenceImportFlags::TEMPORARY)
+                    .handle_type(vk_handle_type)
+                    .fd(fd);
+                extension_fns
+                    .external_fence_fd
+                    .as_ref()
+                    .unwrap()
+                    .import_fence_fd_khr(self.shared.raw.handle(), &*info)
+            }
+            ExternalSyncHandle::OpaqueWin32(handle)
+                if extension_fns.external_fence_win32.is_some() =>
+            {
+                let info = vk::ImportFenceWin32HandleInfoKHR::builder()
+                    .fence(fence.0)
+                    .handle_type(vk_handle_type)
+                    .handle(handle);
+                extension_fns
+                    .external_fence_win32
+                    .as_ref()
+                    .unwrap()
+                    .import_fence_win32_handle_khr(self.shared.raw.handle(), &*info)
+            }
+            other => {
+                return Err(ExternalSyncError::UnsupportedHandleTypes(
+                    other.handle_type().into(),
+                ))
+            }
+        };
+
+        map_external_sync_result(result)
+    }
+
+    unsafe fn export_fence(
+        &self,
+        fence: &n::Fence,
+        handle_type: ExternalSyncHandleType,
+    ) -> Result<ExternalSyncHandle, ExternalSyncError> {
+        let extension_fns = &self.shared.extension_fns;
+        let vk_handle_type = conv::map_external_fence_handle_types(handle_type.into());
+        let (result, handle) = match handle_type {
+            ExternalSyncHandleType::SyncFd if extension_fns.external_fence_fd.is_some() => {
+                let info = vk::FenceGetFdInfoKHR::builder()
+                    .fence(fence.0)
+                    .handle_type(vk_handle_type);
+                let mut fd = -1;
+                let result = extension_fns
+                    .external_fence_fd
+                    .as_ref()
+                    .unwrap()
+                    .get_fence_fd_khr(self.shared.raw.handle(), &*info, &mut fd);
+                (result, ExternalSyncHandle::SyncFd(fd))
+            }
+            ExternalSyncHandleType::OpaqueWin32 if extension_fns.external_fence_win32.is_some() => {
+                let info = vk::FenceGetWin32HandleInfoKHR::builder()
+                    .fence(fence.0)
+                    .handle_type(vk_handle_type);
+                let mut handle = ptr::null_mut();
+                let result = extension_fns
+                    .external_fence_win32
+                    .as_ref()
+                    .unwrap()
+                    .get_fence_win32_handle_khr(self.shared.raw.handle(), &*info, &mut handle);
+                (result, ExternalSyncHandle::OpaqueWin32(handle))
+            }
+            _ => {
+                return Err(ExternalSyncError::UnsupportedHandleTypes(
+                    handle_type.into(),
+                ))
+            }
+        };
+
+        map_external_sync_result(result).map(|()| handle)
+    }
+
+    fn create_exportable_semaphore(
+        &self,
+        handle_types: ExternalSyncHandleTypes,
+    ) -> Result<n::Semaphore, ExternalSyncError> {
+        let unsupported = handle_types - self.external_semaphore_handle_types();
+        if !unsupported.is_empty() {
+            return Err(ExternalSyncError::UnsupportedHandleTypes(unsupported));
+        }
+        Ok(self.create_semaphore_impl(conv::map_external_semaphore_handle_types(handle_types))?)
+    }
+
+    unsafe fn import_semaphore(
+        &self,
+        semaphore: &mut n::Semaphore,
+        handle: ExternalSyncHandle,
+    ) -> Result<(), ExternalSyncError> {
+        let extension_fns = &self.shared.extension_fns;
+        let vk_handle_type = conv::map_external_semaphore_handle_types(handle.handle_type().into());
+        let result = match handle {
+            ExternalSyncHandle::SyncFd(fd) if extension_fns.external_semaphore_fd.is_some() => {
+                let info = vk::ImportSemaphoreFdInfoKHR::builder()
+                    .semaphore(semaphore.0)
+                    .flags(vk::SemaphoreImportFlags::TEMPORARY)
+                    .handle_type(vk_handle_type)
+                    .fd(fd);
+                extension_fns
+                    .external_semaphore_fd
+                    .as_ref()
+                    .unwrap()
+                    .import_semaphore_fd_khr(self.shared.raw.handle(), &*info)
+            }
+            ExternalSyncHandle::OpaqueWin32(handle)
+                if extension_fns.external_semaphore_win32.is_some() =>
+            {
+                let info = vk::ImportSemaphoreWin32HandleInfoKHR::builder()
+                    .semaphore(semaphore.0)
+                    .handle_type(vk_handle_type)
+                    .handle(handle);
+                extension_fns
+                    .external_semaphore_win32
+                    .as_ref()
+                    .unwrap()
+                    .import_semaphore_win32_handle_khr(self.shared.raw.handle(), &*info)
+            }
+            other => {
+                return Err(ExternalSyncError::UnsupportedHandleTypes(
+                    other.handle_type().into(),
+                ))
+            }
+        };
+
+        map_external_sync_result(result)
+    }
+
+    unsafe fn export_semaphore(
+        &self,
+        semaphore: &n::Semaphore,
+        handle_type: ExternalSyncHandleType,
+    ) -> Result<ExternalSyncHandle, ExternalSyncError> {
+        let extension_fns = &self.shared.extension_fns;
+        let vk_handle_type = conv::map_external_semaphore_handle_types(handle_type.into());
+        let (result, handle) = match handle_type {
+            ExternalSyncHandleType::SyncFd if extension_fns.external_semaphore_fd.is_some() => {
+                let info = vk::SemaphoreGetFdInfoKHR::builder()
+                    .semaphore(semaphore.0)
+                    .handle_type(vk_handle_type);
+                let mut fd = -1;
+                let result = extension_fns
+                    .external_semaphore_fd
+                    .as_ref()
+                    .unwrap()
+                    .get_semaphore_fd_khr(self.shared.raw.handle(), &*info, &mut fd);
+                (result, ExternalSyncHandle::SyncFd(fd))
+            }
+            ExternalSyncHandleType::OpaqueWin32
+                if extension_fns.external_semaphore_win32.is_some() =>
+            {
+                let info = vk::SemaphoreGetWin32HandleInfoKHR::builder()
+                    .semaphore(semaphore.0)
+                    .handle_type(vk_handle_type);
+                let mut handle = ptr::null_mut();
+                let result = extension_fns
+                    .external_semaphore_win32
+                    .as_ref()
+                    .unwrap()
+                    .get_semaphore_win32_handle_khr(self.shared.raw.handle(), &*info, &mut handle);
+                (result, ExternalSyncHandle::OpaqueWin32(handle))
+            }
+            _ => {
+                return Err(ExternalSyncError::UnsupportedHandleTypes(
+                    handle_type.into(),
+                ))
+            }
+        };
+
+        map_external_sync_result(result).map(|()| handle)
+    }
+
     unsafe fn destroy_semaphore(&self, semaphore: n::Semaphore) {
         self.shared.raw.destroy_semaphore(semaphore.0, None);
     }
 
+    unsafe fn destroy_timeline_semaphore(&self, semaphore: n::TimelineSemaphore) {
+        self.shared.raw.destroy_semaphore(semaphore.0, None);
+    }
+
     unsafe fn destroy_event(&self, event: n::Event) {
         self.shared.raw.destroy_event(event.0, None);
     }
@@ -1866,6 +2505,27 @@ impl d::Device<B> for super::Device {
             .set_object_name(vk::ObjectType::PIPELINE_LAYOUT, pipeline_layout.raw, name)
     }
 
+    unsafe fn create_private_data_slot(&self) -> Result<(), d::OutOfMemory> {
+        unimplemented!()
+    }
+
+    unsafe fn destroy_private_data_slot(&self, _slot: ()) {
+        unimplemented!()
+    }
+
+    unsafe fn set_private_data(
+        &self,
+        _object: d::PrivateDataObject<B>,
+        _slot: &(),
+        _data: u64,
+    ) -> Result<(), d::OutOfMemory> {
+        unimplemented!()
+    }
+
+    unsafe fn get_private_data(&self, _object: d::PrivateDataObject<B>, _slot: &()) -> u64 {
+        unimplemented!()
+    }
+
     unsafe fn set_display_power_state(
         &self,
         display: &hal::display::Display<B>,
@@ -2009,6 +2669,214 @@ impl super::Device {
         mask
     }
 
+    fn create_fence_impl(
+        &self,
+        signaled: bool,
+        handle_types: vk::ExternalFenceHandleTypeFlags,
+    ) -> Result<n::Fence, d::OutOfMemory> {
+        let mut export_info = vk::ExportFenceCreateInfo::builder().handle_types(handle_types);
+        let mut info = vk::FenceCreateInfo::builder().flags(if signaled {
+            vk::FenceCreateFlags::SIGNALED
+        } else {
+            vk::FenceCreateFlags::empty()
+        });
+        if !handle_types.is_empty() {
+            info = info.push_next(&mut export_info);
+        }
+
+        let result = unsafe { self.shared.raw.create_fence(&info, None) };
+
+        match result {
+            Ok(fence) => Ok(n::Fence(fence)),
+            Err(vk::Result::ERROR_OUT_OF_HOST_MEMORY) => Err(d::OutOfMemory::Host.into()),
+            Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY) => Err(d::OutOfMemory::Device.into()),
+            _ => unreachable!(),
+        }
+    }
+
+    fn create_semaphore_impl(
+        &self,
+        handle_types: vk::ExternalSemaphoreHandleTypeFlags,
+    ) -> Result<n::Semaphore, d::OutOfMemory> {
+        let mut export_info = vk::ExportSemaphoreCreateInfo::builder().handle_types(handle_types);
+        let mut info = vk::SemaphoreCreateInfo::builder().flags(vk::SemaphoreCreateFlags::empty());
+        if !handle_types.is_empty() {
+            info = info.push_next(&mut export_info);
+        }
+
+        let result = unsafe { self.shared.raw.create_semaphore(&info, None) };
+
+        match result {
+            Ok(semaphore) => Ok(n::Semaphore(semaphore)),
+            Err(vk::Result::ERROR_OUT_OF_HOST_MEMORY) => Err(d::OutOfMemory::Host.into()),
+            Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY) => Err(d::OutOfMemory::Device.into()),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Get the external fence handle types whose extensions are enabled.
+    fn external_fence_handle_types(&self) -> ExternalSyncHandleTypes {
+        let extension_fns = &self.shared.extension_fns;
+        let mut types = ExternalSyncHandleTypes::empty();
+        types.set(
+            ExternalSyncHandleTypes::SYNC_FD,
+            extension_fns.external_fence_fd.is_some(),
+        );
+        types.set(
+            ExternalSyncHandleTypes::OPAQUE_WIN32,
+            extension_fns.external_fence_win32.is_some(),
+        );
+        types
+    }
+
+    /// Get the external semaphore handle types whose extensions are enabled.
+    fn external_semaphore_handle_types(&self) -> ExternalSyncHandleTypes {
+        let extension_fns = &self.shared.extension_fns;
+        let mut types = ExternalSyncHandleTypes::empty();
+        types.set(
+            ExternalSyncHandleTypes::SYNC_FD,
+            extension_fns.external_semaphore_fd.is_some(),
+        );
+        types.set(
+            ExternalSyncHandleTypes::OPAQUE_WIN32,
+            extension_fns.external_semaphore_win32.is_some(),
+        );
+        types
+    }
+
+    /// Check that the handle types are among the ones whose extensions are enabled.
+    fn check_external_memory_handle_types(
+        &self,
+        handle_types: ExternalMemoryHandleTypes,
+    ) -> Result<(), ExternalMemoryError> {
+        let extension_fns = &self.shared.extension_fns;
+        let mut supported = ExternalMemoryHandleTypes::empty();
+        supported.set(
+            ExternalMemoryHandleTypes::OPAQUE_FD,
+            extension_fns.external_memory_fd.is_some(),
+        );
+        supported.set(
+            ExternalMemoryHandleTypes::OPAQUE_WIN32,
+            extension_fns.external_memory_win32.is_some(),
+        );
+        supported.set(
+            ExternalMemoryHandleTypes::HOST_POINTER,
+            extension_fns.external_memory_host.is_some(),
+        );
+        if supported.contains(handle_types) {
+            Ok(())
+        } else {
+            Err(ExternalMemoryError::UnsupportedHandleTypes(
+                handle_types - supported,
+            ))
+        }
+    }
+
+    unsafe fn allocate_external_memory(
+        &self,
+        info: &vk::MemoryAllocateInfo,
+    ) -> Result<n::Memory, ExternalMemoryError> {
+        match self.shared.raw.allocate_memory(info, None) {
+            Ok(memory) => Ok(n::Memory { raw: memory }),
+            Err(vk::Result::ERROR_TOO_MANY_OBJECTS) | Err(vk::Result::ERROR_OUT_OF_HOST_MEMORY) => {
+                Err(d::OutOfMemory::Host.into())
+            }
+            Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY) => Err(d::OutOfMemory::Device.into()),
+            Err(vk::Result::ERROR_INVALID_EXTERNAL_HANDLE) => {
+                Err(ExternalMemoryError::InvalidHandle)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    unsafe fn create_buffer_impl(
+        &self,
+        size: u64,
+        usage: buffer::Usage,
+        sparse: memory::SparseFlags,
+        handle_types: vk::ExternalMemoryHandleTypeFlags,
+    ) -> Result<n::Buffer, buffer::CreationError> {
+        let mut external_info =
+            vk::ExternalMemoryBufferCreateInfo::builder().handle_types(handle_types);
+        let mut info = vk::BufferCreateInfo::builder()
+            .flags(conv::map_buffer_create_flags(sparse))
+            .size(size)
+            .usage(conv::map_buffer_usage(usage))
+            .sharing_mode(vk::SharingMode::EXCLUSIVE); // TODO:
+        if !handle_types.is_empty() {
+            info = info.push_next(&mut external_info);
+        }
+
+        let result = self.shared.raw.create_buffer(&info, None);
+
+        match result {
+            Ok(raw) => Ok(n::Buffer { raw }),
+            Err(vk::Result::ERROR_OUT_OF_HOST_MEMORY) => Err(d::OutOfMemory::Host.into()),
+            Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY) => Err(d::OutOfMemory::Device.into()),
+            _ => unreachable!(),
+        }
+    }
+
+    unsafe fn create_image_impl(
+        &self,
+        kind: image::Kind,
+        mip_levels: image::Level,
+        format: format::Format,
+        tiling: image::Tiling,
+        usage: image::Usage,
+        sparse: memory::SparseFlags,
+        view_caps: image::ViewCapabilities,
+        handle_types: vk::ExternalMemoryHandleTypeFlags,
+    ) -> Result<n::Image, image::CreationError> {
+        let flags = conv::map_view_capabilities_sparse(sparse, view_caps);
+        let extent = conv::map_extent(kind.extent());
+        let array_layers = kind.num_layers();
+        let samples = kind.num_samples();
+        let image_type = match kind {
+            image::Kind::D1(..) => vk::ImageType::TYPE_1D,
+            image::Kind::D2(..) => vk::ImageType::TYPE_2D,
+            image::Kind::D3(..) => vk::ImageType::TYPE_3D,
+        };
+
+        //Note: this is a hack, we should expose this in the API instead
+        let layout = match tiling {
+            image::Tiling::Linear => vk::ImageLayout::PREINITIALIZED,
+            image::Tiling::Optimal => vk::ImageLayout::UNDEFINED,
+        };
+
+        let mut external_info =
+            vk::ExternalMemoryImageCreateInfo::builder().handle_types(handle_types);
+        let mut info = vk::ImageCreateInfo::builder()
+            .flags(flags)
+            .image_type(image_type)
+            .format(conv::map_format(format))
+            .extent(extent.clone())
+            .mip_levels(mip_levels as u32)
+            .array_layers(array_layers as u32)
+            .samples(conv::map_sample_count_flags(samples))
+            .tiling(conv::map_tiling(tiling))
+            .usage(conv::map_image_usage(usage))
+            .sharing_mode(vk::SharingMode::EXCLUSIVE) // TODO:
+            .initial_layout(layout);
+        if !handle_types.is_empty() {
+            info = info.push_next(&mut external_info);
+        }
+
+        let result = self.shared.raw.create_image(&info, None);
+
+        match result {
+            Ok(raw) => Ok(n::Image {
+                raw,
+                ty: image_type,
+                flags,
+                extent,
+            }),
+            Err(vk::Result::ERROR_OUT_OF_HOST_MEMORY) => Err(d::OutOfMemory::Host.into()),
+            Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY) => Err(d::OutOfMemory::Device.into()),
+            _ => unreachable!(),
+        }
+    }
+
     fn get_ash_memory_type_index(&self, hal_type: MemoryTypeId) -> u32 {
         let mut hal_count = hal_type.0;
         for ash_index in 0..32 {
@@ -74,6 +74,9 @@ pub struct RawInstance {
     handle_is_external: bool,
     debug_messenger: Option<DebugMessenger>,
     get_physical_device_properties: Option<vk::KhrGetPhysicalDeviceProperties2Fn>,
+    external_memory_capabilities: Option<vk::KhrExternalMemoryCapabilitiesFn>,
+    external_fence_capabilities: Option<vk::KhrExternalFenceCapabilitiesFn>,
+    external_semaphore_capabilities: Option<vk::KhrExternalSemaphoreCapabilitiesFn>,
     display: Option<khr::Display>,
 }
 
@@ -384,6 +387,9 @@ impl Instance {
         }
 
         extensions.push(vk::KhrGetPhysicalDeviceProperties2Fn::name());
+        extensions.push(vk::KhrExternalMemoryCapabilitiesFn::name());
+        extensions.push(vk::KhrExternalFenceCapabilitiesFn::name());
+        extensions.push(vk::KhrExternalSemaphoreCapabilitiesFn::name());
 
         // VK_KHR_storage_buffer_storage_class required for `Naga` on Vulkan 1.0 devices
         if driver_api_version == Version::V1_0 {
@@ -486,6 +492,39 @@ impl Instance {
                 })
             });
 
+        let external_memory_capabilities = extensions
+            .iter()
+            .find(|&&ext| ext == vk::KhrExternalMemoryCapabilitiesFn::name())
+            .map(|_| {
+                vk::KhrExternalMemoryCapabilitiesFn::load(|name| unsafe {
+                    std::mem::transmute(
+                        entry.get_instance_proc_addr(instance.handle(), name.as_ptr()),
+                    )
+                })
+            });
+
+        let external_fence_capabilities = extensions
+            .iter()
+            .find(|&&ext| ext == vk::KhrExternalFenceCapabilitiesFn::name())
+            .map(|_| {
+                vk::KhrExternalFenceCapabilitiesFn::load(|name| unsafe {
+                    std::mem::transmute(
+                        entry.get_instance_proc_addr(instance.handle(), name.as_ptr()),
+                    )
+                })
+            });
+
+        let external_semaphore_capabilities = extensions
+            .iter()
+            .find(|&&ext| ext == vk::KhrExternalSemaphoreCapabilitiesFn::name())
+            .map(|_| {
+                vk::KhrExternalSemaphoreCapabilitiesFn::load(|name| unsafe {
+                    std::mem::transmute(
+                        entry.get_instance_proc_addr(instance.handle(), name.as_ptr()),
+                    )
+                })
+            });
+
         let display = extensions
             .iter()
             .find(|&&ext| ext == khr::Display::name())
@@ -527,6 +566,9 @@ impl Instance {
                 handle_is_external,
                 debug_messenger,
                 get_physical_device_properties,
+                external_memory_capabilities,
+                external_fence_capabilities,
+                external_semaphore_capabilities,
                 display,
             }),
             extensions,
@@ -790,12 +832,27 @@ impl queue::QueueFamily for QueueFamily {
             .queue_flags
             .contains(vk::QueueFlags::SPARSE_BINDING)
     }
+    fn supports_protected(&self) -> bool {
+        self.properties
+            .queue_flags
+            .contains(vk::QueueFlags::PROTECTED)
+    }
 }
 
 struct DeviceExtensionFunctions {
     mesh_shaders: Option<ExtensionFn<MeshShader>>,
     draw_indirect_count: Option<ExtensionFn<khr::DrawIndirectCount>>,
+    timeline_semaphore: Option<ExtensionFn<vk::KhrTimelineSemaphoreFn>>,
     display_control: Option<vk::ExtDisplayControlFn>,
+    conditional_rendering: Option<vk::ExtConditionalRenderingFn>,
+    fragment_shading_rate: Option<vk::KhrFragmentShadingRateFn>,
+    external_memory_fd: Option<vk::KhrExternalMemoryFdFn>,
+    external_memory_win32: Option<vk::KhrExternalMemoryWin32Fn>,
+    external_memory_host: Option<vk::ExtExternalMemoryHostFn>,
+    external_fence_fd: Option<vk::KhrExternalFenceFdFn>,
+    external_fence_win32: Option<vk::KhrExternalFenceWin32Fn>,
+    external_semaphore_fd: Option<vk::KhrExternalSemaphoreFdFn>,
+    external_semaphore_win32: Option<vk::KhrExternalSemaphoreWin32Fn>,
 }
 
 // TODO there's no reason why this can't be unified--the function pointers should all be the same--it's not clear how to do this with `ash`.
@@ -831,6 +888,8 @@ pub struct RawDevice {
     imageless_framebuffers: bool,
     image_view_usage: bool,
     timestamp_period: f32,
+    /// Memory needs to be allocated with `vk::MemoryAllocateFlags::DEVICE_ADDRESS` for the addresses of the buffers bound to it to be queried.
+    buffer_device_address: bool,
 }
 
 impl fmt::Debug for RawDevice {
@@ -927,6 +986,27 @@ impl queue::Queue<Backend> for Queue {
         Ic: Iterator<Item = &'a command::CommandBuffer>,
         Iw: Iterator<Item = (&'a native::Semaphore, PipelineStage)>,
         Is: Iterator<Item = &'a native::Semaphore>,
+    {
+        self.submit_with_flags(
+            command_buffers,
+            wait_semaphores,
+            signal_semaphores,
+            fence,
+            queue::SubmitFlags::empty(),
+        )
+    }
+
+    unsafe fn submit_with_flags<'a, Ic, Iw, Is>(
+        &mut self,
+        command_buffers: Ic,
+        wait_semaphores: Iw,
+        signal_semaphores: Is,
+        fence: Option<&mut native::Fence>,
+        flags: queue::SubmitFlags,
+    ) where
+        Ic: Iterator<Item = &'a command::CommandBuffer>,
+        Iw: Iterator<Item = (&'a native::Semaphore, PipelineStage)>,
+        Is: Iterator<Item = &'a native::Semaphore>,
     {
         //TODO: avoid heap allocations
         let mut waits = Vec::new();
@@ -941,6 +1021,7 @@ impl queue::Queue<Backend> for Queue {
             .map(|semaphore| semaphore.0)
             .collect::<Vec<_>>();
 
+        let mut protected_info = vk::ProtectedSubmitInfo::builder().protected_submit(true);
         let mut info = vk::SubmitInfo::builder()
             .wait_semaphores(&waits)
             .command_buffers(&buffers)
@@ -949,6 +1030,9 @@ impl queue::Queue<Backend> for Queue {
         if !stages.is_empty() {
             info = info.wait_dst_stage_mask(&stages);
         }
+        if flags.contains(queue::SubmitFlags::PROTECTED) {
+            info = info.push_next(&mut protected_info);
+        }
 
         let fence_raw = fence.map(|fence| fence.0).unwrap_or(vk::Fence::null());
 
@@ -958,6 +1042,56 @@ impl queue::Queue<Backend> for Queue {
         }
     }
 
+    unsafe fn submit_timeline<'a, Ic, Iw, Is>(
+        &mut self,
+        command_buffers: Ic,
+        wait_semaphores: Iw,
+        signal_semaphores: Is,
+    ) where
+        Ic: Iterator<Item = &'a command::CommandBuffer>,
+        Iw: Iterator<Item = (&'a native::TimelineSemaphore, u64, PipelineStage)>,
+        Is: Iterator<Item = (&'a native::TimelineSemaphore, u64)>,
+    {
+        //TODO: avoid heap allocations
+        let mut waits = Vec::new();
+        let mut wait_values = Vec::new();
+        let mut stages = Vec::new();
+        let mut signals = Vec::new();
+        let mut signal_values = Vec::new();
+
+        let buffers = command_buffers.map(|cmd| cmd.raw).collect::<Vec<_>>();
+        for (semaphore, value, stage) in wait_semaphores {
+            waits.push(semaphore.0);
+            wait_values.push(value);
+            stages.push(conv::map_pipeline_stage(stage));
+        }
+        for (semaphore, value) in signal_semaphores {
+            signals.push(semaphore.0);
+            signal_values.push(value);
+        }
+
+        let mut timeline_info = vk::TimelineSemaphoreSubmitInfo::builder()
+            .wait_semaphore_values(&wait_values)
+            .signal_semaphore_values(&signal_values);
+        let mut info = vk::SubmitInfo::builder()
+            .wait_semaphores(&waits)
+            .command_buffers(&buffers)
+            .signal_semaphores(&signals)
+            .push_next(&mut timeline_info);
+        // If count is zero, AMD driver crashes if nullptr is not set for stage masks
+        if !stages.is_empty() {
+            info = info.wait_dst_stage_mask(&stages);
+        }
+
+        let result = self
+            .device
+            .raw
+            .queue_submit(*self.raw, &[*info], vk::Fence::null());
+        if let Err(e) = result {
+            error!("Submit resulted in {:?}", e);
+        }
+    }
+
     unsafe fn bind_sparse<'a, Iw, Is, Ibi, Ib, Iii, Io, Ii>(
         &mut self,
         wait_semaphores: Iw,
@@ -1134,6 +1268,67 @@ impl queue::Queue<Backend> for Queue {
         }
     }
 
+    unsafe fn present_multiple<'a, I>(
+        &mut self,
+        presentations: I,
+    ) -> Vec<Result<Option<Suboptimal>, PresentError>>
+    where
+        I: Iterator<
+            Item = (
+                &'a mut window::Surface,
+                window::SurfaceImage,
+                Option<&'a mut native::Semaphore>,
+            ),
+        >,
+    {
+        let mut wait_semaphores = Vec::new();
+        let mut swapchains = Vec::new();
+        let mut image_indices = Vec::new();
+        let mut signals = Vec::new();
+        for (surface, image, wait_semaphore) in presentations {
+            let ssc = surface.swapchain.as_ref().unwrap();
+            match wait_semaphore {
+                Some(wait_semaphore) => wait_semaphores.push(wait_semaphore.0),
+                None => {
+                    signals.push(ssc.semaphore.0);
+                    wait_semaphores.push(ssc.semaphore.0);
+                }
+            }
+            swapchains.push(ssc.swapchain.raw);
+            image_indices.push(image.index);
+        }
+        if !signals.is_empty() {
+            let submit_info = vk::SubmitInfo::builder().signal_semaphores(&signals);
+            self.device
+                .raw
+                .queue_submit(*self.raw, &[*submit_info], vk::Fence::null())
+                .unwrap();
+        }
+
+        let mut results = vec![vk::Result::SUCCESS; swapchains.len()];
+        let present_info = vk::PresentInfoKHR::builder()
+            .wait_semaphores(&wait_semaphores)
+            .swapchains(&swapchains)
+            .image_indices(&image_indices)
+            .results(&mut results);
+        // The outcome of each presentation is reported in `results`.
+        let _ = self.swapchain_fn.queue_present(*self.raw, &present_info);
+
+        results
+            .into_iter()
+            .map(|result| match result {
+                vk::Result::SUCCESS => Ok(None),
+                vk::Result::SUBOPTIMAL_KHR => Ok(Some(Suboptimal)),
+                vk::Result::ERROR_OUT_OF_HOST_MEMORY => Err(OutOfMemory::Host.into()),
+                vk::Result::ERROR_OUT_OF_DEVICE_MEMORY => Err(OutOfMemory::Device.into()),
+                vk::Result::ERROR_DEVICE_LOST => Err(DeviceLost.into()),
+                vk::Result::ERROR_OUT_OF_DATE_KHR => Err(OutOfDate.into()),
+                vk::Result::ERROR_SURFACE_LOST_KHR => Err(SurfaceLost.into()),
+                _ => panic!("Failed to present frame"),
+            })
+            .collect()
+    }
+
     fn wait_idle(&mut self) -> Result<(), OutOfMemory> {
         match unsafe { self.device.raw.queue_wait_idle(*self.raw) } {
             Ok(()) => Ok(()),
@@ -1182,9 +1377,12 @@ impl hal::Backend for Backend {
     type Image = native::Image;
     type ImageView = native::ImageView;
     type Sampler = native::Sampler;
+    type AccelerationStructure = ();
 
     type ComputePipeline = native::ComputePipeline;
+    type RayTracingPipeline = ();
     type GraphicsPipeline = native::GraphicsPipeline;
+    type GraphicsPipelineLibrary = ();
     type PipelineLayout = native::PipelineLayout;
     type PipelineCache = native::PipelineCache;
     type DescriptorSetLayout = native::DescriptorSetLayout;
@@ -1193,8 +1391,10 @@ impl hal::Backend for Backend {
 
     type Fence = native::Fence;
     type Semaphore = native::Semaphore;
+    type TimelineSemaphore = native::TimelineSemaphore;
     type Event = native::Event;
     type QueryPool = native::QueryPool;
+    type PrivateDataSlot = ();
 
     type Display = native::Display;
     type DisplayMode = native::DisplayMode;
@@ -13,6 +13,9 @@ use std::{collections::HashMap, sync::Arc};
 #[derive(Debug, Hash)]
 pub struct Semaphore(pub vk::Semaphore);
 
+#[derive(Debug, Hash)]
+pub struct TimelineSemaphore(pub vk::Semaphore);
+
 #[derive(Debug, Hash, PartialEq, Eq)]
 pub struct Fence(pub vk::Fence);
 
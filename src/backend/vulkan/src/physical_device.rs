@@ -1,16 +1,20 @@
 use ash::{
     extensions::{khr::DrawIndirectCount, khr::Swapchain, nv::MeshShader},
-    version::{DeviceV1_0, InstanceV1_0},
+    version::{DeviceV1_0, DeviceV1_1, InstanceV1_0},
     vk,
 };
 
 use hal::{
-    adapter,
+    adapter, buffer,
     device::{CreationError, OutOfMemory},
-    display, format, image,
+    display,
+    external_memory::{ExternalMemoryHandleTypes, ExternalMemoryProperties},
+    external_sync::{ExternalSyncHandleTypes, ExternalSyncProperties},
+    format, image, memory,
     pso::PatchSize,
-    queue, DescriptorLimits, DownlevelProperties, DynamicStates, Features, Limits,
-    PhysicalDeviceProperties,
+    queue, BufferDeviceAddressProperties, DescriptorLimits, DownlevelProperties, DynamicStates,
+    Features, Limits, LineRasterizationProperties, PhysicalDeviceProperties,
+    ProtectedMemoryProperties, RobustnessProperties,
 };
 
 use std::{ffi::CStr, fmt, mem, ptr, sync::Arc};
@@ -28,6 +32,12 @@ pub struct PhysicalDeviceFeatures {
     descriptor_indexing: Option<vk::PhysicalDeviceDescriptorIndexingFeaturesEXT>,
     mesh_shader: Option<vk::PhysicalDeviceMeshShaderFeaturesNV>,
     imageless_framebuffer: Option<vk::PhysicalDeviceImagelessFramebufferFeaturesKHR>,
+    timeline_semaphore: Option<vk::PhysicalDeviceTimelineSemaphoreFeaturesKHR>,
+    conditional_rendering: Option<vk::PhysicalDeviceConditionalRenderingFeaturesEXT>,
+    fragment_shading_rate: Option<vk::PhysicalDeviceFragmentShadingRateFeaturesKHR>,
+    robustness2: Option<vk::PhysicalDeviceRobustness2FeaturesEXT>,
+    line_rasterization: Option<vk::PhysicalDeviceLineRasterizationFeaturesEXT>,
+    protected_memory: Option<vk::PhysicalDeviceProtectedMemoryFeatures>,
 }
 
 // This is safe because the structs have `p_next: *mut c_void`, which we null out/never read.
@@ -54,18 +64,38 @@ impl PhysicalDeviceFeatures {
         if let Some(ref mut feature) = self.imageless_framebuffer {
             info = info.push_next(feature);
         }
+        if let Some(ref mut feature) = self.timeline_semaphore {
+            info = info.push_next(feature);
+        }
+        if let Some(ref mut feature) = self.conditional_rendering {
+            info = info.push_next(feature);
+        }
+        if let Some(ref mut feature) = self.fragment_shading_rate {
+            info = info.push_next(feature);
+        }
+        if let Some(ref mut feature) = self.robustness2 {
+            info = info.push_next(feature);
+        }
+        if let Some(ref mut feature) = self.line_rasterization {
+            info = info.push_next(feature);
+        }
+        if let Some(ref mut feature) = self.protected_memory {
+            info = info.push_next(feature);
+        }
 
         info
     }
 
     /// Create a `PhysicalDeviceFeatures` that will be used to create a logical device.
     ///
-    /// `requested_features` should be the same as what was used to generate `enabled_extensions`.
+    /// `requested_features` should be the same as what was used to generate `enabled_extensions`,
+    /// and `supported` holds the features of the physical device.
     fn from_extensions_and_requested_features(
         api_version: Version,
         enabled_extensions: &[&'static CStr],
         requested_features: Features,
         supports_vulkan12_imageless_framebuffer: bool,
+        supported: &PhysicalDeviceFeatures,
     ) -> PhysicalDeviceFeatures {
         // This must follow the "Valid Usage" requirements of [`VkDeviceCreateInfo`](https://www.khronos.org/registry/vulkan/specs/1.2-extensions/man/html/VkDeviceCreateInfo.html).
         let features = requested_features;
@@ -184,6 +214,13 @@ impl PhysicalDeviceFeatures {
                         )
                         .sampler_filter_minmax(features.contains(Features::SAMPLER_REDUCTION))
                         .imageless_framebuffer(supports_vulkan12_imageless_framebuffer)
+                        .timeline_semaphore(features.contains(Features::TIMELINE_SEMAPHORE))
+                        // There is no matching feature, so it's enabled whenever it's supported.
+                        .buffer_device_address(
+                            supported
+                                .vulkan_1_2
+                                .map_or(false, |features| features.buffer_device_address != 0),
+                        )
                         .build(),
                 )
             } else {
@@ -235,6 +272,83 @@ impl PhysicalDeviceFeatures {
             } else {
                 None
             },
+            timeline_semaphore: if enabled_extensions.contains(&vk::KhrTimelineSemaphoreFn::name())
+            {
+                Some(
+                    vk::PhysicalDeviceTimelineSemaphoreFeaturesKHR::builder()
+                        .timeline_semaphore(true)
+                        .build(),
+                )
+            } else {
+                None
+            },
+            conditional_rendering: if enabled_extensions
+                .contains(&vk::ExtConditionalRenderingFn::name())
+            {
+                Some(
+                    vk::PhysicalDeviceConditionalRenderingFeaturesEXT::builder()
+                        .conditional_rendering(true)
+                        .build(),
+                )
+            } else {
+                None
+            },
+            fragment_shading_rate: if enabled_extensions
+                .contains(&vk::KhrFragmentShadingRateFn::name())
+            {
+                Some(
+                    vk::PhysicalDeviceFragmentShadingRateFeaturesKHR::builder()
+                        .pipeline_fragment_shading_rate(
+                            features.contains(Features::PIPELINE_FRAGMENT_SHADING_RATE),
+                        )
+                        .primitive_fragment_shading_rate(
+                            features.contains(Features::PRIMITIVE_FRAGMENT_SHADING_RATE),
+                        )
+                        .build(),
+                )
+            } else {
+                None
+            },
+            robustness2: match supported.robustness2 {
+                Some(ref supported)
+                    if enabled_extensions.contains(&vk::ExtRobustness2Fn::name()) =>
+                {
+                    Some(
+                        vk::PhysicalDeviceRobustness2FeaturesEXT::builder()
+                            .robust_buffer_access2(
+                                features.contains(Features::ROBUST_BUFFER_ACCESS)
+                                    && supported.robust_buffer_access2 != 0,
+                            )
+                            .null_descriptor(supported.null_descriptor != 0)
+                            .build(),
+                    )
+                }
+                _ => None,
+            },
+            line_rasterization: match supported.line_rasterization {
+                // There are no matching features, so everything supported is enabled.
+                Some(supported)
+                    if enabled_extensions.contains(&vk::ExtLineRasterizationFn::name()) =>
+                {
+                    Some(vk::PhysicalDeviceLineRasterizationFeaturesEXT {
+                        p_next: ptr::null_mut(),
+                        ..supported
+                    })
+                }
+                _ => None,
+            },
+            // Only enabled when supported, since protected queues are requested separately.
+            protected_memory: supported.protected_memory.and_then(|supported| {
+                if supported.protected_memory == vk::TRUE {
+                    Some(
+                        vk::PhysicalDeviceProtectedMemoryFeatures::builder()
+                            .protected_memory(true)
+                            .build(),
+                    )
+                } else {
+                    None
+                }
+            }),
         }
     }
 
@@ -464,6 +578,9 @@ impl PhysicalDeviceFeatures {
             if vulkan_1_2.draw_indirect_count != 0 {
                 bits |= Features::DRAW_INDIRECT_COUNT
             }
+            if vulkan_1_2.timeline_semaphore != 0 {
+                bits |= Features::TIMELINE_SEMAPHORE
+            }
         }
 
         if let Some(ref descriptor_indexing) = self.descriptor_indexing {
@@ -484,6 +601,29 @@ impl PhysicalDeviceFeatures {
             }
         }
 
+        if let Some(ref timeline_semaphore) = self.timeline_semaphore {
+            if timeline_semaphore.timeline_semaphore != 0 {
+                bits |= Features::TIMELINE_SEMAPHORE;
+            }
+        }
+
+        if let Some(ref conditional_rendering) = self.conditional_rendering {
+            if conditional_rendering.conditional_rendering != 0 {
+                bits |= Features::CONDITIONAL_RENDERING;
+            }
+        }
+
+        // The attachment rate isn't exposed, since render passes are created without
+        // `VK_KHR_create_renderpass2`, which is needed to reference the attachment.
+        if let Some(ref fragment_shading_rate) = self.fragment_shading_rate {
+            if fragment_shading_rate.pipeline_fragment_shading_rate != 0 {
+                bits |= Features::PIPELINE_FRAGMENT_SHADING_RATE;
+            }
+            if fragment_shading_rate.primitive_fragment_shading_rate != 0 {
+                bits |= Features::PRIMITIVE_FRAGMENT_SHADING_RATE;
+            }
+        }
+
         if let Some(ref mesh_shader) = self.mesh_shader {
             if mesh_shader.task_shader != 0 {
                 bits |= Features::TASK_SHADER;
@@ -514,6 +654,52 @@ impl PhysicalDeviceInfo {
             .any(|ep| unsafe { CStr::from_ptr(ep.extension_name.as_ptr()) } == extension)
     }
 
+    /// Get the external memory handle types whose extensions are supported.
+    fn external_memory_handle_types(&self) -> ExternalMemoryHandleTypes {
+        let mut types = ExternalMemoryHandleTypes::empty();
+        types.set(
+            ExternalMemoryHandleTypes::OPAQUE_FD,
+            self.supports_extension(vk::KhrExternalMemoryFdFn::name()),
+        );
+        types.set(
+            ExternalMemoryHandleTypes::OPAQUE_WIN32,
+            self.supports_extension(vk::KhrExternalMemoryWin32Fn::name()),
+        );
+        types.set(
+            ExternalMemoryHandleTypes::HOST_POINTER,
+            self.supports_extension(vk::ExtExternalMemoryHostFn::name()),
+        );
+        types
+    }
+
+    /// Get the external fence handle types whose extensions are supported.
+    fn external_fence_handle_types(&self) -> ExternalSyncHandleTypes {
+        let mut types = ExternalSyncHandleTypes::empty();
+        types.set(
+            ExternalSyncHandleTypes::SYNC_FD,
+            self.supports_extension(vk::KhrExternalFenceFdFn::name()),
+        );
+        types.set(
+            ExternalSyncHandleTypes::OPAQUE_WIN32,
+            self.supports_extension(vk::KhrExternalFenceWin32Fn::name()),
+        );
+        types
+    }
+
+    /// Get the external semaphore handle types whose extensions are supported.
+    fn external_semaphore_handle_types(&self) -> ExternalSyncHandleTypes {
+        let mut types = ExternalSyncHandleTypes::empty();
+        types.set(
+            ExternalSyncHandleTypes::SYNC_FD,
+            self.supports_extension(vk::KhrExternalSemaphoreFdFn::name()),
+        );
+        types.set(
+            ExternalSyncHandleTypes::OPAQUE_WIN32,
+            self.supports_extension(vk::KhrExternalSemaphoreWin32Fn::name()),
+        );
+        types
+    }
+
     /// Map `requested_features` to the list of Vulkan extension strings required to create the logical device.
     fn get_required_extensions(&self, requested_features: Features) -> Vec<&'static CStr> {
         let mut requested_extensions = Vec::new();
@@ -577,6 +763,63 @@ impl PhysicalDeviceInfo {
             requested_extensions.push(DrawIndirectCount::name());
         }
 
+        if self.api_version() < Version::V1_2
+            && requested_features.contains(Features::TIMELINE_SEMAPHORE)
+        {
+            requested_extensions.push(vk::KhrTimelineSemaphoreFn::name());
+        }
+
+        if requested_features.contains(Features::CONDITIONAL_RENDERING) {
+            requested_extensions.push(vk::ExtConditionalRenderingFn::name());
+        }
+
+        if requested_features.contains(Features::EXTERNAL_MEMORY) {
+            if self.api_version() < Version::V1_1 {
+                requested_extensions.push(vk::KhrExternalMemoryFn::name());
+            }
+            let types = self.external_memory_handle_types();
+            if types.contains(ExternalMemoryHandleTypes::OPAQUE_FD) {
+                requested_extensions.push(vk::KhrExternalMemoryFdFn::name());
+            }
+            if types.contains(ExternalMemoryHandleTypes::OPAQUE_WIN32) {
+                requested_extensions.push(vk::KhrExternalMemoryWin32Fn::name());
+            }
+            if types.contains(ExternalMemoryHandleTypes::HOST_POINTER) {
+                requested_extensions.push(vk::ExtExternalMemoryHostFn::name());
+            }
+        }
+
+        if requested_features.contains(Features::EXTERNAL_SYNC) {
+            if self.api_version() < Version::V1_1 {
+                requested_extensions.push(vk::KhrExternalFenceFn::name());
+                requested_extensions.push(vk::KhrExternalSemaphoreFn::name());
+            }
+            let fence_types = self.external_fence_handle_types();
+            if fence_types.contains(ExternalSyncHandleTypes::SYNC_FD) {
+                requested_extensions.push(vk::KhrExternalFenceFdFn::name());
+            }
+            if fence_types.contains(ExternalSyncHandleTypes::OPAQUE_WIN32) {
+                requested_extensions.push(vk::KhrExternalFenceWin32Fn::name());
+            }
+            let semaphore_types = self.external_semaphore_handle_types();
+            if semaphore_types.contains(ExternalSyncHandleTypes::SYNC_FD) {
+                requested_extensions.push(vk::KhrExternalSemaphoreFdFn::name());
+            }
+            if semaphore_types.contains(ExternalSyncHandleTypes::OPAQUE_WIN32) {
+                requested_extensions.push(vk::KhrExternalSemaphoreWin32Fn::name());
+            }
+        }
+
+        if requested_features.intersects(Features::FRAGMENT_SHADING_RATE_MASK) {
+            requested_extensions.push(vk::KhrFragmentShadingRateFn::name());
+            if self.api_version() < Version::V1_2 {
+                requested_extensions.push(vk::KhrCreateRenderpass2Fn::name()); // Required for `KhrFragmentShadingRateFn`
+                if self.api_version() < Version::V1_1 {
+                    requested_extensions.push(vk::KhrMultiviewFn::name()); // Required for `KhrCreateRenderpass2Fn`
+                }
+            }
+        }
+
         if requested_features.contains(Features::CONSERVATIVE_RASTERIZATION) {
             requested_extensions.push(vk::ExtConservativeRasterizationFn::name());
             requested_extensions.push(vk::KhrGetDisplayProperties2Fn::name()); // TODO NOT NEEDED, RIGHT?
@@ -586,6 +829,14 @@ impl PhysicalDeviceInfo {
             requested_extensions.push(vk::ExtDisplayControlFn::name());
         }
 
+        if self.supports_extension(vk::ExtRobustness2Fn::name()) {
+            requested_extensions.push(vk::ExtRobustness2Fn::name());
+        }
+
+        if self.supports_extension(vk::ExtLineRasterizationFn::name()) {
+            requested_extensions.push(vk::ExtLineRasterizationFn::name());
+        }
+
         requested_extensions
     }
 
@@ -644,6 +895,56 @@ impl PhysicalDeviceInfo {
                 mut_ref.p_next = mem::replace(&mut features2.p_next, mut_ref as *mut _ as *mut _);
             }
 
+            if device_properties.api_version() < Version::V1_2
+                && device_properties.supports_extension(vk::KhrTimelineSemaphoreFn::name())
+            {
+                features.timeline_semaphore =
+                    Some(vk::PhysicalDeviceTimelineSemaphoreFeaturesKHR::builder().build());
+
+                let mut_ref = features.timeline_semaphore.as_mut().unwrap();
+                mut_ref.p_next = mem::replace(&mut features2.p_next, mut_ref as *mut _ as *mut _);
+            }
+
+            if device_properties.supports_extension(vk::ExtConditionalRenderingFn::name()) {
+                features.conditional_rendering =
+                    Some(vk::PhysicalDeviceConditionalRenderingFeaturesEXT::builder().build());
+
+                let mut_ref = features.conditional_rendering.as_mut().unwrap();
+                mut_ref.p_next = mem::replace(&mut features2.p_next, mut_ref as *mut _ as *mut _);
+            }
+
+            if device_properties.supports_extension(vk::KhrFragmentShadingRateFn::name()) {
+                features.fragment_shading_rate =
+                    Some(vk::PhysicalDeviceFragmentShadingRateFeaturesKHR::builder().build());
+
+                let mut_ref = features.fragment_shading_rate.as_mut().unwrap();
+                mut_ref.p_next = mem::replace(&mut features2.p_next, mut_ref as *mut _ as *mut _);
+            }
+
+            if device_properties.supports_extension(vk::ExtRobustness2Fn::name()) {
+                features.robustness2 =
+                    Some(vk::PhysicalDeviceRobustness2FeaturesEXT::builder().build());
+
+                let mut_ref = features.robustness2.as_mut().unwrap();
+                mut_ref.p_next = mem::replace(&mut features2.p_next, mut_ref as *mut _ as *mut _);
+            }
+
+            if device_properties.supports_extension(vk::ExtLineRasterizationFn::name()) {
+                features.line_rasterization =
+                    Some(vk::PhysicalDeviceLineRasterizationFeaturesEXT::builder().build());
+
+                let mut_ref = features.line_rasterization.as_mut().unwrap();
+                mut_ref.p_next = mem::replace(&mut features2.p_next, mut_ref as *mut _ as *mut _);
+            }
+
+            if device_properties.api_version() >= Version::V1_1 {
+                features.protected_memory =
+                    Some(vk::PhysicalDeviceProtectedMemoryFeatures::builder().build());
+
+                let mut_ref = features.protected_memory.as_mut().unwrap();
+                mut_ref.p_next = mem::replace(&mut features2.p_next, mut_ref as *mut _ as *mut _);
+            }
+
             unsafe {
                 get_device_properties
                     .get_physical_device_features2_khr(device, &mut features2 as *mut _);
@@ -667,6 +968,12 @@ impl PhysicalDeviceInfo {
             null_p_next(&mut features.descriptor_indexing);
             null_p_next(&mut features.mesh_shader);
             null_p_next(&mut features.imageless_framebuffer);
+            null_p_next(&mut features.timeline_semaphore);
+            null_p_next(&mut features.conditional_rendering);
+            null_p_next(&mut features.fragment_shading_rate);
+            null_p_next(&mut features.robustness2);
+            null_p_next(&mut features.line_rasterization);
+            null_p_next(&mut features.protected_memory);
         }
 
         (device_properties, features)
@@ -697,6 +1004,7 @@ impl PhysicalDevice {
             raw_device,
             true,
             families,
+            &[],
             requested_features,
             enabled_extensions,
         ))
@@ -707,6 +1015,7 @@ impl PhysicalDevice {
         device_raw: ash::Device,
         handle_is_external: bool,
         families: &[(&QueueFamily, &[queue::QueuePriority])],
+        protected_families: &[(&QueueFamily, &[queue::QueuePriority])],
         requested_features: Features,
         enabled_extensions: Vec<&CStr>,
     ) -> adapter::Gpu<Backend> {
@@ -754,6 +1063,23 @@ impl PhysicalDevice {
             None
         };
 
+        let timeline_semaphore_fn =
+            if enabled_extensions.contains(&vk::KhrTimelineSemaphoreFn::name()) {
+                Some(ExtensionFn::Extension(vk::KhrTimelineSemaphoreFn::load(
+                    |name| {
+                        std::mem::transmute(
+                            self.instance
+                                .inner
+                                .get_device_proc_addr(device_raw.handle(), name.as_ptr()),
+                        )
+                    },
+                )))
+            } else if self.device_info.api_version() >= Version::V1_2 {
+                Some(ExtensionFn::Promoted)
+            } else {
+                None
+            };
+
         let display_control = if enabled_extensions.contains(&vk::ExtDisplayControlFn::name()) {
             Some(vk::ExtDisplayControlFn::load(
                 |name| {
@@ -768,6 +1094,122 @@ impl PhysicalDevice {
             None
         };
 
+        let conditional_rendering =
+            if enabled_extensions.contains(&vk::ExtConditionalRenderingFn::name()) {
+                Some(vk::ExtConditionalRenderingFn::load(|name| {
+                    std::mem::transmute(
+                        self.instance
+                            .inner
+                            .get_device_proc_addr(device_raw.handle(), name.as_ptr()),
+                    )
+                }))
+            } else {
+                None
+            };
+
+        let fragment_shading_rate =
+            if enabled_extensions.contains(&vk::KhrFragmentShadingRateFn::name()) {
+                Some(vk::KhrFragmentShadingRateFn::load(|name| {
+                    std::mem::transmute(
+                        self.instance
+                            .inner
+                            .get_device_proc_addr(device_raw.handle(), name.as_ptr()),
+                    )
+                }))
+            } else {
+                None
+            };
+
+        let external_memory_fd = if enabled_extensions.contains(&vk::KhrExternalMemoryFdFn::name())
+        {
+            Some(vk::KhrExternalMemoryFdFn::load(|name| {
+                std::mem::transmute(
+                    self.instance
+                        .inner
+                        .get_device_proc_addr(device_raw.handle(), name.as_ptr()),
+                )
+            }))
+        } else {
+            None
+        };
+
+        let external_memory_win32 =
+            if enabled_extensions.contains(&vk::KhrExternalMemoryWin32Fn::name()) {
+                Some(vk::KhrExternalMemoryWin32Fn::load(|name| {
+                    std::mem::transmute(
+                        self.instance
+                            .inner
+                            .get_device_proc_addr(device_raw.handle(), name.as_ptr()),
+                    )
+                }))
+            } else {
+                None
+            };
+
+        let external_memory_host =
+            if enabled_extensions.contains(&vk::ExtExternalMemoryHostFn::name()) {
+                Some(vk::ExtExternalMemoryHostFn::load(|name| {
+                    std::mem::transmute(
+                        self.instance
+                            .inner
+                            .get_device_proc_addr(device_raw.handle(), name.as_ptr()),
+                    )
+                }))
+            } else {
+                None
+            };
+
+        let external_fence_fd = if enabled_extensions.contains(&vk::KhrExternalFenceFdFn::name()) {
+            Some(vk::KhrExternalFenceFdFn::load(|name| {
+                std::mem::transmute(
+                    self.instance
+                        .inner
+                        .get_device_proc_addr(device_raw.handle(), name.as_ptr()),
+                )
+            }))
+        } else {
+            None
+        };
+
+        let external_fence_win32 =
+            if enabled_extensions.contains(&vk::KhrExternalFenceWin32Fn::name()) {
+                Some(vk::KhrExternalFenceWin32Fn::load(|name| {
+                    std::mem::transmute(
+                        self.instance
+                            .inner
+                            .get_device_proc_addr(device_raw.handle(), name.as_ptr()),
+                    )
+                }))
+            } else {
+                None
+            };
+
+        let external_semaphore_fd =
+            if enabled_extensions.contains(&vk::KhrExternalSemaphoreFdFn::name()) {
+                Some(vk::KhrExternalSemaphoreFdFn::load(|name| {
+                    std::mem::transmute(
+                        self.instance
+                            .inner
+                            .get_device_proc_addr(device_raw.handle(), name.as_ptr()),
+                    )
+                }))
+            } else {
+                None
+            };
+
+        let external_semaphore_win32 =
+            if enabled_extensions.contains(&vk::KhrExternalSemaphoreWin32Fn::name()) {
+                Some(vk::KhrExternalSemaphoreWin32Fn::load(|name| {
+                    std::mem::transmute(
+                        self.instance
+                            .inner
+                            .get_device_proc_addr(device_raw.handle(), name.as_ptr()),
+                    )
+                }))
+            } else {
+                None
+            };
+
         #[cfg(feature = "naga")]
         let naga_options = {
             use naga::back::spv;
@@ -805,7 +1247,17 @@ impl PhysicalDevice {
                 extension_fns: DeviceExtensionFunctions {
                     mesh_shaders: mesh_fn,
                     draw_indirect_count: indirect_count_fn,
+                    timeline_semaphore: timeline_semaphore_fn,
                     display_control,
+                    conditional_rendering,
+                    fragment_shading_rate,
+                    external_memory_fd,
+                    external_memory_win32,
+                    external_memory_host,
+                    external_fence_fd,
+                    external_fence_win32,
+                    external_semaphore_fd,
+                    external_semaphore_win32,
                 },
                 flip_y_requires_shift: self.device_info.api_version() >= Version::V1_1
                     || self
@@ -820,6 +1272,10 @@ impl PhysicalDevice {
                         .device_info
                         .supports_extension(vk::KhrMaintenance2Fn::name()),
                 timestamp_period: self.device_info.properties.limits.timestamp_period,
+                buffer_device_address: self
+                    .device_features
+                    .vulkan_1_2
+                    .map_or(false, |features| features.buffer_device_address == vk::TRUE),
             }),
             vendor_id: self.device_info.properties.vendor_id,
             valid_ash_memory_types,
@@ -844,6 +1300,23 @@ impl PhysicalDevice {
                 }
                 family_raw
             })
+            .chain(protected_families.iter().map(|&(family, ref priorities)| {
+                let mut family_raw =
+                    queue::QueueGroup::new_protected(queue::QueueFamilyId(family.index as usize));
+                for id in 0..priorities.len() {
+                    let info = vk::DeviceQueueInfo2::builder()
+                        .flags(vk::DeviceQueueCreateFlags::PROTECTED)
+                        .queue_family_index(family.index)
+                        .queue_index(id as _);
+                    let queue_raw = device_arc.raw.get_device_queue2(&info);
+                    family_raw.add_queue(Queue {
+                        raw: Arc::new(queue_raw),
+                        device: device_arc.clone(),
+                        swapchain_fn: swapchain_fn.clone(),
+                    });
+                }
+                family_raw
+            }))
             .collect();
 
         adapter::Gpu {
@@ -880,6 +1353,38 @@ impl PhysicalDevice {
     }
 }
 
+/// External memory handle types that can be supported through extensions.
+const EXTERNAL_MEMORY_HANDLE_TYPES: &[ExternalMemoryHandleTypes] = &[
+    ExternalMemoryHandleTypes::OPAQUE_FD,
+    ExternalMemoryHandleTypes::OPAQUE_WIN32,
+    ExternalMemoryHandleTypes::HOST_POINTER,
+];
+
+fn add_external_memory_features(
+    properties: &mut ExternalMemoryProperties,
+    ty: ExternalMemoryHandleTypes,
+    features: vk::ExternalMemoryFeatureFlags,
+) {
+    // Dedicated allocations aren't exposed, so the types requiring them can't be used.
+    if features.contains(vk::ExternalMemoryFeatureFlags::EXTERNAL_MEMORY_FEATURE_DEDICATED_ONLY) {
+        return;
+    }
+    properties.exportable.set(
+        ty,
+        features.contains(vk::ExternalMemoryFeatureFlags::EXTERNAL_MEMORY_FEATURE_EXPORTABLE),
+    );
+    properties.importable.set(
+        ty,
+        features.contains(vk::ExternalMemoryFeatureFlags::EXTERNAL_MEMORY_FEATURE_IMPORTABLE),
+    );
+}
+
+/// External synchronization handle types that can be supported through extensions.
+const EXTERNAL_SYNC_HANDLE_TYPES: &[ExternalSyncHandleTypes] = &[
+    ExternalSyncHandleTypes::SYNC_FD,
+    ExternalSyncHandleTypes::OPAQUE_WIN32,
+];
+
 pub(crate) fn load_adapter(
     instance: &Arc<RawInstance>,
     device: vk::PhysicalDevice,
@@ -919,17 +1424,45 @@ pub(crate) fn load_adapter(
             bits.set(Features::DUAL_SRC_BLENDING, false);
         }
 
+        if instance.external_memory_capabilities.is_some()
+            && (device_info.api_version() >= Version::V1_1
+                || device_info.supports_extension(vk::KhrExternalMemoryFn::name()))
+            && !device_info.external_memory_handle_types().is_empty()
+        {
+            bits |= Features::EXTERNAL_MEMORY;
+        }
+
+        if instance.external_fence_capabilities.is_some()
+            && instance.external_semaphore_capabilities.is_some()
+            && (device_info.api_version() >= Version::V1_1
+                || (device_info.supports_extension(vk::KhrExternalFenceFn::name())
+                    && device_info.supports_extension(vk::KhrExternalSemaphoreFn::name())))
+            && !(device_info.external_fence_handle_types()
+                | device_info.external_semaphore_handle_types())
+            .is_empty()
+        {
+            bits |= Features::EXTERNAL_SYNC;
+        }
+
         bits
     };
 
+    let mut known_memory_flags = vk::MemoryPropertyFlags::DEVICE_LOCAL
+        | vk::MemoryPropertyFlags::HOST_VISIBLE
+        | vk::MemoryPropertyFlags::HOST_COHERENT
+        | vk::MemoryPropertyFlags::HOST_CACHED
+        | vk::MemoryPropertyFlags::LAZILY_ALLOCATED;
+    if device_features
+        .protected_memory
+        .map_or(false, |features| features.protected_memory == vk::TRUE)
+    {
+        known_memory_flags |= vk::MemoryPropertyFlags::PROTECTED;
+    }
+
     let physical_device = PhysicalDevice {
         instance: instance.clone(),
         handle: device,
-        known_memory_flags: vk::MemoryPropertyFlags::DEVICE_LOCAL
-            | vk::MemoryPropertyFlags::HOST_VISIBLE
-            | vk::MemoryPropertyFlags::HOST_COHERENT
-            | vk::MemoryPropertyFlags::HOST_CACHED
-            | vk::MemoryPropertyFlags::LAZILY_ALLOCATED,
+        known_memory_flags,
         device_info,
         device_features,
         available_features,
@@ -968,6 +1501,23 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
         families: &[(&QueueFamily, &[queue::QueuePriority])],
         requested_features: Features,
     ) -> Result<adapter::Gpu<Backend>, CreationError> {
+        self.open_protected(families, &[], requested_features)
+    }
+
+    unsafe fn open_protected(
+        &self,
+        families: &[(&QueueFamily, &[queue::QueuePriority])],
+        protected_families: &[(&QueueFamily, &[queue::QueuePriority])],
+        requested_features: Features,
+    ) -> Result<adapter::Gpu<Backend>, CreationError> {
+        let supports_protected_memory = self
+            .device_features
+            .protected_memory
+            .map_or(false, |features| features.protected_memory == vk::TRUE);
+        if !protected_families.is_empty() && !supports_protected_memory {
+            return Err(CreationError::MissingFeature);
+        }
+
         let family_infos = families
             .iter()
             .map(|&(family, priorities)| {
@@ -977,6 +1527,13 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
                     .queue_priorities(priorities)
                     .build()
             })
+            .chain(protected_families.iter().map(|&(family, priorities)| {
+                vk::DeviceQueueCreateInfo::builder()
+                    .flags(vk::DeviceQueueCreateFlags::PROTECTED)
+                    .queue_family_index(family.index)
+                    .queue_priorities(priorities)
+                    .build()
+            }))
             .collect::<Vec<_>>();
 
         let enabled_extensions = self.enabled_extensions(requested_features)?;
@@ -1002,6 +1559,7 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
                     &enabled_extensions,
                     requested_features,
                     supports_vulkan12_imageless_framebuffer,
+                    &self.device_features,
                 );
             let info = vk::DeviceCreateInfo::builder()
                 .queue_create_infos(&family_infos)
@@ -1036,6 +1594,7 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
             device_raw,
             false,
             families,
+            protected_families,
             requested_features,
             enabled_extensions,
         ))
@@ -1117,6 +1676,191 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
         }
     }
 
+    fn external_buffer_properties(
+        &self,
+        usage: buffer::Usage,
+        sparse: memory::SparseFlags,
+    ) -> ExternalMemoryProperties {
+        let mut properties = ExternalMemoryProperties::default();
+        let capabilities = match self.instance.external_memory_capabilities {
+            Some(ref capabilities)
+                if self.available_features.contains(Features::EXTERNAL_MEMORY) =>
+            {
+                capabilities
+            }
+            _ => return properties,
+        };
+
+        for &ty in EXTERNAL_MEMORY_HANDLE_TYPES {
+            if !self.device_info.external_memory_handle_types().contains(ty) {
+                continue;
+            }
+            let info = vk::PhysicalDeviceExternalBufferInfo::builder()
+                .flags(conv::map_buffer_create_flags(sparse))
+                .usage(conv::map_buffer_usage(usage))
+                .handle_type(conv::map_external_memory_handle_types(ty));
+            let mut external_properties = vk::ExternalBufferProperties::default();
+            unsafe {
+                capabilities.get_physical_device_external_buffer_properties_khr(
+                    self.handle,
+                    &*info,
+                    &mut external_properties,
+                );
+            }
+            add_external_memory_features(
+                &mut properties,
+                ty,
+                external_properties
+                    .external_memory_properties
+                    .external_memory_features,
+            );
+        }
+
+        properties
+    }
+
+    fn external_image_properties(
+        &self,
+        format: format::Format,
+        dimensions: u8,
+        tiling: image::Tiling,
+        usage: image::Usage,
+        view_caps: image::ViewCapabilities,
+    ) -> ExternalMemoryProperties {
+        let mut properties = ExternalMemoryProperties::default();
+        let get_device_properties = match self.instance.get_physical_device_properties {
+            Some(ref get_device_properties)
+                if self.available_features.contains(Features::EXTERNAL_MEMORY) =>
+            {
+                get_device_properties
+            }
+            _ => return properties,
+        };
+
+        for &ty in EXTERNAL_MEMORY_HANDLE_TYPES {
+            if !self.device_info.external_memory_handle_types().contains(ty) {
+                continue;
+            }
+            let mut external_info = vk::PhysicalDeviceExternalImageFormatInfo::builder()
+                .handle_type(conv::map_external_memory_handle_types(ty));
+            let info = vk::PhysicalDeviceImageFormatInfo2::builder()
+                .format(conv::map_format(format))
+                .ty(match dimensions {
+                    1 => vk::ImageType::TYPE_1D,
+                    2 => vk::ImageType::TYPE_2D,
+                    3 => vk::ImageType::TYPE_3D,
+                    _ => panic!("Unexpected image dimensionality: {}", dimensions),
+                })
+                .tiling(conv::map_tiling(tiling))
+                .usage(conv::map_image_usage(usage))
+                .flags(conv::map_view_capabilities(view_caps))
+                .push_next(&mut external_info);
+            let mut external_properties = vk::ExternalImageFormatProperties::default();
+            let mut format_properties =
+                vk::ImageFormatProperties2::builder().push_next(&mut external_properties);
+            let result = unsafe {
+                get_device_properties.get_physical_device_image_format_properties2_khr(
+                    self.handle,
+                    &*info,
+                    &mut *format_properties,
+                )
+            };
+            if result == vk::Result::SUCCESS {
+                add_external_memory_features(
+                    &mut properties,
+                    ty,
+                    external_properties
+                        .external_memory_properties
+                        .external_memory_features,
+                );
+            }
+        }
+
+        properties
+    }
+
+    fn external_fence_properties(&self) -> ExternalSyncProperties {
+        let mut properties = ExternalSyncProperties::default();
+        let capabilities = match self.instance.external_fence_capabilities {
+            Some(ref capabilities) if self.available_features.contains(Features::EXTERNAL_SYNC) => {
+                capabilities
+            }
+            _ => return properties,
+        };
+
+        for &ty in EXTERNAL_SYNC_HANDLE_TYPES {
+            if !self.device_info.external_fence_handle_types().contains(ty) {
+                continue;
+            }
+            let info = vk::PhysicalDeviceExternalFenceInfo::builder()
+                .handle_type(conv::map_external_fence_handle_types(ty));
+            let mut external_properties = vk::ExternalFenceProperties::default();
+            unsafe {
+                capabilities.get_physical_device_external_fence_properties_khr(
+                    self.handle,
+                    &*info,
+                    &mut external_properties,
+                );
+            }
+            let features = external_properties.external_fence_features;
+            properties.exportable.set(
+                ty,
+                features.contains(vk::ExternalFenceFeatureFlags::EXTERNAL_FENCE_FEATURE_EXPORTABLE),
+            );
+            properties.importable.set(
+                ty,
+                features.contains(vk::ExternalFenceFeatureFlags::EXTERNAL_FENCE_FEATURE_IMPORTABLE),
+            );
+        }
+
+        properties
+    }
+
+    fn external_semaphore_properties(&self) -> ExternalSyncProperties {
+        let mut properties = ExternalSyncProperties::default();
+        let capabilities = match self.instance.external_semaphore_capabilities {
+            Some(ref capabilities) if self.available_features.contains(Features::EXTERNAL_SYNC) => {
+                capabilities
+            }
+            _ => return properties,
+        };
+
+        for &ty in EXTERNAL_SYNC_HANDLE_TYPES {
+            if !self
+                .device_info
+                .external_semaphore_handle_types()
+                .contains(ty)
+            {
+                continue;
+            }
+            let info = vk::PhysicalDeviceExternalSemaphoreInfo::builder()
+                .handle_type(conv::map_external_semaphore_handle_types(ty));
+            let mut external_properties = vk::ExternalSemaphoreProperties::default();
+            unsafe {
+                capabilities.get_physical_device_external_semaphore_properties_khr(
+                    self.handle,
+                    &*info,
+                    &mut external_properties,
+                );
+            }
+            let features = external_properties.external_semaphore_features;
+            properties.exportable.set(
+                ty,
+                features.contains(
+                    vk::ExternalSemaphoreFeatureFlags::EXTERNAL_SEMAPHORE_FEATURE_EXPORTABLE,
+                ),
+            );
+            properties.importable.set(
+                ty,
+                features.contains(
+                    vk::ExternalSemaphoreFeatureFlags::EXTERNAL_SEMAPHORE_FEATURE_IMPORTABLE,
+                ),
+            );
+        }
+
+        properties
+    }
+
     fn memory_properties(&self) -> adapter::MemoryProperties {
         let mem_properties = unsafe {
             self.instance
@@ -1195,6 +1939,7 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
                 max_vertex_input_attribute_offset: limits.max_vertex_input_attribute_offset as _,
                 max_vertex_input_binding_stride: limits.max_vertex_input_binding_stride as _,
                 max_vertex_output_components: limits.max_vertex_output_components as _,
+                max_clip_distances: limits.max_clip_distances,
                 optimal_buffer_copy_offset_alignment: limits.optimal_buffer_copy_offset_alignment
                     as _,
                 optimal_buffer_copy_pitch_alignment: limits.optimal_buffer_copy_row_pitch_alignment
@@ -1273,6 +2018,7 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
         let mut descriptor_indexing_capabilities = hal::DescriptorIndexingProperties::default();
         let mut mesh_shader_capabilities = hal::MeshShaderProperties::default();
         let mut sampler_reduction_capabilities = hal::SamplerReductionProperties::default();
+        let mut subgroup_capabilities = hal::SubgroupProperties::default();
 
         if let Some(get_physical_device_properties) =
             self.instance.get_physical_device_properties.as_ref()
@@ -1282,6 +2028,7 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
             let mut mesh_shader_properties = vk::PhysicalDeviceMeshShaderPropertiesNV::builder();
             let mut sampler_reduction_properties =
                 vk::PhysicalDeviceSamplerFilterMinmaxProperties::builder();
+            let mut subgroup_properties = vk::PhysicalDeviceSubgroupProperties::builder();
 
             unsafe {
                 get_physical_device_properties.get_physical_device_properties2_khr(
@@ -1290,6 +2037,7 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
                         .push_next(&mut descriptor_indexing_properties)
                         .push_next(&mut mesh_shader_properties)
                         .push_next(&mut sampler_reduction_properties)
+                        .push_next(&mut subgroup_properties)
                         .build() as *mut _,
                 );
             }
@@ -1348,16 +2096,78 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
                     .filter_minmax_image_component_mapping
                     == vk::TRUE,
             };
+
+            // The stage and operation bits match the Vulkan ones.
+            subgroup_capabilities = hal::SubgroupProperties {
+                size: subgroup_properties.subgroup_size,
+                supported_stages: hal::pso::ShaderStageFlags::from_bits_truncate(
+                    subgroup_properties.supported_stages.as_raw(),
+                ),
+                supported_operations: hal::SubgroupOperations::from_bits_truncate(
+                    subgroup_properties.supported_operations.as_raw(),
+                ),
+                quad_operations_in_all_stages: subgroup_properties.quad_operations_in_all_stages
+                    == vk::TRUE,
+            };
         }
 
         PhysicalDeviceProperties {
             limits,
             descriptor_indexing: descriptor_indexing_capabilities,
             mesh_shader: mesh_shader_capabilities,
+            ray_tracing: Default::default(),
             sampler_reduction: sampler_reduction_capabilities,
             performance_caveats: Default::default(),
             dynamic_pipeline_states: DynamicStates::all(),
             downlevel: DownlevelProperties::all_enabled(),
+            subgroup: subgroup_capabilities,
+            robustness: match self.device_features.robustness2 {
+                Some(ref features) => RobustnessProperties {
+                    robust_buffer_access_2: features.robust_buffer_access2 != 0,
+                    null_descriptor: features.null_descriptor != 0,
+                },
+                None => RobustnessProperties::default(),
+            },
+            sample_locations: Default::default(),
+            line_rasterization: {
+                let strict_lines = self.device_info.properties.limits.strict_lines != 0;
+                match self.device_features.line_rasterization {
+                    Some(ref features) => LineRasterizationProperties {
+                        rectangular_lines: features.rectangular_lines != 0,
+                        bresenham_lines: features.bresenham_lines != 0,
+                        smooth_lines: features.smooth_lines != 0,
+                        stippled_rectangular_lines: features.stippled_rectangular_lines != 0,
+                        stippled_bresenham_lines: features.stippled_bresenham_lines != 0,
+                        stippled_smooth_lines: features.stippled_smooth_lines != 0,
+                        stippled_default_lines: strict_lines
+                            && features.stippled_rectangular_lines != 0,
+                        strict_lines,
+                    },
+                    None => LineRasterizationProperties {
+                        strict_lines,
+                        ..LineRasterizationProperties::default()
+                    },
+                }
+            },
+            // `VK_EXT_depth_bias_control` isn't exposed by the `ash` version in use.
+            depth_bias_control: Default::default(),
+            // `VK_KHR_buffer_device_address` is only used through its promoted version.
+            buffer_device_address: {
+                let supported = self
+                    .device_features
+                    .vulkan_1_2
+                    .map_or(false, |features| features.buffer_device_address != 0);
+                BufferDeviceAddressProperties {
+                    buffer_device_address: supported,
+                    shader_pointers: supported,
+                }
+            },
+            protected_memory: ProtectedMemoryProperties {
+                protected_memory: self
+                    .device_features
+                    .protected_memory
+                    .map_or(false, |features| features.protected_memory == vk::TRUE),
+            },
         }
     }
 
@@ -319,11 +319,15 @@ impl w::Surface<Backend> for Surface {
                     u | conv::map_vk_present_mode(m)
                 }),
             composite_alpha_modes: conv::map_vk_composite_alpha(caps.supported_composite_alpha),
+            current_transform: conv::map_vk_surface_transform(caps.current_transform),
+            transforms: conv::map_vk_surface_transform(caps.supported_transforms),
             image_count: caps.min_image_count..=max_images,
             current_extent,
             extents: min_extent..=max_extent,
             max_image_layers: caps.max_image_array_layers as _,
             usage: conv::map_vk_image_usage(caps.supported_usage_flags),
+            swapchain_maintenance: false,
+            present_wait: false,
         }
     }
 
@@ -1,18 +1,18 @@
 use std::{borrow::Borrow, ops::Range};
 
 use hal::{
-    buffer,
+    acceleration_structure, buffer,
     command::{
         AttachmentClear, BufferCopy, BufferImageCopy, ClearValue, CommandBufferFlags,
-        CommandBufferInheritanceInfo, DescriptorSetOffset, ImageBlit, ImageCopy, ImageResolve,
-        Level, SubpassContents,
+        CommandBufferInheritanceInfo, ConditionalRenderingFlags, DescriptorSetOffset, ImageBlit,
+        ImageCopy, ImageResolve, Level, RenderingInfo, SubpassContents,
     },
     device::OutOfMemory,
     image::{Filter, Layout, SubresourceRange},
     memory::{Barrier, Dependencies},
     pso, query,
     queue::Submission,
-    window::{PresentError, PresentationSurface, Suboptimal, SwapImageIndex},
+    window::{Extent2D, PresentError, PresentationSurface, Suboptimal, SwapImageIndex},
     DrawCount, IndexCount, IndexType, InstanceCount, TaskCount, VertexCount, VertexOffset,
     WorkGroupCount,
 };
@@ -35,6 +35,13 @@ impl hal::queue::Queue<Backend> for Queue {
         todo!()
     }
 
+    unsafe fn submit_timeline<'a, Ic, Iw, Is>(&mut self, _: Ic, _: Iw, _: Is)
+    where
+        Ic: Iterator<Item = &'a <Backend as hal::Backend>::CommandBuffer>,
+    {
+        todo!()
+    }
+
     unsafe fn present(
         &mut self,
         _surface: &mut <Backend as hal::Backend>::Surface,
@@ -233,6 +240,50 @@ impl hal::command::CommandBuffer<Backend> for CommandBuffer {
         todo!()
     }
 
+    unsafe fn set_cull_face(&mut self, _faces: pso::Face) {
+        todo!()
+    }
+
+    unsafe fn set_front_face(&mut self, _front_face: pso::FrontFace) {
+        todo!()
+    }
+
+    unsafe fn set_primitive_topology(&mut self, _primitive: pso::Primitive) {
+        todo!()
+    }
+
+    unsafe fn set_depth_test(&mut self, _depth: Option<pso::DepthTest>) {
+        todo!()
+    }
+
+    unsafe fn set_stencil_faces(&mut self, _faces: pso::Face, _value: pso::StencilFace) {
+        todo!()
+    }
+
+    unsafe fn set_vertex_input(
+        &mut self,
+        _buffers: &[pso::VertexBufferDesc],
+        _attributes: &[pso::AttributeDesc],
+    ) {
+        todo!()
+    }
+
+    unsafe fn set_primitive_restart(&mut self, _enable: bool) {
+        todo!()
+    }
+
+    unsafe fn set_fragment_shading_rate(
+        &mut self,
+        _fragment_size: Extent2D,
+        _combiner_ops: [pso::ShadingRateCombinerOp; 2],
+    ) {
+        todo!()
+    }
+
+    unsafe fn set_sample_locations(&mut self, _locations: &[pso::SampleLocation]) {
+        todo!()
+    }
+
     unsafe fn begin_render_pass<T>(
         &mut self,
         _render_pass: &<Backend as hal::Backend>::RenderPass,
@@ -255,6 +306,14 @@ impl hal::command::CommandBuffer<Backend> for CommandBuffer {
         todo!()
     }
 
+    unsafe fn begin_rendering(&mut self, _info: &RenderingInfo<Backend>) {
+        todo!()
+    }
+
+    unsafe fn end_rendering(&mut self) {
+        todo!()
+    }
+
     unsafe fn bind_graphics_pipeline(
         &mut self,
         _pipeline: &<Backend as hal::Backend>::GraphicsPipeline,
@@ -311,6 +370,47 @@ impl hal::command::CommandBuffer<Backend> for CommandBuffer {
         todo!()
     }
 
+    unsafe fn build_acceleration_structure(
+        &mut self,
+        _desc: &acceleration_structure::BuildDesc<Backend>,
+        _ranges: &[acceleration_structure::BuildRangeDesc],
+    ) {
+        todo!()
+    }
+
+    unsafe fn bind_ray_tracing_pipeline(
+        &mut self,
+        _pipeline: &<Backend as hal::Backend>::RayTracingPipeline,
+    ) {
+        todo!()
+    }
+
+    unsafe fn bind_ray_tracing_descriptor_sets<I, J>(
+        &mut self,
+        _layout: &<Backend as hal::Backend>::PipelineLayout,
+        _first_set: usize,
+        _sets: I,
+        _offsets: J,
+    ) where
+        I: Iterator,
+        I::Item: Borrow<<Backend as hal::Backend>::DescriptorSet>,
+        J: Iterator,
+        J::Item: Borrow<DescriptorSetOffset>,
+    {
+        todo!()
+    }
+
+    unsafe fn trace_rays(
+        &mut self,
+        _raygen: &pso::ShaderBindingTable<Backend>,
+        _miss: Option<&pso::ShaderBindingTable<Backend>>,
+        _hit_group: Option<&pso::ShaderBindingTable<Backend>>,
+        _callable: Option<&pso::ShaderBindingTable<Backend>>,
+        _dimensions: [u32; 3],
+    ) {
+        todo!()
+    }
+
     unsafe fn copy_buffer<T>(
         &mut self,
         _src: &<Backend as hal::Backend>::Buffer,
@@ -511,6 +611,19 @@ impl hal::command::CommandBuffer<Backend> for CommandBuffer {
         todo!()
     }
 
+    unsafe fn begin_conditional_rendering(
+        &mut self,
+        _buffer: &<Backend as hal::Backend>::Buffer,
+        _offset: buffer::Offset,
+        _flags: ConditionalRenderingFlags,
+    ) {
+        todo!()
+    }
+
+    unsafe fn end_conditional_rendering(&mut self) {
+        todo!()
+    }
+
     unsafe fn push_graphics_constants(
         &mut self,
         _layout: &<Backend as hal::Backend>::PipelineLayout,
@@ -4,7 +4,7 @@ use std::{
 };
 
 use hal::{
-    buffer,
+    acceleration_structure, buffer,
     device::{
         AllocationError, BindError, DeviceLost, MapError, OutOfMemory, ShaderError, WaitError,
     },
@@ -121,6 +121,30 @@ impl hal::device::Device<Backend> for Device {
         todo!()
     }
 
+    unsafe fn create_graphics_pipeline_library<'a>(
+        &self,
+        _desc: &pso::GraphicsPipelineDesc<'a, Backend>,
+        _parts: pso::GraphicsPipelineLibraryParts,
+        _cache: Option<&<Backend as hal::Backend>::PipelineCache>,
+    ) -> Result<<Backend as hal::Backend>::GraphicsPipelineLibrary, pso::CreationError> {
+        todo!()
+    }
+
+    unsafe fn link_graphics_pipeline<'a>(
+        &self,
+        _desc: &pso::GraphicsPipelineLinkDesc<'a, Backend>,
+        _cache: Option<&<Backend as hal::Backend>::PipelineCache>,
+    ) -> Result<<Backend as hal::Backend>::GraphicsPipeline, pso::CreationError> {
+        todo!()
+    }
+
+    unsafe fn destroy_graphics_pipeline_library(
+        &self,
+        _library: <Backend as hal::Backend>::GraphicsPipelineLibrary,
+    ) {
+        todo!()
+    }
+
     unsafe fn create_compute_pipeline<'a>(
         &self,
         _desc: &hal::pso::ComputePipelineDesc<'a, Backend>,
@@ -136,6 +160,44 @@ impl hal::device::Device<Backend> for Device {
         todo!()
     }
 
+    unsafe fn get_graphics_pipeline_executables(
+        &self,
+        _pipeline: &<Backend as hal::Backend>::GraphicsPipeline,
+    ) -> Vec<pso::PipelineExecutable> {
+        todo!()
+    }
+
+    unsafe fn get_compute_pipeline_executables(
+        &self,
+        _pipeline: &<Backend as hal::Backend>::ComputePipeline,
+    ) -> Vec<pso::PipelineExecutable> {
+        todo!()
+    }
+
+    unsafe fn create_ray_tracing_pipeline<'a>(
+        &self,
+        _desc: &pso::RayTracingPipelineDesc<'a, Backend>,
+        _cache: Option<&<Backend as hal::Backend>::PipelineCache>,
+    ) -> Result<<Backend as hal::Backend>::RayTracingPipeline, pso::CreationError> {
+        todo!()
+    }
+
+    unsafe fn get_ray_tracing_shader_group_handles(
+        &self,
+        _pipeline: &<Backend as hal::Backend>::RayTracingPipeline,
+        _first_group: u32,
+        _group_count: u32,
+    ) -> Result<Vec<u8>, OutOfMemory> {
+        todo!()
+    }
+
+    unsafe fn destroy_ray_tracing_pipeline(
+        &self,
+        _pipeline: <Backend as hal::Backend>::RayTracingPipeline,
+    ) {
+        todo!()
+    }
+
     unsafe fn create_framebuffer<I>(
         &self,
         _pass: &<Backend as hal::Backend>::RenderPass,
@@ -205,6 +267,35 @@ impl hal::device::Device<Backend> for Device {
         todo!()
     }
 
+    unsafe fn get_acceleration_structure_build_requirements(
+        &self,
+        _desc: &acceleration_structure::GeometryDesc<Backend>,
+        _max_primitive_counts: &[u32],
+    ) -> acceleration_structure::SizeRequirements {
+        todo!()
+    }
+
+    unsafe fn create_acceleration_structure(
+        &self,
+        _desc: &acceleration_structure::CreateDesc<Backend>,
+    ) -> Result<<Backend as hal::Backend>::AccelerationStructure, OutOfMemory> {
+        todo!()
+    }
+
+    unsafe fn get_acceleration_structure_address(
+        &self,
+        _accel_struct: &<Backend as hal::Backend>::AccelerationStructure,
+    ) -> acceleration_structure::DeviceAddress {
+        todo!()
+    }
+
+    unsafe fn destroy_acceleration_structure(
+        &self,
+        _accel_struct: <Backend as hal::Backend>::AccelerationStructure,
+    ) {
+        todo!()
+    }
+
     unsafe fn create_image(
         &self,
         _kind: image::Kind,
@@ -348,6 +439,44 @@ impl hal::device::Device<Backend> for Device {
         todo!()
     }
 
+    fn create_timeline_semaphore(
+        &self,
+        _initial_value: u64,
+    ) -> Result<<Backend as hal::Backend>::TimelineSemaphore, OutOfMemory> {
+        todo!()
+    }
+
+    unsafe fn destroy_timeline_semaphore(
+        &self,
+        _semaphore: <Backend as hal::Backend>::TimelineSemaphore,
+    ) {
+        todo!()
+    }
+
+    unsafe fn get_timeline_semaphore_value(
+        &self,
+        _semaphore: &<Backend as hal::Backend>::TimelineSemaphore,
+    ) -> Result<u64, DeviceLost> {
+        todo!()
+    }
+
+    unsafe fn signal_timeline_semaphore(
+        &self,
+        _semaphore: &<Backend as hal::Backend>::TimelineSemaphore,
+        _value: u64,
+    ) -> Result<(), OutOfMemory> {
+        todo!()
+    }
+
+    unsafe fn wait_for_timeline_semaphore(
+        &self,
+        _semaphore: &<Backend as hal::Backend>::TimelineSemaphore,
+        _value: u64,
+        _timeout_ns: u64,
+    ) -> Result<bool, WaitError> {
+        todo!()
+    }
+
     fn create_fence(
         &self,
         _signaled: bool,
@@ -445,6 +574,14 @@ impl hal::device::Device<Backend> for Device {
         todo!()
     }
 
+    unsafe fn reset_query_pool(
+        &self,
+        _pool: &<Backend as hal::Backend>::QueryPool,
+        _queries: Range<query::Id>,
+    ) {
+        todo!()
+    }
+
     fn wait_idle(&self) -> Result<(), OutOfMemory> {
         todo!()
     }
@@ -517,6 +654,33 @@ impl hal::device::Device<Backend> for Device {
         // TODO
     }
 
+    unsafe fn create_private_data_slot(
+        &self,
+    ) -> Result<<Backend as hal::Backend>::PrivateDataSlot, OutOfMemory> {
+        todo!()
+    }
+
+    unsafe fn destroy_private_data_slot(&self, _slot: <Backend as hal::Backend>::PrivateDataSlot) {
+        todo!()
+    }
+
+    unsafe fn set_private_data(
+        &self,
+        _object: hal::device::PrivateDataObject<Backend>,
+        _slot: &<Backend as hal::Backend>::PrivateDataSlot,
+        _data: u64,
+    ) -> Result<(), OutOfMemory> {
+        todo!()
+    }
+
+    unsafe fn get_private_data(
+        &self,
+        _object: hal::device::PrivateDataObject<Backend>,
+        _slot: &<Backend as hal::Backend>::PrivateDataSlot,
+    ) -> u64 {
+        todo!()
+    }
+
     unsafe fn set_display_power_state(
         &self,
         _display: &display::Display<Backend>,
@@ -43,9 +43,12 @@ impl hal::Backend for Backend {
     type Image = ();
     type ImageView = ();
     type Sampler = ();
+    type AccelerationStructure = ();
 
     type ComputePipeline = ();
+    type RayTracingPipeline = ();
     type GraphicsPipeline = ();
+    type GraphicsPipelineLibrary = ();
     type PipelineCache = ();
     type PipelineLayout = ();
     type DescriptorSetLayout = ();
@@ -54,8 +57,10 @@ impl hal::Backend for Backend {
 
     type Fence = ();
     type Semaphore = ();
+    type TimelineSemaphore = ();
     type Event = ();
     type QueryPool = ();
+    type PrivateDataSlot = ();
 
     type Display = ();
     type DisplayMode = ();
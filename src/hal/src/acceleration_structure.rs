@@ -0,0 +1,212 @@
+//! Acceleration structures.
+//!
+//! Acceleration structures are opaque spatial indices over the geometry traversed by the rays
+//! of [ray tracing pipelines][crate::pso::RayTracingPipelineDesc]. Bottom-level structures
+//! contain triangles or axis-aligned bounding boxes, while top-level structures contain
+//! [instances][Instance] of the bottom-level ones.
+//!
+//! A structure lives in a buffer range sized according to the [`SizeRequirements`] of its
+//! geometry, and is built on the device with the help of a scratch buffer.
+
+use crate::{buffer, format, Backend, IndexType};
+
+/// Address of an acceleration structure on the device,
+/// used to reference bottom-level structures from the [instances][Instance].
+pub type DeviceAddress = u64;
+
+/// Level of an acceleration structure in the hierarchy.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Level {
+    /// Contains instances of bottom-level structures.
+    TopLevel,
+    /// Contains triangles or bounding boxes.
+    BottomLevel,
+}
+
+bitflags!(
+    /// Options of an acceleration structure build.
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct BuildFlags: u32 {
+        /// Allow the structure to be updated in place by later builds.
+        const ALLOW_UPDATE = 0x1;
+        /// Allow the structure to be compacted.
+        const ALLOW_COMPACTION = 0x2;
+        /// Prioritize the tracing performance over the build time.
+        const PREFER_FAST_TRACE = 0x4;
+        /// Prioritize the build time over the tracing performance.
+        const PREFER_FAST_BUILD = 0x8;
+        /// Minimize the memory used by the structure and the scratch space.
+        const LOW_MEMORY = 0x10;
+    }
+);
+
+bitflags!(
+    /// Options of a geometry.
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct GeometryFlags: u32 {
+        /// The geometry doesn't invoke the any-hit shaders.
+        const OPAQUE = 0x1;
+        /// The any-hit shader is invoked at most once per primitive.
+        const NO_DUPLICATE_ANY_HIT_INVOCATION = 0x2;
+    }
+);
+
+bitflags!(
+    /// Options of an instance in a top-level structure.
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct InstanceFlags: u8 {
+        /// Disable face culling for the triangles of the instance.
+        const TRIANGLE_FACING_CULL_DISABLE = 0x1;
+        /// Consider the counter-clockwise triangles as front-facing.
+        const TRIANGLE_FRONT_COUNTERCLOCKWISE = 0x2;
+        /// Treat all the geometries of the instance as opaque.
+        const FORCE_OPAQUE = 0x4;
+        /// Treat all the geometries of the instance as non-opaque.
+        const FORCE_NO_OPAQUE = 0x8;
+    }
+);
+
+/// Description of an acceleration structure to create.
+#[derive(Debug)]
+pub struct CreateDesc<'a, B: Backend> {
+    /// Buffer holding the structure.
+    ///
+    /// Requires `buffer::Usage::ACCELERATION_STRUCTURE_STORAGE`.
+    pub buffer: &'a B::Buffer,
+    /// Offset of the structure in the buffer, aligned to 256 bytes.
+    pub offset: buffer::Offset,
+    /// Size of the structure, as given by the `SizeRequirements` of its geometry.
+    pub size: u64,
+    /// Level of the structure.
+    pub level: Level,
+}
+
+/// Triangle geometry of a bottom-level structure.
+#[derive(Debug)]
+pub struct Triangles<'a, B: Backend> {
+    /// Format of the vertex positions.
+    pub vertex_format: format::Format,
+    /// Buffer holding the vertex positions, and the offset of the first one.
+    pub vertex_buffer: (&'a B::Buffer, buffer::Offset),
+    /// Byte stride between the vertex positions.
+    pub vertex_stride: buffer::Stride,
+    /// Highest vertex index referenced by the triangles.
+    pub max_vertex: u32,
+    /// Buffer holding the indices, the offset of the first one, and their type, if any.
+    pub index_buffer: Option<(&'a B::Buffer, buffer::Offset, IndexType)>,
+    /// Buffer holding a 3x4 row-major transform applied to the vertices, if any.
+    pub transform_buffer: Option<(&'a B::Buffer, buffer::Offset)>,
+}
+
+/// Axis-aligned bounding box geometry of a bottom-level structure,
+/// intersected by the intersection shaders.
+#[derive(Debug)]
+pub struct Aabbs<'a, B: Backend> {
+    /// Buffer holding the boxes as six floats (minimum then maximum corner),
+    /// and the offset of the first one.
+    pub buffer: (&'a B::Buffer, buffer::Offset),
+    /// Byte stride between the boxes.
+    pub stride: buffer::Stride,
+}
+
+/// Instances of a top-level structure.
+#[derive(Debug)]
+pub struct Instances<'a, B: Backend> {
+    /// Buffer holding tightly packed [instances][Instance], and the offset of the first one.
+    pub buffer: (&'a B::Buffer, buffer::Offset),
+}
+
+/// Data of a geometry.
+#[derive(Debug)]
+pub enum GeometryData<'a, B: Backend> {
+    /// Triangles, in a bottom-level structure.
+    Triangles(Triangles<'a, B>),
+    /// Bounding boxes, in a bottom-level structure.
+    Aabbs(Aabbs<'a, B>),
+    /// Instances, in a top-level structure.
+    Instances(Instances<'a, B>),
+}
+
+/// A geometry of an acceleration structure.
+#[derive(Debug)]
+pub struct Geometry<'a, B: Backend> {
+    /// Options of the geometry.
+    pub flags: GeometryFlags,
+    /// Data of the geometry.
+    pub data: GeometryData<'a, B>,
+}
+
+/// Description of the geometries of an acceleration structure.
+///
+/// Used both to query the size requirements of a structure, and to build it.
+#[derive(Debug)]
+pub struct GeometryDesc<'a, B: Backend> {
+    /// Level of the structure.
+    pub level: Level,
+    /// Options of the build.
+    pub flags: BuildFlags,
+    /// Geometries of the structure.
+    ///
+    /// Top-level structures have a single geometry of instances, while bottom-level
+    /// ones have any number of geometries, either all triangles or all bounding boxes.
+    pub geometries: &'a [Geometry<'a, B>],
+}
+
+/// Description of an acceleration structure build.
+#[derive(Debug)]
+pub struct BuildDesc<'a, B: Backend> {
+    /// Structure to update, which was built with `BuildFlags::ALLOW_UPDATE`.
+    /// If `None`, the destination is built from scratch.
+    pub src: Option<&'a B::AccelerationStructure>,
+    /// Structure to build.
+    pub dst: &'a B::AccelerationStructure,
+    /// Geometries to build the structure from.
+    pub geometry: &'a GeometryDesc<'a, B>,
+    /// Buffer used as scratch space by the build, and the offset of the space.
+    ///
+    /// Requires `buffer::Usage::STORAGE`.
+    pub scratch: (&'a B::Buffer, buffer::Offset),
+}
+
+/// Range of the primitives to build of a geometry.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BuildRangeDesc {
+    /// Number of triangles, bounding boxes or instances.
+    pub primitive_count: u32,
+    /// Byte offset of the first primitive in the index buffer, or in the vertex buffer
+    /// for non-indexed triangles, or in the bounding box or instance buffer.
+    pub primitive_offset: u32,
+    /// Index of the first vertex of indexed triangles.
+    pub first_vertex: u32,
+    /// Byte offset of the transform in the transform buffer.
+    pub transform_offset: u32,
+}
+
+/// Memory required by an acceleration structure and its builds.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SizeRequirements {
+    /// Size of the structure.
+    pub acceleration_structure_size: u64,
+    /// Size of the scratch space needed by a build from scratch.
+    pub build_scratch_size: u64,
+    /// Size of the scratch space needed by an update.
+    pub update_scratch_size: u64,
+}
+
+/// Instance of a bottom-level structure, as laid out in the instance buffers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+pub struct Instance {
+    /// Row-major 3x4 transform of the instance.
+    pub transform: [[f32; 4]; 3],
+    /// Custom index in the low 24 bits, visibility mask in the high 8 bits.
+    pub custom_index_and_mask: u32,
+    /// Offset of the hit group in the shader binding table in the low 24 bits,
+    /// `InstanceFlags` in the high 8 bits.
+    pub shader_binding_table_offset_and_flags: u32,
+    /// Address of the instanced bottom-level structure.
+    pub acceleration_structure: DeviceAddress,
+}
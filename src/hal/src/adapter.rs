@@ -9,7 +9,10 @@
 //! of that [backend][crate::Backend].
 
 use crate::{
-    device, display, format, image, memory,
+    buffer, device, display,
+    external_memory::ExternalMemoryProperties,
+    external_sync::ExternalSyncProperties,
+    format, image, memory, query,
     queue::{QueueGroup, QueuePriority},
     Backend, Features, PhysicalDeviceProperties,
 };
@@ -112,6 +115,20 @@ pub trait PhysicalDevice<B: Backend>: fmt::Debug + Any + Send + Sync {
         view_caps: image::ViewCapabilities,
     ) -> Option<image::FormatProperties>;
 
+    /// Fetch the implicit compression properties of images with the given parameters,
+    /// see [`Device::create_image_with_compression`][crate::device::Device::create_image_with_compression].
+    fn image_compression_properties(
+        &self,
+        format: format::Format,
+        dimensions: u8,
+        tiling: image::Tiling,
+        usage: image::Usage,
+        view_caps: image::ViewCapabilities,
+    ) -> image::CompressionProperties {
+        let _ = (format, dimensions, tiling, usage, view_caps);
+        image::CompressionProperties::default()
+    }
+
     /// Fetch details for the memory regions provided by the device.
     fn memory_properties(&self) -> MemoryProperties;
 
@@ -128,6 +145,85 @@ pub trait PhysicalDevice<B: Backend>: fmt::Debug + Any + Send + Sync {
         false
     }
 
+    /// Create a new logical device spanning this physical device and the `peers`,
+    /// which all belong to the same [device group][DeviceGroup].
+    ///
+    /// This physical device gets the device index zero, followed by the peers in order.
+    /// Backends without multi-GPU support only accept an empty list of peers.
+    unsafe fn open_group(
+        &self,
+        peers: &[&B::PhysicalDevice],
+        families: &[(&B::QueueFamily, &[QueuePriority])],
+        requested_features: Features,
+    ) -> Result<Gpu<B>, device::CreationError> {
+        if peers.is_empty() {
+            self.open(families, requested_features)
+        } else {
+            Err(device::CreationError::InitializationFailed)
+        }
+    }
+
+    /// Create a new logical device, with protected queues created from `protected_families`
+    /// in addition to the regular queues created from `families`.
+    ///
+    /// The protected queues are returned in their own [groups][QueueGroup::protected],
+    /// after the regular ones.
+    /// Backends without [protected memory][crate::ProtectedMemoryProperties] support
+    /// only accept an empty list of protected families.
+    unsafe fn open_protected(
+        &self,
+        families: &[(&B::QueueFamily, &[QueuePriority])],
+        protected_families: &[(&B::QueueFamily, &[QueuePriority])],
+        requested_features: Features,
+    ) -> Result<Gpu<B>, device::CreationError> {
+        if protected_families.is_empty() {
+            self.open(families, requested_features)
+        } else {
+            Err(device::CreationError::MissingFeature)
+        }
+    }
+
+    /// Enumerate the sets of hardware performance counters that can be sampled
+    /// with `query::Type::PerformanceCounters` queries.
+    fn performance_counter_sets(&self) -> Vec<query::CounterSet> {
+        Vec::new()
+    }
+
+    /// Get the external memory handle types that buffers with the given usage
+    /// can be bound to.
+    fn external_buffer_properties(
+        &self,
+        usage: buffer::Usage,
+        sparse: memory::SparseFlags,
+    ) -> ExternalMemoryProperties {
+        let _ = (usage, sparse);
+        ExternalMemoryProperties::default()
+    }
+
+    /// Get the external memory handle types that images with the given parameters
+    /// can be bound to.
+    fn external_image_properties(
+        &self,
+        format: format::Format,
+        dimensions: u8,
+        tiling: image::Tiling,
+        usage: image::Usage,
+        view_caps: image::ViewCapabilities,
+    ) -> ExternalMemoryProperties {
+        let _ = (format, dimensions, tiling, usage, view_caps);
+        ExternalMemoryProperties::default()
+    }
+
+    /// Get the external handle types that the payloads of fences can be shared through.
+    fn external_fence_properties(&self) -> ExternalSyncProperties {
+        ExternalSyncProperties::default()
+    }
+
+    /// Get the external handle types that the payloads of semaphores can be shared through.
+    fn external_semaphore_properties(&self) -> ExternalSyncProperties {
+        ExternalSyncProperties::default()
+    }
+
     /// Enumerate active displays [surface][display::Display] from display.
     /// Please notice that, even if a system has displays attached, they could be not returned because they are managed by some other components.
     /// This function only return the display that are available to be managed by the current application.
@@ -207,6 +303,16 @@ pub struct AdapterInfo {
     pub device_type: DeviceType,
 }
 
+/// A group of adapters that can be opened together as a single logical device,
+/// with [`open_group`](PhysicalDevice::open_group).
+#[derive(Debug)]
+pub struct DeviceGroup<B: Backend> {
+    /// Adapters of the group, in the order of their device indices.
+    pub adapters: Vec<Adapter<B>>,
+    /// Whether memory can be instantiated on a subset of the devices of the group.
+    pub subset_allocation: bool,
+}
+
 /// Information about a graphics device, supported by the backend.
 ///
 /// The list of available adapters is obtained by calling
@@ -13,6 +13,9 @@ pub type Offset = u64;
 /// An stride between elements inside a buffer, in bytes.
 pub type Stride = u32;
 
+/// Address of a buffer on the device, offset to the start of its memory binding.
+pub type DeviceAddress = u64;
+
 /// A subrange of the buffer.
 #[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -85,6 +88,17 @@ bitflags!(
         const VERTEX = 0x80;
         ///
         const INDIRECT = 0x100;
+        /// Holds the predicates of conditional rendering.
+        const CONDITIONAL_RENDERING = 0x200;
+        /// Holds shader binding table records for tracing rays.
+        const SHADER_BINDING_TABLE = 0x400;
+        /// Can be addressed by shaders through its device address,
+        /// see [`Device::get_buffer_device_address`][crate::device::Device::get_buffer_device_address].
+        const SHADER_DEVICE_ADDRESS = 0x20000;
+        /// Holds geometry or instances read by acceleration structure builds.
+        const ACCELERATION_STRUCTURE_BUILD_INPUT = 0x80000;
+        /// Holds acceleration structures.
+        const ACCELERATION_STRUCTURE_STORAGE = 0x100000;
     }
 );
 
@@ -144,5 +158,11 @@ bitflags!(
         const MEMORY_READ = 0x8000;
         ///
         const MEMORY_WRITE = 0x10000;
+        /// Read the predicates of conditional rendering.
+        const CONDITIONAL_RENDERING_READ = 0x100000;
+        /// Read acceleration structures, by builds or by traced rays.
+        const ACCELERATION_STRUCTURE_READ = 0x200000;
+        /// Write acceleration structures, by builds.
+        const ACCELERATION_STRUCTURE_WRITE = 0x400000;
     }
 );
@@ -17,11 +17,15 @@ mod clear;
 mod structs;
 
 use crate::{
-    buffer,
-    image::{Filter, Layout, SubresourceRange},
+    acceleration_structure, buffer,
+    device::DeviceMask,
+    format,
+    image::{Filter, Layer, Layout, NumSamples, SubresourceRange},
     memory::{Barrier, Dependencies},
-    pass, pso, query, Backend, DrawCount, IndexCount, IndexType, InstanceCount, TaskCount,
-    VertexCount, VertexOffset, WorkGroupCount,
+    pass, pso, query,
+    window::Extent2D,
+    Backend, DrawCount, IndexCount, IndexType, InstanceCount, TaskCount, VertexCount, VertexOffset,
+    WorkGroupCount,
 };
 
 use std::{any::Any, fmt, ops::Range};
@@ -50,6 +54,15 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// Options of conditional rendering.
+    #[derive(Default)]
+    pub struct ConditionalRenderingFlags: u32 {
+        /// The commands are discarded when the predicate is non-zero, instead of zero.
+        const INVERTED = 0x1;
+    }
+}
+
 /// An enum that indicates whether a command buffer is primary or secondary.
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Level {
@@ -81,6 +94,49 @@ pub struct RenderAttachmentInfo<'a, B: Backend> {
     pub clear_value: ClearValue,
 }
 
+/// An attachment of a rendering scope started by `begin_rendering`.
+#[derive(Debug)]
+pub struct RenderingAttachmentInfo<'a, B: Backend> {
+    /// View of the attachment image.
+    pub image_view: &'a B::ImageView,
+    /// Format of the attachment, matching the format of the view.
+    pub format: format::Format,
+    /// Layout of the image during the rendering.
+    pub layout: Layout,
+    /// Load and store operations of the attachment.
+    ///
+    /// For depth-stencil attachments, these apply to the depth aspect.
+    pub ops: pass::AttachmentOps,
+    /// Load and store operations of the stencil aspect of a depth-stencil attachment.
+    pub stencil_ops: pass::AttachmentOps,
+    /// Clear value, used if a load operation is `Clear`.
+    pub clear_value: ClearValue,
+    /// View of the image the attachment is resolved into at the end of the rendering,
+    /// and its layout, if any.
+    ///
+    /// Depth-stencil attachments are resolved by taking the sample zero of both aspects,
+    /// which requires [`Features::DEPTH_STENCIL_RESOLVE`][crate::Features::DEPTH_STENCIL_RESOLVE].
+    pub resolve: Option<(&'a B::ImageView, Layout)>,
+}
+
+/// Description of a rendering scope started by `begin_rendering`.
+///
+/// Graphics pipelines bound in the scope are created with a subpass of a render pass
+/// whose attachments have the same formats and sample count.
+#[derive(Debug)]
+pub struct RenderingInfo<'a, B: Backend> {
+    /// Section of the attachments to render.
+    pub render_area: pso::Rect,
+    /// Number of layers rendered into.
+    pub layers: Layer,
+    /// Number of samples of the attachments.
+    pub samples: NumSamples,
+    /// Color attachments, in the order of the fragment shader outputs.
+    pub colors: &'a [RenderingAttachmentInfo<'a, B>],
+    /// Depth-stencil attachment, if any.
+    pub depth_stencil: Option<RenderingAttachmentInfo<'a, B>>,
+}
+
 #[allow(missing_docs)]
 #[derive(Debug)]
 pub struct CommandBufferInheritanceInfo<'a, B: Backend> {
@@ -126,6 +182,14 @@ pub trait CommandBuffer<B: Backend>: fmt::Debug + Any + Send + Sync {
     /// resources from the commands that have been submitted.
     unsafe fn reset(&mut self, release_resources: bool);
 
+    /// Restrict the following commands to the devices of `mask`, for command buffers
+    /// of a logical device spanning a device group.
+    ///
+    /// Backends without multi-GPU support only execute commands on the first device.
+    unsafe fn set_device_mask(&mut self, mask: DeviceMask) {
+        debug_assert_eq!(mask & 1, 1);
+    }
+
     // TODO: This REALLY needs to be deeper, but it's complicated.
     // Should probably be a whole book chapter on synchronization and stuff really.
     /// Inserts a synchronization dependency between pipeline stages
@@ -281,6 +345,75 @@ pub trait CommandBuffer<B: Backend>: fmt::Debug + Any + Send + Sync {
     /// Set the depth bias dynamically.
     unsafe fn set_depth_bias(&mut self, depth_bias: pso::DepthBias);
 
+    /// Set the culled faces dynamically.
+    ///
+    /// Only applies to pipelines created with `DynamicStates::CULL_FACE`.
+    unsafe fn set_cull_face(&mut self, faces: pso::Face);
+
+    /// Set the front face winding dynamically.
+    ///
+    /// Only applies to pipelines created with `DynamicStates::FRONT_FACE`.
+    unsafe fn set_front_face(&mut self, front_face: pso::FrontFace);
+
+    /// Set the primitive topology dynamically.
+    ///
+    /// Only applies to pipelines created with `DynamicStates::PRIMITIVE_TOPOLOGY`.
+    unsafe fn set_primitive_topology(&mut self, primitive: pso::Primitive);
+
+    /// Set the depth test dynamically, or disable it with `None`.
+    ///
+    /// Only applies to pipelines created with `DynamicStates::DEPTH_TEST`.
+    unsafe fn set_depth_test(&mut self, depth: Option<pso::DepthTest>);
+
+    /// Set the comparison and operations of the stencil faces dynamically.
+    ///
+    /// Only applies to pipelines created with `DynamicStates::STENCIL_FACES`.
+    unsafe fn set_stencil_faces(&mut self, faces: pso::Face, value: pso::StencilFace);
+
+    /// Set the layout of the vertex buffers and attributes dynamically.
+    ///
+    /// Only applies to pipelines created with `DynamicStates::VERTEX_INPUT`,
+    /// and has to be set before drawing with them.
+    unsafe fn set_vertex_input(
+        &mut self,
+        buffers: &[pso::VertexBufferDesc],
+        attributes: &[pso::AttributeDesc],
+    );
+
+    /// Enable or disable primitive restart dynamically.
+    ///
+    /// Only valid to call if `Features::PRIMITIVE_RESTART_DYNAMIC_STATE` is enabled,
+    /// and only applies to pipelines created with `DynamicStates::PRIMITIVE_RESTART`.
+    /// The restart index is the largest value of the type of the bound index buffer.
+    unsafe fn set_primitive_restart(&mut self, enable: bool);
+
+    /// Set the fragment shading rate of the following draws, as the size in pixels
+    /// of the area covered by each fragment shader invocation.
+    ///
+    /// The rate is combined with the one written by the shaders for each primitive
+    /// using `combiner_ops[0]`, then with the one of the shading rate attachment
+    /// of the subpass using `combiner_ops[1]`. At the beginning of the command buffer,
+    /// the rate is 1x1 and both operations are `Keep`.
+    ///
+    /// Requires [`Features::PIPELINE_FRAGMENT_SHADING_RATE`][crate::Features::PIPELINE_FRAGMENT_SHADING_RATE],
+    /// and the primitive or attachment shading rate features for the matching
+    /// operations to be anything else than `Keep`.
+    unsafe fn set_fragment_shading_rate(
+        &mut self,
+        fragment_size: Extent2D,
+        combiner_ops: [pso::ShadingRateCombinerOp; 2],
+    );
+
+    /// Set the locations of the samples in each pixel for the render passes begun afterwards.
+    ///
+    /// There must be one location per sample of the attachments of these passes,
+    /// in sample index order. An empty slice restores the standard locations,
+    /// which are also used at the beginning of the command buffer.
+    ///
+    /// Requires the sample count of the attachments to be included in
+    /// [`SampleLocationsProperties::sample_counts`][crate::SampleLocationsProperties::sample_counts].
+    unsafe fn set_sample_locations(&mut self, locations: &[pso::SampleLocation]);
+
     /// Begins recording commands for a render pass on the given framebuffer.
     ///
     /// # Arguments
@@ -307,6 +440,15 @@ pub trait CommandBuffer<B: Backend>: fmt::Debug + Any + Send + Sync {
     /// Finishes recording commands for the current a render pass.
     unsafe fn end_render_pass(&mut self);
 
+    /// Begins recording rendering commands into the attachments described by `info`,
+    /// without render pass and framebuffer objects.
+    ///
+    /// Requires [`Features::DYNAMIC_RENDERING`][crate::Features::DYNAMIC_RENDERING].
+    unsafe fn begin_rendering(&mut self, info: &RenderingInfo<B>);
+
+    /// Finishes recording commands for the current rendering scope.
+    unsafe fn end_rendering(&mut self);
+
     /// Bind a graphics pipeline.
     ///
     /// # Errors
@@ -375,6 +517,51 @@ pub trait CommandBuffer<B: Backend>: fmt::Debug + Any + Send + Sync {
     /// buffer during execution.
     unsafe fn dispatch_indirect(&mut self, buffer: &B::Buffer, offset: buffer::Offset);
 
+    /// Build an acceleration structure, or update it from `desc.src` if provided.
+    ///
+    /// `ranges` holds the range of primitives to build for each of the geometries.
+    ///
+    /// - Only queues with compute capability support this function.
+    /// - This function must be called outside of a render pass.
+    unsafe fn build_acceleration_structure(
+        &mut self,
+        desc: &acceleration_structure::BuildDesc<B>,
+        ranges: &[acceleration_structure::BuildRangeDesc],
+    );
+
+    /// Bind a ray tracing pipeline.
+    ///
+    /// - Only queues with compute capability support this function.
+    unsafe fn bind_ray_tracing_pipeline(&mut self, pipeline: &B::RayTracingPipeline);
+
+    /// Takes an iterator of ray tracing `DescriptorSet`'s, and binds them to the command buffer,
+    /// `first_set` is the index that the first descriptor is mapped to in the command buffer.
+    unsafe fn bind_ray_tracing_descriptor_sets<'a, I, J>(
+        &mut self,
+        layout: &B::PipelineLayout,
+        first_set: usize,
+        sets: I,
+        offsets: J,
+    ) where
+        I: Iterator<Item = &'a B::DescriptorSet>,
+        J: Iterator<Item = DescriptorSetOffset>;
+
+    /// Trace a grid of `dimensions` rays with the bound ray tracing pipeline.
+    ///
+    /// The shader binding tables give the ray generation record, and the miss,
+    /// hit group and callable records indexed by the shaders.
+    ///
+    /// - A ray tracing pipeline must be bound using `bind_ray_tracing_pipeline`.
+    /// - This function must be called outside of a render pass.
+    unsafe fn trace_rays(
+        &mut self,
+        raygen: &pso::ShaderBindingTable<B>,
+        miss: Option<&pso::ShaderBindingTable<B>>,
+        hit_group: Option<&pso::ShaderBindingTable<B>>,
+        callable: Option<&pso::ShaderBindingTable<B>>,
+        dimensions: [u32; 3],
+    );
+
     /// Adds a command to copy regions from the source to destination buffer.
     unsafe fn copy_buffer<T>(&mut self, src: &B::Buffer, dst: &B::Buffer, regions: T)
     where
@@ -434,6 +621,34 @@ pub trait CommandBuffer<B: Backend>: fmt::Debug + Any + Send + Sync {
         instances: Range<InstanceCount>,
     );
 
+    /// Performs a non-indexed draw for each of the vertex ranges,
+    /// all with the same instances.
+    ///
+    /// It's equivalent to calling `draw()` in a loop,
+    /// but lets the backends record the draws without the per-call overhead.
+    unsafe fn draw_multi<I>(&mut self, vertices: I, instances: Range<InstanceCount>)
+    where
+        I: Iterator<Item = Range<VertexCount>>,
+    {
+        for range in vertices {
+            self.draw(range, instances.clone());
+        }
+    }
+
+    /// Performs an indexed draw for each of the index ranges and their base vertex,
+    /// all with the same instances.
+    ///
+    /// It's equivalent to calling `draw_indexed()` in a loop,
+    /// but lets the backends record the draws without the per-call overhead.
+    unsafe fn draw_multi_indexed<I>(&mut self, draws: I, instances: Range<InstanceCount>)
+    where
+        I: Iterator<Item = (Range<IndexCount>, VertexOffset)>,
+    {
+        for (indices, base_vertex) in draws {
+            self.draw_indexed(indices, base_vertex, instances.clone());
+        }
+    }
+
     /// Functions identically to `draw()`, except the parameters are read
     /// from the given buffer, starting at `offset` and increasing `stride`
     /// bytes with each successive draw.  Performs `draw_count` draws total.
@@ -574,6 +789,29 @@ pub trait CommandBuffer<B: Backend>: fmt::Debug + Any + Send + Sync {
     /// Requests a timestamp to be written.
     unsafe fn write_timestamp(&mut self, stage: pso::PipelineStage, query: query::Query<B>);
 
+    /// Begin a block of commands that are discarded depending on a predicate in a buffer.
+    ///
+    /// The predicate is the 32-bit unsigned integer at `offset`, which must be a multiple of 8,
+    /// and the 4 bytes following it must be zero. When the predicate is zero, the draws,
+    /// dispatches and attachment clears of the block are discarded.
+    /// The predicate is read once, when the block begins.
+    ///
+    /// The buffer must have the `CONDITIONAL_RENDERING` usage, and be accessed with
+    /// `buffer::Access::CONDITIONAL_RENDERING_READ` in the `CONDITIONAL_RENDERING` stage.
+    /// A block that begins in a render pass must end in the same subpass,
+    /// and one that begins outside must end outside of render passes.
+    ///
+    /// Requires [`Features::CONDITIONAL_RENDERING`][crate::Features::CONDITIONAL_RENDERING].
+    unsafe fn begin_conditional_rendering(
+        &mut self,
+        buffer: &B::Buffer,
+        offset: buffer::Offset,
+        flags: ConditionalRenderingFlags,
+    );
+
+    /// End the block of commands started with `begin_conditional_rendering`.
+    unsafe fn end_conditional_rendering(&mut self);
+
     /// Modify constant data in a graphics pipeline. Push constants are intended to modify data in a
     /// pipeline more quickly than a updating the values inside a descriptor set.
     ///
@@ -0,0 +1,221 @@
+//! Deferred host operations.
+//!
+//! Expensive host work, like compiling pipelines or deserializing pipeline caches,
+//! can be started as a [`DeferredOperation`] instead of being done right away.
+//! The work is then done by the threads of the application that [join][DeferredOperation::join]
+//! the operation, so that it runs wherever the application wants the CPU time to be spent.
+//!
+//! Backends that can't split an operation complete it on the first joining thread,
+//! and the ones that can't defer it at all return an operation that is already complete.
+
+use std::{
+    fmt,
+    sync::{Condvar, Mutex},
+};
+
+/// Work item of a deferred operation, run by a single joining thread.
+pub type DeferredTask = Box<dyn FnOnce() + Send>;
+
+/// Work that combines the results of the tasks, run by the thread that completes the last one.
+pub type DeferredFinish<T> = Box<dyn FnOnce() -> T + Send>;
+
+/// Status returned by [`DeferredOperation::join`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum JoinStatus {
+    /// The operation is complete, and its result can be taken.
+    Done,
+    /// There is no more work for this thread, but the operation is still
+    /// being completed by other threads.
+    ThreadDone,
+}
+
+struct State<T> {
+    tasks: Vec<DeferredTask>,
+    running: usize,
+    finish: Option<DeferredFinish<T>>,
+    result: Option<T>,
+    complete: bool,
+}
+
+/// Host operation whose work is done by the threads that join it.
+pub struct DeferredOperation<T> {
+    state: Mutex<State<T>>,
+    condvar: Condvar,
+}
+
+impl<T> fmt::Debug for DeferredOperation<T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.state.lock().unwrap();
+        formatter
+            .debug_struct("DeferredOperation")
+            .field("pending_tasks", &state.tasks.len())
+            .field("running_tasks", &state.running)
+            .field("complete", &state.complete)
+            .finish()
+    }
+}
+
+impl<T> DeferredOperation<T> {
+    /// Create an operation made of independent `tasks`, which may run concurrently,
+    /// followed by `finish` once all of them are done.
+    pub fn new(tasks: Vec<DeferredTask>, finish: DeferredFinish<T>) -> Self {
+        DeferredOperation {
+            state: Mutex::new(State {
+                tasks,
+                running: 0,
+                finish: Some(finish),
+                result: None,
+                complete: false,
+            }),
+            condvar: Condvar::new(),
+        }
+    }
+
+    /// Create an operation that is already complete.
+    pub fn ready(result: T) -> Self {
+        DeferredOperation {
+            state: Mutex::new(State {
+                tasks: Vec::new(),
+                running: 0,
+                finish: None,
+                result: Some(result),
+                complete: true,
+            }),
+            condvar: Condvar::new(),
+        }
+    }
+
+    /// Get the number of threads that can currently do work by joining the operation.
+    ///
+    /// This is zero once the operation is complete, and at least one before.
+    pub fn max_concurrency(&self) -> usize {
+        let state = self.state.lock().unwrap();
+        if state.complete {
+            0
+        } else {
+            state.tasks.len().max(1)
+        }
+    }
+
+    /// Return true if the operation is complete.
+    pub fn is_complete(&self) -> bool {
+        self.state.lock().unwrap().complete
+    }
+
+    /// Do the work of the operation on the calling thread, until there is none left for it.
+    ///
+    /// Any number of threads can join the operation at the same time.
+    pub fn join(&self) -> JoinStatus {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if state.complete {
+                return JoinStatus::Done;
+            }
+            if let Some(task) = state.tasks.pop() {
+                state.running += 1;
+                drop(state);
+                task();
+                state = self.state.lock().unwrap();
+                state.running -= 1;
+                continue;
+            }
+            if state.running != 0 {
+                return JoinStatus::ThreadDone;
+            }
+            match state.finish.take() {
+                Some(finish) => {
+                    // Keep other threads from finishing while the lock is released.
+                    state.running += 1;
+                    drop(state);
+                    let result = finish();
+                    state = self.state.lock().unwrap();
+                    state.running -= 1;
+                    state.result = Some(result);
+                    state.complete = true;
+                    self.condvar.notify_all();
+                }
+                None => return JoinStatus::ThreadDone,
+            }
+        }
+    }
+
+    /// Join the operation, and block until it is complete.
+    pub fn wait(&self) {
+        if self.join() == JoinStatus::Done {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        while !state.complete {
+            state = self.condvar.wait(state).unwrap();
+        }
+    }
+
+    /// Take the result of a complete operation.
+    ///
+    /// Returns `None` if the operation isn't complete, or if the result was already taken.
+    pub fn take_result(&self) -> Option<T> {
+        self.state.lock().unwrap().result.take()
+    }
+
+    /// Complete the operation on the calling thread, if it isn't already, and return its result.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the result was already taken.
+    pub fn into_result(self) -> T {
+        self.wait();
+        self.take_result()
+            .expect("Deferred operation result was already taken")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    #[test]
+    fn ready() {
+        let op = DeferredOperation::ready(5);
+        assert_eq!(op.max_concurrency(), 0);
+        assert_eq!(op.join(), JoinStatus::Done);
+        assert_eq!(op.into_result(), 5);
+    }
+
+    #[test]
+    fn tasks_then_finish() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let tasks = (0..4)
+            .map(|_| {
+                let counter = Arc::clone(&counter);
+                Box::new(move || {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                }) as DeferredTask
+            })
+            .collect();
+        let finish_counter = Arc::clone(&counter);
+        let op = Arc::new(DeferredOperation::new(
+            tasks,
+            Box::new(move || finish_counter.load(Ordering::SeqCst)),
+        ));
+        assert_eq!(op.max_concurrency(), 4);
+
+        let threads = (0..3)
+            .map(|_| {
+                let op = Arc::clone(&op);
+                std::thread::spawn(move || op.wait())
+            })
+            .collect::<Vec<_>>();
+        op.wait();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert!(op.is_complete());
+        assert_eq!(op.take_result(), Some(4));
+        assert_eq!(op.take_result(), None);
+    }
+}
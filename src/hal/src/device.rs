@@ -12,7 +12,17 @@
 //! and is used to actually do things.
 
 use crate::{
-    buffer, display, format, image, memory,
+    acceleration_structure, buffer, command,
+    deferred::DeferredOperation,
+    display,
+    external_memory::{
+        ExternalMemoryError, ExternalMemoryHandle, ExternalMemoryHandleType,
+        ExternalMemoryHandleTypes,
+    },
+    external_sync::{
+        ExternalSyncError, ExternalSyncHandle, ExternalSyncHandleType, ExternalSyncHandleTypes,
+    },
+    format, image, memory,
     memory::{Requirements, Segment},
     pass,
     pool::CommandPoolCreateFlags,
@@ -126,6 +136,18 @@ pub enum BindError {
     OutOfBounds,
 }
 
+/// Error copying between host memory and an image, on the host.
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum HostCopyError {
+    /// Host image copies are not supported by the device.
+    #[error("Host image copies are not supported")]
+    Unsupported,
+    /// The contents of the image can't be accessed on the host,
+    /// e.g. because its format is emulated.
+    #[error("Image is not accessible on the host")]
+    Access,
+}
+
 /// Specifies the waiting targets.
 #[derive(Clone, Debug, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -167,6 +189,46 @@ pub struct NagaShader {
     pub info: naga::valid::ModuleInfo,
 }
 
+/// Opaque identifier of the contents of a shader module,
+/// which stays the same across runs of the application.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ShaderModuleIdentifier(pub Vec<u8>);
+
+/// Index of a physical device within a device group.
+pub type DeviceIndex = u32;
+
+/// Mask of the physical devices of a device group, with one bit per device index.
+pub type DeviceMask = u32;
+
+bitflags!(
+    /// Ways a device of a group can access the memory instantiated on another one.
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct PeerMemoryFeatures: u32 {
+        /// The memory can be the source of copy commands.
+        const COPY_SRC = 0x1;
+        /// The memory can be the destination of copy commands.
+        const COPY_DST = 0x2;
+        /// The memory can be read by any kind of access.
+        const GENERIC_SRC = 0x4;
+        /// The memory can be written by any kind of access.
+        const GENERIC_DST = 0x8;
+    }
+);
+
+/// An object that private data can be attached to.
+#[derive(Debug)]
+pub enum PrivateDataObject<'a, B: Backend> {
+    /// Buffer object.
+    Buffer(&'a B::Buffer),
+    /// Image object.
+    Image(&'a B::Image),
+    /// Graphics pipeline object.
+    GraphicsPipeline(&'a B::GraphicsPipeline),
+    /// Compute pipeline object.
+    ComputePipeline(&'a B::ComputePipeline),
+}
+
 /// Logical device handle, responsible for creating and managing resources
 /// for the physical device it was created from.
 ///
@@ -201,6 +263,53 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
         size: u64,
     ) -> Result<B::Memory, AllocationError>;
 
+    /// Allocate a memory segment of a specified type, which can be exported
+    /// to the `handle_types` with `export_memory`.
+    ///
+    /// Requires [`Features::EXTERNAL_MEMORY`][crate::Features::EXTERNAL_MEMORY].
+    unsafe fn allocate_exportable_memory(
+        &self,
+        memory_type: MemoryTypeId,
+        size: u64,
+        handle_types: ExternalMemoryHandleTypes,
+    ) -> Result<B::Memory, ExternalMemoryError> {
+        let _ = (memory_type, size);
+        Err(ExternalMemoryError::UnsupportedHandleTypes(handle_types))
+    }
+
+    /// Import a memory segment of a specified type from an external handle.
+    ///
+    /// The memory type has to be compatible with the memory behind the handle,
+    /// which is host visible for host pointers.
+    ///
+    /// Requires [`Features::EXTERNAL_MEMORY`][crate::Features::EXTERNAL_MEMORY].
+    unsafe fn import_external_memory(
+        &self,
+        memory_type: MemoryTypeId,
+        size: u64,
+        handle: ExternalMemoryHandle,
+    ) -> Result<B::Memory, ExternalMemoryError> {
+        let _ = (memory_type, size);
+        Err(ExternalMemoryError::UnsupportedHandleTypes(
+            handle.handle_type().into(),
+        ))
+    }
+
+    /// Export memory allocated with `allocate_exportable_memory` to a new handle
+    /// of the given type, which is owned by the caller.
+    ///
+    /// Requires [`Features::EXTERNAL_MEMORY`][crate::Features::EXTERNAL_MEMORY].
+    unsafe fn export_memory(
+        &self,
+        memory: &B::Memory,
+        handle_type: ExternalMemoryHandleType,
+    ) -> Result<ExternalMemoryHandle, ExternalMemoryError> {
+        let _ = memory;
+        Err(ExternalMemoryError::UnsupportedHandleTypes(
+            handle_type.into(),
+        ))
+    }
+
     /// Free device memory
     unsafe fn free_memory(&self, memory: B::Memory);
 
@@ -277,6 +386,17 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
         data: Option<&[u8]>,
     ) -> Result<B::PipelineCache, OutOfMemory>;
 
+    /// Create a pipeline cache object as a deferred operation, deserializing `data`
+    /// on the threads that join it.
+    ///
+    /// By default, the pipeline cache is created right away.
+    unsafe fn create_pipeline_cache_deferred(
+        &self,
+        data: Option<Vec<u8>>,
+    ) -> DeferredOperation<Result<B::PipelineCache, OutOfMemory>> {
+        DeferredOperation::ready(self.create_pipeline_cache(data.as_deref()))
+    }
+
     /// Retrieve data from pipeline cache object.
     unsafe fn get_pipeline_cache_data(
         &self,
@@ -310,12 +430,53 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
         cache: Option<&B::PipelineCache>,
     ) -> Result<B::GraphicsPipeline, pso::CreationError>;
 
+    /// Create a graphics pipeline as a deferred operation, compiling it
+    /// on the threads that join it.
+    ///
+    /// The description and the cache are only used by this call.
+    /// By default, the pipeline is created right away.
+    unsafe fn create_graphics_pipeline_deferred<'a>(
+        &self,
+        desc: &pso::GraphicsPipelineDesc<'a, B>,
+        cache: Option<&B::PipelineCache>,
+    ) -> DeferredOperation<Result<B::GraphicsPipeline, pso::CreationError>> {
+        DeferredOperation::ready(self.create_graphics_pipeline(desc, cache))
+    }
+
     /// Destroy a graphics pipeline.
     ///
     /// The graphics pipeline shouldn't be destroyed before any submitted command buffer,
     /// which references the graphics pipeline, has finished execution.
     unsafe fn destroy_graphics_pipeline(&self, pipeline: B::GraphicsPipeline);
 
+    /// Create a graphics pipeline library, holding the given parts of a pipeline,
+    /// to be linked into pipelines with `link_graphics_pipeline`.
+    ///
+    /// Only the parts of the description listed in `parts` are used by the linked pipelines,
+    /// the other parts may be used by the backend to compile the shaders ahead of time.
+    ///
+    /// Requires [`Features::GRAPHICS_PIPELINE_LIBRARY`][crate::Features::GRAPHICS_PIPELINE_LIBRARY].
+    unsafe fn create_graphics_pipeline_library<'a>(
+        &self,
+        desc: &pso::GraphicsPipelineDesc<'a, B>,
+        parts: pso::GraphicsPipelineLibraryParts,
+        cache: Option<&B::PipelineCache>,
+    ) -> Result<B::GraphicsPipelineLibrary, pso::CreationError>;
+
+    /// Link a graphics pipeline from pipeline libraries.
+    ///
+    /// Each part of the pipeline must be held by exactly one of the libraries.
+    unsafe fn link_graphics_pipeline<'a>(
+        &self,
+        desc: &pso::GraphicsPipelineLinkDesc<'a, B>,
+        cache: Option<&B::PipelineCache>,
+    ) -> Result<B::GraphicsPipeline, pso::CreationError>;
+
+    /// Destroy a graphics pipeline library.
+    ///
+    /// The pipelines linked from the library remain valid.
+    unsafe fn destroy_graphics_pipeline_library(&self, library: B::GraphicsPipelineLibrary);
+
     /// Create a compute pipeline.
     unsafe fn create_compute_pipeline<'a>(
         &self,
@@ -323,12 +484,71 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
         cache: Option<&B::PipelineCache>,
     ) -> Result<B::ComputePipeline, pso::CreationError>;
 
+    /// Create a compute pipeline as a deferred operation, compiling it
+    /// on the threads that join it.
+    ///
+    /// The description and the cache are only used by this call.
+    /// By default, the pipeline is created right away.
+    unsafe fn create_compute_pipeline_deferred<'a>(
+        &self,
+        desc: &pso::ComputePipelineDesc<'a, B>,
+        cache: Option<&B::PipelineCache>,
+    ) -> DeferredOperation<Result<B::ComputePipeline, pso::CreationError>> {
+        DeferredOperation::ready(self.create_compute_pipeline(desc, cache))
+    }
+
     /// Destroy a compute pipeline.
     ///
     /// The compute pipeline shouldn't be destroyed before any submitted command buffer,
     /// which references the compute pipeline, has finished execution.
     unsafe fn destroy_compute_pipeline(&self, pipeline: B::ComputePipeline);
 
+    /// Get the executables compiled for a graphics pipeline,
+    /// along with their statistics and internal representations.
+    ///
+    /// Requires [`Features::PIPELINE_EXECUTABLE_INFO`][crate::Features::PIPELINE_EXECUTABLE_INFO].
+    unsafe fn get_graphics_pipeline_executables(
+        &self,
+        pipeline: &B::GraphicsPipeline,
+    ) -> Vec<pso::PipelineExecutable>;
+
+    /// Get the executables compiled for a compute pipeline,
+    /// along with their statistics and internal representations.
+    ///
+    /// Requires [`Features::PIPELINE_EXECUTABLE_INFO`][crate::Features::PIPELINE_EXECUTABLE_INFO].
+    unsafe fn get_compute_pipeline_executables(
+        &self,
+        pipeline: &B::ComputePipeline,
+    ) -> Vec<pso::PipelineExecutable>;
+
+    /// Create a ray tracing pipeline.
+    ///
+    /// Requires [`Features::RAY_TRACING_PIPELINE`][crate::Features::RAY_TRACING_PIPELINE].
+    unsafe fn create_ray_tracing_pipeline<'a>(
+        &self,
+        desc: &pso::RayTracingPipelineDesc<'a, B>,
+        cache: Option<&B::PipelineCache>,
+    ) -> Result<B::RayTracingPipeline, pso::CreationError>;
+
+    /// Get the handles of `group_count` shader groups of a ray tracing pipeline,
+    /// starting at `first_group`.
+    ///
+    /// The handles are tightly packed, each of `RayTracingProperties::shader_group_handle_size`
+    /// bytes, and are meant to be copied into the records of the
+    /// [shader binding tables][crate::pso::ShaderBindingTable].
+    unsafe fn get_ray_tracing_shader_group_handles(
+        &self,
+        pipeline: &B::RayTracingPipeline,
+        first_group: u32,
+        group_count: u32,
+    ) -> Result<Vec<u8>, OutOfMemory>;
+
+    /// Destroy a ray tracing pipeline.
+    ///
+    /// The ray tracing pipeline shouldn't be destroyed before any submitted command buffer,
+    /// which references the ray tracing pipeline, has finished execution.
+    unsafe fn destroy_ray_tracing_pipeline(&self, pipeline: B::RayTracingPipeline);
+
     /// Create a new framebuffer object.
     ///
     /// # Safety
@@ -364,6 +584,31 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
         Err((ShaderError::Unsupported, shader))
     }
 
+    /// Get the identifier of a shader module, or `None` if it can't be identified.
+    ///
+    /// Requires [`Features::SHADER_MODULE_IDENTIFIER`][crate::Features::SHADER_MODULE_IDENTIFIER].
+    unsafe fn get_shader_module_identifier(
+        &self,
+        shader: &B::ShaderModule,
+    ) -> Option<ShaderModuleIdentifier> {
+        let _ = shader;
+        None
+    }
+
+    /// Create a new shader module from the identifier of a module, without its code.
+    ///
+    /// The module can only be used in pipelines created with a pipeline cache
+    /// that already holds its compiled shaders, the creation fails otherwise.
+    ///
+    /// Requires [`Features::SHADER_MODULE_IDENTIFIER`][crate::Features::SHADER_MODULE_IDENTIFIER].
+    unsafe fn create_shader_module_from_identifier(
+        &self,
+        identifier: &ShaderModuleIdentifier,
+    ) -> Result<B::ShaderModule, ShaderError> {
+        let _ = identifier;
+        Err(ShaderError::Unsupported)
+    }
+
     /// Destroy a shader module module
     ///
     /// A shader module can be destroyed while pipelines created using its shaders are still in use.
@@ -379,6 +624,24 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
         sparse: memory::SparseFlags,
     ) -> Result<B::Buffer, buffer::CreationError>;
 
+    /// Create a new buffer (unbound), which can be bound to memory imported from
+    /// or exported to the `handle_types`.
+    ///
+    /// Requires [`Features::EXTERNAL_MEMORY`][crate::Features::EXTERNAL_MEMORY].
+    unsafe fn create_external_buffer(
+        &self,
+        size: u64,
+        usage: buffer::Usage,
+        sparse: memory::SparseFlags,
+        handle_types: ExternalMemoryHandleTypes,
+    ) -> Result<B::Buffer, ExternalMemoryError> {
+        if handle_types.is_empty() {
+            Ok(self.create_buffer(size, usage, sparse)?)
+        } else {
+            Err(ExternalMemoryError::UnsupportedHandleTypes(handle_types))
+        }
+    }
+
     /// Get memory requirements for the buffer
     unsafe fn get_buffer_requirements(&self, buf: &B::Buffer) -> Requirements;
 
@@ -393,6 +656,23 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
         buf: &mut B::Buffer,
     ) -> Result<(), BindError>;
 
+    /// Get the address of a bound buffer on the device, which shaders can
+    /// dereference as a pointer to physical storage buffer memory.
+    ///
+    /// The buffer has to be created with [`buffer::Usage::SHADER_DEVICE_ADDRESS`].
+    /// The address stays valid until the buffer is destroyed.
+    ///
+    /// There is no feature to enable, the devices reporting
+    /// [`BufferDeviceAddressProperties::buffer_device_address`][crate::BufferDeviceAddressProperties::buffer_device_address]
+    /// always support it. Returns `None` on the other ones.
+    unsafe fn get_buffer_device_address(
+        &self,
+        buffer: &B::Buffer,
+    ) -> Option<buffer::DeviceAddress> {
+        let _ = buffer;
+        None
+    }
+
     /// Destroy a buffer.
     ///
     /// The buffer shouldn't be destroyed before any submitted command buffer,
@@ -410,6 +690,41 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
     /// Destroy a buffer view object
     unsafe fn destroy_buffer_view(&self, view: B::BufferView);
 
+    /// Get the memory required by an acceleration structure with the given geometries,
+    /// and by the scratch space of its builds.
+    ///
+    /// `max_primitive_counts` holds the maximum number of primitives
+    /// built for each of the geometries.
+    ///
+    /// Requires [`Features::ACCELERATION_STRUCTURE`][crate::Features::ACCELERATION_STRUCTURE].
+    unsafe fn get_acceleration_structure_build_requirements(
+        &self,
+        desc: &acceleration_structure::GeometryDesc<B>,
+        max_primitive_counts: &[u32],
+    ) -> acceleration_structure::SizeRequirements;
+
+    /// Create a new acceleration structure in a buffer range.
+    ///
+    /// The structure is empty until built with
+    /// [`build_acceleration_structure`][crate::command::CommandBuffer::build_acceleration_structure].
+    unsafe fn create_acceleration_structure(
+        &self,
+        desc: &acceleration_structure::CreateDesc<B>,
+    ) -> Result<B::AccelerationStructure, OutOfMemory>;
+
+    /// Get the device address of a bottom-level acceleration structure,
+    /// to be referenced by the instances of top-level structures.
+    unsafe fn get_acceleration_structure_address(
+        &self,
+        accel_struct: &B::AccelerationStructure,
+    ) -> acceleration_structure::DeviceAddress;
+
+    /// Destroy an acceleration structure.
+    ///
+    /// The acceleration structure shouldn't be destroyed before any submitted command buffer,
+    /// which references it, has finished execution.
+    unsafe fn destroy_acceleration_structure(&self, accel_struct: B::AccelerationStructure);
+
     //TODO: add a list of supported formats for casting the views
 
     /// Create a new image object
@@ -424,6 +739,51 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
         view_caps: image::ViewCapabilities,
     ) -> Result<B::Image, image::CreationError>;
 
+    /// Create a new image object (unbound), which can be bound to memory imported from
+    /// or exported to the `handle_types`.
+    ///
+    /// Requires [`Features::EXTERNAL_MEMORY`][crate::Features::EXTERNAL_MEMORY].
+    unsafe fn create_external_image(
+        &self,
+        kind: image::Kind,
+        mip_levels: image::Level,
+        format: format::Format,
+        tiling: image::Tiling,
+        usage: image::Usage,
+        sparse: memory::SparseFlags,
+        view_caps: image::ViewCapabilities,
+        handle_types: ExternalMemoryHandleTypes,
+    ) -> Result<B::Image, ExternalMemoryError> {
+        if handle_types.is_empty() {
+            Ok(self.create_image(kind, mip_levels, format, tiling, usage, sparse, view_caps)?)
+        } else {
+            Err(ExternalMemoryError::UnsupportedHandleTypes(handle_types))
+        }
+    }
+
+    /// Create a new image object (unbound), with control over its implicit compression.
+    ///
+    /// Disabling the compression is only supported when reported by
+    /// [`PhysicalDevice::image_compression_properties`][crate::adapter::PhysicalDevice::image_compression_properties].
+    unsafe fn create_image_with_compression(
+        &self,
+        kind: image::Kind,
+        mip_levels: image::Level,
+        format: format::Format,
+        tiling: image::Tiling,
+        usage: image::Usage,
+        sparse: memory::SparseFlags,
+        view_caps: image::ViewCapabilities,
+        compression: image::Compression,
+    ) -> Result<B::Image, image::CreationError> {
+        match compression {
+            image::Compression::Default => {
+                self.create_image(kind, mip_levels, format, tiling, usage, sparse, view_caps)
+            }
+            image::Compression::Disabled => Err(image::CreationError::Compression(compression)),
+        }
+    }
+
     /// Get memory requirements for the Image
     unsafe fn get_image_requirements(&self, image: &B::Image) -> Requirements;
 
@@ -434,6 +794,19 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
         subresource: image::Subresource,
     ) -> image::SubresourceFootprint;
 
+    /// Get the sparse memory requirements of an image created with
+    /// [`SparseFlags::SPARSE_RESIDENCY`][crate::memory::SparseFlags::SPARSE_RESIDENCY].
+    ///
+    /// Returns an empty list if the image isn't partially resident,
+    /// or the backend doesn't support sparse residency.
+    unsafe fn get_image_sparse_requirements(
+        &self,
+        image: &B::Image,
+    ) -> Vec<image::SparseRequirements> {
+        let _ = image;
+        Vec::new()
+    }
+
     /// Bind device memory to an image object
     unsafe fn bind_image_memory(
         &self,
@@ -442,6 +815,48 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
         image: &mut B::Image,
     ) -> Result<(), BindError>;
 
+    /// Copy regions of host memory into an image, on the host.
+    ///
+    /// The `buffer_offset` of each region is relative to the start of `data`.
+    /// The image must have the `HOST_TRANSFER` usage and be bound to memory, and the
+    /// regions must not be accessed by any command buffer while the copy is performed.
+    ///
+    /// Requires [`Features::HOST_IMAGE_COPY`][crate::Features::HOST_IMAGE_COPY],
+    /// returns `HostCopyError::Unsupported` otherwise.
+    unsafe fn copy_memory_to_image<T>(
+        &self,
+        data: &[u8],
+        image: &B::Image,
+        regions: T,
+    ) -> Result<(), HostCopyError>
+    where
+        T: Iterator<Item = command::BufferImageCopy>,
+    {
+        let _ = (data, image, regions);
+        Err(HostCopyError::Unsupported)
+    }
+
+    /// Copy regions of an image into host memory, on the host.
+    ///
+    /// The `buffer_offset` of each region is relative to the start of `data`.
+    /// The image must have the `HOST_TRANSFER` usage and be bound to memory, and the
+    /// regions must not be written by any command buffer while the copy is performed.
+    ///
+    /// Requires [`Features::HOST_IMAGE_COPY`][crate::Features::HOST_IMAGE_COPY],
+    /// returns `HostCopyError::Unsupported` otherwise.
+    unsafe fn copy_image_to_memory<T>(
+        &self,
+        image: &B::Image,
+        data: &mut [u8],
+        regions: T,
+    ) -> Result<(), HostCopyError>
+    where
+        T: Iterator<Item = command::BufferImageCopy>,
+    {
+        let _ = (image, data, regions);
+        Err(HostCopyError::Unsupported)
+    }
+
     /// Destroy an image.
     ///
     /// The image shouldn't be destroyed before any submitted command buffer,
@@ -545,6 +960,45 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
     /// Destroy a semaphore object.
     unsafe fn destroy_semaphore(&self, semaphore: B::Semaphore);
 
+    /// Create a new timeline semaphore object, with the counter starting at `initial_value`.
+    ///
+    /// Timeline semaphores hold a 64-bit counter that only increases. They are signalled
+    /// and waited for by value, either on the [queues][crate::queue::Queue::submit_timeline]
+    /// or on the host, so a single one can replace the semaphores and fences of many submissions.
+    ///
+    /// Requires [`Features::TIMELINE_SEMAPHORE`][crate::Features::TIMELINE_SEMAPHORE].
+    fn create_timeline_semaphore(
+        &self,
+        initial_value: u64,
+    ) -> Result<B::TimelineSemaphore, OutOfMemory>;
+
+    /// Destroy a timeline semaphore object.
+    unsafe fn destroy_timeline_semaphore(&self, semaphore: B::TimelineSemaphore);
+
+    /// Get the current counter value of a timeline semaphore.
+    unsafe fn get_timeline_semaphore_value(
+        &self,
+        semaphore: &B::TimelineSemaphore,
+    ) -> Result<u64, DeviceLost>;
+
+    /// Set the counter of a timeline semaphore to `value` from the host.
+    ///
+    /// The value has to be greater than the current one, as well as any pending signal operation.
+    unsafe fn signal_timeline_semaphore(
+        &self,
+        semaphore: &B::TimelineSemaphore,
+        value: u64,
+    ) -> Result<(), OutOfMemory>;
+
+    /// Blocks until the counter of a timeline semaphore reaches `value`.
+    /// Returns true if it did before the timeout.
+    unsafe fn wait_for_timeline_semaphore(
+        &self,
+        semaphore: &B::TimelineSemaphore,
+        value: u64,
+        timeout_ns: u64,
+    ) -> Result<bool, WaitError>;
+
     /// Create a new fence object.
     ///
     /// Fences are a synchronization primitive that **can** be used to insert a dependency from
@@ -632,6 +1086,100 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
     /// Destroy a fence object
     unsafe fn destroy_fence(&self, fence: B::Fence);
 
+    /// Create a new fence object, whose payload can be exported to the `handle_types`.
+    ///
+    /// Requires [`Features::EXTERNAL_SYNC`][crate::Features::EXTERNAL_SYNC].
+    fn create_exportable_fence(
+        &self,
+        signaled: bool,
+        handle_types: ExternalSyncHandleTypes,
+    ) -> Result<B::Fence, ExternalSyncError> {
+        if handle_types.is_empty() {
+            Ok(self.create_fence(signaled)?)
+        } else {
+            Err(ExternalSyncError::UnsupportedHandleTypes(handle_types))
+        }
+    }
+
+    /// Replace the payload of a fence with the one of an external handle.
+    ///
+    /// The fence must not be in use by a pending queue submission.
+    ///
+    /// Requires [`Features::EXTERNAL_SYNC`][crate::Features::EXTERNAL_SYNC].
+    unsafe fn import_fence(
+        &self,
+        fence: &mut B::Fence,
+        handle: ExternalSyncHandle,
+    ) -> Result<(), ExternalSyncError> {
+        let _ = fence;
+        Err(ExternalSyncError::UnsupportedHandleTypes(
+            handle.handle_type().into(),
+        ))
+    }
+
+    /// Export the payload of a fence created with `create_exportable_fence` to a new handle
+    /// of the given type, which is owned by the caller.
+    ///
+    /// Requires [`Features::EXTERNAL_SYNC`][crate::Features::EXTERNAL_SYNC].
+    unsafe fn export_fence(
+        &self,
+        fence: &B::Fence,
+        handle_type: ExternalSyncHandleType,
+    ) -> Result<ExternalSyncHandle, ExternalSyncError> {
+        let _ = fence;
+        Err(ExternalSyncError::UnsupportedHandleTypes(
+            handle_type.into(),
+        ))
+    }
+
+    /// Create a new semaphore object, whose payload can be exported to the `handle_types`.
+    ///
+    /// Requires [`Features::EXTERNAL_SYNC`][crate::Features::EXTERNAL_SYNC].
+    fn create_exportable_semaphore(
+        &self,
+        handle_types: ExternalSyncHandleTypes,
+    ) -> Result<B::Semaphore, ExternalSyncError> {
+        if handle_types.is_empty() {
+            Ok(self.create_semaphore()?)
+        } else {
+            Err(ExternalSyncError::UnsupportedHandleTypes(handle_types))
+        }
+    }
+
+    /// Replace the payload of a semaphore with the one of an external handle.
+    ///
+    /// The semaphore must not be in use by a pending queue submission.
+    ///
+    /// Requires [`Features::EXTERNAL_SYNC`][crate::Features::EXTERNAL_SYNC].
+    unsafe fn import_semaphore(
+        &self,
+        semaphore: &mut B::Semaphore,
+        handle: ExternalSyncHandle,
+    ) -> Result<(), ExternalSyncError> {
+        let _ = semaphore;
+        Err(ExternalSyncError::UnsupportedHandleTypes(
+            handle.handle_type().into(),
+        ))
+    }
+
+    /// Export the payload of a semaphore created with `create_exportable_semaphore`
+    /// to a new handle of the given type, which is owned by the caller.
+    ///
+    /// Exporting a sync fd requires a signal operation of the semaphore to be pending,
+    /// and unsignals the semaphore like a wait operation would.
+    ///
+    /// Requires [`Features::EXTERNAL_SYNC`][crate::Features::EXTERNAL_SYNC].
+    unsafe fn export_semaphore(
+        &self,
+        semaphore: &B::Semaphore,
+        handle_type: ExternalSyncHandleType,
+    ) -> Result<ExternalSyncHandle, ExternalSyncError> {
+        let _ = semaphore;
+        Err(ExternalSyncError::UnsupportedHandleTypes(
+            handle_type.into(),
+        ))
+    }
+
     /// Create an event object.
     fn create_event(&self) -> Result<B::Event, OutOfMemory>;
 
@@ -673,6 +1221,13 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
         flags: query::ResultFlags,
     ) -> Result<bool, WaitError>;
 
+    /// Reset a range of queries of the pool from the host, making them unavailable
+    /// without recording a `reset_query_pool` command.
+    ///
+    /// The queries must not be used by any pending command buffer.
+    /// Requires `Features::HOST_QUERY_RESET`.
+    unsafe fn reset_query_pool(&self, pool: &B::QueryPool, queries: Range<query::Id>);
+
     /// Wait for all queues associated with this device to idle.
     ///
     /// Host access to all queues needs to be **externally** sycnhronized!
@@ -714,6 +1269,31 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
     /// validation layers that can print a friendly name when referring to objects in error messages
     unsafe fn set_pipeline_layout_name(&self, pipeline_layout: &mut B::PipelineLayout, name: &str);
 
+    /// Create a private data slot, which holds a `u64` value for each object of the device,
+    /// so that tools layered on top can tag the objects without keeping their own maps.
+    ///
+    /// Requires `Features::PRIVATE_DATA`.
+    unsafe fn create_private_data_slot(&self) -> Result<B::PrivateDataSlot, OutOfMemory>;
+
+    /// Destroy a private data slot, discarding the values stored in it.
+    unsafe fn destroy_private_data_slot(&self, slot: B::PrivateDataSlot);
+
+    /// Store a value in the private data slot of an object.
+    unsafe fn set_private_data(
+        &self,
+        object: PrivateDataObject<B>,
+        slot: &B::PrivateDataSlot,
+        data: u64,
+    ) -> Result<(), OutOfMemory>;
+
+    /// Get the value stored in the private data slot of an object,
+    /// or zero if none was stored.
+    unsafe fn get_private_data(
+        &self,
+        object: PrivateDataObject<B>,
+        slot: &B::PrivateDataSlot,
+    ) -> u64;
+
     /// Control the power state of the provided display
     unsafe fn set_display_power_state(
         &self,
@@ -736,6 +1316,19 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
         fence: &mut B::Fence,
     ) -> Result<(), display::control::DisplayControlError>;
 
+    /// Get how the device `local_device` of the group can access the memory of the heap
+    /// `heap_index` instantiated on the device `remote_device`.
+    ///
+    /// Devices opened without peers are the only device of their group.
+    fn get_peer_memory_features(
+        &self,
+        _heap_index: usize,
+        _local_device: DeviceIndex,
+        _remote_device: DeviceIndex,
+    ) -> PeerMemoryFeatures {
+        PeerMemoryFeatures::empty()
+    }
+
     /// Starts frame capture.
     fn start_capture(&self);
 
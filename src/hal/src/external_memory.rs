@@ -0,0 +1,113 @@
+//! External memory.
+//!
+//! Memory can be shared with other APIs, processes or media frameworks through handles
+//! of the platform. Buffers and images using it are created with the handle types
+//! they may be bound to, which are queried with
+//! [`PhysicalDevice::external_buffer_properties`][crate::adapter::PhysicalDevice::external_buffer_properties]
+//! and [`PhysicalDevice::external_image_properties`][crate::adapter::PhysicalDevice::external_image_properties].
+//!
+//! Requires [`Features::EXTERNAL_MEMORY`][crate::Features::EXTERNAL_MEMORY].
+
+use crate::{buffer, device::OutOfMemory, image};
+
+use std::{
+    os::raw::{c_int, c_void},
+    ptr::NonNull,
+};
+
+/// Type of the handle through which memory is shared.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ExternalMemoryHandleType {
+    /// POSIX file descriptor, only meaningful to the same driver and device.
+    OpaqueFd,
+    /// Windows NT handle, only meaningful to the same driver and device.
+    OpaqueWin32,
+    /// Pointer to host memory, allocated by the application or another API.
+    HostPointer,
+    /// `IOSurfaceRef` of the Apple platforms.
+    IOSurface,
+}
+
+bitflags!(
+    /// Set of external memory handle types.
+    #[derive(Default)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct ExternalMemoryHandleTypes: u32 {
+        /// See [`ExternalMemoryHandleType::OpaqueFd`].
+        const OPAQUE_FD = 0x1;
+        /// See [`ExternalMemoryHandleType::OpaqueWin32`].
+        const OPAQUE_WIN32 = 0x2;
+        /// See [`ExternalMemoryHandleType::HostPointer`].
+        const HOST_POINTER = 0x4;
+        /// See [`ExternalMemoryHandleType::IOSurface`].
+        const IO_SURFACE = 0x8;
+    }
+);
+
+impl From<ExternalMemoryHandleType> for ExternalMemoryHandleTypes {
+    fn from(ty: ExternalMemoryHandleType) -> Self {
+        match ty {
+            ExternalMemoryHandleType::OpaqueFd => ExternalMemoryHandleTypes::OPAQUE_FD,
+            ExternalMemoryHandleType::OpaqueWin32 => ExternalMemoryHandleTypes::OPAQUE_WIN32,
+            ExternalMemoryHandleType::HostPointer => ExternalMemoryHandleTypes::HOST_POINTER,
+            ExternalMemoryHandleType::IOSurface => ExternalMemoryHandleTypes::IO_SURFACE,
+        }
+    }
+}
+
+/// Handle to external memory.
+#[derive(Debug)]
+pub enum ExternalMemoryHandle {
+    /// File descriptor, whose ownership is transferred to the device when imported.
+    OpaqueFd(c_int),
+    /// Windows NT handle, which stays owned by the application when imported.
+    OpaqueWin32(*mut c_void),
+    /// Pointer to host memory, aligned to the page size of the host. The memory has
+    /// to outlive the device memory it is imported as.
+    HostPointer(NonNull<u8>),
+    /// `IOSurfaceRef`, which is retained by the device memory it is imported as.
+    IOSurface(NonNull<c_void>),
+}
+
+impl ExternalMemoryHandle {
+    /// Get the type of the handle.
+    pub fn handle_type(&self) -> ExternalMemoryHandleType {
+        match *self {
+            ExternalMemoryHandle::OpaqueFd(_) => ExternalMemoryHandleType::OpaqueFd,
+            ExternalMemoryHandle::OpaqueWin32(_) => ExternalMemoryHandleType::OpaqueWin32,
+            ExternalMemoryHandle::HostPointer(_) => ExternalMemoryHandleType::HostPointer,
+            ExternalMemoryHandle::IOSurface(_) => ExternalMemoryHandleType::IOSurface,
+        }
+    }
+}
+
+/// Handle types that the memory of a kind of resource can be shared through.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ExternalMemoryProperties {
+    /// Types that memory allocated by the device can be exported to.
+    pub exportable: ExternalMemoryHandleTypes,
+    /// Types that memory can be imported from.
+    pub importable: ExternalMemoryHandleTypes,
+}
+
+/// Error creating, importing or exporting external memory and its resources.
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum ExternalMemoryError {
+    /// Out of either host or device memory.
+    #[error(transparent)]
+    OutOfMemory(#[from] OutOfMemory),
+    /// The handle types aren't supported for this resource or memory.
+    #[error("Unsupported handle types: {0:?}")]
+    UnsupportedHandleTypes(ExternalMemoryHandleTypes),
+    /// The handle doesn't refer to memory that can be imported.
+    #[error("Invalid external handle")]
+    InvalidHandle,
+    /// Creating the buffer failed.
+    #[error(transparent)]
+    Buffer(#[from] buffer::CreationError),
+    /// Creating the image failed.
+    #[error(transparent)]
+    Image(#[from] image::CreationError),
+}
@@ -0,0 +1,103 @@
+//! External synchronization.
+//!
+//! The payloads of fences and semaphores can be shared with other APIs and processes
+//! through handles of the platform. The objects are created with the handle types they
+//! may be exported to, and the supported types are queried with
+//! [`PhysicalDevice::external_fence_properties`][crate::adapter::PhysicalDevice::external_fence_properties]
+//! and [`PhysicalDevice::external_semaphore_properties`][crate::adapter::PhysicalDevice::external_semaphore_properties].
+//!
+//! Requires [`Features::EXTERNAL_SYNC`][crate::Features::EXTERNAL_SYNC].
+
+use crate::device::OutOfMemory;
+
+use std::{
+    os::raw::{c_int, c_void},
+    ptr::NonNull,
+};
+
+/// Type of the handle through which a synchronization payload is shared.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ExternalSyncHandleType {
+    /// Linux sync file descriptor, holding a snapshot of the payload.
+    ///
+    /// Importing it replaces the payload only until the object is next waited for,
+    /// or reset in the case of fences.
+    SyncFd,
+    /// Windows NT handle, only meaningful to the same driver and device.
+    OpaqueWin32,
+    /// `MTLSharedEvent` of Metal, which can be shared with other Metal devices
+    /// and processes.
+    MetalSharedEvent,
+}
+
+bitflags!(
+    /// Set of external synchronization handle types.
+    #[derive(Default)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct ExternalSyncHandleTypes: u32 {
+        /// See [`ExternalSyncHandleType::SyncFd`].
+        const SYNC_FD = 0x1;
+        /// See [`ExternalSyncHandleType::OpaqueWin32`].
+        const OPAQUE_WIN32 = 0x2;
+        /// See [`ExternalSyncHandleType::MetalSharedEvent`].
+        const METAL_SHARED_EVENT = 0x4;
+    }
+);
+
+impl From<ExternalSyncHandleType> for ExternalSyncHandleTypes {
+    fn from(ty: ExternalSyncHandleType) -> Self {
+        match ty {
+            ExternalSyncHandleType::SyncFd => ExternalSyncHandleTypes::SYNC_FD,
+            ExternalSyncHandleType::OpaqueWin32 => ExternalSyncHandleTypes::OPAQUE_WIN32,
+            ExternalSyncHandleType::MetalSharedEvent => ExternalSyncHandleTypes::METAL_SHARED_EVENT,
+        }
+    }
+}
+
+/// Handle to a synchronization payload.
+#[derive(Debug)]
+pub enum ExternalSyncHandle {
+    /// File descriptor, whose ownership is transferred to the device when imported.
+    /// A value of `-1` stands for an already signaled payload.
+    SyncFd(c_int),
+    /// Windows NT handle, which stays owned by the application when imported.
+    OpaqueWin32(*mut c_void),
+    /// `id<MTLSharedEvent>`, which is retained by the object it is imported into.
+    MetalSharedEvent(NonNull<c_void>),
+}
+
+impl ExternalSyncHandle {
+    /// Get the type of the handle.
+    pub fn handle_type(&self) -> ExternalSyncHandleType {
+        match *self {
+            ExternalSyncHandle::SyncFd(_) => ExternalSyncHandleType::SyncFd,
+            ExternalSyncHandle::OpaqueWin32(_) => ExternalSyncHandleType::OpaqueWin32,
+            ExternalSyncHandle::MetalSharedEvent(_) => ExternalSyncHandleType::MetalSharedEvent,
+        }
+    }
+}
+
+/// Handle types that the payload of a kind of synchronization object can be shared through.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ExternalSyncProperties {
+    /// Types that the payload can be exported to.
+    pub exportable: ExternalSyncHandleTypes,
+    /// Types that the payload can be imported from.
+    pub importable: ExternalSyncHandleTypes,
+}
+
+/// Error creating, importing or exporting external synchronization objects.
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum ExternalSyncError {
+    /// Out of either host or device memory.
+    #[error(transparent)]
+    OutOfMemory(#[from] OutOfMemory),
+    /// The handle types aren't supported for this kind of object.
+    #[error("Unsupported handle types: {0:?}")]
+    UnsupportedHandleTypes(ExternalSyncHandleTypes),
+    /// The handle doesn't refer to a payload that can be imported.
+    #[error("Invalid external handle")]
+    InvalidHandle,
+}
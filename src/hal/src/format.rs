@@ -24,6 +24,10 @@ bitflags!(
         const DEPTH = 0x2;
         /// Stencil aspect.
         const STENCIL = 0x4;
+        /// First plane of a multi-planar format.
+        const PLANE_0 = 0x10;
+        /// Second plane of a multi-planar format.
+        const PLANE_1 = 0x20;
     }
 );
 
@@ -329,6 +333,9 @@ surface_types! {
     ASTC_10x10          { 128, COLOR, (10, 10) },
     ASTC_12x10          { 128, COLOR, (12, 10) },
     ASTC_12x12          { 128, COLOR, (12, 12) },
+    // multi-planar 4:2:0 formats, with the average number of bits per texel
+    G8_B8R8_420         {  12, COLOR, (1, 1), color: 24 },
+    G10X6_B10X6R10X6_420 { 24, COLOR, (1, 1), color: 30 },
 }
 
 /// Generic run-time base format.
@@ -588,6 +595,9 @@ formats! {
     Astc12x10Srgb = (ASTC_12x10, Srgb),
     Astc12x12Unorm = (ASTC_12x12, Unorm),
     Astc12x12Srgb = (ASTC_12x12, Srgb),
+    // Multi-planar formats, which come from an extension range in Vulkan.
+    G8B8r8TwoPlane420Unorm = (G8_B8R8_420, Unorm),
+    G10x6B10x6r10x6TwoPlane420Unorm = (G10X6_B10X6R10X6_420, Unorm),
 }
 
 impl Format {
@@ -618,6 +628,19 @@ impl Format {
     pub fn is_stencil(self) -> bool {
         self.surface_desc().aspects.contains(Aspects::STENCIL)
     }
+
+    /// Formats of the planes of a multi-planar format, in order,
+    /// or an empty slice for the other formats.
+    ///
+    /// The first plane holds the luma samples. The second one holds the chroma samples,
+    /// interleaved, at half the width and height of the image (NV12 and P010 layouts).
+    pub fn planes(self) -> &'static [Format] {
+        match self {
+            Format::G8B8r8TwoPlane420Unorm => &[Format::R8Unorm, Format::Rg8Unorm],
+            Format::G10x6B10x6r10x6TwoPlane420Unorm => &[Format::R16Unorm, Format::Rg16Unorm],
+            _ => &[],
+        }
+    }
 }
 
 // Common vertex attribute formats
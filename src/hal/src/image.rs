@@ -102,6 +102,33 @@ pub enum Tiling {
     Linear = 1,
 }
 
+/// Control over the implicit, lossless compression of an image by the implementation.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Compression {
+    /// The implementation may compress the image whenever it's beneficial.
+    Default,
+    /// The image is never compressed, keeping its memory in the layout expected
+    /// for host access and for aliasing with other resources.
+    Disabled,
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Compression::Default
+    }
+}
+
+/// Implicit compression properties of images with a particular format and usage.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CompressionProperties {
+    /// The image may be compressed when created with [`Compression::Default`].
+    pub implicit: bool,
+    /// The image can be created with [`Compression::Disabled`].
+    pub can_disable: bool,
+}
+
 /// Pure image object creation error.
 #[derive(Clone, Debug, PartialEq, thiserror::Error)]
 pub enum CreationError {
@@ -126,6 +153,9 @@ pub enum CreationError {
     /// The mentioned usage mode is not supported
     #[error("Unsupported usage: {0:?}")]
     Usage(Usage),
+    /// The requested compression control is not supported.
+    #[error("Unsupported compression: {0:?}")]
+    Compression(Compression),
 }
 
 /// Error creating an `ImageView`.
@@ -361,7 +391,10 @@ bitflags!(
         const TRANSIENT_ATTACHMENT = 0x40;
         ///
         const INPUT_ATTACHMENT = 0x80;
-
+        /// The image is used as a fragment shading rate attachment.
+        const FRAGMENT_SHADING_RATE_ATTACHMENT = 0x100;
+        /// The image is copied to and from host memory by the device, without command buffers.
+        const HOST_TRANSFER = 0x40_0000;
     }
 );
 
@@ -477,6 +510,45 @@ impl Into<[f32; 4]> for BorderColor {
     }
 }
 
+/// Color model of the samples of a multi-planar image.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum YcbcrModel {
+    /// ITU-R BT.601, used by standard definition video.
+    Bt601,
+    /// ITU-R BT.709, used by high definition video.
+    Bt709,
+    /// ITU-R BT.2020, used by ultra high definition video.
+    Bt2020,
+}
+
+/// Range of the encoded values of a multi-planar image.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum YcbcrRange {
+    /// The values use the full range of the bit depth.
+    Full,
+    /// The values leave a headroom and a footroom, with luma in `16..=235` and
+    /// chroma in `16..=240` for 8 bits, scaled up for larger bit depths.
+    Narrow,
+}
+
+/// Conversion of the samples of a multi-planar image from YCbCr to RGB,
+/// performed when the image is sampled.
+///
+/// The chroma samples are taken at the midpoints between the luma samples,
+/// and are filtered with the filter of the sampler.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SamplerYcbcrConversion {
+    /// Multi-planar format of the sampled images.
+    pub format: format::Format,
+    /// Color model of the samples.
+    pub model: YcbcrModel,
+    /// Range of the encoded values.
+    pub range: YcbcrRange,
+}
+
 /// Specifies how to sample from an image.  These are all the parameters
 /// available that alter how the GPU goes from a coordinate in an image
 /// to producing an actual value from the texture, including filtering/
@@ -511,6 +583,13 @@ pub struct SamplerDesc {
     ///
     /// Can be `Some(_)` only if `Features::SAMPLER_ANISOTROPY` is enabled.
     pub anisotropy_clamp: Option<u8>,
+    /// Conversion of the samples of multi-planar images to RGB.
+    ///
+    /// Can be `Some(_)` only if `Features::SAMPLER_YCBCR_CONVERSION` is enabled.
+    /// Samplers with a conversion are only used as immutable samplers of combined
+    /// image sampler bindings, and each descriptor of these bindings takes
+    /// two images from the descriptor pool.
+    pub ycbcr_conversion: Option<SamplerYcbcrConversion>,
 }
 
 impl SamplerDesc {
@@ -529,6 +608,7 @@ impl SamplerDesc {
             border: BorderColor::TransparentBlack,
             normalized: true,
             anisotropy_clamp: None,
+            ycbcr_conversion: None,
         }
     }
 }
@@ -610,6 +690,8 @@ bitflags!(
         const MEMORY_READ = 0x8000;
         /// Write access for memory to be accessed by a non-specific entity.
         const MEMORY_WRITE = 0x10000;
+        /// Read access to a fragment shading rate attachment while rasterizing.
+        const FRAGMENT_SHADING_RATE_ATTACHMENT_READ = 0x80_0000;
     }
 );
 
@@ -716,6 +798,29 @@ pub struct SubresourceFootprint {
     pub depth_pitch: RawOffset,
 }
 
+/// Sparse memory requirements of the aspects of an image.
+///
+/// Subresource binds with [`bind_sparse`][crate::queue::Queue::bind_sparse] are aligned
+/// to the `granularity`, except for the mip tail, which is bound opaquely.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SparseRequirements {
+    /// Aspects that the requirements apply to.
+    pub aspects: format::Aspects,
+    /// Extent of the blocks of texels that are bound together.
+    pub granularity: Extent,
+    /// Whether all the layers share a single mip tail.
+    pub single_mip_tail: bool,
+    /// First mip level of the tail, or the number of levels if there is no tail.
+    pub mip_tail_first_level: Level,
+    /// Byte size of the mip tail, per layer unless `single_mip_tail` is set.
+    pub mip_tail_size: RawOffset,
+    /// Opaque offset of the mip tail of the first layer.
+    pub mip_tail_offset: RawOffset,
+    /// Opaque byte distance between the mip tails of consecutive layers.
+    pub mip_tail_stride: RawOffset,
+}
+
 /// The type of tile to check for with `get_tile_size`.
 #[derive(Debug)]
 pub enum TileKind {
@@ -48,11 +48,15 @@ extern crate serde;
 
 use std::{any::Any, fmt, hash::Hash};
 
+pub mod acceleration_structure;
 pub mod adapter;
 pub mod buffer;
 pub mod command;
+pub mod deferred;
 pub mod device;
 pub mod display;
+pub mod external_memory;
+pub mod external_sync;
 pub mod format;
 pub mod image;
 pub mod memory;
@@ -61,6 +65,7 @@ pub mod pool;
 pub mod pso;
 pub mod query;
 pub mod queue;
+pub mod stream;
 pub mod window;
 
 /// Prelude module re-exports all the traits necessary to use `gfx-hal`.
@@ -274,6 +279,14 @@ bitflags! {
         const MUTABLE_COMPARISON_SAMPLER = 0x0020 << 64;
         /// Can create non-normalized samplers in regular descriptor sets.
         const MUTABLE_UNNORMALIZED_SAMPLER = 0x0040 << 64;
+        /// Support resolving multisampled depth/stencil attachments at the end of a subpass.
+        const DEPTH_STENCIL_RESOLVE = 0x0080 << 64;
+        /// Support sampling multi-planar images with a YCbCr to RGB conversion,
+        /// with `image::SamplerDesc::ycbcr_conversion`.
+        const SAMPLER_YCBCR_CONVERSION = 0x0100 << 64;
+        /// Support enabling and disabling primitive restart with commands,
+        /// for the pipelines created with `pso::DynamicStates::PRIMITIVE_RESTART`.
+        const PRIMITIVE_RESTART_DYNAMIC_STATE = 0x0200 << 64;
 
         // Bits for WebGPU features
 
@@ -290,6 +303,89 @@ bitflags! {
         const MESH_SHADER_MASK = Features::TASK_SHADER.bits | Features::MESH_SHADER.bits;
         /// Support sampler min/max reduction mode.
         const SAMPLER_REDUCTION = 0x0004 << 96;
+        /// Support graphics pipelines with `PipelineCreationFlags::RASTER_ORDERED`.
+        const FRAGMENT_SHADER_INTERLOCK = 0x0008 << 96;
+        /// Support timeline semaphores, signalled and waited for by value on the queues and the host.
+        const TIMELINE_SEMAPHORE = 0x0010 << 96;
+        /// Support building acceleration structures.
+        const ACCELERATION_STRUCTURE = 0x0020 << 96;
+        /// Support ray tracing pipelines and tracing rays with them.
+        ///
+        /// Requires `Features::ACCELERATION_STRUCTURE`.
+        const RAY_TRACING_PIPELINE = 0x0040 << 96;
+        /// Mask for all the features associated with ray tracing.
+        const RAY_TRACING_MASK = Features::ACCELERATION_STRUCTURE.bits | Features::RAY_TRACING_PIPELINE.bits;
+        /// Support rendering without render pass and framebuffer objects,
+        /// with `begin_rendering` and `end_rendering`.
+        const DYNAMIC_RENDERING = 0x0080 << 96;
+        /// Support writing descriptors after binding their sets,
+        /// with `DescriptorBindingFlags::UPDATE_AFTER_BIND`.
+        const DESCRIPTOR_BINDING_UPDATE_AFTER_BIND = 0x0100 << 96;
+        /// Support leaving the unused descriptors of a set unwritten,
+        /// with `DescriptorBindingFlags::PARTIALLY_BOUND`.
+        const DESCRIPTOR_BINDING_PARTIALLY_BOUND = 0x0200 << 96;
+        /// Support allocating sets with a variable number of descriptors in their last binding,
+        /// with `DescriptorBindingFlags::VARIABLE_DESCRIPTOR_COUNT`.
+        const DESCRIPTOR_BINDING_VARIABLE_COUNT = 0x0400 << 96;
+        /// Support setting the culling, primitive topology, depth test and stencil operations
+        /// with commands, for the pipelines created with the corresponding `pso::DynamicStates`.
+        const EXTENDED_DYNAMIC_STATE = 0x0800 << 96;
+        /// Support setting the vertex buffer and attribute layout with commands,
+        /// for the pipelines created with `pso::DynamicStates::VERTEX_INPUT`.
+        const VERTEX_INPUT_DYNAMIC_STATE = 0x1000 << 96;
+        /// Support querying the executables of pipelines, with their statistics
+        /// and internal representations.
+        const PIPELINE_EXECUTABLE_INFO = 0x2000 << 96;
+        /// Support 16-bit types in storage buffers, uniform buffers and push constants.
+        const STORAGE_16BIT = 0x4000 << 96;
+        /// Support 8-bit types in storage buffers, uniform buffers and push constants.
+        const STORAGE_8BIT = 0x8000 << 96;
+        /// Support 16-bit floating point arithmetic in shaders.
+        const SHADER_FLOAT16 = 0x0001_0000 << 96;
+        /// Support 8-bit integer arithmetic in shaders.
+        const SHADER_INT8 = 0x0002_0000 << 96;
+        /// Support resetting query pools from the host with `Device::reset_query_pool`.
+        const HOST_QUERY_RESET = 0x0004_0000 << 96;
+        /// Support attaching private data to buffers, images and pipelines.
+        const PRIVATE_DATA = 0x0008_0000 << 96;
+        /// Support sampling hardware performance counters with queries.
+        const PERFORMANCE_COUNTER_QUERY = 0x0010_0000 << 96;
+        /// Support `DescriptorType::Mutable` bindings, which hold a descriptor
+        /// of any of the declared types.
+        const MUTABLE_DESCRIPTOR_TYPE = 0x0020_0000 << 96;
+        /// Support linking graphics pipelines from pipeline libraries.
+        const GRAPHICS_PIPELINE_LIBRARY = 0x0040_0000 << 96;
+        /// Support creating shader modules from identifiers, for pipelines
+        /// that are in a pipeline cache.
+        const SHADER_MODULE_IDENTIFIER = 0x0080_0000 << 96;
+        /// Support discarding commands depending on a predicate in a buffer,
+        /// with `CommandBuffer::begin_conditional_rendering`.
+        const CONDITIONAL_RENDERING = 0x0100_0000 << 96;
+        /// Support setting the fragment shading rate of draws,
+        /// with `CommandBuffer::set_fragment_shading_rate`.
+        const PIPELINE_FRAGMENT_SHADING_RATE = 0x0200_0000 << 96;
+        /// Support writing the fragment shading rate of primitives in shaders.
+        const PRIMITIVE_FRAGMENT_SHADING_RATE = 0x0400_0000 << 96;
+        /// Support reading the fragment shading rate from an attachment of the subpass.
+        const ATTACHMENT_FRAGMENT_SHADING_RATE = 0x0800_0000 << 96;
+        /// Support sharing memory with other APIs and processes, through the handle types
+        /// returned by `PhysicalDevice::external_buffer_properties` and
+        /// `PhysicalDevice::external_image_properties`.
+        const EXTERNAL_MEMORY = 0x1000_0000 << 96;
+        /// Support sharing the payloads of fences and semaphores with other APIs and processes,
+        /// through the handle types returned by `PhysicalDevice::external_fence_properties`
+        /// and `PhysicalDevice::external_semaphore_properties`.
+        const EXTERNAL_SYNC = 0x2000_0000 << 96;
+        /// Support copying between host memory and images without command buffers,
+        /// with `Device::copy_memory_to_image` and `Device::copy_image_to_memory`.
+        const HOST_IMAGE_COPY = 0x4000_0000 << 96;
+        /// Support taking the flat-shaded attributes of the primitives from their last vertex,
+        /// with `pso::ProvokingVertex::Last`.
+        const PROVOKING_VERTEX_LAST = 0x8000_0000 << 96;
+        /// Mask for all the features associated with the fragment shading rate.
+        const FRAGMENT_SHADING_RATE_MASK = Features::PIPELINE_FRAGMENT_SHADING_RATE.bits | Features::PRIMITIVE_FRAGMENT_SHADING_RATE.bits | Features::ATTACHMENT_FRAGMENT_SHADING_RATE.bits;
+        /// Mask for all the features associated with narrow types in shaders.
+        const NARROW_TYPES_MASK = Features::STORAGE_16BIT.bits | Features::STORAGE_8BIT.bits | Features::SHADER_FLOAT16.bits | Features::SHADER_INT8.bits;
     }
 }
 
@@ -330,6 +426,30 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// Categories of subgroup operations supported in shaders.
+    #[derive(Default)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct SubgroupOperations: u32 {
+        /// Electing an invocation and querying the subgroup size and invocation index.
+        const BASIC = 0x0001;
+        /// Evaluating a condition across the invocations of the subgroup.
+        const VOTE = 0x0002;
+        /// Reductions and scans, like sums, minimums and maximums.
+        const ARITHMETIC = 0x0004;
+        /// Gathering a condition from the invocations into a bit mask.
+        const BALLOT = 0x0008;
+        /// Reading a value from an arbitrary invocation.
+        const SHUFFLE = 0x0010;
+        /// Reading a value from an invocation at a relative index.
+        const SHUFFLE_RELATIVE = 0x0020;
+        /// Operations on clusters of invocations within the subgroup.
+        const CLUSTERED = 0x0040;
+        /// Exchanging values within quads of invocations.
+        const QUAD = 0x0080;
+    }
+}
+
 /// Properties of physical devices that are exposed but do not need to be explicitly opted into.
 ///
 /// This contains things like resource limits, alignment requirements, and finer-grained feature
@@ -343,6 +463,8 @@ pub struct PhysicalDeviceProperties {
     pub descriptor_indexing: DescriptorIndexingProperties,
     /// Mesh Shader properties.
     pub mesh_shader: MeshShaderProperties,
+    /// Ray tracing properties.
+    pub ray_tracing: RayTracingProperties,
     /// Sampler reduction modes.
     pub sampler_reduction: SamplerReductionProperties,
     /// Downlevel properties.
@@ -351,6 +473,20 @@ pub struct PhysicalDeviceProperties {
     pub performance_caveats: PerformanceCaveats,
     /// Dynamic pipeline states.
     pub dynamic_pipeline_states: DynamicStates,
+    /// Subgroup properties.
+    pub subgroup: SubgroupProperties,
+    /// Robustness properties.
+    pub robustness: RobustnessProperties,
+    /// Programmable sample locations properties.
+    pub sample_locations: SampleLocationsProperties,
+    /// Line rasterization properties.
+    pub line_rasterization: LineRasterizationProperties,
+    /// Depth bias representation properties.
+    pub depth_bias_control: DepthBiasControlProperties,
+    /// Buffer device address properties.
+    pub buffer_device_address: BufferDeviceAddressProperties,
+    /// Protected memory properties.
+    pub protected_memory: ProtectedMemoryProperties,
 }
 
 ///
@@ -432,6 +568,8 @@ pub struct Limits {
     pub max_vertex_input_binding_stride: usize,
     /// Maximum number of components of output variables which can be output by a vertex shader.
     pub max_vertex_output_components: usize,
+    /// Maximum number of clip distances that can be written by a vertex shader.
+    pub max_clip_distances: u32,
 
     /// Maximum number of vertices for each patch.
     pub max_patch_size: pso::PatchSize,
@@ -576,6 +714,132 @@ pub struct MeshShaderProperties {
     pub mesh_output_per_primitive_granularity: u32,
 }
 
+/// Resource limits related to ray tracing.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RayTracingProperties {
+    /// The size of a shader group handle, at the start of the shader binding table records.
+    pub shader_group_handle_size: u32,
+    /// The required alignment of the shader binding table records.
+    pub shader_group_handle_alignment: u32,
+    /// The required alignment of the start of the shader binding tables.
+    pub shader_group_base_alignment: u32,
+    /// The maximum stride between the shader binding table records.
+    pub max_shader_group_stride: u32,
+    /// The maximum recursion depth of the traced rays.
+    pub max_ray_recursion_depth: u32,
+    /// The maximum number of geometries in a bottom-level acceleration structure.
+    pub max_geometry_count: u64,
+    /// The maximum number of instances in a top-level acceleration structure.
+    pub max_instance_count: u64,
+    /// The maximum number of triangles or bounding boxes in a bottom-level acceleration structure.
+    pub max_primitive_count: u64,
+    /// The required alignment of the scratch space of acceleration structure builds.
+    pub min_acceleration_structure_scratch_offset_alignment: u32,
+}
+
+/// Properties of the subgroups, the sets of invocations that a device executes together,
+/// also known as SIMD-groups or waves.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SubgroupProperties {
+    /// The number of invocations in a subgroup.
+    pub size: u32,
+    /// The shader stages supporting subgroup operations.
+    pub supported_stages: pso::ShaderStageFlags,
+    /// The subgroup operations supported in these stages.
+    pub supported_operations: SubgroupOperations,
+    /// Whether quad operations are supported in all the stages,
+    /// instead of only the fragment and compute ones.
+    pub quad_operations_in_all_stages: bool,
+}
+
+/// Stricter robustness guarantees of the device.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RobustnessProperties {
+    /// Whether buffer accesses are checked against the exact range of the descriptor
+    /// when [`Features::ROBUST_BUFFER_ACCESS`] is enabled, with out-of-range reads
+    /// returning zero and out-of-range writes being discarded.
+    pub robust_buffer_access_2: bool,
+    /// Whether descriptors can be written with [`Descriptor::Null`][pso::Descriptor::Null],
+    /// which read as zero and discard writes.
+    pub null_descriptor: bool,
+}
+
+/// Support for the line rasterization modes and stippling,
+/// see [`Rasterizer`][pso::Rasterizer].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LineRasterizationProperties {
+    /// Support for [`LineRasterizationMode::Rectangular`][pso::LineRasterizationMode::Rectangular].
+    pub rectangular_lines: bool,
+    /// Support for [`LineRasterizationMode::Bresenham`][pso::LineRasterizationMode::Bresenham].
+    pub bresenham_lines: bool,
+    /// Support for [`LineRasterizationMode::RectangularSmooth`][pso::LineRasterizationMode::RectangularSmooth].
+    pub smooth_lines: bool,
+    /// Support for stippling rectangular lines.
+    pub stippled_rectangular_lines: bool,
+    /// Support for stippling Bresenham lines.
+    pub stippled_bresenham_lines: bool,
+    /// Support for stippling smooth lines.
+    pub stippled_smooth_lines: bool,
+    /// Support for stippling lines of the default mode.
+    pub stippled_default_lines: bool,
+    /// Whether lines of the default mode are rasterized as rectangles,
+    /// instead of parallelograms that may be narrower than the line width.
+    pub strict_lines: bool,
+}
+
+/// Support for controlling the representation of the depth bias,
+/// see [`DepthBias`][pso::DepthBias].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DepthBiasControlProperties {
+    /// Support for [`DepthBiasRepresentation::ForceUnorm`][pso::DepthBiasRepresentation::ForceUnorm].
+    pub force_unorm_representation: bool,
+    /// Support for [`DepthBiasRepresentation::Float`][pso::DepthBiasRepresentation::Float].
+    pub float_representation: bool,
+    /// Support for scaling the constant factor exactly.
+    pub exact: bool,
+}
+
+/// Support for getting the device addresses of buffers and dereferencing them in shaders,
+/// see [`Device::get_buffer_device_address`][device::Device::get_buffer_device_address].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BufferDeviceAddressProperties {
+    /// Support for getting the device addresses of buffers.
+    pub buffer_device_address: bool,
+    /// Support for shaders accessing memory through the `PhysicalStorageBuffer`
+    /// pointers of SPIR-V.
+    pub shader_pointers: bool,
+}
+
+/// Support for protected content, which the host and unprotected work can't read.
+///
+/// Protected resources are created with [`SparseFlags::PROTECTED`][memory::SparseFlags::PROTECTED],
+/// bound to [`Properties::PROTECTED`][memory::Properties::PROTECTED] memory, and only
+/// accessed by [protected submissions][queue::SubmitFlags::PROTECTED] to the queues
+/// opened with [`PhysicalDevice::open_protected`][adapter::PhysicalDevice::open_protected].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ProtectedMemoryProperties {
+    /// Support for protected memory, queues and submissions.
+    pub protected_memory: bool,
+}
+
+/// Support for programming the locations of the samples in a pixel.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SampleLocationsProperties {
+    /// Mask of the sample counts that the locations can be set for,
+    /// empty if programmable sample locations are not supported.
+    pub sample_counts: image::NumSamples,
+    /// Number of bits of sub-pixel precision of the locations.
+    pub sub_pixel_bits: u32,
+}
+
 /// Resource limits related to the reduction samplers.
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -602,6 +866,9 @@ pub struct DownlevelProperties {
     pub device_local_image_copies: bool,
     /// Supports textures with mipmaps which are non power of two.
     pub non_power_of_two_mipmapped_textures: bool,
+    /// Supports framebuffers that only describe their attachments,
+    /// with the image views provided at the beginning of a render pass.
+    pub imageless_framebuffers: bool,
 }
 
 impl DownlevelProperties {
@@ -614,6 +881,7 @@ impl DownlevelProperties {
             read_only_depth_stencil: true,
             device_local_image_copies: true,
             non_power_of_two_mipmapped_textures: true,
+            imageless_framebuffers: true,
         }
     }
 }
@@ -694,6 +962,19 @@ pub trait Instance<B: Backend>: Any + Send + Sync + Sized {
     /// Return all available [graphics adapters][adapter::Adapter].
     fn enumerate_adapters(&self) -> Vec<adapter::Adapter<B>>;
 
+    /// Return the available [device groups][adapter::DeviceGroup], which cover all the adapters.
+    ///
+    /// Backends without multi-GPU support put each adapter in its own group.
+    fn enumerate_device_groups(&self) -> Vec<adapter::DeviceGroup<B>> {
+        self.enumerate_adapters()
+            .into_iter()
+            .map(|adapter| adapter::DeviceGroup {
+                adapters: vec![adapter],
+                subset_allocation: false,
+            })
+            .collect()
+    }
+
     /// Create a new [surface][window::Surface].
     ///
     /// Surfaces can be used to render to windows.
@@ -796,11 +1077,17 @@ pub trait Backend: 'static + Sized + Eq + Clone + Hash + fmt::Debug + Any + Send
     type ImageView: fmt::Debug + Any + Send + Sync;
     /// The corresponding sampler type for this backend.
     type Sampler: fmt::Debug + Any + Send + Sync;
+    /// The corresponding acceleration structure type for this backend.
+    type AccelerationStructure: fmt::Debug + Any + Send + Sync;
 
     /// The corresponding compute pipeline type for this backend.
     type ComputePipeline: fmt::Debug + Any + Send + Sync;
+    /// The corresponding ray tracing pipeline type for this backend.
+    type RayTracingPipeline: fmt::Debug + Any + Send + Sync;
     /// The corresponding graphics pipeline type for this backend.
     type GraphicsPipeline: fmt::Debug + Any + Send + Sync;
+    /// The corresponding graphics pipeline library type for this backend.
+    type GraphicsPipelineLibrary: fmt::Debug + Any + Send + Sync;
     /// The corresponding pipeline cache type for this backend.
     type PipelineCache: fmt::Debug + Any + Send + Sync;
     /// The corresponding pipeline layout type for this backend.
@@ -816,10 +1103,14 @@ pub trait Backend: 'static + Sized + Eq + Clone + Hash + fmt::Debug + Any + Send
     type Fence: fmt::Debug + Any + Send + Sync;
     /// The corresponding semaphore type for this backend.
     type Semaphore: fmt::Debug + Any + Send + Sync;
+    /// The corresponding timeline semaphore type for this backend.
+    type TimelineSemaphore: fmt::Debug + Any + Send + Sync;
     /// The corresponding event type for this backend.
     type Event: fmt::Debug + Any + Send + Sync;
     /// The corresponding query pool type for this backend.
     type QueryPool: fmt::Debug + Any + Send + Sync;
+    /// The corresponding private data slot type for this backend.
+    type PrivateDataSlot: fmt::Debug + Any + Send + Sync;
     /// The corresponding display type for this backend.
     type Display: fmt::Debug + Any + Send + Sync;
     /// The corresponding display mode type for this backend
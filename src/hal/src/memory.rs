@@ -26,6 +26,10 @@ bitflags!(
         /// Memory that may be lazily allocated as needed on the GPU
         /// and *must not* be visible to the CPU.
         const LAZILY_ALLOCATED = 0x10;
+
+        /// Memory that only protected resources can be bound to,
+        /// and *must not* be visible to the CPU.
+        const PROTECTED = 0x20;
     }
 );
 
@@ -166,7 +170,7 @@ pub struct SparseImageBind<M> {
 }
 
 bitflags!(
-    /// Sparse flags for creating images and buffers.
+    /// Sparse and protection flags for creating images and buffers.
     #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
     pub struct SparseFlags: u32 {
         /// Specifies the view will be backed using sparse memory binding.
@@ -177,5 +181,8 @@ bitflags!(
         /// Specifies the view will be backed using sparse memory binding with memory bindings that
         /// might alias other data. Must have `SPARSE_BINDING` enabled.
         const SPARSE_ALIASED = 0x0000_0004;
+        /// Specifies the view will be backed by protected memory.
+        /// Can't be combined with the sparse flags.
+        const PROTECTED = 0x0000_0008;
     }
 );
@@ -8,7 +8,9 @@
 //!
 //! and describes how the attachments are used over the course of the subpasses.
 
-use crate::{format::Format, image, memory::Dependencies, pso::PipelineStage, Backend};
+use crate::{
+    format::Format, image, memory::Dependencies, pso::PipelineStage, window::Extent2D, Backend,
+};
 use std::ops::Range;
 
 /// Specifies the operation to be used when reading data from a subpass attachment.
@@ -146,6 +148,49 @@ pub struct SubpassDependency {
     pub flags: Dependencies,
 }
 
+/// Operation used to combine the samples of a depth or stencil attachment
+/// into a single value when resolving it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ResolveMode {
+    /// Take the value of the sample zero.
+    SampleZero,
+    /// Take the minimum of all the sample values.
+    Min,
+    /// Take the maximum of all the sample values.
+    Max,
+}
+
+/// Resolve of the depth/stencil attachment of a subpass.
+///
+/// Requires [`Features::DEPTH_STENCIL_RESOLVE`][crate::Features::DEPTH_STENCIL_RESOLVE].
+#[derive(Clone, Debug, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DepthStencilResolve {
+    /// Attachment receiving the resolved values. It must not be multisampled.
+    pub attachment: AttachmentRef,
+    /// How the depth aspect is resolved, `None` leaves it untouched.
+    pub depth_mode: Option<ResolveMode>,
+    /// How the stencil aspect is resolved, `None` leaves it untouched.
+    pub stencil_mode: Option<ResolveMode>,
+}
+
+/// Attachment from which the fragment shading rate of a subpass is read.
+///
+/// Each texel of the attachment, which has a `R8Uint` format and has to be in the
+/// `General` layout, holds the rate of `texel_size` pixels of the render area,
+/// encoded as `(log2(width) << 2) | log2(height)`.
+///
+/// Requires [`Features::ATTACHMENT_FRAGMENT_SHADING_RATE`][crate::Features::ATTACHMENT_FRAGMENT_SHADING_RATE].
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ShadingRateAttachment {
+    /// Attachment holding the rates.
+    pub attachment: AttachmentRef,
+    /// Size in pixels of the area covered by each texel.
+    pub texel_size: Extent2D,
+}
+
 /// Description of a subpass for render pass creation.
 #[derive(Clone, Debug)]
 pub struct SubpassDesc<'a> {
@@ -164,6 +209,10 @@ pub struct SubpassDesc<'a> {
     ///
     /// The resolve attachment must not be multisampled.
     pub resolves: &'a [AttachmentRef],
+    /// Where the depth/stencil attachment is resolved to at the end of the subpass.
+    pub depth_stencil_resolve: Option<&'a DepthStencilResolve>,
+    /// Where the fragment shading rate of the subpass is read from.
+    pub shading_rate: Option<&'a ShadingRateAttachment>,
     /// Attachments that are not used by the subpass but must be preserved to be
     /// passed on to subsequent passes.
     pub preserves: &'a [AttachmentId],
@@ -13,6 +13,9 @@ bitflags!(
         const TRANSIENT = 0x1;
         /// Allow command buffers to be reset individually.
         const RESET_INDIVIDUAL = 0x2;
+        /// Allocate protected command buffers, which can only be submitted
+        /// with [`SubmitFlags::PROTECTED`][crate::queue::SubmitFlags::PROTECTED].
+        const PROTECTED = 0x4;
     }
 );
 
@@ -94,6 +94,93 @@ pub enum DescriptorType {
     },
     /// A descriptor associated with an input attachment.
     InputAttachment,
+    /// A descriptor associated with a top-level acceleration structure.
+    AccelerationStructure,
+    /// A descriptor of any of the listed types, chosen when it's written
+    /// with `Descriptor::Mutable`.
+    ///
+    /// Requires [`Features::MUTABLE_DESCRIPTOR_TYPE`][crate::Features::MUTABLE_DESCRIPTOR_TYPE].
+    Mutable {
+        /// The types the descriptor can take.
+        types: MutableDescriptorTypes,
+    },
+}
+
+bitflags! {
+    /// Types that a `DescriptorType::Mutable` descriptor can take.
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct MutableDescriptorTypes: u32 {
+        /// A sampled image, without a sampler.
+        const SAMPLED_IMAGE = 0x1;
+        /// A writable storage image.
+        const STORAGE_IMAGE = 0x2;
+        /// A uniform texel buffer.
+        const UNIFORM_TEXEL_BUFFER = 0x4;
+        /// A writable storage texel buffer.
+        const STORAGE_TEXEL_BUFFER = 0x8;
+        /// A uniform buffer, without a dynamic offset.
+        const UNIFORM_BUFFER = 0x10;
+        /// A writable storage buffer, without a dynamic offset.
+        const STORAGE_BUFFER = 0x20;
+    }
+}
+
+impl MutableDescriptorTypes {
+    /// Iterate over the concrete descriptor types of the set.
+    pub fn descriptor_types(self) -> impl Iterator<Item = DescriptorType> {
+        const TYPES: [(MutableDescriptorTypes, DescriptorType); 6] = [
+            (
+                MutableDescriptorTypes::SAMPLED_IMAGE,
+                DescriptorType::Image {
+                    ty: ImageDescriptorType::Sampled {
+                        with_sampler: false,
+                    },
+                },
+            ),
+            (
+                MutableDescriptorTypes::STORAGE_IMAGE,
+                DescriptorType::Image {
+                    ty: ImageDescriptorType::Storage { read_only: false },
+                },
+            ),
+            (
+                MutableDescriptorTypes::UNIFORM_TEXEL_BUFFER,
+                DescriptorType::Buffer {
+                    ty: BufferDescriptorType::Uniform,
+                    format: BufferDescriptorFormat::Texel,
+                },
+            ),
+            (
+                MutableDescriptorTypes::STORAGE_TEXEL_BUFFER,
+                DescriptorType::Buffer {
+                    ty: BufferDescriptorType::Storage { read_only: false },
+                    format: BufferDescriptorFormat::Texel,
+                },
+            ),
+            (
+                MutableDescriptorTypes::UNIFORM_BUFFER,
+                DescriptorType::Buffer {
+                    ty: BufferDescriptorType::Uniform,
+                    format: BufferDescriptorFormat::Structured {
+                        dynamic_offset: false,
+                    },
+                },
+            ),
+            (
+                MutableDescriptorTypes::STORAGE_BUFFER,
+                DescriptorType::Buffer {
+                    ty: BufferDescriptorType::Storage { read_only: false },
+                    format: BufferDescriptorFormat::Structured {
+                        dynamic_offset: false,
+                    },
+                },
+            ),
+        ];
+        TYPES
+            .iter()
+            .filter(move |&&(flag, _)| self.contains(flag))
+            .map(|&(_, ty)| ty)
+    }
 }
 
 /// Information about the contents of and in which stages descriptors may be bound to a descriptor
@@ -124,6 +211,35 @@ pub struct DescriptorSetLayoutBinding {
     pub stage_flags: ShaderStageFlags,
     /// Use the associated list of immutable samplers.
     pub immutable_samplers: bool,
+    /// Options of the binding.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub flags: DescriptorBindingFlags,
+}
+
+bitflags! {
+    /// Options of a descriptor set layout binding, used by bindless renderers
+    /// to manage large arrays of descriptors.
+    #[derive(Default)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct DescriptorBindingFlags: u32 {
+        /// The descriptors of the binding can be written after the set is bound,
+        /// until the command buffers using it are submitted.
+        ///
+        /// Requires [`Features::DESCRIPTOR_BINDING_UPDATE_AFTER_BIND`][crate::Features::DESCRIPTOR_BINDING_UPDATE_AFTER_BIND],
+        /// and a pool created with `DescriptorPoolCreateFlags::UPDATE_AFTER_BIND`.
+        const UPDATE_AFTER_BIND = 0x1;
+        /// The descriptors of the binding that are not accessed by the shaders
+        /// don't need to be written.
+        ///
+        /// Requires [`Features::DESCRIPTOR_BINDING_PARTIALLY_BOUND`][crate::Features::DESCRIPTOR_BINDING_PARTIALLY_BOUND].
+        const PARTIALLY_BOUND = 0x2;
+        /// The number of descriptors of the binding is given when allocating the set,
+        /// up to the `count` of the binding.
+        ///
+        /// Only the binding with the highest number of a layout can have a variable count.
+        /// Requires [`Features::DESCRIPTOR_BINDING_VARIABLE_COUNT`][crate::Features::DESCRIPTOR_BINDING_VARIABLE_COUNT].
+        const VARIABLE_DESCRIPTOR_COUNT = 0x4;
+    }
 }
 
 /// Set of descriptors of a specific type.
@@ -178,6 +294,19 @@ pub trait DescriptorPool<B: Backend>: Send + Sync + fmt::Debug {
         Ok(result.0.unwrap())
     }
 
+    /// Allocate a descriptor set from the pool, with `variable_count` descriptors in the binding
+    /// of the layout that has `DescriptorBindingFlags::VARIABLE_DESCRIPTOR_COUNT`.
+    ///
+    /// By default, the set is allocated with the full count of the binding.
+    unsafe fn allocate_one_with_variable_count(
+        &mut self,
+        layout: &B::DescriptorSetLayout,
+        variable_count: DescriptorArrayIndex,
+    ) -> Result<B::DescriptorSet, AllocationError> {
+        let _ = variable_count;
+        self.allocate_one(layout)
+    }
+
     /// Allocate multiple descriptor sets from the pool.
     ///
     /// The descriptor set will be allocated from the pool according to the corresponding set layout. However,
@@ -240,6 +369,14 @@ where
 /// A handle to a specific shader resource that can be bound for use in a `DescriptorSet`.
 /// Usually provided in a [`DescriptorSetWrite`]
 ///
+/// Descriptors of `DescriptorType::Mutable` bindings are written with `Descriptor::Mutable`,
+/// which gives the type they take, one of the types of the binding, along with the
+/// descriptor itself. The inner descriptor can't be a sampler or another mutable descriptor.
+///
+/// `Descriptor::Null` leaves a descriptor of the given type unbound, reading as zero in the
+/// shaders. It requires [`RobustnessProperties::null_descriptor`][crate::RobustnessProperties::null_descriptor],
+/// and can't be of the sampler or combined image sampler types.
+///
 /// [`DescriptorSetWrite`]: struct.DescriptorSetWrite.html
 #[allow(missing_docs)]
 #[derive(Clone, Debug)]
@@ -249,6 +386,9 @@ pub enum Descriptor<'a, B: Backend> {
     CombinedImageSampler(&'a B::ImageView, Layout, &'a B::Sampler),
     Buffer(&'a B::Buffer, SubRange),
     TexelBuffer(&'a B::BufferView),
+    AccelerationStructure(&'a B::AccelerationStructure),
+    Mutable(DescriptorType, &'a Descriptor<'a, B>),
+    Null(DescriptorType),
 }
 
 /// Copies a range of descriptors to be bound from one descriptor set to another.
@@ -288,5 +428,8 @@ bitflags! {
         /// Specifies that descriptor sets are allowed to be freed from the pool
         /// individually.
         const FREE_DESCRIPTOR_SET = 0x1;
+        /// Specifies that the pool can allocate sets with layouts having
+        /// `DescriptorBindingFlags::UPDATE_AFTER_BIND` bindings.
+        const UPDATE_AFTER_BIND = 0x2;
     }
 }
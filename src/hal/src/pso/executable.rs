@@ -0,0 +1,60 @@
+//! Pipeline executable properties, for inspecting how pipelines were compiled.
+
+use crate::pso::ShaderStageFlags;
+
+/// Value of a pipeline executable statistic.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ExecutableStatisticValue {
+    /// A boolean value.
+    Bool(bool),
+    /// A signed integer value.
+    Int(i64),
+    /// An unsigned integer value, like a register or instruction count.
+    UInt(u64),
+    /// A floating point value.
+    Float(f64),
+}
+
+/// A statistic reported by the backend for a pipeline executable.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ExecutableStatistic {
+    /// Short name of the statistic.
+    pub name: String,
+    /// Description of what the statistic measures.
+    pub description: String,
+    /// The value of the statistic.
+    pub value: ExecutableStatisticValue,
+}
+
+/// A textual internal representation of a pipeline executable,
+/// like the generated shader source or the disassembly.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ExecutableRepresentation {
+    /// Short name of the representation.
+    pub name: String,
+    /// Description of the representation.
+    pub description: String,
+    /// The text of the representation.
+    pub text: String,
+}
+
+/// An executable compiled for a pipeline, covering one or more shader stages.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PipelineExecutable {
+    /// The shader stages the executable is compiled from.
+    pub stages: ShaderStageFlags,
+    /// Short name of the executable.
+    pub name: String,
+    /// Description of the executable.
+    pub description: String,
+    /// Statistics of the executable, captured if the pipeline is created with
+    /// [`PipelineCreationFlags::CAPTURE_STATISTICS`][crate::pso::PipelineCreationFlags::CAPTURE_STATISTICS].
+    pub statistics: Vec<ExecutableStatistic>,
+    /// Internal representations of the executable, captured if the pipeline is created with
+    /// [`PipelineCreationFlags::CAPTURE_INTERNAL_REPRESENTATIONS`][crate::pso::PipelineCreationFlags::CAPTURE_INTERNAL_REPRESENTATIONS].
+    pub representations: Vec<ExecutableRepresentation>,
+}
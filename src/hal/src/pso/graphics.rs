@@ -65,6 +65,59 @@ pub struct BakedStates {
     /// Static depth bounds.
     pub depth_bounds: Option<Range<f32>>,
 }
+
+bitflags!(
+    /// Pipeline states that are set with commands instead of taken from the pipeline description.
+    ///
+    /// Requires [`Features::EXTENDED_DYNAMIC_STATE`][crate::Features::EXTENDED_DYNAMIC_STATE].
+    #[derive(Default)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct DynamicStates: u32 {
+        /// The culled faces, set with `set_cull_face`.
+        const CULL_FACE = 0x1;
+        /// The front face winding, set with `set_front_face`.
+        const FRONT_FACE = 0x2;
+        /// The primitive topology, set with `set_primitive_topology`.
+        ///
+        /// The topology can only change to another one of the same class
+        /// (points, lines or triangles) as the description.
+        const PRIMITIVE_TOPOLOGY = 0x4;
+        /// The depth test, set with `set_depth_test`.
+        const DEPTH_TEST = 0x8;
+        /// The operations of the stencil faces, set with `set_stencil_faces`.
+        const STENCIL_FACES = 0x10;
+        /// The vertex buffers and attributes, set with `set_vertex_input`.
+        ///
+        /// The ones of the primitive assembler description are ignored.
+        /// Requires [`Features::VERTEX_INPUT_DYNAMIC_STATE`][crate::Features::VERTEX_INPUT_DYNAMIC_STATE].
+        const VERTEX_INPUT = 0x20;
+        /// Whether primitive restart is enabled, set with `set_primitive_restart`.
+        ///
+        /// The restart index of the input assembler description is only used
+        /// for its index type.
+        /// Requires [`Features::PRIMITIVE_RESTART_DYNAMIC_STATE`][crate::Features::PRIMITIVE_RESTART_DYNAMIC_STATE],
+        /// pipelines with this state are rejected by the backends that don't support it.
+        const PRIMITIVE_RESTART = 0x40;
+    }
+);
+
+/// Operation combining two fragment shading rates, applied to the width and height separately.
+///
+/// The result is clamped to the rates supported by the device.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ShadingRateCombinerOp {
+    /// Keep the first rate.
+    Keep,
+    /// Replace the first rate by the second one.
+    Replace,
+    /// Take the finest of the two rates.
+    Min,
+    /// Take the coarsest of the two rates.
+    Max,
+    /// Multiply the two rates.
+    Mul,
+}
 #[derive(Debug)]
 /// Primitive Assembler describes how input data are fetched in the pipeline and formed into primitives before being sent into the fragment shader.
 pub enum PrimitiveAssemblerDesc<'a, B: Backend> {
@@ -132,6 +185,8 @@ pub struct GraphicsPipelineDesc<'a, B: Backend> {
     pub multisampling: Option<Multisampling>,
     /// Static pipeline states.
     pub baked_states: BakedStates,
+    /// Pipeline states set with commands, ignoring the corresponding parts of the description.
+    pub dynamic_states: DynamicStates,
     /// Pipeline layout.
     pub layout: &'a B::PipelineLayout,
     /// Subpass in which the pipeline can be executed.
@@ -161,6 +216,7 @@ impl<'a, B: Backend> GraphicsPipelineDesc<'a, B> {
             depth_stencil: DepthStencilDesc::default(),
             multisampling: None,
             baked_states: BakedStates::default(),
+            dynamic_states: DynamicStates::empty(),
             layout,
             subpass,
             flags: PipelineCreationFlags::empty(),
@@ -169,6 +225,41 @@ impl<'a, B: Backend> GraphicsPipelineDesc<'a, B> {
     }
 }
 
+bitflags!(
+    /// Parts of a graphics pipeline that a pipeline library holds.
+    ///
+    /// Requires [`Features::GRAPHICS_PIPELINE_LIBRARY`][crate::Features::GRAPHICS_PIPELINE_LIBRARY].
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct GraphicsPipelineLibraryParts: u32 {
+        /// The vertex buffers, attributes and input assembler
+        /// of the primitive assembler description.
+        const VERTEX_INPUT_INTERFACE = 0x1;
+        /// The vertex shader and the rasterizer.
+        const PRE_RASTERIZATION_SHADERS = 0x2;
+        /// The fragment shader and the depth-stencil description.
+        const FRAGMENT_SHADER = 0x4;
+        /// The blender and the multisampling description.
+        const FRAGMENT_OUTPUT_INTERFACE = 0x8;
+    }
+);
+
+/// A description of a graphics pipeline linked from pipeline libraries,
+/// which together hold all the parts of the pipeline.
+#[derive(Debug)]
+pub struct GraphicsPipelineLinkDesc<'a, B: Backend> {
+    /// Pipeline label
+    pub label: Option<&'a str>,
+    /// The pipeline libraries to link, each holding different parts.
+    pub libraries: &'a [&'a B::GraphicsPipelineLibrary],
+    /// Pipeline layout, compatible with the ones of the libraries.
+    pub layout: &'a B::PipelineLayout,
+    /// Subpass in which the pipeline can be executed,
+    /// compatible with the ones of the libraries.
+    pub subpass: pass::Subpass<'a, B>,
+    /// Options that may be set to alter pipeline properties.
+    pub flags: PipelineCreationFlags,
+}
+
 /// Methods for rasterizing polygons, ie, turning the mesh
 /// into a raster image.
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
@@ -204,12 +295,110 @@ pub enum FrontFace {
 #[derive(Copy, Clone, Debug, Default, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct DepthBias {
-    /// A constant depth value added to each fragment.
+    /// A constant depth value added to each fragment,
+    /// scaled according to the `representation`.
     pub const_factor: f32,
     /// The minimum or maximum depth bias of a fragment.
+    ///
+    /// The bias isn't clamped when this is zero, is at most this value when positive,
+    /// and at least this value when negative. Any other value than zero
+    /// requires [`Features::DEPTH_BIAS_CLAMP`][crate::Features::DEPTH_BIAS_CLAMP].
     pub clamp: f32,
     /// A constant bias applied to the fragment's slope.
     pub slope_factor: f32,
+    /// How the constant factor is converted to a depth offset.
+    pub representation: DepthBiasRepresentation,
+    /// Whether the constant factor is scaled by the exact minimum resolvable difference,
+    /// instead of a value that the implementation can pick up to twice as large.
+    ///
+    /// Requires [`DepthBiasControlProperties::exact`][crate::DepthBiasControlProperties::exact].
+    pub exact: bool,
+}
+
+/// Conversion of the constant factor of a [`DepthBias`] to a depth offset.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DepthBiasRepresentation {
+    /// Scaled by the minimum resolvable difference of the depth format.
+    /// For floating point formats, it depends on the exponent of the largest
+    /// depth of the primitive. This is the only one available without
+    /// support for the other representations.
+    Format,
+    /// Scaled by the minimum resolvable difference of a normalized format
+    /// with as many bits as the mantissa of floating point depth formats,
+    /// regardless of the depth of the primitive.
+    ///
+    /// Requires [`DepthBiasControlProperties::force_unorm_representation`][crate::DepthBiasControlProperties::force_unorm_representation].
+    ForceUnorm,
+    /// Used as is, in depth units.
+    ///
+    /// Requires [`DepthBiasControlProperties::float_representation`][crate::DepthBiasControlProperties::float_representation].
+    Float,
+}
+
+impl Default for DepthBiasRepresentation {
+    fn default() -> Self {
+        DepthBiasRepresentation::Format
+    }
+}
+
+/// Algorithm used to rasterize lines.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum LineRasterizationMode {
+    /// Implementation-defined algorithm, which is the only one available
+    /// without support for the other modes.
+    Default,
+    /// Lines are rasterized as rectangles of the line width, aligned with the line.
+    ///
+    /// Requires [`LineRasterizationProperties::rectangular_lines`][crate::LineRasterizationProperties::rectangular_lines].
+    Rectangular,
+    /// Lines are rasterized with the diamond-exit rule, touching one pixel per major step.
+    ///
+    /// Requires [`LineRasterizationProperties::bresenham_lines`][crate::LineRasterizationProperties::bresenham_lines].
+    Bresenham,
+    /// Lines are rasterized as rectangles, with the coverage of the pixels
+    /// they partially cover being used to smooth the edges.
+    ///
+    /// Requires [`LineRasterizationProperties::smooth_lines`][crate::LineRasterizationProperties::smooth_lines].
+    RectangularSmooth,
+}
+
+impl Default for LineRasterizationMode {
+    fn default() -> Self {
+        LineRasterizationMode::Default
+    }
+}
+
+/// Vertex of a primitive that the flat-shaded attributes are taken from.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ProvokingVertex {
+    /// The first vertex, as in Vulkan and Direct3D.
+    First,
+    /// The last vertex, as in OpenGL.
+    ///
+    /// Requires [`Features::PROVOKING_VERTEX_LAST`][crate::Features::PROVOKING_VERTEX_LAST].
+    Last,
+}
+
+impl Default for ProvokingVertex {
+    fn default() -> Self {
+        ProvokingVertex::First
+    }
+}
+
+/// Stipple pattern of the rasterized lines.
+///
+/// Requires the stippled lines support of [`LineRasterizationProperties`][crate::LineRasterizationProperties]
+/// for the rasterization mode.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LineStipple {
+    /// Number of consecutive fragments using each bit of the pattern, in the range `[1, 256]`.
+    pub factor: u32,
+    /// Bits telling which fragments are kept, starting from the least significant one.
+    pub pattern: u16,
 }
 
 /// Rasterization state.
@@ -232,6 +421,12 @@ pub struct Rasterizer {
     pub conservative: bool,
     /// Controls width of rasterized line segments.
     pub line_width: State<f32>,
+    /// Algorithm used to rasterize line segments.
+    pub line_rasterization_mode: LineRasterizationMode,
+    /// Stipple pattern applied to line segments, if any.
+    pub line_stipple: Option<LineStipple>,
+    /// Vertex providing the flat-shaded attributes of the primitives.
+    pub provoking_vertex: ProvokingVertex,
 }
 
 impl Rasterizer {
@@ -244,6 +439,9 @@ impl Rasterizer {
         depth_bias: None,
         conservative: false,
         line_width: State::Static(1.0),
+        line_rasterization_mode: LineRasterizationMode::Default,
+        line_stipple: None,
+        provoking_vertex: ProvokingVertex::First,
     };
 }
 
@@ -283,6 +481,16 @@ pub enum LogicOp {
 ///
 pub type SampleMask = u64;
 
+/// Location of a sample within a pixel, with `(0.5, 0.5)` being the center of the pixel.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SampleLocation {
+    /// Horizontal position, in the range `[0, 1)`.
+    pub x: f32,
+    /// Vertical position, in the range `[0, 1)`.
+    pub y: f32,
+}
+
 ///
 #[derive(Clone, Debug, PartialEq)]
 pub struct Multisampling {
@@ -128,6 +128,18 @@ pub struct InputAssemblerDesc {
     /// allows a mark to be placed in an index buffer where it is
     /// is "broken" into multiple pieces of geometry.
     ///
+    /// When enabled, the mark is the largest value of the given index type,
+    /// `0xFFFF` for `U16` and `0xFFFF_FFFF` for `U32`, and the pipeline
+    /// must only be used with index buffers of that type. It only affects
+    /// indexed draws of strip and fan topologies.
+    ///
+    /// The behavior differs between backends when it is disabled:
+    /// - Vulkan, D3D12 and OpenGL treat the mark as a regular index.
+    /// - Metal, D3D11 and WebGL always restart strips at the mark,
+    ///   so it can't be disabled there.
+    ///
+    /// It can also be set with commands, see `DynamicStates::PRIMITIVE_RESTART`.
+    ///
     /// See <https://www.khronos.org/opengl/wiki/Vertex_Rendering#Primitive_Restart>
     /// for more detail.
     pub restart_index: Option<IndexType>,
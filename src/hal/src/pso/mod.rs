@@ -6,13 +6,16 @@ use crate::{device, pass, Backend};
 
 mod compute;
 mod descriptor;
+mod executable;
 mod graphics;
 mod input_assembler;
 mod output_merger;
+mod ray_tracing;
 mod specialization;
 
 pub use self::{
-    compute::*, descriptor::*, graphics::*, input_assembler::*, output_merger::*, specialization::*,
+    compute::*, descriptor::*, executable::*, graphics::*, input_assembler::*, output_merger::*,
+    ray_tracing::*, specialization::*,
 };
 
 /// Error types happening upon PSO creation on the device side.
@@ -79,10 +82,18 @@ bitflags!(
         /// Read/Write access from host.
         /// (Not a real pipeline stage)
         const HOST = 0x4000;
+        /// Stage in which the predicates of conditional rendering are read.
+        const CONDITIONAL_RENDERING = 0x40000;
         /// Task shader stage.
         const TASK_SHADER = 0x80000;
         /// Mesh shader stage.
         const MESH_SHADER = 0x100000;
+        /// Ray tracing shader execution.
+        const RAY_TRACING_SHADER = 0x200000;
+        /// Stage in which the fragment shading rate attachment is read.
+        const FRAGMENT_SHADING_RATE_ATTACHMENT = 0x400000;
+        /// Acceleration structure build command execution.
+        const ACCELERATION_STRUCTURE_BUILD = 0x2000000;
     }
 );
 
@@ -107,9 +118,24 @@ bitflags!(
         const TASK     = 0x40;
         /// Mesh shader stage.
         const MESH     = 0x80;
+        /// Ray generation shader stage.
+        const RAYGEN   = 0x100;
+        /// Any-hit shader stage.
+        const ANY_HIT  = 0x200;
+        /// Closest hit shader stage.
+        const CLOSEST_HIT = 0x400;
+        /// Miss shader stage.
+        const MISS     = 0x800;
+        /// Intersection shader stage.
+        const INTERSECTION = 0x1000;
+        /// Callable shader stage.
+        const CALLABLE = 0x2000;
         /// All graphics pipeline shader stages.
         const GRAPHICS = Self::VERTEX.bits | Self::HULL.bits |
             Self::DOMAIN.bits | Self::GEOMETRY.bits | Self::FRAGMENT.bits;
+        /// All ray tracing pipeline shader stages.
+        const RAY_TRACING = Self::RAYGEN.bits | Self::ANY_HIT.bits | Self::CLOSEST_HIT.bits |
+            Self::MISS.bits | Self::INTERSECTION.bits | Self::CALLABLE.bits;
         /// All shader stages (matches Vulkan).
         const ALL      = 0x7FFFFFFF;
     }
@@ -159,6 +185,23 @@ bitflags!(
         ///
         /// Must be set when pipelines set the pipeline as base.
         const ALLOW_DERIVATIVES = 0x2;
+        /// Order the accesses of fragment shaders to writable storage resources,
+        /// so that overlapping fragments access them in primitive order.
+        ///
+        /// Requires `Features::FRAGMENT_SHADER_INTERLOCK`.
+        const RASTER_ORDERED = 0x4;
+        /// Allow the compute pipeline to be dispatched with a base workgroup,
+        /// on backends that provide such a dispatch.
+        const DISPATCH_BASE = 0x10;
+        /// Capture the statistics of the pipeline executables.
+        ///
+        /// Requires `Features::PIPELINE_EXECUTABLE_INFO`.
+        const CAPTURE_STATISTICS = 0x20;
+        /// Capture the internal representations of the pipeline executables,
+        /// like the generated shader source.
+        ///
+        /// Requires `Features::PIPELINE_EXECUTABLE_INFO`.
+        const CAPTURE_INTERNAL_REPRESENTATIONS = 0x40;
     }
 );
 
@@ -0,0 +1,103 @@
+//! Ray tracing pipeline descriptor.
+
+use crate::{
+    buffer,
+    pso::{BasePipeline, EntryPoint, PipelineCreationFlags, ShaderStageFlags},
+    Backend,
+};
+
+/// A shader stage of a ray tracing pipeline.
+#[derive(Debug)]
+pub struct RayTracingShaderStage<'a, B: Backend> {
+    /// One of the ray tracing stages.
+    pub stage: ShaderStageFlags,
+    /// The shader entry point of the stage.
+    pub entry: EntryPoint<'a, B>,
+}
+
+/// A group of shaders, recorded together in the shader binding tables.
+///
+/// The shaders are indices into the stages of the pipeline.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ShaderGroup {
+    /// A ray generation, miss or callable shader.
+    General {
+        /// The shader of the group.
+        shader: u32,
+    },
+    /// A hit group for the triangle geometries.
+    TrianglesHitGroup {
+        /// The closest hit shader, if any.
+        closest_hit: Option<u32>,
+        /// The any-hit shader, if any.
+        any_hit: Option<u32>,
+    },
+    /// A hit group for the bounding box geometries.
+    ProceduralHitGroup {
+        /// The closest hit shader, if any.
+        closest_hit: Option<u32>,
+        /// The any-hit shader, if any.
+        any_hit: Option<u32>,
+        /// The intersection shader.
+        intersection: u32,
+    },
+}
+
+/// A description of the data needed to construct a ray tracing pipeline.
+#[derive(Debug)]
+pub struct RayTracingPipelineDesc<'a, B: Backend> {
+    /// Pipeline label
+    pub label: Option<&'a str>,
+    /// The shader stages of the pipeline.
+    pub stages: &'a [RayTracingShaderStage<'a, B>],
+    /// The shader groups, indexed by the shader binding tables.
+    pub groups: &'a [ShaderGroup],
+    /// Maximum depth of the rays traced from the closest hit and miss shaders,
+    /// up to `RayTracingProperties::max_ray_recursion_depth`.
+    pub max_recursion_depth: u32,
+    /// Pipeline layout.
+    pub layout: &'a B::PipelineLayout,
+    /// Any flags necessary for the pipeline creation.
+    pub flags: PipelineCreationFlags,
+    /// The parent pipeline to this one, if any.
+    pub parent: BasePipeline<'a, B::RayTracingPipeline>,
+}
+
+impl<'a, B: Backend> RayTracingPipelineDesc<'a, B> {
+    /// Create a new PSO descriptor, tracing primary rays only.
+    pub fn new(
+        stages: &'a [RayTracingShaderStage<'a, B>],
+        groups: &'a [ShaderGroup],
+        layout: &'a B::PipelineLayout,
+    ) -> Self {
+        RayTracingPipelineDesc {
+            label: None,
+            stages,
+            groups,
+            max_recursion_depth: 1,
+            layout,
+            flags: PipelineCreationFlags::empty(),
+            parent: BasePipeline::None,
+        }
+    }
+}
+
+/// A buffer region holding the records of a shader binding table.
+///
+/// Each record starts with the handle of a shader group, as returned by
+/// [`get_ray_tracing_shader_group_handles`][crate::device::Device::get_ray_tracing_shader_group_handles].
+#[derive(Debug)]
+pub struct ShaderBindingTable<'a, B: Backend> {
+    /// Buffer holding the records.
+    ///
+    /// Requires `buffer::Usage::SHADER_BINDING_TABLE`.
+    pub buffer: &'a B::Buffer,
+    /// Offset of the first record, aligned to `RayTracingProperties::shader_group_base_alignment`.
+    pub offset: buffer::Offset,
+    /// Byte stride between the records, aligned to
+    /// `RayTracingProperties::shader_group_handle_alignment`.
+    pub stride: buffer::Offset,
+    /// Size of the table.
+    pub size: buffer::Offset,
+}
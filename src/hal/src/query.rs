@@ -10,6 +10,19 @@ use crate::{device::OutOfMemory, Backend};
 /// A query identifier.
 pub type Id = u32;
 
+/// Index of a performance counter set, in the list returned by
+/// `PhysicalDevice::performance_counter_sets`.
+pub type CounterSetId = u32;
+
+/// A set of hardware performance counters that are sampled together.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CounterSet {
+    /// Name of the set.
+    pub name: String,
+    /// Names of the counters, in the order of their values in the query results.
+    pub counters: Vec<String>,
+}
+
 /// Query creation error.
 #[derive(Clone, Debug, PartialEq, thiserror::Error)]
 pub enum CreationError {
@@ -70,6 +83,12 @@ pub enum Type {
     /// Timestamp query. Timestamps can be recorded to the
     /// query pool by calling `write_timestamp()`.
     Timestamp,
+    /// Performance counter query. Samples the hardware counters of the given set
+    /// at the start and end of the query command, and reports their differences,
+    /// one value per counter of the set.
+    ///
+    /// The results can only be read with `Device::get_query_pool_results`.
+    PerformanceCounters(CounterSetId),
 }
 
 bitflags!(
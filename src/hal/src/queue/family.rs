@@ -19,6 +19,11 @@ pub trait QueueFamily: Debug + Any + Send + Sync {
     fn id(&self) -> QueueFamilyId;
     /// Returns true if the queue family supports sparse binding
     fn supports_sparse_binding(&self) -> bool;
+    /// Returns true if the queue family can create protected queues,
+    /// see [`PhysicalDevice::open_protected`][crate::adapter::PhysicalDevice::open_protected].
+    fn supports_protected(&self) -> bool {
+        false
+    }
 }
 
 /// Identifier for a queue family of a physical device.
@@ -35,6 +40,8 @@ pub struct QueueGroup<B: Backend> {
     pub family: QueueFamilyId,
     /// List of queues.
     pub queues: Vec<B::Queue>,
+    /// Whether the queues accept protected submissions.
+    pub protected: bool,
 }
 
 impl<B: Backend> QueueGroup<B> {
@@ -43,6 +50,15 @@ impl<B: Backend> QueueGroup<B> {
         QueueGroup {
             family,
             queues: Vec::new(),
+            protected: false,
+        }
+    }
+
+    /// Create a new, empty group of protected queues for a queue family.
+    pub fn new_protected(family: QueueFamilyId) -> Self {
+        QueueGroup {
+            protected: true,
+            ..Self::new(family)
         }
     }
 
@@ -9,9 +9,9 @@
 pub mod family;
 
 use crate::{
-    device::OutOfMemory,
+    device::{DeviceMask, OutOfMemory},
     pso,
-    window::{PresentError, PresentationSurface, Suboptimal},
+    window::{DeviceGroupPresentMode, PresentError, PresentationSurface, Suboptimal},
     Backend,
 };
 use std::{any::Any, fmt};
@@ -58,6 +58,18 @@ impl QueueType {
 /// `1.0` (high).
 pub type QueuePriority = f32;
 
+bitflags!(
+    /// Queue submission flags.
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct SubmitFlags: u32 {
+        /// Protected submission, which can access protected resources.
+        ///
+        /// Requires a [protected][QueueGroup::protected] queue, and command buffers
+        /// allocated from [protected pools][crate::pool::CommandPoolCreateFlags::PROTECTED].
+        const PROTECTED = 0x1;
+    }
+);
+
 /// Abstraction for an internal GPU execution engine.
 ///
 /// Commands are executed on the the device by submitting
@@ -68,9 +80,23 @@ pub type QueuePriority = f32;
 pub trait Queue<B: Backend>: fmt::Debug + Any + Send + Sync {
     /// Sparse memory bind operation.
     ///
+    /// Requires a queue family that [supports sparse binding][QueueFamily::supports_sparse_binding],
+    /// and [`Features::SPARSE_BINDING`][crate::Features::SPARSE_BINDING]. Binding parts of
+    /// resources created with [`SparseFlags::SPARSE_RESIDENCY`][crate::memory::SparseFlags::SPARSE_RESIDENCY]
+    /// also requires the `SPARSE_RESIDENCY_*` feature of their kind.
+    ///
     /// # Arguments
     ///
-    /// * `info` - information about the memory bindings.
+    /// * `wait_semaphores` - semaphores to wait being signalled before binding.
+    /// * `signal_semaphores` - semaphores to signal after the memory is bound.
+    /// * `buffer_memory_binds` - ranges of sparse buffers to bind memory to, or to unbind.
+    /// * `image_opaque_memory_binds` - opaque ranges of sparse images to bind memory to,
+    ///   which is the only way to bind the whole of images that aren't partially resident,
+    ///   and their [mip tails][crate::image::SparseRequirements].
+    /// * `image_memory_binds` - regions of subresources of partially resident images to bind
+    ///   memory to, aligned to the [granularity][crate::image::SparseRequirements::granularity].
+    /// * `device` - device that the resources and memory belong to.
+    /// * `fence` - must be in unsignaled state, and will be signaled after the memory is bound.
     ///
     /// # Safety
     ///
@@ -129,6 +155,57 @@ pub trait Queue<B: Backend>: fmt::Debug + Any + Send + Sync {
         Iw: Iterator<Item = (&'a B::Semaphore, pso::PipelineStage)>,
         Is: Iterator<Item = &'a B::Semaphore>;
 
+    /// Submit command buffers to queue for execution, with the given `flags`.
+    ///
+    /// With empty flags, this is the same as [`submit`][Queue::submit].
+    ///
+    /// # Safety
+    ///
+    /// Unsafe for the same reasons as [`submit`][Queue::submit].
+    /// Protected submissions require [protected memory][crate::ProtectedMemoryProperties] support.
+    unsafe fn submit_with_flags<'a, Ic, Iw, Is>(
+        &mut self,
+        command_buffers: Ic,
+        wait_semaphores: Iw,
+        signal_semaphores: Is,
+        fence: Option<&mut B::Fence>,
+        flags: SubmitFlags,
+    ) where
+        Ic: Iterator<Item = &'a B::CommandBuffer>,
+        Iw: Iterator<Item = (&'a B::Semaphore, pso::PipelineStage)>,
+        Is: Iterator<Item = &'a B::Semaphore>,
+    {
+        if flags.is_empty() {
+            self.submit(command_buffers, wait_semaphores, signal_semaphores, fence)
+        } else {
+            unimplemented!("Protected submissions are not supported")
+        }
+    }
+
+    /// Submit command buffers to queue for execution, synchronized with timeline semaphores.
+    ///
+    /// # Arguments
+    ///
+    /// * `command_buffers` - command buffers to submit.
+    /// * `wait_semaphores` - timeline semaphores to wait reaching the given values,
+    ///   before the given stage of the command buffers.
+    /// * `signal_semaphores` - timeline semaphores to set to the given values after
+    ///   all command buffers in the submission have finished execution.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe for the same reasons as [`submit`][Queue::submit].
+    /// Requires [`Features::TIMELINE_SEMAPHORE`][crate::Features::TIMELINE_SEMAPHORE].
+    unsafe fn submit_timeline<'a, Ic, Iw, Is>(
+        &mut self,
+        command_buffers: Ic,
+        wait_semaphores: Iw,
+        signal_semaphores: Is,
+    ) where
+        Ic: Iterator<Item = &'a B::CommandBuffer>,
+        Iw: Iterator<Item = (&'a B::TimelineSemaphore, u64, pso::PipelineStage)>,
+        Is: Iterator<Item = (&'a B::TimelineSemaphore, u64)>;
+
     /// Present a swapchain image directly to a surface, after waiting on `wait_semaphore`.
     ///
     /// # Safety
@@ -142,6 +219,96 @@ pub trait Queue<B: Backend>: fmt::Debug + Any + Send + Sync {
         wait_semaphore: Option<&mut B::Semaphore>,
     ) -> Result<Option<Suboptimal>, PresentError>;
 
+    /// Present swapchain images to multiple surfaces, each after waiting on its semaphore.
+    ///
+    /// The surfaces keep their own present modes and timing, and the results are returned
+    /// in the order of the `presentations`. Backends that can present to multiple
+    /// swapchains at once do it with a single operation.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe for the same reasons as [`present`][Queue::present].
+    unsafe fn present_multiple<'a, I>(
+        &mut self,
+        presentations: I,
+    ) -> Vec<Result<Option<Suboptimal>, PresentError>>
+    where
+        I: Iterator<
+            Item = (
+                &'a mut B::Surface,
+                <B::Surface as PresentationSurface<B>>::SwapchainImage,
+                Option<&'a mut B::Semaphore>,
+            ),
+        >,
+    {
+        presentations
+            .map(|(surface, image, wait_semaphore)| self.present(surface, image, wait_semaphore))
+            .collect()
+    }
+
+    /// Present a swapchain image like [`present`][Queue::present], and signal `fence`
+    /// once the presentation no longer uses `wait_semaphore` and the resources of the image,
+    /// so that they can be recycled.
+    ///
+    /// Requires [`SurfaceCapabilities::swapchain_maintenance`][crate::window::SurfaceCapabilities::swapchain_maintenance].
+    ///
+    /// # Safety
+    ///
+    /// Unsafe for the same reasons as [`present`][Queue::present].
+    unsafe fn present_with_fence(
+        &mut self,
+        surface: &mut B::Surface,
+        image: <B::Surface as PresentationSurface<B>>::SwapchainImage,
+        wait_semaphore: Option<&mut B::Semaphore>,
+        fence: &mut B::Fence,
+    ) -> Result<Option<Suboptimal>, PresentError> {
+        let _ = (surface, image, wait_semaphore, fence);
+        unimplemented!("Presentation fences are not supported")
+    }
+
+    /// Present a swapchain image like [`present`][Queue::present], identified by `present_id`,
+    /// which can then be waited for with
+    /// [`PresentationSurface::wait_for_present`][crate::window::PresentationSurface::wait_for_present].
+    ///
+    /// Identifiers must be greater than zero, and increase with each presentation to the surface.
+    ///
+    /// Requires [`SurfaceCapabilities::present_wait`][crate::window::SurfaceCapabilities::present_wait].
+    ///
+    /// # Safety
+    ///
+    /// Unsafe for the same reasons as [`present`][Queue::present].
+    unsafe fn present_with_id(
+        &mut self,
+        surface: &mut B::Surface,
+        image: <B::Surface as PresentationSurface<B>>::SwapchainImage,
+        wait_semaphore: Option<&mut B::Semaphore>,
+        present_id: u64,
+    ) -> Result<Option<Suboptimal>, PresentError> {
+        let _ = (surface, image, wait_semaphore, present_id);
+        unimplemented!("Presentation identifiers are not supported")
+    }
+
+    /// Present a swapchain image of a device group, rendered by the devices of `device_mask`
+    /// and combined according to `mode`, after waiting on `wait_semaphore`.
+    ///
+    /// Backends without multi-GPU support only accept the first device with the `LOCAL` mode.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe for the same reasons as [`present`][Queue::present].
+    unsafe fn present_group(
+        &mut self,
+        surface: &mut B::Surface,
+        image: <B::Surface as PresentationSurface<B>>::SwapchainImage,
+        wait_semaphore: Option<&mut B::Semaphore>,
+        device_mask: DeviceMask,
+        mode: DeviceGroupPresentMode,
+    ) -> Result<Option<Suboptimal>, PresentError> {
+        debug_assert_eq!(device_mask, 1);
+        debug_assert_eq!(mode, DeviceGroupPresentMode::LOCAL);
+        self.present(surface, image, wait_semaphore)
+    }
+
     /// Wait for the queue to be idle.
     fn wait_idle(&mut self) -> Result<(), OutOfMemory>;
 
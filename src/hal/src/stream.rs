@@ -0,0 +1,281 @@
+//! Streaming of dynamic data.
+//!
+//! A [`StreamBuffer`] is a ring buffer in CPU-visible memory, mapped once for its whole
+//! lifetime, into which dynamic vertex, index or uniform data is written every frame.
+//! The data written during a frame is kept untouched until the application
+//! [releases][StreamBuffer::release_frame] it, once the submissions reading it are complete,
+//! so that writing never has to wait for the device.
+//!
+//! Persistent mappings are cheap on all the backends: on GL, they are backed by
+//! `GL_ARB_buffer_storage`, instead of uploading the data again with `glBufferData`.
+
+use crate::{
+    adapter::MemoryType,
+    buffer,
+    device::{AllocationError, BindError, Device as _, MapError, OutOfMemory},
+    memory::{self, Requirements, Segment},
+    Backend, MemoryTypeId,
+};
+
+use std::{collections::VecDeque, ptr, slice};
+
+/// Error on creating a [`StreamBuffer`].
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum StreamBufferError {
+    /// Creating the buffer failed.
+    #[error(transparent)]
+    Creation(#[from] buffer::CreationError),
+    /// No CPU-visible memory type can hold the buffer.
+    #[error("No CPU-visible memory type is suitable for the buffer")]
+    NoMemoryType,
+    /// Allocating the memory failed.
+    #[error(transparent)]
+    Allocation(#[from] AllocationError),
+    /// Binding the memory failed.
+    #[error(transparent)]
+    Bind(#[from] BindError),
+    /// Mapping the memory failed.
+    #[error(transparent)]
+    Map(#[from] MapError),
+}
+
+/// Allocations of a ring, as positions increasing since its creation.
+#[derive(Debug)]
+struct Ring {
+    size: u64,
+    /// End of the last allocation.
+    head: u64,
+    /// Start of the data that may still be read by the device.
+    tail: u64,
+    /// Start of the allocations of the current frame.
+    frame_start: u64,
+    /// Ends of the frames that may still be read by the device, oldest first.
+    frames: VecDeque<u64>,
+}
+
+impl Ring {
+    fn new(size: u64) -> Self {
+        Ring {
+            size,
+            head: 0,
+            tail: 0,
+            frame_start: 0,
+            frames: VecDeque::new(),
+        }
+    }
+
+    /// Allocate `size` bytes, and return their offset in the buffer.
+    ///
+    /// Allocations don't wrap around the end of the buffer.
+    fn allocate(&mut self, size: u64, alignment: u64) -> Option<u64> {
+        assert!(alignment.is_power_of_two());
+        let base = self.head - self.head % self.size;
+        let mut offset = (self.head % self.size + alignment - 1) & !(alignment - 1);
+        let mut start = base + offset;
+        if offset + size > self.size {
+            start = base + self.size;
+            offset = 0;
+        }
+        if size > self.size || start + size > self.tail + self.size {
+            return None;
+        }
+        self.head = start + size;
+        Some(offset)
+    }
+
+    fn end_frame(&mut self) {
+        self.frames.push_back(self.head);
+        self.frame_start = self.head;
+    }
+
+    fn release_frame(&mut self) {
+        if let Some(end) = self.frames.pop_front() {
+            self.tail = end;
+        }
+    }
+}
+
+fn find_memory_type(
+    memory_types: &[MemoryType],
+    requirements: &Requirements,
+) -> Option<(MemoryTypeId, bool)> {
+    let allowed = |&(id, ty): &(usize, &MemoryType)| {
+        requirements.type_mask & (1 << id) != 0
+            && ty.properties.contains(memory::Properties::CPU_VISIBLE)
+    };
+    memory_types
+        .iter()
+        .enumerate()
+        .filter(allowed)
+        .find(|(_, ty)| ty.properties.contains(memory::Properties::COHERENT))
+        .or_else(|| memory_types.iter().enumerate().find(allowed))
+        .map(|(id, ty)| {
+            (
+                MemoryTypeId(id),
+                ty.properties.contains(memory::Properties::COHERENT),
+            )
+        })
+}
+
+/// Ring buffer for the data written by the host every frame, in persistently mapped memory.
+///
+/// Frames are delimited by [`end_frame`][StreamBuffer::end_frame], and released in the same
+/// order with [`release_frame`][StreamBuffer::release_frame], usually after waiting on
+/// the fence of the submission that read them.
+#[derive(Debug)]
+pub struct StreamBuffer<B: Backend> {
+    buffer: B::Buffer,
+    memory: B::Memory,
+    mapping: *mut u8,
+    coherent: bool,
+    ring: Ring,
+}
+
+// The mapping is only accessed through `&mut self`.
+unsafe impl<B: Backend> Send for StreamBuffer<B> {}
+unsafe impl<B: Backend> Sync for StreamBuffer<B> {}
+
+impl<B: Backend> StreamBuffer<B> {
+    /// Create a buffer of `size` bytes with the given `usage`, in memory allocated
+    /// from `memory_types`, and map it.
+    ///
+    /// Coherent memory is preferred, so that the data doesn't need to be flushed.
+    pub unsafe fn new(
+        device: &B::Device,
+        memory_types: &[MemoryType],
+        size: u64,
+        usage: buffer::Usage,
+    ) -> Result<Self, StreamBufferError> {
+        let mut buffer = device.create_buffer(size, usage, memory::SparseFlags::empty())?;
+        let requirements = device.get_buffer_requirements(&buffer);
+        let (memory_type, coherent) = match find_memory_type(memory_types, &requirements) {
+            Some(found) => found,
+            None => {
+                device.destroy_buffer(buffer);
+                return Err(StreamBufferError::NoMemoryType);
+            }
+        };
+        let mut memory = match device.allocate_memory(memory_type, requirements.size) {
+            Ok(memory) => memory,
+            Err(err) => {
+                device.destroy_buffer(buffer);
+                return Err(err.into());
+            }
+        };
+        let mapping = device
+            .bind_buffer_memory(&memory, 0, &mut buffer)
+            .map_err(StreamBufferError::from)
+            .and_then(|()| {
+                device
+                    .map_memory(&mut memory, Segment::ALL)
+                    .map_err(StreamBufferError::from)
+            });
+        match mapping {
+            Ok(mapping) => Ok(StreamBuffer {
+                buffer,
+                memory,
+                mapping,
+                coherent,
+                ring: Ring::new(size),
+            }),
+            Err(err) => {
+                device.destroy_buffer(buffer);
+                device.free_memory(memory);
+                Err(err)
+            }
+        }
+    }
+
+    /// Get the buffer, to bind the written data with the returned offsets.
+    pub fn buffer(&self) -> &B::Buffer {
+        &self.buffer
+    }
+
+    /// Get the number of frames that ended, and aren't released yet.
+    pub fn frames_in_flight(&self) -> usize {
+        self.ring.frames.len()
+    }
+
+    /// Allocate `size` bytes at an offset aligned to `alignment`, and return the offset
+    /// and the mapped bytes to write the data into.
+    ///
+    /// Returns `None` if the space is still used by frames that aren't released,
+    /// in which case the oldest frame should be released first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `alignment` isn't a power of two.
+    pub fn allocate(&mut self, size: u64, alignment: u64) -> Option<(buffer::Offset, &mut [u8])> {
+        let offset = self.ring.allocate(size, alignment)?;
+        let bytes =
+            unsafe { slice::from_raw_parts_mut(self.mapping.add(offset as usize), size as usize) };
+        Some((offset, bytes))
+    }
+
+    /// Write `data` at an offset aligned to `alignment`, and return the offset.
+    ///
+    /// See [`allocate`][StreamBuffer::allocate].
+    pub fn write(&mut self, data: &[u8], alignment: u64) -> Option<buffer::Offset> {
+        let (offset, bytes) = self.allocate(data.len() as u64, alignment)?;
+        unsafe {
+            ptr::copy_nonoverlapping(data.as_ptr(), bytes.as_mut_ptr(), data.len());
+        }
+        Some(offset)
+    }
+
+    /// End the current frame, making its data visible to the device.
+    ///
+    /// This must be called before submitting the commands reading the data of the frame.
+    pub unsafe fn end_frame(&mut self, device: &B::Device) -> Result<(), OutOfMemory> {
+        if !self.coherent && self.ring.head != self.ring.frame_start {
+            device.flush_mapped_memory_ranges(std::iter::once((&self.memory, Segment::ALL)))?;
+        }
+        self.ring.end_frame();
+        Ok(())
+    }
+
+    /// Release the oldest frame, once the device has finished reading its data,
+    /// so that its space can be allocated again.
+    pub fn release_frame(&mut self) {
+        self.ring.release_frame();
+    }
+
+    /// Unmap and destroy the buffer.
+    ///
+    /// The device must have finished reading the data of all the frames.
+    pub unsafe fn destroy(mut self, device: &B::Device) {
+        device.unmap_memory(&mut self.memory);
+        device.destroy_buffer(self.buffer);
+        device.free_memory(self.memory);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_alignment() {
+        let mut ring = Ring::new(64);
+        assert_eq!(ring.allocate(3, 1), Some(0));
+        assert_eq!(ring.allocate(4, 16), Some(16));
+        assert_eq!(ring.allocate(8, 4), Some(20));
+    }
+
+    #[test]
+    fn ring_wraps_after_release() {
+        let mut ring = Ring::new(64);
+        assert_eq!(ring.allocate(40, 1), Some(0));
+        ring.end_frame();
+        assert_eq!(ring.allocate(16, 1), Some(40));
+        ring.end_frame();
+        // The end of the buffer is too small, and the start is still used by the first frame.
+        assert_eq!(ring.allocate(16, 1), None);
+        ring.release_frame();
+        assert_eq!(ring.allocate(16, 1), Some(0));
+        assert_eq!(ring.allocate(40, 1), None);
+        ring.release_frame();
+        assert_eq!(ring.allocate(40, 1), Some(16));
+        assert_eq!(ring.allocate(65, 1), None);
+    }
+}
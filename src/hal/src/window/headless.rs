@@ -0,0 +1,386 @@
+//! Headless presentation.
+//!
+//! A [`HeadlessSurface`] provides the acquire and present steps of a swapchain without
+//! a window, so that CI rendering tests and server-side renderers go through
+//! the same presentation logic as the applications showing their frames on screen.
+//!
+//! The surface is backed by plain images, created with the [device][crate::device::Device]
+//! of any backend. Once the rendering of a presented image is finished, the
+//! [completion callback][HeadlessSurface::with_completion_callback] is called with it,
+//! and the image can be acquired again.
+
+use crate::{
+    adapter::MemoryType,
+    device::{AllocationError, BindError, Device as _, WaitError},
+    format, image,
+    memory::{self, Requirements},
+    pso,
+    queue::Queue as _,
+    window::{
+        AcquireError, OutOfDate, PresentError, Suboptimal, SwapImageIndex, SwapchainConfig,
+        SwapchainError,
+    },
+    Backend, MemoryTypeId,
+};
+
+use std::{borrow::Borrow, collections::VecDeque, fmt, iter, sync::Arc};
+
+/// Callback called with the index and the image of each presentation,
+/// once its rendering is finished.
+pub type CompletionCallback<B> =
+    Box<dyn FnMut(SwapImageIndex, &<B as Backend>::Image) + Send + Sync>;
+
+#[derive(Debug)]
+struct Frame<B: Backend> {
+    image: B::Image,
+    view: B::ImageView,
+}
+
+/// Image acquired from a [`HeadlessSurface`].
+#[derive(Debug)]
+pub struct HeadlessImage<B: Backend> {
+    index: SwapImageIndex,
+    frame: Arc<Frame<B>>,
+}
+
+impl<B: Backend> HeadlessImage<B> {
+    /// Get the index of the image in the swapchain.
+    pub fn index(&self) -> SwapImageIndex {
+        self.index
+    }
+}
+
+impl<B: Backend> Borrow<B::Image> for HeadlessImage<B> {
+    fn borrow(&self) -> &B::Image {
+        &self.frame.image
+    }
+}
+
+impl<B: Backend> Borrow<B::ImageView> for HeadlessImage<B> {
+    fn borrow(&self) -> &B::ImageView {
+        &self.frame.view
+    }
+}
+
+#[derive(Debug)]
+struct Swapchain<B: Backend> {
+    config: SwapchainConfig,
+    frames: Vec<Arc<Frame<B>>>,
+    memories: Vec<B::Memory>,
+    fences: Vec<B::Fence>,
+    available: VecDeque<SwapImageIndex>,
+    pending: VecDeque<SwapImageIndex>,
+}
+
+fn find_memory_type(
+    memory_types: &[MemoryType],
+    requirements: &Requirements,
+) -> Option<MemoryTypeId> {
+    let allowed = |&(id, _): &(usize, &MemoryType)| requirements.type_mask & (1 << id) != 0;
+    memory_types
+        .iter()
+        .enumerate()
+        .filter(allowed)
+        .find(|(_, ty)| ty.properties.contains(memory::Properties::DEVICE_LOCAL))
+        .or_else(|| memory_types.iter().enumerate().find(allowed))
+        .map(|(id, _)| MemoryTypeId(id))
+}
+
+fn map_wait_error(err: WaitError) -> AcquireError {
+    match err {
+        WaitError::OutOfMemory(err) => AcquireError::OutOfMemory(err),
+        WaitError::DeviceLost(err) => AcquireError::DeviceLost(err),
+    }
+}
+
+impl<B: Backend> Swapchain<B> {
+    unsafe fn add_frame(
+        &mut self,
+        device: &B::Device,
+        memory_types: &[MemoryType],
+    ) -> Result<(), SwapchainError> {
+        let config = &self.config;
+        let mut image = device
+            .create_image(
+                image::Kind::D2(
+                    config.extent.width,
+                    config.extent.height,
+                    config.image_layers,
+                    1,
+                ),
+                1,
+                config.format,
+                image::Tiling::Optimal,
+                config.image_usage,
+                memory::SparseFlags::empty(),
+                image::ViewCapabilities::empty(),
+            )
+            .map_err(|err| match err {
+                image::CreationError::OutOfMemory(err) => SwapchainError::OutOfMemory(err),
+                _ => SwapchainError::Unknown,
+            })?;
+
+        let requirements = device.get_image_requirements(&image);
+        let memory = match find_memory_type(memory_types, &requirements) {
+            Some(memory_type) => device.allocate_memory(memory_type, requirements.size),
+            None => {
+                device.destroy_image(image);
+                return Err(SwapchainError::Unknown);
+            }
+        };
+        let memory = match memory {
+            Ok(memory) => memory,
+            Err(err) => {
+                device.destroy_image(image);
+                return Err(match err {
+                    AllocationError::OutOfMemory(err) => SwapchainError::OutOfMemory(err),
+                    _ => SwapchainError::Unknown,
+                });
+            }
+        };
+        // The memory is kept with the other ones from now on, to be freed on failure.
+        self.memories.push(memory);
+        if let Err(err) = device.bind_image_memory(self.memories.last().unwrap(), 0, &mut image) {
+            device.destroy_image(image);
+            return Err(match err {
+                BindError::OutOfMemory(err) => SwapchainError::OutOfMemory(err),
+                _ => SwapchainError::Unknown,
+            });
+        }
+
+        let view_kind = if config.image_layers > 1 {
+            image::ViewKind::D2Array
+        } else {
+            image::ViewKind::D2
+        };
+        let view = match device.create_image_view(
+            &image,
+            view_kind,
+            config.format,
+            format::Swizzle::NO,
+            config.image_usage,
+            image::SubresourceRange {
+                aspects: format::Aspects::COLOR,
+                ..Default::default()
+            },
+        ) {
+            Ok(view) => view,
+            Err(err) => {
+                device.destroy_image(image);
+                return Err(match err {
+                    image::ViewCreationError::OutOfMemory(err) => SwapchainError::OutOfMemory(err),
+                    _ => SwapchainError::Unknown,
+                });
+            }
+        };
+        let fence = match device.create_fence(false) {
+            Ok(fence) => fence,
+            Err(err) => {
+                device.destroy_image_view(view);
+                device.destroy_image(image);
+                return Err(err.into());
+            }
+        };
+
+        let index = self.frames.len() as SwapImageIndex;
+        self.available.push_back(index);
+        self.frames.push(Arc::new(Frame { image, view }));
+        self.fences.push(fence);
+        Ok(())
+    }
+
+    /// Wait for the oldest pending presentation to be finished, and make its image available.
+    ///
+    /// Returns `false` if there is no pending presentation, or on timeout.
+    unsafe fn complete(
+        &mut self,
+        device: &B::Device,
+        callback: &mut Option<CompletionCallback<B>>,
+        timeout_ns: u64,
+    ) -> Result<bool, WaitError> {
+        let index = match self.pending.front() {
+            Some(&index) => index,
+            None => return Ok(false),
+        };
+        let fence = &mut self.fences[index as usize];
+        if !device.wait_for_fence(fence, timeout_ns)? {
+            return Ok(false);
+        }
+        device.reset_fence(fence)?;
+        self.pending.pop_front();
+        if let Some(ref mut callback) = *callback {
+            callback(index, &self.frames[index as usize].image);
+        }
+        self.available.push_back(index);
+        Ok(true)
+    }
+
+    unsafe fn destroy(self, device: &B::Device) {
+        for frame in self.frames {
+            let frame = Arc::try_unwrap(frame)
+                .ok()
+                .expect("Acquired headless images must be dropped before unconfiguring");
+            device.destroy_image_view(frame.view);
+            device.destroy_image(frame.image);
+        }
+        for memory in self.memories {
+            device.free_memory(memory);
+        }
+        for fence in self.fences {
+            device.destroy_fence(fence);
+        }
+    }
+}
+
+/// Surface presenting to plain images, instead of a window.
+pub struct HeadlessSurface<B: Backend> {
+    swapchain: Option<Swapchain<B>>,
+    callback: Option<CompletionCallback<B>>,
+}
+
+impl<B: Backend> fmt::Debug for HeadlessSurface<B> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("HeadlessSurface")
+            .field("swapchain", &self.swapchain)
+            .finish()
+    }
+}
+
+impl<B: Backend> Default for HeadlessSurface<B> {
+    fn default() -> Self {
+        HeadlessSurface::new()
+    }
+}
+
+impl<B: Backend> HeadlessSurface<B> {
+    /// Create a new surface, without a swapchain.
+    pub fn new() -> Self {
+        HeadlessSurface {
+            swapchain: None,
+            callback: None,
+        }
+    }
+
+    /// Specify the callback to call with the presented images, once their rendering is finished.
+    ///
+    /// The callback is called by [`acquire_image`][HeadlessSurface::acquire_image] and
+    /// [`unconfigure_swapchain`][HeadlessSurface::unconfigure_swapchain], before the image
+    /// can be acquired again.
+    pub fn with_completion_callback(mut self, callback: CompletionCallback<B>) -> Self {
+        self.callback = Some(callback);
+        self
+    }
+
+    /// Get the configuration of the current swapchain, if any.
+    pub fn swapchain_config(&self) -> Option<&SwapchainConfig> {
+        self.swapchain.as_ref().map(|swapchain| &swapchain.config)
+    }
+
+    /// Set up the swapchain of the surface, with images allocated from `memory_types`.
+    ///
+    /// The images are 2D color images, with the format, extent, layers and usage of `config`.
+    /// Any previous swapchain is unconfigured first.
+    pub unsafe fn configure_swapchain(
+        &mut self,
+        device: &B::Device,
+        memory_types: &[MemoryType],
+        config: SwapchainConfig,
+    ) -> Result<(), SwapchainError> {
+        self.unconfigure_swapchain(device);
+
+        let image_count = config.image_count;
+        let mut swapchain = Swapchain {
+            config,
+            frames: Vec::new(),
+            memories: Vec::new(),
+            fences: Vec::new(),
+            available: VecDeque::new(),
+            pending: VecDeque::new(),
+        };
+        for _ in 0..image_count {
+            if let Err(err) = swapchain.add_frame(device, memory_types) {
+                swapchain.destroy(device);
+                return Err(err);
+            }
+        }
+        self.swapchain = Some(swapchain);
+        Ok(())
+    }
+
+    /// Remove the swapchain from this surface, after waiting for the pending presentations.
+    ///
+    /// All the acquired images must be dropped first.
+    pub unsafe fn unconfigure_swapchain(&mut self, device: &B::Device) {
+        if let Some(mut swapchain) = self.swapchain.take() {
+            while let Ok(true) = swapchain.complete(device, &mut self.callback, !0) {}
+            swapchain.destroy(device);
+        }
+    }
+
+    /// Acquire an image for rendering, waiting up to `timeout_ns` for a pending
+    /// presentation to finish if none is available.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no swapchain configured.
+    pub unsafe fn acquire_image(
+        &mut self,
+        device: &B::Device,
+        timeout_ns: u64,
+    ) -> Result<(HeadlessImage<B>, Option<Suboptimal>), AcquireError> {
+        let swapchain = self.swapchain.as_mut().expect("No swapchain is configured");
+        while swapchain
+            .complete(device, &mut self.callback, 0)
+            .map_err(map_wait_error)?
+        {}
+        if swapchain.available.is_empty()
+            && !swapchain
+                .complete(device, &mut self.callback, timeout_ns)
+                .map_err(map_wait_error)?
+        {
+            return Err(AcquireError::NotReady {
+                timeout: timeout_ns != 0,
+            });
+        }
+
+        let index = swapchain.available.pop_front().unwrap();
+        let image = HeadlessImage {
+            index,
+            frame: Arc::clone(&swapchain.frames[index as usize]),
+        };
+        Ok((image, None))
+    }
+
+    /// Present an acquired image on `queue`, once `wait_semaphore` is signalled.
+    ///
+    /// Returns `OutOfDate` if the image was acquired from a previous swapchain.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no swapchain configured.
+    pub unsafe fn present(
+        &mut self,
+        queue: &mut B::Queue,
+        image: HeadlessImage<B>,
+        wait_semaphore: Option<&mut B::Semaphore>,
+    ) -> Result<Option<Suboptimal>, PresentError> {
+        let swapchain = self.swapchain.as_mut().expect("No swapchain is configured");
+        let index = image.index as usize;
+        match swapchain.frames.get(index) {
+            Some(frame) if Arc::ptr_eq(frame, &image.frame) => {}
+            _ => return Err(OutOfDate.into()),
+        }
+
+        queue.submit(
+            iter::empty(),
+            wait_semaphore
+                .map(|semaphore| (&*semaphore, pso::PipelineStage::BOTTOM_OF_PIPE))
+                .into_iter(),
+            iter::empty(),
+            Some(&mut swapchain.fences[index]),
+        );
+        swapchain.pending.push_back(image.index);
+        Ok(None)
+    }
+}
@@ -0,0 +1,222 @@
+//! Swapchain recreation helper.
+//!
+//! A [`ManagedSwapchain`] owns a surface and the configuration of its swapchain.
+//! When the swapchain goes out of date, or becomes suboptimal, it's configured again
+//! with the same settings and the current extent of the surface, and acquisition is retried,
+//! without the application having to handle it.
+
+use crate::{
+    queue::Queue as _,
+    window::{
+        AcquireError, Extent2D, OutOfDate, PresentError, PresentationSurface, Suboptimal,
+        Surface as _, SwapchainConfig, SwapchainError,
+    },
+    Backend,
+};
+
+/// Error on acquiring an image from a [`ManagedSwapchain`].
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum ManagedAcquireError {
+    /// Acquiring the image failed.
+    #[error(transparent)]
+    Acquire(#[from] AcquireError),
+    /// Recreating the swapchain failed.
+    #[error(transparent)]
+    Swapchain(#[from] SwapchainError),
+}
+
+/// Swapchain image of the surface of a backend.
+pub type SwapchainImage<B> = <<B as Backend>::Surface as PresentationSurface<B>>::SwapchainImage;
+
+/// Surface whose swapchain is recreated automatically when needed.
+#[derive(Debug)]
+pub struct ManagedSwapchain<B: Backend> {
+    surface: B::Surface,
+    config: SwapchainConfig,
+    configured: bool,
+    outdated: bool,
+    frame_count: u64,
+}
+
+impl<B: Backend> ManagedSwapchain<B> {
+    /// Wrap a surface, whose swapchain will be configured with `config` on the first acquisition.
+    ///
+    /// The extent of `config` is used on the surfaces without a current extent.
+    pub fn new(surface: B::Surface, config: SwapchainConfig) -> Self {
+        ManagedSwapchain {
+            surface,
+            config,
+            configured: false,
+            outdated: true,
+            frame_count: 0,
+        }
+    }
+
+    /// Get the wrapped surface.
+    pub fn surface(&self) -> &B::Surface {
+        &self.surface
+    }
+
+    /// Get the configuration of the swapchain.
+    ///
+    /// Its extent is the one of the last recreation.
+    pub fn config(&self) -> &SwapchainConfig {
+        &self.config
+    }
+
+    /// Get the number of images presented to this surface.
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    /// Notify the swapchain that the window was resized to `extent`,
+    /// so that it's recreated on the next acquisition.
+    pub fn resize(&mut self, extent: Extent2D) {
+        self.config.extent = extent;
+        self.outdated = true;
+    }
+
+    /// Configure the swapchain again, with the current capabilities of the surface.
+    ///
+    /// Nothing is done while the surface has a zero extent, such as a minimized window.
+    ///
+    /// This is done automatically by [`acquire_image`][ManagedSwapchain::acquire_image],
+    /// and doesn't need to be called by the application.
+    pub unsafe fn recreate(
+        &mut self,
+        physical_device: &B::PhysicalDevice,
+        device: &B::Device,
+    ) -> Result<(), SwapchainError> {
+        let caps = self.surface.capabilities(physical_device);
+        self.config.extent = caps.clamped_extent(self.config.extent);
+        self.config.image_count = self
+            .config
+            .image_count
+            .max(*caps.image_count.start())
+            .min(*caps.image_count.end());
+        if self.config.extent.width == 0 || self.config.extent.height == 0 {
+            return Ok(());
+        }
+
+        self.surface
+            .configure_swapchain(device, self.config.clone())?;
+        self.configured = true;
+        self.outdated = false;
+        Ok(())
+    }
+
+    /// Acquire a swapchain image for rendering, recreating the swapchain as needed.
+    ///
+    /// Returns `OutOfDate` when the surface has a zero extent, such as a minimized window,
+    /// in which case the frame should be skipped.
+    pub unsafe fn acquire_image(
+        &mut self,
+        physical_device: &B::PhysicalDevice,
+        device: &B::Device,
+        timeout_ns: u64,
+    ) -> Result<SwapchainImage<B>, ManagedAcquireError> {
+        // The swapchain is recreated at most once, to not loop on a surface
+        // that keeps going out of date.
+        let mut recreated = false;
+        loop {
+            if self.outdated {
+                self.recreate(physical_device, device)?;
+                if self.outdated {
+                    return Err(AcquireError::from(OutOfDate).into());
+                }
+                recreated = true;
+            }
+
+            match self.surface.acquire_image(timeout_ns) {
+                Ok((image, suboptimal)) => {
+                    // The image is still usable, so the recreation waits for the next frame.
+                    if suboptimal.is_some() {
+                        self.outdated = true;
+                    }
+                    return Ok(image);
+                }
+                Err(AcquireError::OutOfDate(_)) if !recreated => {
+                    self.outdated = true;
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    /// Present a swapchain image on `queue`, after waiting on `wait_semaphore`.
+    ///
+    /// Out of date and suboptimal presentations are not errors, and recreate
+    /// the swapchain on the next acquisition.
+    pub unsafe fn present(
+        &mut self,
+        queue: &mut B::Queue,
+        image: SwapchainImage<B>,
+        wait_semaphore: Option<&mut B::Semaphore>,
+    ) -> Result<(), PresentError> {
+        let result = queue.present(&mut self.surface, image, wait_semaphore);
+        self.handle_present(result)
+    }
+
+    /// Present swapchain images to multiple managed surfaces on `queue`,
+    /// each after waiting on its semaphore.
+    ///
+    /// The results are returned in the order of the `presentations`.
+    pub unsafe fn present_multiple<'a, I>(
+        queue: &mut B::Queue,
+        presentations: I,
+    ) -> Vec<Result<(), PresentError>>
+    where
+        I: IntoIterator<
+            Item = (
+                &'a mut Self,
+                SwapchainImage<B>,
+                Option<&'a mut B::Semaphore>,
+            ),
+        >,
+    {
+        let mut swapchains = Vec::new();
+        let mut images = Vec::new();
+        for (swapchain, image, wait_semaphore) in presentations {
+            swapchains.push(swapchain);
+            images.push((image, wait_semaphore));
+        }
+        let results = queue.present_multiple(swapchains.iter_mut().zip(images).map(
+            |(swapchain, (image, wait_semaphore))| (&mut swapchain.surface, image, wait_semaphore),
+        ));
+        swapchains
+            .into_iter()
+            .zip(results)
+            .map(|(swapchain, result)| swapchain.handle_present(result))
+            .collect()
+    }
+
+    fn handle_present(
+        &mut self,
+        result: Result<Option<Suboptimal>, PresentError>,
+    ) -> Result<(), PresentError> {
+        match result {
+            Ok(None) => {
+                self.frame_count += 1;
+                Ok(())
+            }
+            Ok(Some(_)) => {
+                self.frame_count += 1;
+                self.outdated = true;
+                Ok(())
+            }
+            Err(PresentError::OutOfDate(_)) => {
+                self.outdated = true;
+                Ok(())
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Remove the swapchain from the surface, and return the surface.
+    pub unsafe fn into_surface(mut self, device: &B::Device) -> B::Surface {
+        if self.configured {
+            self.surface.unconfigure_swapchain(device);
+        }
+        self.surface
+    }
+}
@@ -52,7 +52,28 @@
 //!
 //! ### Recreation
 //!
-//! DOC TODO
+//! The swapchain has to be configured again when acquiring or presenting reports
+//! that it's out of date, and should be when it's suboptimal, for example after
+//! the window was resized. A [managed swapchain][managed::ManagedSwapchain]
+//! does this automatically.
+//!
+//! ### Multiple surfaces
+//!
+//! A device can present to any number of surfaces, each with its own swapchain
+//! configuration and present mode. The images of several surfaces can be presented
+//! together with [`Queue::present_multiple`][crate::queue::Queue::present_multiple].
+//!
+//! Swapchain images belong to a single surface. To show the same content in several
+//! windows, render it once into a regular image, and copy it into the image acquired
+//! from each surface.
+//!
+//! ## Headless
+//!
+//! Frames can be rendered without a window, with the acquire and present steps
+//! of a [headless surface][headless::HeadlessSurface].
+
+pub mod headless;
+pub mod managed;
 
 use crate::{device, format::Format, image, Backend};
 
@@ -199,6 +220,22 @@ pub struct SurfaceCapabilities {
 
     /// A bitmask of supported alpha composition modes.
     pub composite_alpha_modes: CompositeAlphaMode,
+
+    /// Transform of the surface relative to the natural orientation of the display.
+    ///
+    /// Must be one of the `transforms`.
+    pub current_transform: SurfaceTransform,
+
+    /// A bitmask of supported transforms of the presented images.
+    pub transforms: SurfaceTransform,
+
+    /// Support signaling fences from presentation, with `Queue::present_with_fence`,
+    /// and releasing acquired images, with `PresentationSurface::release_images`.
+    pub swapchain_maintenance: bool,
+
+    /// Support identifying presentations, with `Queue::present_with_id`,
+    /// and waiting for them to be displayed, with `PresentationSurface::wait_for_present`.
+    pub present_wait: bool,
 }
 
 impl SurfaceCapabilities {
@@ -272,6 +309,34 @@ pub trait PresentationSurface<B: Backend>: Surface<B> {
         &mut self,
         timeout_ns: u64,
     ) -> Result<(Self::SwapchainImage, Option<Suboptimal>), AcquireError>;
+
+    /// Release acquired images that won't be presented, so that they can be acquired again.
+    ///
+    /// The images must not be in use by any submitted command buffer.
+    ///
+    /// Requires [`SurfaceCapabilities::swapchain_maintenance`].
+    unsafe fn release_images<I>(&mut self, images: I)
+    where
+        I: Iterator<Item = Self::SwapchainImage>,
+    {
+        let _ = images;
+        unimplemented!("Releasing swapchain images is not supported")
+    }
+
+    /// Wait until the presentation identified by `present_id`, or a later one,
+    /// is displayed, or until `timeout_ns` expires.
+    ///
+    /// Returns `true` if the presentation was displayed, and `false` on timeout.
+    ///
+    /// Requires [`SurfaceCapabilities::present_wait`].
+    unsafe fn wait_for_present(
+        &self,
+        present_id: u64,
+        timeout_ns: u64,
+    ) -> Result<bool, PresentError> {
+        let _ = (present_id, timeout_ns);
+        unimplemented!("Waiting for presentations is not supported")
+    }
 }
 
 /// Index of an image in the swapchain.
@@ -297,6 +362,49 @@ bitflags!(
     }
 );
 
+bitflags!(
+    /// Specifies how the images rendered by the devices of a group are presented.
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct DeviceGroupPresentMode: u32 {
+        /// Present the image of a single device, rendered by that device.
+        /// Suited for alternate frame rendering.
+        const LOCAL = 0x1;
+        /// Present the image of a device, rendered by another one.
+        const REMOTE = 0x2;
+        /// Present the sum of the images of several devices.
+        const SUM = 0x4;
+        /// Present the image of each device to the regions of the surface
+        /// it rendered. Suited for split frame rendering.
+        const LOCAL_MULTI_DEVICE = 0x8;
+    }
+);
+
+bitflags!(
+    /// Specifies how the presented images are transformed relative to the
+    /// natural orientation of the display.
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct SurfaceTransform: u32 {
+        /// The images are presented without a transform.
+        const IDENTITY = 0x1;
+        /// The images are rotated 90 degrees clockwise.
+        const ROTATE_90 = 0x2;
+        /// The images are rotated 180 degrees.
+        const ROTATE_180 = 0x4;
+        /// The images are rotated 270 degrees clockwise.
+        const ROTATE_270 = 0x8;
+        /// The images are mirrored horizontally.
+        const HORIZONTAL_MIRROR = 0x10;
+        /// The images are mirrored horizontally, then rotated 90 degrees clockwise.
+        const HORIZONTAL_MIRROR_ROTATE_90 = 0x20;
+        /// The images are mirrored horizontally, then rotated 180 degrees.
+        const HORIZONTAL_MIRROR_ROTATE_180 = 0x40;
+        /// The images are mirrored horizontally, then rotated 270 degrees clockwise.
+        const HORIZONTAL_MIRROR_ROTATE_270 = 0x80;
+        /// The transform is unknown to gfx-hal, and set with native window system commands.
+        const INHERIT = 0x100;
+    }
+);
+
 bitflags!(
     /// Specifies how the alpha channel of the images should be handled during
     /// compositing.
@@ -0,0 +1,150 @@
+//! Tests of the swapchain recreation of `ManagedSwapchain`, with the surface of the empty backend.
+
+use gfx_backend_empty as empty;
+use gfx_hal::{
+    format::Format,
+    window::{
+        managed::{ManagedAcquireError, ManagedSwapchain},
+        AcquireError, Extent2D, OutOfDate, PresentError, SwapchainConfig,
+    },
+};
+
+const EXTENT: Extent2D = Extent2D {
+    width: 640,
+    height: 480,
+};
+
+fn swapchain(surface: empty::Surface) -> ManagedSwapchain<empty::Backend> {
+    let config = SwapchainConfig::new(EXTENT.width, EXTENT.height, Format::Bgra8Srgb, 3);
+    ManagedSwapchain::new(surface, config)
+}
+
+fn out_of_date() -> ManagedAcquireError {
+    ManagedAcquireError::Acquire(AcquireError::OutOfDate(OutOfDate))
+}
+
+#[test]
+fn configure_on_first_acquisition() {
+    let mut swapchain = swapchain(empty::Surface::default());
+    assert!(swapchain.surface().configurations.is_empty());
+
+    unsafe {
+        swapchain
+            .acquire_image(&empty::PhysicalDevice, &empty::Device, !0)
+            .unwrap();
+        swapchain
+            .acquire_image(&empty::PhysicalDevice, &empty::Device, !0)
+            .unwrap();
+    }
+    let configurations = &swapchain.surface().configurations;
+    assert_eq!(configurations.len(), 1);
+    assert_eq!(configurations[0].extent, EXTENT);
+    // The image count is clamped to the capabilities of the surface.
+    assert_eq!(configurations[0].image_count, 1);
+}
+
+#[test]
+fn recreate_when_out_of_date() {
+    let mut surface = empty::Surface::default();
+    surface.current_extent = Some(EXTENT);
+    // The first acquisition configures the swapchain without getting an image,
+    // then the swapchain goes out of date.
+    surface.acquire_errors = vec![
+        AcquireError::NotReady { timeout: false },
+        AcquireError::OutOfDate(OutOfDate),
+    ]
+    .into();
+    let mut swapchain = swapchain(surface);
+
+    unsafe {
+        assert_eq!(
+            swapchain
+                .acquire_image(&empty::PhysicalDevice, &empty::Device, 0)
+                .unwrap_err(),
+            ManagedAcquireError::Acquire(AcquireError::NotReady { timeout: false })
+        );
+        swapchain
+            .acquire_image(&empty::PhysicalDevice, &empty::Device, !0)
+            .unwrap();
+    }
+    assert_eq!(swapchain.surface().configurations.len(), 2);
+    assert!(swapchain.surface().acquire_errors.is_empty());
+}
+
+#[test]
+fn recreate_only_once_per_acquisition() {
+    let mut surface = empty::Surface::default();
+    surface.acquire_errors = vec![
+        AcquireError::OutOfDate(OutOfDate),
+        AcquireError::OutOfDate(OutOfDate),
+    ]
+    .into();
+    let mut swapchain = swapchain(surface);
+
+    unsafe {
+        // Configured on the first acquisition, then out of date right after.
+        assert_eq!(
+            swapchain
+                .acquire_image(&empty::PhysicalDevice, &empty::Device, !0)
+                .unwrap_err(),
+            out_of_date()
+        );
+        assert_eq!(swapchain.surface().configurations.len(), 1);
+        // Recreated on the next acquisition, which succeeds.
+        swapchain
+            .acquire_image(&empty::PhysicalDevice, &empty::Device, !0)
+            .unwrap();
+    }
+    assert_eq!(swapchain.surface().configurations.len(), 2);
+}
+
+#[test]
+fn skip_minimized_window() {
+    let mut swapchain = swapchain(empty::Surface::default());
+    swapchain.resize(Extent2D {
+        width: 0,
+        height: 0,
+    });
+
+    unsafe {
+        assert_eq!(
+            swapchain
+                .acquire_image(&empty::PhysicalDevice, &empty::Device, !0)
+                .unwrap_err(),
+            out_of_date()
+        );
+        assert!(swapchain.surface().configurations.is_empty());
+
+        swapchain.resize(EXTENT);
+        swapchain
+            .acquire_image(&empty::PhysicalDevice, &empty::Device, !0)
+            .unwrap();
+    }
+    let configurations = &swapchain.surface().configurations;
+    assert_eq!(configurations.len(), 1);
+    assert_eq!(configurations[0].extent, EXTENT);
+}
+
+#[test]
+fn recreate_after_out_of_date_presentation() {
+    let mut surface = empty::Surface::default();
+    surface.present_errors = vec![PresentError::OutOfDate(OutOfDate)].into();
+    let mut swapchain = swapchain(surface);
+    let mut queue = empty::Queue;
+
+    unsafe {
+        let image = swapchain
+            .acquire_image(&empty::PhysicalDevice, &empty::Device, !0)
+            .unwrap();
+        // Out of date presentations are not errors, but aren't counted as frames.
+        swapchain.present(&mut queue, image, None).unwrap();
+        assert_eq!(swapchain.frame_count(), 0);
+
+        let image = swapchain
+            .acquire_image(&empty::PhysicalDevice, &empty::Device, !0)
+            .unwrap();
+        swapchain.present(&mut queue, image, None).unwrap();
+        assert_eq!(swapchain.frame_count(), 1);
+    }
+    assert_eq!(swapchain.surface().configurations.len(), 2);
+}
@@ -659,6 +659,8 @@ impl<B: hal::Backend> Scene<B> {
                         inputs: &t.2,
                         preserves: &t.3,
                         resolves: &t.4,
+                        depth_stencil_resolve: None,
+                        shading_rate: None,
                     });
                     let raw_deps = dependencies.iter().map(|dep| hal::pass::SubpassDependency {
                         passes: subpass_ref(&dep.passes.start)..subpass_ref(&dep.passes.end),
@@ -941,7 +943,8 @@ impl<B: hal::Backend> Scene<B> {
                         blender: blender.clone(),
                         depth_stencil: depth_stencil.clone(),
                         baked_states: pso::BakedStates::default(), //TODO
-                        multisampling: None,                       // TODO
+                        dynamic_states: pso::DynamicStates::empty(),
+                        multisampling: None, // TODO
                         layout: &resources.pipeline_layouts[layout],
                         subpass: hal::pass::Subpass {
                             main_pass: &resources